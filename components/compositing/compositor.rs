@@ -6,7 +6,7 @@ use compositor_data::{CompositorData, DoesntWantScrollEvents, WantsScrollEvents}
 use compositor_task::{Msg, CompositorTask, Exit, ChangeReadyState, SetIds, LayerProperties};
 use compositor_task::{GetGraphicsMetadata, CreateOrUpdateRootLayer, CreateOrUpdateDescendantLayer};
 use compositor_task::{SetLayerOrigin, Paint, ScrollFragmentPoint, LoadComplete};
-use compositor_task::{ShutdownComplete, ChangeRenderState, RenderMsgDiscarded};
+use compositor_task::{ShutdownComplete, ChangeRenderState, RenderMsgDiscarded, SetCursor};
 use constellation::SendableFrameTree;
 use events;
 use events::ScrollPositionChanged;
@@ -26,9 +26,10 @@ use std::num::Zero;
 use std::time::duration::Duration;
 use geom::point::{Point2D, TypedPoint2D};
 use geom::rect::{Rect, TypedRect};
-use geom::size::TypedSize2D;
+use geom::size::{Size2D, TypedSize2D};
 use geom::scale_factor::ScaleFactor;
-use gfx::render_task::{RenderChan, RenderMsg, RenderRequest, UnusedBufferMsg};
+use gfx::render_task::{PrintMsg, PrintedFrame, RenderChan, RenderMsg, RenderRequest};
+use gfx::render_task::UnusedBufferMsg;
 use layers::geometry::{DevicePixel, LayerPixel};
 use layers::layers::{BufferRequest, Layer, LayerBufferSet};
 use layers::rendergl;
@@ -41,7 +42,7 @@ use servo_msg::compositor_msg::{ReadyState, RenderingRenderState, RenderState, S
 use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, LoadUrlMsg, NavigateMsg};
 use servo_msg::constellation_msg::{PipelineId, ResizedWindowMsg, WindowSizeData};
 use servo_msg::constellation_msg;
-use servo_util::geometry::{PagePx, ScreenPx, ViewportPx};
+use servo_util::geometry::{Au, PagePx, ScreenPx, ViewportPx};
 use servo_util::memory::MemoryProfilerChan;
 use servo_util::opts::Opts;
 use servo_util::time::{profile, TimeProfilerChan};
@@ -343,6 +344,10 @@ impl IOCompositor {
                     self.got_load_complete_message = true;
                 }
 
+                (Ok(SetCursor(cursor)), NotShuttingDown) => {
+                    self.window.set_cursor(cursor);
+                }
+
                 // When we are shutting_down, we need to avoid performing operations
                 // such as Paint that may crash because we have begun tearing down
                 // the rest of our resources.
@@ -448,6 +453,8 @@ impl IOCompositor {
             rect: Rect::zero(),
             background_color: azure_hl::Color::new(0., 0., 0., 0.),
             scroll_policy: Scrollable,
+            parent_id: None,
+            is_scroll_root: true,
         };
         let root_layer = CompositorData::new_layer(frame_tree.pipeline.clone(),
                                                    layer_properties,
@@ -523,6 +530,7 @@ impl IOCompositor {
 
         self.scroll_layer_to_fragment_point_if_necessary(layer_properties.pipeline_id,
                                                          layer_properties.id);
+        self.update_layer_positions();
         self.send_buffer_requests_for_all_layers();
     }
 
@@ -532,17 +540,50 @@ impl IOCompositor {
         }
         self.scroll_layer_to_fragment_point_if_necessary(layer_properties.pipeline_id,
                                                          layer_properties.id);
+        self.update_layer_positions();
         self.send_buffer_requests_for_all_layers();
     }
 
+    /// Reapplies the scroll positions, and with them the sticky offsets, to every layer after
+    /// layout has changed the layer tree.
+    fn update_layer_positions(&self) {
+        match self.scene.root {
+            Some(ref root_layer) => events::update_layer_positions(root_layer.clone()),
+            None => {}
+        }
+    }
+
     fn create_descendant_layer(&self, layer_properties: LayerProperties) {
         let root_layer = self.find_pipeline_root_layer(layer_properties.pipeline_id);
+        let parent_layer = match layer_properties.parent_id {
+            None => root_layer.clone(),
+            Some(parent_id) => {
+                match self.find_layer_with_pipeline_and_layer_id(layer_properties.pipeline_id,
+                                                                 parent_id) {
+                    Some(parent_layer) => parent_layer,
+                    None => fail!("Compositor: Tried to create a layer with an unknown parent."),
+                }
+            }
+        };
+
+        // Scroll roots clip their contents and handle scroll events themselves, like the root
+        // layer of a pipeline does.
+        let wants_scroll_events = if layer_properties.is_scroll_root {
+            WantsScrollEvents
+        } else {
+            DoesntWantScrollEvents
+        };
+
         let root_layer_pipeline = root_layer.extra_data.borrow().pipeline.clone();
         let new_layer = CompositorData::new_layer(root_layer_pipeline,
                                                   layer_properties,
-                                                  DoesntWantScrollEvents,
+                                                  wants_scroll_events,
                                                   root_layer.tile_size);
-        root_layer.add_child(new_layer);
+        parent_layer.add_child(new_layer.clone());
+
+        if layer_properties.is_scroll_root {
+            *new_layer.masks_to_bounds.borrow_mut() = true;
+        }
     }
 
     fn send_window_size(&self) {
@@ -602,6 +643,7 @@ impl IOCompositor {
             None => fail!("Compositor received SetLayerOrigin for nonexistent layer"),
         };
 
+        self.update_layer_positions();
         self.send_buffer_requests_for_all_layers();
     }
 
@@ -929,9 +971,13 @@ impl IOCompositor {
     }
 
     fn composite(&mut self) {
-        let output_image = self.opts.output_file.is_some() &&
+        let output_ready = self.opts.output_file.is_some() &&
                             self.is_ready_to_render_image_output();
 
+        // When paginating, the render task paints the pages that it was last sent into a PDF
+        // instead, so there is nothing to read back here.
+        let output_image = output_ready && !self.opts.paginate;
+
         let mut framebuffer_ids = vec!();
         let mut texture_ids = vec!();
         let (width, height) = (self.window_size.width.get(), self.window_size.height.get());
@@ -1012,6 +1058,15 @@ impl IOCompositor {
             self.shutdown_state = ShuttingDown;
         }
 
+        if output_ready && self.opts.paginate {
+            self.print();
+
+            debug!("shutting down the constellation after printing to an output file");
+            let ConstellationChan(ref chan) = self.constellation_chan;
+            chan.send(ExitMsg);
+            self.shutdown_state = ShuttingDown;
+        }
+
         self.window.present();
 
         let exit = self.opts.exit_after_load;
@@ -1022,7 +1077,53 @@ impl IOCompositor {
         }
     }
 
+    /// Asks the render task of the root pipeline to write its pages, with the frames inside them,
+    /// to the output file, and waits until it has.
+    fn print(&self) {
+        let root_layer = match self.scene.root {
+            Some(ref root_layer) => root_layer.clone(),
+            None => return,
+        };
+        let mut frames = vec!();
+        collect_printed_frames(&*root_layer, Point2D(0f32, 0f32), &mut frames);
+
+        let output_path =
+            from_str::<Path>(self.opts.output_file.as_ref().unwrap().as_slice()).unwrap();
+        let (result_chan, result_port) = channel();
+        let render_chan = root_layer.extra_data.borrow().pipeline.render_chan.clone();
+        render_chan.send(PrintMsg(output_path.clone(), frames, result_chan));
+        match result_port.recv() {
+            Ok(()) => {}
+            Err(error) => error!("failed to print to {}: {}", output_path.display(), error),
+        }
+    }
+
     fn recomposite_if(&mut self, result: bool) {
         self.recomposite = result || self.recomposite;
     }
 }
+
+/// Gathers the frames nested inside the pipeline whose root layer is `layer`, which is at `origin`
+/// in the document that is being printed.
+fn collect_printed_frames(layer: &Layer<CompositorData>,
+                          origin: Point2D<f32>,
+                          frames: &mut Vec<PrintedFrame>) {
+    let pipeline_id = layer.extra_data.borrow().pipeline.id;
+    for kid in layer.children().iter() {
+        let kid_data = kid.extra_data.borrow();
+        if kid_data.pipeline.id == pipeline_id || kid_data.id != LayerId::null() {
+            continue
+        }
+
+        let bounds = kid.bounds.borrow().to_untyped();
+        let kid_origin = origin + bounds.origin;
+        frames.push(PrintedFrame {
+            rect: Rect(Point2D(Au::from_frac32_px(kid_origin.x),
+                               Au::from_frac32_px(kid_origin.y)),
+                       Size2D(Au::from_frac32_px(bounds.size.width),
+                              Au::from_frac32_px(bounds.size.height))),
+            render_chan: kid_data.pipeline.render_chan.clone(),
+        });
+        collect_printed_frames(&**kid, kid_origin, frames);
+    }
+}
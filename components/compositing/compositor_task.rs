@@ -19,6 +19,7 @@ use layers::layers::LayerBufferSet;
 use servo_msg::compositor_msg::{Epoch, LayerId, LayerMetadata, ReadyState};
 use servo_msg::compositor_msg::{RenderListener, RenderState, ScriptListener, ScrollPolicy};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
+use servo_util::cursor::Cursor;
 use servo_util::memory::MemoryProfilerChan;
 use servo_util::opts::Opts;
 use servo_util::time::TimeProfilerChan;
@@ -68,6 +69,8 @@ pub struct LayerProperties {
     pub rect: Rect<f32>,
     pub background_color: Color,
     pub scroll_policy: ScrollPolicy,
+    pub parent_id: Option<LayerId>,
+    pub is_scroll_root: bool,
 }
 
 impl LayerProperties {
@@ -82,6 +85,8 @@ impl LayerProperties {
                               metadata.position.size.height as f32)),
             background_color: metadata.background_color,
             scroll_policy: metadata.scroll_policy,
+            parent_id: metadata.parent_id,
+            is_scroll_root: metadata.is_scroll_root,
         }
     }
 }
@@ -181,6 +186,8 @@ pub enum Msg {
     SetIds(SendableFrameTree, Sender<()>, ConstellationChan),
     /// The load of a page for a given URL has completed.
     LoadComplete(PipelineId, Url),
+    /// Changes the mouse cursor shown over the window.
+    SetCursor(Cursor),
 }
 
 pub enum CompositorMode {
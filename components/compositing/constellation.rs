@@ -2,23 +2,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use compositor_task::{CompositorChan, LoadComplete, ShutdownComplete, SetLayerOrigin, SetIds};
+use compositor_task::{CompositorChan, LoadComplete, ShutdownComplete, SetCursor, SetLayerOrigin};
+use compositor_task::SetIds;
 use devtools_traits::DevtoolsControlChan;
 use std::collections::hashmap::{HashMap, HashSet};
 use geom::rect::{Rect, TypedRect};
 use geom::scale_factor::ScaleFactor;
+use gfx::render_task::WorkerThreadPool;
 use gfx::render_task;
 use libc;
 use pipeline::{Pipeline, CompositionPipeline};
 use layout_traits::{LayoutControlChan, LayoutTaskFactory, ExitNowMsg};
 use script_traits::{ResizeMsg, ResizeInactiveMsg, ExitPipelineMsg};
 use script_traits::{ScriptControlChan, ScriptTaskFactory};
-use servo_msg::compositor_msg::LayerId;
+use servo_msg::compositor_msg::{LayerId, RenderListener};
 use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FailureMsg, Failure, FrameRectMsg};
 use servo_msg::constellation_msg::{IFrameSandboxState, IFrameUnsandboxed, InitLoadUrlMsg};
 use servo_msg::constellation_msg::{LoadCompleteMsg, LoadIframeUrlMsg, LoadUrlMsg, Msg, NavigateMsg};
 use servo_msg::constellation_msg::{NavigationType, PipelineId, RendererReadyMsg, ResizedWindowMsg};
-use servo_msg::constellation_msg::{SubpageId, WindowSizeData};
+use servo_msg::constellation_msg::{SetCursorMsg, SubpageId, WindowSizeData};
 use servo_msg::constellation_msg;
 use servo_net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
 use gfx::font_cache_task::FontCacheTask;
@@ -44,6 +46,9 @@ pub struct Constellation<LTF, STF> {
     devtools_chan: Option<DevtoolsControlChan>,
     pipelines: HashMap<PipelineId, Rc<Pipeline>>,
     font_cache_task: FontCacheTask,
+    /// The threads that the render tasks of all pipelines rasterize tiles on, if tiles are
+    /// painted on the CPU by more than one thread.
+    render_worker_pool: Option<WorkerThreadPool>,
     navigation_context: NavigationContext,
     next_pipeline_id: PipelineId,
     pending_frames: Vec<FrameChange>,
@@ -252,6 +257,15 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         let constellation_chan_clone = constellation_chan.clone();
         let opts_clone = opts.clone();
         spawn_named("Constellation", proc() {
+            let render_worker_pool = if opts_clone.cpu_painting &&
+                    opts_clone.n_render_threads > 1 {
+                Some(WorkerThreadPool::new(compositor_chan.get_graphics_metadata(),
+                                           font_cache_task.clone(),
+                                           opts_clone.clone(),
+                                           time_profiler_chan.clone()))
+            } else {
+                None
+            };
             let mut constellation : Constellation<LTF, STF> = Constellation {
                 chan: constellation_chan_clone,
                 request_port: constellation_port,
@@ -260,6 +274,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 resource_task: resource_task,
                 image_cache_task: image_cache_task,
                 font_cache_task: font_cache_task,
+                render_worker_pool: render_worker_pool,
                 pipelines: HashMap::new(),
                 navigation_context: NavigationContext::new(),
                 next_pipeline_id: PipelineId(0),
@@ -303,6 +318,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                                                     self.font_cache_task.clone(),
                                                     self.resource_task.clone(),
                                                     self.time_profiler_chan.clone(),
+                                                    self.render_worker_pool.clone(),
                                                     self.window_size,
                                                     self.opts.clone(),
                                                     script_pipeline,
@@ -388,6 +404,11 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got window resize message");
                 self.handle_resized_window_msg(new_size);
             }
+            // Layout found a new cursor under the mouse.
+            SetCursorMsg(cursor) => {
+                debug!("constellation got set cursor message");
+                self.compositor_chan.send(SetCursor(cursor));
+            }
         }
         true
     }
@@ -399,6 +420,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         self.image_cache_task.exit();
         self.resource_task.send(resource_task::Exit);
         self.font_cache_task.exit();
+        self.render_worker_pool.as_ref().map(|pool| pool.exit());
         self.compositor_chan.send(ShutdownComplete);
     }
 
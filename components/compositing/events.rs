@@ -13,8 +13,9 @@ use geom::size::TypedSize2D;
 use layers::geometry::LayerPixel;
 use layers::layers::Layer;
 use script_traits::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent, SendEventMsg};
-use script_traits::{ScriptControlChan};
-use servo_msg::compositor_msg::FixedPosition;
+use script_traits::{ScriptControlChan, ScrollEvent};
+use servo_msg::compositor_msg::{FixedPosition, LayerId, Scrollable, StickyConstraints};
+use servo_msg::compositor_msg::StickyPosition;
 use std::rc::Rc;
 
 
@@ -47,8 +48,9 @@ pub enum ScrollEventResult {
 
 /// Move the layer's descendants that don't want scroll events and scroll by a relative
 /// specified amount in page coordinates. This also takes in a cursor position to see if the
-/// mouse is over child layers first. If a layer successfully scrolled, returns true; otherwise
-/// returns false, so a parent layer can scroll instead.
+/// mouse is over child layers first, so that the innermost scrollable layer under the cursor
+/// scrolls. If a child layer is already scrolled as far as it can go, this layer scrolls
+/// instead.
 pub fn handle_scroll_event(layer: Rc<Layer<CompositorData>>,
                            delta: TypedPoint2D<LayerPixel, f32>,
                            cursor: TypedPoint2D<LayerPixel, f32>)
@@ -68,7 +70,7 @@ pub fn handle_scroll_event(layer: Rc<Layer<CompositorData>>,
             let result = handle_scroll_event(child.clone(),
                                              delta,
                                              new_cursor - child_bounds.origin);
-            if result != ScrollEventUnhandled {
+            if result == ScrollPositionChanged {
                 return result;
             }
         }
@@ -101,66 +103,192 @@ pub fn clamp_scroll_offset_and_scroll_layer(layer: Rc<Layer<CompositorData>>,
     }
 
     // The scroll offset is just a record of the scroll position of this scrolling root,
-    // but scroll_layer_and_all_child_layers actually moves the child layers.
+    // but scroll_layer_and_all_child_layers actually moves the child layers. The children must
+    // also stay offset by however far this layer has been scrolled by its ancestors.
     layer.extra_data.borrow_mut().scroll_offset = new_offset;
 
+    let offset_for_children = *layer.content_offset.borrow() + new_offset;
     let mut result = false;
     for child in layer.children().iter() {
-        result |= scroll_layer_and_all_child_layers(child.clone(), new_offset);
+        result |= scroll_layer_and_all_child_layers(child.clone(), &*layer, offset_for_children);
     }
 
-    if result {
-        return ScrollPositionChanged;
-    } else {
+    if !result {
         return ScrollPositionUnchanged;
     }
+
+    send_scroll_offset_to_script(layer);
+    ScrollPositionChanged
+}
+
+/// Informs script of the new scroll position of a scroll root, so that it can answer `scrollTop`
+/// and `scrollLeft` queries. The scroll position of a pipeline's root layer is not reported.
+fn send_scroll_offset_to_script(layer: Rc<Layer<CompositorData>>) {
+    let layer_data = layer.extra_data.borrow();
+    if layer_data.id == LayerId::null() {
+        return
+    }
+
+    let scroll_position = (TypedPoint2D(0f32, 0f32) - layer_data.scroll_offset).to_untyped();
+    let ScriptControlChan(ref chan) = layer_data.pipeline.script_chan;
+    let _ = chan.send_opt(SendEventMsg(layer_data.pipeline.id.clone(),
+                                       ScrollEvent(layer_data.id, scroll_position)));
+}
+
+/// Moves every descendant of the given layer to where the scroll positions of its ancestors put
+/// it. Sticky offsets depend on the bounds of a layer and of its parent, so this must run whenever
+/// layout creates, resizes or moves layers, and not only when a layer scrolls.
+pub fn update_layer_positions(layer: Rc<Layer<CompositorData>>) {
+    let offset_for_children = *layer.content_offset.borrow() +
+                              layer.extra_data.borrow().scroll_offset;
+    for child in layer.children().iter() {
+        scroll_layer_and_all_child_layers(child.clone(), &*layer, offset_for_children);
+    }
 }
 
 fn scroll_layer_and_all_child_layers(layer: Rc<Layer<CompositorData>>,
+                                     parent: &Layer<CompositorData>,
                                      new_offset: TypedPoint2D<LayerPixel, f32>)
                                      -> bool {
     let mut result = false;
 
-    // Only scroll this layer if it's not fixed-positioned.
-    if layer.extra_data.borrow().scroll_policy != FixedPosition {
-        let new_offset = new_offset.to_untyped();
-        *layer.transform.borrow_mut() = identity().translate(new_offset.x,
-                                                             new_offset.y,
-                                                             0.0);
-        *layer.content_offset.borrow_mut() = Point2D::from_untyped(&new_offset);
-        result = true
-    }
+    // Only scroll this layer if it's not fixed-positioned. Sticky layers scroll, but are then
+    // pushed back into view if they would leave their parent's visible area.
+    let new_offset = match layer.extra_data.borrow().scroll_policy {
+        FixedPosition => new_offset,
+        Scrollable => {
+            scroll_layer(&*layer, new_offset);
+            result = true;
+            new_offset
+        }
+        StickyPosition(ref constraints) => {
+            let new_offset = new_offset + sticky_position_offset(&*layer, parent, constraints);
+            scroll_layer(&*layer, new_offset);
+            result = true;
+            new_offset
+        }
+    };
 
     let offset_for_children = new_offset + layer.extra_data.borrow().scroll_offset;
     for child in layer.children().iter() {
-        result |= scroll_layer_and_all_child_layers(child.clone(), offset_for_children);
+        result |= scroll_layer_and_all_child_layers(child.clone(), &*layer, offset_for_children);
     }
 
     return result;
 }
 
+fn scroll_layer(layer: &Layer<CompositorData>, new_offset: TypedPoint2D<LayerPixel, f32>) {
+    let new_offset = new_offset.to_untyped();
+    *layer.transform.borrow_mut() = identity().translate(new_offset.x, new_offset.y, 0.0);
+    *layer.content_offset.borrow_mut() = Point2D::from_untyped(&new_offset);
+}
+
+/// Returns how far a `position: sticky` layer must be moved from where its parent's scrolling
+/// would put it in order to satisfy its constraints.
+fn sticky_position_offset(layer: &Layer<CompositorData>,
+                          parent: &Layer<CompositorData>,
+                          constraints: &StickyConstraints)
+                          -> TypedPoint2D<LayerPixel, f32> {
+    let bounds = layer.bounds.borrow().to_untyped();
+    let parent_scroll_offset = parent.extra_data.borrow().scroll_offset.to_untyped();
+    let visible_rect = Rect(Point2D(-parent_scroll_offset.x, -parent_scroll_offset.y),
+                            parent.bounds.borrow().size.to_untyped());
+    let containing_block = constraints.containing_block;
+
+    let x = sticky_position_offset_along_axis(bounds.origin.x,
+                                              bounds.max_x(),
+                                              visible_rect.origin.x,
+                                              visible_rect.max_x(),
+                                              containing_block.origin.x,
+                                              containing_block.max_x(),
+                                              constraints.insets.left,
+                                              constraints.insets.right);
+    let y = sticky_position_offset_along_axis(bounds.origin.y,
+                                              bounds.max_y(),
+                                              visible_rect.origin.y,
+                                              visible_rect.max_y(),
+                                              containing_block.origin.y,
+                                              containing_block.max_y(),
+                                              constraints.insets.top,
+                                              constraints.insets.bottom);
+    Point2D(Length(x), Length(y))
+}
+
+/// Computes the sticky offset of a layer along one axis. If the layer would violate both of its
+/// insets, the start inset wins, as the CSS Positioned Layout spec requires.
+fn sticky_position_offset_along_axis(start: f32,
+                                     end: f32,
+                                     visible_start: f32,
+                                     visible_end: f32,
+                                     containing_block_start: f32,
+                                     containing_block_end: f32,
+                                     start_inset: Option<f32>,
+                                     end_inset: Option<f32>)
+                                     -> f32 {
+    match start_inset {
+        Some(inset) if start < visible_start + inset => {
+            // Move forward, but never past the end of the containing block.
+            return (visible_start + inset - start).min(containing_block_end - end).max(0.0)
+        }
+        _ => {}
+    }
+    match end_inset {
+        Some(inset) if end > visible_end - inset => {
+            // Move backward, but never past the start of the containing block.
+            (visible_end - inset - end).max(containing_block_start - start).min(0.0)
+        }
+        _ => 0.0,
+    }
+}
+
 struct HitTestResult {
     layer: Rc<Layer<CompositorData>>,
     point: TypedPoint2D<LayerPixel, f32>,
 }
 
+/// Finds the topmost layer under the given point. The point in the result is in the page
+/// coordinates of the pipeline that the layer belongs to.
 pub fn find_topmost_layer_at_point(layer: Rc<Layer<CompositorData>>,
                                    point: TypedPoint2D<LayerPixel, f32>)
                                    -> Option<HitTestResult> {
-    let child_point = point - layer.bounds.borrow().origin;
+    find_topmost_layer_at_point_for_layer(layer, point, TypedPoint2D(0f32, 0f32))
+}
+
+/// `parent_page_origin` is the position of the layer's parent in the page coordinates of the
+/// layer's pipeline. Layers nested inside scroll roots are positioned relative to their
+/// parents, so this is needed to translate points back into page coordinates for script.
+fn find_topmost_layer_at_point_for_layer(layer: Rc<Layer<CompositorData>>,
+                                         point: TypedPoint2D<LayerPixel, f32>,
+                                         parent_page_origin: TypedPoint2D<LayerPixel, f32>)
+                                         -> Option<HitTestResult> {
+    let bounds = *layer.bounds.borrow();
+    let unscrolled_point = point - *layer.content_offset.borrow();
+
+    // Nothing outside a clipping layer is visible, so nothing there can be hit.
+    if *layer.masks_to_bounds.borrow() && !bounds.contains(&unscrolled_point) {
+        return None;
+    }
+
+    // The children of a pipeline's root layer are positioned in that pipeline's page coordinates.
+    let page_origin = if layer.extra_data.borrow().id == LayerId::null() {
+        TypedPoint2D(0f32, 0f32)
+    } else {
+        parent_page_origin + bounds.origin
+    };
+
+    let child_point = point - bounds.origin;
     for child in layer.children().iter().rev() {
-        let result = find_topmost_layer_at_point(child.clone(), child_point);
+        let result = find_topmost_layer_at_point_for_layer(child.clone(), child_point, page_origin);
         if result.is_some() {
             return result;
         }
     }
 
-    let point = point - *layer.content_offset.borrow();
-    if !layer.bounds.borrow().contains(&point) {
+    if !bounds.contains(&unscrolled_point) {
         return None;
     }
 
-    return Some(HitTestResult { layer: layer, point: point });
+    return Some(HitTestResult { layer: layer, point: unscrolled_point + parent_page_origin });
 }
 
 // Takes in a MouseWindowEvent, determines if it should be passed to children, and
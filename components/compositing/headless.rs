@@ -4,7 +4,7 @@
 
 use compositor_task::{Msg, Exit, ChangeReadyState, SetIds};
 use compositor_task::{GetGraphicsMetadata, CreateOrUpdateRootLayer, CreateOrUpdateDescendantLayer};
-use compositor_task::{SetLayerOrigin, Paint, ScrollFragmentPoint, LoadComplete};
+use compositor_task::{SetLayerOrigin, Paint, ScrollFragmentPoint, LoadComplete, SetCursor};
 use compositor_task::{ShutdownComplete, ChangeRenderState, RenderMsgDiscarded};
 
 use geom::scale_factor::ScaleFactor;
@@ -92,7 +92,7 @@ impl NullCompositor {
                 CreateOrUpdateDescendantLayer(..) |
                 SetLayerOrigin(..) | Paint(..) |
                 ChangeReadyState(..) | ChangeRenderState(..) | ScrollFragmentPoint(..) |
-                LoadComplete(..) | RenderMsgDiscarded(..) => ()
+                LoadComplete(..) | RenderMsgDiscarded(..) | SetCursor(..) => ()
             }
         }
     }
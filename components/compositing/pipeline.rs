@@ -9,7 +9,7 @@ use script_traits::{AttachLayoutMsg, LoadMsg, NewLayoutInfo, ExitPipelineMsg};
 
 use devtools_traits::DevtoolsControlChan;
 use gfx::render_task::{PaintPermissionGranted, PaintPermissionRevoked};
-use gfx::render_task::{RenderChan, RenderTask};
+use gfx::render_task::{RenderChan, RenderTask, WorkerThreadPool};
 use servo_msg::constellation_msg::{ConstellationChan, Failure, PipelineId, SubpageId};
 use servo_msg::constellation_msg::WindowSizeData;
 use servo_net::image_cache_task::ImageCacheTask;
@@ -55,6 +55,7 @@ impl Pipeline {
                       font_cache_task: FontCacheTask,
                       resource_task: ResourceTask,
                       time_profiler_chan: TimeProfilerChan,
+                      render_worker_pool: Option<WorkerThreadPool>,
                       window_size: WindowSizeData,
                       opts: Opts,
                       script_pipeline: Option<Rc<Pipeline>>,
@@ -104,12 +105,14 @@ impl Pipeline {
 
         RenderTask::create(id,
                            render_port,
+                           render_chan.clone(),
                            compositor_chan.clone(),
                            constellation_chan.clone(),
                            font_cache_task.clone(),
                            failure.clone(),
                            opts.clone(),
                            time_profiler_chan.clone(),
+                           render_worker_pool,
                            render_shutdown_chan);
 
         LayoutTaskFactory::create(None::<&mut LTF>,
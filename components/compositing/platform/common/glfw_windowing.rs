@@ -25,6 +25,7 @@ use geom::size::TypedSize2D;
 use layers::geometry::DevicePixel;
 use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, Loading, PerformingLayout, ReadyState};
+use servo_util::cursor::{Cursor, NoCursor};
 use servo_util::geometry::ScreenPx;
 
 use glfw;
@@ -206,6 +207,15 @@ impl WindowMethods<Application> for Window {
         let window_size = self.size().width.get();
         ScaleFactor((backing_size as f32) / window_size)
     }
+
+    /// GLFW 3.0 has no standard cursor shapes, so only hiding the cursor is supported.
+    fn set_cursor(&self, cursor: Cursor) {
+        let mode = match cursor {
+            NoCursor => glfw::CursorHidden,
+            _ => glfw::CursorNormal,
+        };
+        self.glfw_window.set_cursor_mode(mode)
+    }
 }
 
 impl Window {
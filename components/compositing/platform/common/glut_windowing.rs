@@ -20,6 +20,7 @@ use geom::size::TypedSize2D;
 use layers::geometry::DevicePixel;
 use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, ReadyState};
+use servo_util::cursor::Cursor;
 use servo_util::geometry::ScreenPx;
 
 use glut::glut::{ACTIVE_SHIFT, DOUBLE, WindowHeight};
@@ -196,6 +197,10 @@ impl WindowMethods<Application> for Window {
         //FIXME: Do nothing in GLUT now.
         ScaleFactor(1.0)
     }
+
+    fn set_cursor(&self, _: Cursor) {
+        //FIXME: Do nothing in GLUT now.
+    }
 }
 
 impl Window {
@@ -9,6 +9,7 @@ use geom::scale_factor::ScaleFactor;
 use geom::size::TypedSize2D;
 use layers::geometry::DevicePixel;
 use servo_msg::compositor_msg::{ReadyState, RenderState};
+use servo_util::cursor::Cursor;
 use servo_util::geometry::ScreenPx;
 use std::rc::Rc;
 
@@ -79,5 +80,8 @@ pub trait WindowMethods<A> {
 
     /// Returns the hidpi factor of the monitor.
     fn hidpi_factor(&self) -> ScaleFactor<ScreenPx, DevicePixel, f32>;
+
+    /// Changes the mouse cursor shown over the window to the one the page asks for.
+    fn set_cursor(&self, cursor: Cursor);
 }
 
@@ -5,7 +5,7 @@
 /// Liberally derived from the [Firefox JS implementation](http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/inspector.js).
 
 use devtools_traits::{GetRootNode, GetDocumentElement, GetChildren, DevtoolScriptControlMsg};
-use devtools_traits::{GetLayout, NodeInfo};
+use devtools_traits::{GetDisplayList, GetLayout, NodeInfo};
 
 use actor::{Actor, ActorRegistry};
 use protocol::JsonPacketSender;
@@ -32,6 +32,21 @@ struct GetHighlighterReply {
     from: String,
 }
 
+/// Not part of the Firefox protocol: the display lists of the page, for comparing layout results
+/// without comparing pixels.
+#[deriving(Encodable)]
+struct GetDisplayListReply {
+    displayList: json::Json,
+    from: String,
+}
+
+#[deriving(Encodable)]
+struct ErrorReply {
+    from: String,
+    error: String,
+    message: String,
+}
+
 #[deriving(Encodable)]
 struct HighlighterMsg {
     actor: String,
@@ -510,6 +525,32 @@ impl Actor for InspectorActor {
                 true
             }
 
+            "getDisplayList" => {
+                let (tx, rx) = channel();
+                self.script_chan.send(GetDisplayList(self.pipeline, tx));
+                let display_list = rx.recv();
+
+                match json::from_str(display_list.as_slice()) {
+                    Ok(display_list) => {
+                        let msg = GetDisplayListReply {
+                            displayList: display_list,
+                            from: self.name(),
+                        };
+                        stream.write_json_packet(&msg);
+                    }
+                    Err(error) => {
+                        let msg = ErrorReply {
+                            from: self.name(),
+                            error: "unknownError".to_string(),
+                            message: format!("Layout sent a display list that is not JSON: {}",
+                                             error),
+                        };
+                        stream.write_json_packet(&msg);
+                    }
+                }
+                true
+            }
+
             _ => false,
         }
     }
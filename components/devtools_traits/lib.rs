@@ -73,6 +73,7 @@ pub enum DevtoolScriptControlMsg {
     GetDocumentElement(PipelineId, Sender<NodeInfo>),
     GetChildren(PipelineId, String, Sender<Vec<NodeInfo>>),
     GetLayout(PipelineId, String, Sender<(f32, f32)>),
+    GetDisplayList(PipelineId, Sender<String>),
 }
 
 /// Messages to instruct devtools server to update its state relating to a particular
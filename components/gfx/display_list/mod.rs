@@ -15,6 +15,7 @@
 //! low-level drawing primitives.
 
 use color::Color;
+use display_list::spatial_index::SpatialIndex;
 use render_context::RenderContext;
 use text::glyph::CharIndex;
 use text::TextRun;
@@ -23,14 +24,20 @@ use collections::dlist::DList;
 use collections::dlist;
 use geom::{Point2D, Rect, SideOffsets2D, Size2D, Matrix2D};
 use libc::uintptr_t;
+use servo_net::image::animation::Animation;
 use servo_net::image::base::Image;
+use servo_util::cursor::Cursor;
 use servo_util::geometry::Au;
+use servo_util::geometry;
 use servo_util::range::Range;
+use std::cmp;
+use std::collections::hashmap::HashMap;
 use std::fmt;
 use std::mem;
 use std::slice::Items;
-use style::computed_values::border_style;
+use style::computed_values::{border_style, text_decoration_style};
 use sync::Arc;
+use url::Url;
 use std::num::Zero;
 use std::ptr;
 
@@ -39,6 +46,8 @@ use azure::scaled_font::ScaledFont;
 use azure::azure_hl::ColorPattern;
 
 pub mod optimizer;
+pub mod serializer;
+pub mod spatial_index;
 
 /// An opaque handle to a node. The only safe operation that can be performed on this node is to
 /// compare it to another opaque handle or to another node.
@@ -62,6 +71,7 @@ trait ScaledFontExtensionMethods {
                               rctx: &RenderContext,
                               run: &Box<TextRun>,
                               range: &Range<CharIndex>,
+                              hyphenated: bool,
                               baseline_origin: Point2D<Au>,
                               color: Color,
                               antialias: bool);
@@ -72,6 +82,7 @@ impl ScaledFontExtensionMethods for ScaledFont {
                               rctx: &RenderContext,
                               run: &Box<TextRun>,
                               range: &Range<CharIndex>,
+                              hyphenated: bool,
                               baseline_origin: Point2D<Au>,
                               color: Color,
                               antialias: bool) {
@@ -102,22 +113,48 @@ impl ScaledFontExtensionMethods for ScaledFont {
         let mut azglyphs = vec!();
         azglyphs.reserve(range.length().to_uint());
 
+        // Right-to-left glyphs are stored in the order of their characters, so they are placed
+        // from the end of the range back to its start.
+        let is_rtl = run.is_rtl();
+        let range_advance = run.advance_for_range(range);
+        let mut pen_x = if is_rtl { origin.x + range_advance } else { origin.x };
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
             for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
                 let glyph_advance = glyph.advance();
                 let glyph_offset = glyph.offset().unwrap_or(Zero::zero());
+                let glyph_x = if is_rtl {
+                    pen_x = pen_x - glyph_advance;
+                    pen_x
+                } else {
+                    pen_x = pen_x + glyph_advance;
+                    pen_x - glyph_advance
+                };
 
                 let azglyph = struct__AzGlyph {
                     mIndex: glyph.id() as uint32_t,
                     mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_subpx() as AzFloat,
+                        x: (glyph_x + glyph_offset.x).to_subpx() as AzFloat,
                         y: (origin.y + glyph_offset.y).to_subpx() as AzFloat
                     }
                 };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
                 azglyphs.push(azglyph)
             };
         }
+        origin = Point2D(origin.x + range_advance, origin.y);
+
+        // A line broken at a soft hyphen ends with a hyphen.
+        match run.hyphen_glyph {
+            Some(hyphen_glyph) if hyphenated => {
+                azglyphs.push(struct__AzGlyph {
+                    mIndex: hyphen_glyph as uint32_t,
+                    mPosition: struct__AzPoint {
+                        x: origin.x.to_subpx() as AzFloat,
+                        y: origin.y.to_subpx() as AzFloat
+                    }
+                })
+            }
+            _ => {}
+        }
 
         let azglyph_buf_len = azglyphs.len();
         if azglyph_buf_len == 0 { return; } // Otherwise the Quartz backend will assert.
@@ -187,7 +224,8 @@ impl StackingContext {
     /// Creates a stacking context from a display list.
     fn new(list: DisplayList) -> StackingContext {
         let DisplayList {
-            list: list
+            list: list,
+            index: _,
         } = list;
 
         let mut stacking_context = StackingContext {
@@ -299,6 +337,9 @@ pub enum BackgroundAndBorderLevel {
 #[deriving(Clone)]
 pub struct DisplayList {
     pub list: DList<DisplayItem>,
+    /// An index of where the items are, built once the list is finished. Changing the list
+    /// discards it.
+    index: Option<Arc<SpatialIndex>>,
 }
 
 pub enum DisplayListIterator<'a> {
@@ -321,20 +362,92 @@ impl DisplayList {
     pub fn new() -> DisplayList {
         DisplayList {
             list: DList::new(),
+            index: None,
+        }
+    }
+
+    /// Creates a display list of the given items.
+    pub fn from_items(list: DList<DisplayItem>) -> DisplayList {
+        DisplayList {
+            list: list,
+            index: None,
         }
     }
 
     /// Appends the given item to the display list.
     pub fn push(&mut self, item: DisplayItem) {
+        self.index = None;
         self.list.push(item)
     }
 
     /// Appends the given display list to this display list, consuming the other display list in
     /// the process.
     pub fn push_all_move(&mut self, other: DisplayList) {
+        self.index = None;
         self.list.append(other.list)
     }
 
+    /// Indexes the items of this finished, flattened display list by where they are, so that
+    /// `display_list_for_rect()` and `items_at_point()` need not walk the whole list.
+    pub fn build_spatial_index(mut self) -> DisplayList {
+        self.index = Some(Arc::new(SpatialIndex::new(&self)));
+        self
+    }
+
+    /// Returns a display list of the items that show in the given rectangle, if this list has
+    /// been indexed.
+    pub fn display_list_for_rect(&self, rect: &Rect<Au>) -> Option<DisplayList> {
+        self.index.as_ref().map(|index| {
+            index.display_list_for_rect(self.leaf_items().as_slice(), rect)
+        })
+    }
+
+    /// Returns the items that are visible at the given point, inside their clips, topmost
+    /// first. The display list must be flattened first for correct results.
+    pub fn items_at_point<'a>(&'a self, point: &Point2D<Au>) -> Vec<&'a DisplayItem> {
+        match self.index {
+            Some(ref index) => return index.items_at_point(self.leaf_items().as_slice(), point),
+            None => {}
+        }
+
+        fn add_items_at_point<'a>(display_list: &'a DisplayList,
+                                  point: &Point2D<Au>,
+                                  result: &mut Vec<&'a DisplayItem>) {
+            for item in display_list.list.iter().rev() {
+                if !geometry::rect_contains_point(item.bounds(), *point) {
+                    continue
+                }
+                match *item {
+                    ClipDisplayItemClass(ref clip) => {
+                        add_items_at_point(&clip.children, point, result)
+                    }
+                    _ => result.push(item),
+                }
+            }
+        }
+
+        let mut result = vec!();
+        add_items_at_point(self, point, &mut result);
+        result
+    }
+
+    /// Returns the items of this display list and of the clips in it that are not clips
+    /// themselves, in painting order.
+    pub fn leaf_items<'a>(&'a self) -> Vec<&'a DisplayItem> {
+        fn add_leaf_items<'a>(display_list: &'a DisplayList, result: &mut Vec<&'a DisplayItem>) {
+            for item in display_list.list.iter() {
+                match *item {
+                    ClipDisplayItemClass(ref clip) => add_leaf_items(&clip.children, result),
+                    _ => result.push(item),
+                }
+            }
+        }
+
+        let mut result = vec!();
+        add_leaf_items(self, &mut result);
+        result
+    }
+
     pub fn debug(&self) {
         if log_enabled!(::log::DEBUG) {
             for item in self.list.iter() {
@@ -354,6 +467,32 @@ impl DisplayList {
         debug!("Ending display list.");
     }
 
+    /// Returns the image items of this display list, and of the clips in it, that show animated
+    /// images.
+    pub fn animated_image_items<'a>(&'a self) -> Vec<&'a ImageDisplayItem> {
+        self.leaf_items().into_iter().filter_map(|item| {
+            match *item {
+                ImageDisplayItemClass(ref image_item) if image_item.animation.is_some() => {
+                    Some(&**image_item)
+                }
+                _ => None,
+            }
+        }).collect()
+    }
+
+    /// Returns the time after `now` at which the frame of an animated image in this display list
+    /// next changes, or `None` if none of them will change again. Each image plays from its time
+    /// in `start_times`; all times are in milliseconds on the same clock.
+    pub fn next_animation_frame_time(&self, now: u64, start_times: &HashMap<Url, u64>)
+                                     -> Option<u64> {
+        self.animated_image_items().into_iter().filter_map(|image_item| {
+            let start_time = start_times.find(&image_item.url).map(|&time| time).unwrap_or(0);
+            let animation = image_item.animation.as_ref().unwrap();
+            let elapsed = now - cmp::min(now, start_time);
+            animation.next_frame_time(elapsed).map(|time| start_time + time)
+        }).min()
+    }
+
     /// Returns a preorder iterator over the given display list.
     pub fn iter<'a>(&'a self) -> DisplayItemIterator<'a> {
         ParentDisplayItemIterator(self.list.iter())
@@ -418,6 +557,7 @@ impl DisplayList {
 
     /// Sets the stacking level for this display list and all its subitems.
     fn set_stacking_level(&mut self, new_level: StackingLevel) {
+        self.index = None;
         for item in self.list.iter_mut() {
             item.mut_base().level = new_level;
             match item.mut_sublist() {
@@ -436,14 +576,15 @@ pub enum DisplayItem {
     ImageDisplayItemClass(Box<ImageDisplayItem>),
     BorderDisplayItemClass(Box<BorderDisplayItem>),
     LineDisplayItemClass(Box<LineDisplayItem>),
+    TextDecorationDisplayItemClass(Box<TextDecorationDisplayItem>),
     ClipDisplayItemClass(Box<ClipDisplayItem>),
 
     /// A pseudo-display item that exists only so that queries like `ContentBoxQuery` and
-    /// `ContentBoxesQuery` can be answered.
+    /// `ContentBoxesQuery` can be answered, and so that hit testing can find the mouse cursor.
     ///
     /// FIXME(pcwalton): This is really bogus. Those queries should not consult the display list
     /// but should instead consult the flow/box tree.
-    PseudoDisplayItemClass(Box<BaseDisplayItem>),
+    PseudoDisplayItemClass(Box<PseudoDisplayItem>),
 }
 
 /// Information common to all display items.
@@ -490,6 +631,9 @@ pub struct TextDisplayItem {
     /// The range of text within the text run.
     pub range: Range<CharIndex>,
 
+    /// Whether a hyphen is drawn after the range, because the line was broken at a soft hyphen.
+    pub hyphenated: bool,
+
     /// The color of the text.
     pub text_color: Color,
 
@@ -508,7 +652,14 @@ pub enum TextOrientation {
 #[deriving(Clone)]
 pub struct ImageDisplayItem {
     pub base: BaseDisplayItem,
-    pub image: Arc<Box<Image>>,
+    pub image: Arc<Image>,
+
+    /// The URL that the image was loaded from.
+    pub url: Url,
+
+    /// The frames of the image if it is animated, in which case the frame that shows at the
+    /// render context's animation time is painted instead of `image`.
+    pub animation: Option<Arc<Animation>>,
 
     /// The dimensions to which the image display item should be stretched. If this is smaller than
     /// the bounds of this display item, then the image will be repeated in the appropriate
@@ -543,6 +694,30 @@ pub struct LineDisplayItem {
     pub style: border_style::T
 }
 
+/// Renders an underline, overline or line-through.
+#[deriving(Clone)]
+pub struct TextDecorationDisplayItem {
+    pub base: BaseDisplayItem,
+
+    /// The line color.
+    pub color: Color,
+
+    /// The line style.
+    pub style: text_decoration_style::T,
+
+    /// The thickness of the line. Double and wavy lines have bounds taller than this.
+    pub thickness: Au,
+}
+
+/// Paints nothing, but stands in for a fragment in layout queries.
+#[deriving(Clone)]
+pub struct PseudoDisplayItem {
+    pub base: BaseDisplayItem,
+
+    /// The mouse cursor to show over the fragment, with `cursor: auto` already resolved.
+    pub cursor: Cursor,
+}
+
 /// Clips a list of child display items to this display item's boundaries.
 #[deriving(Clone)]
 pub struct ClipDisplayItem {
@@ -640,6 +815,7 @@ impl DisplayItem {
                     render_context,
                     &*text.text_run,
                     &text.range,
+                    text.hyphenated,
                     baseline_origin,
                     text.text_color,
                     render_context.opts.enable_text_antialiasing
@@ -654,6 +830,12 @@ impl DisplayItem {
             ImageDisplayItemClass(ref image_item) => {
                 debug!("Drawing image at {:?}.", image_item.base.bounds);
 
+                let image = match image_item.animation {
+                    Some(ref animation) => {
+                        animation.image_at(render_context.animation_time_for(&image_item.url))
+                    }
+                    None => image_item.image.clone(),
+                };
                 let mut y_offset = Au(0);
                 while y_offset < image_item.base.bounds.size.height {
                     let mut x_offset = Au(0);
@@ -663,7 +845,7 @@ impl DisplayItem {
                         bounds.origin.y = bounds.origin.y + y_offset;
                         bounds.size = image_item.stretch_size;
 
-                        render_context.draw_image(bounds, image_item.image.clone());
+                        render_context.draw_image(bounds, &*image);
 
                         x_offset = x_offset + image_item.stretch_size.width;
                     }
@@ -685,6 +867,13 @@ impl DisplayItem {
                                           line.style)
             }
 
+            TextDecorationDisplayItemClass(ref decoration) => {
+                render_context.draw_text_decoration(&decoration.base.bounds,
+                                                    decoration.color,
+                                                    decoration.style,
+                                                    decoration.thickness)
+            }
+
             PseudoDisplayItemClass(_) => {}
         }
     }
@@ -696,8 +885,9 @@ impl DisplayItem {
             ImageDisplayItemClass(ref image_item) => &image_item.base,
             BorderDisplayItemClass(ref border) => &border.base,
             LineDisplayItemClass(ref line) => &line.base,
+            TextDecorationDisplayItemClass(ref decoration) => &decoration.base,
             ClipDisplayItemClass(ref clip) => &clip.base,
-            PseudoDisplayItemClass(ref base) => &**base,
+            PseudoDisplayItemClass(ref pseudo) => &pseudo.base,
         }
     }
 
@@ -708,8 +898,9 @@ impl DisplayItem {
             ImageDisplayItemClass(ref mut image_item) => &mut image_item.base,
             BorderDisplayItemClass(ref mut border) => &mut border.base,
             LineDisplayItemClass(ref mut line) => &mut line.base,
+            TextDecorationDisplayItemClass(ref mut decoration) => &mut decoration.base,
             ClipDisplayItemClass(ref mut clip) => &mut clip.base,
-            PseudoDisplayItemClass(ref mut base) => &mut **base,
+            PseudoDisplayItemClass(ref mut pseudo) => &mut pseudo.base,
         }
     }
 
@@ -725,6 +916,7 @@ impl DisplayItem {
             ImageDisplayItemClass(..) |
             BorderDisplayItemClass(..) |
             LineDisplayItemClass(..) |
+            TextDecorationDisplayItemClass(..) |
             PseudoDisplayItemClass(..) => EmptyDisplayItemIterator,
         }
     }
@@ -738,6 +930,7 @@ impl DisplayItem {
             ImageDisplayItemClass(..) |
             BorderDisplayItemClass(..) |
             LineDisplayItemClass(..) |
+            TextDecorationDisplayItemClass(..) |
             PseudoDisplayItemClass(..) => None,
         }
     }
@@ -763,6 +956,7 @@ impl fmt::Show for DisplayItem {
                 ImageDisplayItemClass(_) => "Image",
                 BorderDisplayItemClass(_) => "Border",
                 LineDisplayItemClass(_) => "Line",
+                TextDecorationDisplayItemClass(_) => "TextDecoration",
                 ClipDisplayItemClass(_) => "Clip",
                 PseudoDisplayItemClass(_) => "Pseudo",
             },
@@ -771,3 +965,18 @@ impl fmt::Show for DisplayItem {
         )
     }
 }
+
+/// Helpers for the tests of the display list modules.
+#[cfg(test)]
+mod test_helpers {
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+    use servo_util::geometry::Au;
+
+    /// Returns a rectangle with the given origin and size in pixels.
+    pub fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(Point2D(Au::from_px(x), Au::from_px(y)),
+             Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+}
@@ -5,6 +5,7 @@
 use display_list::{BorderDisplayItemClass, ClipDisplayItem, ClipDisplayItemClass, DisplayItem};
 use display_list::{DisplayList, ImageDisplayItemClass, LineDisplayItemClass};
 use display_list::{PseudoDisplayItemClass, SolidColorDisplayItemClass, TextDisplayItemClass};
+use display_list::TextDecorationDisplayItemClass;
 
 use collections::dlist::DList;
 use geom::rect::Rect;
@@ -27,7 +28,11 @@ impl DisplayListOptimizer {
     }
 
     pub fn optimize(self) -> DisplayList {
-        self.process_display_list(&*self.display_list)
+        // Indexed display lists can find the visible items without visiting the others.
+        match self.display_list.display_list_for_rect(&self.visible_rect) {
+            Some(display_list) => display_list,
+            None => self.process_display_list(&*self.display_list),
+        }
     }
 
     fn process_display_list(&self, display_list: &DisplayList) -> DisplayList {
@@ -38,9 +43,7 @@ impl DisplayListOptimizer {
                 Some(display_item) => result.push(display_item),
             }
         }
-        DisplayList {
-            list: result,
-        }
+        DisplayList::from_items(result)
     }
 
     fn process_display_item(&self, display_item: &DisplayItem) -> Option<DisplayItem> {
@@ -64,7 +67,7 @@ impl DisplayListOptimizer {
 
             BorderDisplayItemClass(_) | ImageDisplayItemClass(_) | LineDisplayItemClass(_) |
             PseudoDisplayItemClass(_) | SolidColorDisplayItemClass(_) |
-            TextDisplayItemClass(_) => {
+            TextDecorationDisplayItemClass(_) | TextDisplayItemClass(_) => {
                 Some((*display_item).clone())
             }
         }
@@ -0,0 +1,398 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Serializes display lists to JSON, so that what layout produced can be read and compared
+//! without painting it.
+//!
+//! The output is meant to be stable across runs: object keys are sorted, lengths are integral
+//! app units, colors are RGBA bytes, and node addresses are left out. Layer IDs are the exception,
+//! since they are made from the addresses of the nodes that the layers are for.
+
+use color::Color;
+use display_list::{BackgroundAndBordersStackingLevel, BlockBackgroundsAndBordersStackingLevel};
+use display_list::{BaseDisplayItem, BorderDisplayItemClass, ClipDisplayItemClass};
+use display_list::{ContentStackingLevel, DisplayItem, DisplayList, FloatStackingLevel};
+use display_list::{ImageDisplayItemClass, LineDisplayItemClass};
+use display_list::{PositionedDescendantStackingLevel, PseudoDisplayItemClass, SidewaysLeft};
+use display_list::{SidewaysRight, SolidColorDisplayItemClass, StackingLevel};
+use display_list::{TextDecorationDisplayItemClass, TextDisplayItemClass, TextOrientation};
+use display_list::Upright;
+use render_task::LayerDisplayList;
+
+use azure::AzFloat;
+use collections::TreeMap;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::side_offsets::SideOffsets2D;
+use geom::size::Size2D;
+use serialize::json::{Json, ToJson};
+use servo_msg::compositor_msg::LayerId;
+use servo_util::geometry::Au;
+
+/// Returns the given display list as indented JSON.
+pub fn serialize(display_list: &DisplayList) -> String {
+    display_list.to_json().to_pretty_str()
+}
+
+/// Returns the display list of a page and those of its layers as indented JSON.
+pub fn serialize_with_layers(display_list: &DisplayList, layers: &[LayerDisplayList]) -> String {
+    let mut object = TreeMap::new();
+    object.insert("display_list".to_string(), display_list.to_json());
+    object.insert("layers".to_string(),
+                  layers.iter().map(|layer| layer.to_json()).collect::<Vec<Json>>().to_json());
+    object.to_json().to_pretty_str()
+}
+
+/// Layers are written with their ID as a pair of numbers and their position in pixels.
+impl ToJson for LayerDisplayList {
+    fn to_json(&self) -> Json {
+        let LayerId(first, second) = self.id;
+        let mut position = TreeMap::new();
+        position.insert("x".to_string(), self.position.origin.x.to_json());
+        position.insert("y".to_string(), self.position.origin.y.to_json());
+        position.insert("width".to_string(), self.position.size.width.to_json());
+        position.insert("height".to_string(), self.position.size.height.to_json());
+
+        let mut object = TreeMap::new();
+        object.insert("id".to_string(), vec!(first, second).to_json());
+        object.insert("position".to_string(), position.to_json());
+        object.insert("display_list".to_string(), self.display_list.to_json());
+        object.to_json()
+    }
+}
+
+impl ToJson for DisplayList {
+    fn to_json(&self) -> Json {
+        self.list.iter().map(|item| item.to_json()).collect::<Vec<Json>>().to_json()
+    }
+}
+
+impl ToJson for DisplayItem {
+    fn to_json(&self) -> Json {
+        let mut object = base_object(self.base());
+        let item_type = match *self {
+            SolidColorDisplayItemClass(ref solid_color) => {
+                object.insert("color".to_string(), color_to_json(&solid_color.color));
+                "solid_color"
+            }
+            TextDisplayItemClass(ref text) => {
+                let (begin, end) = (text.range.begin().to_uint(), text.range.end().to_uint());
+                let mut range = TreeMap::new();
+                range.insert("begin".to_string(), begin.to_json());
+                range.insert("length".to_string(), (end - begin).to_json());
+                object.insert("range".to_string(), range.to_json());
+                object.insert("text".to_string(),
+                              str_to_json(text.text_run.text.as_slice().slice_chars(begin, end)));
+                object.insert("hyphenated".to_string(), text.hyphenated.to_json());
+                object.insert("color".to_string(), color_to_json(&text.text_color));
+                object.insert("baseline_origin".to_string(), point_to_json(&text.baseline_origin));
+                object.insert("orientation".to_string(),
+                              orientation_to_json(&text.orientation));
+                "text"
+            }
+            ImageDisplayItemClass(ref image) => {
+                object.insert("url".to_string(), image.url.serialize().to_json());
+                object.insert("stretch_size".to_string(), size_to_json(&image.stretch_size));
+                for animation in image.animation.iter() {
+                    object.insert("frame_count".to_string(), animation.frames.len().to_json());
+                }
+                "image"
+            }
+            BorderDisplayItemClass(ref border) => {
+                object.insert("widths".to_string(),
+                              side_offsets_to_json(&border.border, |&width| au_to_json(width)));
+                object.insert("colors".to_string(),
+                              side_offsets_to_json(&border.color, |color| color_to_json(color)));
+                object.insert("styles".to_string(),
+                              side_offsets_to_json(&border.style, |style| {
+                                  str_to_json(style.to_css_keyword())
+                              }));
+                "border"
+            }
+            LineDisplayItemClass(ref line) => {
+                object.insert("color".to_string(), color_to_json(&line.color));
+                object.insert("style".to_string(), str_to_json(line.style.to_css_keyword()));
+                "line"
+            }
+            TextDecorationDisplayItemClass(ref decoration) => {
+                object.insert("color".to_string(), color_to_json(&decoration.color));
+                object.insert("style".to_string(), str_to_json(decoration.style.to_css_keyword()));
+                object.insert("thickness".to_string(), au_to_json(decoration.thickness));
+                "text_decoration"
+            }
+            ClipDisplayItemClass(ref clip) => {
+                object.insert("children".to_string(), clip.children.to_json());
+                "clip"
+            }
+            PseudoDisplayItemClass(ref pseudo) => {
+                object.insert("cursor".to_string(), str_to_json(pseudo.cursor.to_css_keyword()));
+                "pseudo"
+            }
+        };
+        object.insert("type".to_string(), str_to_json(item_type));
+        object.to_json()
+    }
+}
+
+/// Returns the fields that every display item has. The node is left out, since its address
+/// changes from run to run.
+fn base_object(base: &BaseDisplayItem) -> TreeMap<String, Json> {
+    let mut object = TreeMap::new();
+    object.insert("bounds".to_string(), rect_to_json(&base.bounds));
+    object.insert("level".to_string(), stacking_level_to_json(&base.level));
+    object
+}
+
+fn str_to_json(string: &str) -> Json {
+    string.to_string().to_json()
+}
+
+fn au_to_json(Au(length): Au) -> Json {
+    (length as i64).to_json()
+}
+
+fn point_to_json(point: &Point2D<Au>) -> Json {
+    let mut object = TreeMap::new();
+    object.insert("x".to_string(), au_to_json(point.x));
+    object.insert("y".to_string(), au_to_json(point.y));
+    object.to_json()
+}
+
+fn size_to_json(size: &Size2D<Au>) -> Json {
+    let mut object = TreeMap::new();
+    object.insert("width".to_string(), au_to_json(size.width));
+    object.insert("height".to_string(), au_to_json(size.height));
+    object.to_json()
+}
+
+fn rect_to_json(rect: &Rect<Au>) -> Json {
+    let mut object = TreeMap::new();
+    object.insert("x".to_string(), au_to_json(rect.origin.x));
+    object.insert("y".to_string(), au_to_json(rect.origin.y));
+    object.insert("width".to_string(), au_to_json(rect.size.width));
+    object.insert("height".to_string(), au_to_json(rect.size.height));
+    object.to_json()
+}
+
+fn side_offsets_to_json<T>(offsets: &SideOffsets2D<T>, side_to_json: |&T| -> Json) -> Json {
+    let mut object = TreeMap::new();
+    object.insert("top".to_string(), side_to_json(&offsets.top));
+    object.insert("right".to_string(), side_to_json(&offsets.right));
+    object.insert("bottom".to_string(), side_to_json(&offsets.bottom));
+    object.insert("left".to_string(), side_to_json(&offsets.left));
+    object.to_json()
+}
+
+/// Colors are written as `[red, green, blue, alpha]`, each from 0 to 255.
+fn color_to_json(color: &Color) -> Json {
+    let to_byte = |component: AzFloat| (component * 255.0).round() as i64;
+    vec!(to_byte(color.r), to_byte(color.g), to_byte(color.b), to_byte(color.a)).to_json()
+}
+
+fn stacking_level_to_json(level: &StackingLevel) -> Json {
+    match *level {
+        BackgroundAndBordersStackingLevel => str_to_json("background_and_borders"),
+        BlockBackgroundsAndBordersStackingLevel => str_to_json("block_backgrounds_and_borders"),
+        FloatStackingLevel => str_to_json("float"),
+        ContentStackingLevel => str_to_json("content"),
+        PositionedDescendantStackingLevel(z_index) => {
+            format!("positioned_descendant({})", z_index).to_json()
+        }
+    }
+}
+
+fn orientation_to_json(orientation: &TextOrientation) -> Json {
+    str_to_json(match *orientation {
+        Upright => "upright",
+        SidewaysLeft => "sideways_left",
+        SidewaysRight => "sideways_right",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serialize, serialize_with_layers};
+
+    use display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass};
+    use display_list::{ClipDisplayItem, ClipDisplayItemClass, ContentStackingLevel, DisplayList};
+    use display_list::{FloatStackingLevel, ImageDisplayItem, ImageDisplayItemClass};
+    use display_list::{LineDisplayItem, LineDisplayItemClass, OpaqueNode, PseudoDisplayItem};
+    use display_list::{PseudoDisplayItemClass, SolidColorDisplayItem};
+    use display_list::{SolidColorDisplayItemClass, TextDecorationDisplayItem};
+    use display_list::{TextDecorationDisplayItemClass, TextDisplayItem, TextDisplayItemClass};
+    use display_list::Upright;
+    use display_list::test_helpers::rect;
+    use font::FontMetrics;
+    use platform::font_template::FontTemplateData;
+    use render_task::LayerDisplayList;
+    use text::TextRun;
+    use text::glyph::CharIndex;
+    use text::script::Latin;
+
+    use azure::azure_hl::Color;
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::side_offsets::SideOffsets2D;
+    use geom::size::Size2D;
+    use serialize::json;
+    use servo_msg::compositor_msg::LayerId;
+    use servo_net::image::base::Image;
+    use servo_util::cursor::PointerCursor;
+    use servo_util::geometry::Au;
+    use servo_util::range::Range;
+    use style::computed_values::{border_style, text_decoration_style};
+    use sync::Arc;
+    use url::Url;
+
+    fn base(x: int, y: int, width: int, height: int) -> BaseDisplayItem {
+        BaseDisplayItem::new(rect(x, y, width, height), OpaqueNode(1), ContentStackingLevel)
+    }
+
+    /// Returns a text run of the given text that has no glyphs, which is enough to serialize it.
+    fn text_run(text: &str) -> Arc<Box<TextRun>> {
+        let zero = Au(0);
+        Arc::new(box TextRun {
+            text: Arc::new(text.to_string()),
+            font_template: Arc::new(FontTemplateData::new("test", Some(vec!()))),
+            actual_pt_size: 16.0,
+            font_metrics: FontMetrics {
+                underline_size: zero,
+                underline_offset: zero,
+                strikeout_size: zero,
+                strikeout_offset: zero,
+                leading: zero,
+                x_height: zero,
+                em_size: zero,
+                ascent: zero,
+                descent: zero,
+                max_advance: zero,
+                average_advance: zero,
+                line_gap: zero,
+            },
+            script: Latin,
+            glyphs: Arc::new(vec!()),
+            hyphen_glyph: None,
+            hyphen_advance: zero,
+        })
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut clipped = DisplayList::new();
+        clipped.push(PseudoDisplayItemClass(box PseudoDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 5, 5), OpaqueNode(2), ContentStackingLevel),
+            cursor: PointerCursor,
+        }));
+        let mut list = DisplayList::new();
+        list.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 10, 20), OpaqueNode(1), ContentStackingLevel),
+            color: Color::new(1.0, 0.0, 0.0, 1.0),
+        }));
+        list.push(ClipDisplayItemClass(box ClipDisplayItem::new(
+            BaseDisplayItem::new(rect(1, 2, 3, 4), OpaqueNode(1), ContentStackingLevel),
+            clipped)));
+
+        let expected = "[\
+            {\"bounds\":{\"height\":1200,\"width\":600,\"x\":0,\"y\":0},\
+             \"color\":[255,0,0,255],\"level\":\"content\",\"type\":\"solid_color\"},\
+            {\"bounds\":{\"height\":240,\"width\":180,\"x\":60,\"y\":120},\
+             \"children\":[{\"bounds\":{\"height\":300,\"width\":300,\"x\":0,\"y\":0},\
+                            \"cursor\":\"pointer\",\"level\":\"content\",\"type\":\"pseudo\"}],\
+             \"level\":\"content\",\"type\":\"clip\"}]";
+        let expected = json::from_str(expected).unwrap();
+        assert_eq!(json::from_str(serialize(&list).as_slice()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_serialize_every_item_type() {
+        let black = Color::new(0.0, 0.0, 0.0, 1.0);
+        let mut list = DisplayList::new();
+        list.push(TextDisplayItemClass(box TextDisplayItem {
+            base: base(0, 0, 30, 10),
+            text_run: text_run("Hello"),
+            range: Range::new(CharIndex(1), CharIndex(3)),
+            hyphenated: true,
+            text_color: black,
+            baseline_origin: Point2D(Au::from_px(0), Au::from_px(8)),
+            orientation: Upright,
+        }));
+        list.push(ImageDisplayItemClass(box ImageDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 2, 2), OpaqueNode(1), FloatStackingLevel),
+            image: Arc::new(Image::new(1, 1, vec!(0, 0, 0, 255))),
+            url: Url::parse("http://example.com/a.png").unwrap(),
+            animation: None,
+            stretch_size: Size2D(Au::from_px(1), Au::from_px(1)),
+        }));
+        list.push(BorderDisplayItemClass(box BorderDisplayItem {
+            base: base(0, 0, 10, 10),
+            border: SideOffsets2D::new(Au::from_px(1), Au::from_px(2), Au::from_px(3),
+                                       Au::from_px(4)),
+            color: SideOffsets2D::new_all_same(black),
+            style: SideOffsets2D::new_all_same(border_style::solid),
+        }));
+        list.push(LineDisplayItemClass(box LineDisplayItem {
+            base: base(0, 5, 10, 1),
+            color: black,
+            style: border_style::dashed,
+        }));
+        list.push(TextDecorationDisplayItemClass(box TextDecorationDisplayItem {
+            base: base(0, 9, 30, 3),
+            color: black,
+            style: text_decoration_style::wavy,
+            thickness: Au::from_px(1),
+        }));
+
+        let expected = "[\
+            {\"baseline_origin\":{\"x\":0,\"y\":480},\
+             \"bounds\":{\"height\":600,\"width\":1800,\"x\":0,\"y\":0},\
+             \"color\":[0,0,0,255],\"hyphenated\":true,\"level\":\"content\",\
+             \"orientation\":\"upright\",\"range\":{\"begin\":1,\"length\":3},\
+             \"text\":\"ell\",\"type\":\"text\"},\
+            {\"bounds\":{\"height\":120,\"width\":120,\"x\":0,\"y\":0},\
+             \"level\":\"float\",\"stretch_size\":{\"height\":60,\"width\":60},\
+             \"type\":\"image\",\"url\":\"http://example.com/a.png\"},\
+            {\"bounds\":{\"height\":600,\"width\":600,\"x\":0,\"y\":0},\
+             \"colors\":{\"bottom\":[0,0,0,255],\"left\":[0,0,0,255],\
+                         \"right\":[0,0,0,255],\"top\":[0,0,0,255]},\
+             \"level\":\"content\",\
+             \"styles\":{\"bottom\":\"solid\",\"left\":\"solid\",\"right\":\"solid\",\
+                         \"top\":\"solid\"},\
+             \"type\":\"border\",\
+             \"widths\":{\"bottom\":180,\"left\":240,\"right\":120,\"top\":60}},\
+            {\"bounds\":{\"height\":60,\"width\":600,\"x\":0,\"y\":300},\
+             \"color\":[0,0,0,255],\"level\":\"content\",\"style\":\"dashed\",\
+             \"type\":\"line\"},\
+            {\"bounds\":{\"height\":180,\"width\":1800,\"x\":0,\"y\":540},\
+             \"color\":[0,0,0,255],\"level\":\"content\",\"style\":\"wavy\",\
+             \"thickness\":60,\"type\":\"text_decoration\"}]";
+        let expected = json::from_str(expected).unwrap();
+        assert_eq!(json::from_str(serialize(&list).as_slice()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_serialize_with_layers() {
+        let mut layer_list = DisplayList::new();
+        layer_list.push(PseudoDisplayItemClass(box PseudoDisplayItem {
+            base: base(0, 0, 1, 1),
+            cursor: PointerCursor,
+        }));
+        let layers = [LayerDisplayList {
+            id: LayerId(7, 1),
+            position: Rect(Point2D(10u, 20), Size2D(30u, 40)),
+            display_list: Arc::new(layer_list),
+        }];
+
+        let expected = "{\"display_list\":[],\
+                         \"layers\":[{\
+                             \"display_list\":[{\
+                                 \"bounds\":{\"height\":60,\"width\":60,\"x\":0,\"y\":0},\
+                                 \"cursor\":\"pointer\",\"level\":\"content\",\
+                                 \"type\":\"pseudo\"}],\
+                             \"id\":[7,1],\
+                             \"position\":{\"height\":40,\"width\":30,\"x\":10,\"y\":20}}]}";
+        let expected = json::from_str(expected).unwrap();
+        let serialized = serialize_with_layers(&DisplayList::new(), layers.as_slice());
+        assert_eq!(json::from_str(serialized.as_slice()).unwrap(), expected);
+    }
+}
@@ -0,0 +1,325 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A grid over the items of a finished display list, so that tiles can replay only the items
+//! that they show and hit testing can find the items under a point without walking the whole
+//! list.
+//!
+//! Clip items are flattened away: each item is stored with the intersection of the clips that it
+//! is drawn in. Since clips are rectangles, drawing consecutive items with the same clip inside a
+//! single clip item paints the same as the nested clips did.
+//!
+//! The index refers to items by their position in `leaf_items()` of the display list it was built
+//! from, rather than holding copies of them, so lookups take that list.
+
+use display_list::{BaseDisplayItem, ClipDisplayItem, ClipDisplayItemClass, DisplayItem};
+use display_list::DisplayList;
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use std::cmp;
+
+/// The smallest side of a grid cell, in pixels.
+static MIN_CELL_SIZE_PX: int = 256;
+
+/// The most cells along either side of the grid. Larger lists get larger cells.
+static MAX_CELLS_PER_SIDE: i32 = 64;
+
+/// A display item that is not a clip, with the clip that it is drawn in.
+#[deriving(Clone)]
+struct IndexedItem {
+    /// The position of the item in the leaf items of the display list.
+    leaf_index: uint,
+    /// The intersection of the clips around the item, if it is clipped.
+    clip: Option<Rect<Au>>,
+    /// The part of the item that the clips leave visible.
+    visible_bounds: Rect<Au>,
+}
+
+/// A uniform grid of the visible items of a display list.
+#[deriving(Clone)]
+pub struct SpatialIndex {
+    /// The items in painting order.
+    items: Vec<IndexedItem>,
+    /// The page coordinates of the top left corner of the grid.
+    origin: Point2D<Au>,
+    /// The width and height of each cell.
+    cell_size: Au,
+    columns: uint,
+    rows: uint,
+    /// The indices in `items` of the items that overlap each cell, in ascending order. Cells are
+    /// stored row by row.
+    cells: Vec<Vec<uint>>,
+}
+
+impl SpatialIndex {
+    /// Builds an index over a flattened display list.
+    pub fn new(display_list: &DisplayList) -> SpatialIndex {
+        let mut items = vec!();
+        collect_items(display_list, None, &mut 0, &mut items);
+
+        let grid_bounds = items.iter().fold(None, |bounds: Option<Rect<Au>>, indexed_item| {
+            match bounds {
+                None => Some(indexed_item.visible_bounds),
+                Some(bounds) => Some(bounds.union(&indexed_item.visible_bounds)),
+            }
+        }).unwrap_or(Rect(Point2D(Au(0), Au(0)), Size2D(Au(0), Au(0))));
+
+        let longest_side = Au::max(grid_bounds.size.width, grid_bounds.size.height);
+        let cell_size = Au::max(Au::from_px(MIN_CELL_SIZE_PX),
+                                longest_side / MAX_CELLS_PER_SIDE + Au(1));
+        let Au(cell_length) = cell_size;
+        let cells_along = |Au(length): Au| (length / cell_length) as uint + 1;
+        let (columns, rows) = (cells_along(grid_bounds.size.width),
+                               cells_along(grid_bounds.size.height));
+
+        let mut index = SpatialIndex {
+            items: vec!(),
+            origin: grid_bounds.origin,
+            cell_size: cell_size,
+            columns: columns,
+            rows: rows,
+            cells: Vec::from_fn(columns * rows, |_| vec!()),
+        };
+        for (item_index, indexed_item) in items.iter().enumerate() {
+            let (first_column, first_row, last_column, last_row) =
+                index.cells_for_rect(&indexed_item.visible_bounds);
+            for row in range(first_row, last_row + 1) {
+                for column in range(first_column, last_column + 1) {
+                    index.cells.get_mut(row * columns + column).push(item_index)
+                }
+            }
+        }
+        index.items = items;
+        index
+    }
+
+    /// Returns the columns and rows of the first and last cells that the given rectangle
+    /// overlaps, clamped to the grid.
+    fn cells_for_rect(&self, rect: &Rect<Au>) -> (uint, uint, uint, uint) {
+        let Au(cell_length) = self.cell_size;
+        let cell_along = |Au(offset): Au, count: uint| {
+            if offset < 0 {
+                0
+            } else {
+                cmp::min((offset / cell_length) as uint, count - 1)
+            }
+        };
+        (cell_along(rect.origin.x - self.origin.x, self.columns),
+         cell_along(rect.origin.y - self.origin.y, self.rows),
+         cell_along(rect.max_x() - self.origin.x, self.columns),
+         cell_along(rect.max_y() - self.origin.y, self.rows))
+    }
+
+    /// Returns the indices of the items that may overlap the given rectangle, in painting order.
+    fn candidates_for_rect(&self, rect: &Rect<Au>) -> Vec<uint> {
+        let (first_column, first_row, last_column, last_row) = self.cells_for_rect(rect);
+        let mut candidates = vec!();
+        for row in range(first_row, last_row + 1) {
+            for column in range(first_column, last_column + 1) {
+                candidates.push_all(self.cells[row * self.columns + column].as_slice())
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Returns a display list of the items that show in the given rectangle, for painting a tile.
+    /// `leaf_items` are those of the display list that the index was built from.
+    pub fn display_list_for_rect(&self, leaf_items: &[&DisplayItem], rect: &Rect<Au>)
+                                 -> DisplayList {
+        let mut result = DisplayList::new();
+        let mut clipped_items: Option<(Rect<Au>, DisplayList)> = None;
+        for &item_index in self.candidates_for_rect(rect).iter() {
+            let indexed_item = &self.items[item_index];
+            if !indexed_item.visible_bounds.intersects(rect) {
+                continue
+            }
+
+            // Consecutive items with the same clip share a clip item.
+            let same_clip = match (&clipped_items, indexed_item.clip) {
+                (&Some((ref clip, _)), Some(ref item_clip)) => clip == item_clip,
+                _ => false,
+            };
+            if !same_clip {
+                flush_clipped_items(&mut result, clipped_items.take());
+            }
+            match indexed_item.clip {
+                None => result.push(leaf_items[indexed_item.leaf_index].clone()),
+                Some(clip) => {
+                    if clipped_items.is_none() {
+                        clipped_items = Some((clip, DisplayList::new()))
+                    }
+                    match clipped_items {
+                        Some((_, ref mut items)) => {
+                            items.push(leaf_items[indexed_item.leaf_index].clone())
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+        flush_clipped_items(&mut result, clipped_items);
+        result
+    }
+
+    /// Returns the items that are visible at the given point, topmost first. `leaf_items` are
+    /// those of the display list that the index was built from.
+    pub fn items_at_point<'a>(&self, leaf_items: &[&'a DisplayItem], point: &Point2D<Au>)
+                              -> Vec<&'a DisplayItem> {
+        let rect = Rect(*point, Size2D(Au(0), Au(0)));
+        self.candidates_for_rect(&rect).iter().rev().map(|&item_index| {
+            &self.items[item_index]
+        }).filter(|indexed_item| {
+            geometry::rect_contains_point(indexed_item.visible_bounds, *point)
+        }).map(|indexed_item| leaf_items[indexed_item.leaf_index]).collect()
+    }
+}
+
+/// Appends the items in `display_list` that are not clips and show to `items`, with the clips
+/// around them. `leaf_index` is the position in the leaf items of the next item that is not a
+/// clip.
+fn collect_items(display_list: &DisplayList,
+                 clip: Option<Rect<Au>>,
+                 leaf_index: &mut uint,
+                 items: &mut Vec<IndexedItem>) {
+    for item in display_list.list.iter() {
+        let visible_bounds = match clip {
+            None => Some(item.bounds()),
+            Some(ref clip) => clip.intersection(&item.bounds()),
+        };
+
+        match (item, visible_bounds) {
+            (&ClipDisplayItemClass(ref clip_item), Some(visible_bounds)) => {
+                collect_items(&clip_item.children, Some(visible_bounds), leaf_index, items)
+            }
+            (&ClipDisplayItemClass(ref clip_item), None) => {
+                *leaf_index += clip_item.children.leaf_items().len()
+            }
+            (_, visible_bounds) => {
+                match visible_bounds {
+                    Some(visible_bounds) => {
+                        items.push(IndexedItem {
+                            leaf_index: *leaf_index,
+                            clip: clip,
+                            visible_bounds: visible_bounds,
+                        })
+                    }
+                    None => {}
+                }
+                *leaf_index += 1
+            }
+        }
+    }
+}
+
+/// Wraps the given items in a clip item and appends it to `display_list`.
+fn flush_clipped_items(display_list: &mut DisplayList,
+                       clipped_items: Option<(Rect<Au>, DisplayList)>) {
+    match clipped_items {
+        Some((clip, items)) => {
+            let (node, level) = {
+                let base = items.list.front().unwrap().base();
+                (base.node, base.level.clone())
+            };
+            let base = BaseDisplayItem::new(clip, node, level);
+            display_list.push(ClipDisplayItemClass(box ClipDisplayItem::new(base, items)))
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpatialIndex;
+
+    use display_list::{BaseDisplayItem, ClipDisplayItem, ClipDisplayItemClass};
+    use display_list::{ContentStackingLevel, DisplayItem, DisplayList, OpaqueNode};
+    use display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass};
+    use display_list::test_helpers::rect;
+
+    use azure::azure_hl::Color;
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use servo_util::geometry::Au;
+
+    fn solid_color(node: uint, bounds: Rect<Au>) -> DisplayItem {
+        SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(bounds, OpaqueNode(node), ContentStackingLevel),
+            color: Color::new(0.0, 0.0, 0.0, 1.0),
+        })
+    }
+
+    fn nodes(items: Vec<&DisplayItem>) -> Vec<uint> {
+        items.iter().map(|item| item.base().node.id() as uint).collect()
+    }
+
+    fn test_list() -> DisplayList {
+        let mut clipped = DisplayList::new();
+        clipped.push(solid_color(3, rect(0, 0, 1000, 1000)));
+        let mut list = DisplayList::new();
+        list.push(solid_color(1, rect(0, 0, 2000, 2000)));
+        list.push(solid_color(2, rect(1500, 1500, 100, 100)));
+        list.push(ClipDisplayItemClass(box ClipDisplayItem::new(
+            BaseDisplayItem::new(rect(0, 0, 100, 100), OpaqueNode(0), ContentStackingLevel),
+            clipped)));
+        list
+    }
+
+    #[test]
+    fn test_items_at_point() {
+        let list = test_list();
+        let leaf_items = list.leaf_items();
+        let index = SpatialIndex::new(&list);
+        let items_at_point = |x, y| {
+            nodes(index.items_at_point(leaf_items.as_slice(),
+                                       &Point2D(Au::from_px(x), Au::from_px(y))))
+        };
+        assert_eq!(items_at_point(50, 50), vec!(3, 1));
+        assert_eq!(items_at_point(500, 500), vec!(1));
+        assert_eq!(items_at_point(1550, 1550), vec!(2, 1));
+        assert_eq!(items_at_point(3000, 3000), vec!());
+    }
+
+    #[test]
+    fn test_display_list_for_rect() {
+        let list = test_list();
+        let leaf_items = list.leaf_items();
+        let index = SpatialIndex::new(&list);
+        let tile = index.display_list_for_rect(leaf_items.as_slice(), &rect(0, 0, 512, 512));
+        let items: Vec<&DisplayItem> = tile.list.iter().collect();
+        assert_eq!(items.len(), 2);
+        match *items[1] {
+            ClipDisplayItemClass(ref clip) => {
+                assert!(clip.base.bounds == rect(0, 0, 100, 100));
+                assert_eq!(clip.children.list.len(), 1);
+            }
+            _ => fail!("the clipped item should stay in its clip"),
+        }
+
+        let tile = index.display_list_for_rect(leaf_items.as_slice(), &rect(1024, 1024, 512, 512));
+        assert_eq!(nodes(tile.list.iter().collect()), vec!(1, 2));
+    }
+
+    #[test]
+    fn test_items_after_a_hidden_clip() {
+        // The items of a clip that hides them still count toward the positions of later items.
+        let mut clipped = DisplayList::new();
+        clipped.push(solid_color(1, rect(0, 0, 10, 10)));
+        let mut list = DisplayList::new();
+        list.push(ClipDisplayItemClass(box ClipDisplayItem::new(
+            BaseDisplayItem::new(rect(100, 100, 10, 10), OpaqueNode(0), ContentStackingLevel),
+            clipped)));
+        list.push(solid_color(2, rect(0, 0, 10, 10)));
+
+        let leaf_items = list.leaf_items();
+        let index = SpatialIndex::new(&list);
+        let point = Point2D(Au::from_px(5), Au::from_px(5));
+        assert_eq!(nodes(index.items_at_point(leaf_items.as_slice(), &point)), vec!(2));
+    }
+}
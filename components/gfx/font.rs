@@ -8,15 +8,17 @@ use std::string;
 use std::rc::Rc;
 use std::cell::RefCell;
 use servo_util::cache::{Cache, HashCache};
-use style::computed_values::{font_weight, font_style, font_variant};
+use style::computed_values::{font_weight, font_stretch, font_style, font_variant};
+use style::UnicodeRange;
 use sync::Arc;
 
 use servo_util::geometry::Au;
 use platform::font_context::FontContextHandle;
 use platform::font::{FontHandle, FontTable};
 use text::glyph::{GlyphStore, GlyphId};
-use text::shaping::ShaperMethods;
-use text::{Shaper, TextRun};
+use text::shaping::{ShaperMethods, ShapingOptions};
+use text::Shaper;
+use text::util::true_type_tag;
 use font_template::FontTemplateDescriptor;
 use platform::font_template::FontTemplateData;
 
@@ -33,9 +35,13 @@ pub trait FontHandleMethods {
     fn face_name(&self) -> String;
     fn is_italic(&self) -> bool;
     fn boldness(&self) -> font_weight::T;
+    fn stretchiness(&self) -> font_stretch::T;
 
     fn glyph_index(&self, codepoint: char) -> Option<GlyphId>;
     fn glyph_h_advance(&self, GlyphId) -> Option<FractionalPixel>;
+    /// Returns the bounding box of the glyph's ink, relative to its origin on the baseline, with
+    /// y pointing up.
+    fn glyph_ink_bounds(&self, GlyphId) -> Option<Rect<FractionalPixel>>;
     fn glyph_h_kerning(&self, GlyphId, GlyphId) -> FractionalPixel;
     fn get_metrics(&self) -> FontMetrics;
     fn get_table_for_tag(&self, FontTableTag) -> Option<FontTable>;
@@ -64,8 +70,30 @@ impl FontTableTagConversions for FontTableTag {
 
 pub trait FontTableMethods {
     fn with_buffer(&self, |*const u8, uint|);
+
+    /// Reads the big-endian 16-bit integer at `offset` in the table, or returns `None` if the
+    /// table is too short.
+    fn read_i16(&self, offset: uint) -> Option<i16> {
+        let mut result = None;
+        self.with_buffer(|buffer, length| {
+            if offset + 2 <= length {
+                unsafe {
+                    let high = *buffer.offset(offset as int) as u16;
+                    let low = *buffer.offset(offset as int + 1) as u16;
+                    result = Some((high << 8 | low) as i16)
+                }
+            }
+        });
+        result
+    }
 }
 
+/// The offsets of the fields of the `post` and `OS/2` tables that hold the decoration metrics.
+pub static POST_UNDERLINE_POSITION_OFFSET: uint = 8;
+pub static POST_UNDERLINE_THICKNESS_OFFSET: uint = 10;
+pub static OS2_STRIKEOUT_SIZE_OFFSET: uint = 26;
+pub static OS2_STRIKEOUT_POSITION_OFFSET: uint = 28;
+
 #[deriving(Clone)]
 pub struct FontMetrics {
     pub underline_size:   Au,
@@ -82,6 +110,27 @@ pub struct FontMetrics {
     pub line_gap:         Au,
 }
 
+impl FontMetrics {
+    /// Replaces decoration metrics that the font left at zero with values derived from the rest
+    /// of the metrics, so that lines are always drawn somewhere sensible.
+    pub fn fill_in_decoration_metrics(&mut self) {
+        if self.underline_size == Au(0) {
+            self.underline_size = Au::max(self.em_size / 14, Au::from_px(1));
+        }
+        if self.underline_offset == Au(0) {
+            self.underline_offset = -self.underline_size;
+        }
+        if self.strikeout_size == Au(0) {
+            self.strikeout_size = self.underline_size;
+        }
+        if self.strikeout_offset == Au(0) {
+            // Center the line on the middle of lowercase letters, or guess where that is.
+            let x_height = if self.x_height > Au(0) { self.x_height } else { self.ascent / 2 };
+            self.strikeout_offset = (x_height + self.strikeout_size) / 2;
+        }
+    }
+}
+
 // TODO(Issue #179): eventually this will be split into the specified
 // and used font styles.  specified contains uninterpreted CSS font
 // property values, while 'used' is attached to gfx::Font to descript
@@ -95,31 +144,67 @@ pub struct FontStyle {
     pub style: font_style::T,
     pub families: Vec<String>,
     pub variant: font_variant::T,
-    // TODO(Issue #198): font-stretch, text-decoration, size-adjust
+    pub stretch: font_stretch::T,
+    /// The aspect value to scale fonts to, from `font-size-adjust`.
+    pub size_adjust: Option<f64>,
+    /// The OpenType features to apply when shaping, in the order they should take effect.
+    pub features: Vec<FontFeature>,
+    /// The language of the text, from the `lang` attribute, if it is known.
+    pub language: Option<String>,
+    // TODO(Issue #198): text-decoration
 }
 
 pub type SpecifiedFontStyle = FontStyle;
 pub type UsedFontStyle = FontStyle;
 
+/// An OpenType feature to turn on or off while shaping, like `liga` or `tnum`.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub struct FontFeature {
+    pub tag: FontTableTag,
+    /// The value to set the feature to; 0 disables it, and 1 enables it or picks the first
+    /// alternate.
+    pub value: u32,
+}
+
+impl FontFeature {
+    /// Creates a feature from its four-character tag.
+    pub fn new(tag: &str, value: u32) -> FontFeature {
+        assert!(tag.len() == 4);
+        let bytes = tag.as_bytes();
+        FontFeature {
+            tag: true_type_tag(bytes[0] as char, bytes[1] as char, bytes[2] as char,
+                               bytes[3] as char),
+            value: value,
+        }
+    }
+}
+
 pub struct Font {
     pub handle: FontHandle,
     pub metrics: FontMetrics,
     pub variant: font_variant::T,
     pub descriptor: FontTemplateDescriptor,
     pub requested_pt_size: f64,
+    pub size_adjust: Option<f64>,
     pub actual_pt_size: f64,
+    /// The characters this font may be used for, from the `unicode-range` of web fonts.
+    pub unicode_range: Vec<UnicodeRange>,
     pub shaper: Option<Shaper>,
-    pub shape_cache: HashCache<String, Arc<GlyphStore>>,
+    /// Shaped text, keyed by the text and the options it was shaped with.
+    pub shape_cache: HashCache<(String, ShapingOptions), Arc<GlyphStore>>,
     pub glyph_advance_cache: HashCache<u32, FractionalPixel>,
+    pub glyph_ink_bounds_cache: HashCache<u32, Option<Rect<FractionalPixel>>>,
 }
 
 impl Font {
-    pub fn shape_text(&mut self, text: String, is_whitespace: bool) -> Arc<GlyphStore> {
+    pub fn shape_text(&mut self, text: String, is_whitespace: bool, options: &ShapingOptions)
+                      -> Arc<GlyphStore> {
         self.make_shaper();
         let shaper = &self.shaper;
-        self.shape_cache.find_or_create(&text, |txt| {
-            let mut glyphs = GlyphStore::new(text.as_slice().char_len() as int, is_whitespace);
-            shaper.as_ref().unwrap().shape_text(txt.as_slice(), &mut glyphs);
+        let char_len = text.as_slice().char_len();
+        self.shape_cache.find_or_create(&(text, options.clone()), |&(ref txt, ref options)| {
+            let mut glyphs = GlyphStore::new(char_len as int, is_whitespace, options.script.is_rtl());
+            shaper.as_ref().unwrap().shape_text(txt.as_slice(), options, &mut glyphs);
             Arc::new(glyphs)
         })
     }
@@ -158,6 +243,12 @@ impl Font {
         self.handle.glyph_index(codepoint)
     }
 
+    /// Returns true if this font may be used for the given character and has a glyph for it.
+    pub fn covers(&self, codepoint: char) -> bool {
+        self.unicode_range.iter().any(|range| range.contains(codepoint)) &&
+            self.glyph_index(codepoint).is_some()
+    }
+
     pub fn glyph_h_kerning(&mut self, first_glyph: GlyphId, second_glyph: GlyphId) -> FractionalPixel {
         self.handle.glyph_h_kerning(first_glyph, second_glyph)
     }
@@ -171,6 +262,13 @@ impl Font {
             }
         })
     }
+
+    pub fn glyph_ink_bounds(&mut self, glyph: GlyphId) -> Option<Rect<FractionalPixel>> {
+        let handle = &self.handle;
+        self.glyph_ink_bounds_cache.find_or_create(&glyph, |glyph| {
+            handle.glyph_ink_bounds(*glyph)
+        })
+    }
 }
 
 pub struct FontGroup {
@@ -184,11 +282,12 @@ impl FontGroup {
         }
     }
 
-    pub fn create_textrun(&self, text: String) -> TextRun {
-        assert!(self.fonts.len() > 0);
-
-        // TODO(Issue #177): Actually fall back through the FontGroup when a font is unsuitable.
-        TextRun::new(&mut *self.fonts[0].borrow_mut(), text.clone())
+    /// Returns the first font in this group that may be used for the given character and has a
+    /// glyph for it.
+    pub fn first_font_with_glyph(&self, codepoint: char) -> Option<Rc<RefCell<Font>>> {
+        self.fonts.iter().find(|font| font.borrow().covers(codepoint)).map(|font| {
+            font.clone()
+        })
     }
 }
 
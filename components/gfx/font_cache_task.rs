@@ -3,26 +3,36 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use platform::font_list::get_available_families;
+use platform::font_list::get_fallback_family_for_char;
 use platform::font_list::get_system_default_family;
 use platform::font_list::get_variations_for_family;
 use platform::font_list::get_last_resort_font_families;
 use platform::font_context::FontContextHandle;
 
+use std::ascii::StrAsciiExt;
 use std::collections::HashMap;
+use std::sync::atomics::{AtomicUint, SeqCst};
 use sync::Arc;
+use font::FontHandleMethods;
 use font_template::{FontTemplate, FontTemplateDescriptor};
+use platform::font::FontHandle;
 use platform::font_template::FontTemplateData;
 use servo_net::resource_task::{ResourceTask, load_whole_resource};
+use style::UnicodeRange;
 use url::Url;
+use woff::decode_web_font;
 
 /// A list of font templates that make up a given font family.
 struct FontFamily {
+    /// The name of the family, as the platform knows it.
+    name: String,
     templates: Vec<FontTemplate>,
 }
 
 impl FontFamily {
-    fn new() -> FontFamily {
+    fn new(name: String) -> FontFamily {
         FontFamily {
+            name: name,
             templates: vec!(),
         }
     }
@@ -36,10 +46,16 @@ impl FontFamily {
 
         // TODO(Issue #190): if not in the fast path above, do
         // expensive matching of weights, etc.
-        for template in self.templates.iter_mut() {
-            let maybe_template = template.get_if_matches(fctx, desc);
-            if maybe_template.is_some() {
-                return maybe_template;
+        for stretch in desc.fallback_stretches().into_iter() {
+            let desc = FontTemplateDescriptor {
+                stretch: stretch,
+                ..*desc
+            };
+            for template in self.templates.iter_mut() {
+                let maybe_template = template.get_if_matches(fctx, &desc);
+                if maybe_template.is_some() {
+                    return maybe_template;
+                }
             }
         }
 
@@ -68,16 +84,63 @@ impl FontFamily {
     }
 }
 
+/// A face of a web font family, as declared by an `@font-face` rule.
+#[deriving(Clone)]
+pub struct WebFontDeclaration {
+    pub family: String,
+    /// The URLs to download the face from, in order of preference.
+    pub sources: Vec<Url>,
+    pub descriptor: FontTemplateDescriptor,
+    pub unicode_range: Vec<UnicodeRange>,
+}
+
+impl PartialEq for WebFontDeclaration {
+    fn eq(&self, other: &WebFontDeclaration) -> bool {
+        self.family == other.family &&
+        self.sources == other.sources &&
+        self.descriptor.weight == other.descriptor.weight &&
+        self.descriptor.stretch == other.descriptor.stretch &&
+        self.descriptor.italic == other.descriptor.italic &&
+        self.unicode_range == other.unicode_range
+    }
+}
+
+/// A face of a web font family. It is only downloaded once text needs a character in its
+/// `unicode-range`.
+struct WebFontFace {
+    declaration: WebFontDeclaration,
+    /// The face once it has been downloaded.
+    template: Option<FontTemplate>,
+    /// True if every source failed to load, so that they are not downloaded again.
+    failed: bool,
+    /// The number of stylesheets that declare this face. It is removed when none do anymore.
+    registrations: uint,
+}
+
+impl WebFontFace {
+    fn covers(&self, character: char) -> bool {
+        self.declaration.unicode_range.iter().any(|range| range.contains(character))
+    }
+}
+
+/// A font template, along with the characters it may be used for.
+pub struct FontTemplateInfo {
+    pub data: Arc<FontTemplateData>,
+    pub unicode_range: Vec<UnicodeRange>,
+}
+
 /// Commands that the FontContext sends to the font cache task.
 pub enum Command {
-    GetFontTemplate(String, FontTemplateDescriptor, Sender<Reply>),
-    AddWebFont(String, Url, Sender<()>),
+    GetFontTemplate(String, FontTemplateDescriptor, Option<char>, Sender<Reply>),
+    AddWebFont(WebFontDeclaration),
+    RemoveWebFont(WebFontDeclaration),
+    GetFallbackFamily(char, Sender<Option<String>>),
     Exit(Sender<()>),
 }
 
 /// Reply messages sent from the font cache task to the FontContext caller.
 pub enum Reply {
-    GetFontTemplateReply(Arc<FontTemplateData>),
+    GetFontTemplateReply(Option<FontTemplateInfo>),
 }
 
 /// The font cache task itself. It maintains a list of reference counted
@@ -85,19 +148,35 @@ pub enum Reply {
 struct FontCache {
     port: Receiver<Command>,
     generic_fonts: HashMap<String, String>,
+    /// The local font families, keyed by lowercase name since family names match
+    /// case-insensitively. Every localized name of a family is a key.
     local_families: HashMap<String, FontFamily>,
-    web_families: HashMap<String, FontFamily>,
+    /// The faces of each web font family, keyed by lowercase name like local families.
+    web_families: HashMap<String, Vec<WebFontFace>>,
+    /// The system font family that covers each character looked up so far, if any does.
+    fallback_families: HashMap<char, Option<String>>,
     font_context: FontContextHandle,
     resource_task: ResourceTask,
 }
 
+/// Maps a generic family to the family given by the user, or else the one the system prefers, or
+/// else `mapped_name`.
 fn add_generic_font(generic_fonts: &mut HashMap<String, String>,
+                    overrides: &[(String, String)],
                     generic_name: &str, mapped_name: &str) {
-    let opt_system_default = get_system_default_family(generic_name);
-    let family_name = match opt_system_default {
-        Some(system_default) => system_default,
-        None => mapped_name.to_string(),
+    let user_override = overrides.iter().find(|&&(ref generic, _)| {
+        generic.as_slice() == generic_name
+    });
+    let family_name = match user_override {
+        Some(&(_, ref family_name)) => family_name.clone(),
+        None => {
+            match get_system_default_family(generic_name) {
+                Some(system_default) => system_default,
+                None => mapped_name.to_string(),
+            }
+        }
     };
+    debug!("FontList: Using {} for {}", family_name, generic_name);
     generic_fonts.insert(generic_name.to_string(), family_name);
 }
 
@@ -107,31 +186,15 @@ impl FontCache {
             let msg = self.port.recv();
 
             match msg {
-                GetFontTemplate(family, descriptor, result) => {
-                    let maybe_font_template = self.get_font_template(&family, &descriptor);
-                    let font_template = match maybe_font_template {
-                        Some(font_template) => font_template,
-                        None => self.get_last_resort_template(&descriptor),
-                    };
-
-                    result.send(GetFontTemplateReply(font_template));
+                GetFontTemplate(family, descriptor, character, result) => {
+                    result.send(GetFontTemplateReply(self.get_font_template(&family,
+                                                                            &descriptor,
+                                                                            character)));
                 }
-                AddWebFont(family_name, url, result) => {
-                    let maybe_resource = load_whole_resource(&self.resource_task, url.clone());
-                    match maybe_resource {
-                        Ok((_, bytes)) => {
-                            if !self.web_families.contains_key(&family_name) {
-                                let family = FontFamily::new();
-                                self.web_families.insert(family_name.clone(), family);
-                            }
-                            let family = self.web_families.get_mut(&family_name);
-                            family.add_template(format!("{}", url).as_slice(), Some(bytes));
-                        },
-                        Err(_) => {
-                            debug!("Failed to load web font: family={} url={}", family_name, url);
-                        }
-                    }
-                    result.send(());
+                AddWebFont(declaration) => self.add_web_font(declaration),
+                RemoveWebFont(declaration) => self.remove_web_font(&declaration),
+                GetFallbackFamily(character, result) => {
+                    result.send(self.get_fallback_family(character));
                 }
                 Exit(result) => {
                     result.send(());
@@ -144,9 +207,10 @@ impl FontCache {
     fn refresh_local_families(&mut self) {
         self.local_families.clear();
         get_available_families(|family_name| {
-            if !self.local_families.contains_key(&family_name) {
-                let family = FontFamily::new();
-                self.local_families.insert(family_name, family);
+            let key = family_name.as_slice().to_ascii_lower();
+            if !self.local_families.contains_key(&key) {
+                let family = FontFamily::new(family_name);
+                self.local_families.insert(key, family);
             }
         });
     }
@@ -160,20 +224,19 @@ impl FontCache {
 
     fn find_font_in_local_family<'a>(&'a mut self, family_name: &String, desc: &FontTemplateDescriptor)
                                 -> Option<Arc<FontTemplateData>> {
-        // TODO(Issue #188): look up localized font family names if canonical name not found
-        // look up canonical name
-        if self.local_families.contains_key(family_name) {
+        let family_name = family_name.as_slice().to_ascii_lower();
+        if self.local_families.contains_key(&family_name) {
             debug!("FontList: Found font family with name={:s}", family_name.to_string());
-            let s = self.local_families.get_mut(family_name);
+            let s = self.local_families.get_mut(&family_name);
 
             if s.templates.len() == 0 {
-                get_variations_for_family(family_name.as_slice(), |path| {
+                let name = s.name.clone();
+                get_variations_for_family(name.as_slice(), |path| {
                     s.add_template(path.as_slice(), None);
                 });
             }
 
-            // TODO(Issue #192: handle generic font families, like 'serif' and 'sans-serif'.
-            // if such family exists, try to match style to a font
+            // If such family exists, try to match style to a font.
             let result = s.find_font_for_style(desc, &self.font_context);
             if result.is_some() {
                 return result;
@@ -186,24 +249,186 @@ impl FontCache {
         }
     }
 
-    fn find_font_in_web_family<'a>(&'a mut self, family_name: &String, desc: &FontTemplateDescriptor)
-                                -> Option<Arc<FontTemplateData>> {
-        if self.web_families.contains_key(family_name) {
-            let family = self.web_families.get_mut(family_name);
-            let maybe_font = family.find_font_for_style(desc, &self.font_context);
-            maybe_font
-        } else {
-            None
+    fn add_web_font(&mut self, declaration: WebFontDeclaration) {
+        let key = declaration.family.as_slice().to_ascii_lower();
+        if !self.web_families.contains_key(&key) {
+            self.web_families.insert(key.clone(), vec!());
+        }
+        let faces = self.web_families.get_mut(&key);
+        match faces.iter_mut().find(|face| face.declaration == declaration) {
+            Some(face) => {
+                face.registrations += 1;
+                return
+            }
+            None => {}
+        }
+        faces.push(WebFontFace {
+            declaration: declaration,
+            template: None,
+            failed: false,
+            registrations: 1,
+        });
+    }
+
+    fn remove_web_font(&mut self, declaration: &WebFontDeclaration) {
+        let key = declaration.family.as_slice().to_ascii_lower();
+        let is_empty = match self.web_families.find_mut(&key) {
+            Some(faces) => {
+                match faces.iter().position(|face| face.declaration == *declaration) {
+                    Some(index) => {
+                        faces.get_mut(index).registrations -= 1;
+                        if faces[index].registrations == 0 {
+                            // This drops the font data, once no font made from it is in use.
+                            faces.remove(index);
+                        }
+                    }
+                    None => {}
+                }
+                faces.is_empty()
+            }
+            None => false,
+        };
+        if is_empty {
+            self.web_families.remove(&key);
         }
     }
 
-    fn get_font_template(&mut self, family: &String, desc: &FontTemplateDescriptor) -> Option<Arc<FontTemplateData>> {
+    /// Downloads the given face from the first of its sources that loads, unless that has
+    /// already been done.
+    fn load_web_font_face(&mut self, face: &mut WebFontFace) -> Option<Arc<FontTemplateData>> {
+        match face.template {
+            Some(ref mut template) => return template.get(),
+            None if face.failed => return None,
+            None => {}
+        }
+
+        for url in face.declaration.sources.iter() {
+            let bytes = match load_whole_resource(&self.resource_task, url.clone()) {
+                Ok((_, bytes)) => bytes,
+                Err(_) => {
+                    debug!("Failed to load web font: family={} url={}",
+                           face.declaration.family, url);
+                    continue
+                }
+            };
+            let bytes = match decode_web_font(bytes) {
+                Ok(bytes) => bytes,
+                Err(()) => {
+                    debug!("Failed to decode web font: family={} url={}",
+                           face.declaration.family, url);
+                    continue
+                }
+            };
+
+            let mut template = FontTemplate::new(format!("{}", url).as_slice(), Some(bytes));
+            let data = template.get_data();
+            let handle: Result<FontHandle, ()> =
+                FontHandleMethods::new_from_template(&self.font_context, data.clone(), None);
+            if handle.is_err() {
+                debug!("Unusable web font: family={} url={}", face.declaration.family, url);
+                continue
+            }
+            face.template = Some(template);
+            return Some(data)
+        }
+
+        face.failed = true;
+        None
+    }
+
+    /// Finds the face of a web font family that best matches the descriptor among the faces for
+    /// the given character, or for a space if there is none, downloading it if need be.
+    fn find_font_in_web_family<'a>(&'a mut self, family_name: &String,
+                                   desc: &FontTemplateDescriptor, character: Option<char>)
+                                   -> Option<FontTemplateInfo> {
+        let key = family_name.as_slice().to_ascii_lower();
+        let mut faces = match self.web_families.pop(&key) {
+            Some(faces) => faces,
+            None => return None,
+        };
+
+        // Try the faces for the character first, matching the descriptor as closely as
+        // `FontFamily::find_font_for_style` does. Without a character, fall back to any face.
+        let covered_character = character.unwrap_or(' ');
+        let mut candidates: Vec<uint> = vec!();
+        for stretch in desc.fallback_stretches().into_iter() {
+            let desc = FontTemplateDescriptor {
+                stretch: stretch,
+                ..*desc
+            };
+            for (index, face) in faces.iter().enumerate() {
+                if face.covers(covered_character) && face.declaration.descriptor == desc &&
+                        !candidates.contains(&index) {
+                    candidates.push(index)
+                }
+            }
+        }
+        for (index, face) in faces.iter().enumerate() {
+            if (face.covers(covered_character) || character.is_none()) &&
+                    !candidates.contains(&index) {
+                candidates.push(index)
+            }
+        }
+
+        let mut result = None;
+        for &index in candidates.iter() {
+            match self.load_web_font_face(faces.get_mut(index)) {
+                Some(data) => {
+                    result = Some(FontTemplateInfo {
+                        data: data,
+                        unicode_range: faces[index].declaration.unicode_range.clone(),
+                    });
+                    break
+                }
+                None => {}
+            }
+        }
+
+        self.web_families.insert(key, faces);
+        result
+    }
+
+    /// Returns the font of the family that best matches the descriptor. If a character is given,
+    /// the font may be used for it; `None` is returned if a web font family has no face for it,
+    /// so that the next family can be tried. Otherwise the last resort font is returned if the
+    /// family is not found.
+    fn get_font_template(&mut self, family: &String, desc: &FontTemplateDescriptor,
+                         character: Option<char>)
+                         -> Option<FontTemplateInfo> {
         let transformed_family_name = self.transform_family(family);
-        let mut maybe_template = self.find_font_in_web_family(&transformed_family_name, desc);
-        if maybe_template.is_none() {
-            maybe_template = self.find_font_in_local_family(&transformed_family_name, desc);
+        let maybe_template = self.find_font_in_web_family(&transformed_family_name, desc,
+                                                          character);
+        if maybe_template.is_some() {
+            return maybe_template
+        }
+
+        // Web font families hide local families of the same name.
+        let is_web_family = self.web_families.contains_key(
+            &transformed_family_name.as_slice().to_ascii_lower());
+        if is_web_family && character.is_some() {
+            return None
+        }
+
+        let data = match self.find_font_in_local_family(&transformed_family_name, desc) {
+            Some(data) => data,
+            None => self.get_last_resort_template(desc),
+        };
+        Some(FontTemplateInfo {
+            data: data,
+            unicode_range: vec!(UnicodeRange::all()),
+        })
+    }
+
+    fn get_fallback_family(&mut self, character: char) -> Option<String> {
+        match self.fallback_families.find(&character) {
+            Some(family) => return family.clone(),
+            None => {}
         }
-        maybe_template
+
+        let family = get_fallback_family_for_char(character);
+        debug!("FontList: Fallback family for {} is {}", character, family);
+        self.fallback_families.insert(character, family.clone());
+        family
     }
 
     fn get_last_resort_template(&mut self, desc: &FontTemplateDescriptor) -> Arc<FontTemplateData> {
@@ -225,26 +450,33 @@ impl FontCache {
 #[deriving(Clone)]
 pub struct FontCacheTask {
     chan: Sender<Command>,
+    /// Counts the changes to the set of web font faces, so that font contexts know when the
+    /// fonts they cached may no longer be the ones to use.
+    web_font_generation: Arc<AtomicUint>,
 }
 
 impl FontCacheTask {
-    pub fn new(resource_task: ResourceTask) -> FontCacheTask {
+    /// Creates the font cache task. `generic_font_families` maps generic family names to the
+    /// families the user wants for them.
+    pub fn new(resource_task: ResourceTask, generic_font_families: Vec<(String, String)>)
+               -> FontCacheTask {
         let (chan, port) = channel();
 
         spawn(proc() {
-            // TODO: Allow users to specify these.
+            let overrides = generic_font_families.as_slice();
             let mut generic_fonts = HashMap::with_capacity(5);
-            add_generic_font(&mut generic_fonts, "serif", "Times New Roman");
-            add_generic_font(&mut generic_fonts, "sans-serif", "Arial");
-            add_generic_font(&mut generic_fonts, "cursive", "Apple Chancery");
-            add_generic_font(&mut generic_fonts, "fantasy", "Papyrus");
-            add_generic_font(&mut generic_fonts, "monospace", "Menlo");
+            add_generic_font(&mut generic_fonts, overrides, "serif", "Times New Roman");
+            add_generic_font(&mut generic_fonts, overrides, "sans-serif", "Arial");
+            add_generic_font(&mut generic_fonts, overrides, "cursive", "Apple Chancery");
+            add_generic_font(&mut generic_fonts, overrides, "fantasy", "Papyrus");
+            add_generic_font(&mut generic_fonts, overrides, "monospace", "Menlo");
 
             let mut cache = FontCache {
                 port: port,
                 generic_fonts: generic_fonts,
                 local_families: HashMap::new(),
                 web_families: HashMap::new(),
+                fallback_families: HashMap::new(),
                 font_context: FontContextHandle::new(),
                 resource_task: resource_task,
             };
@@ -255,14 +487,19 @@ impl FontCacheTask {
 
         FontCacheTask {
             chan: chan,
+            web_font_generation: Arc::new(AtomicUint::new(0)),
         }
     }
 
-    pub fn get_font_template(&self, family: String, desc: FontTemplateDescriptor)
-                                                -> Arc<FontTemplateData> {
+    /// Returns the font of the given family that best matches the descriptor, or the last resort
+    /// font if there is no such family. If a character is given, `None` is returned when the
+    /// family is a web font family with no face for that character.
+    pub fn get_font_template(&self, family: String, desc: FontTemplateDescriptor,
+                             character: Option<char>)
+                             -> Option<FontTemplateInfo> {
 
         let (response_chan, response_port) = channel();
-        self.chan.send(GetFontTemplate(family, desc, response_chan));
+        self.chan.send(GetFontTemplate(family, desc, character, response_chan));
 
         let reply = response_port.recv();
 
@@ -273,10 +510,32 @@ impl FontCacheTask {
         }
     }
 
-    pub fn add_web_font(&self, family: String, url: Url) {
+    /// Makes a web font face available to every document. Nothing is downloaded until text
+    /// needs the face.
+    pub fn add_web_font(&self, declaration: WebFontDeclaration) {
+        self.chan.send(AddWebFont(declaration));
+        self.web_font_generation.fetch_add(1, SeqCst);
+    }
+
+    /// Undoes one `add_web_font` call for the given face, for when a document that declared it
+    /// goes away.
+    pub fn remove_web_font(&self, declaration: WebFontDeclaration) {
+        self.chan.send(RemoveWebFont(declaration));
+        self.web_font_generation.fetch_add(1, SeqCst);
+    }
+
+    /// Returns a number that changes whenever a web font face is added or removed. The change
+    /// takes effect for every request sent to the font cache task afterwards.
+    pub fn web_font_generation(&self) -> uint {
+        self.web_font_generation.load(SeqCst)
+    }
+
+    /// Returns the name of a local font family that has a glyph for the given character, for use
+    /// when no font in the requested families does.
+    pub fn get_fallback_family_for_char(&self, character: char) -> Option<String> {
         let (response_chan, response_port) = channel();
-        self.chan.send(AddWebFont(family, url, response_chan));
-        response_port.recv();
+        self.chan.send(GetFallbackFamily(character, response_chan));
+        response_port.recv()
     }
 
     pub fn exit(&self) {
@@ -285,3 +544,22 @@ impl FontCacheTask {
         response_port.recv();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::add_generic_font;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_add_generic_font_prefers_user_override() {
+        let overrides = [
+            ("serif".to_string(), "Fira Sans".to_string()),
+            ("monospace".to_string(), "DejaVu Sans Mono".to_string()),
+        ];
+        let mut generic_fonts = HashMap::new();
+        add_generic_font(&mut generic_fonts, overrides.as_slice(), "monospace", "Menlo");
+        assert_eq!(generic_fonts.find(&"monospace".to_string()),
+                   Some(&"DejaVu Sans Mono".to_string()));
+    }
+}
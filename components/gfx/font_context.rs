@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::{Font, FontGroup};
+use font::{Font, FontGroup, FontMetrics};
 use font::SpecifiedFontStyle;
 use platform::font_context::FontContextHandle;
 use style::computed_values::{font_style, font_variant};
@@ -12,8 +12,17 @@ use font_template::FontTemplateDescriptor;
 use platform::font_template::FontTemplateData;
 use font::FontHandleMethods;
 use platform::font::FontHandle;
+use text::glyph::CharIndex;
+use text::hyphenation::{Hyphenator, SOFT_HYPHEN, patterns_for_language};
+use text::script::{Common, Script, is_cluster_extender, script_for_char};
+use text::shaping::ShapingOptions;
+use text::text_run::TextRun;
 use servo_util::cache::HashCache;
+use servo_util::geometry::Au;
+use style::UnicodeRange;
+use servo_util::range::Range;
 
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::cell::RefCell;
 use sync::Arc;
@@ -43,6 +52,9 @@ static SMALL_CAPS_SCALE_FACTOR: f64 = 0.8;      // Matches FireFox (see gfxFont.
 struct LayoutFontCacheEntry {
     family: String,
     font: Rc<RefCell<Font>>,
+    /// True if the font cache task gave this face when no particular character was asked for,
+    /// which is the face used for the family's metrics and spaces.
+    is_family_default: bool,
 }
 
 /// A cached azure font (per render task) that
@@ -64,6 +76,21 @@ pub struct FontContext {
     /// TODO: See bug https://github.com/servo/servo/issues/3300.
     layout_font_cache: Vec<LayoutFontCacheEntry>,
 
+    /// The fallback family for each character that no font in its group had a glyph for, so that
+    /// the font cache task is asked about each character only once.
+    fallback_family_cache: HashMap<char, Option<String>>,
+
+    /// The characters that a web font family was found to have no face for, so that the font
+    /// cache task is asked about each one only once.
+    uncovered_characters: HashSet<(String, char)>,
+
+    /// The web font generation of the font cache task when the caches above were last cleared.
+    web_font_generation: uint,
+
+    /// The hyphenator for each language that `hyphens: auto` has been used with, or `None` if
+    /// there are no patterns for it.
+    hyphenators: HashMap<String, Option<Rc<Hyphenator>>>,
+
     /// Strong reference as the render FontContext is (for now) recycled
     /// per frame. TODO: Make this weak when incremental redraw is done.
     render_font_cache: Vec<RenderFontCacheEntry>,
@@ -72,10 +99,15 @@ pub struct FontContext {
 impl FontContext {
     pub fn new(font_cache_task: FontCacheTask) -> FontContext {
         let handle = FontContextHandle::new();
+        let web_font_generation = font_cache_task.web_font_generation();
         FontContext {
             platform_handle: handle,
             font_cache_task: font_cache_task,
             layout_font_cache: vec!(),
+            fallback_family_cache: HashMap::new(),
+            uncovered_characters: HashSet::new(),
+            web_font_generation: web_font_generation,
+            hyphenators: HashMap::new(),
             render_font_cache: vec!(),
         }
     }
@@ -83,18 +115,30 @@ impl FontContext {
     /// Create a font for use in layout calculations.
     fn create_layout_font(&self, template: Arc<FontTemplateData>,
                             descriptor: FontTemplateDescriptor, pt_size: f64,
-                            variant: font_variant::T) -> Font {
+                            size_adjust: Option<f64>, variant: font_variant::T,
+                            unicode_range: Vec<UnicodeRange>) -> Font {
         // TODO: (Bug #3463): Currently we only support fake small-caps
         // rendering. We should also support true small-caps (where the
         // font supports it) in the future.
-        let actual_pt_size = match variant {
+        let mut actual_pt_size = match variant {
             font_variant::small_caps => pt_size * SMALL_CAPS_SCALE_FACTOR,
             font_variant::normal => pt_size,
         };
 
-        let handle: FontHandle = FontHandleMethods::new_from_template(&self.platform_handle,
-                                    template, Some(actual_pt_size)).unwrap();
-        let metrics = handle.get_metrics();
+        let mut handle: FontHandle = FontHandleMethods::new_from_template(&self.platform_handle,
+                                    template.clone(), Some(actual_pt_size)).unwrap();
+        let mut metrics = handle.get_metrics();
+
+        match size_adjusted_pt_size(actual_pt_size, size_adjust, &metrics) {
+            Some(adjusted_pt_size) => {
+                actual_pt_size = adjusted_pt_size;
+                handle = FontHandleMethods::new_from_template(&self.platform_handle,
+                                                              template,
+                                                              Some(actual_pt_size)).unwrap();
+                metrics = handle.get_metrics();
+            }
+            None => {}
+        }
 
         Font {
             handle: handle,
@@ -102,53 +146,237 @@ impl FontContext {
             variant: variant,
             descriptor: descriptor,
             requested_pt_size: pt_size,
+            size_adjust: size_adjust,
             actual_pt_size: actual_pt_size,
+            unicode_range: unicode_range,
             metrics: metrics,
             shape_cache: HashCache::new(),
             glyph_advance_cache: HashCache::new(),
+            glyph_ink_bounds_cache: HashCache::new(),
         }
     }
 
+    /// Create a font of the given family for use in layout calculations. May return a cached
+    /// font if this font instance has already been used by this context. If a character is
+    /// given, the font is one that may be used for it, and `None` is returned if the family is a
+    /// web font family with no face for it.
+    fn get_layout_font(&mut self, family: &String, style: &SpecifiedFontStyle,
+                       character: Option<char>)
+                       -> Option<Rc<RefCell<Font>>> {
+        let desc = FontTemplateDescriptor::new(style.weight,
+                                               style.stretch,
+                                               style.style == font_style::italic);
+
+        // GWTODO: Check on real pages if this is faster as Vec() or HashMap().
+        for cached_font_entry in self.layout_font_cache.iter() {
+            if cached_font_entry.family == *family {
+                let cached_font = cached_font_entry.font.borrow();
+                let is_face_for_character = match character {
+                    Some(character) => {
+                        cached_font.unicode_range.iter().any(|range| range.contains(character))
+                    }
+                    None => cached_font_entry.is_family_default,
+                };
+                if is_face_for_character &&
+                   cached_font.descriptor == desc &&
+                   cached_font.requested_pt_size == style.pt_size &&
+                   cached_font.size_adjust == style.size_adjust &&
+                   cached_font.variant == style.variant {
+                    return Some(cached_font_entry.font.clone());
+                }
+            }
+        }
+
+        match character {
+            Some(character) if self.uncovered_characters.contains(&(family.clone(), character)) => {
+                return None
+            }
+            _ => {}
+        }
+
+        let font_template = match self.font_cache_task.get_font_template(family.clone(),
+                                                                         desc.clone(),
+                                                                         character) {
+            Some(font_template) => font_template,
+            None => {
+                match character {
+                    Some(character) => {
+                        self.uncovered_characters.insert((family.clone(), character));
+                    }
+                    None => {}
+                }
+                return None
+            }
+        };
+
+        // The face may already be cached under another character of its range.
+        for cached_font_entry in self.layout_font_cache.iter_mut() {
+            if cached_font_entry.family == *family {
+                let is_same_font = {
+                    let cached_font = cached_font_entry.font.borrow();
+                    cached_font.handle.get_template().identifier ==
+                        font_template.data.identifier &&
+                    cached_font.descriptor == desc &&
+                    cached_font.requested_pt_size == style.pt_size &&
+                    cached_font.size_adjust == style.size_adjust &&
+                    cached_font.variant == style.variant
+                };
+                if is_same_font {
+                    if character.is_none() {
+                        cached_font_entry.is_family_default = true
+                    }
+                    return Some(cached_font_entry.font.clone())
+                }
+            }
+        }
+
+        let layout_font = Rc::new(RefCell::new(self.create_layout_font(font_template.data,
+                                                desc.clone(), style.pt_size, style.size_adjust,
+                                                style.variant, font_template.unicode_range)));
+        self.layout_font_cache.push(LayoutFontCacheEntry {
+            family: family.clone(),
+            font: layout_font.clone(),
+            is_family_default: character.is_none(),
+        });
+        Some(layout_font)
+    }
+
+    /// Drops the fonts cached for web font families if a web font face was added or removed
+    /// since, so that text picks up the face that now matches it.
+    fn clear_caches_if_web_fonts_changed(&mut self) {
+        let web_font_generation = self.font_cache_task.web_font_generation();
+        if web_font_generation == self.web_font_generation {
+            return
+        }
+        self.web_font_generation = web_font_generation;
+        self.layout_font_cache.clear();
+        self.uncovered_characters.clear();
+    }
+
     /// Create a group of fonts for use in layout calculations. May return
     /// a cached font if this font instance has already been used by
     /// this context.
     pub fn get_layout_font_group_for_style(&mut self, style: &SpecifiedFontStyle) -> FontGroup {
+        self.clear_caches_if_web_fonts_changed();
+
         // TODO: The font context holds a strong ref to the cached fonts
         // so they will never be released. Find out a good time to drop them.
+        let fonts = style.families.iter().filter_map(|family| {
+            self.get_layout_font(family, style, None)
+        }).collect();
+        FontGroup::new(fonts)
+    }
 
-        let mut fonts: Vec<Rc<RefCell<Font>>> = vec!();
+    /// Returns the font that should render the given character: the first font in the group
+    /// that has a glyph for it, then a system font that does, and failing that the first font in
+    /// the group, which will draw it as a missing glyph.
+    fn get_layout_font_for_char(&mut self, fontgroup: &FontGroup, style: &SpecifiedFontStyle,
+                                character: char)
+                                -> Rc<RefCell<Font>> {
+        match fontgroup.first_font_with_glyph(character) {
+            Some(font) => return font,
+            None => {}
+        }
 
+        // Web font families may have other faces for the character than those in the group.
         for family in style.families.iter() {
-            let desc = FontTemplateDescriptor::new(style.weight, style.style == font_style::italic);
+            match self.get_layout_font(family, style, Some(character)) {
+                Some(ref font) if font.borrow().covers(character) => return font.clone(),
+                _ => {}
+            }
+        }
 
-            // GWTODO: Check on real pages if this is faster as Vec() or HashMap().
-            let mut cache_hit = false;
-            for cached_font_entry in self.layout_font_cache.iter() {
-                if cached_font_entry.family == *family {
-                    let cached_font = cached_font_entry.font.borrow();
-                    if cached_font.descriptor == desc &&
-                       cached_font.requested_pt_size == style.pt_size &&
-                       cached_font.variant == style.variant {
-                        fonts.push(cached_font_entry.font.clone());
-                        cache_hit = true;
-                        break;
-                    }
+        let fallback_family = match self.fallback_family_cache.find(&character) {
+            Some(family) => family.clone(),
+            None => {
+                let family = self.font_cache_task.get_fallback_family_for_char(character);
+                self.fallback_family_cache.insert(character, family.clone());
+                family
+            }
+        };
+
+        match fallback_family {
+            Some(family) => {
+                match self.get_layout_font(&family, style, Some(character)) {
+                    Some(font) => font,
+                    None => fontgroup.fonts[0].clone(),
                 }
             }
+            None => fontgroup.fonts[0].clone(),
+        }
+    }
 
-            if !cache_hit {
-                let font_template = self.font_cache_task.get_font_template(family.clone(), desc.clone());
-                let layout_font = Rc::new(RefCell::new(self.create_layout_font(font_template,
-                                                        desc.clone(), style.pt_size, style.variant)));
-                self.layout_font_cache.push(LayoutFontCacheEntry {
-                    family: family.clone(),
-                    font: layout_font.clone(),
-                });
-                fonts.push(layout_font);
+    /// Splits the given text into pieces that can each be rendered with a single font and are
+    /// written in a single script, and creates a text run for each piece. Returns the runs along
+    /// with the range of characters of `text` that each one covers.
+    pub fn create_text_runs(&mut self, style: &SpecifiedFontStyle, text: &str)
+                            -> Vec<(Range<CharIndex>, TextRun)> {
+        let fontgroup = self.get_layout_font_group_for_style(style);
+        assert!(fontgroup.fonts.len() > 0);
+
+        let pieces = split_into_runs(text, |character| {
+            LayoutFontRef(self.get_layout_font_for_char(&fontgroup, style, character))
+        }, |&LayoutFontRef(ref font), character| font.borrow().covers(character));
+        pieces.into_iter().map(|piece| {
+            let LayoutFontRef(font) = piece.font;
+            let (start_byte, end_byte) = piece.bytes;
+            let options = shaping_options(style, piece.script);
+            let run = TextRun::new(&mut *font.borrow_mut(),
+                                   text.slice(start_byte, end_byte).to_string(),
+                                   &options);
+            (piece.chars, run)
+        }).collect()
+    }
+
+    /// Returns a layout font for the given template at the given actual size, such as the one
+    /// that a text run was shaped with. May return a cached font if this font instance has
+    /// already been used by this context.
+    pub fn get_layout_font_from_template(&mut self, template: &Arc<FontTemplateData>,
+                                         pt_size: f64) -> Rc<RefCell<Font>> {
+        for cached_font_entry in self.layout_font_cache.iter() {
+            let cached_font = cached_font_entry.font.borrow();
+            if cached_font.actual_pt_size == pt_size &&
+               cached_font.handle.get_template().identifier == template.identifier {
+                return cached_font_entry.font.clone();
             }
         }
 
-        FontGroup::new(fonts)
+        // The run may have been shaped by another thread's context. Cache the font with no
+        // Unicode range so that `get_layout_font` never picks it for a family.
+        let handle: FontHandle = FontHandleMethods::new_from_template(&self.platform_handle,
+                                                                      template.clone(),
+                                                                      Some(pt_size)).unwrap();
+        let descriptor = FontTemplateDescriptor::new(handle.boldness(),
+                                                     handle.stretchiness(),
+                                                     handle.is_italic());
+        let family = handle.family_name();
+        let layout_font = Rc::new(RefCell::new(self.create_layout_font(template.clone(),
+                                                                       descriptor,
+                                                                       pt_size,
+                                                                       None,
+                                                                       font_variant::normal,
+                                                                       vec!())));
+        self.layout_font_cache.push(LayoutFontCacheEntry {
+            family: family,
+            font: layout_font.clone(),
+            is_family_default: false,
+        });
+        layout_font
+    }
+
+    /// Returns the hyphenator for the given language, or `None` if there are no hyphenation
+    /// patterns for it. The patterns for each language are read and parsed only once per context.
+    pub fn get_hyphenator(&mut self, language: &str) -> Option<Rc<Hyphenator>> {
+        match self.hyphenators.find(&language.to_string()) {
+            Some(hyphenator) => return hyphenator.clone(),
+            None => {}
+        }
+
+        let hyphenator = patterns_for_language(language).map(|patterns| {
+            Rc::new(Hyphenator::from_patterns(patterns.as_slice()))
+        });
+        self.hyphenators.insert(language.to_string(), hyphenator.clone());
+        hyphenator
     }
 
     /// Create a render font for use with azure. May return a cached
@@ -170,3 +398,212 @@ impl FontContext {
         render_font
     }
 }
+
+/// Returns the size to scale a font of the given size and metrics to so that its x-height is
+/// `size_adjust` times the size (CSS Fonts 3 § 3.6), or `None` if it needs no scaling.
+fn size_adjusted_pt_size(pt_size: f64, size_adjust: Option<f64>, metrics: &FontMetrics)
+                         -> Option<f64> {
+    match size_adjust {
+        Some(size_adjust) if metrics.x_height > Au(0) && metrics.em_size > Au(0) => {
+            let aspect = metrics.x_height.to_f64().unwrap() / metrics.em_size.to_f64().unwrap();
+            Some(pt_size * size_adjust / aspect)
+        }
+        _ => None,
+    }
+}
+
+/// A layout font, compared by identity, so that text runs are split where the font changes.
+struct LayoutFontRef(Rc<RefCell<Font>>);
+
+impl PartialEq for LayoutFontRef {
+    fn eq(&self, other: &LayoutFontRef) -> bool {
+        let (&LayoutFontRef(ref font), &LayoutFontRef(ref other_font)) = (self, other);
+        &**font as *const RefCell<Font> == &**other_font as *const RefCell<Font>
+    }
+}
+
+/// A piece of text that a single font renders and that is written in a single script.
+struct RunPiece<F> {
+    font: F,
+    script: Script,
+    /// Where the piece starts and ends in the text, in bytes.
+    bytes: (uint, uint),
+    /// The characters of the text that the piece covers.
+    chars: Range<CharIndex>,
+}
+
+/// Splits `text` into the pieces that `FontContext::create_text_runs()` makes text runs of.
+/// `font_for_char` returns the font that should render a character, and `covers` whether a font
+/// has a glyph for one.
+fn split_into_runs<F: PartialEq>(text: &str,
+                                 font_for_char: |char| -> F,
+                                 covers: |&F, char| -> bool)
+                                 -> Vec<RunPiece<F>> {
+    let mut pieces = vec!();
+    let mut run_font: Option<F> = None;
+    let mut run_script = Common;
+    let (mut run_start_byte, mut run_start_char) = (0u, CharIndex(0));
+    for (char_i, (byte_i, character)) in text.char_indices().enumerate() {
+        let char_i = CharIndex(char_i as int);
+
+        // Combining marks stay in the run of the character they combine with, so that they are
+        // shaped together, and soft hyphens, which are never drawn, stay in the run of the word
+        // they break. Whitespace stays in the run it interrupts when that font can render it, so
+        // that falling back for a word doesn't also split off the spaces around it.
+        let stays_in_run = match run_font {
+            Some(ref font) => {
+                is_cluster_extender(character) || character == SOFT_HYPHEN ||
+                    (character.is_whitespace() && covers(font, character))
+            }
+            None => false,
+        };
+        if stays_in_run {
+            continue
+        }
+
+        // Punctuation, digits and the like take the script of the text around them.
+        let script = script_for_char(character);
+        let is_same_script = script.is_common_or_inherited() ||
+            run_script.is_common_or_inherited() || script == run_script;
+
+        let font = font_for_char(character);
+        if run_font.as_ref() == Some(&font) && is_same_script {
+            if run_script.is_common_or_inherited() {
+                run_script = script
+            }
+            continue
+        }
+
+        match run_font.take() {
+            Some(previous_font) => {
+                pieces.push(RunPiece {
+                    font: previous_font,
+                    script: run_script,
+                    bytes: (run_start_byte, byte_i),
+                    chars: Range::new(run_start_char, char_i - run_start_char),
+                })
+            }
+            None => {}
+        }
+        run_font = Some(font);
+        run_script = script;
+        run_start_byte = byte_i;
+        run_start_char = char_i;
+    }
+
+    match run_font {
+        Some(font) => {
+            let char_len = CharIndex(text.char_len() as int);
+            pieces.push(RunPiece {
+                font: font,
+                script: run_script,
+                bytes: (run_start_byte, text.len()),
+                chars: Range::new(run_start_char, char_len - run_start_char),
+            })
+        }
+        None => {}
+    }
+    pieces
+}
+
+/// Returns the options to shape a run of text in the given style and script with.
+fn shaping_options(style: &SpecifiedFontStyle, script: Script) -> ShapingOptions {
+    ShapingOptions {
+        script: script,
+        language: style.language.clone(),
+        features: style.features.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunPiece, size_adjusted_pt_size, split_into_runs};
+
+    use font::FontMetrics;
+    use servo_util::geometry::Au;
+    use text::glyph::CharIndex;
+    use text::script::{Arabic, Common, Devanagari, Hebrew, Latin, Script, Thai};
+
+    /// Returns the characters, font and script of each piece, with font 1 rendering the characters
+    /// in `fallback_chars` and font 0 the rest. Font 1 has no glyph for a space.
+    fn split(text: &str, fallback_chars: &str) -> Vec<(int, int, uint, Script)> {
+        let pieces: Vec<RunPiece<uint>> = split_into_runs(text, |character| {
+            if fallback_chars.contains_char(character) { 1 } else { 0 }
+        }, |&font, character| font == 0 || character != ' ');
+        pieces.into_iter().map(|piece| {
+            let CharIndex(start) = piece.chars.begin();
+            let CharIndex(length) = piece.chars.length();
+            (start, length, piece.font, piece.script)
+        }).collect()
+    }
+
+    #[test]
+    fn test_split_into_runs_by_font() {
+        assert_eq!(split("", ""), vec!());
+        assert_eq!(split("abc", ""), vec!((0, 3, 0, Latin)));
+        assert_eq!(split("abxyab", "xy"),
+                   vec!((0, 2, 0, Latin), (2, 2, 1, Latin), (4, 2, 0, Latin)));
+    }
+
+    #[test]
+    fn test_split_into_runs_keeps_clusters_and_spaces() {
+        // A combining mark stays with its base, whichever font would render it alone.
+        assert_eq!(split("e\u0301x", "\u0301"), vec!((0, 3, 0, Latin)));
+        // A space stays in the run that it interrupts if that font has a glyph for it...
+        assert_eq!(split("ab xy", "xy"), vec!((0, 3, 0, Latin), (3, 2, 1, Latin)));
+        // ...and otherwise goes to the font that renders it.
+        assert_eq!(split("xy ab", "xy"), vec!((0, 2, 1, Latin), (2, 3, 0, Latin)));
+    }
+
+    #[test]
+    fn test_split_into_runs_by_script() {
+        assert_eq!(split("ab\u05d0\u05d1", ""), vec!((0, 2, 0, Latin), (2, 2, 0, Hebrew)));
+        // Common characters take the script of the text around them.
+        assert_eq!(split("1, ab", ""), vec!((0, 5, 0, Latin)));
+        assert_eq!(split("ab 1 \u05d0", ""), vec!((0, 5, 0, Latin), (5, 1, 0, Hebrew)));
+        assert_eq!(split("123", ""), vec!((0, 3, 0, Common)));
+    }
+
+    #[test]
+    fn test_split_into_runs_of_complex_scripts() {
+        // Arabic harakat, Devanagari vowel signs and viramas, and Thai tone marks stay in the run
+        // of the letters they combine with.
+        assert_eq!(split("\u0628\u064e\u062a ab", ""), vec!((0, 4, 0, Arabic), (4, 2, 0, Latin)));
+        assert_eq!(split("\u0628\u064e", "\u064e"), vec!((0, 2, 0, Arabic)));
+        assert_eq!(split("\u0915\u093f\u0928\u094d\u0926\u0940", ""),
+                   vec!((0, 6, 0, Devanagari)));
+        assert_eq!(split("\u0e01\u0e48\u0e32 1", ""), vec!((0, 5, 0, Thai)));
+        assert_eq!(split("ab \u0e01\u0e32", ""), vec!((0, 3, 0, Latin), (3, 2, 0, Thai)));
+    }
+
+    /// Returns the metrics of a 20px font whose x-height is the given fraction of its size.
+    fn metrics_with_x_height(x_height: f64) -> FontMetrics {
+        let em_size = Au::from_px(20);
+        FontMetrics {
+            underline_size: Au(0),
+            underline_offset: Au(0),
+            strikeout_size: Au(0),
+            strikeout_offset: Au(0),
+            leading: Au(0),
+            x_height: em_size.scale_by(x_height),
+            em_size: em_size,
+            ascent: em_size,
+            descent: Au(0),
+            max_advance: em_size,
+            average_advance: em_size,
+            line_gap: em_size,
+        }
+    }
+
+    #[test]
+    fn test_size_adjusted_pt_size() {
+        // A font with an aspect of 0.25 is doubled in size to have an aspect of 0.5.
+        assert_eq!(size_adjusted_pt_size(10.0, Some(0.5), &metrics_with_x_height(0.25)),
+                   Some(20.0));
+        assert_eq!(size_adjusted_pt_size(10.0, Some(0.25), &metrics_with_x_height(0.5)),
+                   Some(5.0));
+        assert_eq!(size_adjusted_pt_size(10.0, None, &metrics_with_x_height(0.5)), None);
+        // Fonts with no x-height are left alone.
+        assert_eq!(size_adjusted_pt_size(10.0, Some(0.5), &metrics_with_x_height(0.0)), None);
+    }
+}
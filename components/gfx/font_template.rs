@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use style::computed_values::font_weight;
+use style::computed_values::{font_stretch, font_weight};
 use platform::font_context::FontContextHandle;
 use platform::font::FontHandle;
 use platform::font_template::FontTemplateData;
@@ -17,25 +17,78 @@ use font::FontHandleMethods;
 #[deriving(Clone)]
 pub struct FontTemplateDescriptor {
     pub weight: font_weight::T,
+    pub stretch: font_stretch::T,
     pub italic: bool,
 }
 
 impl FontTemplateDescriptor {
-    pub fn new(weight: font_weight::T, italic: bool) -> FontTemplateDescriptor {
+    pub fn new(weight: font_weight::T, stretch: font_stretch::T, italic: bool)
+               -> FontTemplateDescriptor {
         FontTemplateDescriptor {
             weight: weight,
+            stretch: stretch,
             italic: italic,
         }
     }
+
+    /// Returns the widths to look for, in order, when a family has no face of the requested
+    /// width (CSS Fonts 3 § 5.2): narrower widths first for normal and condensed requests, and
+    /// wider widths first for expanded ones.
+    pub fn fallback_stretches(&self) -> Vec<font_stretch::T> {
+        let width_class = width_class_for_stretch(self.stretch);
+        let narrower = range(1, width_class).rev();
+        let wider = range(width_class + 1, 10);
+        let mut width_classes = vec!(width_class);
+        if width_class <= 5 {
+            width_classes.extend(narrower);
+            width_classes.extend(wider);
+        } else {
+            width_classes.extend(wider);
+            width_classes.extend(narrower);
+        }
+        width_classes.into_iter().map(stretch_for_width_class).collect()
+    }
 }
 
 impl PartialEq for FontTemplateDescriptor {
     fn eq(&self, other: &FontTemplateDescriptor) -> bool {
         self.weight.is_bold() == other.weight.is_bold() &&
+        self.stretch == other.stretch &&
         self.italic == other.italic
     }
 }
 
+/// Returns the width class of the given `font-stretch` value, as found in the OS/2 table of
+/// OpenType fonts: 1 for `ultra-condensed` through 9 for `ultra-expanded`.
+pub fn width_class_for_stretch(stretch: font_stretch::T) -> u16 {
+    match stretch {
+        font_stretch::ultra_condensed => 1,
+        font_stretch::extra_condensed => 2,
+        font_stretch::condensed => 3,
+        font_stretch::semi_condensed => 4,
+        font_stretch::normal => 5,
+        font_stretch::semi_expanded => 6,
+        font_stretch::expanded => 7,
+        font_stretch::extra_expanded => 8,
+        font_stretch::ultra_expanded => 9,
+    }
+}
+
+/// Returns the `font-stretch` value for the given OS/2 width class. Invalid classes are normal.
+pub fn stretch_for_width_class(width_class: u16) -> font_stretch::T {
+    match width_class {
+        1 => font_stretch::ultra_condensed,
+        2 => font_stretch::extra_condensed,
+        3 => font_stretch::condensed,
+        4 => font_stretch::semi_condensed,
+        6 => font_stretch::semi_expanded,
+        7 => font_stretch::expanded,
+        8 => font_stretch::extra_expanded,
+        9 => font_stretch::ultra_expanded,
+        _ => font_stretch::normal,
+    }
+}
+
 /// This describes all the information needed to create
 /// font instance handles. It contains a unique
 /// FontTemplateData structure that is platform specific.
@@ -43,7 +96,9 @@ pub struct FontTemplate {
     identifier: String,
     descriptor: Option<FontTemplateDescriptor>,
     weak_ref: Option<Weak<FontTemplateData>>,
-    strong_ref: Option<Arc<FontTemplateData>>,      // GWTODO: Add code path to unset the strong_ref for web fonts!
+    /// Web fonts can't be loaded again, so they hold on to their data until the font cache
+    /// drops the template.
+    strong_ref: Option<Arc<FontTemplateData>>,
     is_valid: bool,
 }
 
@@ -103,6 +158,7 @@ impl FontTemplate {
                     match handle {
                         Ok(handle) => {
                             let actual_desc = FontTemplateDescriptor::new(handle.boldness(),
+                                                handle.stretchiness(),
                                                 handle.is_italic());
                             let desc_match = actual_desc == *requested_desc;
 
@@ -155,3 +211,29 @@ impl FontTemplate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FontTemplateDescriptor, stretch_for_width_class, width_class_for_stretch};
+
+    use style::computed_values::font_weight;
+
+    /// Returns the width classes that a request of the given width class falls back to.
+    fn fallback_width_classes(width_class: u16) -> Vec<u16> {
+        let descriptor = FontTemplateDescriptor::new(font_weight::Weight400,
+                                                     stretch_for_width_class(width_class),
+                                                     false);
+        descriptor.fallback_stretches().into_iter().map(width_class_for_stretch).collect()
+    }
+
+    #[test]
+    fn test_fallback_stretches() {
+        // Normal and condensed widths fall back to narrower faces first...
+        assert_eq!(fallback_width_classes(5), vec!(5, 4, 3, 2, 1, 6, 7, 8, 9));
+        assert_eq!(fallback_width_classes(3), vec!(3, 2, 1, 4, 5, 6, 7, 8, 9));
+        assert_eq!(fallback_width_classes(1), vec!(1, 2, 3, 4, 5, 6, 7, 8, 9));
+        // ...and expanded widths to wider ones.
+        assert_eq!(fallback_width_classes(7), vec!(7, 8, 9, 6, 5, 4, 3, 2, 1));
+        assert_eq!(fallback_width_classes(9), vec!(9, 8, 7, 6, 5, 4, 3, 2, 1));
+    }
+}
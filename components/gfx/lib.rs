@@ -12,7 +12,9 @@ extern crate log;
 
 extern crate debug;
 extern crate azure;
+extern crate brotli;
 extern crate collections;
+extern crate flate;
 extern crate geom;
 extern crate layers;
 extern crate libc;
@@ -29,6 +31,7 @@ extern crate "util" as servo_util;
 extern crate "msg" as servo_msg;
 extern crate style;
 extern crate sync;
+extern crate "time" as std_time;
 extern crate url;
 
 // Eventually we would like the shaper to be pluggable, as many operating systems have their own
@@ -54,12 +57,14 @@ pub mod color;
 #[path="display_list/mod.rs"]
 pub mod display_list;
 pub mod render_task;
+pub mod pdf;
 
 // Fonts
 pub mod font;
 pub mod font_context;
 pub mod font_cache_task;
 pub mod font_template;
+mod woff;
 
 // Misc.
 mod buffer_map;
@@ -0,0 +1,112 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! PDF output for paginated documents, through the PDF backend of Skia. Each page is an Azure draw
+//! target that display lists are painted into just as they are into tiles, and Skia turns the
+//! drawing into PDF operators, so pages keep their text and shapes as vector graphics.
+
+use azure::azure::AzDrawTargetRef;
+use azure::azure_hl::DrawTarget;
+use azure::AzFloat;
+use geom::size::Size2D;
+use libc::{c_char, c_void};
+use servo_util::geometry::Au;
+use std::io::{IoError, IoResult, OtherIoError};
+use std::ptr;
+
+#[allow(non_camel_case_types)]
+type AzSkiaPdfDocumentRef = *mut c_void;
+
+#[link(name = "azure")]
+extern {
+    fn AzCreateSkiaPdfDocument(path: *const c_char) -> AzSkiaPdfDocumentRef;
+    fn AzSkiaPdfDocumentBeginPage(document: AzSkiaPdfDocumentRef,
+                                  width: AzFloat,
+                                  height: AzFloat)
+                                  -> AzDrawTargetRef;
+    fn AzSkiaPdfDocumentEndPage(document: AzSkiaPdfDocumentRef);
+    fn AzSkiaPdfDocumentClose(document: AzSkiaPdfDocumentRef) -> bool;
+    fn AzReleaseSkiaPdfDocument(document: AzSkiaPdfDocumentRef);
+}
+
+/// A PDF file that is being written, one page at a time.
+pub struct PdfDocument {
+    azure_document: AzSkiaPdfDocumentRef,
+    path: Path,
+}
+
+impl PdfDocument {
+    /// Creates the PDF file at `path`.
+    pub fn new(path: &Path) -> IoResult<PdfDocument> {
+        let azure_document = path.with_c_str(|path| unsafe { AzCreateSkiaPdfDocument(path) });
+        if azure_document == ptr::null_mut() {
+            return Err(pdf_error("could not create the PDF file", path))
+        }
+        Ok(PdfDocument {
+            azure_document: azure_document,
+            path: path.clone(),
+        })
+    }
+
+    /// Starts a page of the given size, and returns the draw target to paint it into. The draw
+    /// target measures lengths in PDF points, from the top left corner of the page.
+    pub fn begin_page(&mut self, size: Size2D<Au>) -> DrawTarget {
+        unsafe {
+            let azure_draw_target =
+                AzSkiaPdfDocumentBeginPage(self.azure_document,
+                                           px_to_points(size.width.to_subpx() as f32),
+                                           px_to_points(size.height.to_subpx() as f32));
+            DrawTarget::new_with_draw_target(azure_draw_target)
+        }
+    }
+
+    /// Finishes the page that was last begun. Its draw target must not be drawn into afterward.
+    pub fn end_page(&mut self) {
+        unsafe {
+            AzSkiaPdfDocumentEndPage(self.azure_document)
+        }
+    }
+
+    /// Writes out the rest of the file.
+    pub fn close(self) -> IoResult<()> {
+        if unsafe { AzSkiaPdfDocumentClose(self.azure_document) } {
+            Ok(())
+        } else {
+            Err(pdf_error("could not write the PDF file", &self.path))
+        }
+    }
+}
+
+impl Drop for PdfDocument {
+    fn drop(&mut self) {
+        unsafe {
+            AzReleaseSkiaPdfDocument(self.azure_document)
+        }
+    }
+}
+
+fn pdf_error(description: &'static str, path: &Path) -> IoError {
+    IoError {
+        kind: OtherIoError,
+        desc: description,
+        detail: Some(path.display().to_string()),
+    }
+}
+
+/// Converts a length in CSS pixels to PDF points. A CSS pixel is 1/96 of an inch, and a point is
+/// 1/72 of one.
+pub fn px_to_points(px: f32) -> f32 {
+    px * 72.0 / 96.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::px_to_points;
+
+    #[test]
+    fn test_px_to_points() {
+        assert_eq!(px_to_points(96.0), 72.0);
+        assert_eq!(px_to_points(816.0), 612.0);
+    }
+}
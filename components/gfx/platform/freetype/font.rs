@@ -5,18 +5,22 @@
 extern crate freetype;
 
 use font::{FontHandleMethods, FontMetrics, FontTableMethods};
-use font::{FontTableTag, FractionalPixel};
+use font::{FontTableTag, FractionalPixel, OS2_STRIKEOUT_POSITION_OFFSET};
+use font::{OS2_STRIKEOUT_SIZE_OFFSET, POST_UNDERLINE_POSITION_OFFSET};
+use font::POST_UNDERLINE_THICKNESS_OFFSET;
+use geom::{Point2D, Rect, Size2D};
 use servo_util::geometry::Au;
 use servo_util::geometry;
 use platform::font_context::FontContextHandle;
 use text::glyph::GlyphId;
-use text::util::{float_to_fixed, fixed_to_float};
-use style::computed_values::font_weight;
+use text::util::{float_to_fixed, fixed_to_float, true_type_tag};
+use style::computed_values::{font_stretch, font_weight};
+use font_template::stretch_for_width_class;
 use platform::font_template::FontTemplateData;
 
 use freetype::freetype::{FT_Get_Char_Index, FT_Get_Postscript_Name};
 use freetype::freetype::{FT_Load_Glyph, FT_Set_Char_Size};
-use freetype::freetype::{FT_Get_Kerning, FT_Get_Sfnt_Table};
+use freetype::freetype::{FT_Get_Kerning, FT_Get_Sfnt_Table, FT_Load_Sfnt_Table};
 use freetype::freetype::{FT_New_Memory_Face, FT_Done_Face};
 use freetype::freetype::{FTErrorMethods, FT_F26Dot6, FT_Face, FT_FaceRec};
 use freetype::freetype::{FT_GlyphSlot, FT_Library, FT_Long, FT_ULong};
@@ -39,11 +43,13 @@ fn fixed_to_float_ft(f: i32) -> f64 {
     fixed_to_float(6, f)
 }
 
-pub struct FontTable;
+pub struct FontTable {
+    buffer: Vec<u8>,
+}
 
 impl FontTableMethods for FontTable {
-    fn with_buffer(&self, _blk: |*const u8, uint|) {
-        fail!()
+    fn with_buffer(&self, blk: |*const u8, uint|) {
+        blk(self.buffer.as_ptr(), self.buffer.len())
     }
 }
 
@@ -157,6 +163,18 @@ impl FontHandleMethods for FontHandle {
         }
     }
 
+    fn stretchiness(&self) -> font_stretch::T {
+        unsafe {
+            let os2 = FT_Get_Sfnt_Table(self.face, ft_sfnt_os2) as *mut TT_OS2;
+            let valid = os2.is_not_null() && (*os2).version != 0xffff;
+            if valid {
+                stretch_for_width_class((*os2).usWidthClass)
+            } else {
+                font_stretch::normal
+            }
+        }
+    }
+
     fn glyph_index(&self,
                        codepoint: char) -> Option<GlyphId> {
         assert!(self.face.is_not_null());
@@ -202,12 +220,29 @@ impl FontHandleMethods for FontHandle {
         }
     }
 
+    fn glyph_ink_bounds(&self, glyph: GlyphId) -> Option<Rect<FractionalPixel>> {
+        unsafe {
+            let res = FT_Load_Glyph(self.face, glyph as FT_UInt, 0);
+            if !res.succeeded() {
+                debug!("Unable to load glyph {}. reason: {}", glyph, res);
+                return None
+            }
+            let void_glyph = (*self.face).glyph;
+            let slot: FT_GlyphSlot = mem::transmute(void_glyph);
+            assert!(slot.is_not_null());
+            let metrics = &(*slot).metrics;
+            let left = fixed_to_float_ft(metrics.horiBearingX as i32);
+            let top = fixed_to_float_ft(metrics.horiBearingY as i32);
+            let width = fixed_to_float_ft(metrics.width as i32);
+            let height = fixed_to_float_ft(metrics.height as i32);
+            Some(Rect(Point2D(left, top - height), Size2D(width, height)))
+        }
+    }
+
     fn get_metrics(&self) -> FontMetrics {
         /* TODO(Issue #76): complete me */
         let face = self.get_face_rec();
 
-        let underline_size = self.font_units_to_au(face.underline_thickness as f64);
-        let underline_offset = self.font_units_to_au(face.underline_position as f64);
         let em_size = self.font_units_to_au(face.units_per_EM as f64);
         let ascent = self.font_units_to_au(face.ascender as f64);
         let descent = self.font_units_to_au(face.descender as f64);
@@ -222,15 +257,26 @@ impl FontHandleMethods for FontHandle {
         let height = self.font_units_to_au(face.height as f64);
         let leading = height - (ascent + descent);
 
-        let mut strikeout_size = geometry::from_pt(0.0);
-        let mut strikeout_offset = geometry::from_pt(0.0);
+        // Read the decoration metrics from the `post` and `OS/2` tables. Any that the font
+        // lacks are filled in once the other metrics are known.
+        let read = |table: &Option<FontTable>, offset: uint| -> Au {
+            table.as_ref()
+                 .and_then(|table| table.read_i16(offset))
+                 .map(|value| self.font_units_to_au(value as f64))
+                 .unwrap_or(Au(0))
+        };
+        let post_table = self.load_table(true_type_tag('p', 'o', 's', 't'));
+        let os2_table = self.load_table(true_type_tag('O', 'S', '/', '2'));
+        let underline_size = read(&post_table, POST_UNDERLINE_THICKNESS_OFFSET);
+        let underline_offset = read(&post_table, POST_UNDERLINE_POSITION_OFFSET);
+        let strikeout_size = read(&os2_table, OS2_STRIKEOUT_SIZE_OFFSET);
+        let strikeout_offset = read(&os2_table, OS2_STRIKEOUT_POSITION_OFFSET);
+
         let mut x_height = geometry::from_pt(0.0);
         unsafe {
             let os2 = FT_Get_Sfnt_Table(face, ft_sfnt_os2) as *mut TT_OS2;
             let valid = os2.is_not_null() && (*os2).version != 0xffff;
             if valid {
-               strikeout_size = self.font_units_to_au((*os2).yStrikeoutSize as f64);
-               strikeout_offset = self.font_units_to_au((*os2).yStrikeoutPosition as f64);
                x_height = self.font_units_to_au((*os2).sxHeight as f64);
             }
         }
@@ -240,7 +286,7 @@ impl FontHandleMethods for FontHandle {
                                   .map(|advance| self.font_units_to_au(advance))
                                   .unwrap_or(max_advance);
 
-        let metrics = FontMetrics {
+        let mut metrics = FontMetrics {
             underline_size:   underline_size,
             underline_offset: underline_offset,
             strikeout_size:   strikeout_size,
@@ -254,12 +300,14 @@ impl FontHandleMethods for FontHandle {
             average_advance:  average_advance,
             line_gap:         height,
         };
+        metrics.fill_in_decoration_metrics();
 
         debug!("Font metrics (@{:f} pt): {:?}", geometry::to_pt(em_size), metrics);
         return metrics;
     }
 
     fn get_table_for_tag(&self, _: FontTableTag) -> Option<FontTable> {
+        // HarfBuzz would keep pointing into the table after it has been freed (Issue #197).
         None
     }
 }
@@ -274,6 +322,26 @@ impl<'a> FontHandle {
         }
     }
 
+    /// Copies the SFNT table with the given tag out of the font, if it has one.
+    fn load_table(&self, tag: FontTableTag) -> Option<FontTable> {
+        unsafe {
+            // Ask for the length of the table first, then load it.
+            let mut length = 0;
+            if !FT_Load_Sfnt_Table(self.face, tag as FT_ULong, 0, ptr::null_mut(),
+                                   &mut length).succeeded() {
+                return None
+            }
+            let mut buffer = Vec::from_elem(length as uint, 0u8);
+            if !FT_Load_Sfnt_Table(self.face, tag as FT_ULong, 0, buffer.as_mut_ptr(),
+                                   &mut length).succeeded() {
+                return None
+            }
+            Some(FontTable {
+                buffer: buffer,
+            })
+        }
+    }
+
     fn get_face_rec(&'a self) -> &'a mut FT_FaceRec {
         unsafe {
             &mut (*self.face)
@@ -298,3 +366,44 @@ impl<'a> FontHandle {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::FontHandle;
+
+    use font::FontHandleMethods;
+    use geom::{Point2D, Rect, Size2D};
+    use platform::font_context::FontContextHandle;
+    use platform::font_template::FontTemplateData;
+    use servo_util::geometry::Au;
+    use sync::Arc;
+
+    /// Returns a handle for the test font of rectangles at 100px, where a font unit is a tenth of
+    /// a pixel. Its `post` table has an underline 50 units thick at -100, and its `OS/2` table a
+    /// strikeout 50 units thick at 300.
+    fn features_font() -> FontHandle {
+        let bytes = include_bin!("../../../../tests/ref/fonts/features/features.ttf");
+        let template = Arc::new(FontTemplateData::new("features", Some(bytes.to_vec())));
+        let handle: Result<FontHandle, ()> =
+            FontHandleMethods::new_from_template(&FontContextHandle::new(), template, Some(100.0));
+        handle.unwrap()
+    }
+
+    #[test]
+    fn test_decoration_metrics_come_from_post_and_os2() {
+        let metrics = features_font().get_metrics();
+        assert_eq!(metrics.underline_size, Au::from_px(5));
+        assert_eq!(metrics.underline_offset, Au::from_px(-10));
+        assert_eq!(metrics.strikeout_size, Au::from_px(5));
+        assert_eq!(metrics.strikeout_offset, Au::from_px(30));
+    }
+
+    #[test]
+    fn test_glyph_ink_bounds() {
+        let handle = features_font();
+        // The `f` is a rectangle 400 units wide and 800 tall on the baseline.
+        let glyph = handle.glyph_index('f').unwrap();
+        assert_eq!(handle.glyph_ink_bounds(glyph),
+                   Some(Rect(Point2D(0.0, 0.0), Size2D(40.0, 80.0))));
+    }
+}
@@ -17,7 +17,9 @@ use fontconfig::fontconfig::{
     FcMatchPattern,
     FcPatternCreate, FcPatternAddString,
     FcFontSetList, FcObjectSetCreate, FcObjectSetDestroy,
-    FcObjectSetAdd, FcPatternGetInteger
+    FcObjectSetAdd, FcPatternGetInteger,
+    FcCharSet, FcCharSetCreate, FcCharSetAddChar, FcCharSetDestroy, FcCharSetHasChar,
+    FcPatternAddCharSet, FcPatternGetCharSet
 };
 
 use libc;
@@ -28,6 +30,7 @@ use std::string;
 static FC_FAMILY: &'static [u8] = b"family\0";
 static FC_FILE: &'static [u8] = b"file\0";
 static FC_INDEX: &'static [u8] = b"index\0";
+static FC_CHARSET: &'static [u8] = b"charset\0";
 
 pub fn get_available_families(callback: |String|) {
     unsafe {
@@ -124,6 +127,45 @@ pub fn get_system_default_family(generic_name: &str) -> Option<String> {
     }
 }
 
+/// Returns the family of the font that fontconfig would pick to render the given character, if
+/// any font on the system has a glyph for it.
+pub fn get_fallback_family_for_char(character: char) -> Option<String> {
+    unsafe {
+        let pattern = FcPatternCreate();
+        assert!(pattern.is_not_null());
+        let char_set = FcCharSetCreate();
+        assert!(char_set.is_not_null());
+        FcCharSetAddChar(char_set, character as u32);
+        FcPatternAddCharSet(pattern, FC_CHARSET.as_ptr() as *mut i8, char_set);
+
+        FcConfigSubstitute(ptr::null_mut(), pattern, FcMatchPattern);
+        FcDefaultSubstitute(pattern);
+
+        let mut result = 0;
+        let font_match = FcFontMatch(ptr::null_mut(), pattern, &mut result);
+
+        // The best match need not cover the character if nothing does.
+        let mut family_name = None;
+        if result == FcResultMatch {
+            let mut match_char_set: *mut FcCharSet = ptr::null_mut();
+            let mut match_string: *mut FcChar8 = ptr::null_mut();
+            if FcPatternGetCharSet(font_match, FC_CHARSET.as_ptr() as *mut i8, 0,
+                                   &mut match_char_set) == FcResultMatch &&
+                    FcCharSetHasChar(match_char_set, character as u32) != 0 &&
+                    FcPatternGetString(font_match, FC_FAMILY.as_ptr() as *mut i8, 0,
+                                       &mut match_string) == FcResultMatch {
+                family_name =
+                    Some(string::raw::from_buf(match_string as *const i8 as *const u8));
+            }
+            FcPatternDestroy(font_match);
+        }
+
+        FcCharSetDestroy(char_set);
+        FcPatternDestroy(pattern);
+        family_name
+    }
+}
+
 #[cfg(target_os="linux")]
 pub fn get_last_resort_font_families() -> Vec<String> {
     vec!(
@@ -137,3 +179,15 @@ pub fn get_last_resort_font_families() -> Vec<String> {
 pub fn get_last_resort_font_families() -> Vec<String> {
     vec!("Roboto".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::get_fallback_family_for_char;
+
+    #[test]
+    fn test_get_fallback_family_for_char() {
+        assert!(get_fallback_family_for_char('A').is_some());
+        // A noncharacter, which no font has a glyph for.
+        assert_eq!(get_fallback_family_for_char('\uffff'), None);
+    }
+}
@@ -32,4 +32,9 @@ impl FontTemplateData {
             identifier: identifier.to_string(),
         }
     }
+
+    /// Returns the contents of the font file, for embedding the font in documents.
+    pub fn font_data<'a>(&'a self) -> Option<&'a [u8]> {
+        Some(self.bytes.as_slice())
+    }
 }
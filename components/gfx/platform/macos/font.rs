@@ -9,27 +9,43 @@ extern crate core_graphics;
 extern crate core_text;
 
 use font::{FontHandleMethods, FontMetrics, FontTableMethods};
-use font::FontTableTag;
-use font::FractionalPixel;
+use font::{FontTableTag, FractionalPixel, OS2_STRIKEOUT_POSITION_OFFSET};
+use font::{OS2_STRIKEOUT_SIZE_OFFSET, POST_UNDERLINE_POSITION_OFFSET};
+use font::POST_UNDERLINE_THICKNESS_OFFSET;
 use servo_util::geometry::{Au, px_to_pt};
-use servo_util::geometry;
 use platform::macos::font_context::FontContextHandle;
 use text::glyph::GlyphId;
-use style::computed_values::font_weight;
+use text::util::true_type_tag;
+use style::computed_values::{font_stretch, font_weight};
+use font_template::stretch_for_width_class;
 use platform::font_template::FontTemplateData;
 
-use core_foundation::base::CFIndex;
+use core_foundation::base::{CFIndex, TCFType};
 use core_foundation::data::CFData;
 use core_foundation::string::UniChar;
 use core_graphics::font::CGGlyph;
 use core_graphics::geometry::CGRect;
-use core_text::font::CTFont;
+use core_text::font::{CTFont, CTFontRef};
 use core_text::font_descriptor::{SymbolicTraitAccessors, TraitAccessors};
-use core_text::font_descriptor::{kCTFontDefaultOrientation};
+use core_text::font_descriptor::{CTFontOrientation, kCTFontDefaultOrientation};
+use geom::{Point2D, Rect, Size2D};
 
 use std::ptr;
 use sync::Arc;
 
+#[link(name = "CoreText", kind = "framework")]
+extern {
+    fn CTFontGetBoundingRectsForGlyphs(font: CTFontRef,
+                                       orientation: CTFontOrientation,
+                                       glyphs: *const CGGlyph,
+                                       bounding_rects: *mut CGRect,
+                                       count: CFIndex)
+                                       -> CGRect;
+}
+
+/// The offset of the `unitsPerEm` field in the `head` table.
+static HEAD_UNITS_PER_EM_OFFSET: uint = 18;
+
 pub struct FontTable {
     data: CFData,
 }
@@ -110,6 +126,14 @@ impl FontHandleMethods for FontHandle {
         return font_weight::Weight900;
     }
 
+    fn stretchiness(&self) -> font_stretch::T {
+        // -1.0 to 1.0
+        let normalized = self.ctfont.all_traits().normalized_width();
+        // 1.0 to 9.0
+        let normalized = (normalized + 1.0) / 2.0 * 8.0 + 1.0;
+        stretch_for_width_class(normalized.round() as u16)
+    }
+
     fn glyph_index(&self, codepoint: char) -> Option<GlyphId> {
         let characters: [UniChar,  ..1] = [codepoint as UniChar];
         let mut glyphs: [CGGlyph, ..1] = [0 as CGGlyph];
@@ -143,6 +167,21 @@ impl FontHandleMethods for FontHandle {
         Some(advance as FractionalPixel)
     }
 
+    fn glyph_ink_bounds(&self, glyph: GlyphId) -> Option<Rect<FractionalPixel>> {
+        let glyphs = [glyph as CGGlyph];
+        let bounds = unsafe {
+            CTFontGetBoundingRectsForGlyphs(self.ctfont.as_concrete_TypeRef(),
+                                            kCTFontDefaultOrientation,
+                                            &glyphs[0],
+                                            ptr::null_mut(),
+                                            1)
+        };
+        Some(Rect(Point2D(bounds.origin.x as FractionalPixel,
+                          bounds.origin.y as FractionalPixel),
+                  Size2D(bounds.size.width as FractionalPixel,
+                         bounds.size.height as FractionalPixel)))
+    }
+
     fn get_metrics(&self) -> FontMetrics {
         let bounding_rect: CGRect = self.ctfont.bounding_box();
         let ascent = self.ctfont.ascent() as f64;
@@ -159,16 +198,35 @@ impl FontHandleMethods for FontHandle {
                                   .map(|advance| Au::from_frac_px(advance))
                                   .unwrap_or(max_advance_width);
 
-        let metrics =  FontMetrics {
-            underline_size:   Au::from_pt(self.ctfont.underline_thickness() as f64),
-            // TODO(Issue #201): underline metrics are not reliable. Have to pull out of font table
-            // directly.
-            //
-            // see also: https://bugs.webkit.org/show_bug.cgi?id=16768
-            // see also: https://bugreports.qt-project.org/browse/QTBUG-13364
-            underline_offset: Au::from_pt(self.ctfont.underline_position() as f64),
-            strikeout_size:   geometry::from_pt(0.0), // FIXME(Issue #942)
-            strikeout_offset: geometry::from_pt(0.0), // FIXME(Issue #942)
+        // Core Text's underline metrics are not reliable (Issue #201), and it has none for
+        // strikeout (Issue #942), so read them from the `post` and `OS/2` tables.
+        //
+        // see also: https://bugs.webkit.org/show_bug.cgi?id=16768
+        // see also: https://bugreports.qt-project.org/browse/QTBUG-13364
+        let units_per_em = self.get_table_for_tag(true_type_tag('h', 'e', 'a', 'd'))
+                               .and_then(|table| table.read_i16(HEAD_UNITS_PER_EM_OFFSET))
+                               .map(|value| value as u16)
+                               .unwrap_or(0);
+        let read = |table: &Option<FontTable>, offset: uint| -> Au {
+            if units_per_em == 0 {
+                return Au(0)
+            }
+            table.as_ref()
+                 .and_then(|table| table.read_i16(offset))
+                 .map(|value| {
+                     Au::from_pt(value as f64 * self.ctfont.pt_size() as f64 /
+                                 units_per_em as f64)
+                 })
+                 .unwrap_or(Au(0))
+        };
+        let post_table = self.get_table_for_tag(true_type_tag('p', 'o', 's', 't'));
+        let os2_table = self.get_table_for_tag(true_type_tag('O', 'S', '/', '2'));
+
+        let mut metrics =  FontMetrics {
+            underline_size:   read(&post_table, POST_UNDERLINE_THICKNESS_OFFSET),
+            underline_offset: read(&post_table, POST_UNDERLINE_POSITION_OFFSET),
+            strikeout_size:   read(&os2_table, OS2_STRIKEOUT_SIZE_OFFSET),
+            strikeout_offset: read(&os2_table, OS2_STRIKEOUT_POSITION_OFFSET),
             leading:          Au::from_pt(leading),
             x_height:         Au::from_pt(self.ctfont.x_height() as f64),
             em_size:          em_size,
@@ -178,6 +236,7 @@ impl FontHandleMethods for FontHandle {
             average_advance:  average_advance,
             line_gap:         Au::from_frac_px(line_gap),
         };
+        metrics.fill_in_decoration_metrics();
         debug!("Font metrics (@{:f} pt): {:?}", self.ctfont.pt_size() as f64, metrics);
         return metrics;
     }
@@ -2,12 +2,20 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use core_foundation::base::TCFType;
-use core_foundation::string::{CFString, CFStringRef};
+use core_foundation::base::{CFIndex, CFRange, TCFType};
+use core_foundation::string::{CFString, CFStringRef, UniChar};
+use core_graphics::font::CGGlyph;
+use core_text::font::{CTFont, CTFontRef};
 use core_text::font_descriptor::{CTFontDescriptor, CTFontDescriptorRef};
 use core_text;
 use std::mem;
 
+#[link(name = "CoreText", kind = "framework")]
+extern {
+    fn CTFontCreateForString(current_font: CTFontRef, string: CFStringRef, range: CFRange)
+                             -> CTFontRef;
+}
+
 pub fn get_available_families(callback: |String|) {
     let family_names = core_text::font_collection::get_family_names();
     for strref in family_names.iter() {
@@ -36,6 +44,42 @@ pub fn get_system_default_family(_generic_name: &str) -> Option<String> {
     None
 }
 
+/// Returns the family of the font that Core Text would substitute for the first last-resort font
+/// to render the given character, if any font on the system has a glyph for it.
+pub fn get_fallback_family_for_char(character: char) -> Option<String> {
+    let base_family = get_last_resort_font_families()[0].clone();
+    let base_font = match core_text::font::new_from_name(base_family.as_slice(), 0.0) {
+        Ok(base_font) => base_font,
+        Err(()) => return None,
+    };
+
+    // Characters outside the Basic Multilingual Plane are two UTF-16 code units long.
+    let code_point = character as u32;
+    let code_units: Vec<UniChar> = if code_point > 0xffff {
+        let code_point = code_point - 0x10000;
+        vec!(0xd800 | (code_point >> 10) as UniChar, 0xdc00 | (code_point & 0x3ff) as UniChar)
+    } else {
+        vec!(code_point as UniChar)
+    };
+
+    let string: CFString = from_str(String::from_char(1, character).as_slice()).unwrap();
+    let font: CTFont = unsafe {
+        let font = CTFontCreateForString(base_font.as_concrete_TypeRef(),
+                                         string.as_concrete_TypeRef(),
+                                         CFRange::init(0, code_units.len() as CFIndex));
+        TCFType::wrap_under_create_rule(font)
+    };
+
+    // Core Text hands back the font it was given when no font covers the character.
+    let mut glyphs: Vec<CGGlyph> = Vec::from_elem(code_units.len(), 0);
+    if !font.get_glyphs_for_characters(code_units.as_ptr(),
+                                       glyphs.as_mut_ptr(),
+                                       code_units.len() as CFIndex) {
+        return None
+    }
+    Some(font.family_name())
+}
+
 pub fn get_last_resort_font_families() -> Vec<String> {
     vec!("Arial Unicode MS".to_string(), "Arial".to_string())
 }
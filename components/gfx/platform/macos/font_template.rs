@@ -37,4 +37,12 @@ impl FontTemplateData {
             identifier: identifier.to_string(),
         }
     }
+
+    /// Returns the contents of the font file, for embedding the font in documents.
+    ///
+    /// TODO: Core Text only hands out the tables of a font, which would have to be put back
+    /// together into a font file.
+    pub fn font_data<'a>(&'a self) -> Option<&'a [u8]> {
+        None
+    }
 }
@@ -3,9 +3,9 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use font_context::FontContext;
-use style::computed_values::border_style;
+use style::computed_values::{border_style, text_decoration_style};
 
-use azure::azure_hl::{B8G8R8A8, A8, Color, ColorPattern, DrawOptions, DrawSurfaceOptions, DrawTarget};
+use azure::azure_hl::{B8G8R8A8, Color, ColorPattern, DrawOptions, DrawSurfaceOptions, DrawTarget};
 use azure::azure_hl::{Linear, SourceOp, StrokeOptions};
 use azure::AZ_CAP_BUTT;
 use azure::AzFloat;
@@ -15,11 +15,14 @@ use geom::size::Size2D;
 use geom::side_offsets::SideOffsets2D;
 use libc::types::common::c99::uint16_t;
 use libc::size_t;
-use png::{RGB8, RGBA8, K8, KA8};
+use servo_net::image::animation::AnimationTime;
 use servo_net::image::base::Image;
 use servo_util::geometry::Au;
 use servo_util::opts::Opts;
-use sync::Arc;
+use std::cmp;
+use std::collections::hashmap::HashMap;
+use std::f32::consts::PI;
+use url::Url;
 
 pub struct RenderContext<'a> {
     pub draw_target: &'a DrawTarget,
@@ -29,6 +32,11 @@ pub struct RenderContext<'a> {
     pub page_rect: Rect<f32>,
     /// The rectangle that this context encompasses in screen coordinates (pixels).
     pub screen_rect: Rect<uint>,
+    /// The point in the playback of animated images to paint them at, on the clock of
+    /// `animation_start_times`.
+    pub animation_time: AnimationTime,
+    /// When each animated image started playing, by the URL it was loaded from.
+    pub animation_start_times: &'a HashMap<Url, u64>,
 }
 
 enum Direction {
@@ -48,6 +56,12 @@ impl<'a> RenderContext<'a>  {
         self.draw_target
     }
 
+    /// Returns the point in the playback of the animated image loaded from `url` to paint it at.
+    pub fn animation_time_for(&self, url: &Url) -> AnimationTime {
+        let start_time = self.animation_start_times.find(url).map(|&time| time).unwrap_or(0);
+        self.animation_time.since(start_time)
+    }
+
     pub fn draw_solid_color(&self, bounds: &Rect<Au>, color: Color) {
         self.draw_target.make_current();
         self.draw_target.fill_rect(&bounds.to_azure_rect(), &ColorPattern::new(color), None);
@@ -76,6 +90,41 @@ impl<'a> RenderContext<'a>  {
         self.draw_line_segment(bounds, color, style);
     }
 
+    pub fn draw_text_decoration(&self,
+                                bounds: &Rect<Au>,
+                                color: Color,
+                                style: text_decoration_style::T,
+                                thickness: Au) {
+        self.draw_target.make_current();
+
+        let rect = bounds.to_azure_rect();
+        let thickness = cmp::max(thickness.to_nearest_px(), 1) as AzFloat;
+        match style {
+            text_decoration_style::solid => {
+                self.draw_solid_color(bounds, color)
+            }
+            text_decoration_style::double => {
+                // Two lines, one at the top of the bounds and one at the bottom.
+                let pattern = ColorPattern::new(color);
+                let size = Size2D(rect.size.width, thickness);
+                self.draw_target.fill_rect(&Rect(rect.origin, size), &pattern, None);
+                let bottom = Point2D(rect.origin.x, rect.origin.y + rect.size.height - thickness);
+                self.draw_target.fill_rect(&Rect(bottom, size), &pattern, None);
+            }
+            text_decoration_style::dotted => {
+                let border = SideOffsets2D::new_all_same(thickness);
+                self.draw_dashed_border_segment(Top, bounds, border, color, DottedBorder);
+            }
+            text_decoration_style::dashed => {
+                let border = SideOffsets2D::new_all_same(thickness);
+                self.draw_dashed_border_segment(Top, bounds, border, color, DashedBorder);
+            }
+            text_decoration_style::wavy => {
+                self.draw_wavy_line(rect, thickness, color);
+            }
+        }
+    }
+
     pub fn draw_push_clip(&self, bounds: &Rect<Au>) {
         let rect = bounds.to_azure_rect();
         let path_builder = self.draw_target.create_path_builder();
@@ -98,24 +147,21 @@ impl<'a> RenderContext<'a>  {
         self.draw_target.pop_clip();
     }
 
-    pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<Box<Image>>) {
-        let size = Size2D(image.width as i32, image.height as i32);
-        let (pixel_width, pixels, source_format) = match image.pixels {
-            RGBA8(ref pixels) => (4, pixels.as_slice(), B8G8R8A8),
-            K8(ref pixels) => (1, pixels.as_slice(), A8),
-            RGB8(_) => fail!("RGB8 color type not supported"),
-            KA8(_) => fail!("KA8 color type not supported"),
-        };
-        let stride = image.width * pixel_width;
+    /// Draws an image into `bounds`. Its pixels are handed to Azure as they are, since they were
+    /// converted to premultiplied BGRA once, when the image was decoded.
+    pub fn draw_image(&self, bounds: Rect<Au>, image: &Image) {
+        let size = Size2D(image.pixel_width() as i32, image.pixel_height() as i32);
+        let stride = image.pixel_width() * 4;
 
         self.draw_target.make_current();
         let draw_target_ref = &self.draw_target;
-        let azure_surface = draw_target_ref.create_source_surface_from_data(pixels,
+        let azure_surface = draw_target_ref.create_source_surface_from_data(image.pixels(),
                                                                             size,
                                                                             stride as i32,
-                                                                            source_format);
+                                                                            B8G8R8A8);
         let source_rect = Rect(Point2D(0u as AzFloat, 0u as AzFloat),
-                               Size2D(image.width as AzFloat, image.height as AzFloat));
+                               Size2D(image.pixel_width() as AzFloat,
+                                      image.pixel_height() as AzFloat));
         let dest_rect = bounds.to_azure_rect();
         let draw_surface_options = DrawSurfaceOptions::new(Linear, true);
         let draw_options = DrawOptions::new(1.0f64 as AzFloat, 0);
@@ -301,6 +347,36 @@ impl<'a> RenderContext<'a>  {
                                      &draw_opts);
     }
 
+    /// Fills a wave `thickness` thick that swings between the top and the bottom of `rect`.
+    fn draw_wavy_line(&self, rect: Rect<AzFloat>, thickness: AzFloat, color: Color) {
+        let amplitude = (rect.size.height - thickness) * 0.5;
+        let wavelength = (amplitude * 4.0).max(4.0);
+        let middle = rect.origin.y + amplitude;
+
+        // The phase depends only on the page position, so that the pieces of a line broken up
+        // around descenders line up with each other.
+        let wave_top = |x: AzFloat| -> Point2D<AzFloat> {
+            Point2D(x, middle - amplitude * (x * 2.0 * PI / wavelength).sin())
+        };
+
+        // Trace the top of the wave left to right at one point per pixel, then its bottom right
+        // to left.
+        let start = rect.origin.x;
+        let end = rect.origin.x + rect.size.width;
+        let steps = rect.size.width.ceil() as uint;
+        let path_builder = self.draw_target.create_path_builder();
+        path_builder.move_to(wave_top(start));
+        for step in range(1, steps + 1) {
+            path_builder.line_to(wave_top((start + step as AzFloat).min(end)));
+        }
+        for step in range(0, steps + 1).rev() {
+            let top = wave_top((start + step as AzFloat).min(end));
+            path_builder.line_to(Point2D(top.x, top.y + thickness));
+        }
+        let path = path_builder.finish();
+        self.draw_target.fill(&path, &ColorPattern::new(color), &DrawOptions::new(1.0, 0));
+    }
+
     fn draw_solid_border_segment(&self, direction: Direction, bounds: &Rect<Au>, border: SideOffsets2D<f32>, color: Color) {
         let rect = bounds.to_azure_rect();
         self.draw_border_path(rect, direction, border, color);
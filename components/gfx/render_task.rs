@@ -8,6 +8,8 @@ use buffer_map::BufferMap;
 use display_list::optimizer::DisplayListOptimizer;
 use display_list::DisplayList;
 use font_context::FontContext;
+use pdf::PdfDocument;
+use pdf;
 use render_context::RenderContext;
 
 use azure::azure_hl::{B8G8R8A8, Color, DrawTarget, StolenGLResources};
@@ -16,23 +18,35 @@ use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use layers::platform::surface::{NativePaintingGraphicsContext, NativeSurface};
-use layers::platform::surface::{NativeSurfaceMethods};
+use layers::platform::surface::{NativeGraphicsMetadata, NativePaintingGraphicsContext};
+use layers::platform::surface::{NativeSurface, NativeSurfaceMethods};
 use layers::layers::{BufferRequest, LayerBuffer, LayerBufferSet};
 use layers;
 use servo_msg::compositor_msg::{Epoch, IdleRenderState, LayerId};
 use servo_msg::compositor_msg::{LayerMetadata, RenderListener, RenderingRenderState, ScrollPolicy};
+use servo_msg::compositor_msg::{StickyConstraints, StickyPosition};
 use servo_msg::constellation_msg::{ConstellationChan, Failure, FailureMsg, PipelineId};
 use servo_msg::constellation_msg::{RendererReadyMsg};
 use servo_msg::platform::surface::NativeSurfaceAzureMethods;
+use servo_net::image::animation::{AnimationTime, ElapsedTime, PinnedFrame};
+use servo_util::geometry::Au;
 use servo_util::geometry;
 use servo_util::opts::Opts;
 use servo_util::smallvec::{SmallVec, SmallVec1};
-use servo_util::task::spawn_named_with_send_on_failure;
-use servo_util::time::{TimeProfilerChan, profile};
+use servo_util::task::{spawn_named, spawn_named_with_send_on_failure};
+use servo_util::time::{TimeProfilerCategory, TimeProfilerChan, profile};
 use servo_util::time;
+use native::task::NativeTaskBuilder;
+use std::collections::hashmap::HashMap;
 use std::comm::{Receiver, Sender, channel};
-use sync::Arc;
+use std::io::{IoError, IoResult, OtherIoError};
+use std::io::timer::sleep;
+use std::mem;
+use std::task::TaskBuilder;
+use std::time::duration::Duration;
+use std_time::precise_time_ns;
+use sync::{Arc, Mutex};
+use url::Url;
 use font_cache_task::FontCacheTask;
 
 /// Information about a layer that layout sends to the painting task.
@@ -47,6 +61,21 @@ pub struct RenderLayer {
     pub background_color: Color,
     /// The scrolling policy of this layer.
     pub scroll_policy: ScrollPolicy,
+    /// The ID of the layer that this layer is nested inside, if any.
+    pub parent_id: Option<LayerId>,
+    /// Whether this layer clips and scrolls its child layers. Scroll roots have no contents of
+    /// their own and are never rendered.
+    pub is_scroll_root: bool,
+}
+
+/// The display list of a layer other than the root layer, with where the layer is. Layout keeps
+/// these after sending the layers to the painting task, to answer queries.
+#[deriving(Clone)]
+pub struct LayerDisplayList {
+    pub id: LayerId,
+    /// The position of the layer in pixels.
+    pub position: Rect<uint>,
+    pub display_list: Arc<DisplayList>,
 }
 
 pub struct RenderRequest {
@@ -56,12 +85,54 @@ pub struct RenderRequest {
     pub epoch: Epoch,
 }
 
+/// The tiles of a layer that have been painted in the current epoch, and the scale that they were
+/// painted at. When the frame of an animated image changes, the tiles that show it are painted
+/// again.
+struct PaintedTiles {
+    scale: f32,
+    tiles: Vec<BufferRequest>,
+}
+
+/// How layout laid a paginated document out on pages, kept so that the pages can be printed when
+/// the compositor asks for them.
+pub struct PageLayout {
+    /// The display lists of all layers, in painting order.
+    pub display_lists: Vec<Arc<DisplayList>>,
+    /// The color to paint each page before its content.
+    pub background_color: Color,
+    /// The size of the page box.
+    pub page_size: Size2D<Au>,
+    /// Where the page area, which shows the content, starts inside the page box.
+    pub page_area_origin: Point2D<Au>,
+    /// The rectangle of the document that each page shows, in page coordinates.
+    pub pages: Vec<Rect<Au>>,
+}
+
+/// A frame inside a document that is being printed. Its contents are painted by the render task
+/// of its own pipeline.
+#[deriving(Clone)]
+pub struct PrintedFrame {
+    /// Where the frame is in the document, in page coordinates.
+    pub rect: Rect<Au>,
+    pub render_chan: RenderChan,
+}
+
 pub enum Msg {
     RenderInitMsg(SmallVec1<RenderLayer>),
     RenderMsg(Vec<RenderRequest>),
+    /// Sent by layout after each reflow of a paginated document.
+    PageLayoutMsg(Box<PageLayout>),
+    /// Sent by the compositor to write the pages, with the frames inside them, to a PDF file. The
+    /// result is sent back on the channel.
+    PrintMsg(Path, Vec<PrintedFrame>, Sender<IoResult<()>>),
+    /// Asks for the display lists that this task paints, so that a parent document can print
+    /// them inside its pages.
+    GetDisplayListsMsg(Sender<Vec<Arc<DisplayList>>>),
     UnusedBufferMsg(Vec<Box<LayerBuffer>>),
     PaintPermissionGranted,
     PaintPermissionRevoked,
+    /// Sent by a timer when the next frame of an animated image is due, to repaint the layers.
+    AnimationFrameMsg,
     ExitMsg(Option<Sender<()>>),
 }
 
@@ -95,6 +166,8 @@ pub enum GraphicsContext {
 pub struct RenderTask<C> {
     id: PipelineId,
     port: Receiver<Msg>,
+    /// A channel to this task, on which animation timers send `AnimationFrameMsg`.
+    chan: RenderChan,
     compositor: C,
     constellation_chan: ConstellationChan,
     font_ctx: Box<FontContext>,
@@ -112,6 +185,9 @@ pub struct RenderTask<C> {
     /// The layers to be rendered.
     render_layers: SmallVec1<RenderLayer>,
 
+    /// How layout last laid the document out on pages, if it is paginated.
+    page_layout: Option<Box<PageLayout>>,
+
     /// Permission to send paint messages to the compositor
     paint_permission: bool,
 
@@ -120,6 +196,28 @@ pub struct RenderTask<C> {
 
     /// A data structure to store unused LayerBuffers
     buffer_map: BufferMap,
+
+    /// The tasks that rasterize tiles in parallel when painting on the CPU with more than one
+    /// render thread (`-t`), shared with the render tasks of the other pipelines. `None` if tiles
+    /// are rasterized by this task.
+    worker_thread_pool: Option<WorkerThreadPool>,
+
+    /// When the clock of animated images started, in nanoseconds.
+    animation_clock_start: u64,
+
+    /// When each animated image in the layers started playing, by the URL it was loaded from, in
+    /// milliseconds on the clock of animated images. Images loaded from the same URL play in
+    /// step.
+    animation_start_times: Arc<HashMap<Url, u64>>,
+
+    /// The time on the clock of animated images that was last painted.
+    animation_time: AnimationTime,
+
+    /// The tiles of each layer that have been painted in this epoch.
+    painted_tiles: HashMap<LayerId, PaintedTiles>,
+
+    /// Whether a timer will send `AnimationFrameMsg`.
+    animation_timer_pending: bool,
 }
 
 // If we implement this as a function, we get borrowck errors from borrowing
@@ -136,11 +234,43 @@ fn initialize_layers<C:RenderListener>(
                      epoch: Epoch,
                      render_layers: &[RenderLayer]) {
     let metadata = render_layers.iter().map(|render_layer| {
+        // Layer positions are in page coordinates, but the compositor wants nested layers to be
+        // positioned relative to their parents.
+        let parent_origin = match render_layer.parent_id {
+            None => Point2D(0, 0),
+            Some(parent_id) => {
+                match render_layers.iter().find(|layer| layer.id == parent_id) {
+                    Some(parent) => parent.position.origin,
+                    None => fail!("render layer's parent wasn't sent before it"),
+                }
+            }
+        };
+        let position = Rect(Point2D(render_layer.position.origin.x - parent_origin.x,
+                                    render_layer.position.origin.y - parent_origin.y),
+                            render_layer.position.size);
+
+        // So are the constraints of sticky layers.
+        let scroll_policy = match render_layer.scroll_policy {
+            StickyPosition(constraints) => {
+                let containing_block = constraints.containing_block;
+                StickyPosition(StickyConstraints {
+                    insets: constraints.insets,
+                    containing_block: Rect(Point2D(containing_block.origin.x -
+                                                   parent_origin.x as f32,
+                                                   containing_block.origin.y -
+                                                   parent_origin.y as f32),
+                                           containing_block.size),
+                })
+            }
+            scroll_policy => scroll_policy,
+        };
         LayerMetadata {
             id: render_layer.id,
-            position: render_layer.position,
+            position: position,
             background_color: render_layer.background_color,
-            scroll_policy: render_layer.scroll_policy,
+            scroll_policy: scroll_policy,
+            parent_id: render_layer.parent_id,
+            is_scroll_root: render_layer.is_scroll_root,
         }
     }).collect();
     compositor.initialize_layers_for_pipeline(pipeline_id, metadata, epoch);
@@ -149,12 +279,14 @@ fn initialize_layers<C:RenderListener>(
 impl<C:RenderListener + Send> RenderTask<C> {
     pub fn create(id: PipelineId,
                   port: Receiver<Msg>,
+                  chan: RenderChan,
                   compositor: C,
                   constellation_chan: ConstellationChan,
                   font_cache_task: FontCacheTask,
                   failure_msg: Failure,
                   opts: Opts,
                   time_profiler_chan: TimeProfilerChan,
+                  worker_thread_pool: Option<WorkerThreadPool>,
                   shutdown_chan: Sender<()>) {
 
         let ConstellationChan(c) = constellation_chan.clone();
@@ -162,14 +294,24 @@ impl<C:RenderListener + Send> RenderTask<C> {
 
         spawn_named_with_send_on_failure("RenderTask", proc() {
             { // Ensures RenderTask and graphics context are destroyed before shutdown msg
-                let native_graphics_context = compositor.get_graphics_metadata().map(
-                    |md| NativePaintingGraphicsContext::from_metadata(&md));
+                let native_graphics_metadata = compositor.get_graphics_metadata();
+                let native_graphics_context = native_graphics_metadata.as_ref().map(
+                    |md| NativePaintingGraphicsContext::from_metadata(md));
                 let cpu_painting = opts.cpu_painting;
+                let animation_time = match opts.animation_frame {
+                    Some(frame) => PinnedFrame(frame),
+                    None => ElapsedTime(0),
+                };
+
+                // GPU painting draws with the GL context of this task, so only CPU painting is
+                // spread across worker threads.
+                let worker_thread_pool = if cpu_painting { worker_thread_pool } else { None };
 
                 // FIXME: rust/#5967
                 let mut render_task = RenderTask {
                     id: id,
                     port: port,
+                    chan: chan,
                     compositor: compositor,
                     constellation_chan: constellation_chan,
                     font_ctx: box FontContext::new(fc.clone()),
@@ -185,10 +327,17 @@ impl<C:RenderListener + Send> RenderTask<C> {
                     native_graphics_context: native_graphics_context,
 
                     render_layers: SmallVec1::new(),
+                    page_layout: None,
 
                     paint_permission: false,
                     epoch: Epoch(0),
                     buffer_map: BufferMap::new(10000000),
+                    worker_thread_pool: worker_thread_pool,
+                    animation_clock_start: precise_time_ns(),
+                    animation_start_times: Arc::new(HashMap::new()),
+                    animation_time: animation_time,
+                    painted_tiles: HashMap::new(),
+                    animation_timer_pending: false,
                 };
 
                 render_task.start();
@@ -212,7 +361,10 @@ impl<C:RenderListener + Send> RenderTask<C> {
             match self.port.recv() {
                 RenderInitMsg(render_layers) => {
                     self.epoch.next();
+                    self.painted_tiles.clear();
+                    self.update_animation_time();
                     self.render_layers = render_layers;
+                    self.update_animation_start_times();
 
                     if !self.paint_permission {
                         debug!("render_task: render ready msg");
@@ -240,6 +392,7 @@ impl<C:RenderListener + Send> RenderTask<C> {
                     for RenderRequest { buffer_requests, scale, layer_id, epoch }
                           in requests.into_iter() {
                         if self.epoch == epoch {
+                            self.record_painted_tiles(layer_id, buffer_requests.as_slice(), scale);
                             self.render(&mut replies, buffer_requests, scale, layer_id);
                         } else {
                             debug!("renderer epoch mismatch: {:?} != {:?}", self.epoch, epoch);
@@ -250,6 +403,16 @@ impl<C:RenderListener + Send> RenderTask<C> {
 
                     debug!("render_task: returning surfaces");
                     self.compositor.paint(self.id, self.epoch, replies);
+                    self.schedule_animation_frame();
+                }
+                PageLayoutMsg(page_layout) => self.page_layout = Some(page_layout),
+                PrintMsg(output_path, frames, result_chan) => {
+                    result_chan.send(self.print(&output_path, frames.as_slice()))
+                }
+                GetDisplayListsMsg(display_lists_chan) => {
+                    display_lists_chan.send(self.render_layers.iter().filter(|layer| {
+                        !layer.is_scroll_root
+                    }).map(|layer| layer.display_list.clone()).collect())
                 }
                 UnusedBufferMsg(unused_buffers) => {
                     for buffer in unused_buffers.into_iter().rev() {
@@ -264,6 +427,7 @@ impl<C:RenderListener + Send> RenderTask<C> {
                     // once we use the layers-based scrolling infrastructure for all scrolling.
                     if self.render_layers.len() > 1 {
                         self.epoch.next();
+                        self.painted_tiles.clear();
                         initialize_layers(&mut self.compositor,
                                           self.id,
                                           self.epoch,
@@ -272,6 +436,18 @@ impl<C:RenderListener + Send> RenderTask<C> {
                 }
                 PaintPermissionRevoked => {
                     self.paint_permission = false;
+                    self.painted_tiles.clear();
+                }
+                AnimationFrameMsg => {
+                    self.animation_timer_pending = false;
+                    if !self.paint_permission {
+                        continue;
+                    }
+
+                    let previous_time = self.animation_time;
+                    self.update_animation_time();
+                    self.repaint_animated_images(previous_time);
+                    self.schedule_animation_frame();
                 }
                 ExitMsg(response_ch) => {
                     debug!("render_task: exitmsg response send");
@@ -282,166 +458,689 @@ impl<C:RenderListener + Send> RenderTask<C> {
         }
     }
 
+    /// Returns the time on the clock of animated images, in milliseconds.
+    fn elapsed_animation_time(&self) -> u64 {
+        (precise_time_ns() - self.animation_clock_start) / 1000000
+    }
+
+    /// Moves the animation time to now, unless animated images are pinned to a frame.
+    fn update_animation_time(&mut self) {
+        match self.animation_time {
+            ElapsedTime(_) => self.animation_time = ElapsedTime(self.elapsed_animation_time()),
+            PinnedFrame(_) => {}
+        }
+    }
+
+    /// Starts the animated images that are new in the layers playing from now. Images that were
+    /// in the layers before keep playing from where they are.
+    fn update_animation_start_times(&mut self) {
+        let now = match self.animation_time {
+            ElapsedTime(now) => now,
+            PinnedFrame(_) => 0,
+        };
+        let mut start_times = HashMap::new();
+        for render_layer in self.render_layers.iter() {
+            for image_item in render_layer.display_list.animated_image_items().into_iter() {
+                let start_time = match self.animation_start_times.find(&image_item.url) {
+                    Some(&start_time) => start_time,
+                    None => now,
+                };
+                start_times.insert(image_item.url.clone(), start_time);
+            }
+        }
+        self.animation_start_times = Arc::new(start_times);
+    }
+
+    /// Remembers the tiles of a layer that the compositor asked for. Tiles at another scale than
+    /// the ones before replace them all.
+    fn record_painted_tiles(&mut self, layer_id: LayerId, tiles: &[BufferRequest], scale: f32) {
+        let painted_tiles = self.painted_tiles.find_or_insert_with(layer_id, |_| {
+            PaintedTiles {
+                scale: scale,
+                tiles: vec!(),
+            }
+        });
+        if painted_tiles.scale != scale {
+            painted_tiles.scale = scale;
+            painted_tiles.tiles.clear();
+        }
+        for tile in tiles.iter() {
+            painted_tiles.tiles.retain(|painted_tile| painted_tile.screen_rect != tile.screen_rect);
+            painted_tiles.tiles.push(copy_tile(tile));
+        }
+    }
+
+    /// Paints again the painted tiles that show an animated image whose frame has changed since
+    /// `previous_time`, and sends them to the compositor in the current epoch. The rest of the
+    /// layers stays as it is.
+    fn repaint_animated_images(&mut self, previous_time: AnimationTime) {
+        let mut requests = vec!();
+        for render_layer in self.render_layers.iter() {
+            let painted_tiles = match self.painted_tiles.find(&render_layer.id) {
+                Some(painted_tiles) => painted_tiles,
+                None => continue,
+            };
+            let changed_bounds: Vec<Rect<Au>> =
+                render_layer.display_list.animated_image_items().into_iter().filter(|image_item| {
+                    let start_time = match self.animation_start_times.find(&image_item.url) {
+                        Some(&start_time) => start_time,
+                        None => return false,
+                    };
+                    let animation = image_item.animation.as_ref().unwrap();
+                    animation.frame_index_at(previous_time.since(start_time)) !=
+                        animation.frame_index_at(self.animation_time.since(start_time))
+                }).map(|image_item| image_item.base.bounds).collect();
+            if changed_bounds.is_empty() {
+                continue
+            }
+
+            // Tiles are positioned relative to the layer, but display items relative to the page.
+            let layer_origin = Point2D(render_layer.position.origin.x as f32,
+                                       render_layer.position.origin.y as f32);
+            let tiles: Vec<BufferRequest> = painted_tiles.tiles.iter().filter(|tile| {
+                let tile_rect = geometry::f32_rect_to_au_rect(tile.page_rect
+                                                                  .translate(&layer_origin));
+                changed_bounds.iter().any(|bounds| bounds.intersects(&tile_rect))
+            }).map(copy_tile).collect();
+            if !tiles.is_empty() {
+                requests.push((render_layer.id, tiles, painted_tiles.scale));
+            }
+        }
+        if requests.is_empty() {
+            return
+        }
+
+        let mut replies = Vec::new();
+        self.compositor.set_render_state(self.id, RenderingRenderState);
+        for (layer_id, tiles, scale) in requests.into_iter() {
+            self.render(&mut replies, tiles, scale, layer_id);
+        }
+        self.compositor.set_render_state(self.id, IdleRenderState);
+        self.compositor.paint(self.id, self.epoch, replies);
+    }
+
+    /// Starts a timer that sends `AnimationFrameMsg` when the frame of an animated image in the
+    /// layers next changes, unless one is already running.
+    fn schedule_animation_frame(&mut self) {
+        let now = match self.animation_time {
+            ElapsedTime(now) => now,
+            PinnedFrame(_) => return,
+        };
+        if self.animation_timer_pending {
+            return
+        }
+        let next_frame_time = self.render_layers.iter().filter_map(|render_layer| {
+            render_layer.display_list.next_animation_frame_time(now,
+                                                                &*self.animation_start_times)
+        }).min();
+        let next_frame_time = match next_frame_time {
+            Some(next_frame_time) => next_frame_time,
+            None => return,
+        };
+
+        let now = self.elapsed_animation_time();
+        let delay = if next_frame_time > now { next_frame_time - now } else { 0 };
+        let chan = self.chan.clone();
+        spawn_named("AnimationTimer", proc() {
+            sleep(Duration::milliseconds(delay as i64));
+            let _ = chan.send_opt(AnimationFrameMsg);
+        });
+        self.animation_timer_pending = true;
+    }
+
     /// Renders one layer and sends the tiles back to the layer.
     fn render(&mut self,
               replies: &mut Vec<(LayerId, Box<LayerBufferSet>)>,
-              tiles: Vec<BufferRequest>,
+              mut tiles: Vec<BufferRequest>,
               scale: f32,
               layer_id: LayerId) {
         time::profile(time::RenderingCategory, None, self.time_profiler_chan.clone(), || {
-            // FIXME: Try not to create a new array here.
-            let mut new_buffers = vec!();
+            // Find the appropriate render layer. Scroll roots have nothing to render.
+            let (display_list, layer_origin) =
+                match self.render_layers.iter().find(|layer| layer.id == layer_id) {
+                    Some(render_layer) if !render_layer.is_scroll_root => {
+                        (render_layer.display_list.clone(), render_layer.position.origin)
+                    }
+                    _ => return,
+                };
 
-            // Find the appropriate render layer.
-            let render_layer = match self.render_layers.iter().find(|layer| layer.id == layer_id) {
-                Some(render_layer) => render_layer,
-                None => return,
+            // The tiles are handed to the workers, so they are moved out of the closure's
+            // environment.
+            let tiles = mem::replace(&mut tiles, vec!());
+            let new_buffers = if self.worker_thread_pool.is_none() {
+                self.render_tiles_sequentially(&display_list, layer_origin, tiles, scale)
+            } else {
+                self.render_tiles_in_parallel(&display_list, layer_origin, tiles, scale)
             };
 
-            // Divide up the layer into tiles.
-            for tile in tiles.iter() {
-                // page_rect is in coordinates relative to the layer origin, but all display list
-                // components are relative to the page origin. We make page_rect relative to
-                // the page origin before passing it to the optimizer.
-                let page_rect =
-                     tile.page_rect.translate(&Point2D(render_layer.position.origin.x as f32,
-                                                       render_layer.position.origin.y as f32));
-                let page_rect_au = geometry::f32_rect_to_au_rect(page_rect);
-
-                // Optimize the display list for this tile.
-                let optimizer = DisplayListOptimizer::new(render_layer.display_list.clone(),
-                                                          page_rect_au);
-                let display_list = optimizer.optimize();
-
-                let width = tile.screen_rect.size.width;
-                let height = tile.screen_rect.size.height;
-
-                let size = Size2D(width as i32, height as i32);
-                let draw_target = match self.graphics_context {
-                    CpuGraphicsContext => {
-                        DrawTarget::new(self.opts.render_backend, size, B8G8R8A8)
-                    }
-                    GpuGraphicsContext => {
-                        // FIXME(pcwalton): Cache the components of draw targets
-                        // (texture color buffer, renderbuffers) instead of recreating them.
-                        let draw_target =
-                            DrawTarget::new_with_fbo(self.opts.render_backend,
-                                                     native_graphics_context!(self),
-                                                     size,
-                                                     B8G8R8A8);
-                        draw_target.make_current();
-                        draw_target
+            let layer_buffer_set = box LayerBufferSet {
+                buffers: new_buffers,
+            };
+
+            replies.push((layer_id, layer_buffer_set));
+        })
+    }
+
+    /// Rasterizes the tiles of a layer one by one on this task.
+    fn render_tiles_sequentially(&mut self,
+                                 display_list: &Arc<DisplayList>,
+                                 layer_origin: Point2D<uint>,
+                                 tiles: Vec<BufferRequest>,
+                                 scale: f32)
+                                 -> Vec<Box<LayerBuffer>> {
+        // FIXME: Try not to create a new array here.
+        let mut new_buffers = vec!();
+
+        // Divide up the layer into tiles.
+        for tile in tiles.iter() {
+            let width = tile.screen_rect.size.width;
+            let height = tile.screen_rect.size.height;
+
+            let size = Size2D(width as i32, height as i32);
+            let draw_target = match self.graphics_context {
+                CpuGraphicsContext => {
+                    DrawTarget::new(self.opts.render_backend, size, B8G8R8A8)
+                }
+                GpuGraphicsContext => {
+                    // FIXME(pcwalton): Cache the components of draw targets
+                    // (texture color buffer, renderbuffers) instead of recreating them.
+                    let draw_target =
+                        DrawTarget::new_with_fbo(self.opts.render_backend,
+                                                 native_graphics_context!(self),
+                                                 size,
+                                                 B8G8R8A8);
+                    draw_target.make_current();
+                    draw_target
+                }
+            };
+
+            paint_tile(&draw_target,
+                       display_list,
+                       layer_origin,
+                       tile,
+                       scale,
+                       self.animation_time,
+                       &*self.animation_start_times,
+                       &mut self.font_ctx,
+                       &self.opts,
+                       time::RenderingDrawingCategory,
+                       self.time_profiler_chan.clone());
+
+            // Extract the texture from the draw target and place it into its slot in the
+            // buffer. If using CPU rendering, upload it first.
+            //
+            // FIXME(pcwalton): We should supply the texture and native surface *to* the
+            // draw target in GPU rendering mode, so that it doesn't have to recreate it.
+            let buffer = match self.graphics_context {
+                CpuGraphicsContext => {
+                    let buffer = self.buffer_map.find(tile.screen_rect.size);
+                    upload_cpu_tile(&draw_target,
+                                    buffer,
+                                    tile,
+                                    scale,
+                                    native_graphics_context!(self))
+                }
+                GpuGraphicsContext => {
+                    draw_target.make_current();
+                    let StolenGLResources {
+                        surface: native_surface
+                    } = draw_target.steal_gl_resources().unwrap();
+
+                    // We mark the native surface as not leaking in case the surfaces
+                    // die on their way to the compositor task.
+                    let mut native_surface: NativeSurface =
+                        NativeSurfaceAzureMethods::from_azure_surface(native_surface);
+                    native_surface.mark_wont_leak();
+
+                    box LayerBuffer {
+                        native_surface: native_surface,
+                        rect: tile.page_rect,
+                        screen_pos: tile.screen_rect,
+                        resolution: scale,
+                        stride: (width * 4) as uint,
+                        painted_with_cpu: false,
+                        content_age: tile.content_age,
                     }
-                };
+                }
+            };
 
-                {
-                    // Build the render context.
-                    let mut ctx = RenderContext {
-                        draw_target: &draw_target,
-                        font_ctx: &mut self.font_ctx,
-                        opts: &self.opts,
-                        page_rect: tile.page_rect,
-                        screen_rect: tile.screen_rect,
-                    };
+            new_buffers.push(buffer);
+        }
 
-                    // Apply the translation to render the tile we want.
-                    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
-                    let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
-                    let matrix = matrix.translate(-page_rect.origin.x as AzFloat,
-                                                  -page_rect.origin.y as AzFloat);
+        new_buffers
+    }
 
-                    ctx.draw_target.set_transform(&matrix);
+    /// Hands the tiles of a layer out to the worker threads and collects the painted buffers in
+    /// the order of the tiles.
+    fn render_tiles_in_parallel(&mut self,
+                                display_list: &Arc<DisplayList>,
+                                layer_origin: Point2D<uint>,
+                                tiles: Vec<BufferRequest>,
+                                scale: f32)
+                                -> Vec<Box<LayerBuffer>> {
+        let tile_count = tiles.len();
+        let (sender, receiver) = channel();
+        for (tile_index, tile) in tiles.into_iter().enumerate() {
+            let buffer = self.buffer_map.find(tile.screen_rect.size);
+            let display_list = display_list.clone();
+            let animation_time = self.animation_time;
+            let animation_start_times = self.animation_start_times.clone();
+            let sender = sender.clone();
+            self.worker_thread_pool.as_ref().unwrap().run(proc(worker: &mut RenderWorker) {
+                let buffer = worker.paint_tile(&display_list,
+                                               layer_origin,
+                                               &tile,
+                                               scale,
+                                               animation_time,
+                                               &*animation_start_times,
+                                               buffer);
+                sender.send((tile_index, buffer))
+            });
+        }
+        collect_in_order(&receiver, tile_count)
+    }
+
+    /// Paints the pages of a paginated document, with the frames inside it, and writes them to a
+    /// PDF file.
+    fn print(&mut self, output_path: &Path, frames: &[PrintedFrame]) -> IoResult<()> {
+        let page_layout = match self.page_layout {
+            Some(ref page_layout) => page_layout,
+            None => {
+                return Err(IoError {
+                    kind: OtherIoError,
+                    desc: "the document was not laid out on pages",
+                    detail: None,
+                })
+            }
+        };
+
+        let frames: Vec<(Rect<Au>, Vec<Arc<DisplayList>>)> = frames.iter().map(|frame| {
+            let (display_lists_chan, display_lists_port) = channel();
+            frame.render_chan.send(GetDisplayListsMsg(display_lists_chan));
+            (frame.rect, display_lists_port.recv())
+        }).collect();
+
+        let mut document = try!(PdfDocument::new(output_path));
+        for page_rect in page_layout.pages.iter() {
+            let draw_target = document.begin_page(page_layout.page_size);
+            let mut ctx = RenderContext {
+                draw_target: &draw_target,
+                font_ctx: &mut self.font_ctx,
+                opts: &self.opts,
+                page_rect: Rect(Point2D(page_rect.origin.x.to_subpx() as f32,
+                                        page_rect.origin.y.to_subpx() as f32),
+                                Size2D(page_rect.size.width.to_subpx() as f32,
+                                       page_rect.size.height.to_subpx() as f32)),
+                screen_rect: Rect(Point2D(0, 0),
+                                  Size2D(geometry::to_px(page_layout.page_size.width) as uint,
+                                         geometry::to_px(page_layout.page_size.height) as uint)),
+                animation_time: self.animation_time,
+                animation_start_times: &*self.animation_start_times,
+            };
 
-                    // Clear the buffer.
-                    ctx.clear();
+            // Paper is white, so a transparent background needs no painting.
+            let points_per_px = pdf::px_to_points(1.0) as AzFloat;
+            let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+            let matrix = matrix.scale(points_per_px, points_per_px);
+            ctx.draw_target.set_transform(&matrix);
+            ctx.draw_solid_color(&Rect(Point2D(Au(0), Au(0)), page_layout.page_size),
+                                 page_layout.background_color);
 
-                    // Draw the display list.
-                    profile(time::RenderingDrawingCategory, None, self.time_profiler_chan.clone(), || {
-                        display_list.draw_into_context(&mut ctx, &matrix);
-                        ctx.draw_target.flush();
-                    });
+            // Move the part of the document that this page shows into the page area. Content that
+            // overflows the page area must not spill into the margins.
+            let offset = page_layout.page_area_origin - page_rect.origin;
+            let matrix = matrix.translate(offset.x.to_subpx() as AzFloat,
+                                          offset.y.to_subpx() as AzFloat);
+            ctx.draw_target.set_transform(&matrix);
+            ctx.draw_push_clip(page_rect);
+            profile(time::RenderingDrawingCategory, None, self.time_profiler_chan.clone(), || {
+                for display_list in page_layout.display_lists.iter() {
+                    let optimizer = DisplayListOptimizer::new(display_list.clone(), *page_rect);
+                    optimizer.optimize().draw_into_context(&mut ctx, &matrix);
                 }
 
-                // Extract the texture from the draw target and place it into its slot in the
-                // buffer. If using CPU rendering, upload it first.
-                //
-                // FIXME(pcwalton): We should supply the texture and native surface *to* the
-                // draw target in GPU rendering mode, so that it doesn't have to recreate it.
-                let buffer = match self.graphics_context {
-                    CpuGraphicsContext => {
-                        let mut buffer = match self.buffer_map.find(tile.screen_rect.size) {
-                            Some(buffer) => {
-                                let mut buffer = buffer;
-                                buffer.rect = tile.page_rect;
-                                buffer.screen_pos = tile.screen_rect;
-                                buffer.resolution = scale;
-                                buffer.native_surface.mark_wont_leak();
-                                buffer.painted_with_cpu = true;
-                                buffer.content_age = tile.content_age;
-                                buffer
-                            }
-                            None => {
-                                // Create an empty native surface. We mark it as not leaking
-                                // in case it dies in transit to the compositor task.
-                                let mut native_surface: NativeSurface =
-                                    layers::platform::surface::NativeSurfaceMethods::new(
-                                        native_graphics_context!(self),
-                                        Size2D(width as i32, height as i32),
-                                        width as i32 * 4);
-                                native_surface.mark_wont_leak();
-
-                                box LayerBuffer {
-                                    native_surface: native_surface,
-                                    rect: tile.page_rect,
-                                    screen_pos: tile.screen_rect,
-                                    resolution: scale,
-                                    stride: (width * 4) as uint,
-                                    painted_with_cpu: true,
-                                    content_age: tile.content_age,
-                                }
-                            }
-                        };
-
-                        draw_target.snapshot().get_data_surface().with_data(|data| {
-                            buffer.native_surface.upload(native_graphics_context!(self), data);
-                            debug!("RENDERER uploading to native surface {:d}",
-                                   buffer.native_surface.get_id() as int);
-                        });
-
-                        buffer
+                // Frames are laid out on their own, in page coordinates of their own.
+                for &(ref frame_rect, ref display_lists) in frames.iter() {
+                    let visible_rect = match frame_rect.intersection(page_rect) {
+                        Some(visible_rect) => visible_rect,
+                        None => continue,
+                    };
+                    ctx.draw_push_clip(&visible_rect);
+                    let frame_matrix = matrix.translate(frame_rect.origin.x.to_subpx() as AzFloat,
+                                                        frame_rect.origin.y.to_subpx() as AzFloat);
+                    ctx.draw_target.set_transform(&frame_matrix);
+                    let frame_page_rect = visible_rect.translate(&Point2D(-frame_rect.origin.x,
+                                                                          -frame_rect.origin.y));
+                    for display_list in display_lists.iter() {
+                        let optimizer = DisplayListOptimizer::new(display_list.clone(),
+                                                                  frame_page_rect);
+                        optimizer.optimize().draw_into_context(&mut ctx, &frame_matrix);
                     }
-                    GpuGraphicsContext => {
-                        draw_target.make_current();
-                        let StolenGLResources {
-                            surface: native_surface
-                        } = draw_target.steal_gl_resources().unwrap();
-
-                        // We mark the native surface as not leaking in case the surfaces
-                        // die on their way to the compositor task.
-                        let mut native_surface: NativeSurface =
-                            NativeSurfaceAzureMethods::from_azure_surface(native_surface);
-                        native_surface.mark_wont_leak();
-
-                        box LayerBuffer {
-                            native_surface: native_surface,
-                            rect: tile.page_rect,
-                            screen_pos: tile.screen_rect,
-                            resolution: scale,
-                            stride: (width * 4) as uint,
-                            painted_with_cpu: false,
-                            content_age: tile.content_age,
+                    ctx.draw_target.set_transform(&matrix);
+                    ctx.draw_pop_clip();
+                }
+            });
+            ctx.draw_pop_clip();
+            document.end_page();
+        }
+
+        debug!("render_task: wrote {} pages", page_layout.pages.len());
+        document.close()
+    }
+}
+
+/// Replays the part of a layer's display list that a tile shows into the given draw target.
+fn paint_tile(draw_target: &DrawTarget,
+              display_list: &Arc<DisplayList>,
+              layer_origin: Point2D<uint>,
+              tile: &BufferRequest,
+              scale: f32,
+              animation_time: AnimationTime,
+              animation_start_times: &HashMap<Url, u64>,
+              font_ctx: &mut Box<FontContext>,
+              opts: &Opts,
+              drawing_category: TimeProfilerCategory,
+              time_profiler_chan: TimeProfilerChan) {
+    // page_rect is in coordinates relative to the layer origin, but all display list
+    // components are relative to the page origin. We make page_rect relative to
+    // the page origin before passing it to the optimizer.
+    let page_rect = tile.page_rect.translate(&Point2D(layer_origin.x as f32,
+                                                      layer_origin.y as f32));
+    let page_rect_au = geometry::f32_rect_to_au_rect(page_rect);
+
+    // Optimize the display list for this tile.
+    let optimizer = DisplayListOptimizer::new(display_list.clone(), page_rect_au);
+    let display_list = optimizer.optimize();
+
+    // Build the render context.
+    let mut ctx = RenderContext {
+        draw_target: draw_target,
+        font_ctx: font_ctx,
+        opts: opts,
+        page_rect: tile.page_rect,
+        screen_rect: tile.screen_rect,
+        animation_time: animation_time,
+        animation_start_times: animation_start_times,
+    };
+
+    // Apply the translation to render the tile we want.
+    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+    let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
+    let matrix = matrix.translate(-page_rect.origin.x as AzFloat,
+                                  -page_rect.origin.y as AzFloat);
+
+    ctx.draw_target.set_transform(&matrix);
+
+    // Clear the buffer.
+    ctx.clear();
+
+    // Draw the display list.
+    profile(drawing_category, None, time_profiler_chan, || {
+        display_list.draw_into_context(&mut ctx, &matrix);
+        ctx.draw_target.flush();
+    });
+}
+
+/// Returns a request for the same tile as `tile`.
+fn copy_tile(tile: &BufferRequest) -> BufferRequest {
+    BufferRequest {
+        screen_rect: tile.screen_rect,
+        page_rect: tile.page_rect,
+        content_age: tile.content_age,
+    }
+}
+
+/// Uploads the pixels of a tile painted on the CPU to a native surface, reusing the surface of
+/// `buffer` if there is one.
+fn upload_cpu_tile(draw_target: &DrawTarget,
+                   buffer: Option<Box<LayerBuffer>>,
+                   tile: &BufferRequest,
+                   scale: f32,
+                   native_graphics_context: &NativePaintingGraphicsContext)
+                   -> Box<LayerBuffer> {
+    let width = tile.screen_rect.size.width;
+    let height = tile.screen_rect.size.height;
+    let mut buffer = match buffer {
+        Some(buffer) => {
+            let mut buffer = buffer;
+            buffer.rect = tile.page_rect;
+            buffer.screen_pos = tile.screen_rect;
+            buffer.resolution = scale;
+            buffer.native_surface.mark_wont_leak();
+            buffer.painted_with_cpu = true;
+            buffer.content_age = tile.content_age;
+            buffer
+        }
+        None => {
+            // Create an empty native surface. We mark it as not leaking
+            // in case it dies in transit to the compositor task.
+            let mut native_surface: NativeSurface =
+                layers::platform::surface::NativeSurfaceMethods::new(
+                    native_graphics_context,
+                    Size2D(width as i32, height as i32),
+                    width as i32 * 4);
+            native_surface.mark_wont_leak();
+
+            box LayerBuffer {
+                native_surface: native_surface,
+                rect: tile.page_rect,
+                screen_pos: tile.screen_rect,
+                resolution: scale,
+                stride: (width * 4) as uint,
+                painted_with_cpu: true,
+                content_age: tile.content_age,
+            }
+        }
+    };
+
+    draw_target.snapshot().get_data_surface().with_data(|data| {
+        buffer.native_surface.upload(native_graphics_context, data);
+        debug!("RENDERER uploading to native surface {:d}",
+               buffer.native_surface.get_id() as int);
+    });
+
+    buffer
+}
+
+/// Collects `count` results that arrive tagged with their index in any order, and returns them in
+/// the order of their indices.
+fn collect_in_order<T: Send>(receiver: &Receiver<(uint, T)>, count: uint) -> Vec<T> {
+    let mut results: Vec<Option<T>> = Vec::from_fn(count, |_| None);
+    for _ in range(0, count) {
+        let (index, result) = receiver.recv();
+        *results.get_mut(index) = Some(result);
+    }
+    results.into_iter().map(|result| result.unwrap()).collect()
+}
+
+/// What a worker thread paints with. Neither font contexts nor native graphics contexts can be
+/// shared between tasks, so each worker has its own.
+pub struct RenderWorker {
+    /// The index of this worker in its pool.
+    pub index: uint,
+    font_ctx: Box<FontContext>,
+    native_graphics_context: Option<NativePaintingGraphicsContext>,
+    opts: Opts,
+    time_profiler_chan: TimeProfilerChan,
+}
+
+impl RenderWorker {
+    /// Paints a tile of a layer at the given origin and uploads it to a native surface, reusing
+    /// the surface of `buffer` if there is one.
+    fn paint_tile(&mut self,
+                  display_list: &Arc<DisplayList>,
+                  layer_origin: Point2D<uint>,
+                  tile: &BufferRequest,
+                  scale: f32,
+                  animation_time: AnimationTime,
+                  animation_start_times: &HashMap<Url, u64>,
+                  buffer: Option<Box<LayerBuffer>>)
+                  -> Box<LayerBuffer> {
+        let size = Size2D(tile.screen_rect.size.width as i32, tile.screen_rect.size.height as i32);
+        let draw_target = DrawTarget::new(self.opts.render_backend, size, B8G8R8A8);
+        paint_tile(&draw_target,
+                   display_list,
+                   layer_origin,
+                   tile,
+                   scale,
+                   animation_time,
+                   animation_start_times,
+                   &mut self.font_ctx,
+                   &self.opts,
+                   time::RenderingDrawingCategory,
+                   self.time_profiler_chan.clone());
+
+        let native_graphics_context = self.native_graphics_context.as_ref().expect(
+            "Need a graphics context to do rendering");
+        upload_cpu_tile(&draw_target, buffer, tile, scale, native_graphics_context)
+    }
+}
+
+/// Messages to the tasks of a worker thread pool.
+enum MsgToWorkerThread {
+    /// Runs the given job on whichever worker takes it.
+    RunMsgToWorkerThread(proc(&mut RenderWorker):Send),
+    /// Stops the worker that takes it, which then replies on the given channel.
+    ExitMsgToWorkerThread(Sender<()>),
+}
+
+/// A pool of tasks that rasterize tiles on the CPU. The render tasks of all pipelines share one
+/// pool, so that there are `-t` painting threads however many frames a page has.
+#[deriving(Clone)]
+pub struct WorkerThreadPool {
+    sender: Sender<MsgToWorkerThread>,
+    thread_count: uint,
+}
+
+impl WorkerThreadPool {
+    /// Spawns `opts.n_render_threads` worker threads, each of which paints with its own font
+    /// context.
+    pub fn new(native_graphics_metadata: Option<NativeGraphicsMetadata>,
+               font_cache_task: FontCacheTask,
+               opts: Opts,
+               time_profiler_chan: TimeProfilerChan)
+               -> WorkerThreadPool {
+        let (sender, receiver) = channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let thread_count = opts.n_render_threads;
+        for index in range(0, thread_count) {
+            let receiver = receiver.clone();
+            let native_graphics_metadata = native_graphics_metadata.clone();
+            let font_cache_task = font_cache_task.clone();
+            let opts = opts.clone();
+            let time_profiler_chan = time_profiler_chan.clone();
+            TaskBuilder::new().named("RenderWorker").native().spawn(proc() {
+                let mut worker = RenderWorker {
+                    index: index,
+                    font_ctx: box FontContext::new(font_cache_task),
+                    native_graphics_context: native_graphics_metadata.map(|metadata| {
+                        NativePaintingGraphicsContext::from_metadata(&metadata)
+                    }),
+                    opts: opts,
+                    time_profiler_chan: time_profiler_chan,
+                };
+
+                loop {
+                    // Whichever worker is idle takes the next job.
+                    let msg = receiver.lock().recv_opt();
+                    match msg {
+                        Ok(RunMsgToWorkerThread(job)) => job(&mut worker),
+                        Ok(ExitMsgToWorkerThread(response_chan)) => {
+                            response_chan.send(());
+                            break
                         }
+                        Err(()) => break,
                     }
-                };
+                }
+            });
+        }
 
-                new_buffers.push(buffer);
-            }
+        WorkerThreadPool {
+            sender: sender,
+            thread_count: thread_count,
+        }
+    }
 
-            let layer_buffer_set = box LayerBufferSet {
-                buffers: new_buffers,
-            };
+    /// Queues a job for the next idle worker.
+    pub fn run(&self, job: proc(&mut RenderWorker):Send) {
+        self.sender.send(RunMsgToWorkerThread(job))
+    }
 
-            replies.push((render_layer.id, layer_buffer_set));
-        })
+    /// Stops the worker threads once they have finished the jobs that were queued before, and
+    /// waits for them to stop.
+    pub fn exit(&self) {
+        let (response_chan, response_port) = channel();
+        for _ in range(0, self.thread_count) {
+            self.sender.send(ExitMsgToWorkerThread(response_chan.clone()))
+        }
+        for _ in range(0, self.thread_count) {
+            response_port.recv()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WorkerThreadPool, collect_in_order};
+
+    use font_cache_task::FontCacheTask;
+    use servo_net::resource_task::new_resource_task;
+    use servo_util::opts;
+    use servo_util::time::TimeProfilerChan;
+    use std::comm::channel;
+
+    fn worker_thread_pool(thread_count: uint) -> WorkerThreadPool {
+        let args = vec!("servo".to_string(), "-c".to_string(), "-t".to_string(),
+                        thread_count.to_string(), "about:blank".to_string());
+        let opts = opts::from_cmdline_args(args.as_slice()).unwrap();
+        let (time_profiler_sender, _) = channel();
+        WorkerThreadPool::new(None,
+                              FontCacheTask::new(new_resource_task(), vec!()),
+                              opts,
+                              TimeProfilerChan(time_profiler_sender))
+    }
+
+    #[test]
+    fn test_collect_in_order() {
+        let (sender, receiver) = channel();
+        for &index in [2u, 0, 3, 1].iter() {
+            sender.send((index, index * 10));
+        }
+        assert_eq!(collect_in_order(&receiver, 4), vec!(0u, 10, 20, 30));
+        assert_eq!(collect_in_order::<uint>(&receiver, 0), vec!());
+    }
+
+    #[test]
+    fn test_pool_is_shared_between_handles() {
+        let pool = worker_thread_pool(3);
+        let other_handle = pool.clone();
+        let (sender, receiver) = channel();
+        for job_index in range(0u, 20) {
+            let sender = sender.clone();
+            let handle = if job_index % 2 == 0 { &pool } else { &other_handle };
+            handle.run(proc(worker) { sender.send((job_index, worker.index)) });
+        }
+
+        let worker_indices = collect_in_order(&receiver, 20);
+        assert!(worker_indices.iter().all(|&worker_index| worker_index < 3));
+        pool.exit();
+    }
+
+    #[test]
+    fn test_exit_finishes_queued_jobs_and_stops_workers() {
+        let pool = worker_thread_pool(2);
+        let (sender, receiver) = channel();
+        for job_index in range(0u, 5) {
+            let sender = sender.clone();
+            pool.run(proc(_) { sender.send(job_index) });
+        }
+
+        // `exit` returns once every worker has stopped, so all the queued jobs have run by then.
+        pool.exit();
+        let mut job_indices: Vec<uint> =
+            range(0u, 5).map(|_| receiver.try_recv().unwrap()).collect();
+        job_indices.sort();
+        assert_eq!(job_indices, vec!(0u, 1, 2, 3, 4));
+        assert!(receiver.try_recv().is_err());
     }
 }
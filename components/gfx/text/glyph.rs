@@ -508,6 +508,9 @@ pub struct GlyphStore {
     detail_store: DetailedGlyphStore,
 
     is_whitespace: bool,
+    /// Whether the text was shaped right to left. The glyphs are still stored in the order of
+    /// the characters, so they must be drawn in reverse.
+    is_rtl: bool,
 }
 
 int_range_index! {
@@ -520,13 +523,14 @@ int_range_index! {
 impl<'a> GlyphStore {
     // Initializes the glyph store, but doesn't actually shape anything.
     // Use the set_glyph, set_glyphs() methods to store glyph data.
-    pub fn new(length: int, is_whitespace: bool) -> GlyphStore {
+    pub fn new(length: int, is_whitespace: bool, is_rtl: bool) -> GlyphStore {
         assert!(length > 0);
 
         GlyphStore {
             entry_buffer: Vec::from_elem(length as uint, GlyphEntry::initial()),
             detail_store: DetailedGlyphStore::new(),
             is_whitespace: is_whitespace,
+            is_rtl: is_rtl,
         }
     }
 
@@ -538,6 +542,10 @@ impl<'a> GlyphStore {
         self.is_whitespace
     }
 
+    pub fn is_rtl(&self) -> bool {
+        self.is_rtl
+    }
+
     pub fn finalize_changes(&mut self) {
         self.detail_store.ensure_sorted();
     }
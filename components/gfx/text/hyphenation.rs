@@ -0,0 +1,251 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Automatic hyphenation for `hyphens: auto`, using Liang's algorithm.
+//!
+//! Each pattern is a string of letters with digits between them. Every pattern that occurs in a
+//! word votes on the positions between its letters, and the largest digit at each position wins:
+//! an odd one allows a break there, and an even one forbids it. The patterns for each language
+//! are read when it is first hyphenated, from the `.pat` files in `resources/hyphenation`.
+
+use servo_util::resource_files::read_resource_file;
+use std::ascii::StrAsciiExt;
+use std::cmp;
+use std::collections::HashMap;
+
+/// The soft hyphen, which marks where a word may be broken with a hyphen.
+pub static SOFT_HYPHEN: char = '\u00ad';
+
+/// Returns the patterns to hyphenate the given BCP 47 language with, read from
+/// `resources/hyphenation/hyph-<language>.pat`. Less specific tags are tried in turn, so that
+/// `en-CA` uses the patterns for `en`.
+pub fn patterns_for_language(language: &str) -> Option<String> {
+    // The tag becomes part of a file name, so it mustn't be able to name anything else.
+    let is_valid = !language.is_empty() && language.chars().all(|ch| {
+        match ch {
+            'a' .. 'z' | 'A' .. 'Z' | '0' .. '9' | '-' => true,
+            _ => false,
+        }
+    });
+    if !is_valid {
+        return None
+    }
+
+    let mut tag = language.to_ascii_lower();
+    loop {
+        let path = format!("hyphenation/hyph-{}.pat", tag);
+        match read_resource_file(path.as_slice()) {
+            Ok(bytes) => return String::from_utf8(bytes).ok(),
+            Err(_) => {}
+        }
+        match tag.as_slice().rfind('-') {
+            Some(index) => tag.truncate(index),
+            None => return None,
+        }
+    }
+}
+
+/// A set of hyphenation patterns and exceptions for one language.
+pub struct Hyphenator {
+    /// The break priorities between the letters of each pattern, by the pattern's letters.
+    patterns: HashMap<String, Vec<u8>>,
+    /// The longest pattern, in characters.
+    max_pattern_length: uint,
+    /// The positions that words which the patterns get wrong are broken at.
+    exceptions: HashMap<String, Vec<uint>>,
+    /// The fewest characters to leave before a break.
+    left_min: uint,
+    /// The fewest characters to leave after a break.
+    right_min: uint,
+}
+
+impl Hyphenator {
+    /// Parses a pattern file. Patterns are separated by whitespace and `%` starts a comment.
+    /// Words without digits are exceptions, which are broken exactly where their `-`s are.
+    pub fn from_patterns(source: &str) -> Hyphenator {
+        let mut patterns = HashMap::new();
+        let mut max_pattern_length = 0;
+        let mut exceptions = HashMap::new();
+        for line in source.lines() {
+            let line = match line.find('%') {
+                Some(index) => line.slice_to(index),
+                None => line,
+            };
+            for token in line.words() {
+                if !token.chars().any(|ch| ch.is_digit()) {
+                    let mut word = String::new();
+                    let mut breaks = vec!();
+                    for ch in token.chars() {
+                        if ch == '-' {
+                            breaks.push(word.as_slice().char_len())
+                        } else {
+                            word.push_char(ch)
+                        }
+                    }
+                    exceptions.insert(word, breaks);
+                    continue
+                }
+
+                let mut letters = String::new();
+                let mut points = vec!(0u8);
+                for ch in token.chars() {
+                    match ch.to_digit(10) {
+                        Some(digit) => {
+                            let last = points.len() - 1;
+                            *points.get_mut(last) = digit as u8
+                        }
+                        None => {
+                            letters.push_char(ch);
+                            points.push(0)
+                        }
+                    }
+                }
+                max_pattern_length = cmp::max(max_pattern_length, letters.as_slice().char_len());
+                patterns.insert(letters, points);
+            }
+        }
+
+        Hyphenator {
+            patterns: patterns,
+            max_pattern_length: max_pattern_length,
+            exceptions: exceptions,
+            left_min: 2,
+            right_min: 3,
+        }
+    }
+
+    /// Returns the character indices in `word` that it may be broken before.
+    pub fn hyphenation_points(&self, word: &str) -> Vec<uint> {
+        let length = word.char_len();
+        if length < self.left_min + self.right_min {
+            return vec!()
+        }
+
+        let lowercase: String = word.chars().map(|ch| ch.to_lowercase()).collect();
+        match self.exceptions.find(&lowercase) {
+            Some(breaks) => {
+                return breaks.iter().map(|&index| index)
+                                    .filter(|&index| self.allows_break_at(index, length))
+                                    .collect()
+            }
+            None => {}
+        }
+
+        // Match the patterns against the word with its ends marked.
+        let mut marked = vec!('.');
+        marked.extend(lowercase.as_slice().chars());
+        marked.push('.');
+        let mut points = Vec::from_elem(marked.len() + 1, 0u8);
+        for start in range(0, marked.len()) {
+            let end = cmp::min(marked.len(), start + self.max_pattern_length);
+            for pattern_end in range(start + 1, end + 1) {
+                let letters = String::from_chars(marked.slice(start, pattern_end));
+                match self.patterns.find(&letters) {
+                    Some(pattern_points) => {
+                        for (offset, &point) in pattern_points.iter().enumerate() {
+                            let point_ref = points.get_mut(start + offset);
+                            *point_ref = cmp::max(*point_ref, point)
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        // The point before the word's character `i` sits after the leading `.`, at `i + 1`.
+        range(0, length).filter(|&index| points[index + 1] % 2 == 1)
+                        .filter(|&index| self.allows_break_at(index, length))
+                        .collect()
+    }
+
+    /// Returns true if a break before character `index` of a word `length` characters long
+    /// leaves enough of the word on both sides.
+    fn allows_break_at(&self, index: uint, length: uint) -> bool {
+        index >= self.left_min && index + self.right_min <= length
+    }
+
+    /// Returns `text` with soft hyphens inserted where its words may be broken. Words that
+    /// already contain a soft hyphen are left as they are, since the author chose their breaks.
+    pub fn hyphenate(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut word = String::new();
+        for ch in text.chars() {
+            if ch.is_alphabetic() || ch == SOFT_HYPHEN {
+                word.push_char(ch);
+                continue
+            }
+            self.push_hyphenated_word(&mut result, word.as_slice());
+            word.truncate(0);
+            result.push_char(ch);
+        }
+        self.push_hyphenated_word(&mut result, word.as_slice());
+        result
+    }
+
+    fn push_hyphenated_word(&self, result: &mut String, word: &str) {
+        if word.contains_char(SOFT_HYPHEN) {
+            result.push_str(word);
+            return
+        }
+        let points = self.hyphenation_points(word);
+        for (index, ch) in word.chars().enumerate() {
+            if points.contains(&index) {
+                result.push_char(SOFT_HYPHEN)
+            }
+            result.push_char(ch)
+        }
+    }
+}
+
+/// Removes the soft hyphens from `text`, for `hyphens: none`.
+pub fn remove_soft_hyphens(text: &str) -> String {
+    text.chars().filter(|&ch| ch != SOFT_HYPHEN).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hyphenator, patterns_for_language, remove_soft_hyphens};
+
+    // The patterns that The TeXbook uses to explain how "hyphenation" is hyphenated.
+    static PATTERNS: &'static str = "hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n % comment
+                                     ta-ble present";
+
+    #[test]
+    fn test_hyphenation_points() {
+        let hyphenator = Hyphenator::from_patterns(PATTERNS);
+        assert_eq!(hyphenator.hyphenation_points("hyphenation"), vec!(2, 6));
+        assert_eq!(hyphenator.hyphenation_points("Hyphenation"), vec!(2, 6));
+        assert_eq!(hyphenator.hyphenation_points("table"), vec!(2));
+        assert_eq!(hyphenator.hyphenation_points("present"), vec!());
+        assert_eq!(hyphenator.hyphenation_points("hyph"), vec!());
+    }
+
+    #[test]
+    fn test_hyphenate() {
+        let hyphenator = Hyphenator::from_patterns(PATTERNS);
+        assert_eq!(hyphenator.hyphenate("a hyphenation table."),
+                   "a hy\u00adphen\u00adation ta\u00adble.".to_string());
+        assert_eq!(hyphenator.hyphenate("hyphen\u00adation"), "hyphen\u00adation".to_string());
+        assert_eq!(remove_soft_hyphens("hy\u00adphen"), "hyphen".to_string());
+    }
+
+    #[test]
+    fn test_patterns_for_language() {
+        assert!(patterns_for_language("en").is_some());
+        assert!(patterns_for_language("en-US").is_some());
+        assert!(patterns_for_language("en-Latn-CA").is_some());
+        assert!(patterns_for_language("tlh").is_none());
+        assert!(patterns_for_language("../en").is_none());
+        assert!(patterns_for_language("").is_none());
+
+        let patterns = patterns_for_language("en").unwrap();
+        let hyphenator = Hyphenator::from_patterns(patterns.as_slice());
+        assert_eq!(hyphenator.hyphenate("hyphenation"), "hy\u00adphen\u00adation".to_string());
+        assert_eq!(hyphenator.hyphenate("extensive"), "ex\u00adten\u00adsive".to_string());
+        assert_eq!(hyphenator.hyphenate("probability"),
+                   "prob\u00ada\u00adbil\u00adity".to_string());
+        assert_eq!(hyphenator.hyphenate("recognize"), "rec\u00adog\u00adnize".to_string());
+        assert_eq!(hyphenator.hyphenate("table"), "ta\u00adble".to_string());
+    }
+}
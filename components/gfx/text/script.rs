@@ -0,0 +1,434 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The Unicode script of characters, and which characters extend a cluster.
+//!
+//! Text is itemized into runs of a single script before shaping, since HarfBuzz shapes each
+//! script with its own rules. The tables below cover the scripts that Servo has fonts for; other
+//! characters are treated as `Common` and take the script of the text around them.
+
+use text::util::true_type_tag;
+
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub enum Script {
+    /// Punctuation, digits, symbols and spaces, which are shared between scripts.
+    Common,
+    /// Combining marks, which take the script of the character they combine with.
+    Inherited,
+    Arabic,
+    Armenian,
+    Bengali,
+    Bopomofo,
+    Cherokee,
+    Cyrillic,
+    Devanagari,
+    Ethiopic,
+    Georgian,
+    Greek,
+    Gujarati,
+    Gurmukhi,
+    Han,
+    Hangul,
+    Hebrew,
+    Hiragana,
+    Kannada,
+    Katakana,
+    Khmer,
+    Lao,
+    Latin,
+    Malayalam,
+    Mongolian,
+    Myanmar,
+    Nko,
+    Oriya,
+    Sinhala,
+    Syriac,
+    Tamil,
+    Telugu,
+    Thaana,
+    Thai,
+    Tibetan,
+}
+
+impl Script {
+    /// Returns true if characters of this script take the script of the text around them.
+    pub fn is_common_or_inherited(&self) -> bool {
+        match *self {
+            Common | Inherited => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this script is written right to left.
+    pub fn is_rtl(&self) -> bool {
+        match *self {
+            Arabic | Hebrew | Nko | Syriac | Thaana => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the ISO 15924 tag of this script, which is also its HarfBuzz `hb_script_t`, or
+    /// `None` for `Common` and `Inherited`, which HarfBuzz should guess from the text.
+    pub fn iso_15924_tag(&self) -> Option<u32> {
+        let tag = match *self {
+            Common | Inherited => return None,
+            Arabic => "Arab",
+            Armenian => "Armn",
+            Bengali => "Beng",
+            Bopomofo => "Bopo",
+            Cherokee => "Cher",
+            Cyrillic => "Cyrl",
+            Devanagari => "Deva",
+            Ethiopic => "Ethi",
+            Georgian => "Geor",
+            Greek => "Grek",
+            Gujarati => "Gujr",
+            Gurmukhi => "Guru",
+            Han => "Hani",
+            Hangul => "Hang",
+            Hebrew => "Hebr",
+            Hiragana => "Hira",
+            Kannada => "Knda",
+            Katakana => "Kana",
+            Khmer => "Khmr",
+            Lao => "Laoo",
+            Latin => "Latn",
+            Malayalam => "Mlym",
+            Mongolian => "Mong",
+            Myanmar => "Mymr",
+            Nko => "Nkoo",
+            Oriya => "Orya",
+            Sinhala => "Sinh",
+            Syriac => "Syrc",
+            Tamil => "Taml",
+            Telugu => "Telu",
+            Thaana => "Thaa",
+            Thai => "Thai",
+            Tibetan => "Tibt",
+        };
+        let tag = tag.as_bytes();
+        Some(true_type_tag(tag[0] as char, tag[1] as char, tag[2] as char, tag[3] as char))
+    }
+}
+
+/// Ranges of characters and their scripts, sorted by their first character. Characters in none
+/// of the ranges are `Common`.
+static SCRIPT_RANGES: &'static [(u32, u32, Script)] = &[
+    (0x0041, 0x005A, Latin),
+    (0x0061, 0x007A, Latin),
+    (0x00AA, 0x00AA, Latin),
+    (0x00BA, 0x00BA, Latin),
+    (0x00C0, 0x00D6, Latin),
+    (0x00D8, 0x00F6, Latin),
+    (0x00F8, 0x02AF, Latin),
+    (0x0300, 0x036F, Inherited),
+    (0x0370, 0x0373, Greek),
+    (0x0375, 0x037D, Greek),
+    (0x037F, 0x0383, Greek),
+    (0x0384, 0x0386, Greek),
+    (0x0388, 0x03FF, Greek),
+    (0x0400, 0x0484, Cyrillic),
+    (0x0485, 0x0486, Inherited),
+    (0x0487, 0x052F, Cyrillic),
+    (0x0531, 0x0588, Armenian),
+    (0x058A, 0x058F, Armenian),
+    (0x0591, 0x05FF, Hebrew),
+    (0x0600, 0x060B, Arabic),
+    (0x060D, 0x061A, Arabic),
+    (0x061C, 0x061E, Arabic),
+    (0x0620, 0x063F, Arabic),
+    (0x0641, 0x064A, Arabic),
+    (0x064B, 0x0655, Inherited),
+    (0x0656, 0x066F, Arabic),
+    (0x0670, 0x0670, Inherited),
+    (0x0671, 0x06DC, Arabic),
+    (0x06DE, 0x06FF, Arabic),
+    (0x0700, 0x074F, Syriac),
+    (0x0750, 0x077F, Arabic),
+    (0x0780, 0x07BF, Thaana),
+    (0x07C0, 0x07FF, Nko),
+    (0x08A0, 0x08E1, Arabic),
+    (0x08E3, 0x08FF, Arabic),
+    (0x0900, 0x0950, Devanagari),
+    (0x0951, 0x0954, Inherited),
+    (0x0955, 0x0963, Devanagari),
+    (0x0966, 0x097F, Devanagari),
+    (0x0980, 0x09FF, Bengali),
+    (0x0A00, 0x0A7F, Gurmukhi),
+    (0x0A80, 0x0AFF, Gujarati),
+    (0x0B00, 0x0B7F, Oriya),
+    (0x0B80, 0x0BFF, Tamil),
+    (0x0C00, 0x0C7F, Telugu),
+    (0x0C80, 0x0CFF, Kannada),
+    (0x0D00, 0x0D7F, Malayalam),
+    (0x0D80, 0x0DFF, Sinhala),
+    (0x0E01, 0x0E3A, Thai),
+    (0x0E40, 0x0E7F, Thai),
+    (0x0E80, 0x0EFF, Lao),
+    (0x0F00, 0x0FD4, Tibetan),
+    (0x0FD9, 0x0FFF, Tibetan),
+    (0x1000, 0x109F, Myanmar),
+    (0x10A0, 0x10FA, Georgian),
+    (0x10FC, 0x10FF, Georgian),
+    (0x1100, 0x11FF, Hangul),
+    (0x1200, 0x139F, Ethiopic),
+    (0x13A0, 0x13FF, Cherokee),
+    (0x1780, 0x17FF, Khmer),
+    (0x1800, 0x1801, Mongolian),
+    (0x1804, 0x1804, Mongolian),
+    (0x1806, 0x18AF, Mongolian),
+    (0x19E0, 0x19FF, Khmer),
+    (0x1AB0, 0x1AFF, Inherited),
+    (0x1D00, 0x1D25, Latin),
+    (0x1D2C, 0x1D5C, Latin),
+    (0x1DC0, 0x1DFF, Inherited),
+    (0x1E00, 0x1EFF, Latin),
+    (0x1F00, 0x1FFF, Greek),
+    (0x200C, 0x200D, Inherited),
+    (0x2071, 0x2071, Latin),
+    (0x207F, 0x207F, Latin),
+    (0x20D0, 0x20FF, Inherited),
+    (0x2C60, 0x2C7F, Latin),
+    (0x2D00, 0x2D2F, Georgian),
+    (0x2D80, 0x2DDF, Ethiopic),
+    (0x2DE0, 0x2DFF, Cyrillic),
+    (0x2E80, 0x2FDF, Han),
+    (0x3005, 0x3005, Han),
+    (0x3007, 0x3007, Han),
+    (0x3021, 0x3029, Han),
+    (0x302A, 0x302D, Inherited),
+    (0x302E, 0x302F, Hangul),
+    (0x3038, 0x303B, Han),
+    (0x3041, 0x3096, Hiragana),
+    (0x3099, 0x309A, Inherited),
+    (0x309D, 0x309F, Hiragana),
+    (0x30A1, 0x30FA, Katakana),
+    (0x30FD, 0x30FF, Katakana),
+    (0x3105, 0x312F, Bopomofo),
+    (0x3131, 0x318F, Hangul),
+    (0x31A0, 0x31BF, Bopomofo),
+    (0x31F0, 0x31FF, Katakana),
+    (0x3400, 0x4DBF, Han),
+    (0x4E00, 0x9FFF, Han),
+    (0xA640, 0xA69F, Cyrillic),
+    (0xA722, 0xA787, Latin),
+    (0xA78B, 0xA7FF, Latin),
+    (0xA960, 0xA97F, Hangul),
+    (0xAB30, 0xAB5A, Latin),
+    (0xAC00, 0xD7FF, Hangul),
+    (0xF900, 0xFAFF, Han),
+    (0xFB00, 0xFB06, Latin),
+    (0xFB13, 0xFB17, Armenian),
+    (0xFB1D, 0xFB4F, Hebrew),
+    (0xFB50, 0xFD3D, Arabic),
+    (0xFD50, 0xFDFF, Arabic),
+    (0xFE00, 0xFE0F, Inherited),
+    (0xFE20, 0xFE2F, Inherited),
+    (0xFE70, 0xFEFC, Arabic),
+    (0xFF21, 0xFF3A, Latin),
+    (0xFF41, 0xFF5A, Latin),
+    (0xFF66, 0xFF6F, Katakana),
+    (0xFF71, 0xFF9D, Katakana),
+    (0xFFA0, 0xFFDC, Hangul),
+    (0x1B000, 0x1B000, Katakana),
+    (0x1B001, 0x1B11E, Hiragana),
+    (0x20000, 0x2FA1F, Han),
+    (0xE0100, 0xE01EF, Inherited),
+];
+
+/// Ranges of characters that extend the cluster of the character before them: combining marks,
+/// including the spacing vowel signs of Indic scripts, zero-width joiners and variation
+/// selectors. A line never breaks before one of these.
+static CLUSTER_EXTENDER_RANGES: &'static [(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8),
+    (0x06EA, 0x06ED),
+    (0x0711, 0x0711),
+    (0x0730, 0x074A),
+    (0x07A6, 0x07B0),
+    (0x07EB, 0x07F3),
+    (0x08D4, 0x08E1),
+    (0x08E3, 0x08FF),
+    (0x0900, 0x0903),
+    (0x093A, 0x093C),
+    (0x093E, 0x094F),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0981, 0x0983),
+    (0x09BC, 0x09BC),
+    (0x09BE, 0x09CD),
+    (0x09D7, 0x09D7),
+    (0x09E2, 0x09E3),
+    (0x0A01, 0x0A03),
+    (0x0A3C, 0x0A51),
+    (0x0A70, 0x0A71),
+    (0x0A75, 0x0A75),
+    (0x0A81, 0x0A83),
+    (0x0ABC, 0x0ABC),
+    (0x0ABE, 0x0ACD),
+    (0x0AE2, 0x0AE3),
+    (0x0B01, 0x0B03),
+    (0x0B3C, 0x0B3C),
+    (0x0B3E, 0x0B57),
+    (0x0B62, 0x0B63),
+    (0x0B82, 0x0B82),
+    (0x0BBE, 0x0BCD),
+    (0x0BD7, 0x0BD7),
+    (0x0C00, 0x0C04),
+    (0x0C3E, 0x0C56),
+    (0x0C62, 0x0C63),
+    (0x0C81, 0x0C83),
+    (0x0CBC, 0x0CBC),
+    (0x0CBE, 0x0CD6),
+    (0x0CE2, 0x0CE3),
+    (0x0D00, 0x0D03),
+    (0x0D3B, 0x0D3C),
+    (0x0D3E, 0x0D4D),
+    (0x0D57, 0x0D57),
+    (0x0D62, 0x0D63),
+    (0x0D82, 0x0D83),
+    (0x0DCA, 0x0DDF),
+    (0x0DF2, 0x0DF3),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x0EB1, 0x0EB1),
+    (0x0EB4, 0x0EBC),
+    (0x0EC8, 0x0ECD),
+    (0x0F18, 0x0F19),
+    (0x0F35, 0x0F35),
+    (0x0F37, 0x0F37),
+    (0x0F39, 0x0F39),
+    (0x0F3E, 0x0F3F),
+    (0x0F71, 0x0F84),
+    (0x0F86, 0x0F87),
+    (0x0F8D, 0x0FBC),
+    (0x0FC6, 0x0FC6),
+    (0x102B, 0x103E),
+    (0x1056, 0x1059),
+    (0x105E, 0x1060),
+    (0x1062, 0x1064),
+    (0x1067, 0x106D),
+    (0x1071, 0x1074),
+    (0x1082, 0x108D),
+    (0x108F, 0x108F),
+    (0x109A, 0x109D),
+    (0x135D, 0x135F),
+    (0x17B4, 0x17D3),
+    (0x17DD, 0x17DD),
+    (0x180B, 0x180D),
+    (0x18A9, 0x18A9),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200C, 0x200D),
+    (0x20D0, 0x20FF),
+    (0x2DE0, 0x2DFF),
+    (0x302A, 0x302F),
+    (0x3099, 0x309A),
+    (0xA66F, 0xA672),
+    (0xA674, 0xA67D),
+    (0xA69E, 0xA69F),
+    (0xFB1E, 0xFB1E),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xE0100, 0xE01EF),
+];
+
+/// Returns the script of the given character.
+pub fn script_for_char(character: char) -> Script {
+    let code_point = character as u32;
+    let index = search_ranges(SCRIPT_RANGES.len(), |i| {
+        let (start, end, _) = SCRIPT_RANGES[i];
+        (start, end)
+    }, code_point);
+    match index {
+        Some(i) => {
+            let (_, _, script) = SCRIPT_RANGES[i];
+            script
+        }
+        None => Common,
+    }
+}
+
+/// Returns true if the given character belongs to the same cluster as the character before it,
+/// so that the two must be shaped together and never separated by a line break.
+pub fn is_cluster_extender(character: char) -> bool {
+    search_ranges(CLUSTER_EXTENDER_RANGES.len(), |i| CLUSTER_EXTENDER_RANGES[i], character as u32)
+        .is_some()
+}
+
+/// Binary searches `count` sorted, non-overlapping, inclusive ranges for the one that contains
+/// `code_point`.
+fn search_ranges(count: uint, range_at: |uint| -> (u32, u32), code_point: u32) -> Option<uint> {
+    let (mut low, mut high) = (0u, count);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let (start, end) = range_at(middle);
+        if code_point < start {
+            high = middle
+        } else if code_point > end {
+            low = middle + 1
+        } else {
+            return Some(middle)
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_cluster_extender, script_for_char, SCRIPT_RANGES, CLUSTER_EXTENDER_RANGES};
+    use super::{Arabic, Common, Devanagari, Han, Hebrew, Inherited, Latin, Thai};
+
+    #[test]
+    fn test_ranges_are_sorted() {
+        for window in SCRIPT_RANGES.windows(2) {
+            let ((_, end, _), (start, _, _)) = (window[0], window[1]);
+            assert!(end < start);
+        }
+        for window in CLUSTER_EXTENDER_RANGES.windows(2) {
+            let ((_, end), (start, _)) = (window[0], window[1]);
+            assert!(end < start);
+        }
+    }
+
+    #[test]
+    fn test_script_for_char() {
+        assert_eq!(script_for_char('a'), Latin);
+        assert_eq!(script_for_char('\u00e9'), Latin);
+        assert_eq!(script_for_char(' '), Common);
+        assert_eq!(script_for_char('1'), Common);
+        assert_eq!(script_for_char('\u0301'), Inherited);
+        assert_eq!(script_for_char('\u0628'), Arabic);
+        assert_eq!(script_for_char('\u060c'), Common);
+        assert_eq!(script_for_char('\u05d0'), Hebrew);
+        assert_eq!(script_for_char('\u0915'), Devanagari);
+        assert_eq!(script_for_char('\u0e01'), Thai);
+        assert_eq!(script_for_char('\u4e2d'), Han);
+        assert!(Arabic.is_rtl());
+        assert!(!Devanagari.is_rtl());
+    }
+
+    #[test]
+    fn test_is_cluster_extender() {
+        assert!(is_cluster_extender('\u0301'));
+        assert!(is_cluster_extender('\u093f'));
+        assert!(is_cluster_extender('\u0e31'));
+        assert!(is_cluster_extender('\u200d'));
+        assert!(!is_cluster_extender('a'));
+        assert!(!is_cluster_extender('\u0915'));
+        assert!(!is_cluster_extender(' '));
+    }
+}
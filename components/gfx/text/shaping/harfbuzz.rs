@@ -7,20 +7,23 @@ extern crate harfbuzz;
 use font::{Font, FontHandleMethods, FontTableMethods, FontTableTag};
 use platform::font::FontTable;
 use text::glyph::{CharIndex, GlyphStore, GlyphId, GlyphData};
-use text::shaping::ShaperMethods;
+use text::script::is_cluster_extender;
+use text::shaping::{ShaperMethods, ShapingOptions};
 use text::util::{float_to_fixed, fixed_to_float};
 
 use geom::Point2D;
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_RTL};
 use harfbuzz::{hb_blob_create, hb_face_create_for_tables};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
 use harfbuzz::{hb_buffer_add_utf8};
 use harfbuzz::{hb_buffer_destroy};
 use harfbuzz::{hb_buffer_get_glyph_positions};
-use harfbuzz::{hb_buffer_set_direction};
+use harfbuzz::{hb_buffer_set_direction, hb_buffer_set_language, hb_buffer_set_script};
+use harfbuzz::{hb_buffer_guess_segment_properties, hb_buffer_reverse};
+use harfbuzz::{hb_language_from_string, hb_script_t};
 use harfbuzz::{hb_face_destroy};
-use harfbuzz::{hb_face_t, hb_font_t};
+use harfbuzz::{hb_face_t, hb_feature_t, hb_font_t};
 use harfbuzz::{hb_font_create};
 use harfbuzz::{hb_font_destroy, hb_buffer_create};
 use harfbuzz::{hb_font_funcs_create};
@@ -43,6 +46,7 @@ use std::mem;
 use std::char;
 use std::cmp;
 use std::ptr;
+use std::u32;
 
 static NO_GLYPH: i32 = -1;
 static CONTINUATION_BYTE: i32 = -2;
@@ -200,10 +204,28 @@ impl Shaper {
 impl ShaperMethods for Shaper {
     /// Calculate the layout metrics associated with the given text when rendered in a specific
     /// font.
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore) {
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore) {
         unsafe {
             let hb_buffer: *mut hb_buffer_t = hb_buffer_create();
-            hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            let is_rtl = options.script.is_rtl();
+            hb_buffer_set_direction(hb_buffer, if is_rtl {
+                HB_DIRECTION_RTL
+            } else {
+                HB_DIRECTION_LTR
+            });
+            match options.script.iso_15924_tag() {
+                Some(tag) => hb_buffer_set_script(hb_buffer, tag as hb_script_t),
+                None => {}
+            }
+            match options.language {
+                Some(ref language) => {
+                    hb_buffer_set_language(hb_buffer,
+                                           hb_language_from_string(
+                                               language.as_ptr() as *const c_char,
+                                               language.len() as c_int))
+                }
+                None => {}
+            }
 
             hb_buffer_add_utf8(hb_buffer,
                                text.as_ptr() as *const c_char,
@@ -211,7 +233,31 @@ impl ShaperMethods for Shaper {
                                0,
                                text.len() as c_int);
 
-            hb_shape(self.hb_font, hb_buffer, ptr::null_mut(), 0);
+            // Guesses whichever of the script and language is still unset from the text and the
+            // locale.
+            hb_buffer_guess_segment_properties(hb_buffer);
+
+            // Each feature applies to the whole buffer. Later features override earlier ones
+            // with the same tag.
+            let mut hb_features: Vec<hb_feature_t> = options.features.iter().map(|feature| {
+                hb_feature_t {
+                    tag: feature.tag as hb_tag_t,
+                    value: feature.value,
+                    start: 0,
+                    end: u32::MAX as c_uint,
+                }
+            }).collect();
+            hb_shape(self.hb_font,
+                     hb_buffer,
+                     hb_features.as_mut_ptr(),
+                     hb_features.len() as c_uint);
+
+            // Right-to-left text comes back in visual order. Glyphs are stored in the order of
+            // the characters they belong to, and drawn in reverse.
+            if is_rtl {
+                hb_buffer_reverse(hb_buffer);
+            }
+
             self.save_glyph_results(text, glyphs, hb_buffer);
             hb_buffer_destroy(hb_buffer);
         }
@@ -393,19 +439,20 @@ impl Shaper {
             let end = covered_byte_span.end(); // FIXME: borrow checker workaround
             covered_byte_span.extend_to(cmp::min(end, byte_max));
 
+            // A character starts a cluster unless it is a combining mark or the like, which
+            // extends the cluster of the character before it.
+            let is_cluster_start = !is_cluster_extender(
+                text.char_at(char_byte_span.begin() as uint));
+
             // fast path: 1-to-1 mapping of single char and single glyph.
             if glyph_span.length() == 1 {
-                // TODO(Issue #214): cluster ranges need to be computed before
-                // shaping, and then consulted here.
-                // for now, just pretend that every character is a cluster start.
-                // (i.e., pretend there are no combining character sequences).
                 // 1-to-1 mapping of character to glyph also treated as ligature start.
                 let shape = glyph_data.get_entry_for_glyph(glyph_span.begin(), &mut y_pos);
                 let data = GlyphData::new(shape.codepoint,
                                           shape.advance,
                                           shape.offset,
                                           false,
-                                          true,
+                                          is_cluster_start,
                                           true);
                 glyphs.add_glyph_for_char_index(char_idx, &data);
             } else {
@@ -418,7 +465,7 @@ impl Shaper {
                                               shape.advance,
                                               shape.offset,
                                               false, // not missing
-                                              true,  // treat as cluster start
+                                              is_cluster_start,
                                               glyph_i > glyph_span.begin()));
                                               // all but first are ligature continuations
                 }
@@ -434,7 +481,8 @@ impl Shaper {
                     i = range.next as int;
                     if i >= covered_byte_span.end() { break; }
                     char_idx = char_idx + CharIndex(1);
-                    glyphs.add_nonglyph_for_char_index(char_idx, false, false);
+                    let is_cluster_start = !is_cluster_extender(text.char_at(i as uint));
+                    glyphs.add_nonglyph_for_char_index(char_idx, is_cluster_start, false);
                 }
             }
 
@@ -7,13 +7,26 @@
 //!
 //! Currently, only harfbuzz bindings are implemented.
 
+use font::FontFeature;
 use text::glyph::GlyphStore;
+use text::script::Script;
 
 pub use text::shaping::harfbuzz::Shaper;
 
 pub mod harfbuzz;
 
+/// Everything besides the font and the text that determines how a piece of text is shaped.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub struct ShapingOptions {
+    /// The script of the text. Runs are itemized so that each has a single script.
+    pub script: Script,
+    /// The language of the text, from the `lang` attribute, if it is known.
+    pub language: Option<String>,
+    /// The OpenType features to apply, in the order they should take effect.
+    pub features: Vec<FontFeature>,
+}
+
 pub trait ShaperMethods {
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore);
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore);
 }
 
@@ -8,7 +8,11 @@ use servo_util::range::Range;
 use servo_util::vec::{Comparator, FullBinarySearchMethods};
 use std::slice::Items;
 use sync::Arc;
-use text::glyph::{CharIndex, GlyphStore};
+use std::num::Zero;
+use text::glyph::{CharIndex, GlyphId, GlyphStore};
+use text::hyphenation::SOFT_HYPHEN;
+use text::script::{Script, is_cluster_extender};
+use text::shaping::ShapingOptions;
 use font::FontHandleMethods;
 use platform::font_template::FontTemplateData;
 
@@ -19,8 +23,14 @@ pub struct TextRun {
     pub font_template: Arc<FontTemplateData>,
     pub actual_pt_size: f64,
     pub font_metrics: FontMetrics,
+    /// The script of the text, which the run was itemized by.
+    pub script: Script,
     /// The glyph runs that make up this text run.
     pub glyphs: Arc<Vec<GlyphRun>>,
+    /// The glyph that is drawn at the end of a line broken at a soft hyphen, if the font has one.
+    pub hyphen_glyph: Option<GlyphId>,
+    /// The advance of `hyphen_glyph`.
+    pub hyphen_advance: Au,
 }
 
 /// A single series of glyphs within a text run.
@@ -30,6 +40,9 @@ pub struct GlyphRun {
     glyph_store: Arc<GlyphStore>,
     /// The range of characters in the containing run.
     range: Range<CharIndex>,
+    /// Whether the last character is a soft hyphen, so that a line broken after this glyph run
+    /// ends with a hyphen.
+    ends_with_soft_hyphen: bool,
 }
 
 pub struct SliceIterator<'a> {
@@ -116,79 +129,129 @@ impl<'a> Iterator<Range<CharIndex>> for LineIterator<'a> {
     }
 }
 
+/// A piece of the text of a run that starts at a line break opportunity and ends at the next.
+struct TextSlice {
+    /// Where the slice starts and ends in the text, in bytes.
+    bytes: (uint, uint),
+    /// The characters of the text that the slice covers.
+    chars: Range<CharIndex>,
+    is_whitespace: bool,
+}
+
+/// Splits text into slices at its line break opportunities.
+///
+/// TODO(Issue #230): do a better job. See Gecko's LineBreaker.
+fn break_into_slices(text: &str) -> Vec<TextSlice> {
+    let mut slices = vec!();
+    let mut cur_slice_is_whitespace = false;
+    let mut prev_char_is_soft_hyphen = false;
+    let (mut byte_last_boundary, mut char_last_boundary) = (0, CharIndex(0));
+    let mut char_i = CharIndex(0);
+    for (byte_i, ch) in text.char_indices() {
+        // Slices alternate between whitespace and non-whitespace,
+        // representing line break opportunities. Combining marks stay in the slice of the
+        // character they combine with. A soft hyphen ends its slice too, so that a word can
+        // be broken after it.
+        let slice_is_whitespace = cur_slice_is_whitespace;
+        let can_break_before = if is_cluster_extender(ch) {
+            false
+        } else if cur_slice_is_whitespace {
+            match ch {
+                ' ' | '\t' | '\n' => false,
+                _ => {
+                    cur_slice_is_whitespace = false;
+                    true
+                }
+            }
+        } else {
+            match ch {
+                ' ' | '\t' | '\n' => {
+                    cur_slice_is_whitespace = true;
+                    true
+                },
+                _ => prev_char_is_soft_hyphen,
+            }
+        };
+        prev_char_is_soft_hyphen = ch == SOFT_HYPHEN;
+
+        // End the current slice here if it's nonempty.
+        if can_break_before && byte_i > byte_last_boundary {
+            slices.push(TextSlice {
+                bytes: (byte_last_boundary, byte_i),
+                chars: Range::new(char_last_boundary, char_i - char_last_boundary),
+                is_whitespace: slice_is_whitespace,
+            });
+            byte_last_boundary = byte_i;
+            char_last_boundary = char_i;
+        }
+
+        char_i = char_i + CharIndex(1);
+    }
+
+    // End the final slice if it's nonempty.
+    if text.len() > byte_last_boundary {
+        slices.push(TextSlice {
+            bytes: (byte_last_boundary, text.len()),
+            chars: Range::new(char_last_boundary, char_i - char_last_boundary),
+            is_whitespace: cur_slice_is_whitespace,
+        });
+    }
+    slices
+}
+
+/// Sorts horizontal extents by where they start and merges the ones that overlap.
+fn merge_extents(mut extents: Vec<(Au, Au)>) -> Vec<(Au, Au)> {
+    extents.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    let mut merged: Vec<(Au, Au)> = vec!();
+    for &(start, end) in extents.iter() {
+        match merged.last().map(|&extent| extent) {
+            Some((last_start, last_end)) if start <= last_end => {
+                let last = merged.len() - 1;
+                *merged.get_mut(last) = (last_start, Au::max(last_end, end))
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
 impl<'a> TextRun {
-    pub fn new(font: &mut Font, text: String) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, text.as_slice());
+    pub fn new(font: &mut Font, text: String, options: &ShapingOptions) -> TextRun {
+        let glyphs = TextRun::break_and_shape(font, text.as_slice(), options);
+
+        // Prefer a real hyphen to the hyphen-minus for lines broken at soft hyphens.
+        let hyphen_glyph = font.glyph_index('\u2010').or_else(|| font.glyph_index('-'));
+        let hyphen_advance = match hyphen_glyph {
+            Some(glyph) => Au::from_frac_px(font.glyph_h_advance(glyph)),
+            None => Au(0),
+        };
+
         let run = TextRun {
             text: Arc::new(text),
             font_metrics: font.metrics.clone(),
             font_template: font.handle.get_template(),
             actual_pt_size: font.actual_pt_size,
+            script: options.script.clone(),
             glyphs: Arc::new(glyphs),
+            hyphen_glyph: hyphen_glyph,
+            hyphen_advance: hyphen_advance,
         };
         return run;
     }
 
-    pub fn break_and_shape(font: &mut Font, text: &str) -> Vec<GlyphRun> {
-        // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
-        let mut glyphs = vec!();
-        let (mut byte_i, mut char_i) = (0u, CharIndex(0));
-        let mut cur_slice_is_whitespace = false;
-        let (mut byte_last_boundary, mut char_last_boundary) = (0, CharIndex(0));
-        while byte_i < text.len() {
-            let range = text.char_range_at(byte_i);
-            let ch = range.ch;
-            let next = range.next;
-
-            // Slices alternate between whitespace and non-whitespace,
-            // representing line break opportunities.
-            let can_break_before = if cur_slice_is_whitespace {
-                match ch {
-                    ' ' | '\t' | '\n' => false,
-                    _ => {
-                        cur_slice_is_whitespace = false;
-                        true
-                    }
-                }
-            } else {
-                match ch {
-                    ' ' | '\t' | '\n' => {
-                        cur_slice_is_whitespace = true;
-                        true
-                    },
-                    _ => false
-                }
-            };
-
-            // Create a glyph store for this slice if it's nonempty.
-            if can_break_before && byte_i > byte_last_boundary {
-                let slice = text.slice(byte_last_boundary, byte_i).to_string();
-                debug!("creating glyph store for slice {} (ws? {}), {} - {} in run {}",
-                        slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
-                glyphs.push(GlyphRun {
-                    glyph_store: font.shape_text(slice, !cur_slice_is_whitespace),
-                    range: Range::new(char_last_boundary, char_i - char_last_boundary),
-                });
-                byte_last_boundary = byte_i;
-                char_last_boundary = char_i;
+    pub fn break_and_shape(font: &mut Font, text: &str, options: &ShapingOptions)
+                           -> Vec<GlyphRun> {
+        break_into_slices(text).into_iter().map(|slice| {
+            let (start, end) = slice.bytes;
+            let slice_text = text.slice(start, end);
+            debug!("creating glyph store for slice {} (ws? {}), {} - {} in run {}",
+                   slice_text, slice.is_whitespace, start, end, text);
+            GlyphRun {
+                glyph_store: font.shape_text(slice_text.to_string(), slice.is_whitespace, options),
+                range: slice.chars,
+                ends_with_soft_hyphen: slice_text.ends_with("\u00ad"),
             }
-
-            byte_i = next;
-            char_i = char_i + CharIndex(1);
-        }
-
-        // Create a glyph store for the final slice if it's nonempty.
-        if byte_i > byte_last_boundary {
-            let slice = text.slice_from(byte_last_boundary).to_string();
-            debug!("creating glyph store for final slice {} (ws? {}), {} - {} in run {}",
-                slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
-            glyphs.push(GlyphRun {
-                glyph_store: font.shape_text(slice, cur_slice_is_whitespace),
-                range: Range::new(char_last_boundary, char_i - char_last_boundary),
-            });
-        }
-
-        glyphs
+        }).collect()
     }
 
     pub fn char_len(&self) -> CharIndex {
@@ -202,6 +265,11 @@ impl<'a> TextRun {
         &*self.glyphs
     }
 
+    /// Returns true if the text of this run is written right to left.
+    pub fn is_rtl(&self) -> bool {
+        self.script.is_rtl()
+    }
+
     pub fn range_is_trimmable_whitespace(&self, range: &Range<CharIndex>) -> bool {
         self.iter_slices_for_range(range).all(|(slice_glyphs, _, _)| {
             slice_glyphs.is_whitespace()
@@ -241,10 +309,69 @@ impl<'a> TextRun {
         debug!("iterating outer range {:?}", range);
         self.iter_slices_for_range(range).fold(Au(0), |max_piece_width, (_, offset, slice_range)| {
             debug!("iterated on {:?}[{:?}]", offset, slice_range);
-            Au::max(max_piece_width, self.advance_for_range(&slice_range))
+            // A piece that a line can end after with a hyphen needs room for the hyphen too.
+            let mut piece_width = self.advance_for_range(&slice_range);
+            if self.breaks_with_hyphen_at(offset + slice_range.end()) {
+                piece_width = piece_width + self.hyphen_advance
+            }
+            Au::max(max_piece_width, piece_width)
         })
     }
 
+    /// Returns true if a line broken before the given character ends with a hyphen, because the
+    /// character before it is a soft hyphen.
+    pub fn breaks_with_hyphen_at(&self, index: CharIndex) -> bool {
+        if index == CharIndex(0) {
+            return false
+        }
+        match self.index_of_first_glyph_run_containing(index - CharIndex(1)) {
+            Some(glyph_run_index) => {
+                let glyph_run = &self.glyphs[glyph_run_index];
+                glyph_run.range.end() == index && glyph_run.ends_with_soft_hyphen
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the horizontal extents of the ink of the glyphs in `range` that cross the band
+    /// between `top` and `bottom`, which are distances below the baseline. The extents are
+    /// relative to the left of the range as it is drawn, sorted, and merged where they overlap.
+    /// Underlines skip over these so that they do not cut through descenders.
+    pub fn ink_extents_in_band(&self, font: &mut Font, range: &Range<CharIndex>, top: Au,
+                               bottom: Au)
+                               -> Vec<(Au, Au)> {
+        // Place the glyphs as `draw_text_into_context` does.
+        let is_rtl = self.is_rtl();
+        let mut pen_x = if is_rtl { self.advance_for_range(range) } else { Au(0) };
+        let mut extents = vec!();
+        for (glyphs, _, slice_range) in self.iter_slices_for_range(range) {
+            for (_, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
+                let glyph_x = if is_rtl {
+                    pen_x = pen_x - glyph.advance();
+                    pen_x
+                } else {
+                    pen_x = pen_x + glyph.advance();
+                    pen_x - glyph.advance()
+                };
+                let offset = glyph.offset().unwrap_or(Zero::zero());
+                match font.glyph_ink_bounds(glyph.id()) {
+                    Some(bounds) => {
+                        // The ink bounds have y pointing up from the baseline.
+                        let ink_top = offset.y - Au::from_frac_px(bounds.origin.y +
+                                                                   bounds.size.height);
+                        let ink_bottom = offset.y - Au::from_frac_px(bounds.origin.y);
+                        if ink_bottom > top && ink_top < bottom && bounds.size.width > 0.0 {
+                            let start = glyph_x + offset.x + Au::from_frac_px(bounds.origin.x);
+                            extents.push((start, start + Au::from_frac_px(bounds.size.width)))
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        merge_extents(extents)
+    }
+
     /// Returns the index of the first glyph run containing the given character index.
     fn index_of_first_glyph_run_containing(&self, index: CharIndex) -> Option<uint> {
         self.glyphs.as_slice().binary_search_index_by(&index, CharIndexComparator)
@@ -269,3 +396,60 @@ impl<'a> TextRun {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{break_into_slices, merge_extents};
+
+    use servo_util::geometry::Au;
+
+    /// Returns the text of each slice and whether it is whitespace.
+    fn slices(text: &str) -> Vec<(String, bool)> {
+        break_into_slices(text).into_iter().map(|slice| {
+            let (start, end) = slice.bytes;
+            (text.slice(start, end).to_string(), slice.is_whitespace)
+        }).collect()
+    }
+
+    fn slice(text: &str, is_whitespace: bool) -> (String, bool) {
+        (text.to_string(), is_whitespace)
+    }
+
+    #[test]
+    fn test_break_into_slices() {
+        assert_eq!(slices(""), vec!());
+        assert_eq!(slices("ab  cd"),
+                   vec!(slice("ab", false), slice("  ", true), slice("cd", false)));
+        // A soft hyphen ends a word slice without starting a whitespace one.
+        assert_eq!(slices("ab\u00adcd"), vec!(slice("ab\u00ad", false), slice("cd", false)));
+    }
+
+    #[test]
+    fn test_break_into_slices_keeps_combining_marks_with_their_base() {
+        assert_eq!(slices("e\u0301 x\u0301"),
+                   vec!(slice("e\u0301", false), slice(" ", true), slice("x\u0301", false)));
+        // A mark on a space stays with the space rather than starting a word.
+        assert_eq!(slices("a \u0301b"),
+                   vec!(slice("a", false), slice(" \u0301", true), slice("b", false)));
+        // Devanagari vowel signs, and Thai vowels and tone marks.
+        assert_eq!(slices("\u0915\u093f \u0915\u093f"),
+                   vec!(slice("\u0915\u093f", false), slice(" ", true),
+                        slice("\u0915\u093f", false)));
+        assert_eq!(slices("\u0e01\u0e34\u0e48 \u0e02"),
+                   vec!(slice("\u0e01\u0e34\u0e48", false), slice(" ", true),
+                        slice("\u0e02", false)));
+    }
+
+    #[test]
+    fn test_merge_extents() {
+        let extents = |pairs: &[(i32, i32)]| -> Vec<(Au, Au)> {
+            pairs.iter().map(|&(start, end)| (Au(start), Au(end))).collect()
+        };
+        assert_eq!(merge_extents(vec!()), vec!());
+        // Right-to-left text yields extents from right to left.
+        assert_eq!(merge_extents(extents(&[(50, 60), (10, 20)])), extents(&[(10, 20), (50, 60)]));
+        // Overlapping and touching extents, and one inside another, are merged.
+        assert_eq!(merge_extents(extents(&[(10, 20), (15, 30), (30, 40), (50, 60), (52, 55)])),
+                   extents(&[(10, 40), (50, 60)]));
+    }
+}
@@ -104,7 +104,8 @@ pub fn transform_text(text: &str, mode: CompressionMode,
     }
 
     fn is_always_discardable_char(_ch: char) -> bool {
-        // TODO: check for bidi control chars, soft hyphens.
+        // TODO: check for bidi control chars. Soft hyphens are kept: they are break
+        // opportunities, and `hyphens: none` removes them before the text gets here.
         false
     }
 }
@@ -0,0 +1,839 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoding of WOFF and WOFF2 web fonts into the plain sfnt (TrueType or OpenType) data that the
+//! platform font libraries read.
+//!
+//! See the WOFF 1.0 and WOFF 2.0 specifications, http://www.w3.org/TR/WOFF/ and
+//! http://www.w3.org/TR/WOFF2/.
+
+use brotli;
+use flate;
+use std::cmp;
+
+static WOFF_SIGNATURE: u32 = 0x774F4646;     // 'wOFF'
+static WOFF2_SIGNATURE: u32 = 0x774F4632;    // 'wOF2'
+static TTC_FLAVOR: u32 = 0x74746366;         // 'ttcf'
+
+static GLYF_TAG: u32 = 0x676C7966;
+static LOCA_TAG: u32 = 0x6C6F6361;
+static HHEA_TAG: u32 = 0x68686561;
+static HMTX_TAG: u32 = 0x686D7478;
+
+/// The tags that a WOFF2 table directory entry can refer to by index, in index order.
+static KNOWN_TAGS: [&'static [u8], ..63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// The largest sfnt font that a WOFF2 font is allowed to decode to. Fonts that big are unheard of,
+/// and all of it is allocated before decompression can tell whether the font is lying.
+static MAX_SFNT_SIZE: uint = 30 * 1024 * 1024;
+
+/// Returns the sfnt data of the given font, decoding it first if it is a WOFF or WOFF2 font.
+/// Fonts in other formats are returned unchanged, for the platform to accept or reject.
+pub fn decode_web_font(bytes: Vec<u8>) -> Result<Vec<u8>, ()> {
+    let signature = try!(Reader::new(bytes.as_slice()).read_u32());
+    if signature == WOFF_SIGNATURE {
+        decode_woff(bytes.as_slice())
+    } else if signature == WOFF2_SIGNATURE {
+        decode_woff2(bytes.as_slice())
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// A table of an sfnt font.
+struct Table {
+    tag: u32,
+    data: Vec<u8>,
+}
+
+/// Decodes a WOFF font, whose tables are each compressed with zlib.
+fn decode_woff(bytes: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut reader = Reader::new(bytes);
+    let _signature = try!(reader.read_u32());
+    let flavor = try!(reader.read_u32());
+    let length = try!(reader.read_u32());
+    let num_tables = try!(reader.read_u16());
+    if length as uint != bytes.len() || try!(reader.read_u16()) != 0 {
+        return Err(())
+    }
+    // totalSfntSize, the version and the metadata and private blocks are not needed.
+    try!(reader.skip(4 + 2 + 2 + 4 * 5));
+
+    let mut tables = vec!();
+    for _ in range(0, num_tables) {
+        let tag = try!(reader.read_u32());
+        let offset = try!(reader.read_u32()) as uint;
+        let compressed_length = try!(reader.read_u32()) as uint;
+        let original_length = try!(reader.read_u32()) as uint;
+        let _original_checksum = try!(reader.read_u32());
+        if compressed_length > original_length ||
+                offset.checked_add(&compressed_length).map_or(true, |end| end > bytes.len()) {
+            return Err(())
+        }
+
+        let table_data = bytes.slice(offset, offset + compressed_length);
+        let data = if compressed_length == original_length {
+            table_data.to_vec()
+        } else {
+            match flate::inflate_bytes_zlib(table_data) {
+                Some(data) => data.as_slice().to_vec(),
+                None => return Err(()),
+            }
+        };
+        if data.len() != original_length {
+            return Err(())
+        }
+        tables.push(Table {
+            tag: tag,
+            data: data,
+        });
+    }
+    Ok(write_sfnt(flavor, tables))
+}
+
+/// Where a table of a WOFF2 font is in the decompressed data.
+struct Woff2TableEntry {
+    tag: u32,
+    transformed: bool,
+    original_length: uint,
+    offset: uint,
+    length: uint,
+}
+
+/// Decodes a WOFF2 font, whose tables are compressed together with Brotli and whose `glyf`,
+/// `loca` and `hmtx` tables may be transformed to compress better.
+fn decode_woff2(bytes: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut reader = Reader::new(bytes);
+    let _signature = try!(reader.read_u32());
+    let flavor = try!(reader.read_u32());
+    let length = try!(reader.read_u32());
+    let num_tables = try!(reader.read_u16());
+    if length as uint != bytes.len() || try!(reader.read_u16()) != 0 {
+        return Err(())
+    }
+    let total_sfnt_size = try!(reader.read_u32()) as uint;
+    let total_compressed_size = try!(reader.read_u32()) as uint;
+    // The version and the metadata and private blocks are not needed.
+    try!(reader.skip(2 + 2 + 4 * 5));
+
+    // Font collections can't be used by `@font-face`.
+    if flavor == TTC_FLAVOR || total_sfnt_size > MAX_SFNT_SIZE {
+        return Err(())
+    }
+
+    let mut entries = vec!();
+    let mut decompressed_length = 0u;
+    let mut sfnt_length = 0u;
+    for _ in range(0, num_tables) {
+        let flags = try!(reader.read_u8());
+        let tag = match flags & 0x3f {
+            0x3f => try!(reader.read_u32()),
+            index => tag_from_bytes(KNOWN_TAGS[index as uint]),
+        };
+        // Version 0 is the transform for every table but `glyf` and `loca`, for which it is the
+        // null transform instead.
+        let transform_version = flags >> 6;
+        let transformed = if tag == GLYF_TAG || tag == LOCA_TAG {
+            transform_version != 3
+        } else {
+            transform_version != 0
+        };
+        let original_length = try!(reader.read_base128()) as uint;
+        let length = if transformed {
+            try!(reader.read_base128()) as uint
+        } else {
+            original_length
+        };
+        entries.push(Woff2TableEntry {
+            tag: tag,
+            transformed: transformed,
+            original_length: original_length,
+            offset: decompressed_length,
+            length: length,
+        });
+        decompressed_length = match decompressed_length.checked_add(&length) {
+            Some(decompressed_length) => decompressed_length,
+            None => return Err(()),
+        };
+        sfnt_length = match sfnt_length.checked_add(&original_length) {
+            Some(sfnt_length) => sfnt_length,
+            None => return Err(()),
+        };
+    }
+
+    // All of the decompressed data is allocated up front, so it has to fit in the sfnt font that
+    // the header promises. The rebuilt `glyf` and `loca` tables may not match `totalSfntSize`
+    // exactly, so the tables as they are rebuilt are only held to the overall limit.
+    if decompressed_length > total_sfnt_size || sfnt_length > MAX_SFNT_SIZE {
+        return Err(())
+    }
+
+    let compressed = try!(reader.read_bytes(total_compressed_size));
+    let data = match brotli::decompress(compressed, decompressed_length) {
+        Some(data) => data,
+        None => return Err(()),
+    };
+
+    // `glyf` and `loca` are rebuilt together, and `hmtx` needs the bounding boxes of the glyphs.
+    let mut rebuilt_glyf = None;
+    for entry in entries.iter() {
+        if entry.transformed && entry.tag == GLYF_TAG {
+            let table_data = data.slice(entry.offset, entry.offset + entry.length);
+            rebuilt_glyf = Some(try!(reconstruct_glyf(table_data)));
+        }
+    }
+
+    let mut tables = vec!();
+    for entry in entries.iter() {
+        let table_data = data.slice(entry.offset, entry.offset + entry.length);
+        if !entry.transformed {
+            tables.push(Table {
+                tag: entry.tag,
+                data: table_data.to_vec(),
+            });
+            continue
+        }
+
+        let &(ref glyf, ref loca, ref glyph_x_mins) = match rebuilt_glyf {
+            Some(ref rebuilt_glyf) => rebuilt_glyf,
+            None => return Err(()),
+        };
+        let table_data = if entry.tag == GLYF_TAG {
+            glyf.clone()
+        } else if entry.tag == LOCA_TAG {
+            // The transformed table is empty.
+            if entry.length != 0 {
+                return Err(())
+            }
+            loca.clone()
+        } else if entry.tag == HMTX_TAG {
+            let num_h_metrics = match entries.iter().position(|entry| entry.tag == HHEA_TAG) {
+                Some(index) if !entries[index].transformed => {
+                    let hhea = &entries[index];
+                    let mut reader = Reader::new(data.slice(hhea.offset, hhea.offset + hhea.length));
+                    try!(reader.skip(34));
+                    try!(reader.read_u16()) as uint
+                }
+                _ => return Err(()),
+            };
+            try!(reconstruct_hmtx(table_data, num_h_metrics, glyph_x_mins.as_slice()))
+        } else {
+            return Err(())
+        };
+        // The rebuilt `glyf` table encodes its points differently from the original font, so only
+        // the other tables have to come out the same size.
+        if entry.tag != GLYF_TAG && table_data.len() != entry.original_length {
+            return Err(())
+        }
+        tables.push(Table {
+            tag: entry.tag,
+            data: table_data,
+        });
+    }
+
+    Ok(write_sfnt(flavor, tables))
+}
+
+// Flags of the points of simple glyphs.
+static ON_CURVE_POINT: u8 = 0x01;
+static X_SHORT_VECTOR: u8 = 0x02;
+static Y_SHORT_VECTOR: u8 = 0x04;
+static X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+static Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+static OVERLAP_SIMPLE: u8 = 0x40;
+
+// Flags of the components of composite glyphs.
+static ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+static WE_HAVE_A_SCALE: u16 = 0x0008;
+static MORE_COMPONENTS: u16 = 0x0020;
+static WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+static WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+static WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// A point of a simple glyph, in font units.
+struct Point {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+/// Rebuilds the `glyf` and `loca` tables from a transformed `glyf` table (WOFF2 § 5.1). Also
+/// returns the `xMin` of each glyph, or zero for empty glyphs.
+fn reconstruct_glyf(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<i16>), ()> {
+    let mut header = Reader::new(data);
+    let _reserved = try!(header.read_u16());
+    let option_flags = try!(header.read_u16());
+    let num_glyphs = try!(header.read_u16()) as uint;
+    let index_format = try!(header.read_u16());
+
+    // The streams follow the header, in the order of their sizes in it.
+    let mut stream_offset = 36u;
+    let mut streams = vec!();
+    for _ in range(0, 7u) {
+        let size = try!(header.read_u32()) as uint;
+        let end = match stream_offset.checked_add(&size) {
+            Some(end) if end <= data.len() => end,
+            _ => return Err(()),
+        };
+        streams.push(Reader::new(data.slice(stream_offset, end)));
+        stream_offset = end;
+    }
+    let mut instruction_stream = streams.pop().unwrap();
+    let mut bbox_stream = streams.pop().unwrap();
+    let mut composite_stream = streams.pop().unwrap();
+    let mut glyph_stream = streams.pop().unwrap();
+    let mut flag_stream = streams.pop().unwrap();
+    let mut n_points_stream = streams.pop().unwrap();
+    let mut n_contour_stream = streams.pop().unwrap();
+
+    let bbox_bitmap = try!(bbox_stream.read_bytes(((num_glyphs + 31) / 32) * 4));
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        let end = cmp::min(stream_offset + (num_glyphs + 7) / 8, data.len());
+        Some(data.slice(stream_offset, end))
+    } else {
+        None
+    };
+    let bit_is_set = |bitmap: &[u8], index: uint| {
+        index / 8 < bitmap.len() && bitmap[index / 8] & (0x80 >> (index % 8)) != 0
+    };
+
+    let mut glyf = vec!();
+    let mut loca_offsets = vec!();
+    let mut x_mins = vec!();
+    for glyph_index in range(0, num_glyphs) {
+        loca_offsets.push(glyf.len());
+        let n_contours = try!(n_contour_stream.read_u16()) as i16;
+        let has_bbox = bit_is_set(bbox_bitmap, glyph_index);
+        if n_contours == 0 {
+            // An empty glyph, which takes no space.
+            if has_bbox {
+                return Err(())
+            }
+            x_mins.push(0);
+            continue
+        }
+
+        if n_contours < 0 {
+            // A composite glyph, whose bounding box is always given.
+            if !has_bbox {
+                return Err(())
+            }
+            let composite_start = composite_stream.position;
+            let mut have_instructions = false;
+            loop {
+                let flags = try!(composite_stream.read_u16());
+                let _glyph_index = try!(composite_stream.read_u16());
+                let mut arguments_size = if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                if flags & WE_HAVE_A_SCALE != 0 {
+                    arguments_size += 2
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    arguments_size += 4
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    arguments_size += 8
+                }
+                try!(composite_stream.skip(arguments_size));
+                have_instructions = have_instructions || flags & WE_HAVE_INSTRUCTIONS != 0;
+                if flags & MORE_COMPONENTS == 0 {
+                    break
+                }
+            }
+            push_u16(&mut glyf, n_contours as u16);
+            let bbox = try!(bbox_stream.read_bytes(8));
+            x_mins.push(((bbox[0] as u16 << 8) | bbox[1] as u16) as i16);
+            glyf.push_all(bbox);
+            glyf.push_all(composite_stream.data.slice(composite_start, composite_stream.position));
+            if have_instructions {
+                let instruction_length = try!(glyph_stream.read_255_u16()) as uint;
+                push_u16(&mut glyf, instruction_length as u16);
+                glyf.push_all(try!(instruction_stream.read_bytes(instruction_length)));
+            }
+        } else {
+            // A simple glyph.
+            let mut end_points = vec!();
+            let mut n_points = 0u;
+            for _ in range(0, n_contours) {
+                n_points += try!(n_points_stream.read_255_u16()) as uint;
+                end_points.push(n_points - 1);
+            }
+
+            let mut points = vec!();
+            let (mut x, mut y) = (0i32, 0i32);
+            for _ in range(0, n_points) {
+                let flag = try!(flag_stream.read_u8());
+                let (dx, dy) = try!(decode_triplet(flag & 0x7f, &mut glyph_stream));
+                x += dx;
+                y += dy;
+                points.push(Point {
+                    x: x,
+                    y: y,
+                    on_curve: flag & 0x80 == 0,
+                });
+            }
+            let instruction_length = try!(glyph_stream.read_255_u16()) as uint;
+
+            push_u16(&mut glyf, n_contours as u16);
+            if has_bbox {
+                let bbox = try!(bbox_stream.read_bytes(8));
+                x_mins.push(((bbox[0] as u16 << 8) | bbox[1] as u16) as i16);
+                glyf.push_all(bbox);
+            } else {
+                let x_min = points.iter().map(|point| point.x).min().unwrap_or(0);
+                let y_min = points.iter().map(|point| point.y).min().unwrap_or(0);
+                let x_max = points.iter().map(|point| point.x).max().unwrap_or(0);
+                let y_max = points.iter().map(|point| point.y).max().unwrap_or(0);
+                x_mins.push(x_min as i16);
+                for &value in [x_min, y_min, x_max, y_max].iter() {
+                    push_u16(&mut glyf, value as i16 as u16);
+                }
+            }
+            for &end_point in end_points.iter() {
+                push_u16(&mut glyf, end_point as u16);
+            }
+            push_u16(&mut glyf, instruction_length as u16);
+            glyf.push_all(try!(instruction_stream.read_bytes(instruction_length)));
+            let overlaps = match overlap_bitmap {
+                Some(bitmap) => bit_is_set(bitmap, glyph_index),
+                None => false,
+            };
+            write_points(&mut glyf, points.as_slice(), overlaps);
+        }
+
+        // Glyphs start at even offsets in both formats of `loca`, and we use four like most fonts.
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    loca_offsets.push(glyf.len());
+
+    let mut loca = vec!();
+    for &offset in loca_offsets.iter() {
+        if index_format == 0 {
+            // The short format can't point past 128K, which the rebuilt glyphs may have grown to.
+            if offset / 2 > 0xffff {
+                return Err(())
+            }
+            push_u16(&mut loca, (offset / 2) as u16);
+        } else {
+            push_u32(&mut loca, offset as u32);
+        }
+    }
+    Ok((glyf, loca, x_mins))
+}
+
+/// Decodes the change in position to the next point of a simple glyph, from the given flag and
+/// the bytes that follow it in the glyph stream (WOFF2 § 5.2).
+fn decode_triplet(flag: u8, glyph_stream: &mut Reader) -> Result<(i32, i32), ()> {
+    let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+    let flag_value = flag as i32;
+    if flag < 10 {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        Ok((0, with_sign(flag, ((flag_value & 14) << 7) + b0)))
+    } else if flag < 20 {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, (((flag_value - 10) & 14) << 7) + b0), 0))
+    } else if flag < 84 {
+        let b0 = flag_value - 20;
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+            with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f))))
+    } else if flag < 120 {
+        let b0 = flag_value - 84;
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        let b2 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+            with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2)))
+    } else if flag < 124 {
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        let b2 = try!(glyph_stream.read_u8()) as i32;
+        let b3 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, (b1 << 4) + (b2 >> 4)), with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3)))
+    } else {
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        let b2 = try!(glyph_stream.read_u8()) as i32;
+        let b3 = try!(glyph_stream.read_u8()) as i32;
+        let b4 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, (b1 << 8) + b2), with_sign(flag >> 1, (b3 << 8) + b4)))
+    }
+}
+
+/// Writes the flags and coordinates of the points of a simple glyph in the `glyf` format, which
+/// stores each coordinate as the change from the previous point.
+fn write_points(glyf: &mut Vec<u8>, points: &[Point], overlaps: bool) {
+    let mut flags = vec!();
+    let mut x_coordinates = vec!();
+    let mut y_coordinates = vec!();
+    let (mut last_x, mut last_y) = (0i32, 0i32);
+    for (index, point) in points.iter().enumerate() {
+        let mut flag = if point.on_curve { ON_CURVE_POINT } else { 0 };
+        if overlaps && index == 0 {
+            flag |= OVERLAP_SIMPLE
+        }
+
+        let dx = point.x - last_x;
+        if dx == 0 {
+            flag |= X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR
+        } else if dx > -256 && dx < 256 {
+            flag |= X_SHORT_VECTOR;
+            if dx > 0 {
+                flag |= X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR
+            }
+            x_coordinates.push(dx.abs() as u8);
+        } else {
+            push_u16(&mut x_coordinates, dx as i16 as u16);
+        }
+
+        let dy = point.y - last_y;
+        if dy == 0 {
+            flag |= Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR
+        } else if dy > -256 && dy < 256 {
+            flag |= Y_SHORT_VECTOR;
+            if dy > 0 {
+                flag |= Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR
+            }
+            y_coordinates.push(dy.abs() as u8);
+        } else {
+            push_u16(&mut y_coordinates, dy as i16 as u16);
+        }
+
+        flags.push(flag);
+        last_x = point.x;
+        last_y = point.y;
+    }
+    glyf.push_all(flags.as_slice());
+    glyf.push_all(x_coordinates.as_slice());
+    glyf.push_all(y_coordinates.as_slice());
+}
+
+/// Rebuilds the `hmtx` table from a transformed one, which leaves out the left side bearings
+/// that are equal to the `xMin` of their glyphs (WOFF2 § 5.4).
+fn reconstruct_hmtx(data: &[u8], num_h_metrics: uint, glyph_x_mins: &[i16])
+                    -> Result<Vec<u8>, ()> {
+    let num_glyphs = glyph_x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(())
+    }
+
+    let mut reader = Reader::new(data);
+    let flags = try!(reader.read_u8());
+    let mut advance_widths = vec!();
+    for _ in range(0, num_h_metrics) {
+        advance_widths.push(try!(reader.read_u16()));
+    }
+    let mut left_side_bearings = vec!();
+    for glyph_index in range(0, num_h_metrics) {
+        left_side_bearings.push(if flags & 1 == 0 {
+            try!(reader.read_u16()) as i16
+        } else {
+            glyph_x_mins[glyph_index]
+        });
+    }
+    for glyph_index in range(num_h_metrics, num_glyphs) {
+        left_side_bearings.push(if flags & 2 == 0 {
+            try!(reader.read_u16()) as i16
+        } else {
+            glyph_x_mins[glyph_index]
+        });
+    }
+
+    let mut hmtx = vec!();
+    for glyph_index in range(0, num_glyphs) {
+        if glyph_index < num_h_metrics {
+            push_u16(&mut hmtx, advance_widths[glyph_index]);
+        }
+        push_u16(&mut hmtx, left_side_bearings[glyph_index] as u16);
+    }
+    Ok(hmtx)
+}
+
+/// Writes an sfnt font with the given tables, sorting them by tag as the table directory must be.
+fn write_sfnt(flavor: u32, tables: Vec<Table>) -> Vec<u8> {
+    let mut tables = tables;
+    tables.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    let num_tables = tables.len();
+    let mut entry_selector = 0u;
+    while (2u << entry_selector) <= num_tables {
+        entry_selector += 1
+    }
+    let search_range = (1u << entry_selector) * 16;
+
+    let mut sfnt = vec!();
+    push_u32(&mut sfnt, flavor);
+    push_u16(&mut sfnt, num_tables as u16);
+    push_u16(&mut sfnt, search_range as u16);
+    push_u16(&mut sfnt, entry_selector as u16);
+    push_u16(&mut sfnt, (num_tables * 16 - search_range) as u16);
+
+    let mut offset = 12 + num_tables * 16;
+    for table in tables.iter() {
+        push_u32(&mut sfnt, table.tag);
+        push_u32(&mut sfnt, checksum(table.data.as_slice()));
+        push_u32(&mut sfnt, offset as u32);
+        push_u32(&mut sfnt, table.data.len() as u32);
+        offset += (table.data.len() + 3) & !3;
+    }
+    for table in tables.iter() {
+        sfnt.push_all(table.data.as_slice());
+        while sfnt.len() % 4 != 0 {
+            sfnt.push(0);
+        }
+    }
+    sfnt
+}
+
+/// The sum of the data as big-endian 32-bit integers, padded with zeroes.
+fn checksum(data: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for chunk in data.chunks(4) {
+        let mut value = 0u32;
+        for index in range(0, 4u) {
+            value = (value << 8) | (if index < chunk.len() { chunk[index] as u32 } else { 0 });
+        }
+        sum = sum + value;
+    }
+    sum
+}
+
+fn tag_from_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |tag, &byte| (tag << 8) | byte as u32)
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    push_u16(out, (value >> 16) as u16);
+    push_u16(out, value as u16);
+}
+
+/// Reads big-endian values from font data, failing instead of reading past the end.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: uint,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    fn read_bytes(&mut self, length: uint) -> Result<&'a [u8], ()> {
+        match self.position.checked_add(&length) {
+            Some(end) if end <= self.data.len() => {
+                let bytes = self.data.slice(self.position, end);
+                self.position = end;
+                Ok(bytes)
+            }
+            _ => Err(()),
+        }
+    }
+
+    fn skip(&mut self, length: uint) -> Result<(), ()> {
+        self.read_bytes(length).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ()> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, ()> {
+        self.read_bytes(2).map(|bytes| (bytes[0] as u16 << 8) | bytes[1] as u16)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ()> {
+        self.read_bytes(4).map(tag_from_bytes)
+    }
+
+    /// Reads a `UIntBase128`: up to five bytes of seven bits each, most significant first.
+    fn read_base128(&mut self) -> Result<u32, ()> {
+        let mut value = 0u32;
+        for index in range(0, 5u) {
+            let byte = try!(self.read_u8());
+            // Leading zeroes are not allowed.
+            if index == 0 && byte == 0x80 {
+                return Err(())
+            }
+            if value & 0xFE000000 != 0 {
+                return Err(())
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err(())
+    }
+
+    /// Reads a `255UInt16`, which takes one byte for small values.
+    fn read_255_u16(&mut self) -> Result<u16, ()> {
+        match try!(self.read_u8()) {
+            253 => self.read_u16(),
+            254 => self.read_u8().map(|value| value as u16 + 253 * 2),
+            255 => self.read_u8().map(|value| value as u16 + 253),
+            code => Ok(code as u16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_SFNT_SIZE, Reader, decode_triplet, decode_web_font, push_u16, push_u32};
+
+    /// Returns the data of the table with the given tag in an sfnt font.
+    fn find_table<'a>(sfnt: &'a [u8], tag: &[u8]) -> &'a [u8] {
+        let mut reader = Reader::new(sfnt);
+        reader.skip(4).unwrap();
+        let num_tables = reader.read_u16().unwrap();
+        reader.skip(6).unwrap();
+        for _ in range(0, num_tables) {
+            let table_tag = reader.read_bytes(4).unwrap();
+            reader.skip(4).unwrap();
+            let offset = reader.read_u32().unwrap() as uint;
+            let length = reader.read_u32().unwrap() as uint;
+            if table_tag == tag {
+                return sfnt.slice(offset, offset + length)
+            }
+        }
+        fail!("the font has no {} table", tag)
+    }
+
+    /// Returns the number of contours and the bounding box of each glyph of an sfnt font whose
+    /// `loca` table has the short format, or nothing for empty glyphs.
+    fn glyph_headers<'a>(sfnt: &'a [u8]) -> Vec<&'a [u8]> {
+        let glyf = find_table(sfnt, b"glyf");
+        let loca = find_table(sfnt, b"loca");
+        let mut reader = Reader::new(loca);
+        let offsets: Vec<uint> = range(0, loca.len() / 2).map(|_| {
+            reader.read_u16().unwrap() as uint * 2
+        }).collect();
+        offsets.as_slice().windows(2).map(|offsets| {
+            if offsets[0] == offsets[1] {
+                glyf.slice(0, 0)
+            } else {
+                glyf.slice(offsets[0], offsets[0] + 10)
+            }
+        }).collect()
+    }
+
+    /// Returns a WOFF2 font with a `name` table that holds "hello", and the given `totalSfntSize`.
+    fn woff2_with_name_table(total_sfnt_size: u32) -> Vec<u8> {
+        // "hello" as a single uncompressed Brotli meta-block.
+        let compressed = [0x0b, 0x02, 0x80, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x03];
+        let mut woff2 = vec!();
+        push_u32(&mut woff2, 0x774F4632);
+        push_u32(&mut woff2, 0x00010000);
+        push_u32(&mut woff2, (48 + 2 + compressed.len()) as u32);
+        push_u16(&mut woff2, 1);
+        push_u16(&mut woff2, 0);
+        push_u32(&mut woff2, total_sfnt_size);
+        push_u32(&mut woff2, compressed.len() as u32);
+        woff2.push_all(&[0, ..24]);
+        woff2.push_all(&[5, 5]);
+        woff2.push_all(compressed);
+        woff2
+    }
+
+    #[test]
+    fn test_read_base128() {
+        assert_eq!(Reader::new(&[0x3f]).read_base128(), Ok(63));
+        assert_eq!(Reader::new(&[0x81, 0x00]).read_base128(), Ok(128));
+        assert_eq!(Reader::new(&[0x8f, 0xff, 0xff, 0xff, 0x7f]).read_base128(), Ok(0xffffffff));
+        assert_eq!(Reader::new(&[0x80, 0x01]).read_base128(), Err(()));
+        assert_eq!(Reader::new(&[0x90, 0x80, 0x80, 0x80, 0x00]).read_base128(), Err(()));
+        assert_eq!(Reader::new(&[0x81]).read_base128(), Err(()));
+    }
+
+    #[test]
+    fn test_read_255_u16() {
+        assert_eq!(Reader::new(&[252]).read_255_u16(), Ok(252));
+        assert_eq!(Reader::new(&[255, 0]).read_255_u16(), Ok(253));
+        assert_eq!(Reader::new(&[254, 0]).read_255_u16(), Ok(506));
+        assert_eq!(Reader::new(&[253, 0x01, 0x02]).read_255_u16(), Ok(258));
+    }
+
+    #[test]
+    fn test_decode_triplet() {
+        assert_eq!(decode_triplet(1, &mut Reader::new(&[5])), Ok((0, 5)));
+        assert_eq!(decode_triplet(10, &mut Reader::new(&[5])), Ok((-5, 0)));
+        assert_eq!(decode_triplet(23, &mut Reader::new(&[0x12])), Ok((2, 3)));
+        assert_eq!(decode_triplet(127, &mut Reader::new(&[0x01, 0x00, 0x02, 0x00])),
+                   Ok((256, 512)));
+        assert_eq!(decode_triplet(127, &mut Reader::new(&[0x01])), Err(()));
+    }
+
+    #[test]
+    fn test_decode_uncompressed_woff() {
+        let table = b"abcdefg";
+        let mut woff = vec!();
+        push_u32(&mut woff, 0x774F4646);
+        push_u32(&mut woff, 0x00010000);
+        push_u32(&mut woff, (44 + 20 + 8) as u32);
+        push_u16(&mut woff, 1);
+        push_u16(&mut woff, 0);
+        push_u32(&mut woff, (12 + 16 + 8) as u32);
+        woff.push_all(&[0, ..24]);
+        woff.push_all(b"name");
+        push_u32(&mut woff, 44 + 20);
+        push_u32(&mut woff, table.len() as u32);
+        push_u32(&mut woff, table.len() as u32);
+        push_u32(&mut woff, 0);
+        woff.push_all(table);
+        woff.push(0);
+
+        let sfnt = decode_web_font(woff).unwrap();
+        assert_eq!(sfnt.slice(0, 12), [0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0].as_slice());
+        assert_eq!(sfnt.slice(12, 16), b"name");
+        assert_eq!(sfnt.slice(20, 28), [0, 0, 0, 28, 0, 0, 0, 7].as_slice());
+        assert_eq!(sfnt.slice_from(28), b"abcdefg\0");
+    }
+
+    #[test]
+    fn test_decode_woff2() {
+        let ttf = include_bin!("../../tests/ref/fonts/ahem/ahem.ttf");
+        let woff2 = include_bin!("../../tests/ref/fonts/ahem/ahem.woff2");
+        let sfnt = decode_web_font(woff2.to_vec()).unwrap();
+        let sfnt = sfnt.as_slice();
+
+        // The `glyf`, `loca` and `hmtx` tables of this font are transformed. All tables but
+        // `glyf` and `loca`, whose glyphs are written out differently, come back as they were.
+        let tags = [
+            b"OS/2", b"cmap", b"gasp", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"post",
+        ];
+        for tag in tags.iter() {
+            assert_eq!(find_table(sfnt, *tag), find_table(ttf, *tag));
+        }
+        assert_eq!(glyph_headers(sfnt), glyph_headers(ttf));
+    }
+
+    #[test]
+    fn test_woff2_size_limits() {
+        let sfnt = decode_web_font(woff2_with_name_table(12 + 16 + 8)).unwrap();
+        assert_eq!(find_table(sfnt.as_slice(), b"name"), b"hello");
+
+        // The tables don't fit in the sfnt font that the header promises.
+        assert_eq!(decode_web_font(woff2_with_name_table(4)), Err(()));
+        // The header promises more than any font should take.
+        assert_eq!(decode_web_font(woff2_with_name_table((MAX_SFNT_SIZE + 1) as u32)), Err(()));
+    }
+
+    #[test]
+    fn test_other_formats_are_unchanged() {
+        let ttf = vec!(0, 1, 0, 0, 0, 0);
+        assert_eq!(decode_web_font(ttf.clone()), Ok(ttf));
+        assert_eq!(decode_web_font(vec!(0, 1)), Err(()));
+    }
+}
@@ -17,7 +17,7 @@
 use construct::FlowConstructor;
 use context::LayoutContext;
 use floats::{ClearBoth, ClearLeft, ClearRight, FloatKind, FloatLeft, Floats, PlacementInfo};
-use flow::{BaseFlow, BlockFlowClass, FlowClass, Flow, ImmutableFlowUtils};
+use flow::{BaseFlow, BlockFlowClass, FlowClass, Flow, ImmutableFlowUtils, InlineFlowClass};
 use flow::{MutableFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal, mut_base};
 use flow;
 use fragment::{Fragment, ImageFragment, InlineBlockFragment, ScannedTextFragment};
@@ -25,26 +25,31 @@ use layout_debug;
 use model::{Auto, IntrinsicISizes, MarginCollapseInfo, MarginsCollapse};
 use model::{MarginsCollapseThrough, MaybeAuto, NoCollapsibleMargins, Specified, specified};
 use model::{specified_or_none};
+use multicol;
+use pagination;
 use wrapper::ThreadSafeLayoutNode;
 use style::ComputedValues;
 use style::computed_values::{clear, position};
 
 use collections::dlist::DList;
-use geom::{Size2D, Point2D, Rect};
+use geom::{Size2D, Point2D, Rect, SideOffsets2D};
 use gfx::color;
 use gfx::display_list::{BackgroundAndBorderLevel, BlockLevel, ContentStackingLevel, DisplayList};
 use gfx::display_list::{FloatStackingLevel, PositionedDescendantStackingLevel};
+use gfx::display_list::{BaseDisplayItem, ClipDisplayItem, ClipDisplayItemClass};
 use gfx::display_list::{RootOfStackingContextLevel};
 use gfx::render_task::RenderLayer;
-use servo_msg::compositor_msg::{FixedPosition, LayerId, Scrollable};
+use servo_msg::compositor_msg::{FixedPosition, LayerId, Scrollable, StickyConstraints};
+use servo_msg::compositor_msg::StickyPosition;
 use servo_util::geometry::{Au, MAX_AU};
 use servo_util::logical_geometry::{LogicalPoint, LogicalRect, LogicalSize};
 use std::cmp::{max, min};
 use std::fmt;
 use std::mem;
 use style::computed_values::{LPA_Auto, LPA_Length, LPA_Percentage, LPN_Length, LPN_None};
+use style::computed_values::{LengthOrPercentageOrAuto};
 use style::computed_values::{LPN_Percentage, LP_Length, LP_Percentage, box_sizing};
-use style::computed_values::{display, float, overflow};
+use style::computed_values::{display, float, overflow_x, overflow_y};
 use sync::Arc;
 
 /// Information specific to floated blocks.
@@ -823,6 +828,7 @@ impl BlockFlow {
 
         // At this point, `cur_b` is at the content edge of our box. Now iterate over children.
         let mut floats = self.base.floats.clone();
+        let page_grid = self.base.page_grid.clone();
         let mut layers_needed_for_descendants = false;
         for kid in self.base.child_iter() {
             if kid.is_absolutely_positioned() {
@@ -837,6 +843,20 @@ impl BlockFlow {
                 continue
             }
 
+            // When paginating, start the child on a new page if it asks for one.
+            let (forces_page_break_before, forces_page_break_after, avoids_page_break_inside) =
+                match page_grid {
+                    Some(_) if !kid.is_float() => pagination::page_break_properties(kid),
+                    _ => (false, false, false),
+                };
+            match page_grid {
+                Some(ref page_grid) if forces_page_break_before => {
+                    let page_start = page_grid.break_before(cur_b);
+                    translate_including_floats(&mut cur_b, page_start - cur_b, &mut floats);
+                }
+                _ => {}
+            }
+
             // Assign block-size now for the child if it was impacted by floats and we couldn't
             // before.
             flow::mut_base(kid).floats = floats.clone();
@@ -858,15 +878,39 @@ impl BlockFlow {
                 continue
             }
 
-            // If we have clearance, assume there are no floats in.
-            //
-            // FIXME(#2008, pcwalton): This could be wrong if we have `clear: left` or `clear:
-            // right` and there are still floats to impact, of course. But this gets complicated
-            // with margin collapse. Possibly the right thing to do is to lay out the block again
-            // in this rare case. (Note that WebKit can lay blocks out twice; this may be related,
-            // although I haven't looked into it closely.)
-            if kid.float_clearance() != clear::none {
-                flow::mut_base(kid).floats = Floats::new(self.fragment.style.writing_mode)
+            // If we have clearance, the floats that we clear can't impact us, since our border box
+            // will end up below them. Floats on the other side still can, so pass those in,
+            // positioned as though our block-start border edge were at the bottom of the cleared
+            // floats. Margin collapsing can make that guess wrong, in which case we lay the child
+            // out again below once we know where it really goes. See CSS 2.1 § 9.5.2.
+            let clear_type = match kid.float_clearance() {
+                clear::none => None,
+                clear::left => Some(ClearLeft),
+                clear::right => Some(ClearRight),
+                clear::both => Some(ClearBoth),
+            };
+            let mut assumed_clearance = Au(0);
+            match clear_type {
+                None => {}
+                Some(clear_type) => {
+                    assumed_clearance = floats.clearance(clear_type);
+                    let mut kid_floats = floats.without_cleared_floats(clear_type);
+                    kid_floats.translate(LogicalSize::new(self.fragment.style.writing_mode,
+                                                          Au(0),
+                                                          -assumed_clearance));
+                    flow::mut_base(kid).floats = kid_floats
+                }
+            }
+
+            // When paginating, lay the child out against the pages as though its border edge were
+            // where the floats above assume it is. A child that must not be broken is laid out
+            // whole instead, and moved as a unit below.
+            let page_grid_position = cur_b + assumed_clearance;
+            match page_grid {
+                Some(ref page_grid) if !avoids_page_break_inside => {
+                    flow::mut_base(kid).page_grid = Some(page_grid.translate(page_grid_position))
+                }
+                _ => {}
             }
 
             // Lay the child out if this was an in-order traversal.
@@ -882,17 +926,60 @@ impl BlockFlow {
             translate_including_floats(&mut cur_b, delta, &mut floats);
 
             // Clear past the floats that came in, if necessary.
-            let clearance = match kid.float_clearance() {
-                clear::none => Au(0),
-                clear::left => floats.clearance(ClearLeft),
-                clear::right => floats.clearance(ClearRight),
-                clear::both => floats.clearance(ClearBoth),
+            let clearance = match clear_type {
+                None => Au(0),
+                Some(clear_type) => floats.clearance(clear_type),
             };
             translate_including_floats(&mut cur_b, clearance, &mut floats);
 
+            // If we guessed the child's position wrong above and there are floats that can
+            // impact it, lay it out again now that `floats` is relative to its border edge.
+            match clear_type {
+                Some(clear_type) if need_to_process_child_floats &&
+                        delta + clearance != assumed_clearance => {
+                    let kid_floats = floats.without_cleared_floats(clear_type);
+                    if kid_floats.is_present() {
+                        flow::mut_base(kid).floats = kid_floats;
+                        kid.assign_block_size_for_inorder_child_if_necessary(layout_context);
+                    }
+                }
+                _ => {}
+            }
+
+            // Likewise, if margins moved the child from where it was laid out against the pages,
+            // lay it out again where it really is.
+            match page_grid {
+                Some(ref page_grid) if !avoids_page_break_inside && cur_b != page_grid_position => {
+                    flow::mut_base(kid).page_grid = Some(page_grid.translate(cur_b));
+                    flow::mut_base(kid).floats = match clear_type {
+                        Some(clear_type) => floats.without_cleared_floats(clear_type),
+                        None => floats.clone(),
+                    };
+                    kid.assign_block_size_for_inorder_child_if_necessary(layout_context);
+                }
+                _ => {}
+            }
+
+            // A child that must not be broken across pages starts the next page instead of
+            // straddling a page break, if it fits on one.
+            match page_grid {
+                Some(ref page_grid) if avoids_page_break_inside => {
+                    let kid_block_end = cur_b + flow::base(kid).position.size.block;
+                    let kid_block_start = page_grid.avoid_break_inside(cur_b, kid_block_end);
+                    translate_including_floats(&mut cur_b, kid_block_start - cur_b, &mut floats);
+                }
+                _ => {}
+            }
+
             // At this point, `cur_b` is at the border edge of the child.
             flow::mut_base(kid).position.start.b = cur_b;
 
+            // A child laid out here when paginating has moved since the traversal stored its
+            // overflow.
+            if page_grid.is_some() {
+                (&mut *kid).store_overflow(layout_context)
+            }
+
             // Now pull out the child's outgoing floats. We didn't do this immediately after the
             // `assign_block_size_for_inorder_child_if_necessary` call because clearance on a block
             // operates on the floats that come *in*, not the floats that go *out*.
@@ -909,6 +996,15 @@ impl BlockFlow {
             let delta =
                 margin_collapse_info.advance_block_end_margin(&kid_base.collapsible_margins);
             translate_including_floats(&mut cur_b, delta, &mut floats);
+
+            // When paginating, start whatever follows the child on a new page if it asks for one.
+            match page_grid {
+                Some(ref page_grid) if forces_page_break_after => {
+                    let page_start = page_grid.break_before(cur_b);
+                    translate_including_floats(&mut cur_b, page_start - cur_b, &mut floats);
+                }
+                _ => {}
+            }
         }
 
         // Mark ourselves for layerization if that will be necessary to paint in the proper order
@@ -1079,12 +1175,23 @@ impl BlockFlow {
                                              .relative_containing_block_size);
 
         // Add the box that starts the block context.
+        let flow_origin =
+            self.base.abs_position.add_size(&rel_offset.to_physical(self.base.writing_mode));
         let mut display_list = DisplayList::new();
-        let mut accumulator = self.fragment.build_display_list(
-            &mut display_list,
-            layout_context,
-            self.base.abs_position.add_size(&rel_offset.to_physical(self.base.writing_mode)),
-            background_border_level);
+        let mut accumulator = self.fragment.build_display_list(&mut display_list,
+                                                               layout_context,
+                                                               flow_origin,
+                                                               background_border_level);
+
+        // The contents of a scroll container that actually overflows go into their own layer so
+        // that the compositor can scroll them without a reflow. Otherwise they are just clipped.
+        let scroll_areas = if self.is_scroll_container() {
+            self.scroll_areas(flow_origin)
+        } else {
+            None
+        };
+        let is_scroll_container = scroll_areas.is_some();
+        let mut scrolled_display_list = DisplayList::new();
 
         let mut child_layers = DList::new();
         for kid in self.base.child_iter() {
@@ -1093,20 +1200,184 @@ impl BlockFlow {
                 continue
             }
 
-            accumulator.push_child(&mut display_list, kid);
+            if is_scroll_container {
+                scrolled_display_list.push_all_move(
+                    mem::replace(&mut flow::mut_base(kid).display_list, DisplayList::new()))
+            } else {
+                accumulator.push_child(&mut display_list, kid);
+            }
             child_layers.append(mem::replace(&mut flow::mut_base(kid).layers, DList::new()))
         }
 
         // Process absolute descendant links.
         for abs_descendant_link in self.base.abs_descendants.iter() {
             // TODO(pradeep): Send in our absolute position directly.
-            accumulator.push_child(&mut display_list, abs_descendant_link);
+            if is_scroll_container {
+                scrolled_display_list.push_all_move(
+                    mem::replace(&mut flow::mut_base(abs_descendant_link).display_list,
+                                 DisplayList::new()))
+            } else {
+                accumulator.push_child(&mut display_list, abs_descendant_link);
+            }
             child_layers.append(mem::replace(&mut flow::mut_base(abs_descendant_link).layers,
                                              DList::new()));
         }
 
         accumulator.finish(&mut *self, display_list);
-        self.base.layers = child_layers
+
+        match scroll_areas {
+            Some((padding_box, scrollable_area)) => {
+                self.base.layers = self.build_scroll_layers(padding_box,
+                                                            scrollable_area,
+                                                            scrolled_display_list,
+                                                            child_layers)
+            }
+            None => self.base.layers = child_layers,
+        }
+    }
+
+    /// Returns true if this flow clips its contents and lets the user scroll them, per the
+    /// `overflow-x` and `overflow-y` properties. The root flow is scrolled by the compositor's
+    /// root layer instead.
+    pub fn is_scroll_container(&self) -> bool {
+        if self.is_root() {
+            return false
+        }
+        let box_style = self.fragment.style().get_box();
+        match (box_style.overflow_x, box_style.overflow_y) {
+            (overflow_x::scroll, _) | (overflow_x::auto, _) |
+            (_, overflow_y::scroll) | (_, overflow_y::auto) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the distance from the block-start edge of this flow to the baseline of its last
+    /// line box in the normal flow, or `None` if it has no line boxes. This is the baseline of an
+    /// `inline-block`, per CSS 2.1 § 10.8.1.
+    ///
+    /// FIXME: Tables should report the baseline of their first row.
+    pub fn last_line_baseline(&self) -> Option<Au> {
+        let box_style = self.fragment.style().get_box();
+        if box_style.overflow_x != overflow_x::visible ||
+                box_style.overflow_y != overflow_y::visible {
+            return None
+        }
+
+        let mut baseline = None;
+        for kid in self.base.children.iter() {
+            if kid.is_absolutely_positioned() || kid.is_float() {
+                continue
+            }
+
+            let kid_block_start = flow::base(kid).position.start.b;
+            let kid_baseline = match kid.class() {
+                InlineFlowClass => {
+                    kid.as_immutable_inline().lines.as_slice().last().map(|line| {
+                        line.bounds.start.b + line.baseline_offset
+                    })
+                }
+                BlockFlowClass => kid.as_immutable_block().last_line_baseline(),
+                _ => None,
+            };
+            match kid_baseline {
+                Some(kid_baseline) => baseline = Some(kid_block_start + kid_baseline),
+                None => {}
+            }
+        }
+        baseline
+    }
+
+    /// The ID of the layer that clips and scrolls the contents of this scroll container.
+    fn scroll_root_layer_id(&self) -> LayerId {
+        LayerId::scroll_root(self.fragment.node.id() as uint)
+    }
+
+    /// The ID of the layer that holds the scrolled contents of this scroll container.
+    fn scrolled_content_layer_id(&self) -> LayerId {
+        self.layer_id(2)
+    }
+
+    /// Returns the padding box of this scroll container and the area that its contents can be
+    /// scrolled over, both in page coordinates, or `None` if the contents fit inside the padding
+    /// box and there is nothing to scroll.
+    ///
+    /// `flow_origin` is the position of this flow in page coordinates.
+    fn scroll_areas(&self, flow_origin: Point2D<Au>) -> Option<(Rect<Au>, Rect<Au>)> {
+        // FIXME(#2795): Get the real container size
+        let container_size = Size2D::zero();
+        let writing_mode = self.base.writing_mode;
+
+        let padding_box = self.generated_containing_block_rect().to_physical(writing_mode,
+                                                                             container_size);
+        let padding_box = Rect(padding_box.origin + flow_origin, padding_box.size);
+
+        // The overflow area is stored relative to our parent, like our position.
+        let position = self.base.position.to_physical(writing_mode, container_size);
+        let overflow = self.base.overflow.to_physical(writing_mode, container_size);
+        let overflow = Rect(flow_origin + overflow.origin - position.origin, overflow.size);
+
+        // Content can be scrolled to from the padding box origin onwards; overflow to the top or
+        // left is unreachable, as in other browsers.
+        let scrollable_area = padding_box.union(&overflow);
+        let scrollable_area = Rect(padding_box.origin,
+                                   Size2D(scrollable_area.max_x() - padding_box.origin.x,
+                                          scrollable_area.max_y() - padding_box.origin.y));
+
+        // Layers are positioned in whole pixels, so overflow of less than a pixel can't scroll.
+        if scrollable_area.size.width.to_nearest_px() <= padding_box.size.width.to_nearest_px() &&
+                scrollable_area.size.height.to_nearest_px() <=
+                    padding_box.size.height.to_nearest_px() {
+            return None
+        }
+        Some((padding_box, scrollable_area))
+    }
+
+    /// Builds the layers for a scroll container: a scroll root that clips to the padding box,
+    /// containing a layer as large as the scrollable overflow area that holds the given display
+    /// list. Layers belonging to descendants are nested inside the scroll root so that they
+    /// scroll along with the contents. The scrolled contents must be the first layer nested
+    /// inside the scroll root; layout finds the scroll range of the container that way.
+    fn build_scroll_layers(&self,
+                           padding_box: Rect<Au>,
+                           scrollable_area: Rect<Au>,
+                           display_list: DisplayList,
+                           child_layers: DList<RenderLayer>)
+                           -> DList<RenderLayer> {
+        let to_pixel_rect = |rect: Rect<Au>| {
+            Rect(Point2D(rect.origin.x.to_nearest_px() as uint,
+                         rect.origin.y.to_nearest_px() as uint),
+                 Size2D(rect.size.width.to_nearest_px() as uint,
+                        rect.size.height.to_nearest_px() as uint))
+        };
+
+        let scroll_root_id = self.scroll_root_layer_id();
+        let mut layers = DList::new();
+        layers.push(RenderLayer {
+            id: scroll_root_id,
+            display_list: Arc::new(DisplayList::new()),
+            position: to_pixel_rect(padding_box),
+            background_color: color::rgba(1.0, 1.0, 1.0, 0.0),
+            scroll_policy: Scrollable,
+            parent_id: None,
+            is_scroll_root: true,
+        });
+        layers.push(RenderLayer {
+            id: self.scrolled_content_layer_id(),
+            display_list: Arc::new(display_list.flatten(ContentStackingLevel)
+                                               .build_spatial_index()),
+            position: to_pixel_rect(scrollable_area),
+            background_color: color::rgba(1.0, 1.0, 1.0, 0.0),
+            scroll_policy: Scrollable,
+            parent_id: Some(scroll_root_id),
+            is_scroll_root: false,
+        });
+        for mut layer in child_layers.into_iter() {
+            if layer.parent_id.is_none() {
+                layer.parent_id = Some(scroll_root_id)
+            }
+            layers.push(layer)
+        }
+        layers
     }
 
     /// Add display items for current block.
@@ -1120,6 +1391,8 @@ impl BlockFlow {
             self.build_display_list_float(layout_context)
         } else if self.is_absolutely_positioned() {
             self.build_display_list_abs(layout_context)
+        } else if self.is_sticky_positioned() {
+            self.build_display_list_sticky(layout_context)
         } else {
             self.build_display_list_block_common(layout_context, BlockLevel)
         }
@@ -1235,6 +1508,7 @@ impl BlockFlow {
     /// Add display items for Absolutely Positioned flow.
     fn build_display_list_abs(&mut self, layout_context: &LayoutContext) {
         self.build_display_list_block_common(layout_context, RootOfStackingContextLevel);
+        self.clip_display_list_to_clip_rect_if_necessary();
 
         if !self.base.absolute_position_info.layers_needed_for_positioned_flows &&
                 !self.base.flags.needs_layer() {
@@ -1261,14 +1535,109 @@ impl BlockFlow {
         let display_list = mem::replace(&mut self.base.display_list, DisplayList::new());
         let new_layer = RenderLayer {
             id: self.layer_id(0),
-            display_list: Arc::new(display_list.flatten(ContentStackingLevel)),
+            display_list: Arc::new(display_list.flatten(ContentStackingLevel)
+                                               .build_spatial_index()),
             position: Rect(origin, size),
             background_color: color::rgba(1.0, 1.0, 1.0, 0.0),
             scroll_policy: scroll_policy,
+            parent_id: None,
+            is_scroll_root: false,
         };
         self.base.layers.push(new_layer)
     }
 
+    /// Clips the display list of this absolutely positioned flow, including its own background and
+    /// borders, to the rectangle given by the `clip` property, if any.
+    fn clip_display_list_to_clip_rect_if_necessary(&mut self) {
+        let clip_rect = match self.fragment.style().get_box().clip {
+            None => return,
+            Some(clip_rect) => clip_rect,
+        };
+
+        // FIXME(#2795): Get the real container size
+        let container_size = Size2D::zero();
+        let border_box = self.fragment.border_box.to_physical(self.base.writing_mode,
+                                                              container_size);
+        let origin = self.base.abs_position + border_box.origin;
+        let right = clip_rect.right.unwrap_or(border_box.size.width);
+        let bottom = clip_rect.bottom.unwrap_or(border_box.size.height);
+        let bounds = Rect(Point2D(origin.x + clip_rect.left, origin.y + clip_rect.top),
+                          Size2D(max(right - clip_rect.left, Au(0)),
+                                 max(bottom - clip_rect.top, Au(0))));
+
+        let display_list = mem::replace(&mut self.base.display_list, DisplayList::new());
+        let base = BaseDisplayItem::new(bounds, self.fragment.node, RootOfStackingContextLevel);
+        self.base.display_list.push(ClipDisplayItemClass(box ClipDisplayItem::new(base,
+                                                                                   display_list)))
+    }
+
+    /// Add display items for a sticky-positioned flow. Sticky flows are laid out like relatively
+    /// positioned flows with no offset, but are placed in their own layer so that the compositor
+    /// can keep them in view as the nearest scrolling ancestor scrolls, without a reflow.
+    fn build_display_list_sticky(&mut self, layout_context: &LayoutContext) {
+        self.build_display_list_block_common(layout_context, RootOfStackingContextLevel);
+
+        // FIXME(#2795): Get the real container size
+        let container_size = Size2D::zero();
+        let writing_mode = self.base.writing_mode;
+
+        // The overflow area is stored relative to our parent, like our position.
+        //
+        // FIXME: The insets apply to the whole layer, including any overflow, rather than to the
+        // border box.
+        let position = self.base.position.to_physical(writing_mode, container_size);
+        let overflow = self.base.overflow.to_physical(writing_mode, container_size);
+        let layer_rect = Rect(self.base.abs_position + overflow.origin - position.origin,
+                              overflow.size);
+
+        // FIXME: Percentage insets should be resolved against the size of the nearest scrolling
+        // ancestor, not the containing block.
+        let containing_block = self.base.absolute_position_info.sticky_containing_block;
+        let inset = |value: LengthOrPercentageOrAuto, containing_block_length: Au| {
+            match value {
+                LPA_Auto => None,
+                value => {
+                    Some(MaybeAuto::from_style(value, containing_block_length).specified_or_zero()
+                                                                            .to_subpx() as f32)
+                }
+            }
+        };
+        let offsets = self.fragment.style().get_positionoffsets();
+        let insets = SideOffsets2D::new(inset(offsets.top, containing_block.size.height),
+                                        inset(offsets.right, containing_block.size.width),
+                                        inset(offsets.bottom, containing_block.size.height),
+                                        inset(offsets.left, containing_block.size.width));
+
+        let to_px_rect = |rect: Rect<Au>| {
+            Rect(Point2D(rect.origin.x.to_subpx() as f32, rect.origin.y.to_subpx() as f32),
+                 Size2D(rect.size.width.to_subpx() as f32, rect.size.height.to_subpx() as f32))
+        };
+        let scroll_policy = StickyPosition(StickyConstraints {
+            insets: insets,
+            containing_block: to_px_rect(containing_block),
+        });
+
+        // The layer is nested inside the layer of the nearest scroll container, if there is one;
+        // see `build_scroll_layers()`.
+        let display_list = mem::replace(&mut self.base.display_list, DisplayList::new());
+        let mut layers = DList::new();
+        layers.push(RenderLayer {
+            id: self.layer_id(0),
+            display_list: Arc::new(display_list.flatten(ContentStackingLevel)
+                                               .build_spatial_index()),
+            position: Rect(Point2D(layer_rect.origin.x.to_nearest_px() as uint,
+                                   layer_rect.origin.y.to_nearest_px() as uint),
+                           Size2D(layer_rect.size.width.to_nearest_px() as uint,
+                                  layer_rect.size.height.to_nearest_px() as uint)),
+            background_color: color::rgba(1.0, 1.0, 1.0, 0.0),
+            scroll_policy: scroll_policy,
+            parent_id: None,
+            is_scroll_root: false,
+        });
+        layers.append(mem::replace(&mut self.base.layers, DList::new()));
+        self.base.layers = layers
+    }
+
     /// Return the block-start outer edge of the hypothetical box for an absolute flow.
     ///
     /// This is wrt its parent flow box.
@@ -1389,7 +1758,7 @@ impl BlockFlow {
                 kid_base.flags.set_impacted_by_right_floats(inline_end_floats_impact_child);
             }
 
-            if kid.is_block_flow() {
+            if kid.is_block_like() {
                 let kid_block = kid.as_block();
                 kid_block.inline_size_of_preceding_left_floats =
                     inline_size_of_preceding_left_floats;
@@ -1427,7 +1796,9 @@ impl BlockFlow {
             display::table_cell | display::table_caption | display::inline_block => {
                 OtherFormattingContext
             }
-            _ if style.get_box().overflow != overflow::visible => BlockFormattingContext,
+            _ if style.get_box().overflow_x != overflow_x::visible ||
+                    style.get_box().overflow_y != overflow_y::visible => BlockFormattingContext,
+            _ if multicol::is_multicol(style) => BlockFormattingContext,
             _ => NonformattingContext,
         }
     }
@@ -1516,14 +1887,32 @@ impl Flow for BlockFlow {
         let mut left_float_width = Au(0);
         let mut right_float_width = Au(0);
         for child_ctx in self.base.child_iter() {
-            assert!(child_ctx.is_block_flow() ||
+            assert!(child_ctx.is_block_like() ||
                     child_ctx.is_inline_flow() ||
                     child_ctx.is_table_kind());
 
             let float_kind = child_ctx.float_kind();
+            let clearance = child_ctx.float_clearance();
             let child_base = flow::mut_base(child_ctx);
 
             if !fixed_width {
+                // Floats that this child clears end up above it and any floats after it, so
+                // they can't sit beside those and their inline-sizes mustn't be summed with them.
+                if clearance != clear::none {
+                    intrinsic_inline_sizes.preferred_inline_size =
+                        max(intrinsic_inline_sizes.preferred_inline_size,
+                            left_float_width + right_float_width);
+                    match clearance {
+                        clear::none => {}
+                        clear::left => left_float_width = Au(0),
+                        clear::right => right_float_width = Au(0),
+                        clear::both => {
+                            left_float_width = Au(0);
+                            right_float_width = Au(0);
+                        }
+                    }
+                }
+
                 intrinsic_inline_sizes.minimum_inline_size =
                     max(intrinsic_inline_sizes.minimum_inline_size,
                                   child_base.intrinsic_inline_sizes.total_minimum_inline_size());
@@ -1657,7 +2046,7 @@ impl Flow for BlockFlow {
             self.assign_inline_position_for_formatting_context();
         }
 
-        if self.base.flags.impacted_by_floats() {
+        if self.base.flags.impacted_by_floats() || self.base.page_grid.is_some() {
             self.assign_block_size(layout_context);
             return true
         }
@@ -1736,6 +2125,12 @@ impl Flow for BlockFlow {
         absolute_position_info.relative_containing_block_size = self.fragment.content_box().size;
         absolute_position_info.layers_needed_for_positioned_flows =
             self.base.flags.layers_needed_for_descendants();
+        let content_box = self.fragment.content_box();
+        absolute_position_info.sticky_containing_block =
+            Rect(self.base.abs_position +
+                 (content_box.start + relative_offset).to_physical(self.base.writing_mode,
+                                                                   container_size),
+                 content_box.size.to_physical(self.base.writing_mode));
 
         // Process children.
         let this_position = self.base.abs_position;
@@ -1783,9 +2178,9 @@ impl Flow for BlockFlow {
 
     /// Return true if this is the root of an Absolute flow tree.
     ///
-    /// It has to be either relatively positioned or the Root flow.
+    /// It has to be either relatively or sticky positioned or the Root flow.
     fn is_root_of_absolute_flow_tree(&self) -> bool {
-        self.is_relatively_positioned() || self.is_root()
+        self.is_relatively_positioned() || self.is_sticky_positioned() || self.is_root()
     }
 
     /// Return the dimensions of the containing block generated by this flow for absolutely-
@@ -11,19 +11,22 @@
 //! maybe it's an absolute or fixed position thing that hasn't found its containing block yet.
 //! Construction items bubble up the tree from children to parents until they find their homes.
 //!
-//! TODO(pcwalton): There is no incremental reflow yet. This scheme requires that nodes either have
-//! weak references to flows or that there be some mechanism to efficiently (O(1) time) "blow
-//! apart" a flow tree and have the flows migrate "home" to their respective DOM nodes while we
-//! perform flow tree construction. The precise mechanism for this will take some experimentation
-//! to get right.
+//! Flows stay attached to their nodes between reflows: taking a flow out of a node to put it into
+//! its parent leaves a reference to it behind. Construction items, on the other hand, are consumed
+//! by their parents, so the nodes that produced them are visited again whenever their parents are
+//! rebuilt.
 
 #![deny(unsafe_block)]
 
 use css::node_style::StyledNode;
+use css::node_util::NodeUtil;
 use block::BlockFlow;
 use context::LayoutContext;
 use floats::FloatKind;
-use flow::{Flow, ImmutableFlowUtils, MutableOwnedFlowUtils};
+use flow::{BlockFlowClass, Flow, ImmutableFlowUtils, InlineFlowClass, MulticolFlowClass};
+use flow::{MutableOwnedFlowUtils, TableCaptionFlowClass, TableCellFlowClass};
+use flow::{TableColGroupFlowClass, TableFlowClass, TableRowFlowClass, TableRowGroupFlowClass};
+use flow::TableWrapperFlowClass;
 use flow::{Descendants, AbsDescendants};
 use flow;
 use flow_ref::FlowRef;
@@ -34,7 +37,12 @@ use fragment::{InlineBlockFragmentInfo, InputFragment, InputFragmentInfo, Specif
 use fragment::{TableCellFragment, TableColumnFragment, TableColumnFragmentInfo, TableFragment};
 use fragment::{TableRowFragment, TableWrapperFragment, UnscannedTextFragment};
 use fragment::{UnscannedTextFragmentInfo};
+use generated_content::GeneratedQuote;
+use generated_content;
+use incremental::{RebuildFlow, RestyleDamage};
 use inline::{InlineFragments, InlineFlow};
+use multicol::MulticolFlow;
+use multicol;
 use parallel;
 use table_wrapper::TableWrapperFlow;
 use table::TableFlow;
@@ -58,10 +66,13 @@ use script::dom::node::{CommentNodeTypeId, DoctypeNodeTypeId, DocumentFragmentNo
 use script::dom::node::{DocumentNodeTypeId, ElementNodeTypeId, ProcessingInstructionNodeTypeId};
 use script::dom::node::{TextNodeTypeId};
 use script::dom::htmlobjectelement::is_image_data;
+use libc::uintptr_t;
+use servo_net::image::holder::ImageHolder;
+use std::collections::hashmap::HashMap;
 use std::mem;
 use std::sync::atomics::Relaxed;
-use style::ComputedValues;
-use style::computed_values::{display, position, float};
+use style::{ComputedValues, cascade_anonymous};
+use style::computed_values::{content, display, position, float};
 use sync::Arc;
 use url::Url;
 
@@ -145,8 +156,8 @@ struct InlineFragmentsAccumulator {
     fragments: InlineFragments,
 
     /// Whether we've created a range to enclose all the fragments. This will be Some() if the outer node
-    /// is an inline and None otherwise.
-    enclosing_style: Option<Arc<ComputedValues>>,
+    /// is an inline and None otherwise. Holds the outer node along with its style.
+    enclosing_style: Option<(OpaqueNode, Arc<ComputedValues>)>,
 }
 
 impl InlineFragmentsAccumulator {
@@ -161,7 +172,8 @@ impl InlineFragmentsAccumulator {
         let fragments = InlineFragments::new();
         InlineFragmentsAccumulator {
             fragments: fragments,
-            enclosing_style: Some(node.style().clone()),
+            enclosing_style: Some((OpaqueNodeMethods::from_thread_safe_layout_node(node),
+                                   node.style().clone())),
         }
     }
 
@@ -172,9 +184,9 @@ impl InlineFragmentsAccumulator {
         } = self;
 
         match enclosing_style {
-            Some(enclosing_style) => {
+            Some((enclosing_node, enclosing_style)) => {
                 for frag in fragments.fragments.iter_mut() {
-                    frag.add_inline_context_style(enclosing_style.clone());
+                    frag.add_inline_context_style(enclosing_node, enclosing_style.clone());
                 }
             }
             None => {}
@@ -212,16 +224,58 @@ impl<'a> FlowConstructor<'a> {
             Some(url) => {
                 // FIXME(pcwalton): The fact that image fragments store the cache within them makes
                 // little sense to me.
+                let decode_size = ImageFragmentInfo::decode_size(
+                    node,
+                    self.layout_context.shared.device_pixels_per_px);
                 ImageFragment(ImageFragmentInfo::new(node,
                                                      url,
                                                      self.layout_context
                                                          .shared
                                                          .image_cache
-                                                         .clone()))
+                                                         .clone(),
+                                                     decode_size))
             }
         }
     }
 
+    /// Returns the alternative text of an image element whose image could not be loaded or
+    /// decoded, which is shown in place of the image. Images with no alternative text stay empty
+    /// replaced elements.
+    ///
+    /// This asks the image cache, so it is only done when the fragment of the element is built.
+    /// Afterwards, `alt_text_from_fragment` tells it from the fragment.
+    fn alt_text_for_broken_image(&mut self, node: &ThreadSafeLayoutNode) -> Option<String> {
+        if node.type_id() != Some(ElementNodeTypeId(HTMLImageElementTypeId)) ||
+                node.get_pseudo_element_type() != Normal {
+            return None
+        }
+        let alt = match node.as_element().get_attr(&ns!(""), "alt") {
+            Some(alt) if !alt.is_empty() => alt.to_string(),
+            _ => return None,
+        };
+        let url = match node.image_url() {
+            Some(url) => url,
+            None => return None,
+        };
+        // The image is decoded at the size that its fragment would decode it at.
+        let decode_size =
+            ImageFragmentInfo::decode_size(node, self.layout_context.shared.device_pixels_per_px);
+        let node_address: OpaqueNode = OpaqueNodeMethods::from_thread_safe_layout_node(node);
+        let mut image = ImageHolder::new_with_decode_size(url,
+                                                          node_address.id() as uint,
+                                                          self.layout_context
+                                                              .shared
+                                                              .image_cache
+                                                              .clone(),
+                                                          decode_size);
+        image.get_image();
+        if image.has_failed() {
+            Some(alt)
+        } else {
+            None
+        }
+    }
+
     fn build_fragment_info_for_input(&mut self, node: &ThreadSafeLayoutNode) -> SpecificFragmentInfo {
         //FIXME: would it make more sense to use HTMLInputElement::input_type instead of the raw
         //       value? definitely for string comparisons.
@@ -239,10 +293,9 @@ impl<'a> FlowConstructor<'a> {
 
     /// Builds specific `Fragment` info for the given node.
     ///
-    /// This does *not* construct the text for generated content (but, for generated content with
-    /// `display: block`, it does construct the generic fragment corresponding to the block).
-    /// Construction of the text fragment is done specially by `build_flow_using_children()` and
-    /// `build_fragments_for_replaced_inline_content()`.
+    /// This does *not* construct the fragments for the content of generated content (but, for
+    /// generated content with `display: block`, it does construct the generic fragment
+    /// corresponding to the block). Those are built by `build_fragments_for_generated_content()`.
     pub fn build_specific_fragment_info_for_node(&mut self, node: &ThreadSafeLayoutNode)
                                                  -> SpecificFragmentInfo {
         match node.type_id() {
@@ -250,7 +303,11 @@ impl<'a> FlowConstructor<'a> {
                 IframeFragment(IframeFragmentInfo::new(node))
             }
             Some(ElementNodeTypeId(HTMLImageElementTypeId)) => {
-                self.build_fragment_info_for_image(node, node.image_url())
+                // Broken images with alternative text hold the text instead.
+                match self.alt_text_for_broken_image(node) {
+                    Some(_) => GenericFragment,
+                    None => self.build_fragment_info_for_image(node, node.image_url()),
+                }
             }
             Some(ElementNodeTypeId(HTMLInputElementTypeId)) => {
                 self.build_fragment_info_for_input(node)
@@ -275,6 +332,61 @@ impl<'a> FlowConstructor<'a> {
         }
     }
 
+    /// Builds the fragments for the `content` of a `::before` or `::after` pseudo-element, in
+    /// order. Quotes are left without text; see the `generated_content` module.
+    fn build_fragments_for_generated_content(&mut self, node: &ThreadSafeLayoutNode)
+                                             -> Vec<Fragment> {
+        let items = match node.style().get_box().content {
+            content::Content(ref items) => items.clone(),
+            content::normal | content::none => return Vec::new(),
+        };
+
+        // Images don't repeat the borders and padding of the pseudo-element around themselves.
+        let image_style = Arc::new(cascade_anonymous(&**node.style()));
+
+        let mut fragments = Vec::new();
+        for item in items.into_iter() {
+            let fragment_info = match item {
+                content::StringContent(string) => {
+                    UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(string))
+                }
+                content::AttrContent(name) => {
+                    let value = node.as_element().get_attr(&ns!(""), name.as_slice());
+                    let text = value.unwrap_or("").to_string();
+                    UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(text))
+                }
+                content::UrlContent(url) => {
+                    let fragment_info = self.build_fragment_info_for_image(node, Some(url));
+                    let opaque_node = OpaqueNodeMethods::from_thread_safe_layout_node(node);
+                    fragments.push(Fragment::from_opaque_node_and_style(opaque_node,
+                                                                        image_style.clone(),
+                                                                        fragment_info));
+                    continue
+                }
+                quote_item => {
+                    let (is_open, is_shown) = match quote_item {
+                        content::OpenQuote => (true, true),
+                        content::CloseQuote => (false, true),
+                        content::NoOpenQuote => (true, false),
+                        _ => (false, false),
+                    };
+                    let mut fragment = Fragment::new_from_specific_info(
+                        node,
+                        UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(String::new())));
+                    fragment.quote = Some(GeneratedQuote {
+                        is_open: is_open,
+                        is_shown: is_shown,
+                        text: String::new(),
+                    });
+                    fragments.push(fragment);
+                    continue
+                }
+            };
+            fragments.push(Fragment::new_from_specific_info(node, fragment_info))
+        }
+        fragments
+    }
+
     /// Creates an inline flow from a set of inline fragments, then adds it as a child of the given
     /// flow or pushes it onto the given flow list.
     ///
@@ -320,6 +432,15 @@ impl<'a> FlowConstructor<'a> {
             }
         }
 
+        // Quotes only get their text once the whole flow tree is built, so the text runs of this
+        // flow are scanned, and its inline sizes bubbled, once `resolve_quotes()` has filled it
+        // in.
+        let has_unresolved_quotes =
+            generated_content::has_unresolved_quotes(fragments.fragments.as_slice());
+        if has_unresolved_quotes {
+            self.layout_context.shared.generated_quotes.store(true, Relaxed)
+        }
+
         let mut inline_flow_ref = FlowRef::new(box InlineFlow::from_fragments((*node).clone(),
                                                                               fragments));
 
@@ -330,15 +451,16 @@ impl<'a> FlowConstructor<'a> {
 
         {
             let inline_flow = inline_flow_ref.get_mut().as_inline();
-            let (ascent, descent) =
-                inline_flow.compute_minimum_ascent_and_descent(self.layout_context.font_context(),
-                                                               &**node.style());
-            inline_flow.minimum_block_size_above_baseline = ascent;
-            inline_flow.minimum_depth_below_baseline = descent;
-            TextRunScanner::new().scan_for_runs(self.layout_context.font_context(), inline_flow);
+            inline_flow.set_root_metrics(self.layout_context.font_context(), &**node.style());
+            if !has_unresolved_quotes {
+                TextRunScanner::new().scan_for_runs(self.layout_context.font_context(),
+                                                    inline_flow);
+            }
         }
 
-        inline_flow_ref.finish(self.layout_context);
+        if !has_unresolved_quotes {
+            inline_flow_ref.finish(self.layout_context);
+        }
 
         if flow.get().need_anonymous_flow(inline_flow_ref.get()) {
             flow_list.push(inline_flow_ref)
@@ -468,13 +590,28 @@ impl<'a> FlowConstructor<'a> {
         let mut first_fragment = true;
 
         // Special case: If this is generated content, then we need to initialize the accumulator
-        // with the fragment corresponding to that content.
-        if node.get_pseudo_element_type() != Normal ||
-           node.type_id() == Some(ElementNodeTypeId(HTMLInputElementTypeId)) {
+        // with the fragments corresponding to that content.
+        if node.get_pseudo_element_type() != Normal {
+            for mut fragment in self.build_fragments_for_generated_content(node).into_iter() {
+                inline_fragment_accumulator.fragments.push(&mut fragment);
+                first_fragment = false;
+            }
+        } else if node.type_id() == Some(ElementNodeTypeId(HTMLInputElementTypeId)) {
             let fragment_info = UnscannedTextFragment(UnscannedTextFragmentInfo::new(node));
             let mut fragment = Fragment::new_from_specific_info(node, fragment_info);
             inline_fragment_accumulator.fragments.push(&mut fragment);
             first_fragment = false;
+        } else {
+            match alt_text_from_fragment(node, &flow.get_mut().as_block().fragment) {
+                Some(alt) => {
+                    let fragment_info =
+                        UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(alt));
+                    let mut fragment = Fragment::new_from_specific_info(node, fragment_info);
+                    inline_fragment_accumulator.fragments.push(&mut fragment);
+                    first_fragment = false;
+                }
+                None => {}
+            }
         }
 
         // List of absolute descendants, in tree order.
@@ -527,18 +664,26 @@ impl<'a> FlowConstructor<'a> {
 
     /// Builds a flow for a node with `display: block`. This yields a `BlockFlow` with possibly
     /// other `BlockFlow`s or `InlineFlow`s underneath it, depending on whether {ib} splits needed
-    /// to happen.
+    /// to happen. Multi-column elements yield a `MulticolFlow` instead.
     fn build_flow_for_nonfloated_block(&mut self, node: &ThreadSafeLayoutNode)
                                        -> ConstructionResult {
-        let flow = box BlockFlow::from_node(self, node) as Box<Flow>;
+        let flow = if multicol::is_multicol(&**node.style()) {
+            box MulticolFlow::from_node(self, node) as Box<Flow>
+        } else {
+            box BlockFlow::from_node(self, node) as Box<Flow>
+        };
         self.build_flow_for_block(FlowRef::new(flow), node)
     }
 
     /// Builds the flow for a node with `float: {left|right}`. This yields a float `BlockFlow` with
-    /// a `BlockFlow` underneath it.
+    /// a `BlockFlow` underneath it, or a float `MulticolFlow` for a multi-column element.
     fn build_flow_for_floated_block(&mut self, node: &ThreadSafeLayoutNode, float_kind: FloatKind)
                                     -> ConstructionResult {
-        let flow = box BlockFlow::float_from_node(self, node, float_kind) as Box<Flow>;
+        let flow = if multicol::is_multicol(&**node.style()) {
+            box MulticolFlow::float_from_node(self, node, float_kind) as Box<Flow>
+        } else {
+            box BlockFlow::float_from_node(self, node, float_kind) as Box<Flow>
+        };
         self.build_flow_for_block(FlowRef::new(flow), node)
     }
 
@@ -652,19 +797,39 @@ impl<'a> FlowConstructor<'a> {
                 node.style().clone()))
         }
 
-        // If this is generated content, then we need to initialize the accumulator with the
-        // fragment corresponding to that content. Otherwise, just initialize with the ordinary
-        // fragment that needs to be generated for this inline node.
-        let mut fragment = if node.get_pseudo_element_type() != Normal {
-            let fragment_info = UnscannedTextFragment(UnscannedTextFragmentInfo::new(node));
-            Fragment::new_from_specific_info(node, fragment_info)
+        // If this is generated content, then its fragments go inside it as they would inside an
+        // inline element, and so does the alternative text of a broken image. Otherwise, just
+        // make the ordinary fragment for this inline node.
+        let fragments = if node.get_pseudo_element_type() != Normal {
+            let mut fragment_accumulator = InlineFragmentsAccumulator::from_inline_node(node);
+            for mut fragment in self.build_fragments_for_generated_content(node).into_iter() {
+                fragment_accumulator.fragments.push(&mut fragment)
+            }
+            if fragment_accumulator.fragments.is_empty() {
+                return NoConstructionResult
+            }
+            fragment_accumulator.finish()
         } else {
-            Fragment::new(self, node)
+            let mut fragment = Fragment::new(self, node);
+            match alt_text_from_fragment(node, &fragment) {
+                Some(alt) => {
+                    let mut fragment_accumulator =
+                        InlineFragmentsAccumulator::from_inline_node(node);
+                    let fragment_info =
+                        UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(alt));
+                    fragment_accumulator.fragments
+                                        .push(&mut Fragment::new_from_specific_info(node,
+                                                                                    fragment_info));
+                    fragment_accumulator.finish()
+                }
+                None => {
+                    let mut fragments = InlineFragments::new();
+                    fragments.push(&mut fragment);
+                    fragments
+                }
+            }
         };
 
-        let mut fragments = InlineFragments::new();
-        fragments.push(&mut fragment);
-
         let construction_item = InlineFragmentsConstructionItem(InlineFragmentsConstructionResult {
             splits: Vec::new(),
             fragments: fragments,
@@ -920,6 +1085,195 @@ impl<'a> FlowConstructor<'a> {
 
         FlowConstructionResult(flow, Descendants::new())
     }
+
+    /// Keeps the flow built for this node by an earlier reflow, handing it the new style of the
+    /// node and the damage that the change of style does, if nothing but the styles of the node and
+    /// its inline content have changed since and the changes don't call for new flows. The
+    /// fragments of the inline content get their new styles in place. Returns true if the flow was
+    /// kept.
+    fn restyle_flow_if_possible(&mut self, node: &ThreadSafeLayoutNode) -> bool {
+        if self.layout_context.shared.full_reflow || node.has_changed() {
+            return false
+        }
+
+        // Kids whose flows were rebuilt have to be put into new flows too.
+        let damage = node.restyle_damage();
+        if damage.contains(RebuildFlow) ||
+                node.children().any(|kid| kid.restyle_damage().contains(RebuildFlow)) {
+            return false
+        }
+
+        let class = {
+            let layout_data_ref = node.borrow_layout_data();
+            match layout_data_ref.as_ref().unwrap().data.flow_construction_result {
+                FlowConstructionResult(ref flow, _) => flow.get().class(),
+                _ => return false,
+            }
+        };
+
+        // The inline content of the kids ends up in inline flows directly under the flows of
+        // block containers only. Tables wrap it in anonymous flows, which aren't kept track of.
+        let holds_inline_flows = match class {
+            BlockFlowClass | MulticolFlowClass | TableCellFlowClass | TableCaptionFlowClass => true,
+            TableWrapperFlowClass | TableFlowClass | TableRowGroupFlowClass |
+            TableRowFlowClass => false,
+            InlineFlowClass | TableColGroupFlowClass => return false,
+        };
+        let (inline_styles, inline_damage) = match new_inline_styles(node) {
+            Some(new_inline_styles) => new_inline_styles,
+            None => return false,
+        };
+        if !holds_inline_flows && !inline_styles.is_empty() {
+            return false
+        }
+
+        // The flow is kept, so the fragments that the kids built in this reflow aren't needed.
+        for kid in node.children() {
+            drop(kid.swap_out_construction_result());
+            kid.set_restyle_damage(RestyleDamage::empty())
+        }
+
+        let style = node.style().clone();
+        let mut layout_data_ref = node.mutate_layout_data();
+        let flow = match layout_data_ref.as_mut().unwrap().data.flow_construction_result {
+            FlowConstructionResult(ref mut flow, _) => flow.get_mut(),
+            _ => fail!("the flow construction result changed while restyling the flow"),
+        };
+        flow.as_block().fragment.style = style.clone();
+        flow::mut_base(flow).restyle_damage.insert(damage);
+
+        for kid in flow::child_iter(flow) {
+            match kid.class() {
+                // The line metrics of inline flows come from the style of this node.
+                InlineFlowClass => {
+                    {
+                        let inline_flow = kid.as_inline();
+                        inline_flow.update_styles(&inline_styles);
+                        inline_flow.set_root_metrics(self.layout_context.font_context(), &*style);
+                    }
+                    flow::mut_base(kid).restyle_damage.insert(damage | inline_damage);
+                }
+                // The table wrapper and the table inside it are both built from this node.
+                TableFlowClass if class == TableWrapperFlowClass => {
+                    kid.as_block().fragment.style = style.clone();
+                    flow::mut_base(kid).restyle_damage.insert(damage);
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+/// Gathers the styles of the nodes that the inline fragments built by the kids of this node in this
+/// reflow belong to, keyed by node ID, along with the damage of those kids. Returns `None` if
+/// fragments of one node have different styles, as for generated content, because the fragments
+/// that they replace couldn't be told apart then.
+fn new_inline_styles(node: &ThreadSafeLayoutNode)
+                     -> Option<(HashMap<uintptr_t, Arc<ComputedValues>>, RestyleDamage)> {
+    let mut styles = HashMap::new();
+    let mut damage = RestyleDamage::empty();
+    for kid in node.children() {
+        let mut kid_styles = vec!();
+        {
+            let layout_data_ref = kid.borrow_layout_data();
+            let layout_data = layout_data_ref.as_ref().unwrap();
+            let result = match kid.get_pseudo_element_type() {
+                Before(_) => &layout_data.data.before_flow_construction_result,
+                After(_) => &layout_data.data.after_flow_construction_result,
+                Normal => &layout_data.data.flow_construction_result,
+            };
+            match *result {
+                ConstructionItemConstructionResult(InlineFragmentsConstructionItem(ref result)) => {
+                    for fragment in result.fragments.fragments.iter() {
+                        kid_styles.push((fragment.node, fragment.style.clone()));
+                        match fragment.inline_context {
+                            Some(ref inline_context) => {
+                                for (node, style) in inline_context.nodes.iter()
+                                                                   .zip(inline_context.styles
+                                                                                      .iter()) {
+                                    kid_styles.push((*node, style.clone()))
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                }
+                ConstructionItemConstructionResult(WhitespaceConstructionItem(ref node,
+                                                                              ref style)) => {
+                    kid_styles.push((*node, style.clone()))
+                }
+                ConstructionItemConstructionResult(TableColumnFragmentConstructionItem(_)) |
+                FlowConstructionResult(..) |
+                NoConstructionResult => continue,
+            }
+        }
+
+        for (kid_node, style) in kid_styles.into_iter() {
+            match styles.find(&kid_node.id()) {
+                Some(other_style) if &**other_style as *const ComputedValues !=
+                        &*style as *const ComputedValues => return None,
+                _ => {}
+            }
+            styles.insert(kid_node.id(), style);
+        }
+        damage.insert(kid.restyle_damage())
+    }
+    Some((styles, damage))
+}
+
+/// Returns the alternative text to show in place of the image of an image element, given the
+/// fragment built for it, if the image is broken. Such elements get a generic fragment instead of
+/// an image fragment; see `FlowConstructor::alt_text_for_broken_image`.
+fn alt_text_from_fragment(node: &ThreadSafeLayoutNode, fragment: &Fragment) -> Option<String> {
+    if node.type_id() != Some(ElementNodeTypeId(HTMLImageElementTypeId)) ||
+            node.get_pseudo_element_type() != Normal {
+        return None
+    }
+    match fragment.specific {
+        GenericFragment => node.as_element().get_attr(&ns!(""), "alt").map(|alt| alt.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns true if this node built nothing but inline fragments in this reflow, so that they can
+/// be left in the inline flow built by an earlier reflow, with new styles, if the parent of the
+/// node keeps its flow.
+fn built_inline_fragments_only(node: &ThreadSafeLayoutNode) -> bool {
+    let layout_data_ref = node.borrow_layout_data();
+    match layout_data_ref.as_ref().unwrap().data.flow_construction_result {
+        ConstructionItemConstructionResult(InlineFragmentsConstructionItem(ref result)) => {
+            result.splits.is_empty() && result.abs_descendants.len() == 0 &&
+                result.fragments.fragments.iter().all(|fragment| {
+                    match fragment.specific {
+                        InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => false,
+                        _ => true,
+                    }
+                })
+        }
+        ConstructionItemConstructionResult(WhitespaceConstructionItem(..)) => true,
+        _ => false,
+    }
+}
+
+/// Returns true if the flow built for this node by the last reflow can't be reused as it is, so the
+/// node has to go through flow construction again. That may still end up keeping the flow if only
+/// the style of the node changed; see `FlowConstructor::restyle_flow_if_possible`.
+pub fn needs_flow_construction(node: &ThreadSafeLayoutNode, layout_context: &LayoutContext)
+                               -> bool {
+    if layout_context.shared.full_reflow || node.is_dirty() || node.has_dirty_descendants() {
+        return true
+    }
+    let layout_data_ref = node.borrow_layout_data();
+    match *layout_data_ref {
+        Some(ref layout_data) => {
+            match layout_data.data.flow_construction_result {
+                FlowConstructionResult(..) => false,
+                NoConstructionResult | ConstructionItemConstructionResult(_) => true,
+            }
+        }
+        None => true,
+    }
 }
 
 impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
@@ -930,19 +1284,24 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
     // TODO: This should actually consult the table in that section to get the
     // final computed value for 'display'.
     fn process(&mut self, node: &ThreadSafeLayoutNode) -> bool {
+        if node.get_pseudo_element_type() == Normal {
+            // Keep the flow from the last reflow if nothing in this subtree has changed since.
+            if !needs_flow_construction(node, self.layout_context) {
+                return true
+            }
+
+            if self.restyle_flow_if_possible(node) {
+                node.set_restyle_damage(RestyleDamage::empty());
+                return true
+            }
+        }
+
         // Get the `display` property for this node, and determine whether this node is floated.
+        //
+        // Generated content is floated and positioned like an element, so absolutely positioned
+        // inline generated content gets a hypothetical box too.
         let (display, float, positioning) = match node.type_id() {
-            None => {
-                // Pseudo-element.
-                let style = node.style();
-                let display = match node.get_pseudo_element_type() {
-                    Normal => display::inline,
-                    Before(display) => display,
-                    After(display) => display,
-                };
-                (display, style.get_box().float, style.get_box().position)
-            }
-            Some(ElementNodeTypeId(_)) => {
+            None | Some(ElementNodeTypeId(_)) => {
                 let style = node.style();
                 let munged_display = if style.get_box()._servo_display_for_hypothetical_box ==
                         display::inline {
@@ -970,7 +1329,7 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
             // results of children.
             (display::none, _, _) => {
                 for child in node.children() {
-                    drop(child.swap_out_construction_result())
+                    child.set_flow_construction_result(NoConstructionResult)
                 }
             }
 
@@ -999,8 +1358,6 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
             }
 
             // Inline items contribute inline fragment construction results.
-            //
-            // FIXME(pcwalton, #3307): This is not sufficient to handle floated generated content.
             (display::inline, float::none, _) => {
                 let construction_result = self.build_fragments_for_inline(node);
                 node.set_flow_construction_result(construction_result)
@@ -1066,6 +1423,26 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
             }
         }
 
+        // The damage of the kids has been dealt with now. Leave a note for the parent of this
+        // node that it has to be put into new flows, unless this is inline content whose fragments
+        // differ from the last time in style only. The parent may then keep its flows and update
+        // the styles of those fragments; see `FlowConstructor::restyle_flow_if_possible`.
+        if node.get_pseudo_element_type() == Normal {
+            let mut damage = node.restyle_damage();
+            let restylable = !node.has_changed() && !damage.contains(RebuildFlow) &&
+                built_inline_fragments_only(node) &&
+                node.children().all(|kid| !kid.restyle_damage().contains(RebuildFlow));
+            for kid in node.children() {
+                damage.insert(kid.restyle_damage());
+                kid.set_restyle_damage(RestyleDamage::empty())
+            }
+            if restylable {
+                node.set_restyle_damage(damage)
+            } else {
+                node.set_restyle_damage(RebuildFlow)
+            }
+        }
+
         true
     }
 }
@@ -1078,8 +1455,8 @@ trait NodeUtils {
     /// Sets the construction result of a flow.
     fn set_flow_construction_result(&self, result: ConstructionResult);
 
-    /// Replaces the flow construction result in a node with `NoConstructionResult` and returns the
-    /// old value.
+    /// Returns the flow construction result of a node. Flows are left in the node as well, so that
+    /// later reflows can reuse them; anything else is replaced with `NoConstructionResult`.
     fn swap_out_construction_result(&self) -> ConstructionResult;
 }
 
@@ -1119,20 +1496,18 @@ impl<'ln> NodeUtils for ThreadSafeLayoutNode<'ln> {
         let mut layout_data_ref = self.mutate_layout_data();
         match &mut *layout_data_ref {
             &Some(ref mut layout_data) => {
-                match self.get_pseudo_element_type() {
-                    Before(_) => {
-                        mem::replace(&mut layout_data.data.before_flow_construction_result,
-                                     NoConstructionResult)
-                    }
-                    After(_) => {
-                        mem::replace(&mut layout_data.data.after_flow_construction_result,
-                                     NoConstructionResult)
-                    }
-                    Normal => {
-                        mem::replace(&mut layout_data.data.flow_construction_result,
-                                     NoConstructionResult)
+                let result = match self.get_pseudo_element_type() {
+                    Before(_) => &mut layout_data.data.before_flow_construction_result,
+                    After(_) => &mut layout_data.data.after_flow_construction_result,
+                    Normal => &mut layout_data.data.flow_construction_result,
+                };
+                match *result {
+                    FlowConstructionResult(ref flow, ref abs_descendants) => {
+                        return FlowConstructionResult((*flow).clone(), abs_descendants.clone())
                     }
+                    NoConstructionResult | ConstructionItemConstructionResult(_) => {}
                 }
+                mem::replace(result, NoConstructionResult)
             }
             &None => fail!("no layout data"),
         }
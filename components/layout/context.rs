@@ -4,7 +4,8 @@
 
 //! Data needed by the layout task.
 
-use css::matching::{ApplicableDeclarationsCache, StyleSharingCandidateCache};
+use css::matching::{ApplicableDeclarationsCache, StyleCacheStatistics};
+use css::matching::StyleSharingCandidateCache;
 
 use geom::{Rect, Size2D};
 use gfx::display_list::OpaqueNode;
@@ -17,6 +18,7 @@ use servo_util::geometry::Au;
 use servo_util::opts::Opts;
 use sync::{Arc, Mutex};
 use std::mem;
+use std::sync::atomics::AtomicBool;
 use style::Stylist;
 use url::Url;
 
@@ -54,6 +56,9 @@ pub struct SharedLayoutContext {
     /// The current screen size.
     pub screen_size: Size2D<Au>,
 
+    /// The number of device pixels per CSS pixel.
+    pub device_pixels_per_px: f32,
+
     /// A channel up to the constellation.
     pub constellation_chan: ConstellationChan,
 
@@ -71,6 +76,10 @@ pub struct SharedLayoutContext {
     /// The root node at which we're starting the layout.
     pub reflow_root: OpaqueNode,
 
+    /// True if every node has to be restyled and every flow rebuilt, no matter which nodes are
+    /// dirty.
+    pub full_reflow: bool,
+
     /// The URL.
     pub url: Url,
 
@@ -80,6 +89,13 @@ pub struct SharedLayoutContext {
     /// The dirty rectangle, used during display list building.
     pub dirty: Rect<Au>,
 
+    /// How often restyling made use of the style caches.
+    pub style_cache_statistics: StyleCacheStatistics,
+
+    /// True if flow construction left quotes of generated content for `resolve_quotes()` to fill
+    /// in.
+    pub generated_quotes: AtomicBool,
+
     /// Starts at zero, and increased by one every time a layout completes.
     /// This can be used to easily check for invalid stale data.
     pub generation: uint,
@@ -5,8 +5,9 @@
 // High-level interface to CSS selector matching.
 
 use css::node_style::StyledNode;
-use construct::FlowConstructor;
+use construct::{FlowConstructor, needs_flow_construction};
 use context::LayoutContext;
+use incremental::{RestyleDamage, compute_damage};
 use util::{LayoutDataAccess, LayoutDataWrapper};
 use wrapper::{LayoutElement, LayoutNode, PostorderNodeMutTraversal, ThreadSafeLayoutNode, TLayoutNode};
 
@@ -18,6 +19,7 @@ use servo_util::str::DOMString;
 use std::mem;
 use std::hash::{Hash, sip};
 use std::slice::Items;
+use std::sync::atomics::{AtomicUint, Relaxed};
 use style;
 use style::{After, Before, ComputedValues, DeclarationBlock, Stylist, TElement, TNode};
 use style::cascade;
@@ -94,6 +96,33 @@ impl<'a> ApplicableDeclarationsCacheQuery<'a> {
     }
 }
 
+/// Returns true if restyling a node changed its style, in which case its kids, which inherit from
+/// it, have to be restyled too.
+fn style_changed(old_style: &Option<Arc<ComputedValues>>, new_style: &Option<Arc<ComputedValues>>)
+                 -> bool {
+    match (old_style, new_style) {
+        (&Some(ref old_style), &Some(ref new_style)) => {
+            !arc_ptr_eq(old_style, new_style) && **old_style != **new_style
+        }
+        _ => true,
+    }
+}
+
+/// Works out how the flows of a node have to be updated now that its style has changed from
+/// `old_style` to `new_style`. Generated content has flows of its own, so those of a node that has
+/// or had any are always rebuilt.
+fn restyle_damage(old_style: &Option<Arc<ComputedValues>>,
+                  new_style: &Option<Arc<ComputedValues>>,
+                  has_generated_content: bool)
+                  -> RestyleDamage {
+    match (old_style, new_style) {
+        (&Some(ref old_style), &Some(ref new_style)) if !has_generated_content => {
+            compute_damage(&**old_style, &**new_style)
+        }
+        _ => RestyleDamage::all(),
+    }
+}
+
 // Workaround for lack of `ptr_eq` on Arcs...
 #[inline]
 fn arc_ptr_eq<T>(a: &Arc<T>, b: &Arc<T>) -> bool {
@@ -132,8 +161,47 @@ impl<'a> Hash for ApplicableDeclarationsCacheQuery<'a> {
 
 static APPLICABLE_DECLARATIONS_CACHE_SIZE: uint = 32;
 
+/// A style that was computed from a list of applicable declarations, along with what else went into
+/// computing it.
+#[deriving(Clone)]
+struct CachedStyle {
+    style: Arc<ComputedValues>,
+    /// The style that the cached style inherits from, or `None` if it is the style of the root.
+    parent_style: Option<Arc<ComputedValues>>,
+    /// Whether the cached style was to be considered shareable.
+    shareable: bool,
+    /// What style sharing would compare the element that the style was computed for on, or `None`
+    /// if it is the style of generated content.
+    element_key: Option<StyleSharingKey>,
+}
+
+impl CachedStyle {
+    /// Returns true if cascading the declarations this style was computed from would give this
+    /// very style again. The cascade only depends on the declarations and the parent style, so
+    /// identical styles are shared rather than computed over and over.
+    ///
+    /// Style sharing takes elements with the same parent style to have equivalent parents, so an
+    /// element only gets the style computed for another one if both have the same key.
+    fn is_for(&self,
+              parent_style: Option<&Arc<ComputedValues>>,
+              shareable: bool,
+              element_key: &Option<StyleSharingKey>)
+              -> bool {
+        if self.shareable != shareable || self.element_key != *element_key {
+            return false
+        }
+        match (&self.parent_style, parent_style) {
+            (&Some(ref this_parent_style), Some(parent_style)) => {
+                arc_ptr_eq(this_parent_style, parent_style)
+            }
+            (&None, None) => true,
+            (&Some(_), None) | (&None, Some(_)) => false,
+        }
+    }
+}
+
 pub struct ApplicableDeclarationsCache {
-    cache: SimpleHashCache<ApplicableDeclarationsCacheEntry,Arc<ComputedValues>>,
+    cache: SimpleHashCache<ApplicableDeclarationsCacheEntry,CachedStyle>,
 }
 
 impl ApplicableDeclarationsCache {
@@ -143,37 +211,109 @@ impl ApplicableDeclarationsCache {
         }
     }
 
-    fn find(&self, declarations: &[DeclarationBlock]) -> Option<Arc<ComputedValues>> {
+    fn find(&self, declarations: &[DeclarationBlock]) -> Option<CachedStyle> {
         match self.cache.find_equiv(&ApplicableDeclarationsCacheQuery::new(declarations)) {
             None => None,
             Some(ref values) => Some((*values).clone()),
         }
     }
 
-    fn insert(&mut self, declarations: &[DeclarationBlock], style: Arc<ComputedValues>) {
+    fn insert(&mut self, declarations: &[DeclarationBlock], style: CachedStyle) {
         self.cache.insert(ApplicableDeclarationsCacheEntry::new(declarations), style)
     }
 }
 
+/// Counts how often restyling a node was spared the work of selector matching or of the cascade.
+/// The layout workers share one of these, and the layout task reports the totals to the time
+/// profiler after every restyle.
+pub struct StyleCacheStatistics {
+    /// The number of nodes that looked for a style to share.
+    pub style_sharing_lookups: AtomicUint,
+    /// The number of nodes that found one.
+    pub style_sharing_hits: AtomicUint,
+    /// The number of styles that went through the cascade.
+    pub computed_values_lookups: AtomicUint,
+    /// The number of those that turned out to be identical to one computed earlier.
+    pub computed_values_hits: AtomicUint,
+}
+
+impl StyleCacheStatistics {
+    pub fn new() -> StyleCacheStatistics {
+        StyleCacheStatistics {
+            style_sharing_lookups: AtomicUint::new(0),
+            style_sharing_hits: AtomicUint::new(0),
+            computed_values_lookups: AtomicUint::new(0),
+            computed_values_hits: AtomicUint::new(0),
+        }
+    }
+}
+
 /// An LRU cache of the last few nodes seen, so that we can aggressively try to reuse their styles.
 pub struct StyleSharingCandidateCache {
     cache: LRUCache<StyleSharingCandidate,()>,
 }
 
+/// What style sharing compares elements on besides their parent styles. Selector matching marks
+/// the style of an element unshareable if any selector it tried depends on anything else.
+#[deriving(Clone, PartialEq)]
+pub struct StyleSharingKey {
+    pub local_name: Atom,
+    pub class: Option<DOMString>,
+    pub id: Option<Atom>,
+    pub hover: bool,
+    pub disabled: bool,
+    pub enabled: bool,
+}
+
+impl StyleSharingKey {
+    fn new(element: &LayoutElement) -> StyleSharingKey {
+        StyleSharingKey {
+            local_name: element.get_local_name().clone(),
+            class: element.get_attr(&ns!(""), "class")
+                          .map(|string| string.to_string()),
+            id: element.get_id(),
+            hover: element.get_hover_state(),
+            disabled: element.get_disabled_state(),
+            enabled: element.get_enabled_state(),
+        }
+    }
+
+    /// Returns true if the given element has this key.
+    fn matches(&self, element: &LayoutElement) -> bool {
+        if *element.get_local_name() != self.local_name {
+            return false
+        }
+        if element.get_id() != self.id {
+            return false
+        }
+        if element.get_hover_state() != self.hover ||
+                element.get_disabled_state() != self.disabled ||
+                element.get_enabled_state() != self.enabled {
+            return false
+        }
+        match (&self.class, element.get_attr(&ns!(""), "class")) {
+            (&None, Some(_)) | (&Some(_), None) => return false,
+            (&Some(ref this_class), Some(element_class))
+                    if element_class != this_class.as_slice() => return false,
+            (&Some(_), Some(_)) | (&None, None) => {}
+        }
+        true
+    }
+}
+
+/// A node whose style other nodes may share.
 #[deriving(Clone)]
 pub struct StyleSharingCandidate {
     pub style: Arc<ComputedValues>,
     pub parent_style: Arc<ComputedValues>,
-    pub local_name: Atom,
-    pub class: Option<DOMString>,
+    pub key: StyleSharingKey,
 }
 
 impl PartialEq for StyleSharingCandidate {
     fn eq(&self, other: &StyleSharingCandidate) -> bool {
         arc_ptr_eq(&self.style, &other.style) &&
             arc_ptr_eq(&self.parent_style, &other.parent_style) &&
-            self.local_name == other.local_name &&
-            self.class == other.class
+            self.key == other.key
     }
 }
 
@@ -194,6 +334,11 @@ impl StyleSharingCandidate {
             match *node.borrow_layout_data_unchecked() {
                 None => return None,
                 Some(ref layout_data_ref) => {
+                    // Nodes that share a style don't get generated content of their own.
+                    if layout_data_ref.data.before_style.is_some() ||
+                            layout_data_ref.data.after_style.is_some() {
+                        return None
+                    }
                     match layout_data_ref.shared_data.style {
                         None => return None,
                         Some(ref data) => (*data).clone(),
@@ -214,31 +359,19 @@ impl StyleSharingCandidate {
         };
 
         let element = node.as_element();
-        if element.style_attribute().is_some() {
+        if element.style_attribute().is_some() || element.get_attr(&ns!(""), "lang").is_some() {
             return None
         }
 
         Some(StyleSharingCandidate {
             style: style,
             parent_style: parent_style,
-            local_name: element.get_local_name().clone(),
-            class: element.get_attr(&ns!(""), "class")
-                          .map(|string| string.to_string()),
+            key: StyleSharingKey::new(&element),
         })
     }
 
     fn can_share_style_with(&self, element: &LayoutElement) -> bool {
-        if *element.get_local_name() != self.local_name {
-            return false
-        }
-        match (&self.class, element.get_attr(&ns!(""), "class")) {
-            (&None, Some(_)) | (&Some(_), None) => return false,
-            (&Some(ref this_class), Some(element_class)) if element_class != this_class.as_slice() => {
-                return false
-            }
-            (&Some(_), Some(_)) | (&None, None) => {}
-        }
-        true
+        self.key.matches(element)
     }
 }
 
@@ -312,13 +445,19 @@ pub trait MatchMethods {
     unsafe fn share_style_if_possible(&self,
                                       style_sharing_candidate_cache:
                                         &mut StyleSharingCandidateCache,
+                                      statistics: &StyleCacheStatistics,
                                       parent: Option<LayoutNode>)
                                       -> StyleSharingResult;
 
     unsafe fn cascade_node(&self,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
-                           applicable_declarations_cache: &mut ApplicableDeclarationsCache);
+                           applicable_declarations_cache: &mut ApplicableDeclarationsCache,
+                           statistics: &StyleCacheStatistics);
+
+    /// Marks the kids of this node whose styles may depend on a changed sibling dirty. Script only
+    /// marks the nodes that changed, so this must be called before any of the kids are restyled.
+    fn dirty_kids_affected_by_siblings(&self, stylist: &Stylist);
 }
 
 trait PrivateMatchMethods {
@@ -328,7 +467,9 @@ trait PrivateMatchMethods {
                                    style: &mut Option<Arc<ComputedValues>>,
                                    applicable_declarations_cache: &mut
                                    ApplicableDeclarationsCache,
-                                   shareable: bool);
+                                   statistics: &StyleCacheStatistics,
+                                   shareable: bool,
+                                   element_key: Option<StyleSharingKey>);
 
     fn share_style_with_candidate_if_possible(&self,
                                               parent_node: Option<LayoutNode>,
@@ -343,15 +484,29 @@ impl<'ln> PrivateMatchMethods for LayoutNode<'ln> {
                                    style: &mut Option<Arc<ComputedValues>>,
                                    applicable_declarations_cache: &mut
                                    ApplicableDeclarationsCache,
-                                   shareable: bool) {
+                                   statistics: &StyleCacheStatistics,
+                                   shareable: bool,
+                                   element_key: Option<StyleSharingKey>) {
+        let cache_entry = applicable_declarations_cache.find(applicable_declarations);
+        statistics.computed_values_lookups.fetch_add(1, Relaxed);
+        match cache_entry {
+            Some(ref cached_style) if cached_style.is_for(parent_style,
+                                                          shareable,
+                                                          &element_key) => {
+                statistics.computed_values_hits.fetch_add(1, Relaxed);
+                *style = Some(cached_style.style.clone());
+                return
+            }
+            _ => {}
+        }
+
         let this_style;
         let cacheable;
         match parent_style {
             Some(ref parent_style) => {
-                let cache_entry = applicable_declarations_cache.find(applicable_declarations);
                 let cached_computed_values = match cache_entry {
+                    Some(ref cached_style) => Some(&*cached_style.style),
                     None => None,
-                    Some(ref style) => Some(&**style),
                 };
                 let (the_style, is_cacheable) = cascade(applicable_declarations,
                                                         shareable,
@@ -370,9 +525,15 @@ impl<'ln> PrivateMatchMethods for LayoutNode<'ln> {
             }
         };
 
-        // Cache the resolved style if it was cacheable.
+        // Cache the resolved style if it was cacheable, so that nodes with the same declarations
+        // and parent style can share it.
         if cacheable {
-            applicable_declarations_cache.insert(applicable_declarations, this_style.clone());
+            applicable_declarations_cache.insert(applicable_declarations, CachedStyle {
+                style: this_style.clone(),
+                parent_style: parent_style.map(|parent_style| parent_style.clone()),
+                shareable: shareable,
+                element_key: element_key,
+            });
         }
 
         *style = Some(this_style);
@@ -429,42 +590,65 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                                  style_attribute,
                                                  None,
                                                  &mut applicable_declarations.normal);
-        stylist.push_applicable_declarations(self,
-                                             parent_bf,
-                                             None,
-                                             Some(Before),
-                                             &mut applicable_declarations.before);
-        stylist.push_applicable_declarations(self,
-                                             parent_bf,
-                                             None,
-                                             Some(After),
-                                             &mut applicable_declarations.after);
+        let before_shareable =
+            stylist.push_applicable_declarations(self,
+                                                 parent_bf,
+                                                 None,
+                                                 Some(Before),
+                                                 &mut applicable_declarations.before);
+        let after_shareable =
+            stylist.push_applicable_declarations(self,
+                                                 parent_bf,
+                                                 None,
+                                                 Some(After),
+                                                 &mut applicable_declarations.after);
 
-        *shareable = applicable_declarations.normal_shareable
+        // Nodes that share this style won't go through matching for their generated content, so
+        // it has to be decided by what they are keyed on too.
+        *shareable = applicable_declarations.normal_shareable && before_shareable &&
+            after_shareable
     }
 
     unsafe fn share_style_if_possible(&self,
                                       style_sharing_candidate_cache:
                                         &mut StyleSharingCandidateCache,
+                                      statistics: &StyleCacheStatistics,
                                       parent: Option<LayoutNode>)
                                       -> StyleSharingResult {
         if !self.is_element() {
             return CannotShare(false)
         }
-        let ok = {
-            let element = self.as_element();
-            element.style_attribute().is_none() && element.get_attr(&ns!(""), "id").is_none()
-        };
-        if !ok {
+        if self.as_element().style_attribute().is_some() ||
+                self.as_element().get_attr(&ns!(""), "lang").is_some() {
             return CannotShare(false)
         }
 
-        for (i, &(ref candidate, ())) in style_sharing_candidate_cache.iter().enumerate() {
+        statistics.style_sharing_lookups.fetch_add(1, Relaxed);
+
+        // Siblings have the same parent style, and the previous sibling is the most recent
+        // candidate, so try the most recent candidates first.
+        for (i, &(ref candidate, ())) in style_sharing_candidate_cache.iter().enumerate().rev() {
             match self.share_style_with_candidate_if_possible(parent.clone(), candidate) {
                 Some(shared_style) => {
                     // Yay, cache hit. Share the style.
+                    statistics.style_sharing_hits.fetch_add(1, Relaxed);
                     let mut layout_data_ref = self.mutate_layout_data();
-                    layout_data_ref.as_mut().unwrap().shared_data.style = Some(shared_style);
+                    let layout_data = layout_data_ref.as_mut().unwrap();
+                    let old_style = mem::replace(&mut layout_data.shared_data.style,
+                                                 Some(shared_style));
+                    let has_generated_content = layout_data.data.before_style.is_some() ||
+                        layout_data.data.after_style.is_some();
+                    layout_data.data.before_style = None;
+                    layout_data.data.after_style = None;
+                    let damage = restyle_damage(&old_style,
+                                                &layout_data.shared_data.style,
+                                                has_generated_content);
+                    layout_data.data.restyle_damage = Some(damage);
+                    if style_changed(&old_style, &layout_data.shared_data.style) {
+                        for kid in self.children() {
+                            kid.set_dirty(true)
+                        }
+                    }
                     return StyleWasShared(i)
                 }
                 None => {}
@@ -535,32 +719,40 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                 parent: Option<LayoutNode>) {
         self.initialize_layout_data(layout_context.shared.layout_chan.clone());
 
-        // First, check to see whether we can share a style with someone.
-        let sharing_result = unsafe {
-            self.share_style_if_possible(layout_context.style_sharing_candidate_cache(), parent.clone())
-        };
+        // Nodes that haven't changed since the last reflow keep their style.
+        if layout_context.shared.full_reflow || self.is_dirty() {
+            // First, check to see whether we can share a style with someone.
+            let sharing_result = unsafe {
+                self.share_style_if_possible(layout_context.style_sharing_candidate_cache(),
+                                             &layout_context.shared.style_cache_statistics,
+                                             parent.clone())
+            };
 
-        // Otherwise, match and cascade selectors.
-        match sharing_result {
-            CannotShare(mut shareable) => {
-                if self.is_element() {
-                    self.match_node(stylist, &*parent_bf, applicable_declarations, &mut shareable);
-                }
+            // Otherwise, match and cascade selectors.
+            match sharing_result {
+                CannotShare(mut shareable) => {
+                    if self.is_element() {
+                        self.match_node(stylist, &*parent_bf, applicable_declarations, &mut shareable);
+                    }
 
-                unsafe {
-                    self.cascade_node(parent,
-                                      applicable_declarations,
-                                      layout_context.applicable_declarations_cache())
-                }
+                    unsafe {
+                        self.cascade_node(parent,
+                                          applicable_declarations,
+                                          layout_context.applicable_declarations_cache(),
+                                          &layout_context.shared.style_cache_statistics)
+                    }
 
-                applicable_declarations.clear();
+                    applicable_declarations.clear();
 
-                // Add ourselves to the LRU cache.
-                if shareable {
-                    layout_context.style_sharing_candidate_cache().insert_if_possible(self)
+                    // Add ourselves to the LRU cache.
+                    if shareable {
+                        layout_context.style_sharing_candidate_cache().insert_if_possible(self)
+                    }
+                }
+                StyleWasShared(index) => {
+                    layout_context.style_sharing_candidate_cache().touch(index)
                 }
             }
-            StyleWasShared(index) => layout_context.style_sharing_candidate_cache().touch(index),
         }
 
         match *parent_bf {
@@ -568,12 +760,16 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
             Some(ref mut pbf) => self.insert_into_bloom_filter(pbf),
         }
 
+        // Kids whose flows can be reused from the last reflow don't need to be visited at all.
+        self.dirty_kids_affected_by_siblings(stylist);
         for kid in self.children() {
-            kid.recalc_style_for_subtree(stylist,
-                                         layout_context,
-                                         parent_bf,
-                                         applicable_declarations,
-                                         Some(self.clone()));
+            if needs_flow_construction(&ThreadSafeLayoutNode::new(&kid), layout_context) {
+                kid.recalc_style_for_subtree(stylist,
+                                             layout_context,
+                                             parent_bf,
+                                             applicable_declarations,
+                                             Some(self.clone()));
+            }
         }
 
         match *parent_bf {
@@ -585,12 +781,14 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
         let layout_node = ThreadSafeLayoutNode::new(self);
         let mut flow_constructor = FlowConstructor::new(layout_context);
         flow_constructor.process(&layout_node);
+        self.clear_dirty_bits();
     }
 
     unsafe fn cascade_node(&self,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
-                           applicable_declarations_cache: &mut ApplicableDeclarationsCache) {
+                           applicable_declarations_cache: &mut ApplicableDeclarationsCache,
+                           statistics: &StyleCacheStatistics) {
         // Get our parent's style. This must be unsafe so that we don't touch the parent's
         // borrow flags.
         //
@@ -613,9 +811,12 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
         };
 
         let mut layout_data_ref = self.mutate_layout_data();
-        match &mut *layout_data_ref {
+        let style_changed = match &mut *layout_data_ref {
             &None => fail!("no layout data"),
             &Some(ref mut layout_data) => {
+                let old_style = layout_data.shared_data.style.clone();
+                let had_generated_content = layout_data.data.before_style.is_some() ||
+                    layout_data.data.after_style.is_some();
                 match self.type_id() {
                     Some(TextNodeTypeId) => {
                         // Text nodes get a copy of the parent style. This ensures
@@ -626,27 +827,100 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                         layout_data.shared_data.style = Some(cloned_parent_style);
                     }
                     _ => {
+                        let element_key = if self.is_element() {
+                            Some(StyleSharingKey::new(&self.as_element()))
+                        } else {
+                            None
+                        };
                         self.cascade_node_pseudo_element(parent_style,
                                                          applicable_declarations.normal.as_slice(),
                                                          &mut layout_data.shared_data.style,
                                                          applicable_declarations_cache,
-                                                         applicable_declarations.normal_shareable);
+                                                         statistics,
+                                                         applicable_declarations.normal_shareable,
+                                                         element_key);
                         if applicable_declarations.before.len() > 0 {
                             self.cascade_node_pseudo_element(Some(layout_data.shared_data.style.as_ref().unwrap()),
                                                              applicable_declarations.before.as_slice(),
                                                              &mut layout_data.data.before_style,
                                                              applicable_declarations_cache,
-                                                             false);
+                                                             statistics,
+                                                             false,
+                                                             None);
+                        } else {
+                            layout_data.data.before_style = None
                         }
                         if applicable_declarations.after.len() > 0 {
                             self.cascade_node_pseudo_element(Some(layout_data.shared_data.style.as_ref().unwrap()),
                                                              applicable_declarations.after.as_slice(),
                                                              &mut layout_data.data.after_style,
                                                              applicable_declarations_cache,
-                                                             false);
+                                                             statistics,
+                                                             false,
+                                                             None);
+                        } else {
+                            layout_data.data.after_style = None
                         }
                     }
                 }
+
+                let has_generated_content = had_generated_content ||
+                    layout_data.data.before_style.is_some() ||
+                    layout_data.data.after_style.is_some();
+                let damage = restyle_damage(&old_style,
+                                            &layout_data.shared_data.style,
+                                            has_generated_content);
+                layout_data.data.restyle_damage = Some(damage);
+
+                style_changed(&old_style, &layout_data.shared_data.style)
+            }
+        };
+
+        // The kids inherit from this style, so if it changed they have to be restyled too.
+        if style_changed {
+            for kid in self.children() {
+                kid.set_dirty(true)
+            }
+        }
+    }
+
+    fn dirty_kids_affected_by_siblings(&self, stylist: &Stylist) {
+        let has_sibling_combinators = stylist.has_sibling_combinators();
+        let has_structural_pseudo_classes = stylist.has_structural_pseudo_classes();
+        if !has_sibling_combinators && !has_structural_pseudo_classes {
+            return
+        }
+
+        // If kids were removed, we no longer know which siblings they were next to.
+        if self.has_changed() {
+            for kid in self.children() {
+                kid.set_dirty(true)
+            }
+            return
+        }
+
+        let mut dirty_following_siblings = false;
+        for kid in self.children() {
+            if dirty_following_siblings {
+                kid.set_dirty(true);
+                continue
+            }
+            if !kid.is_element() || !kid.is_dirty() {
+                continue
+            }
+
+            // A newly inserted element shifts the positions of all its siblings. Other changes
+            // that aren't just to the style are treated the same way.
+            if kid.has_changed() && has_structural_pseudo_classes {
+                for sibling in self.children() {
+                    sibling.set_dirty(true)
+                }
+                return
+            }
+
+            // `+` and `~` only look at the siblings before an element.
+            if has_sibling_combinators {
+                dirty_following_siblings = true
             }
         }
     }
@@ -356,8 +356,10 @@ impl Floats {
             let maybe_location = self.available_rect(float_b, info.size.block, info.max_inline_size);
             debug!("place_float: Got available rect: {:?} for y-pos: {}", maybe_location, float_b);
             match maybe_location {
-                // If there are no floats blocking us, return the current location
-                // TODO(eatkinson): integrate with overflow
+                // If there are no floats blocking us, return the current location. A float wider
+                // than `max_inline_size` ends up here too, since no lower position can give it
+                // more room; it overflows its containing block, toward the inline-start side if
+                // it floats right.
                 None => {
                     return match info.kind {
                         FloatLeft => {
@@ -416,6 +418,36 @@ impl Floats {
         }
     }
 
+    /// Returns true if there might be floats here and false if there are guaranteed not to be.
+    pub fn is_present(&self) -> bool {
+        self.list.is_present()
+    }
+
+    /// Returns a copy of these floats minus the ones that the given kind of clearance moves past.
+    /// A flow that clears floats on one side can still be impacted by floats on the other side.
+    pub fn without_cleared_floats(&self, clear: ClearType) -> Floats {
+        let mut result = Floats::new(self.writing_mode);
+        result.offset = self.offset;
+
+        let list = match self.list.get() {
+            None => return result,
+            Some(list) => list,
+        };
+        for float in list.floats.iter() {
+            match (clear, float.kind) {
+                (ClearLeft, FloatRight) | (ClearRight, FloatLeft) => {
+                    let new_list = result.list.get_mut();
+                    new_list.floats.push(float.clone());
+
+                    // Later floats still can't go any higher than the cleared ones did.
+                    new_list.max_block_start = list.max_block_start;
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
     pub fn clearance(&self, clear: ClearType) -> Au {
         let list = match self.list.get() {
             None => return Au(0),
@@ -35,6 +35,8 @@ use fragment::{Fragment, TableRowFragment, TableCellFragment};
 use incremental::RestyleDamage;
 use inline::InlineFlow;
 use model::{CollapsibleMargins, IntrinsicISizes, MarginCollapseInfo};
+use multicol::MulticolFlow;
+use pagination::PageGrid;
 use parallel::FlowParallelInfo;
 use table::TableFlow;
 use table_caption::TableCaptionFlow;
@@ -46,7 +48,7 @@ use table_wrapper::TableWrapperFlow;
 use wrapper::ThreadSafeLayoutNode;
 
 use collections::dlist::DList;
-use geom::Point2D;
+use geom::{Point2D, Rect};
 use gfx::display_list::DisplayList;
 use gfx::render_task::RenderLayer;
 use serialize::{Encoder, Encodable};
@@ -98,6 +100,11 @@ pub trait Flow: fmt::Show + ToString + Sync {
         fail!("called as_inline() on a non-inline flow")
     }
 
+    /// If this is a multi-column flow, returns the underlying object. Fails otherwise.
+    fn as_multicol<'a>(&'a mut self) -> &'a mut MulticolFlow {
+        fail!("called as_multicol() on a non-multicol flow")
+    }
+
     /// If this is a table wrapper flow, returns the underlying object. Fails otherwise.
     fn as_table_wrapper<'a>(&'a mut self) -> &'a mut TableWrapperFlow {
         fail!("called as_table_wrapper() on a non-tablewrapper flow")
@@ -203,11 +210,11 @@ pub trait Flow: fmt::Show + ToString + Sync {
     }
 
     /// Assigns block-sizes in-order; or, if this is a float, places the float. The default
-    /// implementation simply assigns block-sizes if this flow is impacted by floats. Returns true if
-    /// this child was impacted by floats or false otherwise.
+    /// implementation simply assigns block-sizes if this flow is impacted by floats or laid out
+    /// across pages. Returns true if this child was laid out here or false otherwise.
     fn assign_block_size_for_inorder_child_if_necessary<'a>(&mut self, layout_context: &'a LayoutContext<'a>)
                                                     -> bool {
-        let impacted = base(&*self).flags.impacted_by_floats();
+        let impacted = base(&*self).flags.impacted_by_floats() || base(&*self).page_grid.is_some();
         if impacted {
             self.assign_block_size(layout_context);
         }
@@ -268,13 +275,19 @@ pub trait Flow: fmt::Show + ToString + Sync {
     }
 
     fn is_positioned(&self) -> bool {
-        self.is_relatively_positioned() || self.is_absolutely_positioned()
+        self.is_relatively_positioned() || self.is_sticky_positioned() ||
+            self.is_absolutely_positioned()
     }
 
     fn is_relatively_positioned(&self) -> bool {
         self.positioning() == position::relative
     }
 
+    /// Return true if this flow has position 'sticky'.
+    fn is_sticky_positioned(&self) -> bool {
+        self.positioning() == position::sticky
+    }
+
     fn is_absolutely_positioned(&self) -> bool {
         self.positioning() == position::absolute || self.is_fixed()
     }
@@ -318,7 +331,7 @@ impl<'a, E, S: Encoder<E>> Encodable<S, E> for &'a Flow + 'a {
             try!(e.emit_struct_field("class", 0, |e| self.class().encode(e)))
             e.emit_struct_field("data", 1, |e| {
                 match self.class() {
-                    BlockFlowClass => self.as_immutable_block().encode(e),
+                    BlockFlowClass | MulticolFlowClass => self.as_immutable_block().encode(e),
                     InlineFlowClass => self.as_immutable_inline().encode(e),
                     TableFlowClass => self.as_immutable_table().encode(e),
                     TableWrapperFlowClass => self.as_immutable_table_wrapper().encode(e),
@@ -457,6 +470,7 @@ pub trait MutableOwnedFlowUtils {
 pub enum FlowClass {
     BlockFlowClass,
     InlineFlowClass,
+    MulticolFlowClass,
     TableWrapperFlowClass,
     TableFlowClass,
     TableColGroupFlowClass,
@@ -500,7 +514,7 @@ pub trait PostorderFlowTraversal {
 }
 
 /// Flags used in flows, tightly packed to save space.
-#[deriving(Clone, Encodable)]
+#[deriving(Clone, Encodable, PartialEq)]
 pub struct FlowFlags(pub u8);
 
 /// The bitmask of flags that represent the `has_left_floated_descendants` and
@@ -593,6 +607,7 @@ impl FlowFlags {
 /// The Descendants of a flow.
 ///
 /// Also, details about their position wrt this flow.
+#[deriving(Clone)]
 pub struct Descendants {
     /// Links to every descendant. This must be private because it is unsafe to leak `FlowRef`s to
     /// layout.
@@ -677,6 +692,9 @@ pub struct AbsolutePositionInfo {
     ///
     /// FIXME(pcwalton): Move into `FlowFlags`.
     pub layers_needed_for_positioned_flows: bool,
+    /// The content box of the containing block for sticky-positioned descendants, in page
+    /// coordinates.
+    pub sticky_containing_block: Rect<Au>,
 }
 
 impl AbsolutePositionInfo {
@@ -687,6 +705,7 @@ impl AbsolutePositionInfo {
             relative_containing_block_size: LogicalSize::zero(writing_mode),
             absolute_containing_block_position: Zero::zero(),
             layers_needed_for_positioned_flows: false,
+            sticky_containing_block: Rect::zero(),
         }
     }
 }
@@ -698,6 +717,8 @@ pub struct BaseFlow {
     /// The necessity of this will disappear once we have dynamically-sized types.
     ref_count: AtomicUint,
 
+    /// The layout passes that this flow needs to go through again. Flows that are reused from
+    /// the last reflow start out with none.
     pub restyle_damage: RestyleDamage,
 
     /// The children of this flow.
@@ -728,6 +749,10 @@ pub struct BaseFlow {
     /// The floats next to this flow.
     pub floats: Floats,
 
+    /// The pages that this flow is laid out across, if the document is paginated. Like `floats`,
+    /// this is handed down by the parent before it lays this flow out.
+    pub page_grid: Option<PageGrid>,
+
     /// The collapsible margins for this flow, if any.
     pub collapsible_margins: CollapsibleMargins,
 
@@ -816,7 +841,8 @@ impl BaseFlow {
         BaseFlow {
             ref_count: AtomicUint::new(1),
 
-            restyle_damage: node.restyle_damage(),
+            // A flow that was just constructed has never been laid out, so it needs every pass.
+            restyle_damage: RestyleDamage::all(),
 
             children: FlowList::new(),
 
@@ -827,6 +853,7 @@ impl BaseFlow {
             parallel: FlowParallelInfo::new(),
 
             floats: Floats::new(writing_mode),
+            page_grid: None,
             collapsible_margins: CollapsibleMargins::new(),
             abs_position: Zero::zero(),
             abs_descendants: Descendants::new(),
@@ -857,10 +884,10 @@ impl BaseFlow {
 }
 
 impl<'a> ImmutableFlowUtils for &'a Flow + 'a {
-    /// Returns true if this flow is a block flow.
+    /// Returns true if this flow is a block flow, or wraps one to lay out its children.
     fn is_block_like(self) -> bool {
         match self.class() {
-            BlockFlowClass => true,
+            BlockFlowClass | MulticolFlowClass => true,
             _ => false,
         }
     }
@@ -1112,6 +1139,7 @@ impl<'a> MutableFlowUtils for &'a mut Flow + 'a {
         match self.class() {
             BlockFlowClass => self.as_block().build_display_list_block(layout_context),
             InlineFlowClass => self.as_inline().build_display_list_inline(layout_context),
+            MulticolFlowClass => self.as_multicol().build_display_list_multicol(layout_context),
             TableWrapperFlowClass => {
                 self.as_table_wrapper().build_display_list_table_wrapper(layout_context)
             }
@@ -13,13 +13,14 @@ use floats::{ClearBoth, ClearLeft, ClearRight, ClearType};
 use flow::Flow;
 use flow;
 use flow_ref::FlowRef;
+use generated_content::GeneratedQuote;
 use inline::{InlineFragmentContext, InlineMetrics};
 use layout_debug;
 use model::{Auto, IntrinsicISizes, MaybeAuto, Specified, specified};
 use model;
 use text;
 use util::{OpaqueNodeMethods, ToGfxColor};
-use wrapper::{TLayoutNode, ThreadSafeLayoutNode};
+use wrapper::{Normal, TLayoutNode, ThreadSafeLayoutNode};
 
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use geom::approxeq::ApproxEq;
@@ -28,34 +29,45 @@ use gfx::display_list::{BackgroundAndBorderLevel, BaseDisplayItem, BorderDisplay
 use gfx::display_list::{BorderDisplayItemClass, ClipDisplayItem, ClipDisplayItemClass};
 use gfx::display_list::{ContentStackingLevel, DisplayItem, DisplayList, ImageDisplayItem};
 use gfx::display_list::{ImageDisplayItemClass, LineDisplayItem};
-use gfx::display_list::{LineDisplayItemClass, OpaqueNode, PseudoDisplayItemClass};
+use gfx::display_list::{LineDisplayItemClass, OpaqueNode, PseudoDisplayItem};
+use gfx::display_list::{PseudoDisplayItemClass};
 use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, StackingLevel};
+use gfx::display_list::{TextDecorationDisplayItem, TextDecorationDisplayItemClass};
 use gfx::display_list::{TextDisplayItem, TextDisplayItemClass};
 use gfx::display_list::{Upright, SidewaysLeft, SidewaysRight};
 use gfx::font::FontStyle;
 use gfx::text::glyph::CharIndex;
 use gfx::text::text_run::TextRun;
+use libc::uintptr_t;
 use serialize::{Encodable, Encoder};
 use servo_msg::constellation_msg::{ConstellationChan, FrameRectMsg, PipelineId, SubpageId};
 use servo_net::image::holder::ImageHolder;
+use servo_net::image_cache_task::{DecodeSize, FitSize, FullSize};
 use servo_net::local_image_cache::LocalImageCache;
-use servo_util::geometry::Au;
+use servo_util::cursor::{Cursor, DefaultCursor, TextCursor};
+use servo_util::geometry::{Au, MAX_AU};
 use servo_util::geometry;
 use servo_util::logical_geometry::{LogicalRect, LogicalSize, LogicalMargin};
 use servo_util::range::*;
 use servo_util::smallvec::SmallVec;
 use servo_util::str::is_whitespace;
 use std::cmp::{max, min};
+use std::collections::hashmap::HashMap;
 use std::fmt;
 use std::from_str::FromStr;
 use std::mem;
 use std::num::Zero;
-use style::{ComputedValues, TElement, TNode, cascade_anonymous, RGBA};
+use style::{ComputedValues, TElement, TNode, cascade_anonymous};
 use style::computed_values::{LengthOrPercentage, LengthOrPercentageOrAuto};
 use style::computed_values::{LengthOrPercentageOrNone};
-use style::computed_values::{overflow, LPA_Auto, background_attachment};
-use style::computed_values::{background_repeat, border_style, clear, position, text_align};
-use style::computed_values::{text_decoration, vertical_align, visibility, white_space};
+use style::computed_values::{LPA_Auto, LPA_Length, LPA_Percentage, LPN_Length, LPN_None};
+use style::computed_values::{LPN_Percentage, LP_Length, LP_Percentage};
+use style::computed_values::{overflow_x, overflow_y, background_attachment, object_fit};
+use style::computed_values::{background_repeat, border_style, clear, hyphens, position};
+use style::computed_values::text_align;
+use style::computed_values::cursor::{AutoCursor, SpecifiedCursor};
+use style::computed_values::{text_decoration_line, text_decoration_style, vertical_align};
+use style::computed_values::{visibility, white_space};
 use sync::{Arc, Mutex};
 use url::Url;
 
@@ -110,6 +122,11 @@ pub struct Fragment {
     /// that are part of an inline formatting context.
     pub inline_context: Option<InlineFragmentContext>,
 
+    /// If this fragment shows a quote of generated content, which one. Every piece that the text
+    /// of the quote is split into keeps it, so that `generated_content::resolve_quotes()` can
+    /// change the text when quotes before it are added or removed.
+    pub quote: Option<GeneratedQuote>,
+
     /// A debug ID that is consistent for the life of
     /// this fragment (via transform etc).
     pub debug_id: uint,
@@ -211,27 +228,25 @@ pub struct ImageFragmentInfo {
 }
 
 impl ImageFragmentInfo {
-    /// Creates a new image fragment from the given URL and local image cache.
+    /// Creates a new image fragment from the given URL and local image cache. The image is
+    /// decoded at `decode_size`.
     ///
     /// FIXME(pcwalton): The fact that image fragments store the cache in the fragment makes little sense to
     /// me.
     pub fn new(node: &ThreadSafeLayoutNode,
                image_url: Url,
-               local_image_cache: Arc<Mutex<LocalImageCache>>)
+               local_image_cache: Arc<Mutex<LocalImageCache>>,
+               decode_size: DecodeSize)
                -> ImageFragmentInfo {
-        fn convert_length(node: &ThreadSafeLayoutNode, name: &str) -> Option<Au> {
-            let element = node.as_element();
-            element.get_attr(&ns!(""), name).and_then(|string| {
-                let n: Option<int> = FromStr::from_str(string);
-                n
-            }).and_then(|pixels| Some(Au::from_px(pixels)))
-        }
-
         let is_vertical = node.style().writing_mode.is_vertical();
-        let dom_width = convert_length(node, "width");
-        let dom_height = convert_length(node, "height");
+        let dom_width = ImageFragmentInfo::dom_length(node, "width");
+        let dom_height = ImageFragmentInfo::dom_length(node, "height");
+        let node_address: OpaqueNode = OpaqueNodeMethods::from_thread_safe_layout_node(node);
         ImageFragmentInfo {
-            image: ImageHolder::new(image_url, local_image_cache),
+            image: ImageHolder::new_with_decode_size(image_url,
+                                                     node_address.id() as uint,
+                                                     local_image_cache,
+                                                     decode_size),
             computed_inline_size: None,
             computed_block_size: None,
             dom_inline_size: if is_vertical { dom_height } else { dom_width },
@@ -240,6 +255,55 @@ impl ImageFragmentInfo {
         }
     }
 
+    /// Returns the length in the given attribute of an image element.
+    fn dom_length(node: &ThreadSafeLayoutNode, name: &str) -> Option<Au> {
+        // Images of generated content don't take the element's attributes.
+        if node.get_pseudo_element_type() != Normal {
+            return None
+        }
+        let element = node.as_element();
+        element.get_attr(&ns!(""), name).and_then(|string| {
+            let n: Option<int> = FromStr::from_str(string);
+            n
+        }).and_then(|pixels| Some(Au::from_px(pixels)))
+    }
+
+    /// Returns how many pixels the image of the given node needs to keep. An image whose size is
+    /// fixed by lengths in its style, or by its attributes, is shown at that size, so it only
+    /// keeps enough pixels for that size in device pixels. Other images may be shown at any size.
+    pub fn decode_size(node: &ThreadSafeLayoutNode, device_pixels_per_px: f32) -> DecodeSize {
+        let style = node.style();
+        let box_style = style.get_box();
+
+        // A minimum size could make the image bigger than its fixed size.
+        match (box_style.min_width, box_style.min_height) {
+            (LP_Length(Au(0)), LP_Length(Au(0))) => {}
+            _ => return FullSize,
+        }
+        // The image overflows its box at its own size with `object-fit: none`, and scaled up to
+        // cover the box with `object-fit: cover`.
+        match box_style.object_fit {
+            object_fit::none | object_fit::cover => return FullSize,
+            object_fit::fill | object_fit::contain | object_fit::scale_down => {}
+        }
+        let length = |style_length: LengthOrPercentageOrAuto, name: &str| {
+            match style_length {
+                LPA_Length(length) => Some(length),
+                LPA_Auto => ImageFragmentInfo::dom_length(node, name),
+                LPA_Percentage(_) => None,
+            }
+        };
+        match (length(box_style.width, "width"), length(box_style.height, "height")) {
+            (Some(width), Some(height)) => {
+                let to_device_pixels = |length: Au| {
+                    (length.to_subpx() * device_pixels_per_px as f64).ceil() as u32
+                };
+                FitSize(Size2D(to_device_pixels(width), to_device_pixels(height)))
+            }
+            _ => FullSize,
+        }
+    }
+
     /// Returns the calculated inline-size of the image, accounting for the inline-size attribute.
     pub fn computed_inline_size(&self) -> Au {
         self.computed_inline_size.expect("image inline_size is not computed yet!")
@@ -265,33 +329,172 @@ impl ImageFragmentInfo {
     // Return used value for inline-size or block-size.
     //
     // `dom_length`: inline-size or block-size as specified in the `img` tag.
-    // `style_length`: inline-size as given in the CSS
+    // `style_length`: inline-size or block-size as given in the CSS.
+    // `container_size`: the size that percentages resolve against, or `None` if percentages
+    // should be treated as `auto`.
     pub fn style_length(style_length: LengthOrPercentageOrAuto,
                         dom_length: Option<Au>,
-                        container_inline_size: Au) -> MaybeAuto {
-        match (MaybeAuto::from_style(style_length,container_inline_size),dom_length) {
-            (Specified(length),_) => {
-                Specified(length)
-            },
-            (Auto,Some(length)) => {
+                        container_size: Option<Au>) -> MaybeAuto {
+        match (style_length, container_size, dom_length) {
+            (LPA_Length(length), _, _) => Specified(length),
+            (LPA_Percentage(percent), Some(container_size), _) => {
+                Specified(container_size.scale_by(percent))
+            }
+            (LPA_Percentage(_), None, Some(length)) | (LPA_Auto, _, Some(length)) => {
                 Specified(length)
-            },
-            (Auto,None) => {
-                Auto
             }
+            (LPA_Percentage(_), None, None) | (LPA_Auto, _, None) => Auto,
         }
     }
 
-    /// Clamp a value obtained from style_length, based on min / max lengths.
-    pub fn clamp_size(size: Au, min_size: LengthOrPercentage, max_size: LengthOrPercentageOrNone,
-                        container_inline_size: Au) -> Au {
-        let min_size = model::specified(min_size, container_inline_size);
-        let max_size = model::specified_or_none(max_size, container_inline_size);
+    /// Returns the used minimum and maximum lengths in one dimension. If `container_size` is
+    /// `None`, percentages are treated as `0` and `none` respectively, per CSS 2.1 § 10.7. The
+    /// maximum is never less than the minimum (CSS 2.1 § 10.4).
+    pub fn min_max_sizes(min_size: LengthOrPercentage,
+                         max_size: LengthOrPercentageOrNone,
+                         container_size: Option<Au>)
+                         -> (Au, Au) {
+        let min_size = match (min_size, container_size) {
+            (LP_Length(length), _) => length,
+            (LP_Percentage(percent), Some(container_size)) => container_size.scale_by(percent),
+            (LP_Percentage(_), None) => Au(0),
+        };
+        let max_size = match (max_size, container_size) {
+            (LPN_Length(length), _) => length,
+            (LPN_Percentage(percent), Some(container_size)) => container_size.scale_by(percent),
+            (LPN_Percentage(_), None) | (LPN_None, _) => MAX_AU,
+        };
+        (min_size, max(min_size, max_size))
+    }
 
-        Au::max(min_size, match max_size {
-            None => size,
-            Some(max_size) => Au::min(size, max_size),
-        })
+    /// Computes the used inline-size and block-size of the image's content box per CSS 2.1
+    /// § 10.3.2 and § 10.6.2, honoring the intrinsic aspect ratio and resolving `min-*` and
+    /// `max-*` constraints per the table in § 10.4.
+    ///
+    /// Inline-size percentages resolve against `container_inline_size`, or are treated as `auto`
+    /// if it's `None`, as they are when computing intrinsic inline-sizes. Block-size percentages
+    /// are always treated as `auto`, since the containing block's block-size isn't known yet.
+    pub fn compute_used_size(&mut self, style: &ComputedValues, container_inline_size: Option<Au>)
+                             -> (Au, Au) {
+        let inline_size = ImageFragmentInfo::style_length(style.content_inline_size(),
+                                                          self.dom_inline_size,
+                                                          container_inline_size);
+        let block_size = ImageFragmentInfo::style_length(style.content_block_size(),
+                                                         self.dom_block_size,
+                                                         None);
+        let (min_inline_size, max_inline_size) =
+            ImageFragmentInfo::min_max_sizes(style.min_inline_size(),
+                                             style.max_inline_size(),
+                                             container_inline_size);
+        let (min_block_size, max_block_size) =
+            ImageFragmentInfo::min_max_sizes(style.min_block_size(), style.max_block_size(), None);
+
+        let intrinsic_inline_size = self.image_inline_size();
+        let intrinsic_block_size = self.image_block_size();
+        let has_ratio = intrinsic_inline_size != Au(0) && intrinsic_block_size != Au(0);
+
+        match (inline_size, block_size) {
+            (Specified(inline_size), Specified(block_size)) => {
+                (clamp(inline_size, min_inline_size, max_inline_size),
+                 clamp(block_size, min_block_size, max_block_size))
+            }
+            (Specified(inline_size), Auto) => {
+                let inline_size = clamp(inline_size, min_inline_size, max_inline_size);
+                let block_size = if has_ratio {
+                    scale_length(inline_size, intrinsic_block_size, intrinsic_inline_size)
+                } else {
+                    intrinsic_block_size
+                };
+                (inline_size, clamp(block_size, min_block_size, max_block_size))
+            }
+            (Auto, Specified(block_size)) => {
+                let block_size = clamp(block_size, min_block_size, max_block_size);
+                let inline_size = if has_ratio {
+                    scale_length(block_size, intrinsic_inline_size, intrinsic_block_size)
+                } else {
+                    intrinsic_inline_size
+                };
+                (clamp(inline_size, min_inline_size, max_inline_size), block_size)
+            }
+            (Auto, Auto) if !has_ratio => {
+                (clamp(intrinsic_inline_size, min_inline_size, max_inline_size),
+                 clamp(intrinsic_block_size, min_block_size, max_block_size))
+            }
+            (Auto, Auto) => {
+                // This is the constraint violation table from CSS 2.1 § 10.4.
+                let (w, h) = (intrinsic_inline_size, intrinsic_block_size);
+                let (min_w, max_w, min_h, max_h) =
+                    (min_inline_size, max_inline_size, min_block_size, max_block_size);
+                if w > max_w && h > max_h {
+                    if max_w.to_f64().unwrap() * h.to_f64().unwrap() <=
+                            max_h.to_f64().unwrap() * w.to_f64().unwrap() {
+                        (max_w, max(min_h, scale_length(max_w, h, w)))
+                    } else {
+                        (max(min_w, scale_length(max_h, w, h)), max_h)
+                    }
+                } else if w < min_w && h < min_h {
+                    if min_w.to_f64().unwrap() * h.to_f64().unwrap() <=
+                            min_h.to_f64().unwrap() * w.to_f64().unwrap() {
+                        (min(max_w, scale_length(min_h, w, h)), min_h)
+                    } else {
+                        (min_w, min(max_h, scale_length(min_w, h, w)))
+                    }
+                } else if w < min_w && h > max_h {
+                    (min_w, max_h)
+                } else if w > max_w && h < min_h {
+                    (max_w, min_h)
+                } else if w > max_w {
+                    (max_w, max(scale_length(max_w, h, w), min_h))
+                } else if w < min_w {
+                    (min_w, min(scale_length(min_w, h, w), max_h))
+                } else if h > max_h {
+                    (max(scale_length(max_h, w, h), min_w), max_h)
+                } else if h < min_h {
+                    (min(scale_length(min_h, w, h), max_w), min_h)
+                } else {
+                    (w, h)
+                }
+            }
+        }
+    }
+
+    /// Returns the rectangle that the image should be painted into, given its content box, per
+    /// the `object-fit` and `object-position` properties. The result may extend outside the
+    /// content box, in which case the image must be clipped to it.
+    pub fn object_fit_bounds(style: &ComputedValues, content_box: &Rect<Au>, image_size: Size2D<Au>)
+                             -> Rect<Au> {
+        let box_style = style.get_box();
+        let size = if image_size.width == Au(0) || image_size.height == Au(0) {
+            content_box.size
+        } else {
+            let inline_scale = content_box.size.width.to_f64().unwrap() /
+                image_size.width.to_f64().unwrap();
+            let block_scale = content_box.size.height.to_f64().unwrap() /
+                image_size.height.to_f64().unwrap();
+            let (contain_scale, cover_scale) = if inline_scale < block_scale {
+                (inline_scale, block_scale)
+            } else {
+                (block_scale, inline_scale)
+            };
+            let scaled_size = |scale: f64| {
+                Size2D(image_size.width.scale_by(scale), image_size.height.scale_by(scale))
+            };
+            match box_style.object_fit {
+                object_fit::fill => content_box.size,
+                object_fit::contain => scaled_size(contain_scale),
+                object_fit::cover => scaled_size(cover_scale),
+                object_fit::none => image_size,
+                object_fit::scale_down if contain_scale < 1.0 => scaled_size(contain_scale),
+                object_fit::scale_down => image_size,
+            }
+        };
+
+        let origin = Point2D(
+            content_box.origin.x + model::specified(box_style.object_position.horizontal,
+                                                    content_box.size.width - size.width),
+            content_box.origin.y + model::specified(box_style.object_position.vertical,
+                                                    content_box.size.height - size.height));
+        Rect(origin, size)
     }
 
     /// Tile an image
@@ -307,6 +510,16 @@ impl ImageFragmentInfo {
     }
 }
 
+/// Clamps `size` to lie between `min_size` and `max_size`.
+fn clamp(size: Au, min_size: Au, max_size: Au) -> Au {
+    max(min_size, min(size, max_size))
+}
+
+/// Returns `length * numerator / denominator`, for scaling by an intrinsic aspect ratio.
+fn scale_length(length: Au, numerator: Au, denominator: Au) -> Au {
+    length.scale_by(numerator.to_f64().unwrap() / denominator.to_f64().unwrap())
+}
+
 /// A fragment that represents an inline frame (iframe). This stores the pipeline ID so that the size
 /// of this iframe can be communicated via the constellation to the iframe's own layout task.
 #[deriving(Clone)]
@@ -339,6 +552,10 @@ pub struct ScannedTextFragmentInfo {
 
     /// The range within the above text run that this represents.
     pub range: Range<CharIndex>,
+
+    /// Whether a line was broken at a soft hyphen at the end of the range, so that a hyphen is
+    /// drawn after the text.
+    pub hyphenated: bool,
 }
 
 impl ScannedTextFragmentInfo {
@@ -347,6 +564,7 @@ impl ScannedTextFragmentInfo {
         ScannedTextFragmentInfo {
             run: run,
             range: range,
+            hyphenated: false,
         }
     }
 }
@@ -357,13 +575,23 @@ pub struct SplitInfo {
     // currently needed for splitting in the `inline::try_append_*` functions.
     pub range: Range<CharIndex>,
     pub inline_size: Au,
+    /// Whether the split ends at a soft hyphen inside the fragment, so that a hyphen is drawn
+    /// after it. The hyphen is included in `inline_size`.
+    pub hyphenated: bool,
 }
 
 impl SplitInfo {
     fn new(range: Range<CharIndex>, info: &ScannedTextFragmentInfo) -> SplitInfo {
+        let hyphenated = range.end() < info.range.end() &&
+            info.run.breaks_with_hyphen_at(range.end());
+        let mut inline_size = info.run.advance_for_range(&range);
+        if hyphenated {
+            inline_size = inline_size + info.run.hyphen_advance
+        }
         SplitInfo {
             range: range,
-            inline_size: info.run.advance_for_range(&range),
+            inline_size: inline_size,
+            hyphenated: hyphenated,
         }
     }
 }
@@ -439,6 +667,7 @@ impl Fragment {
             specific: constructor.build_specific_fragment_info_for_node(node),
             new_line_pos: vec!(),
             inline_context: None,
+            quote: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -457,6 +686,7 @@ impl Fragment {
             specific: specific,
             new_line_pos: vec!(),
             inline_context: None,
+            quote: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -483,6 +713,7 @@ impl Fragment {
             specific: specific,
             new_line_pos: vec!(),
             inline_context: None,
+            quote: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -502,6 +733,7 @@ impl Fragment {
             specific: specific,
             new_line_pos: vec!(),
             inline_context: None,
+            quote: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -525,17 +757,42 @@ impl Fragment {
             specific: specific,
             new_line_pos: self.new_line_pos.clone(),
             inline_context: self.inline_context.clone(),
+            quote: self.quote.clone(),
             debug_id: self.debug_id,
         }
     }
 
-    /// Adds a style to the inline context for this fragment. If the inline
-    /// context doesn't exist yet, it will be created.
-    pub fn add_inline_context_style(&mut self, style: Arc<ComputedValues>) {
+    /// Adds the style of an enclosing inline node to the inline context for this fragment. If the
+    /// inline context doesn't exist yet, it will be created.
+    pub fn add_inline_context_style(&mut self, node: OpaqueNode, style: Arc<ComputedValues>) {
         if self.inline_context.is_none() {
             self.inline_context = Some(InlineFragmentContext::new());
         }
-        self.inline_context.as_mut().unwrap().styles.push(style.clone());
+        let inline_context = self.inline_context.as_mut().unwrap();
+        inline_context.styles.push(style.clone());
+        inline_context.nodes.push(node);
+    }
+
+    /// Gives this fragment and its inline context the styles in `new_styles`, which are keyed by
+    /// the IDs of the nodes that they belong to. Used to keep the fragments of inline content
+    /// whose style changed in ways that don't call for new flows.
+    pub fn update_styles(&mut self, new_styles: &HashMap<uintptr_t, Arc<ComputedValues>>) {
+        match new_styles.find(&self.node.id()) {
+            Some(style) => self.style = style.clone(),
+            None => {}
+        }
+        match self.inline_context {
+            Some(ref mut inline_context) => {
+                for (node, style) in inline_context.nodes.iter()
+                                                   .zip(inline_context.styles.iter_mut()) {
+                    match new_styles.find(&node.id()) {
+                        Some(new_style) => *style = new_style.clone(),
+                        None => {}
+                    }
+                }
+            }
+            None => {}
+        }
     }
 
     /// Uses the style only to estimate the intrinsic inline-sizes. These may be modified for text
@@ -736,6 +993,10 @@ impl Fragment {
         self.style().get_inheritedtext().white_space
     }
 
+    pub fn hyphens(&self) -> hyphens::T {
+        self.style().get_inheritedtext().hyphens
+    }
+
     /// Returns the text decoration of this fragment, according to the style of the nearest ancestor
     /// element.
     ///
@@ -743,8 +1004,8 @@ impl Fragment {
     /// CSS 2.1 § 16.3.1. Unfortunately, computing this properly doesn't really fit into Servo's
     /// model. Therefore, this is a best lower bound approximation, but the end result may actually
     /// have the various decoration flags turned on afterward.
-    pub fn text_decoration(&self) -> text_decoration::T {
-        self.style().get_text().text_decoration
+    pub fn text_decoration_line(&self) -> text_decoration_line::T {
+        self.style().get_text().text_decoration_line
     }
 
     /// Returns the inline-start offset from margin edge to content edge.
@@ -804,7 +1065,9 @@ impl Fragment {
             Some(ref image_url) => image_url,
         };
 
-        let mut holder = ImageHolder::new(image_url.clone(), layout_context.shared.image_cache.clone());
+        let mut holder = ImageHolder::new(image_url.clone(),
+                                          self.node.id() as uint,
+                                          layout_context.shared.image_cache.clone());
         let image = match holder.get_image() {
             None => {
                 // No image data at all? Do nothing.
@@ -879,6 +1142,8 @@ impl Fragment {
         let image_display_item = ImageDisplayItemClass(box ImageDisplayItem {
             base: BaseDisplayItem::new(bounds, self.node, level),
             image: image.clone(),
+            url: image_url.clone(),
+            animation: holder.get_animation_if_present(),
             stretch_size: Size2D(Au::from_px(image.width as int),
                                  Au::from_px(image.height as int)),
         });
@@ -921,6 +1186,42 @@ impl Fragment {
         list.push(BorderDisplayItemClass(border_display_item))
     }
 
+    /// Adds the display item for the outline of the given style, which is drawn `outline-offset`
+    /// outside of the border box.
+    fn build_display_list_for_outline_if_applicable(&self,
+                                                    style: &ComputedValues,
+                                                    list: &mut DisplayList,
+                                                    abs_bounds: &Rect<Au>) {
+        let outline = style.get_outline();
+        let width = outline.outline_width;
+        if width == Au(0) {
+            return
+        }
+
+        let outset = width + outline.outline_offset;
+        let bounds = Rect(Point2D(abs_bounds.origin.x - outset, abs_bounds.origin.y - outset),
+                          Size2D(max(abs_bounds.size.width + outset + outset, Au(0)),
+                                 max(abs_bounds.size.height + outset + outset, Au(0))));
+        let color = style.resolve_color(outline.outline_color).to_gfx_color();
+        let outline_display_item = box BorderDisplayItem {
+            base: BaseDisplayItem::new(bounds, self.node, ContentStackingLevel),
+            border: SideOffsets2D::new_all_same(width),
+            color: SideOffsets2D::new_all_same(color),
+            style: SideOffsets2D::new_all_same(outline.outline_style),
+        };
+        list.push(BorderDisplayItemClass(outline_display_item))
+    }
+
+    /// Returns the mouse cursor to show over this fragment, resolving `cursor: auto` to the text
+    /// cursor over text and to the default cursor elsewhere.
+    pub fn cursor(&self) -> Cursor {
+        match (self.style().get_pointing().cursor, &self.specific) {
+            (SpecifiedCursor(cursor), _) => cursor,
+            (AutoCursor, &ScannedTextFragment(_)) => TextCursor,
+            (AutoCursor, _) => DefaultCursor,
+        }
+    }
+
     fn build_debug_borders_around_text_fragments(&self,
                                              display_list: &mut DisplayList,
                                              flow_origin: Point2D<Au>,
@@ -1033,9 +1334,13 @@ impl Fragment {
             let level =
                 StackingLevel::from_background_and_border_level(background_and_border_level);
 
-            // Add a pseudo-display item for content box queries. This is a very bogus thing to do.
-            let base_display_item = box BaseDisplayItem::new(absolute_fragment_bounds, self.node, level);
-            display_list.push(PseudoDisplayItemClass(base_display_item));
+            // Add a pseudo-display item for content box queries and hit testing. This is a very
+            // bogus thing to do.
+            let pseudo_display_item = box PseudoDisplayItem {
+                base: BaseDisplayItem::new(absolute_fragment_bounds, self.node, level),
+                cursor: self.cursor(),
+            };
+            display_list.push(PseudoDisplayItemClass(pseudo_display_item));
 
             // Add the background to the list, if applicable.
             match self.inline_context {
@@ -1063,8 +1368,6 @@ impl Fragment {
             }
 
             // Add a border, if applicable.
-            //
-            // TODO: Outlines.
             match self.inline_context {
                 Some(ref inline_context) => {
                     for style in inline_context.styles.iter().rev() {
@@ -1118,6 +1421,7 @@ impl Fragment {
                         absolute_content_box, self.node, ContentStackingLevel),
                     text_run: text_fragment.run.clone(),
                     range: text_fragment.range,
+                    hyphenated: text_fragment.hyphenated,
                     text_color: self.style().get_color().color.to_gfx_color(),
                     orientation: orientation,
                     baseline_origin: baseline_origin,
@@ -1126,43 +1430,98 @@ impl Fragment {
 
                 // Create display items for text decoration
                 {
-                    let line = |maybe_color: Option<RGBA>, rect: || -> LogicalRect<Au>| {
-                        match maybe_color {
-                            None => {},
-                            Some(color) => {
-                                accumulator.push(display_list, SolidColorDisplayItemClass(
-                                    box SolidColorDisplayItem {
-                                        base: BaseDisplayItem::new(
-                                            rect_to_absolute(rect()),
-                                            self.node, ContentStackingLevel),
-                                        color: color.to_gfx_color(),
-                                    }
-                                ));
+                    // Double and wavy lines need room for two lines or a wave.
+                    let decoration_height = |style: text_decoration_style::T, thickness: Au| {
+                        match style {
+                            text_decoration_style::double | text_decoration_style::wavy => {
+                                thickness * 3
                             }
+                            text_decoration_style::solid | text_decoration_style::dotted |
+                            text_decoration_style::dashed => thickness,
                         }
                     };
 
+                    // Each line, with its bounds, its thickness and whether it skips ink.
+                    let mut lines = vec!();
                     let text_decorations =
                         self.style().get_inheritedtext()._servo_text_decorations_in_effect;
-                    line(text_decorations.underline, || {
-                        let mut rect = content_box.clone();
-                        rect.start.b = rect.start.b + metrics.ascent - metrics.underline_offset;
-                        rect.size.block = metrics.underline_size;
-                        rect
-                    });
-
-                    line(text_decorations.overline, || {
-                        let mut rect = content_box.clone();
-                        rect.size.block = metrics.underline_size;
-                        rect
-                    });
-
-                    line(text_decorations.line_through, || {
-                        let mut rect = content_box.clone();
-                        rect.start.b = rect.start.b + metrics.ascent - metrics.strikeout_offset;
-                        rect.size.block = metrics.strikeout_size;
-                        rect
-                    });
+                    match text_decorations.underline {
+                        Some(line) => {
+                            let thickness = metrics.underline_size;
+                            let mut rect = content_box.clone();
+                            rect.start.b = rect.start.b + metrics.ascent - metrics.underline_offset;
+                            rect.size.block = decoration_height(line.style, thickness);
+                            lines.push((line, rect, thickness, true))
+                        }
+                        None => {}
+                    }
+                    match text_decorations.overline {
+                        Some(line) => {
+                            let thickness = metrics.underline_size;
+                            let mut rect = content_box.clone();
+                            rect.size.block = decoration_height(line.style, thickness);
+                            lines.push((line, rect, thickness, false))
+                        }
+                        None => {}
+                    }
+                    match text_decorations.line_through {
+                        Some(line) => {
+                            // Keep taller lines centered on where a solid one would be.
+                            let thickness = metrics.strikeout_size;
+                            let height = decoration_height(line.style, thickness);
+                            let mut rect = content_box.clone();
+                            rect.start.b = rect.start.b + metrics.ascent - metrics.strikeout_offset -
+                                (height - thickness) / 2;
+                            rect.size.block = height;
+                            lines.push((line, rect, thickness, false))
+                        }
+                        None => {}
+                    }
+
+                    for &(line, rect, thickness, skips_ink) in lines.iter() {
+                        let bounds = rect_to_absolute(rect);
+
+                        // Find where glyphs cross the line, give or take its thickness, so that
+                        // underlines pass between descenders instead of through them.
+                        let ink_extents = if skips_ink && !self.style.writing_mode.is_vertical() {
+                            let run = &text_fragment.run;
+                            let font = layout_context.font_context()
+                                                     .get_layout_font_from_template(
+                                                         &run.font_template,
+                                                         run.actual_pt_size);
+                            let top = rect.start.b - content_box.start.b - metrics.ascent;
+                            run.ink_extents_in_band(&mut *font.borrow_mut(),
+                                                    &text_fragment.range,
+                                                    top - thickness,
+                                                    top + rect.size.block + thickness)
+                        } else {
+                            vec!()
+                        };
+
+                        let gaps: Vec<(Au, Au)> = ink_extents.iter().map(|&(start, end)| {
+                            (baseline_origin.x + start - thickness,
+                             baseline_origin.x + end + thickness)
+                        }).collect();
+                        let segments = segments_between_gaps(bounds.origin.x,
+                                                             bounds.max_x(),
+                                                             gaps.as_slice());
+
+                        for &(segment_start, segment_end) in segments.iter() {
+                            let segment_bounds = Rect(Point2D(segment_start, bounds.origin.y),
+                                                      Size2D(segment_end - segment_start,
+                                                             bounds.size.height));
+                            accumulator.push(display_list, TextDecorationDisplayItemClass(
+                                box TextDecorationDisplayItem {
+                                    base: BaseDisplayItem::new(segment_bounds,
+                                                               self.node,
+                                                               ContentStackingLevel),
+                                    color: line.color.to_gfx_color(),
+                                    style: line.style,
+                                    thickness: thickness,
+                                }
+                            ));
+                        }
+                    }
                 }
 
                 // Draw debug frames for text bounds.
@@ -1188,16 +1547,41 @@ impl Fragment {
                             Some(image) => {
                                 debug!("(building display list) building image fragment");
 
-                                // Place the image into the display list.
-                                let image_display_item = box ImageDisplayItem {
-                                    base: BaseDisplayItem::new(absolute_content_box,
+                                // Place the image into the display list, fitting it into the
+                                // content box per `object-fit` and `object-position`.
+                                let image_size = Size2D(Au::from_px(image.width as int),
+                                                        Au::from_px(image.height as int));
+                                let image_bounds = ImageFragmentInfo::object_fit_bounds(
+                                    &*self.style,
+                                    &absolute_content_box,
+                                    image_size);
+                                let image_display_item = ImageDisplayItemClass(box ImageDisplayItem {
+                                    base: BaseDisplayItem::new(image_bounds,
                                                                self.node,
                                                                ContentStackingLevel),
                                     image: image.clone(),
-                                    stretch_size: absolute_content_box.size,
-                                };
-                                accumulator.push(display_list,
-                                                 ImageDisplayItemClass(image_display_item))
+                                    url: image_ref.url().clone(),
+                                    animation: image_ref.get_animation_if_present(),
+                                    stretch_size: image_bounds.size,
+                                });
+
+                                // Clip the image to the content box if it overflows.
+                                if image_bounds.origin.x < absolute_content_box.origin.x ||
+                                        image_bounds.origin.y < absolute_content_box.origin.y ||
+                                        image_bounds.max_x() > absolute_content_box.max_x() ||
+                                        image_bounds.max_y() > absolute_content_box.max_y() {
+                                    let mut clip_display_item = box ClipDisplayItem {
+                                        base: BaseDisplayItem::new(absolute_content_box,
+                                                                   self.node,
+                                                                   ContentStackingLevel),
+                                        children: DisplayList::new(),
+                                    };
+                                    clip_display_item.children.push(image_display_item);
+                                    accumulator.push(display_list,
+                                                     ClipDisplayItemClass(clip_display_item))
+                                } else {
+                                    accumulator.push(display_list, image_display_item)
+                                }
                             }
                             None => {
                                 // No image data at all? Do nothing.
@@ -1216,6 +1600,30 @@ impl Fragment {
             }
         }
 
+        // Add an outline, if applicable. Outlines are painted over the content of the fragment and
+        // are not clipped by its `overflow`.
+        if self.is_primary_fragment() {
+            match self.inline_context {
+                Some(ref inline_context) => {
+                    for style in inline_context.styles.iter().rev() {
+                        self.build_display_list_for_outline_if_applicable(
+                            &**style,
+                            display_list,
+                            &absolute_fragment_bounds);
+                    }
+                }
+                None => {}
+            }
+            match self.specific {
+                ScannedTextFragment(_) => {},
+                _ => {
+                    self.build_display_list_for_outline_if_applicable(&*self.style,
+                                                                      display_list,
+                                                                      &absolute_fragment_bounds);
+                }
+            }
+        }
+
         // If this is an iframe, then send its position and size up to the constellation.
         //
         // FIXME(pcwalton): Doing this during display list construction seems potentially
@@ -1256,7 +1664,8 @@ impl Fragment {
                         block_flow.base.intrinsic_inline_sizes.surround_inline_size);
             }
             ImageFragment(ref mut image_fragment_info) => {
-                let image_inline_size = image_fragment_info.image_inline_size();
+                let (image_inline_size, _) = image_fragment_info.compute_used_size(&*self.style,
+                                                                                   None);
                 result.minimum_inline_size = max(result.minimum_inline_size, image_inline_size);
                 result.preferred_inline_size = max(result.preferred_inline_size,
                                                    image_inline_size);
@@ -1435,9 +1844,20 @@ impl Fragment {
 
                     let metrics = text_fragment_info.run.metrics_for_slice(glyphs, &slice_range);
                     let advance = metrics.advance_width;
+                    let slice_begin = offset + slice_range.begin();
+                    let slice_end = offset + slice_range.end();
+
+                    // If the line may be broken at a soft hyphen after this slice, the hyphen has
+                    // to fit too.
+                    let hyphen_advance = if slice_end < text_fragment_info.range.end() &&
+                            text_fragment_info.run.breaks_with_hyphen_at(slice_end) {
+                        text_fragment_info.run.hyphen_advance
+                    } else {
+                        Au(0)
+                    };
 
                     let should_continue;
-                    if advance <= remaining_inline_size {
+                    if advance + hyphen_advance <= remaining_inline_size {
                         should_continue = true;
 
                         if starts_line && pieces_processed_count == 0 && glyphs.is_whitespace() {
@@ -1451,8 +1871,6 @@ impl Fragment {
                     } else {
                         // The advance is more than the remaining inline-size.
                         should_continue = false;
-                        let slice_begin = offset + slice_range.begin();
-                        let slice_end = offset + slice_range.end();
 
                         if glyphs.is_whitespace() {
                             // If there are still things after the trimmable whitespace, create the
@@ -1526,12 +1944,6 @@ impl Fragment {
 
         self.compute_border_padding_margins(container_inline_size);
 
-        let style_inline_size = self.style().content_inline_size();
-        let style_block_size = self.style().content_block_size();
-        let style_min_inline_size = self.style().min_inline_size();
-        let style_max_inline_size = self.style().max_inline_size();
-        let style_min_block_size = self.style().min_block_size();
-        let style_max_block_size = self.style().max_block_size();
         let noncontent_inline_size = self.border_padding.inline_start_end();
 
         match self.specific {
@@ -1556,46 +1968,12 @@ impl Fragment {
                 self.border_box.size.inline = self.border_box.size.inline + noncontent_inline_size
             }
             ImageFragment(ref mut image_fragment_info) => {
-                // TODO(ksh8281): compute border,margin
-                let inline_size = ImageFragmentInfo::style_length(style_inline_size,
-                                                        image_fragment_info.dom_inline_size,
-                                                        container_inline_size);
-
-                let inline_size = match inline_size {
-                    Auto => {
-                        let intrinsic_width = image_fragment_info.image_inline_size();
-                        let intrinsic_height = image_fragment_info.image_block_size();
-
-                        if intrinsic_height == Au(0) {
-                            intrinsic_width
-                        } else {
-                            let ratio = intrinsic_width.to_f32().unwrap() /
-                                        intrinsic_height.to_f32().unwrap();
-
-                            let specified_height = ImageFragmentInfo::style_length(style_block_size,
-                                                            image_fragment_info.dom_block_size,
-                                                            Au(0));
-                            let specified_height = match specified_height {
-                                Auto => intrinsic_height,
-                                Specified(h) => h,
-                            };
-                            let specified_height = ImageFragmentInfo::clamp_size(specified_height,
-                                                                                 style_min_block_size,
-                                                                                 style_max_block_size,
-                                                                                 Au(0));
-                            Au::new((specified_height.to_f32().unwrap() * ratio) as i32)
-                        }
-                    },
-                    Specified(w) => w,
-                };
-
-                let inline_size = ImageFragmentInfo::clamp_size(inline_size,
-                                                                style_min_inline_size,
-                                                                style_max_inline_size,
-                                                                container_inline_size);
-
+                let (inline_size, block_size) =
+                    image_fragment_info.compute_used_size(&*self.style,
+                                                          Some(container_inline_size));
                 self.border_box.size.inline = inline_size + noncontent_inline_size;
                 image_fragment_info.computed_inline_size = Some(inline_size);
+                image_fragment_info.computed_block_size = Some(block_size);
             }
             _ => fail!("this case should have been handled above"),
         }
@@ -1603,8 +1981,6 @@ impl Fragment {
 
     /// Assign block-size for this fragment if it is replaced content. The inline-size must have
     /// been assigned first.
-    ///
-    /// Ideally, this should follow CSS 2.1 § 10.6.2.
     pub fn assign_replaced_block_size_if_necessary(&mut self) {
         match self.specific {
             GenericFragment | IframeFragment(_) | TableFragment | TableCellFragment |
@@ -1617,37 +1993,13 @@ impl Fragment {
             InlineAbsoluteHypotheticalFragment(_) => {}
         }
 
-        let style_block_size = self.style().content_block_size();
-        let style_min_block_size = self.style().min_block_size();
-        let style_max_block_size = self.style().max_block_size();
         let noncontent_block_size = self.border_padding.block_start_end();
 
         match self.specific {
-            ImageFragment(ref mut image_fragment_info) => {
-                // TODO(ksh8281): compute border,margin,padding
-                let inline_size = image_fragment_info.computed_inline_size();
-                // FIXME(ksh8281): we shouldn't assign block-size this way
-                // we don't know about size of parent's block-size
-                let block_size = ImageFragmentInfo::style_length(style_block_size,
-                                                        image_fragment_info.dom_block_size,
-                                                        Au(0));
-
-                let block_size = match block_size {
-                    Auto => {
-                        let scale = image_fragment_info.image_inline_size().to_f32().unwrap()
-                            / inline_size.to_f32().unwrap();
-                        Au::new((image_fragment_info.image_block_size().to_f32().unwrap() / scale) as i32)
-                    },
-                    Specified(h) => {
-                        h
-                    }
-                };
-
-                let block_size = ImageFragmentInfo::clamp_size(block_size, style_min_block_size,
-                                                               style_max_block_size,
-                                                               Au(0));
-
-                image_fragment_info.computed_block_size = Some(block_size);
+            ImageFragment(ref image_fragment_info) => {
+                // The block-size was computed along with the inline-size, since the two are
+                // interdependent for replaced elements. See CSS 2.1 § 10.4.
+                let block_size = image_fragment_info.computed_block_size();
                 self.border_box.size.block = block_size + noncontent_block_size
             }
             ScannedTextFragment(_) => {
@@ -1687,12 +2039,16 @@ impl Fragment {
                 InlineMetrics::from_font_metrics(&text_fragment.run.font_metrics, line_height)
             }
             InlineBlockFragment(ref info) => {
-                // See CSS 2.1 § 10.8.1.
+                // The baseline of an inline-block is the baseline of its last line box, or its
+                // block-end edge if it has none. See CSS 2.1 § 10.8.1.
                 let block_flow = info.flow_ref.get().as_immutable_block();
-                let font_style = text::computed_style_to_font_style(&*self.style);
-                let font_metrics = text::font_metrics_for_style(layout_context.font_context(),
-                                                                &font_style);
-                InlineMetrics::from_block_height(&font_metrics, block_flow.base.position.size.block)
+                let block_size = block_flow.base.position.size.block;
+                let ascent = block_flow.last_line_baseline().unwrap_or(block_size);
+                InlineMetrics {
+                    block_size_above_baseline: ascent,
+                    depth_below_baseline: block_size - ascent,
+                    ascent: ascent,
+                }
             }
             InlineAbsoluteHypotheticalFragment(ref info) => {
                 // See CSS 2.1 § 10.8.1.
@@ -1716,10 +2072,15 @@ impl Fragment {
     pub fn can_merge_with_fragment(&self, other: &Fragment) -> bool {
         match (&self.specific, &other.specific) {
             (&UnscannedTextFragment(_), &UnscannedTextFragment(_)) => {
+                // Quotes get text runs of their own, so that their text can be replaced without
+                // scanning the text around them again.
+                //
                 // FIXME: Should probably use a whitelist of styles that can safely differ (#3165)
-                self.font_style() == other.font_style() &&
-                    self.text_decoration() == other.text_decoration() &&
-                    self.white_space() == other.white_space()
+                self.quote.is_none() && other.quote.is_none() &&
+                    self.font_style() == other.font_style() &&
+                    self.text_decoration_line() == other.text_decoration_line() &&
+                    self.white_space() == other.white_space() &&
+                    self.hyphens() == other.hyphens()
             }
             _ => false,
         }
@@ -1791,6 +2152,23 @@ impl Fragment {
     }
 }
 
+/// Returns the pieces of the line from `start` to `end` that are left after cutting out `gaps`,
+/// which are sorted and don't overlap. This is how underlines skip the ink of descenders.
+fn segments_between_gaps(start: Au, end: Au, gaps: &[(Au, Au)]) -> Vec<(Au, Au)> {
+    let mut segments = vec!();
+    let mut segment_start = start;
+    for &(gap_start, gap_end) in gaps.iter() {
+        if gap_start > segment_start && segment_start < end {
+            segments.push((segment_start, Au::min(gap_start, end)))
+        }
+        segment_start = Au::max(segment_start, gap_end);
+    }
+    if segment_start < end {
+        segments.push((segment_start, end))
+    }
+    segments
+}
+
 impl fmt::Show for Fragment {
     /// Outputs a debugging string describing this fragment.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -1823,19 +2201,21 @@ pub struct ChildDisplayListAccumulator {
 }
 
 impl ChildDisplayListAccumulator {
-    /// Creates a `ChildDisplayListAccumulator` from the `overflow` property in the given style.
+    /// Creates a `ChildDisplayListAccumulator` from the `overflow-x` and `overflow-y` properties
+    /// in the given style.
     fn new(style: &ComputedValues, bounds: Rect<Au>, node: OpaqueNode,
             level: StackingLevel, may_need_clip: bool)
            -> ChildDisplayListAccumulator {
+        let box_style = style.get_box();
         ChildDisplayListAccumulator {
-            clip_display_item: match (may_need_clip, style.get_box().overflow) {
-                (true, overflow::hidden) | (true, overflow::auto) | (true, overflow::scroll) => {
+            clip_display_item: match (may_need_clip, box_style.overflow_x, box_style.overflow_y) {
+                (true, overflow_x::visible, overflow_y::visible) | (false, _, _) => None,
+                (true, _, _) => {
                     Some(box ClipDisplayItem {
                         base: BaseDisplayItem::new(bounds, node, level),
                         children: DisplayList::new(),
                     })
                 },
-                (false, _) | (_, overflow::visible) => None,
             }
         }
     }
@@ -1873,3 +2253,34 @@ impl ChildDisplayListAccumulator {
         flow::mut_base(parent).display_list = display_list
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::segments_between_gaps;
+
+    use servo_util::geometry::Au;
+
+    fn segments(start: i32, end: i32, gaps: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let gaps: Vec<(Au, Au)> = gaps.iter().map(|&(start, end)| (Au(start), Au(end))).collect();
+        segments_between_gaps(Au(start), Au(end), gaps.as_slice()).into_iter().map(|segment| {
+            let (Au(start), Au(end)) = segment;
+            (start, end)
+        }).collect()
+    }
+
+    #[test]
+    fn test_segments_between_gaps() {
+        assert_eq!(segments(0, 100, &[]), vec!((0, 100)));
+        assert_eq!(segments(0, 100, &[(10, 20), (50, 60)]), vec!((0, 10), (20, 50), (60, 100)));
+    }
+
+    #[test]
+    fn test_segments_between_gaps_at_the_ends() {
+        // Gaps that run past the ends of the line leave nothing of it there.
+        assert_eq!(segments(0, 100, &[(-10, 20), (90, 110)]), vec!((20, 90)));
+        assert_eq!(segments(0, 100, &[(-10, 110)]), vec!());
+        // A gap past the end of the line doesn't leave an empty segment.
+        assert_eq!(segments(0, 100, &[(150, 160)]), vec!((0, 100)));
+        assert_eq!(segments(0, 100, &[(50, 120), (130, 140)]), vec!((0, 50)));
+    }
+}
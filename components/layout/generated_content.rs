@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Generated content that depends on the rest of the document.
+//!
+//! The marks inserted by `open-quote` and `close-quote` depend on how deeply the quote is nested,
+//! which is only known once every quote before it in the document has been seen (CSS 2.1 §
+//! 12.3.2). Flow construction is bottom-up and parallel, so it leaves the text of quotes out, and
+//! the inline flows holding them unscanned. Once the flow tree is built, `resolve_quotes()` walks
+//! it in document order to fill the text in. Flows are only built when something about the
+//! document changed, and new flows carry `ResolveGeneratedContent` damage up to the root, so the
+//! walk is repeated on exactly those reflows to catch quotes whose depth changed because quotes
+//! before them were added or removed.
+
+#![deny(unsafe_block)]
+
+use context::LayoutContext;
+use flow::{Flow, ImmutableFlowUtils};
+use flow;
+use fragment::{Fragment, InlineAbsoluteHypotheticalFragment, InlineBlockFragment};
+use fragment::{UnscannedTextFragment, UnscannedTextFragmentInfo};
+use incremental::{BubbleISizes, Reflow, Repaint};
+use text::TextRunScanner;
+
+use servo_util::logical_geometry::LogicalSize;
+use std::cmp::min;
+use style::computed_values::quotes;
+
+/// A quote of generated content.
+#[deriving(Clone, PartialEq, Show)]
+pub struct GeneratedQuote {
+    /// True for `open-quote` and `no-open-quote`, which increase the nesting depth.
+    pub is_open: bool,
+    /// False for `no-open-quote` and `no-close-quote`, which only change the nesting depth.
+    pub is_shown: bool,
+    /// The text that `resolve_quotes()` last filled in.
+    pub text: String,
+}
+
+impl GeneratedQuote {
+    /// Returns the text of this quote at the given nesting depth, and updates the depth for the
+    /// quotes that follow. A close quote with no open quote to match shows nothing and leaves the
+    /// depth alone.
+    pub fn text_at_depth(&self, quotes: &quotes::T, depth: &mut uint) -> String {
+        if !self.is_open {
+            if *depth == 0 {
+                return String::new()
+            }
+            *depth -= 1;
+        }
+
+        let text = if !self.is_shown || quotes.is_empty() {
+            String::new()
+        } else {
+            // Quotes nested deeper than the `quotes` list goes use its last pair.
+            let (ref open, ref close) = quotes[min(*depth, quotes.len() - 1)];
+            if self.is_open {
+                open.clone()
+            } else {
+                close.clone()
+            }
+        };
+
+        if self.is_open {
+            *depth += 1;
+        }
+        text
+    }
+}
+
+/// Returns true if any of the given fragments is a quote.
+pub fn has_unresolved_quotes(fragments: &[Fragment]) -> bool {
+    fragments.iter().any(|fragment| fragment.quote.is_some())
+}
+
+/// Fills in the text of every quote in the flow tree rooted at `root`, and scans the text runs of
+/// the inline flows whose quotes are new or changed.
+///
+/// The depth of a quote depends on every quote before it, so this walks the whole tree, including
+/// flows kept from the last reflow. Only the flows whose quotes came out differently are damaged,
+/// along with their ancestors, so that the layout that follows bubbles their inline sizes up and
+/// lays them out again.
+pub fn resolve_quotes(root: &mut Flow, layout_context: &LayoutContext) {
+    let mut depth = 0;
+    resolve_quotes_in_flow(root, layout_context, &mut depth);
+}
+
+/// Fills in the quotes of `flow` and its descendants, starting at the given nesting depth. Returns
+/// true if any of their text runs were scanned again, in which case `flow` is damaged as well.
+fn resolve_quotes_in_flow(flow: &mut Flow, layout_context: &LayoutContext, depth: &mut uint)
+                          -> bool {
+    let damaged = if flow.is_inline_flow() {
+        resolve_quotes_in_inline_flow(flow, layout_context, depth)
+    } else {
+        let mut damaged = false;
+        for kid in flow::child_iter(flow) {
+            damaged = resolve_quotes_in_flow(kid, layout_context, depth) || damaged
+        }
+        damaged
+    };
+
+    if damaged {
+        flow::mut_base(flow).restyle_damage.insert(Repaint | BubbleISizes | Reflow)
+    }
+    damaged
+}
+
+fn resolve_quotes_in_inline_flow(flow: &mut Flow, layout_context: &LayoutContext, depth: &mut uint)
+                                 -> bool {
+    let (damaged, quotes_changed) = {
+        let inline_flow = flow.as_inline();
+
+        // The kids of an inline flow are its inline-block and absolutely positioned fragments, so
+        // walk the fragments instead to see everything in document order. Lines are broken again
+        // from the unsplit fragments if there are any, so those are the ones to fill in.
+        let mut damaged = false;
+        let mut quotes_changed = false;
+        let fragments = match inline_flow.unsplit_fragments {
+            Some(ref mut fragments) => &mut fragments.fragments,
+            None => &mut inline_flow.fragments.fragments,
+        };
+        let mut index = 0;
+        while index < fragments.len() {
+            match fragments.get_mut(index).specific {
+                InlineBlockFragment(ref mut info) => {
+                    damaged = resolve_quotes_in_flow(info.flow_ref.get_mut(),
+                                                     layout_context,
+                                                     depth) || damaged
+                }
+                InlineAbsoluteHypotheticalFragment(ref mut info) => {
+                    damaged = resolve_quotes_in_flow(info.flow_ref.get_mut(),
+                                                     layout_context,
+                                                     depth) || damaged
+                }
+                _ => {}
+            }
+
+            let quote = match fragments[index].quote {
+                Some(ref quote) => quote.clone(),
+                None => {
+                    index += 1;
+                    continue
+                }
+            };
+
+            // A quote whose text was split into several text runs is a row of fragments, which
+            // share the debug ID of the fragment that the quote was built as.
+            let mut end = index + 1;
+            while end < fragments.len() &&
+                    fragments[end].debug_id() == fragments[index].debug_id() {
+                end += 1
+            }
+
+            let text = quote.text_at_depth(&fragments[index].style.get_inheritedbox().quotes,
+                                           depth);
+            let is_scanned = match fragments[index].specific {
+                UnscannedTextFragment(_) => false,
+                _ => true,
+            };
+            if is_scanned && text == quote.text {
+                index = end;
+                continue
+            }
+
+            // Replace the quote with an unscanned fragment holding its new text.
+            let mut fragment = {
+                let old_fragment = &fragments[index];
+                let size = LogicalSize::zero(old_fragment.style.writing_mode);
+                old_fragment.transform(size,
+                                       UnscannedTextFragment(
+                                           UnscannedTextFragmentInfo::from_text(text.clone())))
+            };
+            fragment.new_line_pos = vec!();
+            fragment.quote = Some(GeneratedQuote {
+                text: text,
+                ..quote
+            });
+            for _ in range(index + 1, end) {
+                fragments.remove(index + 1);
+            }
+            *fragments.get_mut(index) = fragment;
+            quotes_changed = true;
+            index += 1
+        }
+        (damaged, quotes_changed)
+    };
+
+    if !quotes_changed {
+        return damaged
+    }
+
+    // The scanner leaves the fragments that are already scanned alone. Lines are broken afresh
+    // from what it produces.
+    {
+        let inline_flow = flow.as_inline();
+        match inline_flow.unsplit_fragments.take() {
+            Some(unsplit_fragments) => inline_flow.fragments = unsplit_fragments,
+            None => {}
+        }
+    }
+    TextRunScanner::new().scan_for_runs(layout_context.font_context(), flow);
+    true
+}
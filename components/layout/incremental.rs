@@ -2,77 +2,64 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use style::ComputedValues;
+use style::{ComputedValues, RepaintDamageClass, RepositionDamageClass, ReflowDamageClass};
+use style::RebuildFlowDamageClass;
 
 bitflags! {
     #[doc = "Individual layout actions that may be necessary after restyling."]
     flags RestyleDamage: int {
         #[doc = "Repaint the node itself."]
-        #[doc = "Currently unused; need to decide how this propagates."]
+        #[doc = "Display lists are rebuilt on every reflow, so this needs no layout work."]
         static Repaint = 0x01,
 
         #[doc = "Recompute intrinsic inline_sizes (minimum and preferred)."]
-        #[doc = "Propagates down the flow tree because the computation is"]
-        #[doc = "bottom-up."]
+        #[doc = "Propagates up the flow tree because the intrinsic inline_sizes"]
+        #[doc = "of a flow depend on those of its children."]
         static BubbleISizes = 0x02,
 
         #[doc = "Recompute actual inline_sizes and block_sizes."]
-        #[doc = "Propagates up the flow tree because the computation is"]
-        #[doc = "top-down."]
-        static Reflow = 0x04
+        #[doc = "Propagates up the flow tree because the block_size of a flow"]
+        #[doc = "depends on those of its children. Children whose assigned"]
+        #[doc = "inline_size changes get it during the assign-inline-sizes"]
+        #[doc = "traversal."]
+        static Reflow = 0x04,
+
+        #[doc = "Recompute the overflow of the flow, which moved relative to"]
+        #[doc = "its normal position without changing size."]
+        #[doc = "Propagates up the flow tree because the overflow of a flow"]
+        #[doc = "includes that of its children."]
+        static Reposition = 0x08,
+
+        #[doc = "Throw away the flows of the node and construct them again."]
+        static RebuildFlow = 0x10,
+
+        #[doc = "Fill in the text of the quotes of generated content again."]
+        #[doc = "Propagates up the flow tree because the depth of a quote"]
+        #[doc = "depends on every quote before it, so the whole tree is walked"]
+        #[doc = "from the root. Flows get this when they are built."]
+        static ResolveGeneratedContent = 0x20
     }
 }
 
 impl RestyleDamage {
     /// Elements of self which should also get set on any ancestor flow.
     pub fn propagate_up(self) -> RestyleDamage {
-        self & Reflow
-    }
-
-    /// Elements of self which should also get set on any child flows.
-    pub fn propagate_down(self) -> RestyleDamage {
-        self & BubbleISizes
+        self & (BubbleISizes | Reflow | Reposition | ResolveGeneratedContent)
     }
 }
 
-// NB: We need the braces inside the RHS due to Rust #8012.  This particular
-// version of this macro might be safe anyway, but we want to avoid silent
-// breakage on modifications.
-macro_rules! add_if_not_equal(
-    ($old:ident, $new:ident, $damage:ident,
-     [ $($effect:ident),* ], [ $($style_struct_getter:ident.$name:ident),* ]) => ({
-        if $( ($old.$style_struct_getter().$name != $new.$style_struct_getter().$name) )||* {
-            $damage.insert($($effect)|*);
-        }
-    })
-)
-
+/// Works out what has to be done about a node whose style changed from `old` to `new`. The
+/// property definitions in the style crate say which damage class each longhand belongs to.
 pub fn compute_damage(old: &ComputedValues, new: &ComputedValues) -> RestyleDamage {
-    let mut damage = RestyleDamage::empty();
-
-    // This checks every CSS property, as enumerated in
-    // impl<'self> CssComputedStyle<'self>
-    // in src/support/netsurfcss/rust-netsurfcss/netsurfcss.rc.
-
-    // FIXME: We can short-circuit more of this.
-
-    add_if_not_equal!(old, new, damage, [ Repaint ],
-        [ get_color.color, get_background.background_color,
-          get_border.border_top_color, get_border.border_right_color,
-          get_border.border_bottom_color, get_border.border_left_color ]);
-
-    add_if_not_equal!(old, new, damage, [ Repaint, BubbleISizes, Reflow ],
-        [ get_border.border_top_width, get_border.border_right_width,
-          get_border.border_bottom_width, get_border.border_left_width,
-          get_margin.margin_top, get_margin.margin_right,
-          get_margin.margin_bottom, get_margin.margin_left,
-          get_padding.padding_top, get_padding.padding_right,
-          get_padding.padding_bottom, get_padding.padding_left,
-          get_box.position, get_box.width, get_box.height, get_box.float, get_box.display,
-          get_font.font_family, get_font.font_size, get_font.font_style, get_font.font_weight,
-          get_inheritedtext.text_align, get_text.text_decoration, get_inheritedbox.line_height ]);
-
-    // FIXME: test somehow that we checked every CSS property
-
-    damage
+    if old.differs_in(new, RebuildFlowDamageClass) {
+        RestyleDamage::all()
+    } else if old.differs_in(new, ReflowDamageClass) {
+        Repaint | BubbleISizes | Reflow
+    } else if old.differs_in(new, RepositionDamageClass) {
+        Repaint | Reposition
+    } else if old.differs_in(new, RepaintDamageClass) {
+        Repaint
+    } else {
+        RestyleDamage::empty()
+    }
 }
@@ -13,21 +13,24 @@ use fragment::{Fragment, InlineBlockFragment, ScannedTextFragment, ScannedTextFr
 use fragment::{SplitInfo};
 use layout_debug;
 use model::IntrinsicISizes;
+use pagination::PageGrid;
 use text;
 use wrapper::ThreadSafeLayoutNode;
 
 use collections::{Deque, RingBuf};
 use geom::Rect;
-use gfx::display_list::ContentLevel;
+use gfx::display_list::{ContentLevel, OpaqueNode};
 use gfx::font::FontMetrics;
 use gfx::font_context::FontContext;
 use geom::Size2D;
 use gfx::text::glyph::CharIndex;
+use libc::uintptr_t;
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::{LogicalRect, LogicalSize};
 use servo_util::range;
 use servo_util::range::{EachIndex, Range, RangeIndex, IntRangeIndex};
 use std::cmp::max;
+use std::collections::hashmap::HashMap;
 use std::fmt;
 use std::mem;
 use std::num;
@@ -144,7 +147,10 @@ pub struct Line {
     /// ::: green zone
     /// FFF float
     /// ~~~
-    pub green_zone: LogicalSize<Au>
+    pub green_zone: LogicalSize<Au>,
+    /// The distance from the block-start edge of the line to the baseline of its root inline
+    /// box. This is set during block-size assignment.
+    pub baseline_offset: Au,
 }
 
 int_range_index! {
@@ -275,6 +281,8 @@ struct LineBreaker {
     pub pending_line: Line,
     pub lines: Vec<Line>,
     pub cur_b: Au,  // Current position on the block direction
+    /// The metrics of the inline boxes that the fragments are nested inside.
+    pub inline_box_metrics: InlineBoxMetricsCache,
 }
 
 impl LineBreaker {
@@ -285,11 +293,13 @@ impl LineBreaker {
             pending_line: Line {
                 range: Range::empty(),
                 bounds: LogicalRect::zero(float_ctx.writing_mode),
-                green_zone: LogicalSize::zero(float_ctx.writing_mode)
+                green_zone: LogicalSize::zero(float_ctx.writing_mode),
+                baseline_offset: Au(0),
             },
             floats: float_ctx,
             lines: Vec::new(),
-            cur_b: Au::new(0)
+            cur_b: Au::new(0),
+            inline_box_metrics: InlineBoxMetricsCache::new(),
         }
     }
 
@@ -379,15 +389,23 @@ impl LineBreaker {
         self.reset_line();
     }
 
-    // FIXME(eatkinson): this assumes that the tallest fragment in the line determines the line block-size
-    // This might not be the case with some weird text fonts.
-    fn new_block_size_for_line(&self, new_fragment: &Fragment, layout_context: &LayoutContext) -> Au {
-        let fragment_block_size = new_fragment.content_block_size(layout_context);
-        if fragment_block_size > self.pending_line.bounds.size.block {
-            fragment_block_size
-        } else {
-            self.pending_line.bounds.size.block
-        }
+    /// Estimates the block-size of the pending line with the given fragment added to it, for the
+    /// purposes of float avoidance. The exact block-size depends on how every fragment on the line
+    /// is vertically aligned, so it is only known once the line is finished; see
+    /// `InlineFlow::assign_block_size()`.
+    fn new_block_size_for_line(&mut self,
+                               new_fragment: &Fragment,
+                               flow: &InlineFlow,
+                               layout_context: &LayoutContext)
+                               -> Au {
+        let strut_block_size = flow.minimum_block_size_above_baseline +
+            flow.minimum_depth_below_baseline;
+        let placement = InlineFlow::vertical_placement(new_fragment,
+                                                       &flow.root_vertical_align_metrics,
+                                                       &mut self.inline_box_metrics,
+                                                       layout_context);
+        let fragment_block_size = placement.block_size_above + placement.depth_below;
+        max(self.pending_line.bounds.size.block, max(strut_block_size, fragment_block_size))
     }
 
     /// Computes the position of a line that has only the provided fragment. Returns the bounding
@@ -502,7 +520,8 @@ impl LineBreaker {
 
             // TODO(bjz): Remove fragment splitting
             let split_fragment = |split: SplitInfo| {
-                let info = ScannedTextFragmentInfo::new(run.clone(), split.range);
+                let mut info = ScannedTextFragmentInfo::new(run.clone(), split.range);
+                info.hyphenated = split.hyphenated;
                 let specific = ScannedTextFragment(info);
                 let size = LogicalSize::new(
                     writing_mode, split.inline_size, in_fragment.border_box.size.block);
@@ -556,7 +575,7 @@ impl LineBreaker {
         // `green_zone.block-size < self.pending_line.bounds.size.block-size`, then we committed a line
         // that overlaps with floats.
 
-        let new_block_size = self.new_block_size_for_line(&in_fragment, layout_context);
+        let new_block_size = self.new_block_size_for_line(&in_fragment, flow, layout_context);
         if new_block_size > green_zone.block {
             // Uh-oh. Float collision imminent. Enter the float collision avoider
             return self.avoid_floats(in_fragment, flow, new_block_size, line_is_empty)
@@ -587,7 +606,8 @@ impl LineBreaker {
         match split.map(|(inline_start, inline_end, run)| {
             // TODO(bjz): Remove fragment splitting
             let split_fragment = |split: SplitInfo| {
-                let info = ScannedTextFragmentInfo::new(run.clone(), split.range);
+                let mut info = ScannedTextFragmentInfo::new(run.clone(), split.range);
+                info.hyphenated = split.hyphenated;
                 let specific = ScannedTextFragment(info);
                 let size = LogicalSize::new(
                     self.floats.writing_mode, split.inline_size, in_fragment.border_box.size.block);
@@ -654,7 +674,7 @@ impl LineBreaker {
 }
 
 /// Represents a list of inline fragments, including element ranges.
-#[deriving(Encodable)]
+#[deriving(Clone, Encodable)]
 pub struct InlineFragments {
     /// The fragments themselves.
     pub fragments: Vec<Fragment>,
@@ -758,6 +778,14 @@ pub struct InlineFlow {
     /// The minimum depth below the baseline for each line, as specified by the line block-size and
     /// font style.
     pub minimum_depth_below_baseline: Au,
+
+    /// The font metrics of the root inline box, against which the `vertical-align` values of the
+    /// outermost inline boxes are resolved.
+    pub root_vertical_align_metrics: VerticalAlignMetrics,
+
+    /// The fragments as they were before line breaking split them up, kept so that lines can be
+    /// broken afresh if this flow is laid out again in a later reflow.
+    pub unsplit_fragments: Option<InlineFragments>,
 }
 
 impl InlineFlow {
@@ -768,7 +796,19 @@ impl InlineFlow {
             lines: Vec::new(),
             minimum_block_size_above_baseline: Au(0),
             minimum_depth_below_baseline: Au(0),
+            root_vertical_align_metrics: VerticalAlignMetrics::zero(),
+            unsplit_fragments: None,
+        }
+    }
+
+    /// Puts back the fragments that this flow had before line breaking split them. The first time
+    /// this is called, it just remembers the current fragments.
+    fn restore_unsplit_fragments(&mut self) {
+        if self.unsplit_fragments.is_none() {
+            self.unsplit_fragments = Some(self.fragments.clone());
+            return
         }
+        self.fragments = self.unsplit_fragments.as_ref().unwrap().clone();
     }
 
     pub fn build_display_list_inline(&mut self, layout_context: &LayoutContext) {
@@ -805,73 +845,169 @@ impl InlineFlow {
         // For now, don't traverse the subtree rooted here.
     }
 
-    /// Returns the distance from the baseline for the logical block-start inline-start corner of this fragment,
-    /// taking into account the value of the CSS `vertical-align` property. Negative values mean
-    /// "toward the logical block-start" and positive values mean "toward the logical block-end".
+    /// Returns the distance from the baseline of an inline box's parent to the baseline of the
+    /// inline box itself, per its `vertical-align` value. Positive values are toward block-end.
     ///
-    /// The extra boolean is set if and only if `biggest_block-start` and/or `biggest_block-end` were updated.
-    /// That is, if the box has a `block-start` or `block-end` value, true is returned.
-    fn distance_from_baseline(fragment: &Fragment,
-                              ascent: Au,
-                              parent_text_block_start: Au,
-                              parent_text_block_end: Au,
-                              block_size_above_baseline: &mut Au,
-                              depth_below_baseline: &mut Au,
-                              largest_block_size_for_top_fragments: &mut Au,
-                              largest_block_size_for_bottom_fragments: &mut Au,
-                              layout_context: &LayoutContext)
-                              -> (Au, bool) {
-        match fragment.vertical_align() {
-            vertical_align::baseline => (-ascent, false),
+    /// `block_size_above_baseline` and `depth_below_baseline` describe the layout box of the inline
+    /// box, and `line_height` is its `line-height`. `vertical-align: top` and `bottom` are not
+    /// relative to the parent and are handled by the caller.
+    fn baseline_shift(vertical_align: vertical_align::T,
+                      parent_metrics: &VerticalAlignMetrics,
+                      block_size_above_baseline: Au,
+                      depth_below_baseline: Au,
+                      line_height: Au)
+                      -> Au {
+        match vertical_align {
+            vertical_align::baseline | vertical_align::top | vertical_align::bottom => Au(0),
             vertical_align::middle => {
-                // TODO: x-block-size value should be used from font info.
-                // TODO: The code below passes our current reftests but
-                // doesn't work in all situations. Add vertical align reftests and fix this.
-                (-ascent, false)
-            },
-            vertical_align::sub => {
-                let sub_offset = (parent_text_block_start + parent_text_block_end)
-                                    .scale_by(FONT_SUBSCRIPT_OFFSET_RATIO);
-                (sub_offset - ascent, false)
-            },
+                // Align the midpoint of the box with the baseline of the parent box plus half the
+                // x-height of the parent.
+                (block_size_above_baseline - depth_below_baseline - parent_metrics.x_height)
+                    .scale_by(0.5)
+            }
+            vertical_align::sub => parent_metrics.em_size.scale_by(FONT_SUBSCRIPT_OFFSET_RATIO),
             vertical_align::super_ => {
-                let super_offset = (parent_text_block_start + parent_text_block_end)
-                                    .scale_by(FONT_SUPERSCRIPT_OFFSET_RATIO);
-                (-super_offset - ascent, false)
-            },
-            vertical_align::text_top => {
-                let fragment_block_size = *block_size_above_baseline + *depth_below_baseline;
-                let prev_depth_below_baseline = *depth_below_baseline;
-                *block_size_above_baseline = parent_text_block_start;
-                *depth_below_baseline = fragment_block_size - *block_size_above_baseline;
-                (*depth_below_baseline - prev_depth_below_baseline - ascent, false)
-            },
-            vertical_align::text_bottom => {
-                let fragment_block_size = *block_size_above_baseline + *depth_below_baseline;
-                let prev_depth_below_baseline = *depth_below_baseline;
-                *depth_below_baseline = parent_text_block_end;
-                *block_size_above_baseline = fragment_block_size - *depth_below_baseline;
-                (*depth_below_baseline - prev_depth_below_baseline - ascent, false)
-            },
+                -parent_metrics.em_size.scale_by(FONT_SUPERSCRIPT_OFFSET_RATIO)
+            }
+            vertical_align::text_top => block_size_above_baseline - parent_metrics.ascent,
+            vertical_align::text_bottom => parent_metrics.descent - depth_below_baseline,
+            vertical_align::Length(length) => -length,
+            vertical_align::Percentage(percentage) => -line_height.scale_by(percentage),
+        }
+    }
+
+    /// Vertically aligns a fragment within its line per CSS 2.1 § 10.8.1.
+    ///
+    /// The `vertical-align` values of the fragment's inline ancestors are applied from the
+    /// outermost inward, and the layout boxes of those ancestors (whose block-sizes are given by
+    /// their `line-height`) are included in the returned extents, since they contribute to the
+    /// block-size of the line box too. Text fragments are always on the baseline of their
+    /// innermost inline ancestor.
+    ///
+    /// FIXME: An inline box aligned to the top or bottom of the line box should be aligned as a
+    /// whole. Here, each of its fragments is aligned separately, relative to the layout box of the
+    /// inline box itself, so descendants that stick out of that box are not taken into account.
+    fn vertical_placement(fragment: &Fragment,
+                          root_metrics: &VerticalAlignMetrics,
+                          inline_box_metrics: &mut InlineBoxMetricsCache,
+                          layout_context: &LayoutContext)
+                          -> VerticalPlacement {
+        // The alignment point that the fragment is placed relative to so far.
+        let mut alignment = AlignedToBaseline;
+        // For inline boxes aligned to the top or bottom of the line box, the distance from the
+        // block-start edge of the line box to their baseline and from their baseline to the
+        // block-end edge of the line box, respectively.
+        let mut alignment_box_offset = Au(0);
+        // The distance from the alignment point to the baseline of the current parent box.
+        let mut shift = Au(0);
+        let mut block_size_above = Au(0);
+        let mut depth_below = Au(0);
+        let mut parent_metrics = root_metrics.clone();
+
+        match fragment.inline_context {
+            None => {}
+            Some(ref inline_context) => {
+                for style in inline_context.styles.iter().rev() {
+                    let (font_metrics, line_height) = inline_box_metrics.get(style,
+                                                                             layout_context);
+                    let inline_metrics = InlineMetrics::from_font_metrics(&font_metrics,
+                                                                          line_height);
+                    let vertical_align = style.get_box().vertical_align;
+
+                    match vertical_align {
+                        vertical_align::top => {
+                            alignment = AlignedToLineTop;
+                            alignment_box_offset = inline_metrics.block_size_above_baseline;
+                            shift = Au(0);
+                            block_size_above = Au(0);
+                            depth_below = Au(0);
+                        }
+                        vertical_align::bottom => {
+                            alignment = AlignedToLineBottom;
+                            alignment_box_offset = inline_metrics.depth_below_baseline;
+                            shift = Au(0);
+                            block_size_above = Au(0);
+                            depth_below = Au(0);
+                        }
+                        _ => {
+                            shift = shift +
+                                InlineFlow::baseline_shift(vertical_align,
+                                                           &parent_metrics,
+                                                           inline_metrics.block_size_above_baseline,
+                                                           inline_metrics.depth_below_baseline,
+                                                           line_height)
+                        }
+                    }
+
+                    block_size_above = max(block_size_above,
+                                           inline_metrics.block_size_above_baseline - shift);
+                    depth_below = max(depth_below, inline_metrics.depth_below_baseline + shift);
+                    parent_metrics = VerticalAlignMetrics::from_font_metrics(&font_metrics);
+                }
+            }
+        }
+
+        let InlineMetrics {
+            block_size_above_baseline,
+            depth_below_baseline,
+            ascent
+        } = fragment.inline_metrics(layout_context);
+        let vertical_align = match fragment.specific {
+            ScannedTextFragment(_) => vertical_align::baseline,
+            _ => fragment.vertical_align(),
+        };
+        match vertical_align {
             vertical_align::top => {
-                *largest_block_size_for_top_fragments =
-                    Au::max(*largest_block_size_for_top_fragments,
-                            *block_size_above_baseline + *depth_below_baseline);
-                let offset_top = *block_size_above_baseline - ascent;
-                (offset_top, true)
-            },
+                alignment = AlignedToLineTop;
+                alignment_box_offset = block_size_above_baseline;
+                shift = Au(0);
+                block_size_above = Au(0);
+                depth_below = Au(0);
+            }
             vertical_align::bottom => {
-                *largest_block_size_for_bottom_fragments =
-                    Au::max(*largest_block_size_for_bottom_fragments,
-                            *block_size_above_baseline + *depth_below_baseline);
-                let offset_bottom = -(*depth_below_baseline + ascent);
-                (offset_bottom, true)
-            },
-            vertical_align::Length(length) => (-(length + ascent), false),
-            vertical_align::Percentage(p) => {
+                alignment = AlignedToLineBottom;
+                alignment_box_offset = depth_below_baseline;
+                shift = Au(0);
+                block_size_above = Au(0);
+                depth_below = Au(0);
+            }
+            _ => {
                 let line_height = fragment.calculate_line_height(layout_context);
-                let percent_offset = line_height.scale_by(p);
-                (-(percent_offset + ascent), false)
+                shift = shift + InlineFlow::baseline_shift(vertical_align,
+                                                           &parent_metrics,
+                                                           block_size_above_baseline,
+                                                           depth_below_baseline,
+                                                           line_height)
+            }
+        }
+        block_size_above = max(block_size_above, block_size_above_baseline - shift);
+        depth_below = max(depth_below, depth_below_baseline + shift);
+
+        // Express everything relative to the alignment point.
+        match alignment {
+            AlignedToBaseline => {
+                VerticalPlacement {
+                    alignment: AlignedToBaseline,
+                    border_box_offset: shift - ascent,
+                    block_size_above: block_size_above,
+                    depth_below: depth_below,
+                }
+            }
+            AlignedToLineTop => {
+                VerticalPlacement {
+                    alignment: AlignedToLineTop,
+                    border_box_offset: alignment_box_offset + shift - ascent,
+                    block_size_above: Au(0),
+                    depth_below: max(alignment_box_offset, block_size_above) + depth_below,
+                }
+            }
+            AlignedToLineBottom => {
+                VerticalPlacement {
+                    alignment: AlignedToLineBottom,
+                    border_box_offset: shift - alignment_box_offset - ascent,
+                    block_size_above: block_size_above + max(alignment_box_offset, depth_below),
+                    depth_below: Au(0),
+                }
             }
         }
     }
@@ -879,7 +1015,8 @@ impl InlineFlow {
     /// Sets fragment positions in the inline direction based on alignment for one line.
     fn set_inline_fragment_positions(fragments: &mut InlineFragments,
                                      line: &Line,
-                                     line_align: text_align::T) {
+                                     line_align: text_align::T,
+                                     base_level: u8) {
         // Figure out how much inline-size we have.
         let slack_inline_size = Au::max(Au(0), line.green_zone.inline - line.bounds.size.inline);
 
@@ -895,8 +1032,17 @@ impl InlineFlow {
             text_align::right => slack_inline_size,
         };
 
-        for i in each_fragment_index(&line.range) {
-            let fragment = fragments.get_mut(i.to_uint());
+        // Place the fragments in visual order. Inline offsets run from the inline-start edge, which
+        // is the right edge of a right-to-left line, so such lines are placed from the right.
+        let first = line.range.begin().fragment_index.to_uint();
+        let levels: Vec<u8> = resolve_bidi_levels(fragments, line, base_level);
+        let mut order = visual_order(levels.as_slice());
+        if base_level % 2 == 1 {
+            order.reverse()
+        }
+
+        for &i in order.iter() {
+            let fragment = fragments.get_mut(first + i);
             let size = fragment.border_box.size;
             fragment.border_box = LogicalRect::new(fragment.style.writing_mode,
                                                    offset,
@@ -909,25 +1055,26 @@ impl InlineFlow {
     }
 
     /// Sets final fragment positions in the block direction for one line. Assumes that
-    /// the fragment positions were initially set to the distance from the baseline first.
+    /// the fragment positions were initially set to the distance from their alignment point.
     fn set_block_fragment_positions(fragments: &mut InlineFragments,
-                                     line: &Line,
-                                     line_distance_from_flow_block_start: Au,
-                                     baseline_distance_from_block_start: Au,
-                                     largest_depth_below_baseline: Au) {
-        for fragment_i in each_fragment_index(&line.range) {
+                                    line: &Line,
+                                    alignments: &[LineAlignment],
+                                    line_distance_from_flow_block_start: Au,
+                                    baseline_distance_from_block_start: Au,
+                                    largest_depth_below_baseline: Au) {
+        for (fragment_i, alignment) in each_fragment_index(&line.range).zip(alignments.iter()) {
             let fragment = fragments.get_mut(fragment_i.to_uint());
-            match fragment.vertical_align() {
-                vertical_align::top => {
+            match *alignment {
+                AlignedToLineTop => {
                     fragment.border_box.start.b = fragment.border_box.start.b +
                         line_distance_from_flow_block_start
                 }
-                vertical_align::bottom => {
+                AlignedToLineBottom => {
                     fragment.border_box.start.b = fragment.border_box.start.b +
                         line_distance_from_flow_block_start + baseline_distance_from_block_start +
                         largest_depth_below_baseline
                 }
-                _ => {
+                AlignedToBaseline => {
                     fragment.border_box.start.b = fragment.border_box.start.b +
                         line_distance_from_flow_block_start + baseline_distance_from_block_start
                 }
@@ -936,8 +1083,9 @@ impl InlineFlow {
         }
     }
 
-    /// Computes the minimum ascent and descent for each line. This is done during flow
-    /// construction.
+    /// Computes the minimum ascent and descent for each line, which are given by the strut of the
+    /// root inline box. This is done during flow construction. The inline boxes inside the block
+    /// only affect the lines that they appear on; see `InlineFlow::vertical_placement()`.
     ///
     /// `style` is the style of the block.
     pub fn compute_minimum_ascent_and_descent(&self,
@@ -947,30 +1095,81 @@ impl InlineFlow {
         let font_metrics = text::font_metrics_for_style(font_context, &font_style);
         let line_height = text::line_height_from_style(style, &font_metrics);
         let inline_metrics = InlineMetrics::from_font_metrics(&font_metrics, line_height);
+        (inline_metrics.block_size_above_baseline, inline_metrics.depth_below_baseline)
+    }
 
-        let mut block_size_above_baseline = inline_metrics.block_size_above_baseline;
-        let mut depth_below_baseline = inline_metrics.depth_below_baseline;
-
-        // CSS section 10.8
-        // line-height of any inline element specifies the minimal height of line
-        // boxes within the element.
-        for frag in self.fragments.fragments.iter() {
-            match frag.inline_context {
-                Some(ref inline_context) => {
-                    for style in inline_context.styles.iter() {
-                        let font_style = text::computed_style_to_font_style(&**style);
-                        let font_metrics = text::font_metrics_for_style(font_context, &font_style);
-                        let line_height = text::line_height_from_style(&**style, &font_metrics);
-                        let inline_metrics = InlineMetrics::from_font_metrics(&font_metrics, line_height);
-                        block_size_above_baseline = Au::max(block_size_above_baseline, inline_metrics.block_size_above_baseline);
-                        depth_below_baseline = Au::max(depth_below_baseline, inline_metrics.depth_below_baseline);
-                    }
-                }
-                None => {}
+    /// Moves the line boxes of this flow down so that none of them straddles a page break.
+    fn move_lines_past_page_breaks(&mut self, page_grid: &PageGrid) {
+        if self.fragments.fragments.is_empty() {
+            return
+        }
+
+        // `orphans` and `widows` are inherited, so every fragment has the containing block's.
+        let (orphans, widows) = {
+            let inherited_box_style = self.fragments.fragments[0].style().get_inheritedbox();
+            (inherited_box_style.orphans as uint, inherited_box_style.widows as uint)
+        };
+        let line_tops: Vec<Au> = self.lines.iter().map(|line| line.bounds.start.b).collect();
+        let line_bottoms: Vec<Au> = self.lines.iter().map(|line| {
+            line.bounds.start.b + line.bounds.size.block
+        }).collect();
+        let offsets = page_grid.line_offsets(line_tops.as_slice(),
+                                             line_bottoms.as_slice(),
+                                             orphans,
+                                             widows);
+
+        for (line, &offset) in self.lines.iter_mut().zip(offsets.iter()) {
+            line.bounds.start.b = line.bounds.start.b + offset;
+            for fragment_index in each_fragment_index(&line.range) {
+                let fragment = self.fragments.fragments.get_mut(fragment_index.to_uint());
+                fragment.border_box.start.b = fragment.border_box.start.b + offset
+            }
+        }
+    }
+
+    /// Moves each line, and the fragments on it, by the offset that `offset_at` returns for the
+    /// block-start position of the line. Multi-column elements use this to move the lines of a
+    /// flow that is broken across columns into their columns.
+    pub fn move_lines(&mut self, offset_at: |Au| -> (Au, Au)) {
+        for line in self.lines.iter_mut() {
+            let (inline_offset, block_offset) = offset_at(line.bounds.start.b);
+            line.bounds.start.i = line.bounds.start.i + inline_offset;
+            line.bounds.start.b = line.bounds.start.b + block_offset;
+            for fragment_index in each_fragment_index(&line.range) {
+                let fragment = self.fragments.fragments.get_mut(fragment_index.to_uint());
+                fragment.border_box.start.i = fragment.border_box.start.i + inline_offset;
+                fragment.border_box.start.b = fragment.border_box.start.b + block_offset
             }
+
+            let line_overflow = line.bounds.translate(&self.base.position.start);
+            self.base.overflow = self.base.overflow.union(&line_overflow)
         }
+    }
+
+    /// Sets the minimum line metrics of this flow, and the metrics of its root inline box, from
+    /// the style of the block container that holds it.
+    pub fn set_root_metrics(&mut self, font_context: &mut FontContext, style: &ComputedValues) {
+        let (ascent, descent) = self.compute_minimum_ascent_and_descent(font_context, style);
+        self.minimum_block_size_above_baseline = ascent;
+        self.minimum_depth_below_baseline = descent;
+        self.root_vertical_align_metrics = VerticalAlignMetrics::from_style(font_context, style);
+    }
 
-        (block_size_above_baseline, depth_below_baseline)
+    /// Gives the fragments of this flow the styles in `new_styles`, which are keyed by the IDs of
+    /// the nodes that they belong to. The fragments kept from before line breaking get them too,
+    /// since the fragments are copied back from those.
+    pub fn update_styles(&mut self, new_styles: &HashMap<uintptr_t, Arc<ComputedValues>>) {
+        for fragment in self.fragments.fragments.iter_mut() {
+            fragment.update_styles(new_styles)
+        }
+        match self.unsplit_fragments {
+            Some(ref mut fragments) => {
+                for fragment in fragments.fragments.iter_mut() {
+                    fragment.update_styles(new_styles)
+                }
+            }
+            None => {}
+        }
     }
 }
 
@@ -990,6 +1189,8 @@ impl Flow for InlineFlow {
     fn bubble_inline_sizes(&mut self, layout_context: &LayoutContext) {
         let _scope = layout_debug_scope!("inline::bubble_inline_sizes {:s}", self.base.debug_id());
 
+        self.restore_unsplit_fragments();
+
         let writing_mode = self.base.writing_mode;
         for kid in self.base.child_iter() {
             flow::mut_base(kid).floats = Floats::new(writing_mode);
@@ -1052,17 +1253,18 @@ impl Flow for InlineFlow {
 
         // Divide the fragments into lines.
         //
-        // TODO(#226): Get the CSS `line-block-size` property from the containing block's style to
-        // determine minimum line block-size.
-        //
-        // TODO(#226): Get the CSS `line-block-size` property from each non-replaced inline element to
-        // determine its block-size for computing line block-size.
-        //
         // TODO(pcwalton): Cache the line scanner?
         debug!("assign_block_size_inline: floats in: {:?}", self.base.floats);
 
+        // Start from the unsplit fragments, since the lines may have been broken differently by an
+        // earlier reflow. That loses the inline sizes of any replaced fragments, so assign those
+        // again.
+        self.restore_unsplit_fragments();
+
         // assign block-size for inline fragments
+        let inline_size = self.base.position.size.inline;
         for fragment in self.fragments.fragments.iter_mut() {
+            fragment.assign_replaced_inline_size_if_necessary(inline_size);
             fragment.assign_replaced_block_size_if_necessary();
         }
 
@@ -1073,17 +1275,25 @@ impl Flow for InlineFlow {
         // All lines use text alignment of the flow.
         let text_align = self.base.flags.text_align();
 
+        // The embedding level of the paragraph, from the direction of the flow.
+        let base_level = if self.base.writing_mode.is_bidi_ltr() { 0 } else { 1 };
+
         // Now, go through each line and lay out the fragments inside.
         let mut line_distance_from_flow_block_start = Au(0);
         for line in self.lines.iter_mut() {
             // Lay out fragments in the inline direction.
-            InlineFlow::set_inline_fragment_positions(&mut self.fragments, line, text_align);
+            InlineFlow::set_inline_fragment_positions(&mut self.fragments,
+                                                      line,
+                                                      text_align,
+                                                      base_level);
 
             // Set the block-start position of the current line.
             // `line_height_offset` is updated at the end of the previous loop.
             line.bounds.start.b = line_distance_from_flow_block_start;
 
-            // Calculate the distance from the baseline to the block-start and block-end of the line.
+            // Calculate the distance from the baseline to the block-start and block-end of the
+            // line. Every line box starts with a zero-width inline box with the font and
+            // line-height of the block: the strut. See CSS 2.1 § 10.8.1.
             let mut largest_block_size_above_baseline = self.minimum_block_size_above_baseline;
             let mut largest_depth_below_baseline = self.minimum_depth_below_baseline;
 
@@ -1092,58 +1302,38 @@ impl Flow for InlineFlow {
             let (mut largest_block_size_for_top_fragments,
                  mut largest_block_size_for_bottom_fragments) = (Au(0), Au(0));
 
+            let mut alignments = Vec::new();
             for fragment_i in each_fragment_index(&line.range) {
                 let fragment = self.fragments.fragments.get_mut(fragment_i.to_uint());
-
-                let InlineMetrics {
-                    block_size_above_baseline: mut block_size_above_baseline,
-                    depth_below_baseline: mut depth_below_baseline,
-                    ascent
-                } = fragment.inline_metrics(ctx);
-
-                // To calculate text-top and text-bottom value when `vertical-align` is involved,
-                // we should find the top and bottom of the content area of the parent fragment.
-                // "Content area" is defined in CSS 2.1 § 10.6.1.
-                //
-                // TODO: We should extract em-box info from the font size of the parent and
-                // calculate the distances from the baseline to the block-start and the block-end of the
-                // parent's content area.
-
-                // We should calculate the distance from baseline to the top of parent's content
-                // area. But for now we assume it's the font size.
-                //
-                // CSS 2.1 does not state which font to use. This version of the code uses
-                // the parent's font.
-
-                // Calculate the final block-size above the baseline for this fragment.
-                //
-                // The no-update flag decides whether `largest_block-size_for_top_fragments` and
-                // `largest_block-size_for_bottom_fragments` are to be updated or not. This will be set
-                // if and only if the fragment has `vertical-align` set to `top` or `bottom`.
-                let (distance_from_baseline, no_update_flag) =
-                    InlineFlow::distance_from_baseline(
-                        fragment,
-                        ascent,
-                        self.minimum_block_size_above_baseline,
-                        self.minimum_depth_below_baseline,
-                        &mut block_size_above_baseline,
-                        &mut depth_below_baseline,
-                        &mut largest_block_size_for_top_fragments,
-                        &mut largest_block_size_for_bottom_fragments,
-                        ctx);
-
-                // Unless the current fragment has `vertical-align` set to `top` or `bottom`,
-                // `largest_block-size_above_baseline` and `largest_depth_below_baseline` are updated.
-                if !no_update_flag {
-                    largest_block_size_above_baseline = Au::max(block_size_above_baseline,
-                                                            largest_block_size_above_baseline);
-                    largest_depth_below_baseline = Au::max(depth_below_baseline,
-                                                           largest_depth_below_baseline);
+                let placement = InlineFlow::vertical_placement(fragment,
+                                                               &self.root_vertical_align_metrics,
+                                                               &mut scanner.inline_box_metrics,
+                                                               ctx);
+
+                // Fragments aligned to the top or bottom of the line box only affect the
+                // block-size of the line if they are taller than everything else; see below.
+                match placement.alignment {
+                    AlignedToBaseline => {
+                        largest_block_size_above_baseline =
+                            max(largest_block_size_above_baseline, placement.block_size_above);
+                        largest_depth_below_baseline =
+                            max(largest_depth_below_baseline, placement.depth_below);
+                    }
+                    AlignedToLineTop => {
+                        largest_block_size_for_top_fragments =
+                            max(largest_block_size_for_top_fragments, placement.depth_below);
+                    }
+                    AlignedToLineBottom => {
+                        largest_block_size_for_bottom_fragments =
+                            max(largest_block_size_for_bottom_fragments,
+                                placement.block_size_above);
+                    }
                 }
 
                 // Temporarily use `fragment.border_box.start.b` to mean "the distance from the
-                // baseline". We will assign the real value later.
-                fragment.border_box.start.b = distance_from_baseline
+                // alignment point". We will assign the real value later.
+                fragment.border_box.start.b = placement.border_box_offset;
+                alignments.push(placement.alignment)
             }
 
             // Calculate the distance from the baseline to the top of the largest fragment with a
@@ -1163,18 +1353,25 @@ impl Flow for InlineFlow {
             let baseline_distance_from_block_start = largest_block_size_above_baseline;
 
             // Compute the final positions in the block direction of each fragment. Recall that
-            // `fragment.border_box.start.b` was set to the distance from the baseline above.
+            // `fragment.border_box.start.b` was set to the distance from the alignment point above.
             InlineFlow::set_block_fragment_positions(&mut self.fragments,
                                                      line,
+                                                     alignments.as_slice(),
                                                      line_distance_from_flow_block_start,
                                                      baseline_distance_from_block_start,
                                                      largest_depth_below_baseline);
 
             // This is used to set the block-start y position of the next line in the next loop.
+            line.baseline_offset = baseline_distance_from_block_start;
             line.bounds.size.block = largest_block_size_above_baseline + largest_depth_below_baseline;
             line_distance_from_flow_block_start = line_distance_from_flow_block_start + line.bounds.size.block;
         } // End of `lines.each` loop.
 
+        match self.base.page_grid.clone() {
+            Some(page_grid) => self.move_lines_past_page_breaks(&page_grid),
+            None => {}
+        }
+
         self.base.position.size.block = match self.lines.as_slice().last() {
             Some(ref last_line) => last_line.bounds.start.b + last_line.bounds.size.block,
             None => Au::new(0)
@@ -1224,12 +1421,15 @@ impl fmt::Show for InlineFlow {
 #[deriving(Clone)]
 pub struct InlineFragmentContext {
     pub styles: Vec<Arc<ComputedValues>>,
+    /// The nodes that `styles` belong to, in the same order.
+    pub nodes: Vec<OpaqueNode>,
 }
 
 impl InlineFragmentContext {
     pub fn new() -> InlineFragmentContext {
         InlineFragmentContext {
-            styles: vec!()
+            styles: vec!(),
+            nodes: vec!(),
         }
     }
 }
@@ -1266,3 +1466,189 @@ impl InlineMetrics {
     }
 }
 
+/// The font metrics of an inline box that the `vertical-align` values of its children are
+/// resolved against. See CSS 2.1 § 10.8.1.
+#[deriving(Clone, Encodable)]
+pub struct VerticalAlignMetrics {
+    pub ascent: Au,
+    pub descent: Au,
+    pub x_height: Au,
+    pub em_size: Au,
+}
+
+impl VerticalAlignMetrics {
+    pub fn zero() -> VerticalAlignMetrics {
+        VerticalAlignMetrics {
+            ascent: Au(0),
+            descent: Au(0),
+            x_height: Au(0),
+            em_size: Au(0),
+        }
+    }
+
+    #[inline]
+    pub fn from_font_metrics(font_metrics: &FontMetrics) -> VerticalAlignMetrics {
+        VerticalAlignMetrics {
+            ascent: font_metrics.ascent,
+            descent: font_metrics.descent,
+            x_height: font_metrics.x_height,
+            em_size: font_metrics.em_size,
+        }
+    }
+
+    pub fn from_style(font_context: &mut FontContext, style: &ComputedValues)
+                      -> VerticalAlignMetrics {
+        let font_style = text::computed_style_to_font_style(style);
+        VerticalAlignMetrics::from_font_metrics(&text::font_metrics_for_style(font_context,
+                                                                              &font_style))
+    }
+}
+
+/// The font metrics and line heights of inline boxes, cached by style. Every fragment inside an
+/// inline box needs these to be vertically aligned, so they are computed only once per line
+/// breaking pass rather than once per fragment. See `InlineFlow::vertical_placement()`.
+struct InlineBoxMetricsCache {
+    /// Maps the address of each style to its font metrics and line height. The styles are kept
+    /// alive by the fragments for as long as the cache is.
+    entries: HashMap<uint, (FontMetrics, Au)>,
+}
+
+impl InlineBoxMetricsCache {
+    fn new() -> InlineBoxMetricsCache {
+        InlineBoxMetricsCache {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the font metrics and line height of an inline box with the given style.
+    fn get(&mut self, style: &Arc<ComputedValues>, layout_context: &LayoutContext)
+           -> (FontMetrics, Au) {
+        let key = &**style as *const ComputedValues as uint;
+        self.entries.find_or_insert_with(key, |_| {
+            let font_style = text::computed_style_to_font_style(&**style);
+            let font_metrics = text::font_metrics_for_style(layout_context.font_context(),
+                                                            &font_style);
+            let line_height = text::line_height_from_style(&**style, &font_metrics);
+            (font_metrics, line_height)
+        }).clone()
+    }
+}
+
+/// Returns the bidi embedding level of each fragment of a line, in logical order.
+///
+/// A text fragment takes the direction of the script of its run, since runs are itemized by script.
+/// Fragments of common script, such as spaces and digits, and non-text fragments are neutral: they
+/// take the direction of the text on both sides of them if it agrees, and that of the paragraph
+/// otherwise. This is a simplification of the Unicode Bidirectional Algorithm (UAX #9), which
+/// works on characters rather than fragments and supports explicit embeddings.
+fn resolve_bidi_levels(fragments: &InlineFragments, line: &Line, base_level: u8) -> Vec<u8> {
+    let strong_levels: Vec<Option<u8>> = each_fragment_index(&line.range).map(|i| {
+        match fragments.get(i.to_uint()).specific {
+            ScannedTextFragment(ref info) if info.run.is_rtl() => {
+                Some(base_level | 1)
+            }
+            ScannedTextFragment(ref info) if !info.run.script.is_common_or_inherited() => {
+                Some((base_level + 1) & !1)
+            }
+            _ => None,
+        }
+    }).collect();
+
+    strong_levels.iter().enumerate().map(|(i, &strong_level)| {
+        match strong_level {
+            Some(level) => level,
+            None => {
+                let before =
+                    strong_levels.slice_to(i).iter().rev().filter_map(|&level| level).next();
+                let after =
+                    strong_levels.slice_from(i + 1).iter().filter_map(|&level| level).next();
+                match (before, after) {
+                    (Some(before), Some(after)) if before == after => before,
+                    _ => base_level,
+                }
+            }
+        }
+    }).collect()
+}
+
+/// Returns the indices of items with the given bidi embedding levels, ordered from left to right.
+///
+/// This is rule L2 of the Unicode Bidirectional Algorithm: from the highest level down to the
+/// lowest odd level, every maximal sequence of items at that level or higher is reversed.
+fn visual_order(levels: &[u8]) -> Vec<uint> {
+    let mut order = Vec::from_fn(levels.len(), |i| i);
+    let max_level = match levels.iter().max() {
+        Some(&level) => level,
+        None => return order,
+    };
+    let lowest_odd_level = match levels.iter().min() {
+        Some(&level) => level | 1,
+        None => return order,
+    };
+
+    let mut level = max_level;
+    while level >= lowest_odd_level {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order.slice_mut(start, i).reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+/// What a fragment is vertically positioned relative to within its line.
+#[deriving(Clone, PartialEq)]
+enum LineAlignment {
+    /// The baseline of the root inline box.
+    AlignedToBaseline,
+    /// The block-start edge of the line box, for `vertical-align: top`.
+    AlignedToLineTop,
+    /// The block-end edge of the line box, for `vertical-align: bottom`.
+    AlignedToLineBottom,
+}
+
+/// The vertical position of a fragment within its line, and the space that it needs. See
+/// `InlineFlow::vertical_placement()`.
+struct VerticalPlacement {
+    alignment: LineAlignment,
+    /// The distance from the alignment point to the block-start edge of the fragment's border box.
+    /// Positive values are toward block-end.
+    border_box_offset: Au,
+    /// The space that the fragment and its inline ancestors need above the alignment point.
+    block_size_above: Au,
+    /// The space that the fragment and its inline ancestors need below the alignment point.
+    depth_below: Au,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::visual_order;
+
+    #[test]
+    fn test_visual_order_of_left_to_right_text() {
+        assert_eq!(visual_order(&[0, 0, 0]), vec![0, 1, 2]);
+        assert_eq!(visual_order(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_visual_order_reverses_right_to_left_runs() {
+        // Latin, Arabic, space, Arabic, Latin.
+        assert_eq!(visual_order(&[0, 1, 1, 1, 0]), vec![0, 3, 2, 1, 4]);
+        // A right-to-left paragraph with Latin in it.
+        assert_eq!(visual_order(&[1, 2, 2, 1]), vec![3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_visual_order_of_nested_levels() {
+        assert_eq!(visual_order(&[0, 1, 2, 2, 1, 0]), vec![0, 4, 2, 3, 1, 5]);
+    }
+}
@@ -5,16 +5,19 @@
 //! The layout task. Performs layout on the DOM, builds display lists and sends them to be
 //! rendered.
 
-use css::matching::{ApplicableDeclarations, MatchMethods};
+use css::matching::{ApplicableDeclarations, MatchMethods, StyleCacheStatistics};
 use css::node_style::StyledNode;
-use construct::{FlowConstructionResult, NoConstructionResult};
+use construct::FlowConstructionResult;
 use context::{LayoutContext, SharedLayoutContext};
-use flow::{Flow, ImmutableFlowUtils, MutableFlowUtils, MutableOwnedFlowUtils};
-use flow::{PreorderFlowTraversal, PostorderFlowTraversal};
+use flow::{Flow, FlowClass, FlowFlags, ImmutableFlowUtils, MutableFlowUtils};
+use flow::{MutableOwnedFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal};
 use flow;
 use flow_ref::FlowRef;
-use incremental::RestyleDamage;
+use generated_content;
+use incremental::{BubbleISizes, Reflow, Reposition, ResolveGeneratedContent, RestyleDamage};
 use layout_debug;
+use pagination::{PageGeometry, PageGrid};
+use pagination;
 use parallel::UnsafeFlow;
 use parallel;
 use util::{LayoutDataAccess, LayoutDataWrapper, OpaqueNodeMethods, ToGfxColor};
@@ -26,9 +29,11 @@ use encoding::all::UTF_8;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use gfx::display_list::{ClipDisplayItemClass, ContentStackingLevel, DisplayItem};
-use gfx::display_list::{DisplayItemIterator, DisplayList, OpaqueNode};
-use gfx::render_task::{RenderInitMsg, RenderChan, RenderLayer};
+use gfx::display_list::{ContentStackingLevel, DisplayItemIterator, DisplayList, OpaqueNode};
+use gfx::display_list::PseudoDisplayItemClass;
+use gfx::display_list::serializer;
+use gfx::render_task::{LayerDisplayList, PageLayout, PageLayoutMsg, RenderInitMsg, RenderChan};
+use gfx::render_task::RenderLayer;
 use gfx::{render_task, color};
 use layout_traits;
 use layout_traits::{LayoutControlMsg, LayoutTaskFactory};
@@ -37,33 +42,39 @@ use script::dom::node::{ElementNodeTypeId, LayoutDataRef, Node};
 use script::dom::element::{HTMLBodyElementTypeId, HTMLHtmlElementTypeId};
 use script::layout_interface::{AddStylesheetMsg, LoadStylesheetMsg, ScriptLayoutChan};
 use script::layout_interface::{TrustedNodeAddress, ContentBoxesResponse, ExitNowMsg};
-use script::layout_interface::{ContentBoxResponse, HitTestResponse, MouseOverResponse};
+use script::layout_interface::{ContentBoxResponse, DisplayListResponse, HitTestResponse};
+use script::layout_interface::{MouseOverResponse, ScrollRangeResponse};
 use script::layout_interface::{ContentChangedDocumentDamage, LayoutChan, Msg, PrepareToExitMsg};
 use script::layout_interface::{GetRPCMsg, LayoutRPC, ReapLayoutDataMsg, Reflow, UntrustedNodeAddress};
 use script::layout_interface::{ReflowForDisplay, ReflowMsg};
 use script_traits::{SendEventMsg, ReflowEvent, ReflowCompleteMsg, OpaqueScriptLayoutChannel, ScriptControlChan};
-use servo_msg::compositor_msg::Scrollable;
+use servo_msg::compositor_msg::{LayerId, Scrollable};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, Failure, FailureMsg};
+use servo_msg::constellation_msg::SetCursorMsg;
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
-use gfx::font_cache_task::{FontCacheTask};
+use gfx::font_cache_task::{FontCacheTask, WebFontDeclaration};
+use gfx::font_template::FontTemplateDescriptor;
 use servo_net::local_image_cache::{ImageResponder, LocalImageCache};
 use servo_util::bloom::BloomFilter;
+use servo_util::cursor::{Cursor, DefaultCursor};
 use servo_net::resource_task::{ResourceTask, load_bytes_iter};
 use servo_util::geometry::Au;
-use servo_util::geometry;
-use servo_util::logical_geometry::LogicalPoint;
+use servo_util::logical_geometry::{LogicalPoint, LogicalRect};
 use servo_util::opts::Opts;
 use servo_util::smallvec::{SmallVec, SmallVec1};
 use servo_util::task::spawn_named_with_send_on_failure;
-use servo_util::time::{TimeProfilerChan, profile};
+use servo_util::time::{TimeProfilerChan, profile, profile_cache};
 use servo_util::time;
 use servo_util::workqueue::WorkQueue;
 use std::cell::Cell;
+use std::collections::hashmap::HashMap;
 use std::comm::{channel, Sender, Receiver, Select};
 use std::mem;
 use std::ptr;
+use std::sync::atomics::{AtomicBool, Relaxed};
 use style;
-use style::{AuthorOrigin, Stylesheet, Stylist};
+use style::{AuthorOrigin, Device, Print, Screen, Stylesheet, Stylist};
+use style::computed_values::font_style;
 use style::iter_font_face_rules;
 use sync::{Arc, Mutex, MutexGuard};
 use url::Url;
@@ -78,9 +89,20 @@ pub struct LayoutTaskData {
     /// The size of the viewport.
     pub screen_size: Size2D<Au>,
 
+    /// The number of device pixels per CSS pixel.
+    pub device_pixels_per_px: f32,
+
     /// A cached display list.
     pub display_list: Option<Arc<DisplayList>>,
 
+    /// The display lists of all layers other than the root layer, in painting order. Queries need
+    /// these to find content that scrolls inside its own layer.
+    pub layer_display_lists: Vec<LayerDisplayList>,
+
+    /// How far the contents of each scroll root layer can be scrolled, in pixels. Script clamps
+    /// `scrollTop` and `scrollLeft` to these.
+    pub scroll_ranges: HashMap<LayerId, Size2D<f32>>,
+
     pub stylist: Box<Stylist>,
 
     /// The workers that we use for parallel operation.
@@ -92,6 +114,24 @@ pub struct LayoutTaskData {
     /// Starts at zero, and increased by one every time a layout completes.
     /// This can be used to easily check for invalid stale data.
     pub generation: uint,
+
+    /// True if a stylesheet has been added since the last reflow, so that every node has to be
+    /// restyled.
+    pub stylesheets_changed: bool,
+
+    /// True if the flow tree holds quotes of generated content, whose depth has to be checked on
+    /// every reflow.
+    pub has_generated_quotes: bool,
+
+    /// The web font faces that the stylesheets declared, to remove from the font cache on exit.
+    pub web_fonts: Vec<WebFontDeclaration>,
+
+    /// The channel on which messages can be sent to the constellation, for queries that run on
+    /// the script task.
+    pub constellation_chan: ConstellationChan,
+
+    /// The cursor that was last shown, so that the constellation is only told when it changes.
+    pub cursor: Cursor,
 }
 
 /// Information needed by the layout task.
@@ -150,6 +190,14 @@ impl PostorderFlowTraversal for ComputeDamageTraversal {
     #[inline]
     fn process(&mut self, flow: &mut Flow) -> bool {
         let mut damage = flow::base(flow).restyle_damage;
+
+        // The containing block of an absolutely positioned flow can be any of its ancestors, and
+        // we don't track which one, so always lay such flows out again, along with the path to
+        // them.
+        if flow.is_absolutely_positioned() {
+            damage.insert(Reflow)
+        }
+
         for child in flow::child_iter(flow) {
             damage.insert(flow::base(child).restyle_damage.propagate_up())
         }
@@ -158,27 +206,47 @@ impl PostorderFlowTraversal for ComputeDamageTraversal {
     }
 }
 
-/// Propagates restyle damage up and down the tree as appropriate.
-///
-/// FIXME(pcwalton): Merge this with flow tree building and/or other traversals.
-struct PropagateDamageTraversal {
-    all_style_damage: bool,
-}
+/// Forgets the damage of every flow once it has been laid out, so that the next reflow only does
+/// the work that the changes made in the meantime call for.
+struct ClearDamageTraversal;
 
-impl PreorderFlowTraversal for PropagateDamageTraversal {
+impl PreorderFlowTraversal for ClearDamageTraversal {
     #[inline]
     fn process(&mut self, flow: &mut Flow) -> bool {
-        if self.all_style_damage {
-            flow::mut_base(flow).restyle_damage.insert(RestyleDamage::all())
-        }
-        debug!("restyle damage = {:?}", flow::base(flow).restyle_damage);
+        flow::mut_base(flow).restyle_damage = RestyleDamage::empty();
+        true
+    }
 
-        let prop = flow::base(flow).restyle_damage.propagate_down();
-        if !prop.is_empty() {
-            for kid_ctx in flow::child_iter(flow) {
-                flow::mut_base(kid_ctx).restyle_damage.insert(prop)
-            }
-        }
+    /// Damage propagates up, so nothing below an undamaged flow is damaged either.
+    #[inline]
+    fn should_prune(&mut self, flow: &mut Flow) -> bool {
+        flow::base(flow).restyle_damage.is_empty()
+    }
+}
+
+/// The geometry of a flow, as recorded by `--verify-incremental-layout`.
+#[deriving(PartialEq, Show)]
+struct FlowGeometry {
+    class: FlowClass,
+    position: LogicalRect<Au>,
+    overflow: LogicalRect<Au>,
+}
+
+/// Records the geometry of every flow in a flow tree, in preorder, so that the results of two
+/// layouts of the same document can be compared.
+struct FlowGeometryTraversal {
+    geometry: Vec<FlowGeometry>,
+}
+
+impl PreorderFlowTraversal for FlowGeometryTraversal {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        let base = flow::base(flow);
+        self.geometry.push(FlowGeometry {
+            class: flow.class(),
+            position: base.position,
+            overflow: base.overflow,
+        });
         true
     }
 }
@@ -214,13 +282,10 @@ impl<'a> PostorderFlowTraversal for BubbleISizesTraversal<'a> {
         true
     }
 
-    // FIXME: We can't prune until we start reusing flows
-    /*
     #[inline]
     fn should_prune(&mut self, flow: &mut Flow) -> bool {
-        flow::mut_base(flow).restyle_damage.lacks(BubbleISizes)
+        !flow::base(flow).restyle_damage.contains(BubbleISizes)
     }
-    */
 }
 
 /// The assign-inline-sizes traversal. In Gecko this corresponds to `Reflow`.
@@ -231,9 +296,48 @@ pub struct AssignISizesTraversal<'a> {
 impl<'a> PreorderFlowTraversal for AssignISizesTraversal<'a> {
     #[inline]
     fn process(&mut self, flow: &mut Flow) -> bool {
+        let old_kid_geometry: Vec<AssignedInlineGeometry> =
+            flow::child_iter(flow).map(|kid| AssignedInlineGeometry::of(kid)).collect();
+
         flow.assign_inline_sizes(self.layout_context);
+
+        // A kid that wasn't damaged still has to be laid out again if what it was given to work
+        // with changed. Floats are laid out along with their parent, so anything they might
+        // intrude on has to be laid out again too.
+        for (kid, old_geometry) in flow::child_iter(flow).zip(old_kid_geometry.iter()) {
+            if AssignedInlineGeometry::of(kid) != *old_geometry ||
+                    flow::base(kid).flags.impacted_by_floats() {
+                flow::mut_base(kid).restyle_damage.insert(Reflow)
+            }
+        }
         true
     }
+
+    #[inline]
+    fn should_prune(&mut self, flow: &mut Flow) -> bool {
+        !flow::base(flow).restyle_damage.contains(Reflow)
+    }
+}
+
+/// The parts of a flow that its parent assigns while assigning inline sizes.
+#[deriving(PartialEq)]
+struct AssignedInlineGeometry {
+    inline_start: Au,
+    inline_size: Au,
+    block_container_explicit_block_size: Option<Au>,
+    flags: FlowFlags,
+}
+
+impl AssignedInlineGeometry {
+    fn of(flow: &Flow) -> AssignedInlineGeometry {
+        let base = flow::base(flow);
+        AssignedInlineGeometry {
+            inline_start: base.position.start.i,
+            inline_size: base.position.size.inline,
+            block_container_explicit_block_size: base.block_container_explicit_block_size,
+            flags: base.flags,
+        }
+    }
 }
 
 /// The assign-block-sizes-and-store-overflow traversal, the last (and most expensive) part of layout
@@ -246,7 +350,10 @@ pub struct AssignBSizesAndStoreOverflowTraversal<'a> {
 impl<'a> PostorderFlowTraversal for AssignBSizesAndStoreOverflowTraversal<'a> {
     #[inline]
     fn process(&mut self, flow: &mut Flow) -> bool {
-        flow.assign_block_size(self.layout_context);
+        // Flows that only moved keep their block-size, but their overflow has to be stored again.
+        if flow::base(flow).restyle_damage.contains(Reflow) {
+            flow.assign_block_size(self.layout_context);
+        }
         // Skip store-overflow for absolutely positioned flows. That will be
         // done in a separate traversal.
         if !flow.is_store_overflow_delayed() {
@@ -257,7 +364,13 @@ impl<'a> PostorderFlowTraversal for AssignBSizesAndStoreOverflowTraversal<'a> {
 
     #[inline]
     fn should_process(&mut self, flow: &mut Flow) -> bool {
-        !flow::base(flow).flags.impacted_by_floats()
+        let base = flow::base(flow);
+        !base.flags.impacted_by_floats() && base.restyle_damage.intersects(Reflow | Reposition)
+    }
+
+    #[inline]
+    fn should_prune(&mut self, flow: &mut Flow) -> bool {
+        !flow::base(flow).restyle_damage.intersects(Reflow | Reposition)
     }
 }
 
@@ -291,12 +404,12 @@ struct LayoutImageResponder {
 }
 
 impl ImageResponder for LayoutImageResponder {
-    fn respond(&self) -> proc(ImageResponseMsg):Send {
+    fn respond(&self) -> proc(ImageResponseMsg, Vec<uint>):Send {
         let id = self.id.clone();
         let script_chan = self.script_chan.clone();
-        let f: proc(ImageResponseMsg):Send = proc(_) {
+        let f: proc(ImageResponseMsg, Vec<uint>):Send = proc(_, node_addresses) {
             let ScriptControlChan(chan) = script_chan;
-            drop(chan.send_opt(SendEventMsg(id.clone(), ReflowEvent)))
+            drop(chan.send_opt(SendEventMsg(id.clone(), ReflowEvent(node_addresses))))
         };
         f
     }
@@ -394,13 +507,18 @@ impl LayoutTask {
         } else {
             None
         };
+        let media_type = if opts.paginate {
+            Print
+        } else {
+            Screen
+        };
 
         LayoutTask {
             id: id,
             port: port,
             pipeline_port: pipeline_port,
             chan: chan,
-            constellation_chan: constellation_chan,
+            constellation_chan: constellation_chan.clone(),
             script_chan: script_chan,
             render_chan: render_chan,
             time_profiler_chan: time_profiler_chan,
@@ -413,11 +531,19 @@ impl LayoutTask {
                 LayoutTaskData {
                     local_image_cache: local_image_cache,
                     screen_size: screen_size,
+                    device_pixels_per_px: 1.0,
                     display_list: None,
-                    stylist: box Stylist::new(),
+                    layer_display_lists: Vec::new(),
+                    scroll_ranges: HashMap::new(),
+                    stylist: box Stylist::new(Device::new(media_type)),
                     parallel_traversal: parallel_traversal,
                     dirty: Rect::zero(),
                     generation: 0,
+                    stylesheets_changed: false,
+                    has_generated_quotes: false,
+                    web_fonts: vec!(),
+                    constellation_chan: constellation_chan,
+                    cursor: DefaultCursor,
               })),
         }
     }
@@ -435,11 +561,13 @@ impl LayoutTask {
       &self,
       rw_data: &LayoutTaskData,
       reflow_root: &LayoutNode,
-      url: &Url)
+      url: &Url,
+      full_reflow: bool)
           -> SharedLayoutContext {
         SharedLayoutContext {
             image_cache: rw_data.local_image_cache.clone(),
             screen_size: rw_data.screen_size.clone(),
+            device_pixels_per_px: rw_data.device_pixels_per_px,
             constellation_chan: self.constellation_chan.clone(),
             layout_chan: self.chan.clone(),
             font_cache_task: self.font_cache_task.clone(),
@@ -448,7 +576,10 @@ impl LayoutTask {
             reflow_root: OpaqueNodeMethods::from_layout_node(reflow_root),
             opts: self.opts.clone(),
             dirty: Rect::zero(),
+            style_cache_statistics: StyleCacheStatistics::new(),
+            generated_quotes: AtomicBool::new(false),
             generation: rw_data.generation,
+            full_reflow: full_reflow,
         }
     }
 
@@ -583,6 +714,9 @@ impl LayoutTask {
                 None => {}
                 Some(ref mut traversal) => traversal.shutdown(),
             }
+            for declaration in mem::replace(&mut rw_data.web_fonts, vec!()).into_iter() {
+                self.font_cache_task.remove_web_font(declaration);
+            }
             LayoutTask::return_rw_data(possibly_locked_rw_data, rw_data);
         }
 
@@ -603,33 +737,62 @@ impl LayoutTask {
     }
 
     fn handle_add_stylesheet<'a>(&'a self, sheet: Stylesheet, possibly_locked_rw_data: &mut Option<MutexGuard<'a, LayoutTaskData>>) {
-        // Find all font-face rules and notify the font cache of them.
-        // GWTODO: Need to handle unloading web fonts (when we handle unloading stylesheets!)
-        iter_font_face_rules(&sheet, |family, url| {
-            self.font_cache_task.add_web_font(family.to_string(), url.clone());
-        });
         let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
+        // Find all font-face rules and notify the font cache of them. They are removed again
+        // when this layout task exits, which is when its stylesheets go away.
+        let mut web_fonts = vec!();
+        iter_font_face_rules(&sheet, rw_data.stylist.device(), |rule, sources| {
+            let declaration = WebFontDeclaration {
+                family: rule.family.clone(),
+                sources: sources,
+                descriptor: FontTemplateDescriptor::new(rule.weight,
+                                                        rule.stretch,
+                                                        rule.style != font_style::normal),
+                unicode_range: rule.unicode_range.clone(),
+            };
+            self.font_cache_task.add_web_font(declaration.clone());
+            web_fonts.push(declaration);
+        });
+        rw_data.web_fonts.extend(web_fonts.into_iter());
         rw_data.stylist.add_stylesheet(sheet, AuthorOrigin);
+        rw_data.stylesheets_changed = true;
         LayoutTask::return_rw_data(possibly_locked_rw_data, rw_data);
     }
 
-    /// Retrieves the flow tree root from the root node.
+    /// Retrieves the flow tree root from the root node. The root node keeps its flow, so that it
+    /// can be reused by the next reflow if nothing in the document changes.
     fn get_layout_root(&self, node: LayoutNode) -> FlowRef {
-        let mut layout_data_ref = node.mutate_layout_data();
-        let result = match &mut *layout_data_ref {
-            &Some(ref mut layout_data) => {
-                mem::replace(&mut layout_data.data.flow_construction_result, NoConstructionResult)
+        // The root has no parent to act on the damage flow construction left for it.
+        {
+            let mut layout_data_ref = node.mutate_layout_data();
+            match &mut *layout_data_ref {
+                &Some(ref mut layout_data) => {
+                    layout_data.data.restyle_damage = Some(RestyleDamage::empty())
+                }
+                &None => fail!("no layout data for root node"),
             }
+        }
+
+        let layout_data_ref = node.borrow_layout_data();
+        let result = match &*layout_data_ref {
+            &Some(ref layout_data) => &layout_data.data.flow_construction_result,
             &None => fail!("no layout data for root node"),
         };
-        let mut flow = match result {
-            FlowConstructionResult(mut flow, abs_descendants) => {
+        let mut flow = match *result {
+            FlowConstructionResult(ref flow, ref abs_descendants) => {
+                // A flow that has been made the root already is being reused from an earlier
+                // reflow, and its absolute descendants are already set up.
+                if flow.get().is_root() {
+                    return flow.clone()
+                }
+                let mut flow = flow.clone();
+
                 // Note: Assuming that the root has display 'static' (as per
                 // CSS Section 9.3.1). Otherwise, if it were absolutely
                 // positioned, it would return a reference to itself in
                 // `abs_descendants` and would lead to a circular reference.
                 // Set Root as CB for any remaining absolute descendants.
-                flow.set_absolute_descendants(abs_descendants);
+                flow.set_absolute_descendants(abs_descendants.clone());
                 flow
             }
             _ => fail!("Flow construction didn't result in a flow at the root of the tree!"),
@@ -655,7 +818,6 @@ impl LayoutTask {
             layout_root.traverse_postorder(&mut traversal);
         }
 
-        // FIXME(pcwalton): Prune these two passes.
         {
             let mut traversal = AssignISizesTraversal {
                 layout_context: layout_context,
@@ -691,8 +853,8 @@ impl LayoutTask {
         match rw_data.parallel_traversal {
             None => fail!("solve_contraints_parallel() called with no parallel traversal ready"),
             Some(ref mut traversal) => {
-                // NOTE: this currently computes borders, so any pruning should separate that
-                // operation out.
+                // NOTE: this also computes borders. Flows that get pruned keep the ones they
+                // computed in an earlier reflow.
                 parallel::traverse_flow_tree_preorder(layout_root,
                                                       &data.url,
                                                       data.iframe,
@@ -717,64 +879,23 @@ impl LayoutTask {
     fn verify_flow_tree(&self, _: &mut FlowRef) {
     }
 
-    /// The high-level routine that performs layout tasks.
-    fn handle_reflow<'a>(&'a self, data: &Reflow, possibly_locked_rw_data: &mut Option<MutexGuard<'a, LayoutTaskData>>) {
-        // FIXME: Isolate this transmutation into a "bridge" module.
-        // FIXME(rust#16366): The following line had to be moved because of a
-        // rustc bug. It should be in the next unsafe block.
-        let mut node: JS<Node> = unsafe { JS::from_trusted_node_address(data.document_root) };
-        let node: &mut LayoutNode = unsafe {
-            mem::transmute(&mut node)
-        };
-
-        debug!("layout: received layout request for: {:s}", data.url.serialize());
-        debug!("layout: parsed Node tree");
-        debug!("{:?}", node.dump());
-
-        let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
-
-        {
-            // Reset the image cache.
-            let mut local_image_cache = rw_data.local_image_cache.lock();
-            local_image_cache.next_round(self.make_on_image_available_cb());
-        }
-
-        // true => Do the reflow with full style damage, because content
-        // changed or the window was resized.
-        let mut all_style_damage = match data.damage.level {
-            ContentChangedDocumentDamage => true,
-            _ => false
-        };
-
-        // TODO: Calculate the "actual viewport":
-        // http://www.w3.org/TR/css-device-adapt/#actual-viewport
-        let viewport_size = data.window_size.initial_viewport;
-
-        let current_screen_size = Size2D(Au::from_frac32_px(viewport_size.width.get()),
-                                         Au::from_frac32_px(viewport_size.height.get()));
-        if rw_data.screen_size != current_screen_size {
-            all_style_damage = true
-        }
-        rw_data.screen_size = current_screen_size;
-
-        // Create a layout context for use throughout the following passes.
-        let mut shared_layout_ctx =
-            self.build_shared_layout_context(
-                rw_data.deref(),
-                node,
-                &data.url);
-
+    /// Performs CSS selector matching and flow construction for the nodes that need it, and
+    /// returns the root of the resulting flow tree.
+    fn build_flow_tree(&self,
+                       data: &Reflow,
+                       node: &mut LayoutNode,
+                       rw_data: &mut LayoutTaskData,
+                       shared_layout_ctx: &mut SharedLayoutContext)
+                       -> FlowRef {
         let mut layout_root = profile(time::LayoutStyleRecalcCategory,
                                       Some((&data.url,
                                       data.iframe,
                                       self.first_reflow.get())),
                                       self.time_profiler_chan.clone(),
                                       || {
-            // Perform CSS selector matching and flow construction.
-            let rw_data = rw_data.deref_mut();
             match rw_data.parallel_traversal {
                 None => {
-                    let layout_ctx = LayoutContext::new(&shared_layout_ctx);
+                    let layout_ctx = LayoutContext::new(&*shared_layout_ctx);
                     let mut applicable_declarations = ApplicableDeclarations::new();
                     let mut parent_bf = Some(BloomFilter::new(
                         style::RECOMMENDED_SELECTOR_BLOOM_FILTER_SIZE));
@@ -785,48 +906,220 @@ impl LayoutTask {
                                                    None)
                 }
                 Some(ref mut traversal) => {
-                    parallel::recalc_style_for_subtree(node, &mut shared_layout_ctx, traversal)
+                    parallel::recalc_style_for_subtree(node, &*shared_layout_ctx, traversal)
                 }
             }
 
             self.get_layout_root((*node).clone())
         });
 
+        self.report_style_cache_statistics(data, &shared_layout_ctx.style_cache_statistics);
+
+        // Flows kept from the last reflow may hold quotes too, so keep resolving them until the
+        // whole tree is built again.
+        if shared_layout_ctx.full_reflow {
+            rw_data.has_generated_quotes = false
+        }
+        if shared_layout_ctx.generated_quotes.swap(false, Relaxed) {
+            rw_data.has_generated_quotes = true
+        }
+
         // Verification of the flow tree, which ensures that all nodes were either marked as leaves
         // or as non-leaves. This becomes a no-op in release builds. (It is inconsequential to
         // memory safety but is a useful debugging tool.)
         self.verify_flow_tree(&mut layout_root);
 
-        if self.opts.trace_layout {
-            layout_debug::begin_trace(layout_root.clone());
-        }
+        layout_root
+    }
+
+    /// Reports how often the style caches were hit during the last restyle to the time profiler,
+    /// and starts counting afresh.
+    fn report_style_cache_statistics(&self, data: &Reflow, statistics: &StyleCacheStatistics) {
+        let meta = Some((&data.url, data.iframe, self.first_reflow.get()));
+        profile_cache(time::StyleSharingCacheCategory,
+                      meta,
+                      self.time_profiler_chan.clone(),
+                      statistics.style_sharing_hits.swap(0, Relaxed),
+                      statistics.style_sharing_lookups.swap(0, Relaxed));
+        profile_cache(time::ComputedValuesCacheCategory,
+                      meta,
+                      self.time_profiler_chan.clone(),
+                      statistics.computed_values_hits.swap(0, Relaxed),
+                      statistics.computed_values_lookups.swap(0, Relaxed));
+    }
 
+    /// Works out which flows were damaged and lays them out, along with anything that depends on
+    /// them.
+    fn lay_out_flow_tree(&self,
+                         data: &Reflow,
+                         rw_data: &mut LayoutTaskData,
+                         layout_root: &mut FlowRef,
+                         shared_layout_ctx: &mut SharedLayoutContext) {
         // Propagate damage.
         profile(time::LayoutDamagePropagateCategory, Some((&data.url, data.iframe, self.first_reflow.get())),
                 self.time_profiler_chan.clone(), || {
-            layout_root.get_mut().traverse_preorder(&mut PropagateDamageTraversal {
-                all_style_damage: all_style_damage
-            });
-            layout_root.get_mut().traverse_postorder(&mut ComputeDamageTraversal.clone());
+            layout_root.get_mut().traverse_postorder(&mut ComputeDamageTraversal);
         });
 
+        // Quotes only have to be resolved again if flows were built since the last reflow.
+        if rw_data.has_generated_quotes &&
+                flow::base(layout_root.get()).restyle_damage.contains(ResolveGeneratedContent) {
+            let layout_ctx = LayoutContext::new(&*shared_layout_ctx);
+            generated_content::resolve_quotes(layout_root.get_mut(), &layout_ctx);
+        }
+
         // Perform the primary layout passes over the flow tree to compute the locations of all
         // the boxes.
         profile(time::LayoutMainCategory, Some((&data.url, data.iframe, self.first_reflow.get())),
                 self.time_profiler_chan.clone(), || {
-            let rw_data = rw_data.deref_mut();
             match rw_data.parallel_traversal {
                 None => {
                     // Sequential mode.
-                    let layout_ctx = LayoutContext::new(&shared_layout_ctx);
+                    let layout_ctx = LayoutContext::new(&*shared_layout_ctx);
                     self.solve_constraints(layout_root.get_mut(), &layout_ctx)
                 }
                 Some(_) => {
                     // Parallel mode.
-                    self.solve_constraints_parallel(data, rw_data, &mut layout_root, &mut shared_layout_ctx)
+                    self.solve_constraints_parallel(data, rw_data, layout_root, &*shared_layout_ctx)
                 }
             }
         });
+    }
+
+    /// Lays out the whole document again from scratch, and fails if that gives any flow different
+    /// geometry from the incremental reflow that produced `incremental_root`. Returns the root of
+    /// the new flow tree, which is the one that layout should carry on with.
+    fn verify_incremental_layout(&self,
+                                 data: &Reflow,
+                                 node: &mut LayoutNode,
+                                 rw_data: &mut LayoutTaskData,
+                                 mut incremental_root: FlowRef,
+                                 shared_layout_ctx: &mut SharedLayoutContext)
+                                 -> FlowRef {
+        let mut incremental_geometry = FlowGeometryTraversal {
+            geometry: Vec::new(),
+        };
+        incremental_root.get_mut().traverse_preorder(&mut incremental_geometry);
+        drop(incremental_root);
+
+        // Bloom filters cached from the incremental pass must not be reused.
+        rw_data.generation += 1;
+        shared_layout_ctx.generation = rw_data.generation;
+        shared_layout_ctx.full_reflow = true;
+
+        let mut layout_root = self.build_flow_tree(data, node, rw_data, shared_layout_ctx);
+        self.lay_out_flow_tree(data, rw_data, &mut layout_root, shared_layout_ctx);
+
+        let mut full_geometry = FlowGeometryTraversal {
+            geometry: Vec::new(),
+        };
+        layout_root.get_mut().traverse_preorder(&mut full_geometry);
+
+        for (i, (incremental, full)) in incremental_geometry.geometry
+                                                            .iter()
+                                                            .zip(full_geometry.geometry.iter())
+                                                            .enumerate() {
+            if incremental != full {
+                fail!("incremental layout verification failed: flow {} was {}, but a full reflow \
+                       gives {}", i, incremental, full)
+            }
+        }
+        if incremental_geometry.geometry.len() != full_geometry.geometry.len() {
+            fail!("incremental layout verification failed: there were {} flows, but a full \
+                   reflow gives {}",
+                  incremental_geometry.geometry.len(),
+                  full_geometry.geometry.len())
+        }
+
+        layout_root
+    }
+
+    /// The high-level routine that performs layout tasks.
+    fn handle_reflow<'a>(&'a self, data: &Reflow, possibly_locked_rw_data: &mut Option<MutexGuard<'a, LayoutTaskData>>) {
+        // FIXME: Isolate this transmutation into a "bridge" module.
+        // FIXME(rust#16366): The following line had to be moved because of a
+        // rustc bug. It should be in the next unsafe block.
+        let mut node: JS<Node> = unsafe { JS::from_trusted_node_address(data.document_root) };
+        let node: &mut LayoutNode = unsafe {
+            mem::transmute(&mut node)
+        };
+
+        debug!("layout: received layout request for: {:s}", data.url.serialize());
+        debug!("layout: parsed Node tree");
+        debug!("{:?}", node.dump());
+
+        let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
+
+        {
+            // Reset the image cache.
+            let mut local_image_cache = rw_data.local_image_cache.lock();
+            local_image_cache.next_round(self.make_on_image_available_cb());
+        }
+
+        // TODO: Calculate the "actual viewport":
+        // http://www.w3.org/TR/css-device-adapt/#actual-viewport
+        let viewport_size = data.window_size.initial_viewport;
+
+        // When paginating, the page area is the viewport, whatever the size of the window (CSS 2.1
+        // § 13.2). Frames keep their own size.
+        //
+        // FIXME: The contents of frames are painted by their own render tasks, so they are missing
+        // from the printed pages.
+        let page_geometry = if self.opts.paginate && !data.iframe {
+            Some(PageGeometry::from_page_rule(&rw_data.stylist.page_rule()))
+        } else {
+            None
+        };
+
+        let current_screen_size = match page_geometry {
+            Some(ref page_geometry) => page_geometry.page_area.size,
+            None => Size2D(Au::from_frac32_px(viewport_size.width.get()),
+                           Au::from_frac32_px(viewport_size.height.get())),
+        };
+        let screen_size_changed = rw_data.screen_size != current_screen_size;
+        rw_data.screen_size = current_screen_size;
+        rw_data.device_pixels_per_px = data.window_size.device_pixel_ratio.get();
+
+        // Restyle and rebuild everything if something that any node might depend on changed, or
+        // if script couldn't say which nodes changed. Otherwise, only the dirty nodes need it.
+        //
+        // Where page breaks fall depends on where everything before them is, so paginated documents
+        // are always laid out afresh.
+        let full_reflow = self.first_reflow.get() || screen_size_changed ||
+            rw_data.stylesheets_changed || data.damage.level == ContentChangedDocumentDamage ||
+            page_geometry.is_some();
+        rw_data.stylesheets_changed = false;
+
+        // Create a layout context for use throughout the following passes.
+        let mut shared_layout_ctx =
+            self.build_shared_layout_context(
+                rw_data.deref(),
+                node,
+                &data.url,
+                full_reflow);
+
+        let mut layout_root = self.build_flow_tree(data, node, rw_data.deref_mut(),
+                                                   &mut shared_layout_ctx);
+
+        if self.opts.trace_layout {
+            layout_debug::begin_trace(layout_root.clone());
+        }
+
+        // When paginating, flows move their own content past page breaks as they are laid out.
+        flow::mut_base(layout_root.get_mut()).page_grid =
+            page_geometry.as_ref().map(|page_geometry| {
+                PageGrid::new(page_geometry.page_area.size.height)
+            });
+
+        self.lay_out_flow_tree(data, rw_data.deref_mut(), &mut layout_root, &mut shared_layout_ctx);
+
+        if self.opts.verify_incremental_layout && !full_reflow {
+            layout_root = self.verify_incremental_layout(data,
+                                                         node,
+                                                         rw_data.deref_mut(),
+                                                         layout_root,
+                                                         &mut shared_layout_ctx);
+        }
 
         // Build the display list if necessary, and send it to the renderer.
         if data.goal == ReflowForDisplay {
@@ -861,10 +1154,10 @@ impl LayoutTask {
                     mem::replace(&mut flow::mut_base(layout_root.get_mut()).display_list,
                                  DisplayList::new());
                 root_display_list.debug();
-                let display_list = Arc::new(root_display_list.flatten(ContentStackingLevel));
+                let display_list = Arc::new(root_display_list.flatten(ContentStackingLevel)
+                                                             .build_spatial_index());
 
-                // FIXME(pcwalton): This is really ugly and can't handle overflow: scroll. Refactor
-                // it with extreme prejudice.
+                // FIXME(pcwalton): This is really ugly. Refactor it with extreme prejudice.
                 let mut color = color::rgba(1.0, 1.0, 1.0, 1.0);
                 for child in node.traverse_preorder() {
                     if child.type_id() == Some(ElementNodeTypeId(HTMLHtmlElementTypeId)) ||
@@ -899,6 +1192,8 @@ impl LayoutTask {
                     position: Rect(Point2D(0u, 0u), root_size),
                     background_color: color,
                     scroll_policy: Scrollable,
+                    parent_id: None,
+                    is_scroll_root: false,
                 };
 
                 rw_data.display_list = Some(display_list.clone());
@@ -908,17 +1203,74 @@ impl LayoutTask {
                 // reflow.
                 let mut layers = SmallVec1::new();
                 layers.push(render_layer);
+                rw_data.layer_display_lists = Vec::new();
+                rw_data.scroll_ranges = HashMap::new();
+                let mut scroll_root_sizes = HashMap::new();
                 for layer in mem::replace(&mut flow::mut_base(layout_root.get_mut()).layers,
                                           DList::new()).into_iter() {
+                    if layer.is_scroll_root {
+                        scroll_root_sizes.insert(layer.id, layer.position.size);
+                    } else {
+                        rw_data.layer_display_lists.push(LayerDisplayList {
+                            id: layer.id,
+                            position: layer.position,
+                            display_list: layer.display_list.clone(),
+                        })
+                    }
+
+                    // The first layer nested inside a scroll root holds its scrolled contents.
+                    let scroll_root = layer.parent_id.and_then(|parent_id| {
+                        scroll_root_sizes.find(&parent_id).map(|size| (parent_id, *size))
+                    });
+                    match scroll_root {
+                        Some((parent_id, size)) if
+                                !rw_data.scroll_ranges.contains_key(&parent_id) => {
+                            let content_size = layer.position.size;
+                            let range =
+                                Size2D((content_size.width as f32 - size.width as f32).max(0.0),
+                                       (content_size.height as f32 - size.height as f32).max(0.0));
+                            rw_data.scroll_ranges.insert(parent_id, range);
+                        }
+                        _ => {}
+                    }
                     layers.push(layer)
                 }
 
+                if self.opts.dump_display_list {
+                    println!("{}", serializer::serialize_with_layers(
+                        &*display_list, rw_data.layer_display_lists.as_slice()));
+                }
+
                 debug!("Layout done!");
 
+                // The render task keeps the pages until the compositor asks it to print them.
+                match page_geometry {
+                    Some(ref page_geometry) => {
+                        let pages = pagination::page_rects(
+                            flow::base(layout_root.get()).position.size.block,
+                            page_geometry.page_area.size);
+                        let display_lists = layers.iter().filter(|layer| {
+                            !layer.is_scroll_root
+                        }).map(|layer| layer.display_list.clone()).collect();
+                        self.render_chan.send(PageLayoutMsg(box PageLayout {
+                            display_lists: display_lists,
+                            background_color: color,
+                            page_size: page_geometry.page_size,
+                            page_area_origin: page_geometry.page_area.origin,
+                            pages: pages,
+                        }));
+                    }
+                    None => {}
+                }
+
                 self.render_chan.send(RenderInitMsg(layers));
             });
         }
 
+        // Everything is laid out now, so the next reflow only has to handle whatever changes in
+        // the meantime.
+        layout_root.get_mut().traverse_preorder(&mut ClearDamageTraversal);
+
         self.first_reflow.set(false);
 
         if self.opts.trace_layout {
@@ -993,6 +1345,9 @@ impl LayoutRPC for LayoutRPCImpl {
                     union_boxes_for_node(&mut rect, display_list.iter(), node)
                 }
             }
+            for layer in rw_data.layer_display_lists.iter() {
+                union_boxes_for_node(&mut rect, layer.display_list.iter(), node)
+            }
         }
         ContentBoxResponse(rect.unwrap_or(Rect::zero()))
     }
@@ -1022,107 +1377,103 @@ impl LayoutRPC for LayoutRPCImpl {
                     add_boxes_for_node(&mut boxes, display_list.iter(), node)
                 }
             }
+            for layer in rw_data.layer_display_lists.iter() {
+                add_boxes_for_node(&mut boxes, layer.display_list.iter(), node)
+            }
         }
         ContentBoxesResponse(boxes)
     }
 
-    /// Requests the node containing the point of interest
-    fn hit_test(&self, _: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()> {
-        fn hit_test<'a,I:Iterator<&'a DisplayItem>>(x: Au, y: Au, mut iterator: I)
-                     -> Option<HitTestResponse> {
-            for item in iterator {
-                match *item {
-                    ClipDisplayItemClass(ref cc) => {
-                        if geometry::rect_contains_point(cc.base.bounds, Point2D(x, y)) {
-                            let ret = hit_test(x, y, cc.children.list.iter().rev());
-                            if !ret.is_none() {
-                                return ret
-                            }
-                        }
-                        continue
-                    }
-                    _ => {}
-                }
-
-                let bounds = item.bounds();
-
-                // TODO(tikue): This check should really be performed by a method of
-                // DisplayItem.
-                if x < bounds.origin.x + bounds.size.width &&
-                        bounds.origin.x <= x &&
-                        y < bounds.origin.y + bounds.size.height &&
-                        bounds.origin.y <= y {
-                    return Some(HitTestResponse(item.base()
-                                                    .node
-                                                    .to_untrusted_node_address()))
-                }
+    /// Requests the display lists of the page and its layers as JSON. Before the first reflow,
+    /// both are empty.
+    fn display_list(&self) -> DisplayListResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock();
+        match rw_data.display_list {
+            None => {
+                DisplayListResponse(serializer::serialize_with_layers(&DisplayList::new(), &[]))
+            }
+            Some(ref display_list) => {
+                DisplayListResponse(serializer::serialize_with_layers(
+                    &**display_list, rw_data.layer_display_lists.as_slice()))
             }
-            let ret: Option<HitTestResponse> = None;
-            ret
         }
-        let (x, y) = (Au::from_frac_px(point.x as f64),
-                      Au::from_frac_px(point.y as f64));
+    }
 
+    /// Requests how far the given node's contents can be scrolled, if it scrolls its overflow.
+    fn scroll_range(&self, node: TrustedNodeAddress) -> ScrollRangeResponse {
+        let node: OpaqueNode = OpaqueNodeMethods::from_script_node(node);
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock();
+        let layer_id = LayerId::scroll_root(node.id() as uint);
+        ScrollRangeResponse(rw_data.scroll_ranges.find(&layer_id).map(|range| *range))
+    }
+
+    /// Requests the node containing the point of interest
+    fn hit_test(&self, _: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()> {
+        let point = Point2D(Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64));
         let resp = {
             let &LayoutRPCImpl(ref rw_data) = self;
             let rw_data = rw_data.lock();
-            match rw_data.display_list {
-                None => fail!("no display list!"),
-                Some(ref display_list) => hit_test(x, y, display_list.list.iter().rev()),
-            }
+            // Nothing has been laid out before the first reflow.
+            let display_list = match rw_data.display_list {
+                None => return Err(()),
+                Some(ref display_list) => display_list,
+            };
+
+            // Layers paint on top of the root display list, so check them first.
+            rw_data.layer_display_lists.iter().rev().map(|layer| &layer.display_list)
+                                       .chain(Some(display_list).into_iter())
+                                       .filter_map(|display_list| {
+                display_list.items_at_point(&point).into_iter().next().map(|item| {
+                    HitTestResponse(item.base().node.to_untrusted_node_address())
+                })
+            }).next()
         };
 
-        if resp.is_some() {
-            return Ok(resp.unwrap());
+        match resp {
+            Some(resp) => Ok(resp),
+            None => Err(()),
         }
-        Err(())
     }
 
+    /// Requests the nodes under the mouse, and shows the cursor of the topmost one.
     fn mouse_over(&self, _: TrustedNodeAddress, point: Point2D<f32>) -> Result<MouseOverResponse, ()> {
-        fn mouse_over_test<'a,
-                           I:Iterator<&'a DisplayItem>>(
-                           x: Au,
-                           y: Au,
-                           mut iterator: I,
-                           result: &mut Vec<UntrustedNodeAddress>) {
-            for item in iterator {
-                match *item {
-                    ClipDisplayItemClass(ref cc) => {
-                        mouse_over_test(x, y, cc.children.list.iter().rev(), result);
-                    }
-                    _ => {
-                        let bounds = item.bounds();
-
-                        // TODO(tikue): This check should really be performed by a method
-                        // of DisplayItem.
-                        if x < bounds.origin.x + bounds.size.width &&
-                                bounds.origin.x <= x &&
-                                y < bounds.origin.y + bounds.size.height &&
-                                bounds.origin.y <= y {
-                            result.push(item.base()
-                                            .node
-                                            .to_untrusted_node_address());
-                        }
-                    }
-                }
-            }
-        }
-
         let mut mouse_over_list: Vec<UntrustedNodeAddress> = vec!();
-        let (x, y) = (Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64));
+        let mut cursor = None;
+        let point = Point2D(Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64));
 
         {
             let &LayoutRPCImpl(ref rw_data) = self;
-            let rw_data = rw_data.lock();
-            match rw_data.display_list {
-                None => fail!("no display list!"),
-                Some(ref display_list) => {
-                    mouse_over_test(x,
-                                    y,
-                                    display_list.list.iter().rev(),
-                                    &mut mouse_over_list);
-                }
+            let mut rw_data = rw_data.lock();
+            // Nothing has been laid out before the first reflow.
+            let display_list = match rw_data.display_list {
+                None => return Err(()),
+                Some(ref display_list) => display_list.clone(),
             };
+
+            // Items are visited from the top down, so the first fragment found decides the
+            // cursor.
+            for display_list in rw_data.layer_display_lists.iter().rev()
+                                       .map(|layer| &layer.display_list)
+                                       .chain(Some(&display_list).into_iter()) {
+                for item in display_list.items_at_point(&point).into_iter() {
+                    mouse_over_list.push(item.base().node.to_untrusted_node_address());
+                    match *item {
+                        PseudoDisplayItemClass(ref pseudo) if cursor.is_none() => {
+                            cursor = Some(pseudo.cursor)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            let cursor = cursor.unwrap_or(DefaultCursor);
+            if cursor != rw_data.cursor {
+                rw_data.cursor = cursor;
+                let ConstellationChan(ref constellation_chan) = rw_data.constellation_chan;
+                constellation_chan.send(SetCursorMsg(cursor));
+            }
         }
 
         if mouse_over_list.is_empty() {
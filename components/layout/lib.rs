@@ -50,9 +50,12 @@ pub mod flow;
 pub mod flow_list;
 pub mod flow_ref;
 pub mod fragment;
+pub mod generated_content;
 pub mod layout_task;
 pub mod inline;
 pub mod model;
+pub mod multicol;
+pub mod pagination;
 pub mod parallel;
 pub mod table_wrapper;
 pub mod table;
@@ -0,0 +1,718 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS multi-column layout.
+//!
+//! http://dev.w3.org/csswg/css-multicol/
+//!
+//! The children of a multi-column element are laid out as a block, in a single column as wide as
+//! one column. That column is then cut into pieces with the fragmenter, and the flows and lines in
+//! each piece are moved into the column that shows it. A child that is broken across columns
+//! stays in the column where it starts, and its own children or lines are moved into theirs.
+//! Children with `column-span: all` interrupt the columns: the content before them is balanced
+//! across one row of columns, and the content after them across another.
+
+#![deny(unsafe_block)]
+
+use block::BlockFlow;
+use construct::FlowConstructor;
+use context::LayoutContext;
+use floats::FloatKind;
+use flow::{BlockFlowClass, MulticolFlowClass, FlowClass, Flow, ImmutableFlowUtils};
+use flow;
+use layout_debug;
+use pagination::Fragmenter;
+use util::ToGfxColor;
+use wrapper::ThreadSafeLayoutNode;
+
+use geom::{Point2D, Rect, Size2D};
+use gfx::display_list::{BaseDisplayItem, BlockBackgroundsAndBordersStackingLevel};
+use gfx::display_list::{ClipDisplayItem, ClipDisplayItemClass, ContentStackingLevel};
+use gfx::display_list::{DisplayList, LineDisplayItem, LineDisplayItemClass};
+use servo_msg::compositor_msg::LayerId;
+use servo_util::geometry::Au;
+use servo_util::logical_geometry::LogicalRect;
+use std::cmp::{max, min};
+use std::fmt;
+use std::mem;
+use std::num::Float;
+use style::ComputedValues;
+use style::computed_values::{LPA_Auto, LPA_Length, clear, column_span, float, position};
+
+/// Returns true if an element with the given style is a multi-column element.
+pub fn is_multicol(style: &ComputedValues) -> bool {
+    let column_style = style.get_column();
+    column_style.column_count.is_some() || column_style.column_width.is_some()
+}
+
+/// The used gap between columns. `normal` is 1em.
+fn column_gap(style: &ComputedValues) -> Au {
+    match style.get_column().column_gap {
+        Some(gap) => gap,
+        None => style.get_font().font_size,
+    }
+}
+
+/// Returns the used number of columns and their inline-size, given the inline-size available for
+/// them, per CSS Multi-column Layout § 3.4.
+fn used_column_count_and_inline_size(available_inline_size: Au,
+                                     column_count: Option<u32>,
+                                     column_inline_size: Option<Au>,
+                                     gap: Au)
+                                     -> (uint, Au) {
+    let column_count = match (column_count, column_inline_size) {
+        (Some(count), None) => count as uint,
+        (count, Some(inline_size)) if inline_size + gap > Au(0) => {
+            let fitting_count = (available_inline_size + gap).to_subpx() /
+                (inline_size + gap).to_subpx();
+            let fitting_count = fitting_count.floor().max(1.0) as uint;
+            match count {
+                Some(count) => min(count as uint, fitting_count),
+                None => fitting_count,
+            }
+        }
+        // Columns with neither an inline-size nor a gap would fit any number of times.
+        (Some(count), Some(_)) => count as uint,
+        (None, _) => 1,
+    };
+    let column_count = max(column_count, 1);
+    let inline_size = (available_inline_size + gap) / (column_count as i32) - gap;
+    (column_count, max(inline_size, Au(0)))
+}
+
+/// Returns the offset from the single column that the children of a multi-column element are laid
+/// out in to the given column.
+///
+/// TODO: Columns always run from left to right.
+fn column_inline_offset(column_inline_size: Au, gap: Au, column_index: uint) -> Au {
+    (column_inline_size + gap) * (column_index as i32)
+}
+
+/// Returns true if `kid` is a spanner: an in-flow block with `column-span: all`.
+///
+/// TODO: Only children of the multi-column element can span it for now.
+fn is_spanner(kid: &mut Flow) -> bool {
+    if !kid.is_block_like() || kid.is_float() || kid.is_absolutely_positioned() {
+        return false
+    }
+    kid.as_block().fragment.style().get_column().column_span == column_span::all
+}
+
+/// A piece of a multi-column element: either a row of columns, or a spanner.
+struct ColumnSegment {
+    /// The children in this segment, as a range of indices.
+    first_kid: uint,
+    kid_count: uint,
+    /// Whether this segment is a spanner rather than a row of columns.
+    is_spanner: bool,
+    /// Where the segment is painted, as block offsets from our block-start border edge.
+    block_start: Au,
+    block_size: Au,
+    /// The part of the single column that our children were laid out in that each column shows,
+    /// as a start and an end position. A spanner shows all of itself, in one column as wide as
+    /// the content box.
+    columns: Vec<(Au, Au)>,
+}
+
+impl ColumnSegment {
+    /// Returns the index of the column that shows the content at `position` in the single column.
+    fn column_at(&self, position: Au) -> uint {
+        self.columns.iter().rposition(|&(start, _)| start <= position).unwrap_or(0)
+    }
+}
+
+/// Moves the content of one segment from the single column into its columns.
+struct ColumnPlacement<'a> {
+    segment: &'a ColumnSegment,
+    column_inline_size: Au,
+    gap: Au,
+}
+
+impl<'a> ColumnPlacement<'a> {
+    /// Returns how far content moves to be in the given column, as an inline and a block offset.
+    fn column_offset(&self, column_index: uint) -> (Au, Au) {
+        let (column_start, _) = self.segment.columns[column_index];
+        (column_inline_offset(self.column_inline_size, self.gap, column_index),
+         self.segment.block_start - column_start)
+    }
+
+    /// Moves `flow`, whose block-start edge is at `source_position` in the single column, into the
+    /// column that shows that edge. `parent_offset` is how far the parent of `flow` has already
+    /// been moved. If `flow` does not fit in that column, its lines or children are moved into
+    /// their own columns too.
+    fn move_flow(&self,
+                 flow: &mut Flow,
+                 source_position: Au,
+                 parent_offset: (Au, Au),
+                 layout_context: &LayoutContext) {
+        let column_index = self.segment.column_at(source_position);
+        let (inline_offset, block_offset) = self.column_offset(column_index);
+        {
+            let (parent_inline_offset, parent_block_offset) = parent_offset;
+            let (inline_delta, block_delta) = (inline_offset - parent_inline_offset,
+                                               block_offset - parent_block_offset);
+            let base = flow::mut_base(flow);
+            base.position.start.i = base.position.start.i + inline_delta;
+            base.position.start.b = base.position.start.b + block_delta;
+            base.overflow.start.i = base.overflow.start.i + inline_delta;
+            base.overflow.start.b = base.overflow.start.b + block_delta;
+        }
+
+        let (_, column_end) = self.segment.columns[column_index];
+        if source_position + flow::base(flow).position.size.block <= column_end {
+            return
+        }
+
+        if flow.is_inline_flow() {
+            flow.as_inline().move_lines(|line_position| {
+                let line_column_index = self.segment.column_at(source_position + line_position);
+                let (line_inline_offset, line_block_offset) =
+                    self.column_offset(line_column_index);
+                (line_inline_offset - inline_offset, line_block_offset - block_offset)
+            })
+        } else if flow.class() == BlockFlowClass {
+            // TODO: The backgrounds and borders of a block that is broken across columns are only
+            // painted in the column where it starts.
+            for kid in flow::mut_base(flow).child_iter() {
+                if kid.is_absolutely_positioned() {
+                    continue
+                }
+                let kid_source_position = source_position + flow::base(kid).position.start.b;
+                self.move_flow(kid, kid_source_position, (inline_offset, block_offset),
+                               layout_context)
+            }
+            flow.store_overflow(layout_context)
+        }
+    }
+}
+
+/// A multi-column element.
+pub struct MulticolFlow {
+    pub block_flow: BlockFlow,
+
+    /// The used number of columns.
+    pub column_count: uint,
+
+    /// The used inline-size of each column.
+    pub column_inline_size: Au,
+
+    /// The used gap between columns.
+    pub column_gap: Au,
+
+    /// The rows of columns and spanners that our children are painted in, in order.
+    segments: Vec<ColumnSegment>,
+}
+
+impl MulticolFlow {
+    fn from_block_flow(block_flow: BlockFlow) -> MulticolFlow {
+        MulticolFlow {
+            block_flow: block_flow,
+            column_count: 1,
+            column_inline_size: Au(0),
+            column_gap: Au(0),
+            segments: vec!(),
+        }
+    }
+
+    pub fn from_node(constructor: &mut FlowConstructor, node: &ThreadSafeLayoutNode)
+                     -> MulticolFlow {
+        MulticolFlow::from_block_flow(BlockFlow::from_node(constructor, node))
+    }
+
+    pub fn float_from_node(constructor: &mut FlowConstructor,
+                           node: &ThreadSafeLayoutNode,
+                           float_kind: FloatKind)
+                           -> MulticolFlow {
+        MulticolFlow::from_block_flow(BlockFlow::float_from_node(constructor, node, float_kind))
+    }
+
+    /// Returns how many columns the children in the given range need if each column is
+    /// `column_block_size` tall, and which part of the content each of them shows.
+    fn fragment_into_columns(&mut self,
+                             first_kid: uint,
+                             kid_count: uint,
+                             source_start: Au,
+                             source_end: Au,
+                             column_block_size: Au)
+                             -> Vec<(Au, Au)> {
+        let mut fragmenter = Fragmenter::new(column_block_size, source_start);
+        for kid in self.block_flow.base.child_iter().skip(first_kid).take(kid_count) {
+            let kid_block_start = flow::base(kid).position.start.b;
+            fragmenter.fragment_flow(kid, kid_block_start)
+        }
+        fragmenter.finish(source_end)
+    }
+
+    /// Finds the shortest columns that fit the children in the given range into at most
+    /// `column_count` columns, to within a pixel.
+    fn balance_columns(&mut self,
+                       first_kid: uint,
+                       kid_count: uint,
+                       source_start: Au,
+                       source_end: Au)
+                       -> Vec<(Au, Au)> {
+        if source_end <= source_start {
+            return vec!()
+        }
+
+        // One column as tall as all the content always fits it. No column count can fit it into
+        // columns shorter than an equal share.
+        let content_block_size = source_end - source_start;
+        let mut too_short = max(content_block_size / (self.column_count as i32) - Au(1), Au(0));
+        let mut tall_enough = content_block_size;
+        while tall_enough - too_short > Au::from_px(1) {
+            let candidate = too_short + (tall_enough - too_short) / 2;
+            let columns = self.fragment_into_columns(first_kid,
+                                                     kid_count,
+                                                     source_start,
+                                                     source_end,
+                                                     candidate);
+            if columns.len() <= self.column_count {
+                tall_enough = candidate
+            } else {
+                too_short = candidate
+            }
+        }
+        self.fragment_into_columns(first_kid, kid_count, source_start, source_end, tall_enough)
+    }
+
+    /// Splits our children into rows of balanced columns, separated by spanners, once they have
+    /// been laid out in a single column. Returns how much taller or shorter that makes our
+    /// content.
+    fn assign_segments(&mut self) -> Au {
+        let content_block_start = self.block_flow.fragment.border_padding.block_start;
+
+        // Where the row of columns that is being gathered starts, in the single column and in
+        // our box.
+        let mut source_position = content_block_start;
+        let mut position = content_block_start;
+
+        let mut segments = vec!();
+        let mut first_kid = 0;
+        let kid_count = self.block_flow.base.children.len();
+        for index in range(0, kid_count + 1) {
+            let spanner_extent = if index == kid_count {
+                None
+            } else {
+                let kid = self.block_flow.base.child_iter().nth(index).unwrap();
+                if is_spanner(kid) {
+                    let kid_base = flow::base(kid);
+                    Some((kid_base.position.start.b, kid_base.position.size.block))
+                } else {
+                    None
+                }
+            };
+            if index < kid_count && spanner_extent.is_none() {
+                continue
+            }
+
+            // Balance the children since the last spanner.
+            let mut source_end = source_position;
+            for kid in self.block_flow.base.child_iter().skip(first_kid).take(index - first_kid) {
+                if !kid.is_absolutely_positioned() {
+                    let kid_base = flow::base(kid);
+                    source_end = max(source_end,
+                                     kid_base.position.start.b + kid_base.position.size.block)
+                }
+            }
+            let columns = self.balance_columns(first_kid,
+                                               index - first_kid,
+                                               source_position,
+                                               source_end);
+            let block_size = columns.iter().fold(Au(0), |block_size, &(start, end)| {
+                max(block_size, end - start)
+            });
+            segments.push(ColumnSegment {
+                first_kid: first_kid,
+                kid_count: index - first_kid,
+                is_spanner: false,
+                block_start: position,
+                block_size: block_size,
+                columns: columns,
+            });
+            position = position + block_size;
+
+            // Then place the spanner below them, keeping the margins between them.
+            match spanner_extent {
+                None => source_position = source_end,
+                Some((spanner_start, spanner_block_size)) => {
+                    position = position + spanner_start - source_end;
+                    segments.push(ColumnSegment {
+                        first_kid: index,
+                        kid_count: 1,
+                        is_spanner: true,
+                        block_start: position,
+                        block_size: spanner_block_size,
+                        columns: vec!((spanner_start, spanner_start + spanner_block_size)),
+                    });
+                    position = position + spanner_block_size;
+                    source_position = spanner_start + spanner_block_size;
+                    first_kid = index + 1;
+                }
+            }
+        }
+
+        self.segments = segments;
+        position - source_position
+    }
+
+    /// Moves our children from the single column that they were laid out in into the columns and
+    /// spanners that show them.
+    fn move_kids_into_columns(&mut self, layout_context: &LayoutContext) {
+        for segment in self.segments.iter() {
+            if segment.columns.is_empty() {
+                continue
+            }
+            let placement = ColumnPlacement {
+                segment: segment,
+                column_inline_size: self.column_inline_size,
+                gap: self.column_gap,
+            };
+            for kid in self.block_flow
+                           .base
+                           .child_iter()
+                           .skip(segment.first_kid)
+                           .take(segment.kid_count) {
+                if kid.is_absolutely_positioned() {
+                    continue
+                }
+                let kid_source_position = flow::base(kid).position.start.b;
+                placement.move_flow(kid, kid_source_position, (Au(0), Au(0)), layout_context)
+            }
+        }
+    }
+
+    /// Builds the display items for the rules between columns. Rules go between columns that
+    /// both have content.
+    fn build_column_rules(&self, content_box: &Rect<Au>) -> DisplayList {
+        let mut display_list = DisplayList::new();
+        let style = self.block_flow.fragment.style();
+        let column_style = style.get_column();
+        if column_style.column_rule_width == Au(0) {
+            return display_list
+        }
+
+        let color = style.resolve_color(column_style.column_rule_color).to_gfx_color();
+        for segment in self.segments.iter() {
+            if segment.is_spanner {
+                continue
+            }
+            for column_index in range(1, segment.columns.len()) {
+                // Center the rule in the gap.
+                let x = content_box.origin.x +
+                    column_inline_offset(self.column_inline_size, self.column_gap, column_index) -
+                    self.column_gap / 2;
+                let bounds = Rect(Point2D(x - column_style.column_rule_width / 2,
+                                          content_box.origin.y + segment.block_start),
+                                  Size2D(column_style.column_rule_width, segment.block_size));
+                display_list.push(LineDisplayItemClass(box LineDisplayItem {
+                    base: BaseDisplayItem::new(bounds,
+                                               self.block_flow.fragment.node,
+                                               BlockBackgroundsAndBordersStackingLevel),
+                    color: color,
+                    style: column_style.column_rule_style,
+                }))
+            }
+        }
+        display_list
+    }
+
+    /// Returns the rectangles that the columns of each row are clipped to, halfway into the gaps,
+    /// so that content that overflows a column can be seen up to that point. Spanners are not
+    /// clipped.
+    fn column_clip_rects(&self, columns_box: &Rect<Au>) -> Vec<Rect<Au>> {
+        let mut clip_rects = vec!();
+        for segment in self.segments.iter().filter(|segment| !segment.is_spanner) {
+            for column_index in range(0, segment.columns.len()) {
+                let inline_offset =
+                    column_inline_offset(self.column_inline_size, self.column_gap, column_index);
+                clip_rects.push(Rect(Point2D(columns_box.origin.x + inline_offset -
+                                             self.column_gap / 2,
+                                             columns_box.origin.y + segment.block_start),
+                                     Size2D(self.column_inline_size + self.column_gap,
+                                            segment.block_size)))
+            }
+        }
+        clip_rects
+    }
+
+    /// Clips the display items of our children, which layout has moved into their columns, to
+    /// those columns, then builds the display list for this flow as a block.
+    pub fn build_display_list_multicol(&mut self, layout_context: &LayoutContext) {
+        // FIXME(#2795): Get the real container size
+        let container_size = Size2D::zero();
+        let writing_mode = self.block_flow.base.writing_mode;
+        let relative_offset =
+            self.block_flow.fragment.relative_position(&self.block_flow
+                                                            .base
+                                                            .absolute_position_info
+                                                            .relative_containing_block_size);
+        let origin =
+            self.block_flow.base.abs_position.add_size(&relative_offset.to_physical(writing_mode));
+
+        // Columns are laid out from the block-start edge of the border box, but from the
+        // inline-start edge of the content box.
+        let content_box = self.block_flow.fragment.content_box();
+        let border_box = self.block_flow.fragment.border_box;
+        let columns_box = LogicalRect::new(writing_mode,
+                                           content_box.start.i,
+                                           border_box.start.b,
+                                           content_box.size.inline,
+                                           border_box.size.block);
+        let columns_box = columns_box.to_physical(writing_mode, container_size);
+        let columns_box = Rect(columns_box.origin + origin, columns_box.size);
+
+        let node = self.block_flow.fragment.node;
+        let clip_rects = self.column_clip_rects(&columns_box);
+        let mut column_rules = Some(self.build_column_rules(&columns_box));
+        for kid in self.block_flow.base.child_iter() {
+            if kid.is_absolutely_positioned() {
+                continue
+            }
+
+            let kid_display_list = mem::replace(&mut flow::mut_base(kid).display_list,
+                                                DisplayList::new());
+
+            // Rules are painted below the children.
+            let mut display_list = column_rules.take().unwrap_or_else(|| DisplayList::new());
+            let mut column_display_lists = Vec::from_fn(clip_rects.len(), |_| DisplayList::new());
+            for item in kid_display_list.list.into_iter() {
+                let origin = item.bounds().origin;
+                let column_index = clip_rects.iter().position(|clip_rect| {
+                    origin.x >= clip_rect.origin.x && origin.x < clip_rect.max_x() &&
+                        origin.y >= clip_rect.origin.y && origin.y < clip_rect.max_y()
+                });
+                match column_index {
+                    Some(column_index) => column_display_lists.get_mut(column_index).push(item),
+                    None => display_list.push(item),
+                }
+            }
+            for (clip_rect, column_display_list) in
+                    clip_rects.iter().zip(column_display_lists.into_iter()) {
+                if column_display_list.list.len() == 0 {
+                    continue
+                }
+                display_list.push(ClipDisplayItemClass(box ClipDisplayItem::new(
+                    BaseDisplayItem::new(*clip_rect, node, ContentStackingLevel),
+                    column_display_list)))
+            }
+            flow::mut_base(kid).display_list = display_list
+        }
+
+        self.block_flow.build_display_list_block(layout_context)
+    }
+}
+
+impl Flow for MulticolFlow {
+    fn class(&self) -> FlowClass {
+        MulticolFlowClass
+    }
+
+    fn as_multicol<'a>(&'a mut self) -> &'a mut MulticolFlow {
+        self
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        &self.block_flow
+    }
+
+    fn float_clearance(&self) -> clear::T {
+        self.block_flow.float_clearance()
+    }
+
+    fn float_kind(&self) -> float::T {
+        self.block_flow.float_kind()
+    }
+
+    /// The intrinsic inline-sizes of a multi-column element with a fixed number of columns are
+    /// those of that many columns and the gaps between them.
+    fn bubble_inline_sizes(&mut self, ctx: &LayoutContext) {
+        self.block_flow.bubble_inline_sizes(ctx);
+
+        let (column_count, gap) = {
+            let style = self.block_flow.fragment.style();
+            match style.get_box().width {
+                LPA_Length(_) => return,
+                _ => {}
+            }
+            (style.get_column().column_count, column_gap(style))
+        };
+        match column_count {
+            None => {}
+            Some(column_count) => {
+                let column_count = column_count as i32;
+                let intrinsic_inline_sizes = &mut self.block_flow.base.intrinsic_inline_sizes;
+                intrinsic_inline_sizes.minimum_inline_size =
+                    intrinsic_inline_sizes.minimum_inline_size * column_count +
+                    gap * (column_count - 1);
+                intrinsic_inline_sizes.preferred_inline_size =
+                    intrinsic_inline_sizes.preferred_inline_size * column_count +
+                    gap * (column_count - 1);
+            }
+        }
+    }
+
+    /// Sizes the multi-column element as a block, then gives each child the inline-size of a
+    /// column, or of the whole content box if it is a spanner.
+    fn assign_inline_sizes(&mut self, ctx: &LayoutContext) {
+        let _scope = layout_debug_scope!("multicol::assign_inline_sizes {:s}",
+                                         self.block_flow.base.debug_id());
+        self.block_flow.assign_inline_sizes(ctx);
+
+        let inline_start_content_edge = self.block_flow.fragment.border_box.start.i +
+            self.block_flow.fragment.border_padding.inline_start;
+        let content_inline_size = self.block_flow.fragment.border_box.size.inline -
+            self.block_flow.fragment.border_padding.inline_start_end();
+
+        let (column_count, column_inline_size, gap) = {
+            let style = self.block_flow.fragment.style();
+            let gap = column_gap(style);
+            let (column_count, column_inline_size) =
+                used_column_count_and_inline_size(content_inline_size,
+                                                  style.get_column().column_count,
+                                                  style.get_column().column_width,
+                                                  gap);
+            (column_count, column_inline_size, gap)
+        };
+        self.column_count = column_count;
+        self.column_inline_size = column_inline_size;
+        self.column_gap = gap;
+
+        self.block_flow.propagate_assigned_inline_size_to_children(inline_start_content_edge,
+                                                                   column_inline_size,
+                                                                   None);
+        for kid in self.block_flow.base.child_iter() {
+            if is_spanner(kid) {
+                flow::mut_base(kid).position.size.inline = content_inline_size
+            }
+        }
+    }
+
+    fn assign_block_size_for_inorder_child_if_necessary<'a>(&mut self,
+                                                            layout_context: &'a LayoutContext<'a>)
+                                                            -> bool {
+        self.block_flow.assign_block_size_for_inorder_child_if_necessary(layout_context)
+    }
+
+    /// Lays the children out in a single column, then balances that column across the columns
+    /// and grows or shrinks to fit them.
+    fn assign_block_size<'a>(&mut self, ctx: &'a LayoutContext<'a>) {
+        let _scope = layout_debug_scope!("multicol::assign_block_size {:s}",
+                                         self.block_flow.base.debug_id());
+        self.block_flow.assign_block_size(ctx);
+
+        let block_size_delta = self.assign_segments();
+        self.move_kids_into_columns(ctx);
+        match self.block_flow.fragment.style().content_block_size() {
+            LPA_Auto => {}
+            _ => return,
+        }
+        self.block_flow.fragment.border_box.size.block =
+            self.block_flow.fragment.border_box.size.block + block_size_delta;
+        if !self.block_flow.is_absolutely_positioned() {
+            self.block_flow.base.position.size.block =
+                self.block_flow.base.position.size.block + block_size_delta;
+        }
+    }
+
+    fn compute_absolute_position(&mut self) {
+        self.block_flow.compute_absolute_position()
+    }
+
+    fn mark_as_root(&mut self) {
+        self.block_flow.mark_as_root()
+    }
+
+    fn is_store_overflow_delayed(&mut self) -> bool {
+        self.block_flow.is_store_overflow_delayed()
+    }
+
+    fn is_root(&self) -> bool {
+        self.block_flow.is_root()
+    }
+
+    fn is_float(&self) -> bool {
+        self.block_flow.is_float()
+    }
+
+    fn positioning(&self) -> position::T {
+        self.block_flow.positioning()
+    }
+
+    fn is_root_of_absolute_flow_tree(&self) -> bool {
+        self.block_flow.is_root_of_absolute_flow_tree()
+    }
+
+    fn generated_containing_block_rect(&self) -> LogicalRect<Au> {
+        self.block_flow.generated_containing_block_rect()
+    }
+
+    fn layer_id(&self, fragment_index: uint) -> LayerId {
+        self.block_flow.layer_id(fragment_index)
+    }
+
+    fn is_absolute_containing_block(&self) -> bool {
+        self.block_flow.is_absolute_containing_block()
+    }
+
+    fn update_late_computed_inline_position_if_necessary(&mut self, inline_position: Au) {
+        self.block_flow.update_late_computed_inline_position_if_necessary(inline_position)
+    }
+
+    fn update_late_computed_block_position_if_necessary(&mut self, block_position: Au) {
+        self.block_flow.update_late_computed_block_position_if_necessary(block_position)
+    }
+}
+
+impl fmt::Show for MulticolFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MulticolFlow({} columns): {}", self.column_count, self.block_flow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::used_column_count_and_inline_size;
+
+    use servo_util::geometry::Au;
+
+    fn px(px: int) -> Au {
+        Au::from_px(px)
+    }
+
+    #[test]
+    fn test_used_column_count_and_inline_size() {
+        // A column count alone divides the available space, less the gaps.
+        assert_eq!(used_column_count_and_inline_size(px(300), Some(3), None, px(0)),
+                   (3, px(100)));
+        assert_eq!(used_column_count_and_inline_size(px(320), Some(3), None, px(10)),
+                   (3, px(100)));
+
+        // A column width fits as many columns as it can, and they stretch to fill the space.
+        assert_eq!(used_column_count_and_inline_size(px(320), None, Some(px(100)), px(10)),
+                   (3, px(100)));
+        assert_eq!(used_column_count_and_inline_size(px(350), None, Some(px(100)), px(10)),
+                   (3, px(110)));
+
+        // With both, the count is a maximum.
+        assert_eq!(used_column_count_and_inline_size(px(320), Some(2), Some(px(100)), px(10)),
+                   (2, px(155)));
+        assert_eq!(used_column_count_and_inline_size(px(320), Some(5), Some(px(100)), px(10)),
+                   (3, px(100)));
+
+        // There is always at least one column, even if it is narrower than the column width.
+        assert_eq!(used_column_count_and_inline_size(px(50), None, Some(px(100)), px(10)),
+                   (1, px(50)));
+
+        // Columns with neither a width nor a gap fit any number of times.
+        assert_eq!(used_column_count_and_inline_size(px(300), Some(4), Some(px(0)), px(0)),
+                   (4, px(75)));
+        assert_eq!(used_column_count_and_inline_size(px(300), None, Some(px(0)), px(0)),
+                   (1, px(300)));
+    }
+}
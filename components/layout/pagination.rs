@@ -0,0 +1,582 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Fragmentation of flows into pages, for paged media (CSS 2.1 § 13), and into columns, for
+//! multi-column elements.
+//!
+//! When paginating, flows break themselves across pages as the block-size traversal lays them out.
+//! Every flow is handed a `PageGrid` by its parent, as it is handed floats, and lays its kids out
+//! in order against it. A block moves a kid to the next page when `page-break-before` or
+//! `page-break-after` forces a break, or when the kid must not be broken and would straddle one;
+//! an inline flow moves its line boxes the same way, honoring `orphans` and `widows` where it can
+//! (§ 13.3.3). The document then runs on from page to page with every page the same size, and
+//! out-of-flow content is positioned against the paginated flows afterward.
+//!
+//! Columns are different: their contents are laid out once, in a single column, and the fragmenter
+//! then decides where each column starts.
+
+use flow::{Flow, InlineFlowClass, MulticolFlowClass, TableColGroupFlowClass, TableRowFlowClass};
+use flow;
+use inline::InlineFlow;
+use model;
+
+use geom::{Point2D, Rect, Size2D};
+use servo_util::geometry::Au;
+use std::cmp::{max, min};
+use style::PageRule;
+use style::computed_values::{LengthOrPercentage, page_break_after, page_break_before};
+use style::computed_values::page_break_inside;
+
+/// The size of the page box when `@page` does not give one: US Letter, in portrait orientation.
+static DEFAULT_PAGE_WIDTH_PX: int = 816;
+static DEFAULT_PAGE_HEIGHT_PX: int = 1056;
+
+/// The margin on each side of the page box when `@page` does not give one: half an inch.
+static DEFAULT_PAGE_MARGIN_PX: int = 48;
+
+/// The size of the page box, and where the page area that shows the content sits inside it.
+pub struct PageGeometry {
+    pub page_size: Size2D<Au>,
+    pub page_area: Rect<Au>,
+}
+
+impl PageGeometry {
+    pub fn from_page_rule(page_rule: &PageRule) -> PageGeometry {
+        let default_size = Size2D(Au::from_px(DEFAULT_PAGE_WIDTH_PX),
+                                  Au::from_px(DEFAULT_PAGE_HEIGHT_PX));
+        let page_size = match page_rule.size {
+            Some(ref size) => size.resolve(default_size),
+            None => default_size,
+        };
+
+        // Percentages refer to the width of the page box for the left and right margins, and to
+        // its height for the top and bottom ones.
+        let margin = |value: Option<LengthOrPercentage>, reference_length: Au| {
+            match value {
+                Some(value) => model::specified(value, reference_length),
+                None => Au::from_px(DEFAULT_PAGE_MARGIN_PX),
+            }
+        };
+        let top = margin(page_rule.margin.top, page_size.height);
+        let right = margin(page_rule.margin.right, page_size.width);
+        let bottom = margin(page_rule.margin.bottom, page_size.height);
+        let left = margin(page_rule.margin.left, page_size.width);
+
+        PageGeometry {
+            page_size: page_size,
+            page_area: Rect(Point2D(left, top),
+                            Size2D(max(page_size.width - left - right, Au(0)),
+                                   max(page_size.height - top - bottom, Au(0)))),
+        }
+    }
+}
+
+/// The pages that a flow is laid out across when paginating. Positions are block offsets from the
+/// block-start border edge of the flow.
+#[deriving(Clone, PartialEq, Show)]
+pub struct PageGrid {
+    /// The block size of the page area of every page.
+    page_block_size: Au,
+    /// The position at which some page starts.
+    page_start: Au,
+}
+
+impl PageGrid {
+    /// Creates the grid of a flow that starts a page.
+    pub fn new(page_block_size: Au) -> PageGrid {
+        PageGrid {
+            // Guard against pages with no room, which would otherwise never fill up.
+            page_block_size: max(page_block_size, Au::from_px(1)),
+            page_start: Au(0),
+        }
+    }
+
+    /// Returns the grid of a kid whose block-start border edge is at `position`.
+    pub fn translate(&self, position: Au) -> PageGrid {
+        PageGrid {
+            page_block_size: self.page_block_size,
+            page_start: self.page_start - position,
+        }
+    }
+
+    /// Returns where the page that `position` is on starts.
+    fn page_start_at(&self, position: Au) -> Au {
+        let (Au(offset), Au(page_block_size)) = (position - self.page_start, self.page_block_size);
+        let page_index = if offset >= 0 {
+            offset / page_block_size
+        } else {
+            (offset + 1) / page_block_size - 1
+        };
+        self.page_start + Au(page_index * page_block_size)
+    }
+
+    /// Returns where the page that `position` is on ends, which is where the next page starts.
+    pub fn page_end_at(&self, position: Au) -> Au {
+        self.page_start_at(position) + self.page_block_size
+    }
+
+    /// Returns where content that must start a page, and would otherwise start at `position`,
+    /// goes. A forced break never leaves a page empty.
+    pub fn break_before(&self, position: Au) -> Au {
+        if self.page_start_at(position) == position {
+            position
+        } else {
+            self.page_end_at(position)
+        }
+    }
+
+    /// Returns where content that must not be broken across pages, and that would otherwise run
+    /// from `start` to `end`, goes: to the start of the next page if it would straddle a page
+    /// break and fits on one page, and where it is otherwise.
+    pub fn avoid_break_inside(&self, start: Au, end: Au) -> Au {
+        let page_end = self.page_end_at(start);
+        if end > page_end && end - start <= self.page_block_size {
+            page_end
+        } else {
+            start
+        }
+    }
+
+    /// Returns how far each line box of an inline flow moves down so that none of them straddles a
+    /// page break, given where they start and end. A line that is taller than a page stays where
+    /// it is.
+    pub fn line_offsets(&self,
+                        line_tops: &[Au],
+                        line_bottoms: &[Au],
+                        orphans: uint,
+                        widows: uint)
+                        -> Vec<Au> {
+        let mut offsets = Vec::from_elem(line_tops.len(), Au(0));
+        let mut moved_line_tops = line_tops.to_vec();
+        let mut offset = Au(0);
+        let mut first_line_on_page = 0;
+        let mut current_page_start = match line_tops.head() {
+            Some(&line_top) => self.page_start_at(line_top),
+            None => return offsets,
+        };
+
+        for line_index in range(0, line_tops.len()) {
+            let line_top = line_tops[line_index] + offset;
+            let line_bottom = line_bottoms[line_index] + offset;
+            moved_line_tops[line_index] = line_top;
+            if self.page_start_at(line_top) != current_page_start {
+                current_page_start = self.page_start_at(line_top);
+                first_line_on_page = line_index;
+            }
+
+            let page_end = self.page_end_at(line_top);
+            if line_bottom > page_end && line_bottom - line_top <= self.page_block_size {
+                // This line starts the next page, perhaps along with some lines before it.
+                let first_line_on_next_page = choose_line_break(moved_line_tops.as_slice(),
+                                                                first_line_on_page,
+                                                                line_index,
+                                                                current_page_start,
+                                                                orphans,
+                                                                widows);
+                offset = offset + page_end - moved_line_tops[first_line_on_next_page];
+                for moved_line_index in range(first_line_on_next_page, line_index + 1) {
+                    moved_line_tops[moved_line_index] = line_tops[moved_line_index] + offset;
+                    offsets[moved_line_index] = offset;
+                }
+                current_page_start = page_end;
+                first_line_on_page = first_line_on_next_page;
+                continue
+            }
+            offsets[line_index] = offset;
+        }
+        offsets
+    }
+}
+
+/// Returns whether `flow` forces a page break before itself, whether it forces one after itself,
+/// and whether it must not be broken across pages. Every page is treated alike, so `left` and
+/// `right` only force a break.
+pub fn page_break_properties(flow: &mut Flow) -> (bool, bool, bool) {
+    match flow.class() {
+        InlineFlowClass | TableColGroupFlowClass => return (false, false, false),
+        _ => {}
+    }
+
+    let (break_before, break_after, break_inside) = {
+        let box_style = flow.as_block().fragment.style().get_box();
+        (box_style.page_break_before, box_style.page_break_after, box_style.page_break_inside)
+    };
+    let forces_break_before = match break_before {
+        page_break_before::always | page_break_before::left | page_break_before::right => true,
+        page_break_before::auto | page_break_before::avoid => false,
+    };
+    let forces_break_after = match break_after {
+        page_break_after::always | page_break_after::left | page_break_after::right => true,
+        page_break_after::auto | page_break_after::avoid => false,
+    };
+
+    // Table rows are never split, since their cells would have to be split in step. Neither are
+    // multi-column elements, whose children are not laid out where they are painted.
+    let is_monolithic = match flow.class() {
+        TableRowFlowClass | MulticolFlowClass => true,
+        _ => false,
+    };
+    (forces_break_before,
+     forces_break_after,
+     is_monolithic || break_inside == page_break_inside::avoid)
+}
+
+/// Chooses where columns start, walking the flow tree in document order. Positions are block
+/// offsets from the block-start border edge of the flow that is being fragmented.
+pub struct Fragmenter {
+    /// The block size of each fragmentainer.
+    fragmentainer_block_size: Au,
+    /// The position at which each fragmentainer starts.
+    fragment_starts: Vec<Au>,
+}
+
+impl Fragmenter {
+    /// Creates a fragmenter whose first fragmentainer starts at `start`.
+    pub fn new(fragmentainer_block_size: Au, start: Au) -> Fragmenter {
+        Fragmenter {
+            // Guard against a fragmentainer with no room, which would otherwise never fill up.
+            fragmentainer_block_size: max(fragmentainer_block_size, Au::from_px(1)),
+            fragment_starts: vec!(start),
+        }
+    }
+
+    fn current_fragment_start(&self) -> Au {
+        *self.fragment_starts.last().unwrap()
+    }
+
+    fn current_fragment_end(&self) -> Au {
+        self.current_fragment_start() + self.fragmentainer_block_size
+    }
+
+    /// Starts a new fragmentainer at `position`, unless that would leave the current one empty.
+    fn break_at(&mut self, position: Au) {
+        // Fragmentainers never hold more than their block size, even when a flow that is taller
+        // than that asks for a break after itself.
+        self.advance_to(position);
+        if position > self.current_fragment_start() {
+            self.fragment_starts.push(position)
+        }
+    }
+
+    /// Cuts fragmentainers off wherever they run out, until `position` is in the current one.
+    /// This is the fallback for content that offers no better place to break.
+    fn advance_to(&mut self, position: Au) {
+        while position >= self.current_fragment_end() {
+            let fragment_end = self.current_fragment_end();
+            self.fragment_starts.push(fragment_end)
+        }
+    }
+
+    /// Chooses breaks inside `flow` and its descendants. `block_start` is the position of the
+    /// block-start border edge of `flow`.
+    pub fn fragment_flow(&mut self, flow: &mut Flow, block_start: Au) {
+        // Absolutely-positioned flows are out of flow, and appear in whichever fragmentainers
+        // they overlap.
+        if flow.is_absolutely_positioned() {
+            return
+        }
+
+        match flow.class() {
+            InlineFlowClass => return self.fragment_lines(flow.as_inline(), block_start),
+            TableColGroupFlowClass => return,
+            _ => {}
+        }
+
+        let block_end = block_start + flow::base(flow).position.size.block;
+
+        self.advance_to(block_start);
+
+        // Page breaks don't force column breaks.
+        let (_, _, avoids_break_inside) = page_break_properties(flow);
+        if avoids_break_inside {
+            // Move the whole flow to the next fragmentainer if it would fit there, but not if it
+            // is taller than a fragmentainer anyway.
+            if block_end > self.current_fragment_end() &&
+                    block_end - block_start <= self.fragmentainer_block_size {
+                self.break_at(block_start)
+            }
+        }
+
+        match flow.class() {
+            TableRowFlowClass | MulticolFlowClass => {}
+            _ => {
+                for kid in flow::child_iter(flow) {
+                    let kid_block_start = block_start + flow::base(kid).position.start.b;
+                    self.fragment_flow(kid, kid_block_start)
+                }
+            }
+        }
+    }
+
+    fn fragment_lines(&mut self, flow: &mut InlineFlow, block_start: Au) {
+        if flow.lines.is_empty() || flow.fragments.fragments.is_empty() {
+            return
+        }
+
+        // `orphans` and `widows` are inherited, so every fragment has the containing block's.
+        let (orphans, widows) = {
+            let inherited_box_style = flow.fragments.fragments[0].style().get_inheritedbox();
+            (inherited_box_style.orphans as uint, inherited_box_style.widows as uint)
+        };
+
+        let line_tops: Vec<Au> = flow.lines.iter().map(|line| {
+            block_start + line.bounds.start.b
+        }).collect();
+        let line_bottoms: Vec<Au> = flow.lines.iter().map(|line| {
+            block_start + line.bounds.start.b + line.bounds.size.block
+        }).collect();
+
+        let line_count = flow.lines.len();
+        for line_index in range(0, line_count) {
+            self.advance_to(line_tops[line_index]);
+            if line_bottoms[line_index] <= self.current_fragment_end() {
+                continue
+            }
+
+            // This line does not fit, so it has to start the next fragmentainer. The first line
+            // that is already in this one bounds how far back the break can move.
+            let fragment_start = self.current_fragment_start();
+            let first_line_in_fragment = range(0, line_index).find(|&index| {
+                line_tops[index] >= fragment_start
+            }).unwrap_or(line_index);
+            let first_line_in_next_fragment = choose_line_break(line_tops.as_slice(),
+                                                                first_line_in_fragment,
+                                                                line_index,
+                                                                fragment_start,
+                                                                orphans,
+                                                                widows);
+            self.break_at(line_tops[first_line_in_next_fragment])
+        }
+    }
+
+    /// Finishes fragmenting content that ends at `end`. Whatever follows the last break runs on
+    /// over as many fragmentainers as it needs. Returns the part of the content that each
+    /// fragmentainer shows, in order, as a start and an end position.
+    pub fn finish(mut self, end: Au) -> Vec<(Au, Au)> {
+        if end > self.fragment_starts[0] {
+            self.advance_to(end - Au(1));
+        }
+
+        let fragmentainer_block_size = self.fragmentainer_block_size;
+        let mut fragment_starts = self.fragment_starts;
+        while fragment_starts.len() > 1 && *fragment_starts.last().unwrap() >= end {
+            fragment_starts.pop();
+        }
+
+        range(0, fragment_starts.len()).map(|index| {
+            let start = fragment_starts[index];
+            let fragment_end = if index + 1 < fragment_starts.len() {
+                fragment_starts[index + 1]
+            } else {
+                min(start + fragmentainer_block_size, max(end, start))
+            };
+            (start, fragment_end)
+        }).collect()
+    }
+}
+
+/// Returns the index of the line that starts the next fragmentainer, given that the line at
+/// `line_index` does not fit in the current one, whose first line is `first_line_in_fragment`.
+fn choose_line_break(line_tops: &[Au],
+                     first_line_in_fragment: uint,
+                     line_index: uint,
+                     fragment_start: Au,
+                     orphans: uint,
+                     widows: uint)
+                     -> uint {
+    let line_count = line_tops.len();
+
+    // Take lines from this fragmentainer until `widows` of them go to the next one...
+    let mut first_line_in_next_fragment = line_index;
+    if line_count - first_line_in_next_fragment < widows {
+        first_line_in_next_fragment = if line_count >= widows {
+            max(line_count - widows, first_line_in_fragment)
+        } else {
+            first_line_in_fragment
+        };
+    }
+
+    // ...but leave either none or at least `orphans` of them behind.
+    if first_line_in_next_fragment - first_line_in_fragment < orphans {
+        first_line_in_next_fragment = first_line_in_fragment
+    }
+
+    // If that leaves nothing in this fragmentainer, neither can be honored.
+    if line_tops[first_line_in_next_fragment] <= fragment_start {
+        first_line_in_next_fragment = line_index
+    }
+    first_line_in_next_fragment
+}
+
+/// Returns the part of the document that each page shows, given the block size of the document,
+/// which runs on from page to page.
+pub fn page_rects(document_block_size: Au, page_area_size: Size2D<Au>) -> Vec<Rect<Au>> {
+    let Au(document_block_size) = document_block_size;
+    let Au(page_block_size) = max(page_area_size.height, Au::from_px(1));
+    let page_count = max((document_block_size + page_block_size - 1) / page_block_size, 1);
+    range(0, page_count).map(|index| {
+        Rect(Point2D(Au(0), Au(page_block_size * index)), page_area_size)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{Point2D, Rect, Size2D};
+    use geom::side_offsets::SideOffsets2D;
+    use servo_util::geometry::Au;
+    use style::computed_values::{LP_Length, LP_Percentage};
+    use style::{ExplicitPageSize, PageRule};
+    use super::{Fragmenter, PageGeometry, PageGrid, choose_line_break, page_rects};
+
+    fn px(px: int) -> Au {
+        Au::from_px(px)
+    }
+
+    #[test]
+    fn test_page_geometry_defaults() {
+        let page_rule = PageRule {
+            size: None,
+            margin: SideOffsets2D::new(None, None, None, None),
+        };
+        let page_geometry = PageGeometry::from_page_rule(&page_rule);
+        assert_eq!(page_geometry.page_size, Size2D(px(816), px(1056)));
+        assert_eq!(page_geometry.page_area,
+                   Rect(Point2D(px(48), px(48)), Size2D(px(720), px(960))));
+    }
+
+    #[test]
+    fn test_page_geometry_from_page_rule() {
+        let page_rule = PageRule {
+            size: Some(ExplicitPageSize(Size2D(px(400), px(200)))),
+            margin: SideOffsets2D::new(Some(LP_Length(px(10))),
+                                       Some(LP_Percentage(0.25)),
+                                       Some(LP_Percentage(0.25)),
+                                       None),
+        };
+        let page_geometry = PageGeometry::from_page_rule(&page_rule);
+        assert_eq!(page_geometry.page_size, Size2D(px(400), px(200)));
+
+        // Left and right percentages refer to the width of the page box, top and bottom ones to
+        // its height.
+        assert_eq!(page_geometry.page_area,
+                   Rect(Point2D(px(48), px(10)), Size2D(px(252), px(140))));
+    }
+
+    #[test]
+    fn test_page_geometry_margins_wider_than_page() {
+        let page_rule = PageRule {
+            size: Some(ExplicitPageSize(Size2D(px(50), px(50)))),
+            margin: SideOffsets2D::new(None, None, None, None),
+        };
+        let page_geometry = PageGeometry::from_page_rule(&page_rule);
+        assert_eq!(page_geometry.page_area.size, Size2D(Au(0), Au(0)));
+    }
+
+    #[test]
+    fn test_fragmenter_breaks() {
+        let mut fragmenter = Fragmenter::new(px(100), Au(0));
+
+        // Content that offers no better place to break is cut where the page runs out.
+        fragmenter.advance_to(px(250));
+        assert_eq!(fragmenter.current_fragment_start(), px(200));
+
+        // A forced break starts a page, but never leaves one empty.
+        fragmenter.break_at(px(230));
+        fragmenter.break_at(px(230));
+        assert_eq!(fragmenter.fragment_starts, vec!(Au(0), px(100), px(200), px(230)));
+
+        assert_eq!(fragmenter.finish(px(300)), vec!((Au(0), px(100)),
+                                                    (px(100), px(200)),
+                                                    (px(200), px(230)),
+                                                    (px(230), px(300))));
+    }
+
+    #[test]
+    fn test_fragmenter_break_after_tall_content() {
+        // A break after content taller than a fragmentainer still leaves none of them taller than
+        // that.
+        let mut fragmenter = Fragmenter::new(px(100), Au(0));
+        fragmenter.break_at(px(150));
+        assert_eq!(fragmenter.fragment_starts, vec!(Au(0), px(100), px(150)));
+    }
+
+    #[test]
+    fn test_fragmenter_without_room() {
+        let fragmenter = Fragmenter::new(Au(0), Au(0));
+        assert_eq!(fragmenter.finish(px(2)), vec!((Au(0), px(1)), (px(1), px(2))));
+
+        let fragmenter = Fragmenter::new(px(100), Au(0));
+        assert_eq!(fragmenter.finish(Au(0)), vec!((Au(0), Au(0))));
+    }
+
+    #[test]
+    fn test_choose_line_break() {
+        // Ten lines, 10px each, of which the seventh does not fit on the page.
+        let line_tops: Vec<Au> = range(0, 10i).map(|index| px(index * 10)).collect();
+        let line_tops = line_tops.as_slice();
+
+        assert_eq!(choose_line_break(line_tops, 0, 6, Au(0), 1, 1), 6);
+
+        // Keeping five widows together moves the sixth line along with them.
+        assert_eq!(choose_line_break(line_tops, 0, 6, Au(0), 1, 5), 5);
+
+        // Seven orphans can't stay behind, so the whole paragraph moves to the next page...
+        assert_eq!(choose_line_break(line_tops, 0, 6, px(-10), 7, 1), 0);
+
+        // ...unless it already starts this one, when neither can be honored.
+        assert_eq!(choose_line_break(line_tops, 0, 6, Au(0), 7, 1), 6);
+
+        // When the page starts at the third line, taking five widows leaves three orphans, but
+        // taking seven would leave just one.
+        assert_eq!(choose_line_break(line_tops, 2, 6, px(15), 2, 5), 5);
+        assert_eq!(choose_line_break(line_tops, 2, 6, px(15), 2, 7), 2);
+    }
+
+    #[test]
+    fn test_page_grid() {
+        // A kid that starts 250px into a flow whose first page starts at its border edge.
+        let page_grid = PageGrid::new(px(100)).translate(px(250));
+        assert_eq!(page_grid.page_end_at(Au(0)), px(50));
+        assert_eq!(page_grid.page_end_at(px(-60)), px(-50));
+        assert_eq!(page_grid.page_end_at(px(50)), px(150));
+
+        // Forced breaks move content to the next page, unless it already starts one.
+        assert_eq!(page_grid.break_before(px(10)), px(50));
+        assert_eq!(page_grid.break_before(px(50)), px(50));
+
+        // Content that must not be broken moves if it fits on the next page.
+        assert_eq!(page_grid.avoid_break_inside(px(10), px(40)), px(10));
+        assert_eq!(page_grid.avoid_break_inside(px(10), px(60)), px(50));
+        assert_eq!(page_grid.avoid_break_inside(px(10), px(200)), px(10));
+    }
+
+    #[test]
+    fn test_line_offsets() {
+        // Ten lines, 10px each, on pages 65px tall: the seventh line straddles the break.
+        let page_grid = PageGrid::new(px(65));
+        let line_tops: Vec<Au> = range(0, 10i).map(|index| px(index * 10)).collect();
+        let line_bottoms: Vec<Au> = range(0, 10i).map(|index| px(index * 10 + 10)).collect();
+        let offsets = page_grid.line_offsets(line_tops.as_slice(), line_bottoms.as_slice(), 1, 1);
+        assert_eq!(offsets, vec!(Au(0), Au(0), Au(0), Au(0), Au(0), Au(0),
+                                 px(5), px(5), px(5), px(5)));
+
+        // Keeping five widows together moves the sixth line along with them.
+        let offsets = page_grid.line_offsets(line_tops.as_slice(), line_bottoms.as_slice(), 1, 5);
+        assert_eq!(offsets, vec!(Au(0), Au(0), Au(0), Au(0), Au(0),
+                                 px(15), px(15), px(15), px(15), px(15)));
+
+        // A line taller than a page stays where it is.
+        let offsets = page_grid.line_offsets(&[px(10)], &[px(100)], 1, 1);
+        assert_eq!(offsets, vec!(Au(0)));
+    }
+
+    #[test]
+    fn test_page_rects() {
+        assert_eq!(page_rects(px(150), Size2D(px(300), px(100))),
+                   vec!(Rect(Point2D(Au(0), Au(0)), Size2D(px(300), px(100))),
+                        Rect(Point2D(Au(0), px(100)), Size2D(px(300), px(100)))));
+        assert_eq!(page_rects(Au(0), Size2D(px(300), px(100))),
+                   vec!(Rect(Point2D(Au(0), Au(0)), Size2D(px(300), px(100)))));
+    }
+}
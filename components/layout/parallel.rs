@@ -7,7 +7,7 @@
 //! This code is highly unsafe. Keep this file small and easy to audit.
 
 use css::matching::{ApplicableDeclarations, CannotShare, MatchMethods, StyleWasShared};
-use construct::FlowConstructor;
+use construct::{FlowConstructor, needs_flow_construction};
 use context::{LayoutContext, SharedLayoutContext};
 use flow::{Flow, MutableFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal};
 use flow;
@@ -179,18 +179,20 @@ trait ParallelPreorderFlowTraversal : PreorderFlowTraversal {
             // Get a real flow.
             let flow: &mut FlowRef = mem::transmute(&unsafe_flow);
 
-            // Perform the appropriate traversal.
-            self.process(flow.get_mut());
-
-            // Possibly enqueue the children.
-            for kid in flow::child_iter(flow.get_mut()) {
-                had_children = true;
-                proxy.push(WorkUnit {
-                    fun: top_down_func,
-                    data: borrowed_flow_to_unsafe_flow(kid),
-                });
+            // Perform the appropriate traversal, unless this whole subtree can be skipped. In that
+            // case the bottom-up traversal picks up right here.
+            if !self.should_prune(flow.get_mut()) {
+                self.process(flow.get_mut());
+
+                // Possibly enqueue the children.
+                for kid in flow::child_iter(flow.get_mut()) {
+                    had_children = true;
+                    proxy.push(WorkUnit {
+                        fun: top_down_func,
+                        data: borrowed_flow_to_unsafe_flow(kid),
+                    });
+                }
             }
-
         }
 
         // If there were no more children, start assigning block-sizes.
@@ -335,46 +337,55 @@ fn recalc_style_for_node(mut unsafe_layout_node: UnsafeLayoutNode,
     // Get the style bloom filter.
     let bf = take_task_local_bloom_filter(parent_opt, &layout_context);
 
-    // First, check to see whether we can share a style with someone.
-    let style_sharing_candidate_cache = layout_context.style_sharing_candidate_cache();
-    let sharing_result = unsafe {
-        node.share_style_if_possible(style_sharing_candidate_cache,
-                                     parent_opt.clone())
-    };
-
     // Just needs to be wrapped in an option for `match_node`.
     let some_bf = Some(bf);
 
-    // Otherwise, match and cascade selectors.
-    match sharing_result {
-        CannotShare(mut shareable) => {
-            let mut applicable_declarations = ApplicableDeclarations::new();
+    // Nodes that haven't changed since the last reflow keep their style.
+    if layout_context.shared.full_reflow || node.is_dirty() {
+        // First, check to see whether we can share a style with someone.
+        let style_sharing_candidate_cache = layout_context.style_sharing_candidate_cache();
+        let sharing_result = unsafe {
+            node.share_style_if_possible(style_sharing_candidate_cache,
+                                         &layout_context.shared.style_cache_statistics,
+                                         parent_opt.clone())
+        };
 
-            if node.is_element() {
-                // Perform the CSS selector matching.
-                let stylist = unsafe { &*layout_context.shared.stylist };
-                node.match_node(stylist, &some_bf, &mut applicable_declarations, &mut shareable);
-            }
+        // Otherwise, match and cascade selectors.
+        match sharing_result {
+            CannotShare(mut shareable) => {
+                let mut applicable_declarations = ApplicableDeclarations::new();
 
-            // Perform the CSS cascade.
-            unsafe {
-                node.cascade_node(parent_opt,
-                                  &applicable_declarations,
-                                  layout_context.applicable_declarations_cache());
-            }
+                if node.is_element() {
+                    // Perform the CSS selector matching.
+                    let stylist = unsafe { &*layout_context.shared.stylist };
+                    node.match_node(stylist, &some_bf, &mut applicable_declarations, &mut shareable);
+                }
+
+                // Perform the CSS cascade.
+                unsafe {
+                    node.cascade_node(parent_opt,
+                                      &applicable_declarations,
+                                      layout_context.applicable_declarations_cache(),
+                                      &layout_context.shared.style_cache_statistics);
+                }
 
-            // Add ourselves to the LRU cache.
-            if shareable {
-                style_sharing_candidate_cache.insert_if_possible(&node);
+                // Add ourselves to the LRU cache.
+                if shareable {
+                    style_sharing_candidate_cache.insert_if_possible(&node);
+                }
             }
+            StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
         }
-        StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
     }
 
-    // Prepare for flow construction by counting the node's children and storing that count.
+    // Prepare for flow construction by counting the kids that have to be visited and storing
+    // that count. Kids whose flows can be reused from the last reflow are skipped.
+    node.dirty_kids_affected_by_siblings(unsafe { &*layout_context.shared.stylist });
     let mut child_count = 0u;
-    for _ in node.children() {
-        child_count += 1;
+    for kid in node.children() {
+        if needs_flow_construction(&ThreadSafeLayoutNode::new(&kid), &layout_context) {
+            child_count += 1;
+        }
     }
     if child_count != 0 {
         let mut layout_data_ref = node.mutate_layout_data();
@@ -403,10 +414,12 @@ fn recalc_style_for_node(mut unsafe_layout_node: UnsafeLayoutNode,
     if child_count != 0 {
         // Enqueue kids.
         for kid in node.children() {
-            proxy.push(WorkUnit {
-                fun: recalc_style_for_node,
-                data: layout_node_to_unsafe_layout_node(&kid),
-            });
+            if needs_flow_construction(&ThreadSafeLayoutNode::new(&kid), &layout_context) {
+                proxy.push(WorkUnit {
+                    fun: recalc_style_for_node,
+                    data: layout_node_to_unsafe_layout_node(&kid),
+                });
+            }
         }
     } else {
         // If we got here, we're a leaf. Start construction of flows for this node.
@@ -430,6 +443,7 @@ fn construct_flows<'a>(unsafe_layout_node: &mut UnsafeLayoutNode,
             let mut flow_constructor = FlowConstructor::new(layout_context);
             flow_constructor.process(&ThreadSafeLayoutNode::new(&node));
         }
+        node.clear_dirty_bits();
 
         // Reset the count of children for the next traversal.
         //
@@ -12,6 +12,7 @@ use construct::FlowConstructor;
 use context::LayoutContext;
 use floats::FloatKind;
 use flow::{TableFlowClass, FlowClass, Flow, ImmutableFlowUtils};
+use flow;
 use fragment::Fragment;
 use layout_debug;
 use table_wrapper::{TableLayout, FixedLayout, AutoLayout};
@@ -39,6 +40,9 @@ pub struct TableFlow {
     /// Column pref inline-sizes.
     pub col_pref_inline_sizes: Vec<Au>,
 
+    /// Whether each column has `visibility: collapse`, as given by the column groups.
+    pub collapsed_columns: Vec<bool>,
+
     /// Table-layout property
     pub table_layout: TableLayout,
 }
@@ -59,6 +63,7 @@ impl TableFlow {
             col_inline_sizes: vec!(),
             col_min_inline_sizes: vec!(),
             col_pref_inline_sizes: vec!(),
+            collapsed_columns: vec!(),
             table_layout: table_layout
         }
     }
@@ -78,6 +83,7 @@ impl TableFlow {
             col_inline_sizes: vec!(),
             col_min_inline_sizes: vec!(),
             col_pref_inline_sizes: vec!(),
+            collapsed_columns: vec!(),
             table_layout: table_layout
         }
     }
@@ -98,10 +104,25 @@ impl TableFlow {
             col_inline_sizes: vec!(),
             col_min_inline_sizes: vec!(),
             col_pref_inline_sizes: vec!(),
+            collapsed_columns: vec!(),
             table_layout: table_layout
         }
     }
 
+    /// Empties the column sizes, and the column collapsing if there is any, that a table part
+    /// gathered in an earlier reflow. Flows can be kept from one reflow to the next, and
+    /// `bubble_inline_sizes` gathers these from the kids again.
+    pub fn reset_columns(col_sizes: &mut [&mut Vec<Au>],
+                         collapsed_columns: Option<&mut Vec<bool>>) {
+        for col_sizes in col_sizes.iter_mut() {
+            col_sizes.truncate(0)
+        }
+        match collapsed_columns {
+            Some(collapsed_columns) => collapsed_columns.truncate(0),
+            None => {}
+        }
+    }
+
     /// Update the corresponding value of self_inline-sizes if a value of kid_inline-sizes has larger value
     /// than one of self_inline-sizes.
     pub fn update_col_inline_sizes(self_inline_sizes: &mut Vec<Au>, kid_inline_sizes: &Vec<Au>) -> Au {
@@ -121,6 +142,41 @@ impl TableFlow {
         sum_inline_sizes
     }
 
+    /// Zeroes the given column inline-sizes of the columns with `visibility: collapse`, which take
+    /// no space per CSS 2.1 § 17.5.5, and returns the total inline-size taken away.
+    fn collapse_columns(col_inline_sizes: &mut Vec<Au>, collapsed_columns: &[bool]) -> Au {
+        let mut collapsed_inline_size = Au(0);
+        for (col_inline_size, &collapsed) in col_inline_sizes.iter_mut()
+                                                             .zip(collapsed_columns.iter()) {
+            if collapsed {
+                collapsed_inline_size = collapsed_inline_size + *col_inline_size;
+                *col_inline_size = Au(0);
+            }
+        }
+        collapsed_inline_size
+    }
+
+    fn is_column_collapsed(&self, index: uint) -> bool {
+        self.collapsed_columns.as_slice().get(index).map_or(false, |&collapsed| collapsed)
+    }
+
+    /// Tells each cell whether it lies in a collapsed column, so that it is not painted.
+    ///
+    /// This is done from the table rather than from the rows because only the table knows which
+    /// columns are collapsed. It happens before the rows and cells assign their own inline-sizes.
+    fn mark_cells_in_collapsed_columns(&mut self) {
+        let collapsed_columns = self.collapsed_columns.as_slice();
+        for kid in self.block_flow.base.child_iter() {
+            if kid.is_table_rowgroup() {
+                for row in flow::mut_base(kid).child_iter() {
+                    mark_cells_of_row_in_collapsed_columns(row, collapsed_columns)
+                }
+            } else if kid.is_table_row() {
+                mark_cells_of_row_in_collapsed_columns(kid, collapsed_columns)
+            }
+        }
+    }
+
     /// Assign block-size for table flow.
     ///
     /// TODO(#2014, pcwalton): This probably doesn't handle margin collapse right.
@@ -175,6 +231,11 @@ impl Flow for TableFlow {
         let _scope = layout_debug_scope!("table::bubble_inline_sizes {:s}",
                                             self.block_flow.base.debug_id());
 
+        TableFlow::reset_columns(&mut [&mut self.col_inline_sizes,
+                                       &mut self.col_min_inline_sizes,
+                                       &mut self.col_pref_inline_sizes],
+                                 Some(&mut self.collapsed_columns));
+
         let mut min_inline_size = Au(0);
         let mut pref_inline_size = Au(0);
         let mut did_first_row = false;
@@ -186,6 +247,8 @@ impl Flow for TableFlow {
                 self.col_inline_sizes.push_all(kid.as_table_colgroup().inline_sizes.as_slice());
                 self.col_min_inline_sizes = self.col_inline_sizes.clone();
                 self.col_pref_inline_sizes = self.col_inline_sizes.clone();
+                self.collapsed_columns
+                    .push_all(kid.as_table_colgroup().collapsed_columns.as_slice());
             } else if kid.is_table_rowgroup() || kid.is_table_row() {
                 // read column inline-sizes from table-row-group/table-row, and assign
                 // inline-size=0 for the columns not defined in column-group
@@ -239,6 +302,17 @@ impl Flow for TableFlow {
             }
         }
 
+        match self.table_layout {
+            FixedLayout => {}
+            AutoLayout => {
+                let collapsed_columns = self.collapsed_columns.as_slice();
+                min_inline_size = min_inline_size -
+                    TableFlow::collapse_columns(&mut self.col_min_inline_sizes, collapsed_columns);
+                pref_inline_size = pref_inline_size -
+                    TableFlow::collapse_columns(&mut self.col_pref_inline_sizes, collapsed_columns);
+            }
+        }
+
         let fragment_intrinsic_inline_sizes =
             self.block_flow.fragment.intrinsic_inline_sizes(layout_context);
         self.block_flow.base.intrinsic_inline_sizes.minimum_inline_size = min_inline_size;
@@ -260,7 +334,10 @@ impl Flow for TableFlow {
 
         let mut num_unspecified_inline_sizes = 0;
         let mut total_column_inline_size = Au::new(0);
-        for col_inline_size in self.col_inline_sizes.iter() {
+        for (i, col_inline_size) in self.col_inline_sizes.iter().enumerate() {
+            if self.is_column_collapsed(i) {
+                continue
+            }
             if *col_inline_size == Au::new(0) {
                 num_unspecified_inline_sizes += 1;
             } else {
@@ -295,6 +372,9 @@ impl Flow for TableFlow {
             }
             _ => {}
         }
+        TableFlow::collapse_columns(&mut self.col_inline_sizes,
+                                    self.collapsed_columns.as_slice());
+        self.mark_cells_in_collapsed_columns();
 
         // As tables are always wrapped inside a table wrapper, they are never impacted by floats.
         self.block_flow.base.flags.set_impacted_by_left_floats(false);
@@ -325,6 +405,13 @@ impl Flow for TableFlow {
     }
 }
 
+fn mark_cells_of_row_in_collapsed_columns(row: &mut Flow, collapsed_columns: &[bool]) {
+    for (i, cell) in flow::mut_base(row).child_iter().enumerate() {
+        cell.as_table_cell().in_collapsed_column =
+            collapsed_columns.get(i).map_or(false, |&collapsed| collapsed)
+    }
+}
+
 impl fmt::Show for TableFlow {
     /// Outputs a debugging string describing this table flow.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -15,6 +15,7 @@ use layout_debug;
 use table::InternalTable;
 use wrapper::ThreadSafeLayoutNode;
 
+use gfx::display_list::DisplayList;
 use servo_util::geometry::Au;
 use std::fmt;
 
@@ -23,12 +24,16 @@ use std::fmt;
 pub struct TableCellFlow {
     /// Data common to all flows.
     pub block_flow: BlockFlow,
+
+    /// Whether this cell lies in a column with `visibility: collapse`. Such cells are not painted.
+    pub in_collapsed_column: bool,
 }
 
 impl TableCellFlow {
     pub fn from_node_and_fragment(node: &ThreadSafeLayoutNode, fragment: Fragment) -> TableCellFlow {
         TableCellFlow {
-            block_flow: BlockFlow::from_node_and_fragment(node, fragment)
+            block_flow: BlockFlow::from_node_and_fragment(node, fragment),
+            in_collapsed_column: false,
         }
     }
 
@@ -42,7 +47,9 @@ impl TableCellFlow {
 
     /// Assign block-size for table-cell flow.
     ///
-    /// TODO(#2015, pcwalton): This doesn't handle floats right.
+    /// Table cells establish block formatting contexts (CSS 2.1 § 9.4.1), so the floats inside
+    /// are contained within the cell's block size. The enclosing row keeps them from leaking into
+    /// neighbouring cells.
     ///
     /// inline(always) because this is only ever called by in-order or non-in-order top-level
     /// methods
@@ -53,6 +60,12 @@ impl TableCellFlow {
 
     pub fn build_display_list_table_cell(&mut self, layout_context: &LayoutContext) {
         debug!("build_display_list_table: same process as block flow");
+        if self.in_collapsed_column {
+            // FIXME: Absolutely positioned descendants whose containing block is outside the cell
+            // still get painted.
+            self.block_flow.base.display_list = DisplayList::new();
+            return
+        }
         self.block_flow.build_display_list_block(layout_context)
     }
 }
@@ -11,10 +11,12 @@ use flow::{BaseFlow, TableColGroupFlowClass, FlowClass, Flow};
 use fragment::{Fragment, TableColumnFragment};
 use layout_debug;
 use model::{MaybeAuto};
+use table::TableFlow;
 use wrapper::ThreadSafeLayoutNode;
 
 use servo_util::geometry::Au;
 use std::fmt;
+use style::computed_values::visibility;
 
 /// A table formatting context.
 pub struct TableColGroupFlow {
@@ -29,6 +31,9 @@ pub struct TableColGroupFlow {
 
     /// The specified inline-sizes of table columns
     pub inline_sizes: Vec<Au>,
+
+    /// Whether each table column has `visibility: collapse`.
+    pub collapsed_columns: Vec<bool>,
 }
 
 impl TableColGroupFlow {
@@ -40,6 +45,7 @@ impl TableColGroupFlow {
             fragment: Some(fragment),
             cols: fragments,
             inline_sizes: vec!(),
+            collapsed_columns: vec!(),
         }
     }
 }
@@ -57,6 +63,8 @@ impl Flow for TableColGroupFlow {
         let _scope = layout_debug_scope!("table_colgroup::bubble_inline_sizes {:s}",
                                             self.base.debug_id());
 
+        TableFlow::reset_columns(&mut [&mut self.inline_sizes], Some(&mut self.collapsed_columns));
+
         for fragment in self.cols.iter() {
             // get the specified value from inline-size property
             let inline_size = MaybeAuto::from_style(fragment.style().content_inline_size(),
@@ -66,8 +74,10 @@ impl Flow for TableColGroupFlow {
                 TableColumnFragment(col_fragment) => col_fragment.span.unwrap_or(1),
                 _ => fail!("Other fragment come out in TableColGroupFlow. {:?}", fragment.specific)
             };
+            let collapsed = fragment.style().get_inheritedbox().visibility == visibility::collapse;
             for _ in range(0, span) {
                 self.inline_sizes.push(inline_size);
+                self.collapsed_columns.push(collapsed);
             }
         }
     }
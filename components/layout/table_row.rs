@@ -10,17 +10,19 @@ use block::BlockFlow;
 use block::ISizeAndMarginsComputer;
 use construct::FlowConstructor;
 use context::LayoutContext;
+use floats::Floats;
 use flow::{TableRowFlowClass, FlowClass, Flow, ImmutableFlowUtils};
 use flow;
 use fragment::Fragment;
 use layout_debug;
-use table::InternalTable;
+use table::{InternalTable, TableFlow};
 use model::{MaybeAuto, Specified, Auto};
 use wrapper::ThreadSafeLayoutNode;
 
 use servo_util::geometry::Au;
 use std::cmp::max;
 use std::fmt;
+use style::computed_values::visibility;
 
 /// A table formatting context.
 #[deriving(Encodable)]
@@ -72,7 +74,11 @@ impl TableRowFlow {
 
     /// Assign block-size for table-row flow.
     ///
-    /// TODO(pcwalton): This doesn't handle floats and positioned elements right.
+    /// Table cells establish block formatting contexts, so floats neither flow into them from
+    /// outside nor escape from them; this flow gives each cell an empty set of floats and
+    /// propagates none to its own successors.
+    ///
+    /// TODO(pcwalton): This doesn't handle positioned elements right.
     ///
     /// inline(always) because this is only ever called by in-order or non-in-order top-level
     /// methods
@@ -84,7 +90,9 @@ impl TableRowFlow {
 
         // Per CSS 2.1 § 17.5.3, find max_y = max( computed `block-size`, minimum block-size of all cells )
         let mut max_y = Au::new(0);
+        let writing_mode = self.block_flow.base.writing_mode;
         for kid in self.block_flow.base.child_iter() {
+            flow::mut_base(kid).floats = Floats::new(writing_mode);
             kid.assign_block_size_for_inorder_child_if_necessary(layout_context);
 
             {
@@ -107,6 +115,12 @@ impl TableRowFlow {
             Auto => block_size,
             Specified(value) => max(value, block_size)
         };
+
+        // A collapsed row takes no space, per CSS 2.1 § 17.5.5. Its cells still count towards the
+        // column inline-sizes.
+        if self.block_flow.fragment.style().get_inheritedbox().visibility == visibility::collapse {
+            block_size = Au(0)
+        }
         // cur_y = cur_y + block-size;
 
         // Assign the block-size of own fragment
@@ -116,6 +130,7 @@ impl TableRowFlow {
         position.size.block = block_size;
         self.block_flow.fragment.border_box = position;
         self.block_flow.base.position.size.block = block_size;
+        self.block_flow.base.floats = Floats::new(writing_mode);
 
         // Assign the block-size of kid fragments, which is the same value as own block-size.
         for kid in self.block_flow.base.child_iter() {
@@ -175,6 +190,11 @@ impl Flow for TableRowFlow {
         let _scope = layout_debug_scope!("table_row::bubble_inline_sizes {:s}",
                                             self.block_flow.base.debug_id());
 
+        TableFlow::reset_columns(&mut [&mut self.col_inline_sizes,
+                                       &mut self.col_min_inline_sizes,
+                                       &mut self.col_pref_inline_sizes],
+                                 None);
+
         let mut min_inline_size = Au(0);
         let mut pref_inline_size = Au(0);
         /* find the specified inline_sizes from child table-cell contexts */
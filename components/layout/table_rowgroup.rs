@@ -10,6 +10,7 @@ use block::BlockFlow;
 use block::ISizeAndMarginsComputer;
 use construct::FlowConstructor;
 use context::LayoutContext;
+use floats::Floats;
 use flow::{TableRowGroupFlowClass, FlowClass, Flow, ImmutableFlowUtils};
 use flow;
 use fragment::Fragment;
@@ -71,7 +72,8 @@ impl TableRowGroupFlow {
 
     /// Assign block-size for table-rowgroup flow.
     ///
-    /// FIXME(pcwalton): This doesn't handle floats right.
+    /// Floats can't escape from table cells, so rows neither receive nor propagate any; this flow
+    /// gives each row an empty set of floats and propagates none to its own successors.
     ///
     /// inline(always) because this is only ever called by in-order or non-in-order top-level
     /// methods
@@ -81,7 +83,9 @@ impl TableRowGroupFlow {
 
         let mut cur_y = block_start_offset;
 
+        let writing_mode = self.block_flow.base.writing_mode;
         for kid in self.block_flow.base.child_iter() {
+            flow::mut_base(kid).floats = Floats::new(writing_mode);
             kid.assign_block_size_for_inorder_child_if_necessary(layout_context);
 
             let child_node = flow::mut_base(kid);
@@ -95,6 +99,7 @@ impl TableRowGroupFlow {
         position.size.block = block_size;
         self.block_flow.fragment.border_box = position;
         self.block_flow.base.position.size.block = block_size;
+        self.block_flow.base.floats = Floats::new(writing_mode);
     }
 
     pub fn build_display_list_table_rowgroup(&mut self, layout_context: &LayoutContext) {
@@ -143,6 +148,11 @@ impl Flow for TableRowGroupFlow {
         let _scope = layout_debug_scope!("table_rowgroup::bubble_inline_sizes {:s}",
                                             self.block_flow.base.debug_id());
 
+        TableFlow::reset_columns(&mut [&mut self.col_inline_sizes,
+                                       &mut self.col_min_inline_sizes,
+                                       &mut self.col_pref_inline_sizes],
+                                 None);
+
         let mut min_inline_size = Au(0);
         let mut pref_inline_size = Au(0);
 
@@ -14,6 +14,7 @@ use floats::FloatKind;
 use flow::{TableWrapperFlowClass, FlowClass, Flow, ImmutableFlowUtils};
 use fragment::Fragment;
 use model::{Specified, Auto, specified};
+use table::TableFlow;
 use wrapper::ThreadSafeLayoutNode;
 
 use servo_util::geometry::Au;
@@ -261,6 +262,8 @@ impl Flow for TableWrapperFlow {
     }
 
     fn bubble_inline_sizes(&mut self, ctx: &LayoutContext) {
+        TableFlow::reset_columns(&mut [&mut self.col_inline_sizes], None);
+
         // get column inline-sizes info from table flow
         for kid in self.block_flow.base.child_iter() {
             assert!(kid.is_table_caption() || kid.is_table());
@@ -327,7 +330,8 @@ impl Flow for TableWrapperFlow {
             return true
         }
 
-        let impacted = self.block_flow.base.flags.impacted_by_floats();
+        let impacted = self.block_flow.base.flags.impacted_by_floats() ||
+            self.block_flow.base.page_grid.is_some();
         if impacted {
             self.assign_block_size(layout_context);
         }
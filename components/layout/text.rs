@@ -7,18 +7,22 @@
 #![deny(unsafe_block)]
 
 use flow::Flow;
-use fragment::{Fragment, ScannedTextFragment, ScannedTextFragmentInfo, UnscannedTextFragment};
+use fragment::{Fragment, GenericFragment, ScannedTextFragment, ScannedTextFragmentInfo};
+use fragment::UnscannedTextFragment;
 
-use gfx::font::{FontMetrics, FontStyle, RunMetrics};
+use gfx::font::{FontFeature, FontMetrics, FontStyle, RunMetrics};
 use gfx::font_context::FontContext;
 use gfx::text::glyph::CharIndex;
+use gfx::text::hyphenation::remove_soft_hyphens;
 use gfx::text::text_run::TextRun;
 use gfx::text::util::{CompressWhitespaceNewline, transform_text, CompressNone};
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::{LogicalSize, WritingMode};
 use servo_util::range::Range;
 use style::ComputedValues;
-use style::computed_values::{font_family, line_height, text_orientation, white_space};
+use style::style_structs;
+use style::computed_values::{font_kerning, hyphens, line_height, text_orientation};
+use style::computed_values::white_space;
 use sync::Arc;
 
 struct NewLinePositions {
@@ -114,64 +118,27 @@ impl TextRunScanner {
                 let new_fragment = in_fragments[self.clump.begin().to_uint()].clone();
                 out_fragments.push(new_fragment)
             },
-            (true, true)  => {
-                let old_fragment = &in_fragments[self.clump.begin().to_uint()];
-                let text = match old_fragment.specific {
-                    UnscannedTextFragment(ref text_fragment_info) => &text_fragment_info.text,
-                    _ => fail!("Expected an unscanned text fragment!"),
-                };
-
-                let font_style = old_fragment.font_style();
-
-                let compression = match old_fragment.white_space() {
-                    white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
-                    white_space::pre => CompressNone,
-                };
-
-                let mut new_line_pos = vec![];
-
-                let (transformed_text, whitespace) = transform_text(text.as_slice(),
-                                                                    compression,
-                                                                    last_whitespace,
-                                                                    &mut new_line_pos);
-
-                new_whitespace = whitespace;
-
-                if transformed_text.len() > 0 {
-                    // TODO(#177): Text run creation must account for the renderability of text by
-                    // font group fonts. This is probably achieved by creating the font group above
-                    // and then letting `FontGroup` decide which `Font` to stick into the text run.
-                    let fontgroup = font_context.get_layout_font_group_for_style(&font_style);
-                    let run = box fontgroup.create_textrun(
-                        transformed_text.clone());
-
-                    debug!("TextRunScanner: pushing single text fragment in range: {} ({})",
-                           self.clump,
-                           *text);
-                    let range = Range::new(CharIndex(0), run.char_len());
-                    let new_metrics = run.metrics_for_range(&range);
-                    let bounding_box_size = bounding_box_for_run_metrics(
-                        &new_metrics, old_fragment.style.writing_mode);
-                    let new_text_fragment_info = ScannedTextFragmentInfo::new(Arc::new(run), range);
-                    let mut new_fragment = old_fragment.transform(
-                        bounding_box_size, ScannedTextFragment(new_text_fragment_info));
-                    new_fragment.new_line_pos = new_line_pos;
-                    out_fragments.push(new_fragment)
-                }
-            },
-            (false, true) => {
-                // TODO(#177): Text run creation must account for the renderability of text by
-                // font group fonts. This is probably achieved by creating the font group above
-                // and then letting `FontGroup` decide which `Font` to stick into the text run.
+            (_, true) => {
                 let in_fragment = &in_fragments[self.clump.begin().to_uint()];
                 let font_style = in_fragment.font_style();
-                let fontgroup = font_context.get_layout_font_group_for_style(&font_style);
 
                 let compression = match in_fragment.white_space() {
                     white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
                     white_space::pre => CompressNone,
                 };
 
+                // Soft hyphens are break opportunities unless `hyphens` is `none`, and with
+                // `hyphens: auto` more are inserted where the language's patterns allow.
+                let hyphens_value = in_fragment.hyphens();
+                let hyphenator = match hyphens_value {
+                    hyphens::auto => {
+                        font_style.language.as_ref().and_then(|language| {
+                            font_context.get_hyphenator(language.as_slice())
+                        })
+                    }
+                    hyphens::manual | hyphens::none => None,
+                };
+
                 let mut new_line_positions: Vec<NewLinePositions> = vec![];
 
                 // First, transform/compress text of all the nodes.
@@ -183,6 +150,18 @@ impl TextRunScanner {
                         _ => fail!("Expected an unscanned text fragment!"),
                     };
 
+                    let hyphenated_text = match (hyphens_value, &hyphenator) {
+                        (hyphens::none, _) => Some(remove_soft_hyphens(in_fragment.as_slice())),
+                        (_, &Some(ref hyphenator)) => {
+                            Some(hyphenator.hyphenate(in_fragment.as_slice()))
+                        }
+                        _ => None,
+                    };
+                    let in_fragment = match hyphenated_text {
+                        Some(ref text) => text,
+                        None => in_fragment,
+                    };
+
                     let mut new_line_pos = vec![];
 
                     let (new_str, new_whitespace) = transform_text(in_fragment.as_slice(),
@@ -208,38 +187,55 @@ impl TextRunScanner {
                     char_total = char_total + added_chars;
                 }
 
-                // Now create the run.
+                // Now create the runs. The font group picks a font for each character, so the
+                // text is split into one run per stretch of text that a single font renders.
                 // TextRuns contain a cycle which is usually resolved by the teardown
                 // sequence. If no clump takes ownership, however, it will leak.
-                let clump = self.clump;
-                let run = if clump.length() != CharIndex(0) && run_str.len() > 0 {
-                    Some(Arc::new(box TextRun::new(
-                        &mut *fontgroup.fonts[0].borrow_mut(),
-                        run_str.to_string())))
-                } else {
-                    None
-                };
-
-                // Make new fragments with the run and adjusted text indices.
+                let runs: Vec<(Range<CharIndex>, Arc<Box<TextRun>>)> =
+                    font_context.create_text_runs(&font_style, run_str.as_slice())
+                                .into_iter()
+                                .map(|(run_range, run)| (run_range, Arc::new(box run)))
+                                .collect();
+                let run_ranges: Vec<Range<CharIndex>> =
+                    runs.iter().map(|&(run_range, _)| run_range).collect();
+
+                // Make new fragments with the runs and adjusted text indices. A fragment whose
+                // text spans several runs becomes one fragment per run.
                 debug!("TextRunScanner: pushing fragment(s) in range: {}", self.clump);
+                let clump = self.clump;
                 for i in clump.each_index() {
                     let logical_offset = i - self.clump.begin();
                     let range = new_ranges[logical_offset.to_uint()];
+                    let old_fragment = &in_fragments[i.to_uint()];
                     if range.length() == CharIndex(0) {
+                        // An empty quote stays behind as an empty fragment, so that it can show
+                        // text once quotes before it change how deeply it is nested.
+                        if old_fragment.quote.is_some() {
+                            let size = LogicalSize::zero(old_fragment.style.writing_mode);
+                            out_fragments.push(old_fragment.transform(size, GenericFragment));
+                            continue
+                        }
                         debug!("Elided an `UnscannedTextFragment` because it was zero-length after \
                                 compression; {}", in_fragments[i.to_uint()]);
                         continue
                     }
 
-                    let new_text_fragment_info = ScannedTextFragmentInfo::new(run.as_ref().unwrap().clone(), range);
-                    let old_fragment = &in_fragments[i.to_uint()];
-                    let new_metrics = new_text_fragment_info.run.metrics_for_range(&range);
-                    let bounding_box_size = bounding_box_for_run_metrics(
-                        &new_metrics, old_fragment.style.writing_mode);
-                    let mut new_fragment = old_fragment.transform(
-                        bounding_box_size, ScannedTextFragment(new_text_fragment_info));
-                    new_fragment.new_line_pos = new_line_positions[logical_offset.to_uint()].new_line_pos.clone();
-                    out_fragments.push(new_fragment)
+                    let new_line_pos = &new_line_positions[logical_offset.to_uint()].new_line_pos;
+                    let pieces = split_among_runs(range,
+                                                  new_line_pos.as_slice(),
+                                                  run_ranges.as_slice());
+                    for (run_index, range_in_run, new_line_pos) in pieces.into_iter() {
+                        let (_, ref run) = runs[run_index];
+                        let new_text_fragment_info = ScannedTextFragmentInfo::new(run.clone(),
+                                                                                  range_in_run);
+                        let new_metrics = run.metrics_for_range(&range_in_run);
+                        let bounding_box_size = bounding_box_for_run_metrics(
+                            &new_metrics, old_fragment.style.writing_mode);
+                        let mut new_fragment = old_fragment.transform(
+                            bounding_box_size, ScannedTextFragment(new_text_fragment_info));
+                        new_fragment.new_line_pos = new_line_pos;
+                        out_fragments.push(new_fragment)
+                    }
                 }
             }
         } // End of match.
@@ -251,6 +247,32 @@ impl TextRunScanner {
     } // End of `flush_clump_to_list`.
 }
 
+/// Splits the characters in `range` of a clump's text between the runs in `run_ranges` that
+/// render them. Returns the index of each run, the characters that the piece covers within it and
+/// the newline positions in the piece, given those in `range` as `new_line_pos`.
+fn split_among_runs(range: Range<CharIndex>,
+                    new_line_pos: &[CharIndex],
+                    run_ranges: &[Range<CharIndex>])
+                    -> Vec<(uint, Range<CharIndex>, Vec<CharIndex>)> {
+    let mut pieces = vec!();
+    for (run_index, run_range) in run_ranges.iter().enumerate() {
+        let piece = range.intersect(run_range);
+        if piece.is_empty() {
+            continue
+        }
+
+        let mut range_in_run = piece;
+        range_in_run.shift_by(-run_range.begin());
+
+        // Newline positions are relative to the start of the fragment.
+        let piece_offset = piece.begin() - range.begin();
+        let piece_new_line_pos = new_line_pos.iter().filter(|&&pos| {
+            piece.contains(range.begin() + pos)
+        }).map(|&pos| pos - piece_offset).collect();
+        pieces.push((run_index, range_in_run, piece_new_line_pos))
+    }
+    pieces
+}
 
 #[inline]
 fn bounding_box_for_run_metrics(metrics: &RunMetrics, writing_mode: WritingMode)
@@ -296,9 +318,7 @@ pub fn computed_style_to_font_style(style: &ComputedValues) -> FontStyle {
 
     // FIXME: Too much allocation here.
     let mut font_families = style.get_font().font_family.iter().map(|family| {
-        match *family {
-            font_family::FamilyName(ref name) => (*name).clone(),
-        }
+        family.name().to_string()
     });
     debug!("(font style) font families: `{:?}`", font_families);
 
@@ -311,9 +331,67 @@ pub fn computed_style_to_font_style(style: &ComputedValues) -> FontStyle {
         style: style.get_font().font_style,
         variant: style.get_font().font_variant,
         families: font_families.collect(),
+        stretch: style.get_font().font_stretch,
+        size_adjust: style.get_font().font_size_adjust,
+        features: font_features(style.get_font()),
+        language: style.get_font()._servo_lang.clone(),
     }
 }
 
+/// Returns the OpenType features that the `font-kerning`, `font-variant-*` and
+/// `font-feature-settings` properties ask for. `font-feature-settings` comes last so that it
+/// overrides the others.
+fn font_features(font: &style_structs::Font) -> Vec<FontFeature> {
+    let mut features = vec!();
+
+    match font.font_kerning {
+        font_kerning::auto => {}
+        font_kerning::normal => features.push(FontFeature::new("kern", 1)),
+        font_kerning::none => features.push(FontFeature::new("kern", 0)),
+    }
+
+    let ligatures = &font.font_variant_ligatures;
+    let ligature_tags: [(Option<bool>, &[&str]), ..4] = [
+        (ligatures.common, &["liga", "clig"]),
+        (ligatures.discretionary, &["dlig"]),
+        (ligatures.historical, &["hlig"]),
+        (ligatures.contextual, &["calt"]),
+    ];
+    for &(enabled, tags) in ligature_tags.iter() {
+        match enabled {
+            Some(enabled) => {
+                for tag in tags.iter() {
+                    features.push(FontFeature::new(*tag, enabled as u32))
+                }
+            }
+            None => {}
+        }
+    }
+
+    let numeric = &font.font_variant_numeric;
+    let numeric_tags = [
+        (numeric.lining_nums, "lnum"),
+        (numeric.oldstyle_nums, "onum"),
+        (numeric.proportional_nums, "pnum"),
+        (numeric.tabular_nums, "tnum"),
+        (numeric.diagonal_fractions, "frac"),
+        (numeric.stacked_fractions, "afrc"),
+        (numeric.ordinal, "ordn"),
+        (numeric.slashed_zero, "zero"),
+    ];
+    for &(enabled, tag) in numeric_tags.iter() {
+        if enabled {
+            features.push(FontFeature::new(tag, 1))
+        }
+    }
+
+    for setting in font.font_feature_settings.iter() {
+        features.push(FontFeature::new(setting.tag.as_slice(), setting.value))
+    }
+
+    features
+}
+
 /// Returns the line block-size needed by the given computed style and font size.
 pub fn line_height_from_style(style: &ComputedValues, metrics: &FontMetrics) -> Au {
     let font_size = style.get_font().font_size;
@@ -324,3 +402,79 @@ pub fn line_height_from_style(style: &ComputedValues, metrics: &FontMetrics) ->
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{font_features, split_among_runs};
+
+    use gfx::font::FontFeature;
+    use gfx::text::glyph::CharIndex;
+    use servo_util::range::Range;
+    use style::{DeclarationBlock, cascade, parse_style_attribute};
+    use url::Url;
+
+    /// Splits the characters from `begin` to `end` among runs of the characters 0 to 3 and 3 to 7,
+    /// returning the run index, the range in the run and the newline positions of each piece.
+    fn split(begin: int, end: int, new_line_pos: &[int]) -> Vec<(uint, int, int, Vec<int>)> {
+        let run_ranges = [
+            Range::new(CharIndex(0), CharIndex(3)),
+            Range::new(CharIndex(3), CharIndex(4)),
+        ];
+        let new_line_pos: Vec<CharIndex> = new_line_pos.iter().map(|&pos| CharIndex(pos)).collect();
+        let range = Range::new(CharIndex(begin), CharIndex(end - begin));
+        split_among_runs(range, new_line_pos.as_slice(), run_ranges.as_slice()).into_iter().map(
+            |(run_index, range_in_run, new_line_pos)| {
+                let (CharIndex(begin), CharIndex(end)) = (range_in_run.begin(), range_in_run.end());
+                let new_line_pos = new_line_pos.into_iter().map(|CharIndex(pos)| pos).collect();
+                (run_index, begin, end, new_line_pos)
+            }).collect()
+    }
+
+    #[test]
+    fn test_split_among_runs_within_one_run() {
+        assert_eq!(split(4, 6, &[1]), vec!((1, 1, 3, vec!(1))));
+        assert_eq!(split(0, 3, &[]), vec!((0, 0, 3, vec!())));
+    }
+
+    #[test]
+    fn test_split_among_runs_across_runs() {
+        // Each newline goes to the piece that it is in, relative to the start of that piece.
+        assert_eq!(split(2, 7, &[0, 3]), vec!((0, 2, 3, vec!(0)), (1, 0, 4, vec!(2))));
+        assert_eq!(split(0, 0, &[]), vec!());
+    }
+
+    /// Returns the features that text styled with the given declarations is shaped with.
+    fn features(css: &str) -> Vec<FontFeature> {
+        let base_url = Url::parse("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(css, &base_url);
+        let (style, _) = cascade(&[DeclarationBlock::from_declarations(declarations.normal)],
+                                 false,
+                                 None,
+                                 None);
+        font_features(style.get_font())
+    }
+
+    #[test]
+    fn test_font_features() {
+        assert_eq!(features(""), vec!());
+        assert_eq!(features("font-kerning: none"), vec!(FontFeature::new("kern", 0)));
+        assert_eq!(features("font-variant-ligatures: no-common-ligatures contextual"),
+                   vec!(FontFeature::new("liga", 0),
+                        FontFeature::new("clig", 0),
+                        FontFeature::new("calt", 1)));
+        assert_eq!(features("font-variant-numeric: oldstyle-nums tabular-nums slashed-zero"),
+                   vec!(FontFeature::new("onum", 1),
+                        FontFeature::new("tnum", 1),
+                        FontFeature::new("zero", 1)));
+    }
+
+    #[test]
+    fn test_font_feature_settings_come_last() {
+        // Shapers apply the last value given for a feature, so the settings override the rest.
+        assert_eq!(features("font-kerning: normal; font-variant-numeric: tabular-nums; \
+                             font-feature-settings: \"tnum\" 0, \"kern\" off"),
+                   vec!(FontFeature::new("kern", 1),
+                        FontFeature::new("tnum", 1),
+                        FontFeature::new("tnum", 0),
+                        FontFeature::new("kern", 0)));
+    }
+}
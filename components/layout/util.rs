@@ -28,7 +28,8 @@ pub struct PrivateLayoutData {
     /// The results of CSS styling for this node's `after` pseudo-element, if any.
     pub after_style: Option<Arc<ComputedValues>>,
 
-    /// Description of how to account for recent style changes.
+    /// Description of how to account for recent style changes. Once flow construction is done
+    /// with the node, this just says whether its flows were rebuilt, for the benefit of its parent.
     pub restyle_damage: Option<RestyleDamage>,
 
     /// The current results of flow construction for this node. This is either a flow or a
@@ -249,6 +249,45 @@ impl<'ln> LayoutNode<'ln> {
             Some(_) => {}
         }
     }
+
+    /// Returns true if this node has changed since the last reflow and must be restyled and
+    /// have its flows rebuilt.
+    pub fn is_dirty(&self) -> bool {
+        unsafe {
+            (*self.node.unsafe_get()).get_is_dirty_for_layout()
+        }
+    }
+
+    /// Returns true if any descendant of this node is dirty.
+    pub fn has_dirty_descendants(&self) -> bool {
+        unsafe {
+            (*self.node.unsafe_get()).get_has_dirty_descendants_for_layout()
+        }
+    }
+
+    /// Returns true if this node has changed in some way other than its style since the last
+    /// reflow, in which case the flows built for it can't be kept.
+    pub fn has_changed(&self) -> bool {
+        unsafe {
+            (*self.node.unsafe_get()).get_has_changed_for_layout()
+        }
+    }
+
+    /// Sets whether this node is dirty. This must only be called on nodes that style recalc hasn't
+    /// reached yet, such as the children of the node being restyled.
+    pub fn set_dirty(&self, dirty: bool) {
+        unsafe {
+            (*self.node.unsafe_get()).set_is_dirty_for_layout(dirty)
+        }
+    }
+
+    /// Marks this node clean again, and forgets about its dirty descendants and any other changes
+    /// to it, once flow construction is done with it.
+    pub fn clear_dirty_bits(&self) {
+        unsafe {
+            (*self.node.unsafe_get()).clear_dirty_bits_for_layout()
+        }
+    }
 }
 
 impl<'ln> TNode<'ln, LayoutElement<'ln>> for LayoutNode<'ln> {
@@ -458,19 +497,6 @@ impl<'le> TElement<'le> for LayoutElement<'le> {
     }
 }
 
-fn get_content(content_list: &content::T) -> String {
-    match *content_list {
-        content::Content(ref value) => {
-            let iter = &mut value.clone().into_iter().peekable();
-            match iter.next() {
-                Some(content::StringContent(content)) => content,
-                _ => "".to_string(),
-            }
-        }
-        _ => "".to_string(),
-    }
-}
-
 #[deriving(PartialEq, Clone)]
 pub enum PseudoElementType {
     Normal,
@@ -559,24 +585,47 @@ impl<'ln> TLayoutNode for ThreadSafeLayoutNode<'ln> {
         }
     }
 
+    /// The text of generated content is that of the strings and attribute values in its `content`
+    /// property. Quotes and images are left out; flow construction builds their fragments from the
+    /// `content` property itself.
     fn text(&self) -> String {
         if self.pseudo != Normal {
             let layout_data_ref = self.borrow_layout_data();
             let node_layout_data_wrapper = layout_data_ref.as_ref().unwrap();
 
-            if self.pseudo.is_before() {
-                let before_style = node_layout_data_wrapper.data.before_style.as_ref().unwrap();
-                return get_content(&before_style.get_box().content)
+            let style = if self.pseudo.is_before() {
+                node_layout_data_wrapper.data.before_style.as_ref().unwrap()
             } else {
-                let after_style = node_layout_data_wrapper.data.after_style.as_ref().unwrap();
-                return get_content(&after_style.get_box().content)
-            }
+                node_layout_data_wrapper.data.after_style.as_ref().unwrap()
+            };
+            return self.get_generated_text(&style.get_box().content)
         }
         self.node.text()
     }
 }
 
 impl<'ln> ThreadSafeLayoutNode<'ln> {
+    /// Concatenates the strings and attribute values of the given `content` property.
+    fn get_generated_text(&self, content_list: &content::T) -> String {
+        let items = match *content_list {
+            content::Content(ref items) => items,
+            content::normal | content::none => return String::new(),
+        };
+
+        let mut text = String::new();
+        for item in items.iter() {
+            match *item {
+                content::StringContent(ref string) => text.push_str(string.as_slice()),
+                content::AttrContent(ref name) => {
+                    let value = self.as_element().get_attr(&ns!(""), name.as_slice());
+                    text.push_str(value.unwrap_or(""))
+                }
+                _ => {}
+            }
+        }
+        text
+    }
+
     /// Creates a new `ThreadSafeLayoutNode` from the given `LayoutNode`.
     pub fn new<'a>(node: &LayoutNode<'a>) -> ThreadSafeLayoutNode<'a> {
         ThreadSafeLayoutNode {
@@ -630,6 +679,22 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         self.pseudo
     }
 
+    /// Returns true if this node has changed since the last reflow. See `LayoutNode::is_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.node.is_dirty()
+    }
+
+    /// Returns true if any descendant of this node is dirty.
+    pub fn has_dirty_descendants(&self) -> bool {
+        self.node.has_dirty_descendants()
+    }
+
+    /// Returns true if this node has changed in some way other than its style since the last
+    /// reflow.
+    pub fn has_changed(&self) -> bool {
+        self.node.has_changed()
+    }
+
     pub fn get_normal_display(&self) -> display::T {
         let mut layout_data_ref = self.mutate_layout_data();
         let node_layout_data_wrapper = layout_data_ref.as_mut().unwrap();
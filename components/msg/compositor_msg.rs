@@ -5,6 +5,7 @@
 use azure::azure_hl::Color;
 use geom::point::Point2D;
 use geom::rect::Rect;
+use geom::side_offsets::SideOffsets2D;
 use layers::platform::surface::NativeGraphicsMetadata;
 use layers::layers::LayerBufferSet;
 use serialize::{Encoder, Encodable};
@@ -43,7 +44,7 @@ impl Epoch {
     }
 }
 
-#[deriving(Clone, PartialEq)]
+#[deriving(Clone, PartialEq, Eq, Hash)]
 pub struct LayerId(pub uint, pub uint);
 
 impl Show for LayerId {
@@ -58,6 +59,12 @@ impl LayerId {
     pub fn null() -> LayerId {
         LayerId(0, 0)
     }
+
+    /// Returns the ID of the layer that clips and scrolls the overflow of the node with the given
+    /// opaque address. Layout creates the layer and script looks it up by this ID.
+    pub fn scroll_root(node_address: uint) -> LayerId {
+        LayerId(node_address, 1)
+    }
 }
 
 /// The scrolling policy of a layer.
@@ -67,6 +74,20 @@ pub enum ScrollPolicy {
     Scrollable,
     /// These layers do not scroll when the parent receives a scrolling message.
     FixedPosition,
+    /// These layers scroll when the parent receives a scrolling message, but are then moved as
+    /// necessary to satisfy the given constraints. This implements `position: sticky`.
+    StickyPosition(StickyConstraints),
+}
+
+/// The constraints on the position of a `position: sticky` layer. All values are in pixels, in
+/// the coordinate space of the layer's parent.
+#[deriving(PartialEq)]
+pub struct StickyConstraints {
+    /// The minimum distance to keep between each edge of the layer and the corresponding edge of
+    /// the parent's visible area, or `None` if that edge of the layer isn't sticky.
+    pub insets: SideOffsets2D<Option<f32>>,
+    /// The content box of the layer's containing block. The layer is never moved outside it.
+    pub containing_block: Rect<f32>,
 }
 
 /// All layer-specific information that the painting task sends to the compositor other than the
@@ -80,6 +101,12 @@ pub struct LayerMetadata {
     pub background_color: Color,
     /// The scrolling policy of this layer.
     pub scroll_policy: ScrollPolicy,
+    /// The ID of the layer that this layer is nested inside, or `None` if this layer is a direct
+    /// child of the pipeline's root layer. The position of this layer is relative to its parent.
+    pub parent_id: Option<LayerId>,
+    /// Whether this layer is a scroll root: a layer with no contents of its own that clips its
+    /// children to its bounds and scrolls them in response to scroll events.
+    pub is_scroll_root: bool,
 }
 
 /// The interface used by the renderer to acquire draw targets for each render frame and
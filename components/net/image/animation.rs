@@ -0,0 +1,353 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Animated images: their frames, when each frame shows, and the compositing of frames that only
+//! cover part of the image onto the frames before them.
+
+use image::base::Image;
+
+use std::cmp;
+use sync::Arc;
+
+/// What happens to the area of a frame once its delay is over, before the next frame is drawn.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DisposalMethod {
+    /// The frame is left in place.
+    KeepDisposal,
+    /// The area of the frame is cleared to transparent black.
+    BackgroundDisposal,
+    /// The area of the frame is restored to what it was before the frame was drawn.
+    PreviousDisposal,
+}
+
+/// One frame of an animated image, already composited onto the frames before it.
+pub struct AnimationFrame {
+    /// The whole image as it looks while this frame shows.
+    pub image: Arc<Image>,
+    /// How long this frame shows, in milliseconds.
+    pub delay: u64,
+    /// How the frame was disposed of before the next frame was drawn.
+    pub disposal: DisposalMethod,
+}
+
+/// The frames of an animated GIF or APNG.
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    /// How many times the frames play before the last frame stays, or `None` to play forever.
+    pub play_count: Option<u32>,
+}
+
+/// The point in the playback of animated images to paint them at.
+#[deriving(Clone, PartialEq, Show)]
+pub enum AnimationTime {
+    /// Milliseconds since the animations started playing.
+    ElapsedTime(u64),
+    /// The given frame of every animation, so that painting is deterministic. Animations with
+    /// fewer frames show their last frame.
+    PinnedFrame(uint),
+}
+
+impl AnimationTime {
+    /// Returns this time in the playback of an animation that started playing at `start_time`
+    /// milliseconds on the same clock.
+    pub fn since(&self, start_time: u64) -> AnimationTime {
+        match *self {
+            ElapsedTime(now) if now > start_time => ElapsedTime(now - start_time),
+            ElapsedTime(_) => ElapsedTime(0),
+            PinnedFrame(index) => PinnedFrame(index),
+        }
+    }
+}
+
+/// Frames that ask for this delay or less play at `DEFAULT_FRAME_DELAY_MS` instead, as they do
+/// in other browsers, since such animations were made for browsers that could not play them any
+/// faster.
+static MAX_IGNORED_FRAME_DELAY_MS: u64 = 10;
+static DEFAULT_FRAME_DELAY_MS: u64 = 100;
+
+/// Returns the delay in milliseconds that a frame which asks for `delay` milliseconds plays for.
+pub fn frame_delay(delay: u64) -> u64 {
+    if delay <= MAX_IGNORED_FRAME_DELAY_MS {
+        DEFAULT_FRAME_DELAY_MS
+    } else {
+        delay
+    }
+}
+
+impl Animation {
+    /// Returns the time that one play of all the frames takes, in milliseconds.
+    pub fn duration(&self) -> u64 {
+        self.frames.iter().fold(0, |duration, frame| duration + frame.delay)
+    }
+
+    /// Returns the index of the frame that shows at the given time.
+    pub fn frame_index_at(&self, time: AnimationTime) -> uint {
+        let last_frame = self.frames.len() - 1;
+        let elapsed = match time {
+            PinnedFrame(index) => return if index < last_frame { index } else { last_frame },
+            ElapsedTime(elapsed) => elapsed,
+        };
+        let duration = self.duration();
+        if duration == 0 || self.has_finished_at(elapsed) {
+            return last_frame
+        }
+
+        let mut frame_start = 0;
+        let time_in_play = elapsed % duration;
+        for (index, frame) in self.frames.iter().enumerate() {
+            frame_start += frame.delay;
+            if time_in_play < frame_start {
+                return index
+            }
+        }
+        last_frame
+    }
+
+    /// Returns the image that shows at the given time.
+    pub fn image_at(&self, time: AnimationTime) -> Arc<Image> {
+        self.frames[self.frame_index_at(time)].image.clone()
+    }
+
+    /// Returns the time after `elapsed` at which the next frame starts to show, or `None` if the
+    /// animation will not change again.
+    pub fn next_frame_time(&self, elapsed: u64) -> Option<u64> {
+        let duration = self.duration();
+        if duration == 0 || self.frames.len() < 2 || self.has_finished_at(elapsed) {
+            return None
+        }
+
+        let play_start = elapsed - elapsed % duration;
+        let mut frame_end = play_start;
+        for frame in self.frames.iter() {
+            frame_end += frame.delay;
+            if elapsed < frame_end {
+                break
+            }
+        }
+        if self.has_finished_at(frame_end) {
+            None
+        } else {
+            Some(frame_end)
+        }
+    }
+
+    fn has_finished_at(&self, elapsed: u64) -> bool {
+        match self.play_count {
+            None => false,
+            Some(play_count) => elapsed >= self.duration() * (play_count as u64),
+        }
+    }
+}
+
+/// Canvases with more pixels than this are rejected before they are allocated.
+static MAX_PIXELS: uint = 1 << 26;
+
+/// Animations whose frames would take more bytes than this in all are rejected, since every frame
+/// keeps a copy of the whole canvas and a small file can hold many frames. They are shown as
+/// still images instead.
+static MAX_ANIMATION_BYTES: uint = 1 << 28;
+
+/// Draws the frames of an animation onto a canvas in turn, applying each frame's disposal before
+/// the next one is drawn, and keeps a copy of the canvas for each frame.
+pub struct AnimationCompositor {
+    width: u32,
+    height: u32,
+    /// Unpremultiplied RGBA pixels.
+    canvas: Vec<u8>,
+    frames: Vec<AnimationFrame>,
+    /// The bytes that the pixels of the frames take.
+    frame_bytes: uint,
+}
+
+impl AnimationCompositor {
+    /// Creates a compositor with a transparent canvas of the given size, or fails if the canvas
+    /// would be too big.
+    pub fn new(width: u32, height: u32) -> Result<AnimationCompositor, ()> {
+        let canvas_size = match (width as uint).checked_mul(&(height as uint)) {
+            Some(pixels) if pixels <= MAX_PIXELS => pixels * 4,
+            _ => return Err(()),
+        };
+        Ok(AnimationCompositor {
+            width: width,
+            height: height,
+            canvas: Vec::from_elem(canvas_size, 0u8),
+            frames: vec!(),
+            frame_bytes: 0,
+        })
+    }
+
+    /// Draws a frame of unpremultiplied RGBA `pixels`, `frame_width` pixels wide, with its top
+    /// left corner at `(x, y)`. If `blend` is set, the frame is drawn over the canvas; otherwise
+    /// it replaces what is under it. Parts of the frame outside the canvas are ignored. Fails if
+    /// keeping another copy of the canvas would make the animation too big.
+    pub fn add_frame(&mut self,
+                     x: u32,
+                     y: u32,
+                     frame_width: u32,
+                     pixels: &[u8],
+                     blend: bool,
+                     delay: u64,
+                     disposal: DisposalMethod)
+                     -> Result<(), ()> {
+        if self.frame_bytes + self.canvas.len() > MAX_ANIMATION_BYTES {
+            return Err(())
+        }
+        self.frame_bytes += self.canvas.len();
+
+        let (x, y, frame_width) = (x as uint, y as uint, frame_width as uint);
+        let (width, height) = (self.width as uint, self.height as uint);
+        let frame_height = if frame_width == 0 { 0 } else { pixels.len() / 4 / frame_width };
+        let right = cmp::min(x.checked_add(&frame_width).unwrap_or(width), width);
+        let bottom = cmp::min(y.checked_add(&frame_height).unwrap_or(height), height);
+        let previous = match disposal {
+            PreviousDisposal => Some(self.canvas.clone()),
+            KeepDisposal | BackgroundDisposal => None,
+        };
+
+        for canvas_y in range(y, bottom) {
+            for canvas_x in range(x, right) {
+                let source = ((canvas_y - y) * frame_width + canvas_x - x) * 4;
+                let destination = (canvas_y * width + canvas_x) * 4;
+                let source = pixels.slice(source, source + 4);
+                let destination = self.canvas.slice_mut(destination, destination + 4);
+                if blend {
+                    blend_over(destination, source)
+                } else {
+                    destination.copy_from(source);
+                }
+            }
+        }
+
+        self.frames.push(AnimationFrame {
+            image: Arc::new(Image::from_rgba(self.width, self.height, self.canvas.clone())),
+            delay: delay,
+            disposal: disposal,
+        });
+
+        match previous {
+            Some(previous) => self.canvas = previous,
+            None if disposal == BackgroundDisposal && x < right => {
+                for canvas_y in range(y, bottom) {
+                    let start = (canvas_y * width + x) * 4;
+                    let end = (canvas_y * width + right) * 4;
+                    for byte in self.canvas.slice_mut(start, end).iter_mut() {
+                        *byte = 0
+                    }
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the animation, or `None` if it has fewer than two frames and so does not move.
+    pub fn finish(self, play_count: Option<u32>) -> Option<Animation> {
+        if self.frames.len() < 2 {
+            return None
+        }
+        Some(Animation {
+            frames: self.frames,
+            play_count: play_count,
+        })
+    }
+}
+
+/// Draws the unpremultiplied RGBA pixel `source` over `destination`.
+fn blend_over(destination: &mut [u8], source: &[u8]) {
+    let source_alpha = source[3] as u32;
+    if source_alpha == 255 {
+        destination.copy_from(source);
+        return
+    }
+    if source_alpha == 0 {
+        return
+    }
+
+    let destination_alpha = destination[3] as u32 * (255 - source_alpha) / 255;
+    let alpha = source_alpha + destination_alpha;
+    for channel in range(0u, 3) {
+        destination[channel] = ((source[channel] as u32 * source_alpha +
+                                 destination[channel] as u32 * destination_alpha) / alpha) as u8;
+    }
+    destination[3] = alpha as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Animation, AnimationCompositor, BackgroundDisposal, ElapsedTime};
+    use super::{KeepDisposal, MAX_ANIMATION_BYTES, PinnedFrame, PreviousDisposal};
+
+    static RED: [u8, ..4] = [255, 0, 0, 255];
+    static GREEN: [u8, ..4] = [0, 255, 0, 255];
+
+    /// Returns the first pixel of each frame, as premultiplied BGRA.
+    fn first_pixels(animation: &Animation) -> Vec<Vec<u8>> {
+        animation.frames.iter().map(|frame| frame.image.pixels().slice_to(4).to_vec()).collect()
+    }
+
+    fn three_frames(disposal: super::DisposalMethod, play_count: Option<u32>) -> Animation {
+        let mut compositor = AnimationCompositor::new(1, 1).unwrap();
+        compositor.add_frame(0, 0, 1, &RED, false, 100, KeepDisposal).unwrap();
+        compositor.add_frame(0, 0, 1, &GREEN, false, 50, disposal).unwrap();
+        compositor.add_frame(0, 0, 1, &[0, 0, 0, 0], true, 200, KeepDisposal).unwrap();
+        compositor.finish(play_count).unwrap()
+    }
+
+    #[test]
+    fn test_disposal() {
+        let (red, green, clear) = (vec!(0, 0, 255, 255), vec!(0, 255, 0, 255), vec!(0, 0, 0, 0));
+        assert_eq!(first_pixels(&three_frames(KeepDisposal, None)),
+                   vec!(red.clone(), green.clone(), green.clone()));
+        assert_eq!(first_pixels(&three_frames(BackgroundDisposal, None)),
+                   vec!(red.clone(), green.clone(), clear));
+        assert_eq!(first_pixels(&three_frames(PreviousDisposal, None)),
+                   vec!(red.clone(), green, red));
+    }
+
+    #[test]
+    fn test_frame_timing() {
+        let animation = three_frames(KeepDisposal, Some(2));
+        assert_eq!(animation.duration(), 350);
+        assert_eq!(animation.frame_index_at(ElapsedTime(0)), 0);
+        assert_eq!(animation.frame_index_at(ElapsedTime(120)), 1);
+        assert_eq!(animation.frame_index_at(ElapsedTime(400)), 0);
+        assert_eq!(animation.frame_index_at(ElapsedTime(1000)), 2);
+        assert_eq!(animation.frame_index_at(PinnedFrame(1)), 1);
+        assert_eq!(animation.frame_index_at(PinnedFrame(7)), 2);
+        assert_eq!(animation.next_frame_time(0), Some(100));
+        assert_eq!(animation.next_frame_time(320), Some(350));
+        assert_eq!(animation.next_frame_time(650), None);
+        assert_eq!(three_frames(KeepDisposal, None).next_frame_time(650), Some(700));
+    }
+
+    #[test]
+    fn test_time_since_start() {
+        assert_eq!(ElapsedTime(500).since(120), ElapsedTime(380));
+        assert_eq!(ElapsedTime(100).since(120), ElapsedTime(0));
+        assert_eq!(PinnedFrame(2).since(120), PinnedFrame(2));
+    }
+
+    #[test]
+    fn test_size_limits() {
+        assert!(AnimationCompositor::new(0xffffffff, 0xffffffff).is_err());
+        assert!(AnimationCompositor::new(1 << 14, 1 << 13).is_err());
+
+        // Each frame keeps a copy of the canvas.
+        let mut compositor = AnimationCompositor::new(1, 1).unwrap();
+        compositor.frame_bytes = MAX_ANIMATION_BYTES - 4;
+        compositor.add_frame(0, 0, 1, &RED, false, 100, KeepDisposal).unwrap();
+        assert!(compositor.add_frame(0, 0, 1, &RED, false, 100, KeepDisposal).is_err());
+    }
+
+    #[test]
+    fn test_frame_outside_canvas() {
+        let mut compositor = AnimationCompositor::new(1, 1).unwrap();
+        compositor.add_frame(5, 0, 1, &RED, false, 100, BackgroundDisposal).unwrap();
+        compositor.add_frame(0xffffffff, 0xffffffff, 1, &RED, false, 100, KeepDisposal).unwrap();
+        compositor.add_frame(0, 0, 1, &GREEN, false, 100, KeepDisposal).unwrap();
+        let (clear, green) = (vec!(0, 0, 0, 0), vec!(0, 255, 0, 255));
+        let animation = compositor.finish(None).unwrap();
+        assert_eq!(first_pixels(&animation), vec!(clear.clone(), clear, green));
+    }
+}
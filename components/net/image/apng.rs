@@ -0,0 +1,251 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes every frame of an animated PNG, per the
+//! [APNG specification](https://wiki.mozilla.org/APNG_Specification).
+//!
+//! The png crate only knows still images, so each frame is rewritten as a PNG of its own: the
+//! image header with the frame's size, the palette and transparency chunks, and the frame's data.
+
+use image::animation::{Animation, AnimationCompositor, BackgroundDisposal, DisposalMethod};
+use image::animation::{KeepDisposal, PreviousDisposal, frame_delay};
+use image::base::png_to_rgba;
+
+use png;
+
+static SIGNATURE: &'static [u8] = &[0x89, b'P' as u8, b'N' as u8, b'G' as u8, 0x0d, 0x0a, 0x1a,
+                                    0x0a];
+
+/// A chunk of a PNG file.
+struct Chunk<'a> {
+    chunk_type: &'a [u8],
+    data: &'a [u8],
+}
+
+/// The frame control chunk that comes before the data of each frame.
+struct FrameControl {
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    delay: u64,
+    disposal: DisposalMethod,
+    blend: bool,
+}
+
+/// Decodes the frames of an animated PNG. Returns `None` for PNGs that are not animated or have
+/// only one frame, and for broken ones.
+pub fn decode_animation(buffer: &[u8]) -> Option<Animation> {
+    if !buffer.starts_with(SIGNATURE) {
+        return None
+    }
+    decode(buffer).ok().and_then(|animation| animation)
+}
+
+fn decode(buffer: &[u8]) -> Result<Option<Animation>, ()> {
+    let chunks = try!(read_chunks(buffer.slice_from(SIGNATURE.len())));
+    let header = match chunks.iter().find(|chunk| chunk.chunk_type == b"IHDR") {
+        Some(header) if header.data.len() == 13 => header.data,
+        _ => return Err(()),
+    };
+    let play_count = match chunks.iter().find(|chunk| chunk.chunk_type == b"acTL") {
+        Some(animation_control) if animation_control.data.len() == 8 => {
+            match read_u32(animation_control.data, 4) {
+                0 => None,
+                play_count => Some(play_count),
+            }
+        }
+        _ => return Ok(None),
+    };
+    let shared_chunks: Vec<&Chunk> = chunks.iter().filter(|chunk| {
+        chunk.chunk_type == b"PLTE" || chunk.chunk_type == b"tRNS"
+    }).collect();
+
+    let mut compositor = try!(AnimationCompositor::new(read_u32(header, 0), read_u32(header, 4)));
+    let mut frame_control = None;
+    let mut frame_data = vec!();
+    for chunk in chunks.iter() {
+        if chunk.chunk_type == b"fcTL" {
+            try!(add_frame(&mut compositor, header, shared_chunks.as_slice(),
+                           frame_control.take(), frame_data.as_slice()));
+            frame_data.truncate(0);
+            frame_control = Some(try!(read_frame_control(chunk.data)));
+        } else if chunk.chunk_type == b"IDAT" {
+            // Image data that no frame control chunk comes before is a still image for decoders
+            // that do not know APNG, and is not part of the animation.
+            if frame_control.is_some() {
+                frame_data.push_all(chunk.data)
+            }
+        } else if chunk.chunk_type == b"fdAT" && chunk.data.len() >= 4 {
+            // Frame data starts with a sequence number.
+            frame_data.push_all(chunk.data.slice_from(4))
+        }
+    }
+    try!(add_frame(&mut compositor, header, shared_chunks.as_slice(), frame_control,
+                   frame_data.as_slice()));
+    Ok(compositor.finish(play_count))
+}
+
+/// Decodes the data of a frame and draws it.
+fn add_frame(compositor: &mut AnimationCompositor,
+             header: &[u8],
+             shared_chunks: &[&Chunk],
+             frame_control: Option<FrameControl>,
+             frame_data: &[u8])
+             -> Result<(), ()> {
+    let frame_control = match frame_control {
+        Some(frame_control) => frame_control,
+        None => return Ok(()),
+    };
+
+    let mut frame_header = header.to_vec();
+    write_u32(frame_header.as_mut_slice(), 0, frame_control.width);
+    write_u32(frame_header.as_mut_slice(), 4, frame_control.height);
+
+    let mut frame_png = SIGNATURE.to_vec();
+    write_chunk(&mut frame_png, b"IHDR", frame_header.as_slice());
+    for chunk in shared_chunks.iter() {
+        write_chunk(&mut frame_png, chunk.chunk_type, chunk.data);
+    }
+    write_chunk(&mut frame_png, b"IDAT", frame_data);
+    write_chunk(&mut frame_png, b"IEND", &[]);
+
+    let image = match png::load_png_from_memory(frame_png.as_slice()) {
+        Ok(image) => image,
+        Err(_) => return Err(()),
+    };
+    let pixels = png_to_rgba(image.pixels);
+    compositor.add_frame(frame_control.x,
+                         frame_control.y,
+                         image.width,
+                         pixels.as_slice(),
+                         frame_control.blend,
+                         frame_control.delay,
+                         frame_control.disposal)
+}
+
+fn read_frame_control(data: &[u8]) -> Result<FrameControl, ()> {
+    if data.len() != 26 {
+        return Err(())
+    }
+    let delay_numerator = (data[20] as u64 << 8) | data[21] as u64;
+    let delay_denominator = match (data[22] as u64 << 8) | data[23] as u64 {
+        0 => 100,
+        delay_denominator => delay_denominator,
+    };
+    Ok(FrameControl {
+        width: read_u32(data, 4),
+        height: read_u32(data, 8),
+        x: read_u32(data, 12),
+        y: read_u32(data, 16),
+        delay: frame_delay(delay_numerator * 1000 / delay_denominator),
+        disposal: match data[24] {
+            1 => BackgroundDisposal,
+            2 => PreviousDisposal,
+            _ => KeepDisposal,
+        },
+        blend: data[25] == 1,
+    })
+}
+
+/// Splits the chunks after the signature, up to the image trailer.
+fn read_chunks<'a>(buffer: &'a [u8]) -> Result<Vec<Chunk<'a>>, ()> {
+    let mut chunks = vec!();
+    let mut position = 0;
+    while position + 8 <= buffer.len() {
+        let length = read_u32(buffer, position) as uint;
+        let chunk_type = buffer.slice(position + 4, position + 8);
+        // Skip the length and type before the data, and the CRC after it.
+        if position + 12 + length > buffer.len() {
+            return Err(())
+        }
+        if chunk_type == b"IEND" {
+            break
+        }
+        chunks.push(Chunk {
+            chunk_type: chunk_type,
+            data: buffer.slice(position + 8, position + 8 + length),
+        });
+        position += 12 + length;
+    }
+    Ok(chunks)
+}
+
+fn read_u32(data: &[u8], offset: uint) -> u32 {
+    (data[offset] as u32 << 24) | (data[offset + 1] as u32 << 16) |
+        (data[offset + 2] as u32 << 8) | data[offset + 3] as u32
+}
+
+fn write_u32(data: &mut [u8], offset: uint, value: u32) {
+    for index in range(0u, 4) {
+        data[offset + index] = (value >> (24 - index * 8)) as u8;
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    let mut length = [0u8, ..4];
+    write_u32(&mut length, 0, data.len() as u32);
+    png.push_all(&length);
+    let crc_start = png.len();
+    png.push_all(chunk_type);
+    png.push_all(data);
+    let mut crc = [0u8, ..4];
+    write_u32(&mut crc, 0, crc32(png.slice_from(crc_start)));
+    png.push_all(&crc);
+}
+
+/// The CRC that PNG chunks end with, over their type and data.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _ in range(0u, 8) {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, decode_animation};
+
+    use image::animation::{BackgroundDisposal, KeepDisposal, PinnedFrame};
+
+    /// A 2×1 APNG that plays three times: red for 100 ms, then a green pixel on the right for
+    /// 30 ms that is cleared afterwards. A blue still image comes before the animation.
+    static ANIMATED_PNG: &'static [u8] = include_bin!("test-animated.png");
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+    }
+
+    #[test]
+    fn test_decode_animation() {
+        let animation = decode_animation(ANIMATED_PNG).unwrap();
+        assert_eq!(animation.play_count, Some(3));
+        assert_eq!(animation.frames.iter().map(|frame| frame.delay).collect::<Vec<u64>>(),
+                   vec!(100, 30));
+        assert_eq!(animation.frames[0].disposal, KeepDisposal);
+        assert_eq!(animation.frames[1].disposal, BackgroundDisposal);
+
+        // Premultiplied BGRA.
+        let expected = vec!(vec!(0u8, 0, 255, 255, 0, 0, 255, 255),
+                            vec!(0u8, 0, 255, 255, 0, 255, 0, 255));
+        for (frame_index, expected_pixels) in expected.into_iter().enumerate() {
+            assert_eq!(animation.image_at(PinnedFrame(frame_index)).pixels().to_vec(),
+                       expected_pixels);
+        }
+    }
+
+    #[test]
+    fn test_still_png() {
+        let mut still = ANIMATED_PNG.to_vec();
+        // Renaming the animation control chunk to an unknown ancillary chunk makes it still.
+        let position = still.as_slice().windows(4).position(|window| window == b"acTL").unwrap();
+        *still.get_mut(position + 1) = b'x' as u8;
+        assert!(decode_animation(still.as_slice()).is_none());
+    }
+}
@@ -2,31 +2,152 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use image::animation::Animation;
+use image::{apng, bmp, gif, ico, webp};
+
+use geom::size::Size2D;
+use servo_util::memory::{record_decoded_image_allocation, record_decoded_image_deallocation};
+use std::cmp;
 use std::iter::range_step;
 use stb_image::image as stb_image;
 use png;
 
-// FIXME: Images must not be copied every frame. Instead we should atomically
-// reference count them.
-pub type Image = png::Image;
+/// A decoded image. Its pixels are premultiplied BGRA, ready to be drawn, and never change once
+/// the image is decoded, so display items and animation frames share them through an `Arc`
+/// instead of copying them.
+pub struct Image {
+    /// The natural width of the image in pixels, which layout sizes the image by even if its
+    /// pixels were downscaled.
+    pub width: u32,
+    /// The natural height of the image in pixels.
+    pub height: u32,
+    pixel_width: u32,
+    pixel_height: u32,
+    pixels: Vec<u8>,
+}
 
+impl Image {
+    /// Creates an image from premultiplied BGRA pixels, in rows of `width` pixels.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Image {
+        Image::with_natural_size(width, height, width, height, pixels)
+    }
 
-static TEST_IMAGE: &'static [u8] = include_bin!("test.jpeg");
+    /// Creates an image from unpremultiplied RGBA pixels, converting them in place.
+    pub fn from_rgba(width: u32, height: u32, mut pixels: Vec<u8>) -> Image {
+        byte_swap_and_premultiply(pixels.as_mut_slice());
+        Image::new(width, height, pixels)
+    }
 
-pub fn test_image_bin() -> Vec<u8> {
-    TEST_IMAGE.iter().map(|&x| x).collect()
+    fn with_natural_size(width: u32,
+                         height: u32,
+                         pixel_width: u32,
+                         pixel_height: u32,
+                         pixels: Vec<u8>)
+                         -> Image {
+        let byte_count = (pixel_width as uint).checked_mul(&(pixel_height as uint))
+                                              .and_then(|pixel_count| pixel_count.checked_mul(&4));
+        assert!(byte_count == Some(pixels.len()));
+        record_decoded_image_allocation(pixels.len());
+        Image {
+            width: width,
+            height: height,
+            pixel_width: pixel_width,
+            pixel_height: pixel_height,
+            pixels: pixels,
+        }
+    }
+
+    /// Returns the number of pixels in each row of the decoded pixels, which is less than the
+    /// natural width if the image was downscaled.
+    pub fn pixel_width(&self) -> u32 {
+        self.pixel_width
+    }
+
+    /// Returns the number of rows of decoded pixels.
+    pub fn pixel_height(&self) -> u32 {
+        self.pixel_height
+    }
+
+    /// Returns the premultiplied BGRA pixels, in rows of `pixel_width()` pixels.
+    pub fn pixels<'a>(&'a self) -> &'a [u8] {
+        self.pixels.as_slice()
+    }
+
+    /// Downscales the image by the largest whole factor that still leaves it at least as big as
+    /// `size` in both dimensions, so that it keeps enough pixels to be shown at `size` whether it
+    /// is stretched, contained or covered. Images that are less than twice as big are returned
+    /// as they are.
+    pub fn downscale_to_fit(self, size: Size2D<u32>) -> Image {
+        if size.width == 0 || size.height == 0 {
+            return self
+        }
+        let factor = cmp::min(self.pixel_width / size.width, self.pixel_height / size.height);
+        if factor < 2 {
+            return self
+        }
+
+        // Average each block of `factor` by `factor` pixels. Averaging premultiplied pixels
+        // weighs each color by its alpha, as it should.
+        let (factor, old_width, old_height) =
+            (factor as uint, self.pixel_width as uint, self.pixel_height as uint);
+        let pixel_width = (old_width + factor - 1) / factor;
+        let pixel_height = (old_height + factor - 1) / factor;
+        let mut pixels = Vec::with_capacity(pixel_width * pixel_height * 4);
+        for block_y in range(0, pixel_height) {
+            let top = block_y * factor;
+            let bottom = cmp::min(top + factor, old_height);
+            for block_x in range(0, pixel_width) {
+                let left = block_x * factor;
+                let right = cmp::min(left + factor, old_width);
+                let mut sums = [0u64, ..4];
+                for y in range(top, bottom) {
+                    let start = (y * old_width + left) * 4;
+                    let end = (y * old_width + right) * 4;
+                    for pixel in self.pixels.slice(start, end).chunks(4) {
+                        for channel in range(0u, 4) {
+                            sums[channel] += pixel[channel] as u64;
+                        }
+                    }
+                }
+                let count = ((right - left) * (bottom - top)) as u64;
+                for &sum in sums.iter() {
+                    pixels.push(((sum + count / 2) / count) as u8);
+                }
+            }
+        }
+        Image::with_natural_size(self.width,
+                                 self.height,
+                                 pixel_width as u32,
+                                 pixel_height as u32,
+                                 pixels)
+    }
 }
 
-// TODO(pcwalton): Speed up with SIMD, or better yet, find some way to not do this.
-fn byte_swap(data: &mut [u8]) {
-    let length = data.len();
-    for i in range_step(0, length, 4) {
-        let r = data[i + 2];
-        data[i + 2] = data[i + 0];
-        data[i + 0] = r;
+impl Drop for Image {
+    fn drop(&mut self) {
+        record_decoded_image_deallocation(self.pixels.len());
     }
 }
 
+/// Why an image could not be decoded.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DecodeError {
+    /// The data is not in any format that we decode.
+    UnrecognizedImageFormat,
+    /// The data is in a known format, but is broken or truncated.
+    MalformedImage(String),
+    /// The image uses a part of its format that we do not decode.
+    UnsupportedImageFeature(String),
+}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+static TEST_IMAGE: &'static [u8] = include_bin!("test.jpeg");
+
+pub fn test_image_bin() -> Vec<u8> {
+    TEST_IMAGE.iter().map(|&x| x).collect()
+}
+
 // TODO(pcwalton): Speed up with SIMD, or better yet, find some way to not do this.
 fn byte_swap_and_premultiply(data: &mut [u8]) {
     let length = data.len();
@@ -41,42 +162,133 @@ fn byte_swap_and_premultiply(data: &mut [u8]) {
     }
 }
 
-pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
+/// Returns true if the buffer starts with a JPEG start of image marker.
+fn is_jpeg(buffer: &[u8]) -> bool {
+    buffer.starts_with(&[0xffu8, 0xd8, 0xff])
+}
+
+/// Returns true if the buffer starts with the header of a Radiance HDR image.
+fn is_radiance_hdr(buffer: &[u8]) -> bool {
+    buffer.starts_with(b"#?RADIANCE\n") || buffer.starts_with(b"#?RGBE\n")
+}
+
+/// Decodes an image, choosing the decoder by sniffing the data.
+pub fn load_from_memory(buffer: &[u8]) -> DecodeResult<Image> {
     if buffer.len() == 0 {
-        return None;
+        return Err(UnrecognizedImageFormat);
     }
 
-    if png::is_png(buffer) {
+    let (width, height, pixels) = if png::is_png(buffer) {
         match png::load_png_from_memory(buffer) {
-            Ok(mut png_image) => {
-                match png_image.pixels {
-                    png::RGB8(ref mut data) => byte_swap(data.as_mut_slice()),
-                    png::RGBA8(ref mut data) => {
-                        byte_swap_and_premultiply(data.as_mut_slice())
-                    }
-                    _ => {}
-                }
-                Some(png_image)
-            }
-            Err(_err) => None,
+            Ok(image) => (image.width as uint, image.height as uint, png_to_rgba(image.pixels)),
+            Err(message) => return Err(MalformedImage(message)),
         }
-    } else {
-        // For non-png images, we use stb_image
+    } else if webp::is_webp(buffer) {
+        try!(webp::decode(buffer))
+    } else if bmp::is_bmp(buffer) {
+        try!(bmp::decode(buffer))
+    } else if ico::is_ico(buffer) {
+        try!(ico::decode(buffer))
+    } else if is_jpeg(buffer) || gif::is_gif(buffer) || is_radiance_hdr(buffer) {
+        // For JPEG, GIF and Radiance HDR images, we use stb_image
         // Can't remember why we do this. Maybe it's what cairo wants
         static FORCE_DEPTH: uint = 4;
 
         match stb_image::load_from_memory_with_depth(buffer, FORCE_DEPTH, true) {
-            stb_image::ImageU8(mut image) => {
+            stb_image::ImageU8(image) => {
+                assert!(image.depth == 4);
+                (image.width, image.height, image.data)
+            }
+            stb_image::ImageF32(image) => {
                 assert!(image.depth == 4);
-                byte_swap(image.data.as_mut_slice());
-                Some(png::Image {
-                    width: image.width as u32,
-                    height: image.height as u32,
-                    pixels: png::RGBA8(image.data)
-                })
+                (image.width, image.height, hdr_to_rgba(image.data.as_slice()))
             }
-            stb_image::ImageF32(_image) => fail!("HDR images not implemented"),
-            stb_image::Error(_) => None
+            stb_image::Error(message) => return Err(MalformedImage(message)),
+        }
+    } else {
+        return Err(UnrecognizedImageFormat)
+    };
+    Ok(Image::from_rgba(width as u32, height as u32, pixels))
+}
+
+/// Tone maps the linear floating point pixels of an HDR image to 8-bit sRGB-ish RGBA, by
+/// clamping them and applying a gamma of 2.2. Alpha is linear.
+fn hdr_to_rgba(data: &[f32]) -> Vec<u8> {
+    data.iter().enumerate().map(|(index, &value)| {
+        let value = value.max(0.0).min(1.0);
+        let value = if index % 4 == 3 { value } else { value.powf(1.0 / 2.2) };
+        (value * 255.0 + 0.5) as u8
+    }).collect()
+}
+
+/// Converts the pixels that the png crate decoded to unpremultiplied RGBA.
+pub fn png_to_rgba(pixels: png::PixelsByColorType) -> Vec<u8> {
+    match pixels {
+        png::RGBA8(data) => data,
+        png::RGB8(data) => {
+            data.as_slice().chunks(3).flat_map(|pixel| {
+                pixel.iter().map(|&byte| byte).chain(Some(255u8).into_iter())
+            }).collect()
         }
+        png::K8(data) => {
+            data.iter().flat_map(|&gray| [gray, gray, gray, 255u8].to_vec().into_iter()).collect()
+        }
+        png::KA8(data) => {
+            data.as_slice().chunks(2).flat_map(|pixel| {
+                [pixel[0], pixel[0], pixel[0], pixel[1]].to_vec().into_iter()
+            }).collect()
+        }
+    }
+}
+
+/// Decodes every frame of an animated GIF or APNG. Returns `None` for other images and for images
+/// with a single frame, which `load_from_memory` decodes instead.
+pub fn load_animation_from_memory(buffer: &[u8]) -> Option<Animation> {
+    if gif::is_gif(buffer) {
+        gif::decode_animation(buffer)
+    } else if png::is_png(buffer) {
+        apng::decode_animation(buffer)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Image, MalformedImage, UnrecognizedImageFormat, load_from_memory};
+
+    use geom::size::Size2D;
+
+    #[test]
+    fn downscales_by_averaging_blocks_of_pixels() {
+        let image = Image::new(3, 2, vec!(10, 20, 30, 40, 20, 30, 40, 50, 100, 100, 100, 100,
+                                          30, 40, 50, 60, 40, 50, 60, 70, 200, 200, 200, 200));
+        let image = image.downscale_to_fit(Size2D(1, 1));
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!((image.pixel_width(), image.pixel_height()), (2, 1));
+        assert_eq!(image.pixels().to_vec(), vec!(25, 35, 45, 55, 150, 150, 150, 150));
+    }
+
+    #[test]
+    fn keeps_images_that_are_not_twice_as_big() {
+        let image = Image::new(3, 1, Vec::from_elem(12, 255u8)).downscale_to_fit(Size2D(2, 1));
+        assert_eq!((image.pixel_width(), image.pixel_height()), (3, 1));
+    }
+
+    #[test]
+    fn sniffs_the_format_before_decoding() {
+        assert!(load_from_memory(b"<html>not an image</html>").err() ==
+                Some(UnrecognizedImageFormat));
+        match load_from_memory(&[0xffu8, 0xd8, 0xff, 0xe0, 0x00]) {
+            Err(MalformedImage(_)) => {}
+            _ => fail!("a truncated JPEG should be malformed"),
+        }
+    }
+
+    #[test]
+    #[should_fail]
+    fn rejects_sizes_whose_byte_count_overflows() {
+        // 65536 * 65536 * 4 bytes wraps around to 0 in 32 bits.
+        Image::new(0x10000, 0x10000, vec!());
     }
 }
@@ -0,0 +1,429 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes Windows bitmaps, per the
+//! [bitmap storage documentation](http://msdn.microsoft.com/en-us/library/dd183391.aspx).
+//! Icons hold bitmaps without a file header, which `decode_dib` decodes.
+
+use image::base::{DecodeResult, MalformedImage, UnsupportedImageFeature};
+
+use std::cmp;
+
+static FILE_HEADER_SIZE: uint = 14;
+static CORE_HEADER_SIZE: uint = 12;
+static INFO_HEADER_SIZE: uint = 40;
+
+/// Images with more pixels than this are rejected before their pixels are allocated, since run
+/// length encoded images can be much larger than their data.
+static MAX_PIXELS: uint = 1 << 26;
+
+static BI_RGB: u32 = 0;
+static BI_RLE8: u32 = 1;
+static BI_RLE4: u32 = 2;
+static BI_BITFIELDS: u32 = 3;
+static BI_ALPHABITFIELDS: u32 = 6;
+
+/// Returns true if the buffer starts with a bitmap file header.
+pub fn is_bmp(buffer: &[u8]) -> bool {
+    buffer.len() >= FILE_HEADER_SIZE && buffer.starts_with(b"BM")
+}
+
+/// Decodes a bitmap file into its width, height and unpremultiplied RGBA pixels.
+pub fn decode(buffer: &[u8]) -> DecodeResult<(uint, uint, Vec<u8>)> {
+    if !is_bmp(buffer) {
+        return Err(MalformedImage("the bitmap file header is missing".to_string()))
+    }
+    let pixel_offset = read_u32(buffer, 10) as uint;
+    if pixel_offset < FILE_HEADER_SIZE {
+        return Err(MalformedImage("the bitmap pixel offset is invalid".to_string()))
+    }
+    decode_dib(buffer.slice_from(FILE_HEADER_SIZE), Some(pixel_offset - FILE_HEADER_SIZE), false)
+}
+
+/// Decodes a device-independent bitmap: a header, a color table and the pixels. The pixels
+/// follow the color table unless `pixel_offset` says where they start.
+///
+/// Bitmaps in icons are twice as tall as the image, since a 1-bit transparency mask follows the
+/// pixels, and they use the alpha channel of 32-bit pixels.
+pub fn decode_dib(data: &[u8], pixel_offset: Option<uint>, is_icon: bool)
+                  -> DecodeResult<(uint, uint, Vec<u8>)> {
+    let header = try!(read_header(data));
+    let height = if is_icon { header.height / 2 } else { header.height };
+    if header.width == 0 || height == 0 || header.width * height > MAX_PIXELS {
+        return Err(MalformedImage("the bitmap size is invalid".to_string()))
+    }
+
+    let mut position = header.header_size;
+    // Info headers with bit fields are followed by the masks, which later headers contain.
+    if header.header_size == INFO_HEADER_SIZE {
+        if header.compression == BI_BITFIELDS {
+            position += 12;
+        } else if header.compression == BI_ALPHABITFIELDS {
+            position += 16;
+        }
+    }
+    let palette = try!(read_palette(data, position, &header));
+    position += palette.len() * header.palette_entry_size;
+    let pixels = match pixel_offset {
+        Some(pixel_offset) if pixel_offset <= data.len() => data.slice_from(pixel_offset),
+        Some(_) => return Err(MalformedImage("the bitmap pixels are missing".to_string())),
+        None => data.slice_from(cmp::min(position, data.len())),
+    };
+
+    let mut rgba = Vec::from_elem(header.width * height * 4, 0u8);
+    let compression = header.compression;
+    let masks_are_valid = compression == BI_RGB || compression == BI_BITFIELDS ||
+        compression == BI_ALPHABITFIELDS;
+    let stride;
+    if compression == BI_RLE8 && header.bits_per_pixel == 8 {
+        try!(decode_rle(pixels, &header, height, palette.as_slice(), false, rgba.as_mut_slice()));
+        stride = 0;
+    } else if compression == BI_RLE4 && header.bits_per_pixel == 4 {
+        try!(decode_rle(pixels, &header, height, palette.as_slice(), true, rgba.as_mut_slice()));
+        stride = 0;
+    } else if masks_are_valid {
+        stride = try!(decode_pixels(pixels, &header, height, palette.as_slice(), is_icon,
+                                    rgba.as_mut_slice()));
+    } else {
+        return Err(UnsupportedImageFeature(format!("bitmap compression {}", compression)))
+    }
+
+    if is_icon && header.bits_per_pixel < 32 && stride > 0 {
+        apply_and_mask(pixels.slice_from(cmp::min(stride * height, pixels.len())),
+                       header.width,
+                       height,
+                       rgba.as_mut_slice());
+    }
+    Ok((header.width, height, rgba))
+}
+
+struct Header {
+    header_size: uint,
+    width: uint,
+    height: uint,
+    top_down: bool,
+    bits_per_pixel: uint,
+    compression: u32,
+    colors_used: uint,
+    palette_entry_size: uint,
+    /// The red, green, blue and alpha masks of 16-bit and 32-bit pixels.
+    masks: [u32, ..4],
+}
+
+fn read_header(data: &[u8]) -> DecodeResult<Header> {
+    let truncated = || MalformedImage("the bitmap header is truncated".to_string());
+    if data.len() < 4 {
+        return Err(truncated())
+    }
+    let header_size = read_u32(data, 0) as uint;
+    if header_size > data.len() {
+        return Err(truncated())
+    }
+    if header_size == CORE_HEADER_SIZE {
+        return Ok(Header {
+            header_size: header_size,
+            width: read_u16(data, 4) as uint,
+            height: read_u16(data, 6) as uint,
+            top_down: false,
+            bits_per_pixel: read_u16(data, 10) as uint,
+            compression: BI_RGB,
+            colors_used: 0,
+            palette_entry_size: 3,
+            masks: [0, 0, 0, 0],
+        })
+    }
+    if header_size < INFO_HEADER_SIZE {
+        return Err(MalformedImage(format!("the bitmap header size {} is unknown", header_size)))
+    }
+
+    let width = read_u32(data, 4) as i32;
+    let height = read_u32(data, 8) as i32;
+    if width <= 0 || height == 0 {
+        return Err(MalformedImage("the bitmap size is invalid".to_string()))
+    }
+    let bits_per_pixel = read_u16(data, 14) as uint;
+    let compression = read_u32(data, 16);
+    let mut masks = match bits_per_pixel {
+        16 => [0x7c00, 0x03e0, 0x001f, 0],
+        _ => [0xff0000, 0xff00, 0xff, 0],
+    };
+    if compression == BI_BITFIELDS || compression == BI_ALPHABITFIELDS {
+        let mask_count = if compression == BI_ALPHABITFIELDS || header_size > INFO_HEADER_SIZE {
+            4
+        } else {
+            3
+        };
+        if data.len() < INFO_HEADER_SIZE + mask_count * 4 {
+            return Err(truncated())
+        }
+        for index in range(0, mask_count) {
+            masks[index] = read_u32(data, INFO_HEADER_SIZE + index * 4);
+        }
+    }
+    Ok(Header {
+        header_size: header_size,
+        width: width as uint,
+        height: if height < 0 { -(height as i64) as uint } else { height as uint },
+        top_down: height < 0,
+        bits_per_pixel: bits_per_pixel,
+        compression: compression,
+        colors_used: read_u32(data, 32) as uint,
+        palette_entry_size: 4,
+        masks: masks,
+    })
+}
+
+/// Reads the color table of images with up to 8 bits per pixel, as RGBA.
+fn read_palette(data: &[u8], position: uint, header: &Header) -> DecodeResult<Vec<[u8, ..4]>> {
+    if header.bits_per_pixel > 8 {
+        return Ok(vec!())
+    }
+    let max_colors = 1 << header.bits_per_pixel;
+    let color_count = match header.colors_used {
+        0 => max_colors,
+        colors_used => cmp::min(colors_used, max_colors),
+    };
+    let entry_size = header.palette_entry_size;
+    if position + color_count * entry_size > data.len() {
+        return Err(MalformedImage("the bitmap color table is truncated".to_string()))
+    }
+    Ok(range(0, color_count).map(|index| {
+        let entry = position + index * entry_size;
+        [data[entry + 2], data[entry + 1], data[entry], 255]
+    }).collect())
+}
+
+/// Decodes uncompressed pixels, and returns the length of each row in bytes.
+fn decode_pixels(pixels: &[u8],
+                 header: &Header,
+                 height: uint,
+                 palette: &[[u8, ..4]],
+                 is_icon: bool,
+                 rgba: &mut [u8])
+                 -> DecodeResult<uint> {
+    let bits_per_pixel = header.bits_per_pixel;
+    match bits_per_pixel {
+        1 | 2 | 4 | 8 | 16 | 24 | 32 => {}
+        _ => return Err(UnsupportedImageFeature(format!("{}-bit bitmaps", bits_per_pixel))),
+    }
+    let stride = (header.width * bits_per_pixel + 31) / 32 * 4;
+    if stride * height > pixels.len() {
+        return Err(MalformedImage("the bitmap pixels are truncated".to_string()))
+    }
+
+    // Bitmaps without alpha masks are opaque, except that icons use the fourth byte of 32-bit
+    // pixels.
+    let mut masks = header.masks;
+    if is_icon && bits_per_pixel == 32 && header.compression == BI_RGB {
+        masks[3] = 0xff000000;
+    }
+    for y in range(0, height) {
+        let row = pixels.slice(stride * y, stride * (y + 1));
+        let output_y = if header.top_down { y } else { height - 1 - y };
+        for x in range(0, header.width) {
+            let color = match bits_per_pixel {
+                1 | 2 | 4 | 8 => {
+                    let bit = x * bits_per_pixel;
+                    let shift = 8 - bits_per_pixel - bit % 8;
+                    let index = (row[bit / 8] >> shift) as uint & ((1 << bits_per_pixel) - 1);
+                    palette_color(palette, index)
+                }
+                16 => unpack_pixel(row[x * 2] as u32 | (row[x * 2 + 1] as u32 << 8), &masks),
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => unpack_pixel(read_u32(row, x * 4), &masks),
+            };
+            let offset = (output_y * header.width + x) * 4;
+            rgba.slice_mut(offset, offset + 4).copy_from(&color);
+        }
+    }
+    Ok(stride)
+}
+
+/// Decodes run length encoded 8-bit or 4-bit pixels. Pixels that the runs skip are transparent.
+fn decode_rle(data: &[u8],
+              header: &Header,
+              height: uint,
+              palette: &[[u8, ..4]],
+              is_rle4: bool,
+              rgba: &mut [u8])
+              -> DecodeResult<()> {
+    let truncated = || MalformedImage("the bitmap pixels are truncated".to_string());
+    let width = header.width;
+    let (mut x, mut y) = (0u, 0u);
+    let mut position = 0;
+    loop {
+        if position + 2 > data.len() {
+            return Err(truncated())
+        }
+        let (count, value) = (data[position] as uint, data[position + 1] as uint);
+        position += 2;
+        if count > 0 {
+            // A run of one index, or of two alternating indices in 4-bit images.
+            for index in range(0, count) {
+                let pixel = if !is_rle4 {
+                    value
+                } else if index % 2 == 0 {
+                    value >> 4
+                } else {
+                    value & 0xf
+                };
+                set_rle_pixel(rgba, width, height, x + index, y, palette_color(palette, pixel));
+            }
+            x += count;
+            continue
+        }
+        match value {
+            // The end of a row.
+            0 => {
+                x = 0;
+                y += 1;
+            }
+            // The end of the bitmap.
+            1 => return Ok(()),
+            // A jump to the right and up.
+            2 => {
+                if position + 2 > data.len() {
+                    return Err(truncated())
+                }
+                x += data[position] as uint;
+                y += data[position + 1] as uint;
+                position += 2;
+            }
+            // Literal indices, padded to an even number of bytes.
+            count => {
+                let length = if is_rle4 { (count + 1) / 2 } else { count };
+                if position + length > data.len() {
+                    return Err(truncated())
+                }
+                for index in range(0, count) {
+                    let pixel = if !is_rle4 {
+                        data[position + index] as uint
+                    } else if index % 2 == 0 {
+                        data[position + index / 2] as uint >> 4
+                    } else {
+                        data[position + index / 2] as uint & 0xf
+                    };
+                    set_rle_pixel(rgba, width, height, x + index, y, palette_color(palette, pixel));
+                }
+                x += count;
+                position += length + (length & 1);
+            }
+        }
+        if y >= height {
+            return Ok(())
+        }
+    }
+}
+
+/// Sets a pixel of a run length encoded image, whose rows are bottom up, unless the runs go past
+/// its edges.
+fn set_rle_pixel(rgba: &mut [u8], width: uint, height: uint, x: uint, y: uint, color: [u8, ..4]) {
+    if x < width && y < height {
+        let offset = ((height - 1 - y) * width + x) * 4;
+        rgba.slice_mut(offset, offset + 4).copy_from(&color);
+    }
+}
+
+/// Makes the pixels of an icon whose bits are set in its transparency mask transparent. The mask
+/// is optional, as some icons leave it out.
+fn apply_and_mask(mask: &[u8], width: uint, height: uint, rgba: &mut [u8]) {
+    let stride = (width + 31) / 32 * 4;
+    if stride * height > mask.len() {
+        return
+    }
+    for y in range(0, height) {
+        let row = mask.slice(stride * y, stride * (y + 1));
+        for x in range(0, width) {
+            if (row[x / 8] >> (7 - x % 8)) & 1 == 1 {
+                let offset = ((height - 1 - y) * width + x) * 4;
+                rgba.slice_mut(offset, offset + 4).copy_from(&[0, 0, 0, 0]);
+            }
+        }
+    }
+}
+
+/// Returns a color from the color table. Indices past its end are black.
+fn palette_color(palette: &[[u8, ..4]], index: uint) -> [u8, ..4] {
+    if index < palette.len() { palette[index] } else { [0, 0, 0, 255] }
+}
+
+/// Extracts the channels of a 16-bit or 32-bit pixel with the given masks, scaling each to 8
+/// bits. Pixels without an alpha mask are opaque.
+fn unpack_pixel(pixel: u32, masks: &[u32, ..4]) -> [u8, ..4] {
+    let mut color = [0u8, 0, 0, 255];
+    for (channel, &mask) in masks.iter().enumerate() {
+        if mask == 0 {
+            continue
+        }
+        let mut shift = 0;
+        while (mask >> shift) & 1 == 0 {
+            shift += 1;
+        }
+        let maximum = mask >> shift;
+        color[channel] = (((pixel & mask) >> shift) as u64 * 255 / maximum as u64) as u8;
+    }
+    color
+}
+
+fn read_u16(data: &[u8], offset: uint) -> u16 {
+    data[offset] as u16 | (data[offset + 1] as u16 << 8)
+}
+
+fn read_u32(data: &[u8], offset: uint) -> u32 {
+    data[offset] as u32 | (data[offset + 1] as u32 << 8) | (data[offset + 2] as u32 << 16) |
+        (data[offset + 3] as u32 << 24)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    /// Builds a bitmap file with an info header.
+    fn bitmap(width: i32, height: i32, bits_per_pixel: u8, compression: u8, palette: &[u8],
+              pixels: &[u8])
+              -> Vec<u8> {
+        let pixel_offset = 14 + 40 + palette.len() as u8;
+        let mut data = b"BM".to_vec();
+        data.push_all(&[0, 0, 0, 0, 0, 0, 0, 0, pixel_offset, 0, 0, 0, 40, 0, 0, 0]);
+        for &value in [width, height].iter() {
+            data.push_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8,
+                           (value >> 24) as u8]);
+        }
+        data.push_all(&[1, 0, bits_per_pixel, 0, compression, 0, 0, 0]);
+        data.push_all(&[0, ..12]);
+        data.push_all(&[palette.len() as u8 / 4, 0, 0, 0, 0, 0, 0, 0]);
+        data.push_all(palette);
+        data.push_all(pixels);
+        data
+    }
+
+    static BLACK_AND_WHITE: [u8, ..8] = [0, 0, 0, 0, 0xff, 0xff, 0xff, 0];
+
+    #[test]
+    fn decodes_bottom_up_24_bit_bitmaps() {
+        // Two rows of one pixel each, bottom row first: blue, then red.
+        let image = bitmap(1, 2, 24, 0, &[], &[0xff, 0, 0, 0, 0, 0, 0xff, 0]);
+        let (width, height, pixels) = decode(image.as_slice()).unwrap();
+        assert_eq!((width, height), (1, 2));
+        assert_eq!(pixels, vec!(255, 0, 0, 255, 0, 0, 255, 255));
+    }
+
+    #[test]
+    fn decodes_top_down_paletted_bitmaps() {
+        let image = bitmap(4, -1, 1, 0, &BLACK_AND_WHITE, &[0x50, 0, 0, 0]);
+        let (_, _, pixels) = decode(image.as_slice()).unwrap();
+        assert_eq!(pixels, vec!(0, 0, 0, 255, 255, 255, 255, 255,
+                                0, 0, 0, 255, 255, 255, 255, 255));
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_bitmaps() {
+        // Runs of three white pixels and one black one, and then the end of the bitmap, which
+        // leaves the last pixel transparent.
+        let image = bitmap(5, 1, 8, 1, &BLACK_AND_WHITE, &[3, 1, 1, 0, 0, 1]);
+        let (_, _, pixels) = decode(image.as_slice()).unwrap();
+        assert_eq!(pixels, vec!(255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
+                                0, 0, 0, 255, 0, 0, 0, 0));
+    }
+}
@@ -0,0 +1,376 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes every frame of an animated GIF, per the
+//! [GIF89a specification](http://www.w3.org/Graphics/GIF/spec-gif89a.txt). GIFs with a single
+//! frame are left to stb_image.
+
+use image::animation::{Animation, AnimationCompositor, BackgroundDisposal, DisposalMethod};
+use image::animation::{KeepDisposal, PreviousDisposal, frame_delay};
+
+use std::iter::range_step;
+
+static EXTENSION_INTRODUCER: u8 = 0x21;
+static IMAGE_SEPARATOR: u8 = 0x2c;
+static TRAILER: u8 = 0x3b;
+
+static GRAPHIC_CONTROL_LABEL: u8 = 0xf9;
+static APPLICATION_LABEL: u8 = 0xff;
+
+/// Codes are at most this many bits long, so the string table has at most 4096 entries.
+static MAX_CODE_SIZE: uint = 12;
+
+/// Returns true if the buffer starts with a GIF signature.
+pub fn is_gif(buffer: &[u8]) -> bool {
+    buffer.starts_with(b"GIF87a") || buffer.starts_with(b"GIF89a")
+}
+
+/// Decodes the frames of an animated GIF. Returns `None` if the GIF is broken before its second
+/// frame or has only one frame.
+pub fn decode_animation(buffer: &[u8]) -> Option<Animation> {
+    if !is_gif(buffer) {
+        return None
+    }
+    decode(buffer).ok().and_then(|animation| animation)
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: uint,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, length: uint) -> Result<&'a [u8], ()> {
+        if self.position + length > self.buffer.len() {
+            return Err(())
+        }
+        let bytes = self.buffer.slice(self.position, self.position + length);
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ()> {
+        Ok(try!(self.read_bytes(1))[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, ()> {
+        let bytes = try!(self.read_bytes(2));
+        Ok(bytes[0] as u16 | (bytes[1] as u16 << 8))
+    }
+
+    /// Reads a sequence of data sub-blocks and returns their contents joined together.
+    fn read_sub_blocks(&mut self) -> Result<Vec<u8>, ()> {
+        let mut data = vec!();
+        loop {
+            let length = try!(self.read_u8()) as uint;
+            if length == 0 {
+                return Ok(data)
+            }
+            data.push_all(try!(self.read_bytes(length)));
+        }
+    }
+
+    /// Reads a color table if the flags of a screen or image descriptor say that one follows.
+    fn read_color_table(&mut self, flags: u8) -> Result<Option<&'a [u8]>, ()> {
+        if flags & 0x80 == 0 {
+            return Ok(None)
+        }
+        let entries = 1u << ((flags & 0x07) as uint + 1);
+        Ok(Some(try!(self.read_bytes(entries * 3))))
+    }
+}
+
+/// The graphic control extension that applies to the next image.
+struct GraphicControl {
+    delay: u64,
+    disposal: DisposalMethod,
+    transparent_index: Option<u8>,
+}
+
+impl GraphicControl {
+    fn new() -> GraphicControl {
+        GraphicControl {
+            delay: 0,
+            disposal: KeepDisposal,
+            transparent_index: None,
+        }
+    }
+}
+
+fn decode(buffer: &[u8]) -> Result<Option<Animation>, ()> {
+    let mut reader = Reader {
+        buffer: buffer,
+        position: 6,
+    };
+    let width = try!(reader.read_u16()) as u32;
+    let height = try!(reader.read_u16()) as u32;
+    let flags = try!(reader.read_u8());
+    // Skip the background color index and the pixel aspect ratio. Like other browsers, we clear
+    // to transparent rather than to the background color.
+    try!(reader.read_bytes(2));
+    let global_color_table = try!(reader.read_color_table(flags));
+
+    let mut compositor = try!(AnimationCompositor::new(width, height));
+    let mut play_count = Some(1);
+    let mut control = GraphicControl::new();
+    loop {
+        // Play whatever frames arrived before a truncated file ends.
+        let introducer = match reader.read_u8() {
+            Ok(introducer) => introducer,
+            Err(()) => break,
+        };
+        if introducer == TRAILER {
+            break
+        } else if introducer == EXTENSION_INTRODUCER {
+            let label = try!(reader.read_u8());
+            let data = try!(reader.read_sub_blocks());
+            if label == GRAPHIC_CONTROL_LABEL && data.len() >= 4 {
+                control = GraphicControl {
+                    delay: (data[1] as u64 | (data[2] as u64 << 8)) * 10,
+                    disposal: match (data[0] >> 2) & 0x07 {
+                        2 => BackgroundDisposal,
+                        3 => PreviousDisposal,
+                        _ => KeepDisposal,
+                    },
+                    transparent_index: if data[0] & 0x01 != 0 { Some(data[3]) } else { None },
+                };
+            } else if label == APPLICATION_LABEL && data.len() >= 14 &&
+                    data.slice_to(11) == b"NETSCAPE2.0" && data[11] == 1 {
+                // The loop count counts the repeats after the first play.
+                play_count = match data[12] as u32 | (data[13] as u32 << 8) {
+                    0 => None,
+                    repeats => Some(repeats + 1),
+                };
+            }
+        } else if introducer == IMAGE_SEPARATOR {
+            let left = try!(reader.read_u16()) as u32;
+            let top = try!(reader.read_u16()) as u32;
+            let frame_width = try!(reader.read_u16()) as uint;
+            let frame_height = try!(reader.read_u16()) as uint;
+            let flags = try!(reader.read_u8());
+            let local_color_table = try!(reader.read_color_table(flags));
+            let color_table = match local_color_table.or(global_color_table) {
+                Some(color_table) => color_table,
+                None => return Err(()),
+            };
+            let min_code_size = try!(reader.read_u8()) as uint;
+            let data = try!(reader.read_sub_blocks());
+
+            let pixel_count = frame_width * frame_height;
+            let indices = try!(decode_lzw(data.as_slice(), min_code_size, pixel_count));
+            let row_order = if flags & 0x40 != 0 {
+                interlaced_row_order(frame_height)
+            } else {
+                range(0, frame_height).collect()
+            };
+
+            // Pixels that are transparent, outside the color table, or missing from a truncated
+            // image are left transparent.
+            let mut pixels = Vec::from_elem(pixel_count * 4, 0u8);
+            for (index_position, &color_index) in indices.iter().enumerate() {
+                if Some(color_index) == control.transparent_index ||
+                        color_index as uint * 3 + 3 > color_table.len() {
+                    continue
+                }
+                let row = row_order[index_position / frame_width];
+                let pixel = (row * frame_width + index_position % frame_width) * 4;
+                let color = color_table.slice(color_index as uint * 3, color_index as uint * 3 + 3);
+                pixels.slice_mut(pixel, pixel + 3).copy_from(color);
+                *pixels.get_mut(pixel + 3) = 255;
+            }
+
+            try!(compositor.add_frame(left,
+                                      top,
+                                      frame_width as u32,
+                                      pixels.as_slice(),
+                                      true,
+                                      frame_delay(control.delay),
+                                      control.disposal));
+            control = GraphicControl::new();
+        } else {
+            return Err(())
+        }
+    }
+    Ok(compositor.finish(play_count))
+}
+
+/// Returns the row of the image that each row of an interlaced image's data belongs in.
+fn interlaced_row_order(height: uint) -> Vec<uint> {
+    let mut rows = vec!();
+    for &(start, step) in [(0u, 8u), (4, 8), (2, 4), (1, 2)].iter() {
+        rows.extend(range_step(start, height, step));
+    }
+    rows
+}
+
+/// Decompresses the color indices of an image. Returns fewer than `pixel_count` indices if the
+/// data ends early.
+fn decode_lzw(data: &[u8], min_code_size: uint, pixel_count: uint) -> Result<Vec<u8>, ()> {
+    if min_code_size == 0 || min_code_size > 8 {
+        return Err(())
+    }
+    let clear_code = 1u << min_code_size;
+    let end_code = clear_code + 1;
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    // Each entry of the string table is the entry of its prefix followed by its suffix. The
+    // entries below the clear code are the single indices.
+    let mut prefixes = Vec::from_elem(1 << MAX_CODE_SIZE, 0u16);
+    let mut suffixes = Vec::from_fn(1 << MAX_CODE_SIZE, |code| code as u8);
+
+    let mut output = Vec::with_capacity(pixel_count);
+    let mut stack = vec!();
+    let mut previous_code = None;
+    let (mut bits, mut bit_count) = (0u32, 0u);
+    let mut bytes = data.iter();
+    while output.len() < pixel_count {
+        // Codes are packed starting from the least significant bit.
+        while bit_count < code_size {
+            match bytes.next() {
+                Some(&byte) => {
+                    bits |= byte as u32 << bit_count;
+                    bit_count += 8;
+                }
+                None => return Ok(output),
+            }
+        }
+        let code = (bits & ((1 << code_size) - 1)) as uint;
+        bits >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+            previous_code = None;
+            continue
+        }
+        if code == end_code {
+            break
+        }
+
+        match previous_code {
+            None => {
+                if code > clear_code {
+                    return Err(())
+                }
+                output.push(code as u8);
+            }
+            Some(previous) => {
+                // A code that is not in the table yet stands for the previous entry followed by
+                // its own first index.
+                let first_index = if code < next_code {
+                    push_entry(&mut output, &mut stack, prefixes.as_slice(), suffixes.as_slice(),
+                               code, clear_code)
+                } else if code == next_code {
+                    let first_index = push_entry(&mut output,
+                                                 &mut stack,
+                                                 prefixes.as_slice(),
+                                                 suffixes.as_slice(),
+                                                 previous,
+                                                 clear_code);
+                    output.push(first_index);
+                    first_index
+                } else {
+                    return Err(())
+                };
+
+                if next_code < (1 << MAX_CODE_SIZE) {
+                    *prefixes.get_mut(next_code) = previous as u16;
+                    *suffixes.get_mut(next_code) = first_index;
+                    next_code += 1;
+                    if next_code == (1 << code_size) && code_size < MAX_CODE_SIZE {
+                        code_size += 1
+                    }
+                }
+            }
+        }
+        previous_code = Some(code);
+    }
+    output.truncate(pixel_count);
+    Ok(output)
+}
+
+/// Appends the indices of a string table entry to `output` and returns the first of them.
+fn push_entry(output: &mut Vec<u8>,
+              stack: &mut Vec<u8>,
+              prefixes: &[u16],
+              suffixes: &[u8],
+              code: uint,
+              clear_code: uint)
+              -> u8 {
+    stack.truncate(0);
+    let mut code = code;
+    while code > clear_code {
+        stack.push(suffixes[code]);
+        code = prefixes[code] as uint;
+    }
+    let first_index = suffixes[code];
+    output.push(first_index);
+    output.extend(stack.iter().rev().map(|&index| index));
+    first_index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_animation, decode_lzw, interlaced_row_order};
+
+    use image::animation::{BackgroundDisposal, KeepDisposal, PinnedFrame};
+
+    /// A 2×2 GIF that loops forever: a red frame for 100 ms, then a green frame over the right
+    /// column for 50 ms that is cleared afterwards, then a blue pixel at the bottom left that
+    /// asks for no delay.
+    static ANIMATED_GIF: &'static [u8] = include_bin!("test-animated.gif");
+
+    #[test]
+    fn test_decode_lzw() {
+        // Codes 4 and 5 are the clear and end codes for indices 0 to 3. Adding entry 7 to the
+        // table makes the codes after it one bit longer.
+        let codes = [(4u32, 3u), (0, 3), (1, 3), (6, 3), (8, 4), (5, 4)];
+        let (mut bits, mut bit_count, mut data) = (0u32, 0u, vec!());
+        for &(code, code_size) in codes.iter() {
+            bits |= code << bit_count;
+            bit_count += code_size;
+            while bit_count >= 8 {
+                data.push(bits as u8);
+                bits >>= 8;
+                bit_count -= 8;
+            }
+        }
+        data.push(bits as u8);
+        assert_eq!(decode_lzw(data.as_slice(), 2, 7), Ok(vec!(0, 1, 0, 1, 0, 1, 0)));
+    }
+
+    #[test]
+    fn test_interlaced_row_order() {
+        assert_eq!(interlaced_row_order(10), vec!(0, 8, 4, 2, 6, 1, 3, 5, 7, 9));
+    }
+
+    #[test]
+    fn test_decode_animation() {
+        let animation = decode_animation(ANIMATED_GIF).unwrap();
+        assert_eq!(animation.play_count, None);
+        assert_eq!(animation.frames.len(), 3);
+        assert_eq!(animation.frames.iter().map(|frame| frame.delay).collect::<Vec<u64>>(),
+                   vec!(100, 50, 100));
+        assert_eq!(animation.frames[1].disposal, BackgroundDisposal);
+        assert_eq!(animation.frames[2].disposal, KeepDisposal);
+
+        // Premultiplied BGRA.
+        let (red, green, blue, clear) =
+            ([0u8, 0, 255, 255], [0u8, 255, 0, 255], [255u8, 0, 0, 255], [0u8, 0, 0, 0]);
+        let expected = vec!(
+            vec!(red, red, red, red),
+            vec!(red, green, red, green),
+            vec!(red, clear, blue, clear),
+        );
+        for (frame_index, expected_pixels) in expected.iter().enumerate() {
+            let image = animation.image_at(PinnedFrame(frame_index));
+            let pixels = image.pixels().to_vec();
+            let expected_pixels: Vec<u8> =
+                expected_pixels.iter().flat_map(|pixel| pixel.iter().map(|&byte| byte)).collect();
+            assert_eq!(pixels, expected_pixels);
+        }
+    }
+}
@@ -2,8 +2,9 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use image::animation::Animation;
 use image::base::Image;
-use image_cache_task::{ImageReady, ImageNotReady, ImageFailed};
+use image_cache_task::{DecodeSize, FullSize, ImageReady, ImageNotReady, ImageFailed};
 use local_image_cache::LocalImageCache;
 
 use geom::size::Size2D;
@@ -20,17 +21,37 @@ use url::Url;
 #[deriving(Clone)]
 pub struct ImageHolder {
     url: Url,
-    image: Option<Arc<Box<Image>>>,
+    /// The opaque address of the node that displays the image, which is reported back to the
+    /// `ImageResponder` if the image has to be waited for.
+    node_address: uint,
+    image: Option<Arc<Image>>,
+    animation: Option<Arc<Animation>>,
+    /// Whether the image could not be loaded or decoded.
+    failed: bool,
     cached_size: Size2D<int>,
     local_image_cache: Arc<Mutex<LocalImageCache>>,
 }
 
 impl ImageHolder {
-    pub fn new(url: Url, local_image_cache: Arc<Mutex<LocalImageCache>>) -> ImageHolder {
+    pub fn new(url: Url, node_address: uint, local_image_cache: Arc<Mutex<LocalImageCache>>)
+               -> ImageHolder {
+        ImageHolder::new_with_decode_size(url, node_address, local_image_cache, FullSize)
+    }
+
+    /// Creates a holder for an image that only needs to keep as many pixels as `decode_size`
+    /// says.
+    pub fn new_with_decode_size(url: Url,
+                                node_address: uint,
+                                local_image_cache: Arc<Mutex<LocalImageCache>>,
+                                decode_size: DecodeSize)
+                                -> ImageHolder {
         debug!("ImageHolder::new() {}", url.serialize());
         let holder = ImageHolder {
             url: url,
+            node_address: node_address,
             image: None,
+            animation: None,
+            failed: false,
             cached_size: Size2D(0,0),
             local_image_cache: local_image_cache.clone(),
         };
@@ -44,12 +65,17 @@ impl ImageHolder {
             let val = holder.local_image_cache.lock();
             let mut local_image_cache = val;
             local_image_cache.prefetch(&holder.url);
-            local_image_cache.decode(&holder.url);
+            local_image_cache.decode_to_size(&holder.url, decode_size);
         }
 
         holder
     }
 
+    /// Returns the URL that the image is loaded from.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
     /// This version doesn't perform any computation, but may be stale w.r.t. newly-available image
     /// data that determines size.
     ///
@@ -69,31 +95,42 @@ impl ImageHolder {
         })
     }
 
-    pub fn get_image_if_present(&self) -> Option<Arc<Box<Image>>> {
+    /// Returns true if `get_image` found that the image could not be loaded or decoded, in which
+    /// case it will never become available.
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn get_image_if_present(&self) -> Option<Arc<Image>> {
         debug!("get_image_if_present() {}", self.url.serialize());
         self.image.clone()
     }
 
-    pub fn get_image(&mut self) -> Option<Arc<Box<Image>>> {
+    /// Returns the frames of the image if it is animated and has been loaded.
+    pub fn get_animation_if_present(&self) -> Option<Arc<Animation>> {
+        self.animation.clone()
+    }
+
+    pub fn get_image(&mut self) -> Option<Arc<Image>> {
         debug!("get_image() {}", self.url.serialize());
 
         // If this is the first time we've called this function, load
         // the image and store it for the future
         if self.image.is_none() {
-            let port = {
-                let val = self.local_image_cache.lock();
-                let mut local_image_cache = val;
-                local_image_cache.get_image(&self.url)
-            };
+            let val = self.local_image_cache.lock();
+            let mut local_image_cache = val;
+            let port = local_image_cache.get_image(&self.url, self.node_address);
             match port.recv() {
                 ImageReady(image) => {
                     self.image = Some(image);
+                    self.animation = local_image_cache.get_animation(&self.url);
                 }
                 ImageNotReady => {
                     debug!("image not ready for {:s}", self.url.serialize());
                 }
                 ImageFailed => {
                     debug!("image decoding failed for {:s}", self.url.serialize());
+                    self.failed = true;
                 }
             }
         }
@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes Windows icons and cursors, which hold bitmaps or PNGs of several sizes, per the
+//! [icon documentation](http://msdn.microsoft.com/en-us/library/ms997538.aspx). The largest
+//! image is decoded.
+
+use image::base::{DecodeResult, MalformedImage, png_to_rgba};
+use image::bmp;
+
+use png;
+
+static HEADER_SIZE: uint = 6;
+static ENTRY_SIZE: uint = 16;
+
+static ICON_TYPE: u16 = 1;
+static CURSOR_TYPE: u16 = 2;
+
+/// Returns true if the buffer starts with an icon or cursor header with at least one image.
+pub fn is_ico(buffer: &[u8]) -> bool {
+    if buffer.len() < HEADER_SIZE || read_u16(buffer, 0) != 0 {
+        return false
+    }
+    let image_type = read_u16(buffer, 2);
+    (image_type == ICON_TYPE || image_type == CURSOR_TYPE) && read_u16(buffer, 4) > 0
+}
+
+struct Entry {
+    width: uint,
+    height: uint,
+    bits_per_pixel: u16,
+    size: uint,
+    offset: uint,
+}
+
+/// Decodes the largest image in an icon into its width, height and unpremultiplied RGBA pixels.
+pub fn decode(buffer: &[u8]) -> DecodeResult<(uint, uint, Vec<u8>)> {
+    if !is_ico(buffer) {
+        return Err(MalformedImage("the icon header is missing".to_string()))
+    }
+    let count = read_u16(buffer, 4) as uint;
+    if HEADER_SIZE + count * ENTRY_SIZE > buffer.len() {
+        return Err(MalformedImage("the icon directory is truncated".to_string()))
+    }
+
+    // Prefer the largest image, and then the one with the most colors.
+    let mut best: Option<Entry> = None;
+    for index in range(0, count) {
+        let start = HEADER_SIZE + index * ENTRY_SIZE;
+        // Sizes of zero stand for 256 pixels.
+        let entry = Entry {
+            width: if buffer[start] == 0 { 256 } else { buffer[start] as uint },
+            height: if buffer[start + 1] == 0 { 256 } else { buffer[start + 1] as uint },
+            bits_per_pixel: read_u16(buffer, start + 6),
+            size: read_u32(buffer, start + 8) as uint,
+            offset: read_u32(buffer, start + 12) as uint,
+        };
+        if entry.offset > buffer.len() || entry.size > buffer.len() - entry.offset {
+            continue
+        }
+        let is_better = match best {
+            None => true,
+            Some(ref best) => {
+                (entry.width * entry.height, entry.bits_per_pixel) >
+                    (best.width * best.height, best.bits_per_pixel)
+            }
+        };
+        if is_better {
+            best = Some(entry);
+        }
+    }
+    let entry = match best {
+        Some(entry) => entry,
+        None => return Err(MalformedImage("the icon images are truncated".to_string())),
+    };
+
+    let data = buffer.slice(entry.offset, entry.offset + entry.size);
+    if png::is_png(data) {
+        return match png::load_png_from_memory(data) {
+            Ok(image) => {
+                Ok((image.width as uint, image.height as uint, png_to_rgba(image.pixels)))
+            }
+            Err(message) => Err(MalformedImage(message)),
+        }
+    }
+    bmp::decode_dib(data, None, true)
+}
+
+fn read_u16(data: &[u8], offset: uint) -> u16 {
+    data[offset] as u16 | (data[offset + 1] as u16 << 8)
+}
+
+fn read_u32(data: &[u8], offset: uint) -> u32 {
+    data[offset] as u32 | (data[offset + 1] as u32 << 8) | (data[offset + 2] as u32 << 16) |
+        (data[offset + 3] as u32 << 24)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, is_ico};
+
+    /// An icon with a 1x1 8-bit image and a 2x1 32-bit image.
+    fn icon() -> Vec<u8> {
+        let mut data = vec!(0, 0, 1, 0, 2, 0);
+        data.push_all(&[1, 1, 0, 0, 1, 0, 8, 0, 52, 0, 0, 0, 38, 0, 0, 0]);
+        data.push_all(&[2, 1, 0, 0, 1, 0, 32, 0, 52, 0, 0, 0, 90, 0, 0, 0]);
+        for &(width, bits_per_pixel, color_count) in [(1u8, 8u8, 1u8), (2, 32, 0)].iter() {
+            // The bitmap is twice as tall as the image, to make room for the mask.
+            data.push_all(&[40, 0, 0, 0, width, 0, 0, 0, 2, 0, 0, 0, 1, 0, bits_per_pixel, 0]);
+            data.push_all(&[0, ..16]);
+            data.push_all(&[color_count, 0, 0, 0, 0, 0, 0, 0]);
+            if bits_per_pixel == 8 {
+                // One red color, a pixel of it, and a mask that leaves it visible.
+                data.push_all(&[0, 0, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            } else {
+                // A translucent blue pixel, an opaque green one, and an unused mask.
+                data.push_all(&[0xff, 0, 0, 0x80, 0, 0xff, 0, 0xff, 0, 0, 0, 0]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_the_largest_image() {
+        let icon = icon();
+        assert!(is_ico(icon.as_slice()));
+        let (width, height, pixels) = decode(icon.as_slice()).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(pixels, vec!(0, 0, 255, 128, 0, 255, 0, 255));
+    }
+}
@@ -0,0 +1,705 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes lossless WebP images, per the [WebP lossless bitstream specification][spec]. Pixels
+//! are handled as ARGB words, as the specification does.
+//!
+//! [spec]: https://developers.google.com/speed/webp/docs/webp_lossless_bitstream_specification
+
+use image::base::{DecodeResult, MalformedImage};
+use image::webp::MAX_PIXELS;
+
+use std::cmp;
+
+static SIGNATURE: u8 = 0x2f;
+
+static NUM_LITERAL_CODES: uint = 256;
+static NUM_LENGTH_CODES: uint = 24;
+static NUM_DISTANCE_CODES: uint = 40;
+static MAX_COLOR_CACHE_BITS: u32 = 11;
+static MAX_CODE_LENGTH: uint = 15;
+
+/// Code lengths are read in this order, so that the ones that are usually zero come last.
+static CODE_LENGTH_CODE_ORDER: [uint, ..19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11,
+                                               12, 13, 14, 15];
+
+/// The offsets of the pixels that the first 120 distance codes refer to, as `(dy << 4) | (8 -
+/// dx)`, nearest first.
+static CODE_TO_PLANE: [u8, ..120] = [
+    0x18, 0x07, 0x17, 0x19, 0x28, 0x06, 0x27, 0x29, 0x16, 0x1a,
+    0x26, 0x2a, 0x38, 0x05, 0x37, 0x39, 0x15, 0x1b, 0x36, 0x3a,
+    0x25, 0x2b, 0x48, 0x04, 0x47, 0x49, 0x14, 0x1c, 0x35, 0x3b,
+    0x46, 0x4a, 0x24, 0x2c, 0x58, 0x45, 0x4b, 0x34, 0x3c, 0x03,
+    0x57, 0x59, 0x13, 0x1d, 0x56, 0x5a, 0x23, 0x2d, 0x44, 0x4c,
+    0x55, 0x5b, 0x33, 0x3d, 0x68, 0x02, 0x67, 0x69, 0x12, 0x1e,
+    0x66, 0x6a, 0x22, 0x2e, 0x54, 0x5c, 0x43, 0x4d, 0x65, 0x6b,
+    0x32, 0x3e, 0x78, 0x01, 0x77, 0x79, 0x53, 0x5d, 0x11, 0x1f,
+    0x64, 0x6c, 0x42, 0x4e, 0x76, 0x7a, 0x21, 0x2f, 0x75, 0x7b,
+    0x31, 0x3f, 0x63, 0x6d, 0x52, 0x5e, 0x00, 0x74, 0x7c, 0x41,
+    0x4f, 0x10, 0x20, 0x62, 0x6e, 0x30, 0x73, 0x7d, 0x51, 0x5f,
+    0x40, 0x72, 0x7e, 0x61, 0x6f, 0x50, 0x71, 0x7f, 0x60, 0x70,
+];
+
+/// Reads bits from the least significant end of each byte.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: uint,
+    buffer: u64,
+    bit_count: uint,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data: data,
+            position: 0,
+            buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.bit_count <= 56 && self.position < self.data.len() {
+            self.buffer |= (self.data[self.position] as u64) << self.bit_count;
+            self.position += 1;
+            self.bit_count += 8;
+        }
+    }
+
+    /// Returns the next `count` bits without consuming them. Bits past the end of the data are
+    /// zeroes.
+    fn peek(&mut self, count: uint) -> u32 {
+        if self.bit_count < count {
+            self.fill();
+        }
+        (self.buffer & ((1 << count) - 1)) as u32
+    }
+
+    fn consume(&mut self, count: uint) -> DecodeResult<()> {
+        if self.bit_count < count {
+            self.fill();
+            if self.bit_count < count {
+                return Err(MalformedImage("the lossless WebP data is truncated".to_string()))
+            }
+        }
+        self.buffer >>= count;
+        self.bit_count -= count;
+        Ok(())
+    }
+
+    fn read_bits(&mut self, count: uint) -> DecodeResult<u32> {
+        let value = self.peek(count);
+        try!(self.consume(count));
+        Ok(value)
+    }
+
+    fn read_flag(&mut self) -> DecodeResult<bool> {
+        Ok(try!(self.read_bits(1)) == 1)
+    }
+}
+
+/// Codes up to this long are decoded with a single table lookup.
+static LOOKUP_BITS: uint = 8;
+
+/// A canonical Huffman code.
+struct HuffmanTree {
+    /// The symbol of a code with a single symbol, which takes no bits.
+    single_symbol: Option<u16>,
+    /// The number of codes of each length.
+    counts: [u16, ..16],
+    /// The symbols, in the order of their codes.
+    symbols: Vec<u16>,
+    /// The symbol and length of the codes up to `LOOKUP_BITS` long, indexed by their bits as
+    /// they are read, as `(length << 12) | symbol`; zero for longer codes.
+    lookup: Vec<u16>,
+}
+
+impl HuffmanTree {
+    /// Builds the code with the given code length for each symbol, where zero means that the
+    /// symbol does not occur. Fails unless the lengths describe a complete code.
+    fn new(code_lengths: &[uint]) -> DecodeResult<HuffmanTree> {
+        let malformed = || MalformedImage("a lossless WebP Huffman code is invalid".to_string());
+        let mut counts = [0u16, ..16];
+        for &length in code_lengths.iter() {
+            counts[length] += 1;
+        }
+        let symbol_count = code_lengths.len() - counts[0] as uint;
+        if symbol_count == 0 {
+            return Err(malformed())
+        }
+        if symbol_count == 1 {
+            let symbol = code_lengths.iter().position(|&length| length != 0).unwrap();
+            return Ok(HuffmanTree {
+                single_symbol: Some(symbol as u16),
+                counts: counts,
+                symbols: vec!(),
+                lookup: vec!(),
+            })
+        }
+
+        let mut open_codes = 1i;
+        for length in range(1, MAX_CODE_LENGTH + 1) {
+            open_codes = open_codes * 2 - counts[length] as int;
+            if open_codes < 0 {
+                return Err(malformed())
+            }
+        }
+        if open_codes != 0 {
+            return Err(malformed())
+        }
+
+        let mut offsets = [0u, ..16];
+        for length in range(1, MAX_CODE_LENGTH) {
+            offsets[length + 1] = offsets[length] + counts[length] as uint;
+        }
+        let mut symbols = Vec::from_elem(symbol_count, 0u16);
+        for (symbol, &length) in code_lengths.iter().enumerate() {
+            if length != 0 {
+                *symbols.get_mut(offsets[length]) = symbol as u16;
+                offsets[length] += 1;
+            }
+        }
+
+        let mut lookup = Vec::from_elem(1 << LOOKUP_BITS, 0u16);
+        let mut code = 0u;
+        let mut index = 0u;
+        for length in range(1, LOOKUP_BITS + 1) {
+            for _ in range(0, counts[length]) {
+                // Codes are read from their most significant bit, so the lookup table is indexed
+                // by their reversal.
+                let mut reversed = 0u;
+                for bit in range(0, length) {
+                    reversed |= ((code >> bit) & 1) << (length - 1 - bit);
+                }
+                let entry = ((length as u16) << 12) | symbols[index];
+                for high_bits in range(0u, 1 << (LOOKUP_BITS - length)) {
+                    *lookup.get_mut(reversed | (high_bits << length)) = entry;
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+
+        Ok(HuffmanTree {
+            single_symbol: None,
+            counts: counts,
+            symbols: symbols,
+            lookup: lookup,
+        })
+    }
+
+    fn read_symbol(&self, reader: &mut BitReader) -> DecodeResult<uint> {
+        match self.single_symbol {
+            Some(symbol) => return Ok(symbol as uint),
+            None => {}
+        }
+        let entry = self.lookup[reader.peek(LOOKUP_BITS) as uint];
+        if entry != 0 {
+            try!(reader.consume((entry >> 12) as uint));
+            return Ok((entry & 0xfff) as uint)
+        }
+
+        // Walk the code a bit at a time, as in zlib's puff.
+        let bits = reader.peek(MAX_CODE_LENGTH);
+        let (mut code, mut first, mut index) = (0i, 0i, 0i);
+        for length in range(1, MAX_CODE_LENGTH + 1) {
+            code |= ((bits >> (length - 1)) & 1) as int;
+            let count = self.counts[length] as int;
+            if code - first < count {
+                try!(reader.consume(length));
+                return Ok(self.symbols[(index + code - first) as uint] as uint)
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(MalformedImage("a lossless WebP Huffman code is invalid".to_string()))
+    }
+}
+
+/// The five codes of a prefix code group: green with lengths and color cache indices, red,
+/// blue, alpha and distance.
+struct HuffmanGroup {
+    trees: Vec<HuffmanTree>,
+}
+
+/// Recently used colors, which pixels can refer to by a hash of the color.
+struct ColorCache {
+    colors: Vec<u32>,
+    bits: u32,
+}
+
+impl ColorCache {
+    fn insert(&mut self, color: u32) {
+        let key = (0x1e35a7bdu32 * color) >> (32 - self.bits as uint);
+        *self.colors.get_mut(key as uint) = color;
+    }
+}
+
+#[deriving(PartialEq)]
+enum TransformType {
+    PredictorTransform,
+    ColorTransform,
+    SubtractGreenTransform,
+    ColorIndexingTransform,
+}
+
+struct Transform {
+    transform_type: TransformType,
+    /// The width of the image before the transform, which the inverse transform restores.
+    width: uint,
+    /// The size of the tiles of predictor and color transforms as a power of two, or the number
+    /// of pixels packed into each byte of color indexed images as a power of two.
+    bits: uint,
+    /// The modes or multipliers of each tile, or the colors of the palette.
+    data: Vec<u32>,
+}
+
+/// Returns the size and ARGB pixels of a lossless WebP bit stream.
+pub fn decode(data: &[u8]) -> DecodeResult<(uint, uint, Vec<u32>)> {
+    let mut reader = BitReader::new(data);
+    if try!(reader.read_bits(8)) as u8 != SIGNATURE {
+        return Err(MalformedImage("the lossless WebP signature is missing".to_string()))
+    }
+    let width = try!(reader.read_bits(14)) as uint + 1;
+    let height = try!(reader.read_bits(14)) as uint + 1;
+    // Whether the alpha channel is used, which is only a hint.
+    try!(reader.read_bits(1));
+    if try!(reader.read_bits(3)) != 0 {
+        return Err(MalformedImage("the lossless WebP version is unknown".to_string()))
+    }
+    let pixels = try!(decode_image(&mut reader, width, height));
+    Ok((width, height, pixels))
+}
+
+/// Returns the alpha values in the green channel of a lossless bit stream with no header, as in
+/// the alpha chunk of lossy images.
+pub fn decode_alpha(data: &[u8], width: uint, height: uint) -> DecodeResult<Vec<u8>> {
+    let mut reader = BitReader::new(data);
+    let pixels = try!(decode_image(&mut reader, width, height));
+    Ok(pixels.iter().map(|&pixel| (pixel >> 8) as u8).collect())
+}
+
+/// Converts ARGB pixels to unpremultiplied RGBA bytes.
+pub fn argb_to_rgba(pixels: &[u32]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(pixels.len() * 4);
+    for &pixel in pixels.iter() {
+        rgba.push((pixel >> 16) as u8);
+        rgba.push((pixel >> 8) as u8);
+        rgba.push(pixel as u8);
+        rgba.push((pixel >> 24) as u8);
+    }
+    rgba
+}
+
+/// Decodes the main image of a bit stream: its transforms, then its pixels, and then undoes the
+/// transforms.
+fn decode_image(reader: &mut BitReader, width: uint, height: uint) -> DecodeResult<Vec<u32>> {
+    if width * height > MAX_PIXELS {
+        return Err(MalformedImage("the lossless WebP image is too large".to_string()))
+    }
+    let mut transforms: Vec<Transform> = vec!();
+    let mut coded_width = width;
+    while try!(reader.read_flag()) {
+        let transform_type = match try!(reader.read_bits(2)) {
+            0 => PredictorTransform,
+            1 => ColorTransform,
+            2 => SubtractGreenTransform,
+            _ => ColorIndexingTransform,
+        };
+        if transforms.iter().any(|transform| transform.transform_type == transform_type) {
+            return Err(MalformedImage("a lossless WebP transform is repeated".to_string()))
+        }
+        let mut transform = Transform {
+            transform_type: transform_type,
+            width: coded_width,
+            bits: 0,
+            data: vec!(),
+        };
+        match transform_type {
+            PredictorTransform | ColorTransform => {
+                transform.bits = try!(reader.read_bits(3)) as uint + 2;
+                transform.data = try!(decode_entropy_image(reader,
+                                                           subsample(coded_width, transform.bits),
+                                                           subsample(height, transform.bits)));
+            }
+            ColorIndexingTransform => {
+                let color_count = try!(reader.read_bits(8)) as uint + 1;
+                transform.bits = match color_count {
+                    0...2 => 3,
+                    3...4 => 2,
+                    5...16 => 1,
+                    _ => 0,
+                };
+                transform.data = try!(read_palette(reader, color_count));
+                coded_width = subsample(coded_width, transform.bits);
+            }
+            SubtractGreenTransform => {}
+        }
+        transforms.push(transform);
+    }
+
+    let mut pixels = try!(decode_pixels(reader, coded_width, height, true));
+    for transform in transforms.iter().rev() {
+        pixels = inverse_transform(transform, pixels, height);
+    }
+    Ok(pixels)
+}
+
+/// Decodes one of the images that describe the transforms and the prefix codes of the main
+/// image, which have no transforms or prefix code groups of their own.
+fn decode_entropy_image(reader: &mut BitReader, width: uint, height: uint)
+                        -> DecodeResult<Vec<u32>> {
+    decode_pixels(reader, width, height, false)
+}
+
+/// Reads the colors of a palette, each of which is stored as the difference from the one before.
+/// Indices past the end of the palette are transparent black.
+fn read_palette(reader: &mut BitReader, color_count: uint) -> DecodeResult<Vec<u32>> {
+    let colors = try!(decode_entropy_image(reader, color_count, 1));
+    let mut palette = Vec::from_elem(256, 0u32);
+    let mut previous = 0u32;
+    for (index, &color) in colors.iter().enumerate() {
+        previous = add_pixels(previous, color);
+        *palette.get_mut(index) = previous;
+    }
+    Ok(palette)
+}
+
+/// Decodes the entropy-coded pixels of an image, reading its color cache and prefix codes
+/// first.
+fn decode_pixels(reader: &mut BitReader, width: uint, height: uint, is_main_image: bool)
+                 -> DecodeResult<Vec<u32>> {
+    let mut color_cache = if try!(reader.read_flag()) {
+        let bits = try!(reader.read_bits(4));
+        if bits < 1 || bits > MAX_COLOR_CACHE_BITS {
+            return Err(MalformedImage("the lossless WebP color cache size is invalid"
+                                      .to_string()))
+        }
+        Some(ColorCache {
+            colors: Vec::from_elem(1 << bits as uint, 0u32),
+            bits: bits,
+        })
+    } else {
+        None
+    };
+    let cache_size = color_cache.as_ref().map_or(0, |cache| cache.colors.len());
+
+    // Main images may use different prefix codes in different tiles.
+    let (group_bits, group_image) = if is_main_image && try!(reader.read_flag()) {
+        let bits = try!(reader.read_bits(3)) as uint + 2;
+        let image = try!(decode_entropy_image(reader, subsample(width, bits),
+                                              subsample(height, bits)));
+        (bits, image)
+    } else {
+        (0, vec!())
+    };
+    let group_count = group_image.iter().fold(1, |count, &pixel| {
+        cmp::max(count, ((pixel >> 8) & 0xffff) as uint + 1)
+    });
+    let mut groups = Vec::with_capacity(group_count);
+    for _ in range(0, group_count) {
+        groups.push(try!(read_huffman_group(reader, cache_size)));
+    }
+    let group_width = subsample(width, group_bits);
+
+    let total = width * height;
+    let mut pixels: Vec<u32> = Vec::with_capacity(total);
+    while pixels.len() < total {
+        let (x, y) = (pixels.len() % width, pixels.len() / width);
+        let group = if group_bits == 0 {
+            &groups[0]
+        } else {
+            let index = (y >> group_bits) * group_width + (x >> group_bits);
+            &groups[((group_image[index] >> 8) & 0xffff) as uint]
+        };
+
+        let code = try!(group.trees[0].read_symbol(reader));
+        if code < NUM_LITERAL_CODES {
+            let red = try!(group.trees[1].read_symbol(reader)) as u32;
+            let blue = try!(group.trees[2].read_symbol(reader)) as u32;
+            let alpha = try!(group.trees[3].read_symbol(reader)) as u32;
+            let pixel = (alpha << 24) | (red << 16) | ((code as u32) << 8) | blue;
+            pixels.push(pixel);
+            match color_cache {
+                Some(ref mut cache) => cache.insert(pixel),
+                None => {}
+            }
+        } else if code < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+            let length = try!(read_copy_value(reader, code - NUM_LITERAL_CODES));
+            let distance_symbol = try!(group.trees[4].read_symbol(reader));
+            let distance_code = try!(read_copy_value(reader, distance_symbol));
+            let distance = plane_code_to_distance(width, distance_code);
+            let start = pixels.len();
+            if distance > start || start + length > total {
+                return Err(MalformedImage("a lossless WebP back reference is out of bounds"
+                                          .to_string()))
+            }
+            for index in range(start, start + length) {
+                let pixel = pixels[index - distance];
+                pixels.push(pixel);
+                match color_cache {
+                    Some(ref mut cache) => cache.insert(pixel),
+                    None => {}
+                }
+            }
+        } else {
+            let pixel = match color_cache {
+                Some(ref mut cache) if code - NUM_LITERAL_CODES - NUM_LENGTH_CODES < cache_size => {
+                    let pixel = cache.colors[code - NUM_LITERAL_CODES - NUM_LENGTH_CODES];
+                    cache.insert(pixel);
+                    pixel
+                }
+                _ => {
+                    return Err(MalformedImage("a lossless WebP color cache index is invalid"
+                                              .to_string()))
+                }
+            };
+            pixels.push(pixel);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Reads the five prefix codes of a group.
+fn read_huffman_group(reader: &mut BitReader, cache_size: uint) -> DecodeResult<HuffmanGroup> {
+    let alphabet_sizes = [NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size, NUM_LITERAL_CODES,
+                          NUM_LITERAL_CODES, NUM_LITERAL_CODES, NUM_DISTANCE_CODES];
+    let mut trees = Vec::with_capacity(5);
+    for &alphabet_size in alphabet_sizes.iter() {
+        trees.push(try!(read_huffman_tree(reader, alphabet_size)));
+    }
+    Ok(HuffmanGroup {
+        trees: trees,
+    })
+}
+
+/// Reads a prefix code, which lists either one or two symbols or the code lengths of all the
+/// symbols, themselves encoded with a prefix code.
+fn read_huffman_tree(reader: &mut BitReader, alphabet_size: uint) -> DecodeResult<HuffmanTree> {
+    let mut code_lengths = Vec::from_elem(alphabet_size, 0u);
+    if try!(reader.read_flag()) {
+        let symbol_count = try!(reader.read_bits(1)) + 1;
+        let first_symbol_bits = if try!(reader.read_flag()) { 8 } else { 1 };
+        let mut symbols = vec!(try!(reader.read_bits(first_symbol_bits)) as uint);
+        if symbol_count == 2 {
+            symbols.push(try!(reader.read_bits(8)) as uint);
+        }
+        for &symbol in symbols.iter() {
+            if symbol < alphabet_size {
+                *code_lengths.get_mut(symbol) = 1;
+            }
+        }
+        return HuffmanTree::new(code_lengths.as_slice())
+    }
+
+    let mut code_length_code_lengths = [0u, ..19];
+    let code_count = try!(reader.read_bits(4)) as uint + 4;
+    for &symbol in CODE_LENGTH_CODE_ORDER.slice_to(code_count).iter() {
+        code_length_code_lengths[symbol] = try!(reader.read_bits(3)) as uint;
+    }
+    let code_length_code = try!(HuffmanTree::new(&code_length_code_lengths));
+
+    let mut remaining_codes = if try!(reader.read_flag()) {
+        let length_bits = 2 + 2 * try!(reader.read_bits(3)) as uint;
+        let count = 2 + try!(reader.read_bits(length_bits)) as uint;
+        if count > alphabet_size {
+            return Err(MalformedImage("a lossless WebP Huffman code is too long".to_string()))
+        }
+        count
+    } else {
+        alphabet_size
+    };
+
+    let mut symbol = 0;
+    let mut previous_length = 8;
+    while symbol < alphabet_size && remaining_codes > 0 {
+        remaining_codes -= 1;
+        let code = try!(code_length_code.read_symbol(reader));
+        if code < 16 {
+            *code_lengths.get_mut(symbol) = code;
+            symbol += 1;
+            if code != 0 {
+                previous_length = code;
+            }
+            continue
+        }
+        let (repeat, length) = match code {
+            16 => (try!(reader.read_bits(2)) as uint + 3, previous_length),
+            17 => (try!(reader.read_bits(3)) as uint + 3, 0),
+            _ => (try!(reader.read_bits(7)) as uint + 11, 0),
+        };
+        if symbol + repeat > alphabet_size {
+            return Err(MalformedImage("a lossless WebP Huffman code is too long".to_string()))
+        }
+        for _ in range(0, repeat) {
+            *code_lengths.get_mut(symbol) = length;
+            symbol += 1;
+        }
+    }
+    HuffmanTree::new(code_lengths.as_slice())
+}
+
+/// Reads a length or distance, which are coded as a prefix symbol and extra bits.
+fn read_copy_value(reader: &mut BitReader, symbol: uint) -> DecodeResult<uint> {
+    if symbol < 4 {
+        return Ok(symbol + 1)
+    }
+    let extra_bits = (symbol - 2) >> 1;
+    let offset = (2 + (symbol & 1)) << extra_bits;
+    Ok(offset + try!(reader.read_bits(extra_bits)) as uint + 1)
+}
+
+/// Turns a distance code into the distance in pixels that it refers to. The first codes stand
+/// for nearby pixels in two dimensions.
+fn plane_code_to_distance(width: uint, code: uint) -> uint {
+    if code > CODE_TO_PLANE.len() {
+        return code - CODE_TO_PLANE.len()
+    }
+    let offset = CODE_TO_PLANE[code - 1] as int;
+    let (dy, dx) = (offset >> 4, 8 - (offset & 0xf));
+    cmp::max(dy * width as int + dx, 1) as uint
+}
+
+fn inverse_transform(transform: &Transform, pixels: Vec<u32>, height: uint) -> Vec<u32> {
+    let width = transform.width;
+    let tiles_per_row = subsample(width, transform.bits);
+    match transform.transform_type {
+        PredictorTransform => {
+            let mut pixels = pixels;
+            for y in range(0, height) {
+                for x in range(0, width) {
+                    let index = y * width + x;
+                    let prediction = if y == 0 {
+                        if x == 0 { 0xff000000 } else { pixels[index - 1] }
+                    } else if x == 0 {
+                        pixels[index - width]
+                    } else {
+                        let tile = transform.data[(y >> transform.bits) * tiles_per_row +
+                                                  (x >> transform.bits)];
+                        predict(pixels.as_slice(), index, width, (tile >> 8) & 0xf)
+                    };
+                    *pixels.get_mut(index) = add_pixels(pixels[index], prediction);
+                }
+            }
+            pixels
+        }
+        ColorTransform => {
+            let mut pixels = pixels;
+            for y in range(0, height) {
+                for x in range(0, width) {
+                    let tile = transform.data[(y >> transform.bits) * tiles_per_row +
+                                              (x >> transform.bits)];
+                    let (green_to_red, green_to_blue, red_to_blue) =
+                        (tile as i8, (tile >> 8) as i8, (tile >> 16) as i8);
+                    let pixel = pixels.get_mut(y * width + x);
+                    let green = (*pixel >> 8) as i8;
+                    let red = ((*pixel >> 16) as i32 + color_transform_delta(green_to_red, green))
+                        & 0xff;
+                    let blue = (*pixel as i32 + color_transform_delta(green_to_blue, green) +
+                                color_transform_delta(red_to_blue, red as i8)) & 0xff;
+                    *pixel = (*pixel & 0xff00ff00) | ((red as u32) << 16) | blue as u32;
+                }
+            }
+            pixels
+        }
+        SubtractGreenTransform => {
+            pixels.into_iter().map(|pixel| {
+                let green = (pixel >> 8) & 0xff;
+                let red_blue = ((pixel & 0x00ff00ff) + ((green << 16) | green)) & 0x00ff00ff;
+                (pixel & 0xff00ff00) | red_blue
+            }).collect()
+        }
+        ColorIndexingTransform => {
+            let bits_per_pixel = 8 >> transform.bits;
+            let mask = (1 << transform.bits) - 1;
+            let packed_width = subsample(width, transform.bits);
+            let mut indexed = Vec::with_capacity(width * height);
+            for y in range(0, height) {
+                for x in range(0, width) {
+                    let packed = (pixels[y * packed_width + (x >> transform.bits)] >> 8) & 0xff;
+                    let index = (packed >> ((x & mask) * bits_per_pixel)) &
+                        ((1 << bits_per_pixel) - 1);
+                    indexed.push(transform.data[index as uint]);
+                }
+            }
+            indexed
+        }
+    }
+}
+
+/// Predicts the pixel at `index` from the pixels to its left and above it with one of the 14
+/// predictor modes.
+fn predict(pixels: &[u32], index: uint, width: uint, mode: u32) -> u32 {
+    let left = pixels[index - 1];
+    let top = pixels[index - width];
+    let top_left = pixels[index - width - 1];
+    // The pixel above and to the right of the last pixel in a row is the first pixel of the row.
+    let top_right = pixels[index - width + 1];
+    match mode {
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average_2(average_2(left, top_right), top),
+        6 => average_2(left, top_left),
+        7 => average_2(left, top),
+        8 => average_2(top_left, top),
+        9 => average_2(top, top_right),
+        10 => average_2(average_2(left, top_left), average_2(top, top_right)),
+        11 => select(top, left, top_left),
+        12 => map_channels(left, top, top_left, |a, b, c| clamp_channel(a + b - c)),
+        13 => {
+            let average = average_2(left, top);
+            map_channels(average, top_left, 0, |a, b, _| clamp_channel(a + (a - b) / 2))
+        }
+        _ => 0xff000000,
+    }
+}
+
+fn average_2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefefefe) >> 1) + (a & b)
+}
+
+/// Returns whichever of `top` and `left` is closer to the gradient `top + left - top_left`.
+fn select(top: u32, left: u32, top_left: u32) -> u32 {
+    let mut difference = 0;
+    for shift in [24u, 16, 8, 0].iter() {
+        let channel = |pixel: u32| ((pixel >> *shift) & 0xff) as i32;
+        difference += (channel(left) - channel(top_left)).abs() -
+            (channel(top) - channel(top_left)).abs();
+    }
+    if difference <= 0 { top } else { left }
+}
+
+/// Combines each channel of three pixels with a function.
+fn map_channels(a: u32, b: u32, c: u32, function: |i32, i32, i32| -> u32) -> u32 {
+    let mut result = 0;
+    for shift in [24u, 16, 8, 0].iter() {
+        let channel = |pixel: u32| ((pixel >> *shift) & 0xff) as i32;
+        result |= function(channel(a), channel(b), channel(c)) << *shift;
+    }
+    result
+}
+
+fn clamp_channel(value: i32) -> u32 {
+    cmp::max(0, cmp::min(value, 255)) as u32
+}
+
+/// Adds two pixels channel by channel, modulo 256.
+fn add_pixels(a: u32, b: u32) -> u32 {
+    let alpha_green = (a & 0xff00ff00) + (b & 0xff00ff00);
+    let red_blue = (a & 0x00ff00ff) + (b & 0x00ff00ff);
+    (alpha_green & 0xff00ff00) | (red_blue & 0x00ff00ff)
+}
+
+fn color_transform_delta(multiplier: i8, color: i8) -> i32 {
+    (multiplier as i32 * color as i32) >> 5
+}
+
+/// Returns the number of tiles of `1 << bits` pixels that cover `size` pixels.
+fn subsample(size: uint, bits: uint) -> uint {
+    (size + (1 << bits) - 1) >> bits
+}
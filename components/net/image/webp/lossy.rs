@@ -0,0 +1,1642 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes lossy WebP images, which are key frames of VP8 video, per
+//! [RFC 6386](https://tools.ietf.org/html/rfc6386).
+//!
+//! The pixels match those of libwebp, which also smooths the chroma planes as it upsamples them.
+
+use image::base::{DecodeResult, MalformedImage, UnsupportedImageFeature};
+use image::webp::MAX_PIXELS;
+
+use std::cmp;
+
+/// The intra prediction modes. Whole macroblocks use only the first four.
+#[deriving(Clone, PartialEq)]
+enum PredictionMode {
+    DcPred,
+    TmPred,
+    VePred,
+    HePred,
+    RdPred,
+    VrPred,
+    LdPred,
+    VlPred,
+    HdPred,
+    HuPred,
+}
+
+static SUBBLOCK_MODES: [PredictionMode, ..10] = [DcPred, TmPred, VePred, HePred, RdPred, VrPred,
+                                                 LdPred, VlPred, HdPred, HuPred];
+
+/// The kinds of loop filter.
+#[deriving(PartialEq)]
+enum FilterType {
+    NoFilter,
+    SimpleFilter,
+    NormalFilter,
+}
+
+/// The order in which coefficients are read into a 4×4 block.
+static ZIGZAG: [uint, ..16] = [0, 1, 4, 8, 5, 2, 3, 6, 9, 12, 13, 10, 7, 11, 14, 15];
+
+/// The band of each coefficient position, which picks its probabilities. The last entry is for
+/// the position after the end of the block.
+static BANDS: [uint, ..17] = [0, 1, 2, 3, 6, 4, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7, 0];
+
+/// The probabilities of the extra bits of large coefficients, for categories 3 to 6.
+static CATEGORY_PROBABILITIES: [&'static [u8], ..4] = [
+    &[173, 148, 140],
+    &[176, 155, 140, 135],
+    &[180, 157, 141, 134, 130],
+    &[254, 254, 243, 230, 196, 177, 153, 140, 133, 130, 129],
+];
+
+/// Quantizer step sizes of DC coefficients, by quantizer index.
+static DC_TABLE: [i32, ..128] = [
+    4, 5, 6, 7, 8, 9, 10, 10, 11, 12, 13, 14, 15, 16, 17, 17,
+    18, 19, 20, 20, 21, 21, 22, 22, 23, 23, 24, 25, 25, 26, 27, 28,
+    29, 30, 31, 32, 33, 34, 35, 36, 37, 37, 38, 39, 40, 41, 42, 43,
+    44, 45, 46, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58,
+    59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74,
+    75, 76, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89,
+    91, 93, 95, 96, 98, 100, 101, 102, 104, 106, 108, 110, 112, 114, 116, 118,
+    122, 124, 126, 128, 130, 132, 134, 136, 138, 140, 143, 145, 148, 151, 154, 157,
+];
+
+/// Quantizer step sizes of AC coefficients, by quantizer index.
+static AC_TABLE: [i32, ..128] = [
+    4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
+    36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51,
+    52, 53, 54, 55, 56, 57, 58, 60, 62, 64, 66, 68, 70, 72, 74, 76,
+    78, 80, 82, 84, 86, 88, 90, 92, 94, 96, 98, 100, 102, 104, 106, 108,
+    110, 112, 114, 116, 119, 122, 125, 128, 131, 134, 137, 140, 143, 146, 149, 152,
+    155, 158, 161, 164, 167, 170, 173, 177, 181, 185, 189, 193, 197, 201, 205, 209,
+    213, 217, 221, 225, 229, 234, 239, 245, 249, 254, 259, 264, 269, 274, 279, 284,
+];
+
+/// The coefficient probabilities that a frame starts with, by plane type, band, context and
+/// token tree node (section 13.5).
+static COEFFS_PROBA_0: [[[[u8, ..11], ..3], ..8], ..4] =
+    [[[[128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+       [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+       [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]],
+      [[253, 136, 254, 255, 228, 219, 128, 128, 128, 128, 128],
+       [189, 129, 242, 255, 227, 213, 255, 219, 128, 128, 128],
+       [106, 126, 227, 252, 214, 209, 255, 255, 128, 128, 128]],
+      [[1, 98, 248, 255, 236, 226, 255, 255, 128, 128, 128],
+       [181, 133, 238, 254, 221, 234, 255, 154, 128, 128, 128],
+       [78, 134, 202, 247, 198, 180, 255, 219, 128, 128, 128]],
+      [[1, 185, 249, 255, 243, 255, 128, 128, 128, 128, 128],
+       [184, 150, 247, 255, 236, 224, 128, 128, 128, 128, 128],
+       [77, 110, 216, 255, 236, 230, 128, 128, 128, 128, 128]],
+      [[1, 101, 251, 255, 241, 255, 128, 128, 128, 128, 128],
+       [170, 139, 241, 252, 236, 209, 255, 255, 128, 128, 128],
+       [37, 116, 196, 243, 228, 255, 255, 255, 128, 128, 128]],
+      [[1, 204, 254, 255, 245, 255, 128, 128, 128, 128, 128],
+       [207, 160, 250, 255, 238, 128, 128, 128, 128, 128, 128],
+       [102, 103, 231, 255, 211, 171, 128, 128, 128, 128, 128]],
+      [[1, 152, 252, 255, 240, 255, 128, 128, 128, 128, 128],
+       [177, 135, 243, 255, 234, 225, 128, 128, 128, 128, 128],
+       [80, 129, 211, 255, 194, 224, 128, 128, 128, 128, 128]],
+      [[1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [246, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [255, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]]],
+     [[[198, 35, 237, 223, 193, 187, 162, 160, 145, 155, 62],
+       [131, 45, 198, 221, 172, 176, 220, 157, 252, 221, 1],
+       [68, 47, 146, 208, 149, 167, 221, 162, 255, 223, 128]],
+      [[1, 149, 241, 255, 221, 224, 255, 255, 128, 128, 128],
+       [184, 141, 234, 253, 222, 220, 255, 199, 128, 128, 128],
+       [81, 99, 181, 242, 176, 190, 249, 202, 255, 255, 128]],
+      [[1, 129, 232, 253, 214, 197, 242, 196, 255, 255, 128],
+       [99, 121, 210, 250, 201, 198, 255, 202, 128, 128, 128],
+       [23, 91, 163, 242, 170, 187, 247, 210, 255, 255, 128]],
+      [[1, 200, 246, 255, 234, 255, 128, 128, 128, 128, 128],
+       [109, 178, 241, 255, 231, 245, 255, 255, 128, 128, 128],
+       [44, 130, 201, 253, 205, 192, 255, 255, 128, 128, 128]],
+      [[1, 132, 239, 251, 219, 209, 255, 165, 128, 128, 128],
+       [94, 136, 225, 251, 218, 190, 255, 255, 128, 128, 128],
+       [22, 100, 174, 245, 186, 161, 255, 199, 128, 128, 128]],
+      [[1, 182, 249, 255, 232, 235, 128, 128, 128, 128, 128],
+       [124, 143, 241, 255, 227, 234, 128, 128, 128, 128, 128],
+       [35, 77, 181, 251, 193, 211, 255, 205, 128, 128, 128]],
+      [[1, 157, 247, 255, 236, 231, 255, 255, 128, 128, 128],
+       [121, 141, 235, 255, 225, 227, 255, 255, 128, 128, 128],
+       [45, 99, 188, 251, 195, 217, 255, 224, 128, 128, 128]],
+      [[1, 1, 251, 255, 213, 255, 128, 128, 128, 128, 128],
+       [203, 1, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+       [137, 1, 177, 255, 224, 255, 128, 128, 128, 128, 128]]],
+     [[[253, 9, 248, 251, 207, 208, 255, 192, 128, 128, 128],
+       [175, 13, 224, 243, 193, 185, 249, 198, 255, 255, 128],
+       [73, 17, 171, 221, 161, 179, 236, 167, 255, 234, 128]],
+      [[1, 95, 247, 253, 212, 183, 255, 255, 128, 128, 128],
+       [239, 90, 244, 250, 211, 209, 255, 255, 128, 128, 128],
+       [155, 77, 195, 248, 188, 195, 255, 255, 128, 128, 128]],
+      [[1, 24, 239, 251, 218, 219, 255, 205, 128, 128, 128],
+       [201, 51, 219, 255, 196, 186, 128, 128, 128, 128, 128],
+       [69, 46, 190, 239, 201, 218, 255, 228, 128, 128, 128]],
+      [[1, 191, 251, 255, 255, 128, 128, 128, 128, 128, 128],
+       [223, 165, 249, 255, 213, 255, 128, 128, 128, 128, 128],
+       [141, 124, 248, 255, 255, 128, 128, 128, 128, 128, 128]],
+      [[1, 16, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+       [190, 36, 230, 255, 236, 255, 128, 128, 128, 128, 128],
+       [149, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+      [[1, 226, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [247, 192, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [240, 128, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+      [[1, 134, 252, 255, 255, 128, 128, 128, 128, 128, 128],
+       [213, 62, 250, 255, 255, 128, 128, 128, 128, 128, 128],
+       [55, 93, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+      [[128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+       [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+       [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]]],
+     [[[202, 24, 213, 235, 186, 191, 220, 160, 240, 175, 255],
+       [126, 38, 182, 232, 169, 184, 228, 174, 255, 187, 128],
+       [61, 46, 138, 219, 151, 178, 240, 170, 255, 216, 128]],
+      [[1, 112, 230, 250, 199, 191, 247, 159, 255, 255, 128],
+       [166, 109, 228, 252, 211, 215, 255, 174, 128, 128, 128],
+       [39, 77, 162, 232, 172, 180, 245, 178, 255, 255, 128]],
+      [[1, 52, 220, 246, 198, 199, 249, 220, 255, 255, 128],
+       [124, 74, 191, 243, 183, 193, 250, 221, 255, 255, 128],
+       [24, 71, 130, 219, 154, 170, 243, 182, 255, 255, 128]],
+      [[1, 182, 225, 249, 219, 240, 255, 224, 128, 128, 128],
+       [149, 150, 226, 252, 216, 205, 255, 171, 128, 128, 128],
+       [28, 108, 170, 242, 183, 194, 254, 223, 255, 255, 128]],
+      [[1, 81, 230, 252, 204, 203, 255, 192, 128, 128, 128],
+       [123, 102, 209, 247, 188, 196, 255, 233, 128, 128, 128],
+       [20, 95, 153, 243, 164, 173, 255, 203, 128, 128, 128]],
+      [[1, 222, 248, 255, 216, 213, 128, 128, 128, 128, 128],
+       [168, 175, 246, 252, 235, 205, 255, 255, 128, 128, 128],
+       [47, 116, 215, 255, 211, 212, 255, 255, 128, 128, 128]],
+      [[1, 121, 236, 253, 212, 214, 255, 255, 128, 128, 128],
+       [141, 84, 213, 252, 201, 202, 255, 219, 128, 128, 128],
+       [42, 80, 160, 240, 162, 185, 255, 205, 128, 128, 128]],
+      [[1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [244, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+       [238, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]]]];
+
+/// The probabilities that the frame header updates each coefficient probability (section 13.4).
+static COEFFS_UPDATE_PROBA: [[[[u8, ..11], ..3], ..8], ..4] =
+    [[[[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[176, 246, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [223, 241, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+       [249, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 244, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+       [234, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 246, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [239, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [251, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [251, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 253, 255, 254, 255, 255, 255, 255, 255, 255],
+       [250, 255, 254, 255, 254, 255, 255, 255, 255, 255, 255],
+       [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]]],
+     [[[217, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [225, 252, 241, 253, 255, 255, 254, 255, 255, 255, 255],
+       [234, 250, 241, 250, 253, 255, 253, 254, 255, 255, 255]],
+      [[255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [223, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [238, 253, 254, 254, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [249, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 253, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [247, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [252, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]]],
+     [[[186, 251, 250, 255, 255, 255, 255, 255, 255, 255, 255],
+       [234, 251, 244, 254, 255, 255, 255, 255, 255, 255, 255],
+       [251, 251, 243, 253, 254, 255, 254, 255, 255, 255, 255]],
+      [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [236, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [251, 253, 253, 254, 254, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]]],
+     [[[248, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [250, 254, 252, 254, 255, 255, 255, 255, 255, 255, 255],
+       [248, 254, 249, 253, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [246, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [252, 254, 251, 254, 254, 255, 255, 255, 255, 255, 255]],
+      [[255, 254, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+       [248, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [253, 255, 254, 254, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [245, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [253, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 251, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [252, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 252, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [249, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+       [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+      [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+       [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]]]];
+
+/// The probabilities of the prediction modes of 4×4 subblocks, by the modes of the subblocks
+/// above and to the left (section 11.5).
+static B_MODES_PROBA: [[[u8, ..9], ..10], ..10] =
+    [[[231, 120, 48, 89, 115, 113, 120, 152, 112],
+      [152, 179, 64, 126, 170, 118, 46, 70, 95],
+      [175, 69, 143, 80, 85, 82, 72, 155, 103],
+      [56, 58, 10, 171, 218, 189, 17, 13, 152],
+      [114, 26, 17, 163, 44, 195, 21, 10, 173],
+      [121, 24, 80, 195, 26, 62, 44, 64, 85],
+      [144, 71, 10, 38, 171, 213, 144, 34, 26],
+      [170, 46, 55, 19, 136, 160, 33, 206, 71],
+      [63, 20, 8, 114, 114, 208, 12, 9, 226],
+      [81, 40, 11, 96, 182, 84, 29, 16, 36]],
+     [[134, 183, 89, 137, 98, 101, 106, 165, 148],
+      [72, 187, 100, 130, 157, 111, 32, 75, 80],
+      [66, 102, 167, 99, 74, 62, 40, 234, 128],
+      [41, 53, 9, 178, 241, 141, 26, 8, 107],
+      [74, 43, 26, 146, 73, 166, 49, 23, 157],
+      [65, 38, 105, 160, 51, 52, 31, 115, 128],
+      [104, 79, 12, 27, 217, 255, 87, 17, 7],
+      [87, 68, 71, 44, 114, 51, 15, 186, 23],
+      [47, 41, 14, 110, 182, 183, 21, 17, 194],
+      [66, 45, 25, 102, 197, 189, 23, 18, 22]],
+     [[88, 88, 147, 150, 42, 46, 45, 196, 205],
+      [43, 97, 183, 117, 85, 38, 35, 179, 61],
+      [39, 53, 200, 87, 26, 21, 43, 232, 171],
+      [56, 34, 51, 104, 114, 102, 29, 93, 77],
+      [39, 28, 85, 171, 58, 165, 90, 98, 64],
+      [34, 22, 116, 206, 23, 34, 43, 166, 73],
+      [107, 54, 32, 26, 51, 1, 81, 43, 31],
+      [68, 25, 106, 22, 64, 171, 36, 225, 114],
+      [34, 19, 21, 102, 132, 188, 16, 76, 124],
+      [62, 18, 78, 95, 85, 57, 50, 48, 51]],
+     [[193, 101, 35, 159, 215, 111, 89, 46, 111],
+      [60, 148, 31, 172, 219, 228, 21, 18, 111],
+      [112, 113, 77, 85, 179, 255, 38, 120, 114],
+      [40, 42, 1, 196, 245, 209, 10, 25, 109],
+      [88, 43, 29, 140, 166, 213, 37, 43, 154],
+      [61, 63, 30, 155, 67, 45, 68, 1, 209],
+      [100, 80, 8, 43, 154, 1, 51, 26, 71],
+      [142, 78, 78, 16, 255, 128, 34, 197, 171],
+      [41, 40, 5, 102, 211, 183, 4, 1, 221],
+      [51, 50, 17, 168, 209, 192, 23, 25, 82]],
+     [[138, 31, 36, 171, 27, 166, 38, 44, 229],
+      [67, 87, 58, 169, 82, 115, 26, 59, 179],
+      [63, 59, 90, 180, 59, 166, 93, 73, 154],
+      [40, 40, 21, 116, 143, 209, 34, 39, 175],
+      [47, 15, 16, 183, 34, 223, 49, 45, 183],
+      [46, 17, 33, 183, 6, 98, 15, 32, 183],
+      [57, 46, 22, 24, 128, 1, 54, 17, 37],
+      [65, 32, 73, 115, 28, 128, 23, 128, 205],
+      [40, 3, 9, 115, 51, 192, 18, 6, 223],
+      [87, 37, 9, 115, 59, 77, 64, 21, 47]],
+     [[104, 55, 44, 218, 9, 54, 53, 130, 226],
+      [64, 90, 70, 205, 40, 41, 23, 26, 57],
+      [54, 57, 112, 184, 5, 41, 38, 166, 213],
+      [30, 34, 26, 133, 152, 116, 10, 32, 134],
+      [39, 19, 53, 221, 26, 114, 32, 73, 255],
+      [31, 9, 65, 234, 2, 15, 1, 118, 73],
+      [75, 32, 12, 51, 192, 255, 160, 43, 51],
+      [88, 31, 35, 67, 102, 85, 55, 186, 85],
+      [56, 21, 23, 111, 59, 205, 45, 37, 192],
+      [55, 38, 70, 124, 73, 102, 1, 34, 98]],
+     [[125, 98, 42, 88, 104, 85, 117, 175, 82],
+      [95, 84, 53, 89, 128, 100, 113, 101, 45],
+      [75, 79, 123, 47, 51, 128, 81, 171, 1],
+      [57, 17, 5, 71, 102, 57, 53, 41, 49],
+      [38, 33, 13, 121, 57, 73, 26, 1, 85],
+      [41, 10, 67, 138, 77, 110, 90, 47, 114],
+      [115, 21, 2, 10, 102, 255, 166, 23, 6],
+      [101, 29, 16, 10, 85, 128, 101, 196, 26],
+      [57, 18, 10, 102, 102, 213, 34, 20, 43],
+      [117, 20, 15, 36, 163, 128, 68, 1, 26]],
+     [[102, 61, 71, 37, 34, 53, 31, 243, 192],
+      [69, 60, 71, 38, 73, 119, 28, 222, 37],
+      [68, 45, 128, 34, 1, 47, 11, 245, 171],
+      [62, 17, 19, 70, 146, 85, 55, 62, 70],
+      [37, 43, 37, 154, 100, 163, 85, 160, 1],
+      [63, 9, 92, 136, 28, 64, 32, 201, 85],
+      [75, 15, 9, 9, 64, 255, 184, 119, 16],
+      [86, 6, 28, 5, 64, 255, 25, 248, 1],
+      [56, 8, 17, 132, 137, 255, 55, 116, 128],
+      [58, 15, 20, 82, 135, 57, 26, 121, 40]],
+     [[164, 50, 31, 137, 154, 133, 25, 35, 218],
+      [51, 103, 44, 131, 131, 123, 31, 6, 158],
+      [86, 40, 64, 135, 148, 224, 45, 183, 128],
+      [22, 26, 17, 131, 240, 154, 14, 1, 209],
+      [45, 16, 21, 91, 64, 222, 7, 1, 197],
+      [56, 21, 39, 155, 60, 138, 23, 102, 213],
+      [83, 12, 13, 54, 192, 255, 68, 47, 28],
+      [85, 26, 85, 85, 128, 128, 32, 146, 171],
+      [18, 11, 7, 63, 144, 171, 4, 4, 246],
+      [35, 27, 10, 146, 174, 171, 12, 26, 128]],
+     [[190, 80, 35, 99, 180, 80, 126, 54, 45],
+      [85, 126, 47, 87, 176, 51, 41, 20, 32],
+      [101, 75, 128, 139, 118, 146, 116, 128, 85],
+      [56, 41, 15, 176, 236, 85, 37, 9, 62],
+      [71, 30, 17, 119, 118, 255, 17, 18, 138],
+      [101, 38, 60, 138, 55, 70, 43, 26, 142],
+      [146, 36, 19, 30, 171, 255, 97, 27, 20],
+      [138, 45, 61, 62, 219, 1, 81, 188, 64],
+      [32, 41, 20, 117, 151, 142, 20, 21, 163],
+      [112, 19, 12, 61, 195, 128, 48, 4, 24]]];
+
+/// The boolean entropy decoder of section 7.
+struct BoolDecoder<'a> {
+    data: &'a [u8],
+    position: uint,
+    value: u32,
+    range: u32,
+    bit_count: uint,
+}
+
+impl<'a> BoolDecoder<'a> {
+    fn new(data: &'a [u8]) -> BoolDecoder<'a> {
+        let mut decoder = BoolDecoder {
+            data: data,
+            position: 0,
+            value: 0,
+            range: 255,
+            bit_count: 0,
+        };
+        decoder.value = (decoder.next_byte() << 8) | decoder.next_byte();
+        decoder
+    }
+
+    /// Returns the next byte of data. Data past the end reads as zeroes, as in libwebp.
+    fn next_byte(&mut self) -> u32 {
+        let byte = if self.position < self.data.len() {
+            self.data[self.position] as u32
+        } else {
+            0
+        };
+        self.position += 1;
+        byte
+    }
+
+    /// Reads a bit that is false with the given probability out of 256.
+    fn read_bool(&mut self, probability: u8) -> bool {
+        let split = 1 + (((self.range - 1) * probability as u32) >> 8);
+        let big_split = split << 8;
+        let bit = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        } else {
+            self.range = split;
+            false
+        };
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte();
+            }
+        }
+        bit
+    }
+
+    fn read_bit(&mut self, probability: u8) -> uint {
+        if self.read_bool(probability) { 1 } else { 0 }
+    }
+
+    fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    /// Reads an unsigned value of the given number of bits, most significant first.
+    fn read_literal(&mut self, bits: uint) -> i32 {
+        let mut value = 0;
+        for _ in range(0, bits) {
+            value = (value << 1) | self.read_bit(128) as i32;
+        }
+        value
+    }
+
+    /// Reads a value of the given number of bits followed by its sign.
+    fn read_signed_literal(&mut self, bits: uint) -> i32 {
+        let value = self.read_literal(bits);
+        if self.read_flag() { -value } else { value }
+    }
+
+    /// Reads a value of the given number of bits if a flag before it is set.
+    fn read_optional_signed_literal(&mut self, bits: uint) -> i32 {
+        if self.read_flag() { self.read_signed_literal(bits) } else { 0 }
+    }
+
+    /// Returns true if more than the data and the two bytes of padding that a bit stream may end
+    /// with have been read.
+    fn is_exhausted(&self) -> bool {
+        self.position > self.data.len() + 2
+    }
+}
+
+/// The segment header of section 9.3.
+struct SegmentHeader {
+    enabled: bool,
+    update_map: bool,
+    absolute_values: bool,
+    quantizer: [i32, ..4],
+    filter_level: [i32, ..4],
+    tree_probabilities: [u8, ..3],
+}
+
+/// The filter header of section 9.6.
+struct FilterHeader {
+    filter_type: FilterType,
+    level: i32,
+    sharpness: i32,
+    use_deltas: bool,
+    reference_delta: i32,
+    mode_delta: i32,
+}
+
+/// The loop filter parameters of a macroblock (section 15.2).
+#[deriving(Clone)]
+struct FilterParameters {
+    limit: i32,
+    interior_limit: i32,
+    hev_threshold: i32,
+    filter_inner_edges: bool,
+}
+
+/// The dequantization factors of a segment, as DC and AC step sizes.
+struct QuantMatrix {
+    y1: [i32, ..2],
+    y2: [i32, ..2],
+    uv: [i32, ..2],
+}
+
+/// Whether the blocks along an edge of a macroblock had nonzero coefficients, which is the
+/// context for the coefficients of the blocks across the edge.
+#[deriving(Clone)]
+struct NonZeroContext {
+    y: [bool, ..4],
+    u: [bool, ..2],
+    v: [bool, ..2],
+    y2: bool,
+}
+
+impl NonZeroContext {
+    fn new() -> NonZeroContext {
+        NonZeroContext {
+            y: [false, ..4],
+            u: [false, ..2],
+            v: [false, ..2],
+            y2: false,
+        }
+    }
+}
+
+/// The header and coefficients of a macroblock.
+struct Macroblock {
+    segment: uint,
+    skip_coefficients: bool,
+    /// The modes of the 4×4 subblocks, or `None` if the luma is predicted as a whole.
+    subblock_modes: Option<[PredictionMode, ..16]>,
+    luma_mode: PredictionMode,
+    chroma_mode: PredictionMode,
+    /// Dequantized coefficients of the 16 luma blocks, then the 4 blue and 4 red blocks.
+    coefficients: [i32, ..384],
+    has_coefficients: bool,
+}
+
+/// A decoded frame, in planes that cover whole macroblocks.
+pub struct Frame {
+    pub width: uint,
+    pub height: uint,
+    luma_stride: uint,
+    chroma_stride: uint,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+/// Returns the size of the frame in a VP8 bit stream.
+pub fn read_size(data: &[u8]) -> DecodeResult<(uint, uint)> {
+    if data.len() < 10 {
+        return Err(MalformedImage("the VP8 frame header is truncated".to_string()))
+    }
+    if data[0] & 1 != 0 {
+        return Err(UnsupportedImageFeature("VP8 interframes".to_string()))
+    }
+    if data.slice(3, 6) != b"\x9d\x01\x2a" {
+        return Err(MalformedImage("the VP8 start code is missing".to_string()))
+    }
+    let width = (data[6] as uint | (data[7] as uint << 8)) & 0x3fff;
+    let height = (data[8] as uint | (data[9] as uint << 8)) & 0x3fff;
+    if width == 0 || height == 0 {
+        return Err(MalformedImage("the VP8 frame is empty".to_string()))
+    }
+    Ok((width, height))
+}
+
+/// Decodes the key frame in a VP8 bit stream.
+pub fn decode(data: &[u8]) -> DecodeResult<Frame> {
+    let (width, height) = try!(read_size(data));
+    if width * height > MAX_PIXELS {
+        return Err(MalformedImage("the VP8 frame is too large".to_string()))
+    }
+    let tag = data[0] as uint | (data[1] as uint << 8) | (data[2] as uint << 16);
+    if (tag >> 1) & 7 > 3 {
+        return Err(MalformedImage("the VP8 profile is invalid".to_string()))
+    }
+    if (tag >> 4) & 1 == 0 {
+        return Err(UnsupportedImageFeature("hidden VP8 frames".to_string()))
+    }
+    let first_partition_size = tag >> 5;
+    let data = data.slice_from(10);
+    if first_partition_size > data.len() {
+        return Err(MalformedImage("the first VP8 partition is truncated".to_string()))
+    }
+
+    let mut header = BoolDecoder::new(data.slice_to(first_partition_size));
+    // The color space and clamping type, which are always the same in practice.
+    header.read_flag();
+    header.read_flag();
+    let segments = read_segment_header(&mut header);
+    let filter = read_filter_header(&mut header);
+
+    let partition_count = 1u << header.read_literal(2) as uint;
+    let partitions = try!(split_partitions(data.slice_from(first_partition_size),
+                                           partition_count));
+    let mut partitions: Vec<BoolDecoder> =
+        partitions.into_iter().map(|partition| BoolDecoder::new(partition)).collect();
+
+    let quant = read_quant_matrices(&mut header, &segments);
+    // Whether to refresh the probabilities for later frames, which do not matter here.
+    header.read_flag();
+    let coefficient_probabilities = read_coefficient_probabilities(&mut header);
+    let skip_probability = if header.read_flag() {
+        Some(header.read_literal(8) as u8)
+    } else {
+        None
+    };
+    if header.is_exhausted() {
+        return Err(MalformedImage("the VP8 frame header is truncated".to_string()))
+    }
+
+    let macroblock_width = (width + 15) / 16;
+    let macroblock_height = (height + 15) / 16;
+    let mut frame = Frame {
+        width: width,
+        height: height,
+        luma_stride: macroblock_width * 16,
+        chroma_stride: macroblock_width * 8,
+        y: Vec::from_elem(macroblock_width * macroblock_height * 256, 0u8),
+        u: Vec::from_elem(macroblock_width * macroblock_height * 64, 0u8),
+        v: Vec::from_elem(macroblock_width * macroblock_height * 64, 0u8),
+    };
+    let filter_strengths = compute_filter_strengths(&filter, &segments);
+    let mut filter_parameters = Vec::with_capacity(macroblock_width * macroblock_height);
+
+    let mut top_modes = Vec::from_elem(macroblock_width * 4, DcPred);
+    let mut top_contexts = Vec::from_elem(macroblock_width, NonZeroContext::new());
+    for macroblock_y in range(0, macroblock_height) {
+        let mut left_modes = [DcPred, ..4];
+        let mut left_context = NonZeroContext::new();
+        let partition = partitions.get_mut(macroblock_y % partition_count);
+        for macroblock_x in range(0, macroblock_width) {
+            let mut macroblock = read_macroblock_header(&mut header,
+                                                        &segments,
+                                                        skip_probability,
+                                                        top_modes.slice_mut(macroblock_x * 4,
+                                                                            macroblock_x * 4 + 4),
+                                                        &mut left_modes);
+            let segment = macroblock.segment;
+            read_residuals(partition,
+                           &mut macroblock,
+                           &coefficient_probabilities,
+                           &quant[segment],
+                           top_contexts.get_mut(macroblock_x),
+                           &mut left_context);
+            if partition.is_exhausted() {
+                return Err(MalformedImage("a VP8 partition is truncated".to_string()))
+            }
+
+            frame.reconstruct_macroblock(&macroblock, macroblock_x, macroblock_y);
+
+            let mut parameters =
+                filter_strengths[macroblock.segment][macroblock.subblock_modes.is_some() as uint]
+                .clone();
+            parameters.filter_inner_edges =
+                parameters.filter_inner_edges || macroblock.has_coefficients;
+            filter_parameters.push(parameters);
+        }
+    }
+    if header.is_exhausted() {
+        return Err(MalformedImage("the VP8 frame header is truncated".to_string()))
+    }
+
+    // Intra prediction uses the pixels from before the loop filter, so the frame is filtered
+    // once it is all decoded.
+    if filter.filter_type != NoFilter {
+        for macroblock_y in range(0, macroblock_height) {
+            for macroblock_x in range(0, macroblock_width) {
+                let parameters = &filter_parameters[macroblock_y * macroblock_width +
+                                                    macroblock_x];
+                frame.filter_macroblock(filter.filter_type, parameters, macroblock_x,
+                                        macroblock_y);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+fn read_segment_header(header: &mut BoolDecoder) -> SegmentHeader {
+    let mut segments = SegmentHeader {
+        enabled: header.read_flag(),
+        update_map: false,
+        absolute_values: true,
+        quantizer: [0, ..4],
+        filter_level: [0, ..4],
+        tree_probabilities: [255, ..3],
+    };
+    if !segments.enabled {
+        return segments
+    }
+    segments.update_map = header.read_flag();
+    if header.read_flag() {
+        segments.absolute_values = header.read_flag();
+        for quantizer in segments.quantizer.iter_mut() {
+            *quantizer = header.read_optional_signed_literal(7);
+        }
+        for filter_level in segments.filter_level.iter_mut() {
+            *filter_level = header.read_optional_signed_literal(6);
+        }
+    }
+    if segments.update_map {
+        for probability in segments.tree_probabilities.iter_mut() {
+            *probability = if header.read_flag() { header.read_literal(8) as u8 } else { 255 };
+        }
+    }
+    segments
+}
+
+fn read_filter_header(header: &mut BoolDecoder) -> FilterHeader {
+    let simple = header.read_flag();
+    let mut filter = FilterHeader {
+        filter_type: NoFilter,
+        level: header.read_literal(6),
+        sharpness: header.read_literal(3),
+        use_deltas: header.read_flag(),
+        reference_delta: 0,
+        mode_delta: 0,
+    };
+    if filter.use_deltas && header.read_flag() {
+        // Only the deltas for intra frames and for subblock prediction matter in key frames.
+        for index in range(0u, 4) {
+            if header.read_flag() {
+                let delta = header.read_signed_literal(6);
+                if index == 0 {
+                    filter.reference_delta = delta
+                }
+            }
+        }
+        for index in range(0u, 4) {
+            if header.read_flag() {
+                let delta = header.read_signed_literal(6);
+                if index == 0 {
+                    filter.mode_delta = delta
+                }
+            }
+        }
+    }
+    filter.filter_type = if filter.level == 0 {
+        NoFilter
+    } else if simple {
+        SimpleFilter
+    } else {
+        NormalFilter
+    };
+    filter
+}
+
+/// Splits the data after the first partition into the partitions that hold the coefficients.
+fn split_partitions<'a>(data: &'a [u8], count: uint) -> DecodeResult<Vec<&'a [u8]>> {
+    let sizes_length = (count - 1) * 3;
+    if data.len() < sizes_length {
+        return Err(MalformedImage("the VP8 partition sizes are truncated".to_string()))
+    }
+    let mut partitions = vec!();
+    let mut start = sizes_length;
+    for index in range(0, count - 1) {
+        let sizes = data.slice(index * 3, index * 3 + 3);
+        let size = sizes[0] as uint | (sizes[1] as uint << 8) | (sizes[2] as uint << 16);
+        let end = cmp::min(start + size, data.len());
+        partitions.push(data.slice(start, end));
+        start = end;
+    }
+    partitions.push(data.slice_from(start));
+    Ok(partitions)
+}
+
+fn read_quant_matrices(header: &mut BoolDecoder, segments: &SegmentHeader) -> [QuantMatrix, ..4] {
+    let base_index = header.read_literal(7);
+    let y1_dc_delta = header.read_optional_signed_literal(4);
+    let y2_dc_delta = header.read_optional_signed_literal(4);
+    let y2_ac_delta = header.read_optional_signed_literal(4);
+    let uv_dc_delta = header.read_optional_signed_literal(4);
+    let uv_ac_delta = header.read_optional_signed_literal(4);
+
+    let matrix = |segment: uint| {
+        let index = if !segments.enabled {
+            base_index
+        } else if segments.absolute_values {
+            segments.quantizer[segment]
+        } else {
+            segments.quantizer[segment] + base_index
+        };
+        let look_up = |table: &[i32, ..128], delta: i32, maximum: i32| {
+            table[clamp(index + delta, 0, maximum) as uint]
+        };
+        QuantMatrix {
+            y1: [look_up(&DC_TABLE, y1_dc_delta, 127), look_up(&AC_TABLE, 0, 127)],
+            y2: [look_up(&DC_TABLE, y2_dc_delta, 127) * 2,
+                 cmp::max(look_up(&AC_TABLE, y2_ac_delta, 127) * 155 / 100, 8)],
+            uv: [look_up(&DC_TABLE, uv_dc_delta, 117), look_up(&AC_TABLE, uv_ac_delta, 127)],
+        }
+    };
+    [matrix(0), matrix(1), matrix(2), matrix(3)]
+}
+
+fn read_coefficient_probabilities(header: &mut BoolDecoder) -> [[[[u8, ..11], ..3], ..8], ..4] {
+    let mut probabilities = COEFFS_PROBA_0;
+    for plane_type in range(0u, 4) {
+        for band in range(0u, 8) {
+            for context in range(0u, 3) {
+                for node in range(0u, 11) {
+                    if header.read_bool(COEFFS_UPDATE_PROBA[plane_type][band][context][node]) {
+                        probabilities[plane_type][band][context][node] =
+                            header.read_literal(8) as u8;
+                    }
+                }
+            }
+        }
+    }
+    probabilities
+}
+
+/// Computes the loop filter parameters of each segment, for macroblocks that are predicted as a
+/// whole and for those predicted by subblocks (section 15.1).
+fn compute_filter_strengths(filter: &FilterHeader, segments: &SegmentHeader)
+                            -> [[FilterParameters, ..2], ..4] {
+    let parameters = |segment: uint, subblocks: bool| {
+        let mut level = if !segments.enabled {
+            filter.level
+        } else if segments.absolute_values {
+            segments.filter_level[segment]
+        } else {
+            segments.filter_level[segment] + filter.level
+        };
+        if filter.use_deltas {
+            level += filter.reference_delta;
+            if subblocks {
+                level += filter.mode_delta;
+            }
+        }
+        let level = clamp(level, 0, 63);
+        if level == 0 {
+            return FilterParameters {
+                limit: 0,
+                interior_limit: 0,
+                hev_threshold: 0,
+                filter_inner_edges: subblocks,
+            }
+        }
+
+        let mut interior_limit = level;
+        if filter.sharpness > 0 {
+            interior_limit >>= if filter.sharpness > 4 { 2 } else { 1 };
+            interior_limit = cmp::min(interior_limit, 9 - filter.sharpness);
+        }
+        let interior_limit = cmp::max(interior_limit, 1);
+        FilterParameters {
+            limit: 2 * level + interior_limit,
+            interior_limit: interior_limit,
+            hev_threshold: if level >= 40 { 2 } else if level >= 15 { 1 } else { 0 },
+            filter_inner_edges: subblocks,
+        }
+    };
+    [[parameters(0, false), parameters(0, true)],
+     [parameters(1, false), parameters(1, true)],
+     [parameters(2, false), parameters(2, true)],
+     [parameters(3, false), parameters(3, true)]]
+}
+
+/// Reads the segment, prediction modes and skip flag of a macroblock from the first partition
+/// (section 19.3). `top_modes` and `left_modes` are the modes of the subblocks along the edges
+/// of the macroblock, and are updated for the macroblocks after it.
+fn read_macroblock_header(header: &mut BoolDecoder,
+                          segments: &SegmentHeader,
+                          skip_probability: Option<u8>,
+                          top_modes: &mut [PredictionMode],
+                          left_modes: &mut [PredictionMode, ..4])
+                          -> Macroblock {
+    let probabilities = &segments.tree_probabilities;
+    let segment = if !segments.update_map {
+        0
+    } else if !header.read_bool(probabilities[0]) {
+        header.read_bit(probabilities[1])
+    } else {
+        header.read_bit(probabilities[2]) + 2
+    };
+    let skip_coefficients = match skip_probability {
+        Some(probability) => header.read_bool(probability),
+        None => false,
+    };
+
+    let mut luma_mode = DcPred;
+    let mut subblock_modes = None;
+    if header.read_bool(145) {
+        luma_mode = if header.read_bool(156) {
+            if header.read_bool(128) { TmPred } else { HePred }
+        } else {
+            if header.read_bool(163) { VePred } else { DcPred }
+        };
+        for mode in top_modes.iter_mut().chain(left_modes.iter_mut()) {
+            *mode = luma_mode;
+        }
+    } else {
+        let mut modes = [DcPred, ..16];
+        for y in range(0u, 4) {
+            for x in range(0u, 4) {
+                let probabilities = &B_MODES_PROBA[top_modes[x] as uint][left_modes[y] as uint];
+                let mode = read_subblock_mode(header, probabilities);
+                modes[y * 4 + x] = mode;
+                top_modes[x] = mode;
+                left_modes[y] = mode;
+            }
+        }
+        subblock_modes = Some(modes);
+    }
+
+    let chroma_mode = if !header.read_bool(142) {
+        DcPred
+    } else if !header.read_bool(114) {
+        VePred
+    } else if header.read_bool(183) {
+        TmPred
+    } else {
+        HePred
+    };
+
+    Macroblock {
+        segment: segment,
+        skip_coefficients: skip_coefficients,
+        subblock_modes: subblock_modes,
+        luma_mode: luma_mode,
+        chroma_mode: chroma_mode,
+        coefficients: [0, ..384],
+        has_coefficients: false,
+    }
+}
+
+/// Reads the prediction mode of a subblock with the tree of section 11.2.
+fn read_subblock_mode(header: &mut BoolDecoder, probabilities: &[u8, ..9]) -> PredictionMode {
+    if !header.read_bool(probabilities[0]) {
+        return DcPred
+    }
+    if !header.read_bool(probabilities[1]) {
+        return TmPred
+    }
+    if !header.read_bool(probabilities[2]) {
+        return VePred
+    }
+    let index = if !header.read_bool(probabilities[3]) {
+        if !header.read_bool(probabilities[4]) {
+            3
+        } else if !header.read_bool(probabilities[5]) {
+            4
+        } else {
+            5
+        }
+    } else if !header.read_bool(probabilities[6]) {
+        6
+    } else if !header.read_bool(probabilities[7]) {
+        7
+    } else if !header.read_bool(probabilities[8]) {
+        8
+    } else {
+        9
+    };
+    SUBBLOCK_MODES[index]
+}
+
+/// Reads the coefficients of a macroblock from its partition (section 13), unless the
+/// macroblock header says there are none.
+fn read_residuals(partition: &mut BoolDecoder,
+                  macroblock: &mut Macroblock,
+                  probabilities: &[[[[u8, ..11], ..3], ..8], ..4],
+                  quant: &QuantMatrix,
+                  top: &mut NonZeroContext,
+                  left: &mut NonZeroContext) {
+    let has_y2 = macroblock.subblock_modes.is_none();
+    if macroblock.skip_coefficients {
+        // Macroblocks with subblocks have no second-order coefficients, so they leave the
+        // context of those alone.
+        let (top_y2, left_y2) = (top.y2 && !has_y2, left.y2 && !has_y2);
+        *top = NonZeroContext { y2: top_y2, ..NonZeroContext::new() };
+        *left = NonZeroContext { y2: left_y2, ..NonZeroContext::new() };
+        return
+    }
+
+    let mut has_coefficients = false;
+    let coefficients = &mut macroblock.coefficients;
+    let first_coefficient = if has_y2 {
+        let mut y2 = [0i32, ..16];
+        let context = top.y2 as uint + left.y2 as uint;
+        let end = read_coefficients(partition, &probabilities[1], context, &quant.y2, 0, &mut y2);
+        top.y2 = end > 0;
+        left.y2 = end > 0;
+        inverse_walsh_hadamard_transform(&y2, coefficients);
+        1
+    } else {
+        0
+    };
+    let luma_probabilities = if has_y2 { &probabilities[0] } else { &probabilities[3] };
+
+    for y in range(0u, 4) {
+        for x in range(0u, 4) {
+            let block = coefficients.slice_mut((y * 4 + x) * 16, (y * 4 + x) * 16 + 16);
+            let context = top.y[x] as uint + left.y[y] as uint;
+            let end = read_coefficients(partition, luma_probabilities, context, &quant.y1,
+                                        first_coefficient, block);
+            top.y[x] = end > first_coefficient;
+            left.y[y] = end > first_coefficient;
+            has_coefficients = has_coefficients || end > 1 || block[0] != 0;
+        }
+    }
+
+    for plane in range(0u, 2) {
+        let (top_context, left_context) = if plane == 0 {
+            (&mut top.u, &mut left.u)
+        } else {
+            (&mut top.v, &mut left.v)
+        };
+        for y in range(0u, 2) {
+            for x in range(0u, 2) {
+                let start = 256 + plane * 64 + (y * 2 + x) * 16;
+                let block = coefficients.slice_mut(start, start + 16);
+                let context = top_context[x] as uint + left_context[y] as uint;
+                let end = read_coefficients(partition, &probabilities[2], context, &quant.uv, 0,
+                                            block);
+                top_context[x] = end > 0;
+                left_context[y] = end > 0;
+                has_coefficients = has_coefficients || end > 1 || block[0] != 0;
+            }
+        }
+    }
+    macroblock.has_coefficients = has_coefficients;
+}
+
+/// Reads the tokens of a 4×4 block, starting at coefficient `first`, and stores them dequantized
+/// in zigzag order. Returns the position after the last token that was read.
+fn read_coefficients(partition: &mut BoolDecoder,
+                     probabilities: &[[[u8, ..11], ..3], ..8],
+                     context: uint,
+                     quant: &[i32, ..2],
+                     first: uint,
+                     block: &mut [i32])
+                     -> uint {
+    let mut position = first;
+    let mut node_probabilities = &probabilities[BANDS[position]][context];
+    while position < 16 {
+        if !partition.read_bool(node_probabilities[0]) {
+            // The end of the block.
+            return position
+        }
+        while !partition.read_bool(node_probabilities[1]) {
+            // A zero.
+            position += 1;
+            if position == 16 {
+                return 16
+            }
+            node_probabilities = &probabilities[BANDS[position]][0];
+        }
+
+        let (value, next_context) = if !partition.read_bool(node_probabilities[2]) {
+            (1, 1)
+        } else {
+            (read_large_value(partition, node_probabilities), 2)
+        };
+        let value = if partition.read_flag() { -value } else { value };
+        block[ZIGZAG[position]] = value * quant[if position > 0 { 1 } else { 0 }];
+        position += 1;
+        node_probabilities = &probabilities[BANDS[position]][next_context];
+    }
+    16
+}
+
+/// Reads the magnitude of a coefficient that is at least 2 (section 13.2).
+fn read_large_value(partition: &mut BoolDecoder, probabilities: &[u8, ..11]) -> i32 {
+    if !partition.read_bool(probabilities[3]) {
+        if !partition.read_bool(probabilities[4]) {
+            2
+        } else {
+            3 + partition.read_bit(probabilities[5]) as i32
+        }
+    } else if !partition.read_bool(probabilities[6]) {
+        if !partition.read_bool(probabilities[7]) {
+            5 + partition.read_bit(159) as i32
+        } else {
+            let high = partition.read_bit(165) as i32;
+            7 + 2 * high + partition.read_bit(145) as i32
+        }
+    } else {
+        let high = partition.read_bit(probabilities[8]);
+        let low = partition.read_bit(probabilities[9 + high]);
+        let category = 2 * high + low;
+        let mut extra = 0;
+        for &probability in CATEGORY_PROBABILITIES[category].iter() {
+            extra = extra * 2 + partition.read_bit(probability) as i32;
+        }
+        extra + 3 + (8 << category)
+    }
+}
+
+/// Turns the second-order luma coefficients into the DC coefficients of the 16 luma blocks
+/// (section 14.3).
+fn inverse_walsh_hadamard_transform(input: &[i32, ..16], coefficients: &mut [i32, ..384]) {
+    let mut temporary = [0i32, ..16];
+    for i in range(0u, 4) {
+        let a0 = input[i] + input[12 + i];
+        let a1 = input[4 + i] + input[8 + i];
+        let a2 = input[4 + i] - input[8 + i];
+        let a3 = input[i] - input[12 + i];
+        temporary[i] = a0 + a1;
+        temporary[8 + i] = a0 - a1;
+        temporary[4 + i] = a3 + a2;
+        temporary[12 + i] = a3 - a2;
+    }
+    for i in range(0u, 4) {
+        let dc = temporary[i * 4] + 3;
+        let a0 = dc + temporary[i * 4 + 3];
+        let a1 = temporary[i * 4 + 1] + temporary[i * 4 + 2];
+        let a2 = temporary[i * 4 + 1] - temporary[i * 4 + 2];
+        let a3 = dc - temporary[i * 4 + 3];
+        coefficients[(i * 4) * 16] = (a0 + a1) >> 3;
+        coefficients[(i * 4 + 1) * 16] = (a3 + a2) >> 3;
+        coefficients[(i * 4 + 2) * 16] = (a0 - a1) >> 3;
+        coefficients[(i * 4 + 3) * 16] = (a3 - a2) >> 3;
+    }
+}
+
+/// Adds the inverse DCT of a 4×4 block of coefficients to the pixels at `offset` (section 14.4).
+fn add_inverse_transform(block: &[i32], pixels: &mut [u8], offset: uint, stride: uint) {
+    if block.iter().all(|&coefficient| coefficient == 0) {
+        return
+    }
+    fn multiply_1(a: i32) -> i32 { ((a * 20091) >> 16) + a }
+    fn multiply_2(a: i32) -> i32 { (a * 35468) >> 16 }
+
+    let mut temporary = [0i32, ..16];
+    for i in range(0u, 4) {
+        let a = block[i] + block[8 + i];
+        let b = block[i] - block[8 + i];
+        let c = multiply_2(block[4 + i]) - multiply_1(block[12 + i]);
+        let d = multiply_1(block[4 + i]) + multiply_2(block[12 + i]);
+        temporary[i * 4] = a + d;
+        temporary[i * 4 + 1] = b + c;
+        temporary[i * 4 + 2] = b - c;
+        temporary[i * 4 + 3] = a - d;
+    }
+    for i in range(0u, 4) {
+        let dc = temporary[i] + 4;
+        let a = dc + temporary[8 + i];
+        let b = dc - temporary[8 + i];
+        let c = multiply_2(temporary[4 + i]) - multiply_1(temporary[12 + i]);
+        let d = multiply_1(temporary[4 + i]) + multiply_2(temporary[12 + i]);
+        let row = offset + i * stride;
+        for (x, &value) in [a + d, b + c, b - c, a - d].iter().enumerate() {
+            pixels[row + x] = clamp_to_u8(pixels[row + x] as i32 + (value >> 3));
+        }
+    }
+}
+
+/// The width of the luma workspace: a column to the left of the macroblock, the macroblock, and
+/// the four pixels above and to the right that subblocks on the right edge predict from.
+static LUMA_WORKSPACE_STRIDE: uint = 21;
+static CHROMA_WORKSPACE_STRIDE: uint = 9;
+
+impl Frame {
+    /// Predicts a macroblock from the pixels above and to the left of it and adds its residue.
+    fn reconstruct_macroblock(&mut self, macroblock: &Macroblock, x: uint, y: uint) {
+        let macroblock_width = self.luma_stride / 16;
+        let stride = LUMA_WORKSPACE_STRIDE;
+        let mut workspace = [0u8, ..21 * 17];
+        fill_workspace(&mut workspace, stride, self.y.as_slice(), self.luma_stride, 16, x, y);
+        // Subblocks on the right edge predict from the pixels above and to the right of the
+        // macroblock, or from the last pixel above if there are none.
+        for index in range(0u, 4) {
+            workspace[17 + index] = if y == 0 {
+                127
+            } else if x + 1 == macroblock_width {
+                self.y[(y * 16 - 1) * self.luma_stride + x * 16 + 15]
+            } else {
+                self.y[(y * 16 - 1) * self.luma_stride + x * 16 + 16 + index]
+            };
+        }
+        for row in [4u, 8, 12].iter() {
+            for index in range(0u, 4) {
+                workspace[*row * stride + 17 + index] = workspace[17 + index];
+            }
+        }
+
+        match macroblock.subblock_modes {
+            None => {
+                predict_macroblock(&mut workspace, stride, 16, macroblock.luma_mode, x, y);
+                for index in range(0u, 16) {
+                    let offset = (1 + index / 4 * 4) * stride + 1 + index % 4 * 4;
+                    add_inverse_transform(macroblock.coefficients.slice(index * 16,
+                                                                        index * 16 + 16),
+                                          &mut workspace, offset, stride);
+                }
+            }
+            Some(ref modes) => {
+                for index in range(0u, 16) {
+                    let offset = (1 + index / 4 * 4) * stride + 1 + index % 4 * 4;
+                    predict_subblock(&mut workspace, stride, offset, modes[index]);
+                    add_inverse_transform(macroblock.coefficients.slice(index * 16,
+                                                                        index * 16 + 16),
+                                          &mut workspace, offset, stride);
+                }
+            }
+        }
+        copy_from_workspace(&workspace, stride, self.y.as_mut_slice(), self.luma_stride, 16, x,
+                            y);
+
+        let stride = CHROMA_WORKSPACE_STRIDE;
+        let chroma_stride = self.chroma_stride;
+        for plane in range(0u, 2) {
+            let mut workspace = [0u8, ..9 * 9];
+            let pixels = if plane == 0 { &mut self.u } else { &mut self.v };
+            fill_workspace(&mut workspace, stride, pixels.as_slice(), chroma_stride, 8, x, y);
+            predict_macroblock(&mut workspace, stride, 8, macroblock.chroma_mode, x, y);
+            for index in range(0u, 4) {
+                let offset = (1 + index / 2 * 4) * stride + 1 + index % 2 * 4;
+                let start = 256 + plane * 64 + index * 16;
+                add_inverse_transform(macroblock.coefficients.slice(start, start + 16),
+                                      &mut workspace, offset, stride);
+            }
+            copy_from_workspace(&workspace, stride, pixels.as_mut_slice(), chroma_stride, 8, x,
+                                y);
+        }
+    }
+
+    /// Applies the loop filter to the edges of a macroblock and of its subblocks (section 15).
+    fn filter_macroblock(&mut self,
+                         filter_type: FilterType,
+                         parameters: &FilterParameters,
+                         x: uint,
+                         y: uint) {
+        if parameters.limit == 0 {
+            return
+        }
+        let luma_stride = self.luma_stride;
+        let luma_start = y * 16 * luma_stride + x * 16;
+        let chroma_stride = self.chroma_stride;
+        let chroma_start = y * 8 * chroma_stride + x * 8;
+        let edge_limit = 2 * (parameters.limit + 4) + 1;
+        let inner_limit = 2 * parameters.limit + 1;
+
+        if filter_type == SimpleFilter {
+            let pixels = self.y.as_mut_slice();
+            if x > 0 {
+                simple_filter(pixels, luma_start, 1, luma_stride, edge_limit);
+            }
+            if parameters.filter_inner_edges {
+                for edge in [4u, 8, 12].iter() {
+                    simple_filter(pixels, luma_start + *edge, 1, luma_stride, inner_limit);
+                }
+            }
+            if y > 0 {
+                simple_filter(pixels, luma_start, luma_stride, 1, edge_limit);
+            }
+            if parameters.filter_inner_edges {
+                for edge in [4u, 8, 12].iter() {
+                    simple_filter(pixels, luma_start + *edge * luma_stride, luma_stride, 1,
+                                  inner_limit);
+                }
+            }
+            return
+        }
+
+        let filter = |pixels: &mut [u8], position: uint, step: uint, along: uint, length: uint,
+                      macroblock_edge: bool| {
+            normal_filter(pixels, position, step, along, length,
+                          if macroblock_edge { edge_limit } else { inner_limit },
+                          parameters.interior_limit, parameters.hev_threshold, macroblock_edge)
+        };
+        if x > 0 {
+            filter(self.y.as_mut_slice(), luma_start, 1, luma_stride, 16, true);
+            filter(self.u.as_mut_slice(), chroma_start, 1, chroma_stride, 8, true);
+            filter(self.v.as_mut_slice(), chroma_start, 1, chroma_stride, 8, true);
+        }
+        if parameters.filter_inner_edges {
+            for edge in [4u, 8, 12].iter() {
+                filter(self.y.as_mut_slice(), luma_start + *edge, 1, luma_stride, 16, false);
+            }
+            filter(self.u.as_mut_slice(), chroma_start + 4, 1, chroma_stride, 8, false);
+            filter(self.v.as_mut_slice(), chroma_start + 4, 1, chroma_stride, 8, false);
+        }
+        if y > 0 {
+            filter(self.y.as_mut_slice(), luma_start, luma_stride, 1, 16, true);
+            filter(self.u.as_mut_slice(), chroma_start, chroma_stride, 1, 8, true);
+            filter(self.v.as_mut_slice(), chroma_start, chroma_stride, 1, 8, true);
+        }
+        if parameters.filter_inner_edges {
+            for edge in [4u, 8, 12].iter() {
+                filter(self.y.as_mut_slice(), luma_start + *edge * luma_stride, luma_stride, 1,
+                       16, false);
+            }
+            filter(self.u.as_mut_slice(), chroma_start + 4 * chroma_stride, chroma_stride, 1, 8,
+                   false);
+            filter(self.v.as_mut_slice(), chroma_start + 4 * chroma_stride, chroma_stride, 1, 8,
+                   false);
+        }
+    }
+
+    /// Converts the frame to unpremultiplied RGBA, upsampling the chroma planes by interpolating
+    /// between their samples as libwebp does. `alpha` holds a byte per pixel, if the image has
+    /// an alpha channel.
+    pub fn to_rgba(&self, alpha: Option<&[u8]>) -> Vec<u8> {
+        let (width, height) = (self.width, self.height);
+        let chroma_height = (height + 1) / 2;
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        let mut u_row = Vec::from_elem(width, 0u8);
+        let mut v_row = Vec::from_elem(width, 0u8);
+        for y in range(0, height) {
+            // Each row interpolates between the nearest row of chroma samples and the row on
+            // the other side of it.
+            let (near, far) = if y == 0 {
+                (0, 0)
+            } else if y % 2 == 1 {
+                ((y - 1) / 2, cmp::min((y + 1) / 2, chroma_height - 1))
+            } else {
+                (y / 2, y / 2 - 1)
+            };
+            upsample_row(self.u.as_slice(), self.chroma_stride, near, far, u_row.as_mut_slice());
+            upsample_row(self.v.as_slice(), self.chroma_stride, near, far, v_row.as_mut_slice());
+            for x in range(0, width) {
+                let luma = self.y[y * self.luma_stride + x] as i32;
+                let (u, v) = (u_row[x] as i32, v_row[x] as i32);
+                rgba.push(yuv_to_channel(luma, multiply_high(v, 26149) - 14234));
+                rgba.push(yuv_to_channel(luma, -multiply_high(u, 6419) -
+                                               multiply_high(v, 13320) + 8708));
+                rgba.push(yuv_to_channel(luma, multiply_high(u, 33050) - 17685));
+                rgba.push(match alpha {
+                    Some(alpha) => alpha[y * width + x],
+                    None => 255,
+                });
+            }
+        }
+        rgba
+    }
+}
+
+/// Copies the pixels above and to the left of a macroblock into a workspace, substituting those
+/// outside the frame as section 12.2 says.
+fn fill_workspace(workspace: &mut [u8],
+                  stride: uint,
+                  pixels: &[u8],
+                  pixels_stride: uint,
+                  size: uint,
+                  x: uint,
+                  y: uint) {
+    let start = y * size * pixels_stride + x * size;
+    for index in range(0, size + 1) {
+        workspace[index] = if y == 0 {
+            127
+        } else if index == 0 {
+            if x == 0 { 129 } else { pixels[start - pixels_stride - 1] }
+        } else {
+            pixels[start - pixels_stride + index - 1]
+        };
+    }
+    for row in range(0, size) {
+        workspace[(row + 1) * stride] = if x == 0 {
+            129
+        } else {
+            pixels[start + row * pixels_stride - 1]
+        };
+    }
+}
+
+fn copy_from_workspace(workspace: &[u8],
+                       stride: uint,
+                       pixels: &mut [u8],
+                       pixels_stride: uint,
+                       size: uint,
+                       x: uint,
+                       y: uint) {
+    for row in range(0, size) {
+        let start = (y * size + row) * pixels_stride + x * size;
+        let source = (row + 1) * stride + 1;
+        pixels.slice_mut(start, start + size).copy_from(workspace.slice(source, source + size));
+    }
+}
+
+/// Predicts a whole luma or chroma macroblock of `size` pixels, at row and column 1 of the
+/// workspace (section 12.2).
+fn predict_macroblock(workspace: &mut [u8],
+                      stride: uint,
+                      size: uint,
+                      mode: PredictionMode,
+                      x: uint,
+                      y: uint) {
+    let shift = if size == 16 { 4 } else { 3 };
+    match mode {
+        DcPred => {
+            let top_sum = range(1, size + 1).fold(0u, |sum, index| sum + workspace[index] as uint);
+            let left_sum = range(1, size + 1).fold(0u, |sum, row| {
+                sum + workspace[row * stride] as uint
+            });
+            let value = match (x > 0, y > 0) {
+                (true, true) => (top_sum + left_sum + size) >> (shift + 1),
+                (true, false) => (left_sum + size / 2) >> shift,
+                (false, true) => (top_sum + size / 2) >> shift,
+                (false, false) => 128,
+            };
+            for row in range(1, size + 1) {
+                for column in range(1, size + 1) {
+                    workspace[row * stride + column] = value as u8;
+                }
+            }
+        }
+        _ => predict_block(workspace, stride, stride + 1, size, mode),
+    }
+}
+
+/// Predicts a block from its edges with the vertical, horizontal or true motion modes.
+fn predict_block(workspace: &mut [u8],
+                 stride: uint,
+                 offset: uint,
+                 size: uint,
+                 mode: PredictionMode) {
+    let top_left = workspace[offset - stride - 1] as i32;
+    for row in range(0, size) {
+        let left = workspace[offset + row * stride - 1];
+        for column in range(0, size) {
+            let top = workspace[offset - stride + column];
+            workspace[offset + row * stride + column] = match mode {
+                VePred => top,
+                HePred => left,
+                _ => clamp_to_u8(left as i32 + top as i32 - top_left),
+            };
+        }
+    }
+}
+
+/// Predicts a 4×4 subblock at `offset` in the workspace (section 12.3).
+fn predict_subblock(workspace: &mut [u8], stride: uint, offset: uint, mode: PredictionMode) {
+    let top_start = offset - stride;
+    let (a, b, c, d) = (workspace[top_start] as u32, workspace[top_start + 1] as u32,
+                        workspace[top_start + 2] as u32, workspace[top_start + 3] as u32);
+    let (e, f, g, h) = (workspace[top_start + 4] as u32, workspace[top_start + 5] as u32,
+                        workspace[top_start + 6] as u32, workspace[top_start + 7] as u32);
+    let x = workspace[top_start - 1] as u32;
+    let (i, j, k, l) = (workspace[offset - 1] as u32, workspace[offset + stride - 1] as u32,
+                        workspace[offset + 2 * stride - 1] as u32,
+                        workspace[offset + 3 * stride - 1] as u32);
+    fn average_2(a: u32, b: u32) -> u8 { ((a + b + 1) >> 1) as u8 }
+    fn average_3(a: u32, b: u32, c: u32) -> u8 { ((a + 2 * b + c + 2) >> 2) as u8 }
+
+    // The predicted pixels, by row.
+    let block: [[u8, ..4], ..4] = match mode {
+        DcPred => {
+            let value = ((a + b + c + d + i + j + k + l + 4) >> 3) as u8;
+            [[value, ..4], ..4]
+        }
+        TmPred => return predict_block(workspace, stride, offset, 4, TmPred),
+        VePred => {
+            let row = [average_3(x, a, b), average_3(a, b, c), average_3(b, c, d),
+                       average_3(c, d, e)];
+            [row, row, row, row]
+        }
+        HePred => {
+            [[average_3(x, i, j), ..4], [average_3(i, j, k), ..4], [average_3(j, k, l), ..4],
+             [average_3(k, l, l), ..4]]
+        }
+        RdPred => {
+            let (lkj, kji, jix) = (average_3(l, k, j), average_3(k, j, i), average_3(j, i, x));
+            let (ixa, xab, abc, bcd) = (average_3(i, x, a), average_3(x, a, b),
+                                        average_3(a, b, c), average_3(b, c, d));
+            [[ixa, xab, abc, bcd],
+             [jix, ixa, xab, abc],
+             [kji, jix, ixa, xab],
+             [lkj, kji, jix, ixa]]
+        }
+        VrPred => {
+            let (xa, ab, bc, cd) = (average_2(x, a), average_2(a, b), average_2(b, c),
+                                    average_2(c, d));
+            let (kji, jix, ixa) = (average_3(k, j, i), average_3(j, i, x), average_3(i, x, a));
+            let (xab, abc, bcd) = (average_3(x, a, b), average_3(a, b, c), average_3(b, c, d));
+            [[xa, ab, bc, cd],
+             [ixa, xab, abc, bcd],
+             [jix, xa, ab, bc],
+             [kji, ixa, xab, abc]]
+        }
+        LdPred => {
+            let (abc, bcd, cde, def) = (average_3(a, b, c), average_3(b, c, d),
+                                        average_3(c, d, e), average_3(d, e, f));
+            let (efg, fgh, ghh) = (average_3(e, f, g), average_3(f, g, h), average_3(g, h, h));
+            [[abc, bcd, cde, def],
+             [bcd, cde, def, efg],
+             [cde, def, efg, fgh],
+             [def, efg, fgh, ghh]]
+        }
+        VlPred => {
+            let (ab, bc, cd, de) = (average_2(a, b), average_2(b, c), average_2(c, d),
+                                    average_2(d, e));
+            let (abc, bcd, cde, def) = (average_3(a, b, c), average_3(b, c, d),
+                                        average_3(c, d, e), average_3(d, e, f));
+            [[ab, bc, cd, de],
+             [abc, bcd, cde, def],
+             [bc, cd, de, average_3(e, f, g)],
+             [bcd, cde, def, average_3(f, g, h)]]
+        }
+        HdPred => {
+            let (ix, ji, kj, lk) = (average_2(i, x), average_2(j, i), average_2(k, j),
+                                    average_2(l, k));
+            let (abc, xab, ixa) = (average_3(a, b, c), average_3(x, a, b), average_3(i, x, a));
+            let (jix, kji, lkj) = (average_3(j, i, x), average_3(k, j, i), average_3(l, k, j));
+            [[ix, ixa, xab, abc],
+             [ji, jix, ix, ixa],
+             [kj, kji, ji, jix],
+             [lk, lkj, kj, kji]]
+        }
+        HuPred => {
+            let (ij, jk, kl) = (average_2(i, j), average_2(j, k), average_2(k, l));
+            let (ijk, jkl, kll) = (average_3(i, j, k), average_3(j, k, l), average_3(k, l, l));
+            let l = l as u8;
+            [[ij, ijk, jk, jkl],
+             [jk, jkl, kl, kll],
+             [kl, kll, l, l],
+             [l, l, l, l]]
+        }
+    };
+    for (row, values) in block.iter().enumerate() {
+        let start = offset + row * stride;
+        workspace.slice_mut(start, start + 4).copy_from(values);
+    }
+}
+
+/// The simple loop filter of section 15.2, across the 16 pixels of a luma edge. `step` goes
+/// across the edge and `along` goes along it.
+fn simple_filter(pixels: &mut [u8], position: uint, step: uint, along: uint, limit: i32) {
+    for index in range(0u, 16) {
+        let position = position + index * along;
+        let (p1, p0) = (pixels[position - 2 * step] as i32, pixels[position - step] as i32);
+        let (q0, q1) = (pixels[position] as i32, pixels[position + step] as i32);
+        if (p0 - q0).abs() * 4 + (p1 - q1).abs() <= limit {
+            common_adjust(pixels, position, step, true);
+        }
+    }
+}
+
+/// The normal loop filter of section 15.3, across `length` pixels of the edge of a macroblock or
+/// of a subblock.
+fn normal_filter(pixels: &mut [u8],
+                 position: uint,
+                 step: uint,
+                 along: uint,
+                 length: uint,
+                 edge_limit: i32,
+                 interior_limit: i32,
+                 hev_threshold: i32,
+                 macroblock_edge: bool) {
+    for index in range(0, length) {
+        let position = position + index * along;
+        let (p3, p2) = (pixels[position - 4 * step] as i32, pixels[position - 3 * step] as i32);
+        let (p1, p0) = (pixels[position - 2 * step] as i32, pixels[position - step] as i32);
+        let (q0, q1) = (pixels[position] as i32, pixels[position + step] as i32);
+        let (q2, q3) = (pixels[position + 2 * step] as i32, pixels[position + 3 * step] as i32);
+        if (p0 - q0).abs() * 4 + (p1 - q1).abs() > edge_limit {
+            continue
+        }
+        if (p3 - p2).abs() > interior_limit || (p2 - p1).abs() > interior_limit ||
+                (p1 - p0).abs() > interior_limit || (q3 - q2).abs() > interior_limit ||
+                (q2 - q1).abs() > interior_limit || (q1 - q0).abs() > interior_limit {
+            continue
+        }
+
+        let high_edge_variance = (p1 - p0).abs() > hev_threshold ||
+            (q1 - q0).abs() > hev_threshold;
+        if high_edge_variance {
+            common_adjust(pixels, position, step, true);
+        } else if macroblock_edge {
+            let a = clamp(3 * (q0 - p0) + clamp(p1 - q1, -128, 127), -128, 127);
+            let a1 = (27 * a + 63) >> 7;
+            let a2 = (18 * a + 63) >> 7;
+            let a3 = (9 * a + 63) >> 7;
+            pixels[position - 3 * step] = clamp_to_u8(p2 + a3);
+            pixels[position - 2 * step] = clamp_to_u8(p1 + a2);
+            pixels[position - step] = clamp_to_u8(p0 + a1);
+            pixels[position] = clamp_to_u8(q0 - a1);
+            pixels[position + step] = clamp_to_u8(q1 - a2);
+            pixels[position + 2 * step] = clamp_to_u8(q2 - a3);
+        } else {
+            let a1 = common_adjust(pixels, position, step, false);
+            let a3 = (a1 + 1) >> 1;
+            pixels[position - 2 * step] = clamp_to_u8(p1 + a3);
+            pixels[position + step] = clamp_to_u8(q1 - a3);
+        }
+    }
+}
+
+/// Moves the two pixels next to an edge towards each other, taking the pixels beyond them into
+/// account if `use_outer_taps` is set. Returns the adjustment of the pixel after the edge.
+fn common_adjust(pixels: &mut [u8], position: uint, step: uint, use_outer_taps: bool) -> i32 {
+    let (p1, p0) = (pixels[position - 2 * step] as i32, pixels[position - step] as i32);
+    let (q0, q1) = (pixels[position] as i32, pixels[position + step] as i32);
+    let outer = if use_outer_taps { clamp(p1 - q1, -128, 127) } else { 0 };
+    let a = 3 * (q0 - p0) + outer;
+    let a1 = clamp((a + 4) >> 3, -16, 15);
+    let a2 = clamp((a + 3) >> 3, -16, 15);
+    pixels[position - step] = clamp_to_u8(p0 + a2);
+    pixels[position] = clamp_to_u8(q0 - a1);
+    a1
+}
+
+/// Interpolates a row of chroma samples to the full width of the image, weighting the row that
+/// is nearer to the output row three times as much as the one farther from it, and doing the
+/// same horizontally.
+fn upsample_row(plane: &[u8], stride: uint, near_row: uint, far_row: uint, output: &mut [u8]) {
+    let near = plane.slice_from(near_row * stride);
+    let far = plane.slice_from(far_row * stride);
+    let width = output.len();
+    output[0] = ((3 * near[0] as u32 + far[0] as u32 + 2) >> 2) as u8;
+    for x in range(1, (width - 1) / 2 + 1) {
+        let (near_left, near_right) = (near[x - 1] as u32, near[x] as u32);
+        let (far_left, far_right) = (far[x - 1] as u32, far[x] as u32);
+        let sum = near_left + near_right + far_left + far_right + 8;
+        let left_diagonal = (sum + 2 * (near_right + far_left)) >> 3;
+        let right_diagonal = (sum + 2 * (near_left + far_right)) >> 3;
+        output[2 * x - 1] = ((left_diagonal + near_left) >> 1) as u8;
+        output[2 * x] = ((right_diagonal + near_right) >> 1) as u8;
+    }
+    if width % 2 == 0 {
+        let last = width / 2 - 1;
+        output[width - 1] = ((3 * near[last] as u32 + far[last] as u32 + 2) >> 2) as u8;
+    }
+}
+
+/// Computes a color channel from luma and the chroma terms of the BT.601 conversion, in the
+/// fixed point arithmetic of libwebp.
+fn yuv_to_channel(luma: i32, chroma_terms: i32) -> u8 {
+    clamp((multiply_high(luma, 19077) + chroma_terms) >> 6, 0, 255) as u8
+}
+
+fn multiply_high(value: i32, coefficient: i32) -> i32 {
+    (value * coefficient) >> 8
+}
+
+fn clamp(value: i32, minimum: i32, maximum: i32) -> i32 {
+    cmp::max(minimum, cmp::min(value, maximum))
+}
+
+fn clamp_to_u8(value: i32) -> u8 {
+    clamp(value, 0, 255) as u8
+}
@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decodes still WebP images, per the
+//! [WebP container specification](https://developers.google.com/speed/webp/docs/riff_container).
+
+use image::base::{DecodeResult, MalformedImage, UnsupportedImageFeature};
+
+use std::cmp;
+
+pub mod lossless;
+pub mod lossy;
+
+/// Images with more pixels than this are rejected before their pixels are allocated, since the
+/// 14-bit sizes in the headers allow images far larger than their data.
+static MAX_PIXELS: uint = 1 << 26;
+
+/// The flag in the extended header that marks animated images.
+static ANIMATION_FLAG: u8 = 0x02;
+
+/// Returns true if the buffer starts with a WebP RIFF header.
+pub fn is_webp(buffer: &[u8]) -> bool {
+    buffer.len() >= 12 && buffer.starts_with(b"RIFF") && buffer.slice(8, 12) == b"WEBP"
+}
+
+/// Decodes a WebP image into its width, height and unpremultiplied RGBA pixels.
+pub fn decode(buffer: &[u8]) -> DecodeResult<(uint, uint, Vec<u8>)> {
+    if !is_webp(buffer) {
+        return Err(MalformedImage("the WebP header is missing".to_string()))
+    }
+
+    let mut position = 12;
+    let mut alpha_chunk = None;
+    while position + 8 <= buffer.len() {
+        let fourcc = buffer.slice(position, position + 4);
+        let size = read_u32(buffer.slice(position + 4, position + 8)) as uint;
+        position += 8;
+        if size > buffer.len() - position {
+            return Err(MalformedImage("a WebP chunk is truncated".to_string()))
+        }
+        let data = buffer.slice(position, position + size);
+        // Chunks are padded to an even length.
+        position += size + (size & 1);
+
+        // Other chunks hold color profiles and metadata.
+        if fourcc == b"VP8X" {
+            if data.len() < 10 {
+                return Err(MalformedImage("the WebP extended header is truncated".to_string()))
+            }
+            if data[0] & ANIMATION_FLAG != 0 {
+                return Err(UnsupportedImageFeature("animated WebP images".to_string()))
+            }
+        } else if fourcc == b"ALPH" {
+            alpha_chunk = Some(data);
+        } else if fourcc == b"VP8 " {
+            let frame = try!(lossy::decode(data));
+            let alpha = match alpha_chunk {
+                Some(chunk) => Some(try!(decode_alpha(chunk, frame.width, frame.height))),
+                None => None,
+            };
+            let rgba = frame.to_rgba(alpha.as_ref().map(|alpha| alpha.as_slice()));
+            return Ok((frame.width, frame.height, rgba))
+        } else if fourcc == b"VP8L" {
+            let (width, height, pixels) = try!(lossless::decode(data));
+            return Ok((width, height, lossless::argb_to_rgba(pixels.as_slice())))
+        }
+    }
+    Err(MalformedImage("the WebP image data is missing".to_string()))
+}
+
+/// Decodes the alpha channel of a lossy image, which is stored raw or as the green channel of a
+/// lossless image, and then optionally filtered.
+fn decode_alpha(chunk: &[u8], width: uint, height: uint) -> DecodeResult<Vec<u8>> {
+    if chunk.len() == 0 {
+        return Err(MalformedImage("the WebP alpha chunk is empty".to_string()))
+    }
+    let compression = chunk[0] & 0x3;
+    let filter = (chunk[0] >> 2) & 0x3;
+    let data = chunk.slice_from(1);
+    let mut alpha = match compression {
+        0 => {
+            if data.len() < width * height {
+                return Err(MalformedImage("the WebP alpha chunk is truncated".to_string()))
+            }
+            data.slice_to(width * height).to_vec()
+        }
+        1 => try!(lossless::decode_alpha(data, width, height)),
+        _ => {
+            return Err(MalformedImage("the WebP alpha compression is unknown".to_string()))
+        }
+    };
+    unfilter_alpha(alpha.as_mut_slice(), width, height, filter);
+    Ok(alpha)
+}
+
+/// Undoes the prediction of each alpha value from the values to its left, above it, or both.
+fn unfilter_alpha(alpha: &mut [u8], width: uint, height: uint, filter: u8) {
+    if filter == 0 {
+        return
+    }
+    for y in range(0, height) {
+        let row = y * width;
+        for x in range(0, width) {
+            let left = if x > 0 {
+                alpha[row + x - 1]
+            } else if y > 0 {
+                alpha[row - width]
+            } else {
+                0
+            };
+            let prediction = if y == 0 {
+                // The first row is always filtered horizontally.
+                left
+            } else {
+                let top = alpha[row - width + x];
+                match filter {
+                    1 => left,
+                    2 => top,
+                    _ => {
+                        if x == 0 {
+                            top
+                        } else {
+                            let top_left = alpha[row - width + x - 1] as i32;
+                            let gradient = left as i32 + top as i32 - top_left;
+                            cmp::max(0, cmp::min(gradient, 255)) as u8
+                        }
+                    }
+                }
+            };
+            alpha[row + x] += prediction;
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32 << 8) | (bytes[2] as u32 << 16) | (bytes[3] as u32 << 24)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+    use image::base::{MalformedImage, UnsupportedImageFeature};
+
+    use png;
+
+    /// The IDLE icon, compressed by cwebp. The PNGs hold the pixels that dwebp decodes.
+    static LOSSY_IMAGE: &'static [u8] = include_bin!("test-lossy.webp");
+    static LOSSY_PIXELS: &'static [u8] = include_bin!("test-lossy.png");
+    static LOSSLESS_IMAGE: &'static [u8] = include_bin!("test-lossless.webp");
+    static LOSSLESS_PIXELS: &'static [u8] = include_bin!("test-lossless.png");
+
+    fn assert_decodes_to(image: &[u8], expected: &[u8]) {
+        let (width, height, pixels) = decode(image).unwrap();
+        let expected = png::load_png_from_memory(expected).unwrap();
+        assert_eq!((width as u32, height as u32), (expected.width, expected.height));
+        match expected.pixels {
+            png::RGBA8(ref expected_pixels) => assert!(pixels == *expected_pixels),
+            _ => fail!("the expected pixels are not RGBA"),
+        }
+    }
+
+    #[test]
+    fn test_decodes_lossy_images_with_alpha() {
+        assert_decodes_to(LOSSY_IMAGE, LOSSY_PIXELS);
+    }
+
+    #[test]
+    fn test_decodes_lossless_images() {
+        assert_decodes_to(LOSSLESS_IMAGE, LOSSLESS_PIXELS);
+    }
+
+    #[test]
+    fn test_rejects_animated_images() {
+        let mut image = b"RIFF\x16\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00".to_vec();
+        image.push_all(&[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        match decode(image.as_slice()) {
+            Err(UnsupportedImageFeature(_)) => {}
+            result => fail!("unexpected result: {}", result),
+        }
+    }
+
+    #[test]
+    fn test_rejects_images_with_too_many_pixels() {
+        // A lossy and a lossless frame header for 16383 by 16383 and 16384 by 16384 pixels.
+        let mut lossy = b"RIFF\x16\x00\x00\x00WEBPVP8 \x0a\x00\x00\x00".to_vec();
+        lossy.push_all(&[0x10, 0, 0, 0x9d, 0x01, 0x2a, 0xff, 0x3f, 0xff, 0x3f]);
+        let mut lossless = b"RIFF\x11\x00\x00\x00WEBPVP8L\x05\x00\x00\x00".to_vec();
+        lossless.push_all(&[0x2f, 0xff, 0xff, 0xff, 0x0f]);
+        for image in [lossy, lossless].iter() {
+            match decode(image.as_slice()) {
+                Err(MalformedImage(_)) => {}
+                result => fail!("unexpected result: {}", result),
+            }
+        }
+    }
+}
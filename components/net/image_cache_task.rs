@@ -2,16 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::{Image, load_from_memory};
+use image::animation::Animation;
+use image::base::{DecodeResult, Image, MalformedImage, load_animation_from_memory};
+use image::base::load_from_memory;
 use resource_task;
 use resource_task::{LoadData, ResourceTask};
 
+use geom::size::Size2D;
+use std::cmp;
 use std::comm::{channel, Receiver, Sender};
 use std::collections::hashmap::HashMap;
 use std::mem::replace;
+use std::task;
 use std::task::spawn;
 use std::result;
-use sync::{Arc, Mutex};
+use sync::Arc;
 use serialize::{Encoder, Encodable};
 use url::Url;
 
@@ -23,6 +28,16 @@ pub enum Msg {
     /// Tell the cache to decode an image. Must be posted before GetImage/WaitForImage
     Decode(Url),
 
+    /// Tell the cache to decode an image, but only to keep as many pixels as showing it at the
+    /// given size in device pixels needs. Requests that arrive before decoding starts are
+    /// combined, so the image keeps its full size if it is also decoded with `Decode`. Later
+    /// requests for more pixels decode the image again, and the smaller image is returned until
+    /// then. Animated images always keep their full size. Can be posted instead of Decode
+    ///
+    /// The image is still decoded at its full size and downscaled afterwards, so this lowers the
+    /// memory that the image keeps, but not the peak memory of decoding it.
+    DecodeToSize(Url, Size2D<u32>),
+
     /// Request an Image object for a URL. If the image is not is not immediately
     /// available then ImageNotReady is returned.
     GetImage(Url, Sender<ImageResponseMsg>),
@@ -30,14 +45,24 @@ pub enum Msg {
     /// Wait for an image to become available (or fail to load).
     WaitForImage(Url, Sender<ImageResponseMsg>),
 
+    /// Like `WaitForImage`, but the cache calls the callback with the response on its own task
+    /// instead of sending it, so clients need no task of their own to wait on. The callback must
+    /// not block.
+    WaitForImageCallback(Url, ImageCallback),
+
+    /// Request the frames of an image that has been decoded, or `None` if the image is not
+    /// animated or not decoded yet.
+    GetAnimation(Url, Sender<Option<Arc<Animation>>>),
+
     /// Clients must wait for a response before shutting down the ResourceTask
     Exit(Sender<()>),
 
     /// Used by the prefetch tasks to post back image binaries
     StorePrefetchedImageData(Url, Result<Vec<u8>, ()>),
 
-    /// Used by the decoder tasks to post decoded images back to the cache
-    StoreImage(Url, Option<Arc<Box<Image>>>),
+    /// Used by the decoder tasks to post decoded images, or why they could not be decoded, back
+    /// to the cache, along with their frames if they are animated
+    StoreImage(Url, DecodeResult<Arc<Image>>, Option<Arc<Animation>>),
 
     /// For testing
     WaitForStore(Sender<()>),
@@ -46,9 +71,12 @@ pub enum Msg {
     WaitForStorePrefetched(Sender<()>),
 }
 
+/// Called with the response once an image that was waited for is available or has failed.
+pub type ImageCallback = proc(ImageResponseMsg):Send;
+
 #[deriving(Clone)]
 pub enum ImageResponseMsg {
-    ImageReady(Arc<Box<Image>>),
+    ImageReady(Arc<Image>),
     ImageNotReady,
     ImageFailed
 }
@@ -65,6 +93,28 @@ impl PartialEq for ImageResponseMsg {
     }
 }
 
+/// How many pixels a decoded image keeps.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DecodeSize {
+    /// The image keeps all of its pixels.
+    FullSize,
+    /// The image keeps enough pixels to be shown at the given size in device pixels.
+    FitSize(Size2D<u32>),
+}
+
+impl DecodeSize {
+    /// Returns the smallest size that serves both this request and `other`.
+    pub fn union(&self, other: &DecodeSize) -> DecodeSize {
+        match (self, other) {
+            (&FitSize(ref size), &FitSize(ref other_size)) => {
+                FitSize(Size2D(cmp::max(size.width, other_size.width),
+                               cmp::max(size.height, other_size.height)))
+            }
+            (&FullSize, _) | (_, &FullSize) => FullSize,
+        }
+    }
+}
+
 #[deriving(Clone)]
 pub struct ImageCacheTask {
     chan: Sender<Msg>,
@@ -76,7 +126,7 @@ impl<E, S: Encoder<E>> Encodable<S, E> for ImageCacheTask {
     }
 }
 
-type DecoderFactory = fn() -> (proc(&[u8]) : 'static -> Option<Image>);
+type DecoderFactory = fn() -> (proc(&[u8]) : 'static -> DecodeResult<Image>);
 
 impl ImageCacheTask {
     pub fn new(resource_task: ResourceTask) -> ImageCacheTask {
@@ -137,8 +187,8 @@ struct ImageCache {
     chan: Sender<Msg>,
     /// The state of processsing an image for a URL
     state_map: HashMap<Url, ImageState>,
-    /// List of clients waiting on a WaitForImage response
-    wait_map: HashMap<Url, Arc<Mutex<Vec<Sender<ImageResponseMsg>>>>>,
+    /// The clients waiting on a WaitForImage or WaitForImageCallback response
+    wait_map: HashMap<Url, Vec<ImageCallback>>,
     need_exit: Option<Sender<()>>,
 }
 
@@ -147,14 +197,25 @@ enum ImageState {
     Init,
     Prefetching(AfterPrefetch),
     Prefetched(Vec<u8>),
-    Decoding,
-    Decoded(Arc<Box<Image>>),
+    /// The image is being decoded to serve the given size. If it was decoded with fewer pixels
+    /// before, that image is returned in the meantime.
+    Decoding(EncodedImage, Option<Arc<Image>>),
+    /// The image has been decoded. If it was downscaled, its data is kept, so that it can be
+    /// decoded again if more pixels are asked for.
+    Decoded(Arc<Image>, Option<Arc<Animation>>, Option<EncodedImage>),
     Failed
 }
 
+/// The data of an image and the size that the requests to decode it so far add up to.
+#[deriving(Clone)]
+struct EncodedImage {
+    data: Arc<Vec<u8>>,
+    size: DecodeSize,
+}
+
 #[deriving(Clone)]
 enum AfterPrefetch {
-    DoDecode,
+    DoDecode(DecodeSize),
     DoNotDecode
 }
 
@@ -178,19 +239,22 @@ impl ImageCache {
 
                     self.store_prefetched_image_data(url, data);
                 }
-                Decode(url) => self.decode(url),
-                StoreImage(url, image) => {
+                Decode(url) => self.decode(url, FullSize),
+                DecodeToSize(url, size) => self.decode(url, FitSize(size)),
+                StoreImage(url, image, animation) => {
                     store_chan.map(|chan| {
                         chan.send(());
                     });
                     store_chan = None;
 
-                    self.store_image(url, image)
+                    self.store_image(url, image, animation)
                 }
                 GetImage(url, response) => self.get_image(url, response),
+                GetAnimation(url, response) => self.get_animation(url, response),
                 WaitForImage(url, response) => {
-                    self.wait_for_image(url, response)
+                    self.wait_for_image(url, proc(image_response) { response.send(image_response) })
                 }
+                WaitForImageCallback(url, callback) => self.wait_for_image(url, callback),
                 WaitForStore(chan) => store_chan = Some(chan),
                 WaitForStorePrefetched(chan) => store_prefetched_chan = Some(chan),
                 Exit(response) => {
@@ -209,7 +273,7 @@ impl ImageCache {
                 for (_, state) in self.state_map.iter() {
                     match *state {
                         Prefetching(..) => can_exit = false,
-                        Decoding => can_exit = false,
+                        Decoding(..) => can_exit = false,
 
                         Init | Prefetched(..) | Decoded(..) | Failed => ()
                     }
@@ -263,7 +327,7 @@ impl ImageCache {
                 self.set_state(url, Prefetching(DoNotDecode));
             }
 
-            Prefetching(..) | Prefetched(..) | Decoding | Decoded(..) | Failed => {
+            Prefetching(..) | Prefetched(..) | Decoding(..) | Decoded(..) | Failed => {
                 // We've already begun working on this image
             }
         }
@@ -276,7 +340,7 @@ impl ImageCache {
               Ok(data) => {
                 self.set_state(url.clone(), Prefetched(data));
                 match next_step {
-                  DoDecode => self.decode(url),
+                  DoDecode(size) => self.decode(url, size),
                   _ => ()
                 }
               }
@@ -289,7 +353,7 @@ impl ImageCache {
 
           Init
           | Prefetched(..)
-          | Decoding
+          | Decoding(..)
           | Decoded(..)
           | Failed => {
             fail!("wrong state for storing prefetched image")
@@ -297,55 +361,100 @@ impl ImageCache {
         }
     }
 
-    fn decode(&mut self, url: Url) {
+    fn decode(&mut self, url: Url, size: DecodeSize) {
         match self.get_state(url.clone()) {
             Init => fail!("decoding image before prefetch"),
 
             Prefetching(DoNotDecode) => {
                 // We don't have the data yet, queue up the decode
-                self.set_state(url, Prefetching(DoDecode))
+                self.set_state(url, Prefetching(DoDecode(size)))
             }
 
-            Prefetching(DoDecode) => {
-                // We don't have the data yet, but the decode request is queued up
+            Prefetching(DoDecode(queued_size)) => {
+                // We don't have the data yet, but the decode request is queued up. It has to
+                // serve this request too.
+                self.set_state(url, Prefetching(DoDecode(queued_size.union(&size))))
             }
 
             Prefetched(data) => {
-                let to_cache = self.chan.clone();
-                let url_clone = url.clone();
+                let encoded_image = EncodedImage {
+                    data: Arc::new(data),
+                    size: size,
+                };
+                self.start_decoding(url, encoded_image, None)
+            }
 
-                spawn(proc() {
-                    let url = url_clone;
-                    debug!("image_cache_task: started image decode for {:s}", url.serialize());
-                    let image = load_from_memory(data.as_slice());
-                    let image = if image.is_some() {
-                        Some(Arc::new(box image.unwrap()))
-                    } else {
-                        None
-                    };
-                    to_cache.send(StoreImage(url.clone(), image));
-                    debug!("image_cache_task: ended image decode for {:s}", url.serialize());
-                });
+            Decoding(mut encoded_image, previous_image) => {
+                // The image is checked against the combined size once it is decoded.
+                encoded_image.size = encoded_image.size.union(&size);
+                self.set_state(url, Decoding(encoded_image, previous_image))
+            }
 
-                self.set_state(url, Decoding);
+            Decoded(image, _, Some(mut encoded_image)) => {
+                if !has_pixels_for(&*image, &size) {
+                    encoded_image.size = encoded_image.size.union(&size);
+                    self.start_decoding(url, encoded_image, Some(image))
+                }
             }
 
-            Decoding | Decoded(..) | Failed => {
-                // We've already begun decoding
+            Decoded(_, _, None) | Failed => {
+                // We've already decoded all of the pixels, or failed to
             }
         }
     }
 
-    fn store_image(&mut self, url: Url, image: Option<Arc<Box<Image>>>) {
+    fn start_decoding(&mut self,
+                      url: Url,
+                      encoded_image: EncodedImage,
+                      previous_image: Option<Arc<Image>>) {
+        let to_cache = self.chan.clone();
+        let url_clone = url.clone();
+        let data = encoded_image.data.clone();
+        let size = encoded_image.size.clone();
+
+        spawn(proc() {
+            let url = url_clone;
+            debug!("image_cache_task: started image decode for {:s}", url.serialize());
+            // A decoder that fails leaves the image broken rather than decoding forever.
+            let (image, animation) = match task::try(proc() { decode_image(data, size) }) {
+                Ok(decoded) => decoded,
+                Err(_) => (Err(MalformedImage("the decoder failed".to_string())), None),
+            };
+            to_cache.send(StoreImage(url.clone(), image, animation));
+            debug!("image_cache_task: ended image decode for {:s}", url.serialize());
+        });
+
+        self.set_state(url, Decoding(encoded_image, previous_image));
+    }
+
+    fn store_image(&mut self,
+                   url: Url,
+                   image: DecodeResult<Arc<Image>>,
+                   animation: Option<Arc<Animation>>) {
 
         match self.get_state(url.clone()) {
-          Decoding => {
-            match image {
-              Some(image) => {
-                self.set_state(url.clone(), Decoded(image.clone()));
-                self.purge_waiters(url, || ImageReady(image.clone()) );
+          Decoding(encoded_image, previous_image) => {
+            match (image, previous_image) {
+              (Ok(image), _) => {
+                // The data is only needed to decode the image again if it was downscaled.
+                let downscaled = !has_pixels_for(&*image, &FullSize);
+                let encoded_image = if downscaled { Some(encoded_image) } else { None };
+                let size = encoded_image.as_ref().map(|encoded_image| encoded_image.size.clone());
+                self.set_state(url.clone(), Decoded(image.clone(), animation, encoded_image));
+                self.purge_waiters(url.clone(), || ImageReady(image.clone()) );
+
+                // Requests for more pixels may have arrived while the image was decoding.
+                match size {
+                    Some(size) => self.decode(url, size),
+                    None => {}
+                }
               }
-              None => {
+              (Err(error), Some(previous_image)) => {
+                debug!("image_cache_task: failed to decode {:s} again: {}", url.serialize(), error);
+                self.set_state(url.clone(), Decoded(previous_image, None, None));
+              }
+              (Err(error), None) => {
+                debug!("image_cache_task: failed to decode {:s}: {}", url.serialize(), error);
                 self.set_state(url.clone(), Failed);
                 self.purge_waiters(url, || ImageFailed );
               }
@@ -366,9 +475,8 @@ impl ImageCache {
     fn purge_waiters(&mut self, url: Url, f: || -> ImageResponseMsg) {
         match self.wait_map.pop(&url) {
             Some(waiters) => {
-                let mut items = waiters.lock();
-                for response in items.iter() {
-                    response.send(f());
+                for waiter in waiters.into_iter() {
+                    waiter(f());
                 }
             }
             None => ()
@@ -378,40 +486,40 @@ impl ImageCache {
     fn get_image(&self, url: Url, response: Sender<ImageResponseMsg>) {
         match self.get_state(url.clone()) {
             Init => fail!("request for image before prefetch"),
-            Prefetching(DoDecode) => response.send(ImageNotReady),
+            Prefetching(DoDecode(..)) => response.send(ImageNotReady),
             Prefetching(DoNotDecode) | Prefetched(..) => fail!("request for image before decode"),
-            Decoding => response.send(ImageNotReady),
-            Decoded(image) => response.send(ImageReady(image.clone())),
+            Decoding(_, None) => response.send(ImageNotReady),
+            Decoding(_, Some(image)) | Decoded(image, _, _) => {
+                response.send(ImageReady(image.clone()))
+            }
             Failed => response.send(ImageFailed),
         }
     }
 
-    fn wait_for_image(&mut self, url: Url, response: Sender<ImageResponseMsg>) {
+    fn get_animation(&self, url: Url, response: Sender<Option<Arc<Animation>>>) {
+        match self.get_state(url) {
+            Decoded(_, animation, _) => response.send(animation),
+            Init | Prefetching(..) | Prefetched(..) | Decoding(..) | Failed => response.send(None),
+        }
+    }
+
+    fn wait_for_image(&mut self, url: Url, callback: ImageCallback) {
         match self.get_state(url.clone()) {
             Init => fail!("request for image before prefetch"),
 
             Prefetching(DoNotDecode) | Prefetched(..) => fail!("request for image before decode"),
 
-            Prefetching(DoDecode) | Decoding => {
+            Prefetching(DoDecode(..)) | Decoding(_, None) => {
                 // We don't have this image yet
-                if self.wait_map.contains_key(&url) {
-                    let waiters = self.wait_map.find_mut(&url).unwrap();
-                    let mut response = Some(response);
-                    let mut items = waiters.lock();
-                    items.push(response.take().unwrap());
-                } else {
-                    let response = vec!(response);
-                    let wrapped = Arc::new(Mutex::new(response));
-                    self.wait_map.insert(url, wrapped);
-                }
+                self.wait_map.find_or_insert_with(url, |_| vec!()).push(callback);
             }
 
-            Decoded(image) => {
-                response.send(ImageReady(image.clone()));
+            Decoding(_, Some(image)) | Decoded(image, _, _) => {
+                callback(ImageReady(image.clone()));
             }
 
             Failed => {
-                response.send(ImageFailed);
+                callback(ImageFailed);
             }
         }
     }
@@ -474,6 +582,35 @@ fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<Vec<u8>, ()>
 }
 
 
+/// Returns true if the image keeps enough pixels to be shown at the given size.
+fn has_pixels_for(image: &Image, size: &DecodeSize) -> bool {
+    let (width, height) = match *size {
+        FullSize => (image.width, image.height),
+        FitSize(ref size) => {
+            (cmp::min(size.width, image.width), cmp::min(size.height, image.height))
+        }
+    };
+    image.pixel_width() >= width && image.pixel_height() >= height
+}
+
+/// Decodes an image, keeping only as many pixels as showing it at `size` needs. The image is
+/// decoded at its full size first, so it takes as much memory while it is decoded as it would
+/// otherwise. An animated image shows its first frame until it is painted as an animation.
+fn decode_image(data: Arc<Vec<u8>>, size: DecodeSize)
+                -> (DecodeResult<Arc<Image>>, Option<Arc<Animation>>) {
+    match load_animation_from_memory(data.as_slice()) {
+        Some(animation) => (Ok(animation.frames[0].image.clone()), Some(Arc::new(animation))),
+        None => {
+            // The full size image is dropped as soon as it is downscaled.
+            let image = match (load_from_memory(data.as_slice()), size) {
+                (Ok(image), FitSize(size)) => Ok(image.downscale_to_fit(size)),
+                (image, _) => image,
+            };
+            (image.map(|image| Arc::new(image)), None)
+        }
+    }
+}
+
 pub fn spawn_listener<A: Send>(f: proc(Receiver<A>):Send) -> Sender<A> {
     let (setup_chan, setup_port) = channel();
 
@@ -490,9 +627,11 @@ pub fn spawn_listener<A: Send>(f: proc(Receiver<A>):Send) -> Sender<A> {
 mod tests {
     use super::*;
 
+    use geom::size::Size2D;
     use resource_task;
     use resource_task::{ResourceTask, Metadata, start_sending};
     use image::base::test_image_bin;
+    use local_image_cache::LocalImageCache;
     use std::comm;
     use url::Url;
 
@@ -944,6 +1083,137 @@ mod tests {
         mock_resource_task.send(resource_task::Exit);
     }
 
+    #[test]
+    fn should_downscale_images_decoded_to_size() {
+        let (wait_chan, wait_port) = comm::channel();
+
+        let mock_resource_task = mock_resource_task(box WaitSendTestImage {wait_port: wait_port});
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(DecodeToSize(url.clone(), Size2D(100, 100)));
+        image_cache_task.send(DecodeToSize(url.clone(), Size2D(50, 100)));
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url, response_chan));
+
+        wait_chan.send(());
+
+        // The 337x450 test image is downscaled by 3, which still fits 100x100.
+        match response_port.recv() {
+          ImageReady(image) => {
+            assert_eq!((image.width, image.height), (337, 450));
+            assert_eq!((image.pixel_width(), image.pixel_height()), (113, 150));
+          }
+          _ => fail!("bleh")
+        }
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
+    #[test]
+    fn should_decode_again_if_more_pixels_are_asked_for_after_decoding() {
+        let mock_resource_task = mock_resource_task(box SendTestImage);
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(DecodeToSize(url.clone(), Size2D(100, 100)));
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url.clone(), response_chan));
+        match response_port.recv() {
+          ImageReady(image) => {
+            assert_eq!((image.pixel_width(), image.pixel_height()), (113, 150));
+          }
+          _ => fail!("bleh")
+        }
+
+        let join_port = image_cache_task.wait_for_store();
+        image_cache_task.send(Decode(url.clone()));
+        join_port.recv();
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(GetImage(url, response_chan));
+        match response_port.recv() {
+          ImageReady(image) => {
+            assert_eq!((image.pixel_width(), image.pixel_height()), (337, 450));
+          }
+          _ => fail!("bleh")
+        }
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
+    #[test]
+    fn should_give_local_caches_more_pixels_once_they_ask_for_them() {
+        let mock_resource_task = mock_resource_task(box SendTestImage);
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+        let mut local_image_cache = LocalImageCache::new(image_cache_task.clone());
+
+        local_image_cache.prefetch(&url);
+        local_image_cache.decode_to_size(&url, FitSize(Size2D(100, 100)));
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url.clone(), response_chan));
+        response_port.recv();
+        match local_image_cache.get_image(&url, 0).recv() {
+          ImageReady(image) => {
+            assert_eq!((image.pixel_width(), image.pixel_height()), (113, 150));
+          }
+          _ => fail!("bleh")
+        }
+
+        let join_port = image_cache_task.wait_for_store();
+        local_image_cache.decode(&url);
+        join_port.recv();
+
+        match local_image_cache.get_image(&url, 0).recv() {
+          ImageReady(image) => {
+            assert_eq!((image.pixel_width(), image.pixel_height()), (337, 450));
+          }
+          _ => fail!("bleh")
+        }
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
+    #[test]
+    fn should_keep_full_size_if_image_is_also_decoded_at_full_size() {
+        let (wait_chan, wait_port) = comm::channel();
+
+        let mock_resource_task = mock_resource_task(box WaitSendTestImage {wait_port: wait_port});
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(DecodeToSize(url.clone(), Size2D(100, 100)));
+        image_cache_task.send(Decode(url.clone()));
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url, response_chan));
+
+        wait_chan.send(());
+
+        match response_port.recv() {
+          ImageReady(image) => {
+            assert_eq!((image.pixel_width(), image.pixel_height()), (337, 450));
+          }
+          _ => fail!("bleh")
+        }
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
     #[test]
     fn should_return_image_failed_on_wait_if_image_fails_to_load() {
         let (wait_chan, wait_port) = comm::channel();
@@ -970,6 +1240,41 @@ mod tests {
         mock_resource_task.send(resource_task::Exit);
     }
 
+    #[test]
+    fn should_call_back_on_the_cache_task_when_image_fails_to_load() {
+        let (wait_chan, wait_port) = comm::channel();
+
+        let mock_resource_task = mock_resource_task(box WaitSendTestImageErr{wait_port: wait_port});
+
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(Decode(url.clone()));
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImageCallback(url.clone(), proc(response) {
+            response_chan.send(response)
+        }));
+
+        wait_chan.send(());
+
+        match response_port.recv() {
+          ImageFailed => (),
+          _ => fail!("bleh")
+        }
+
+        // Once the image has failed, the callback is called right away.
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImageCallback(url, proc(response) {
+            response_chan.send(response)
+        }));
+        assert!(response_port.recv() == ImageFailed);
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
     #[test]
     fn sync_cache_should_wait_for_images() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
@@ -26,8 +26,14 @@ extern crate url;
 /// However, image handling is generally very integrated with the network stack (especially where
 /// caching is involved) and as a result it must live in here.
 pub mod image {
+    pub mod animation;
+    pub mod apng;
     pub mod base;
+    pub mod bmp;
+    pub mod gif;
     pub mod holder;
+    pub mod ico;
+    pub mod webp;
 }
 
 pub mod about_loader;
@@ -8,16 +8,22 @@ extra message traffic, it also avoids waiting on the same image
 multiple times and thus triggering reflows multiple times.
 */
 
-use image_cache_task::{Decode, GetImage, ImageCacheTask, ImageFailed, ImageNotReady, ImageReady};
+use image::animation::Animation;
+use image_cache_task::{Decode, DecodeSize, DecodeToSize, FitSize, FullSize, GetAnimation};
+use image_cache_task::{GetImage, ImageCacheTask, ImageFailed, ImageNotReady, ImageReady};
 use image_cache_task::{ImageResponseMsg, Prefetch, WaitForImage};
 
 use std::comm::{Receiver, channel};
 use std::collections::hashmap::HashMap;
+use std::mem;
 use servo_util::task::spawn_named;
+use sync::{Arc, Mutex};
 use url::Url;
 
 pub trait ImageResponder {
-    fn respond(&self) -> proc(ImageResponseMsg):Send;
+    /// Returns a callback to run once an image that was not ready becomes available. The callback
+    /// is given the opaque addresses of the nodes that asked for the image in the meantime.
+    fn respond(&self) -> proc(ImageResponseMsg, Vec<uint>):Send;
 }
 
 pub struct LocalImageCache {
@@ -41,9 +47,22 @@ impl LocalImageCache {
 #[deriving(Clone)]
 struct ImageState {
     prefetched: bool,
-    decoded: bool,
+    /// The size that the image was asked to be decoded at, once it has been.
+    decode_size: Option<DecodeSize>,
     last_request_round: uint,
-    last_response: ImageResponseMsg
+    last_response: ImageResponseMsg,
+    /// The frames of the image, once they have been requested after the image became ready.
+    animation: Option<Option<Arc<Animation>>>,
+    /// The nodes waiting for the image, if it was not ready when last requested.
+    waiting_nodes: Option<Arc<Mutex<WaitingNodes>>>,
+}
+
+/// The nodes that asked for an image that is still loading. They are handed to the
+/// `ImageResponder` callback once the image is available.
+struct WaitingNodes {
+    nodes: Vec<uint>,
+    /// Whether the image has become available and the nodes have been handed over.
+    notified: bool,
 }
 
 impl LocalImageCache {
@@ -68,19 +87,46 @@ impl LocalImageCache {
     }
 
     pub fn decode(&mut self, url: &Url) {
+        self.decode_to_size(url, FullSize)
+    }
+
+    /// Asks for the image to be decoded at the given size, unless earlier requests already
+    /// cover it.
+    pub fn decode_to_size(&mut self, url: &Url, size: DecodeSize) {
         {
             let state = self.get_state(url);
-            if state.decoded {
+            let decode_size = match state.decode_size {
+                Some(ref decode_size) => decode_size.union(&size),
+                None => size.clone(),
+            };
+            if state.decode_size == Some(decode_size.clone()) {
                 return
             }
-            state.decoded = true;
+            state.decode_size = Some(decode_size);
+
+            // An image that was ready may have too few pixels for the bigger size, so it is asked
+            // for again, and the remote cache hands out the new pixels once they are decoded.
+            match state.last_response {
+                ImageReady(_) => {
+                    state.last_response = ImageNotReady;
+                    state.last_request_round = 0;
+                    state.animation = None;
+                    state.waiting_nodes = None;
+                }
+                ImageNotReady | ImageFailed => {}
+            }
         }
 
-        self.image_cache_task.send(Decode((*url).clone()));
+        match size {
+            FullSize => self.image_cache_task.send(Decode((*url).clone())),
+            FitSize(size) => self.image_cache_task.send(DecodeToSize((*url).clone(), size)),
+        }
     }
 
+    /// Requests the image at `url` for the node with the given opaque address. If the image is
+    /// not ready, the node is reported to the `ImageResponder` once it is.
     // FIXME: Should return a Future
-    pub fn get_image(&mut self, url: &Url) -> Receiver<ImageResponseMsg> {
+    pub fn get_image(&mut self, url: &Url, node_address: uint) -> Receiver<ImageResponseMsg> {
         {
             let round_number = self.round_number;
             let state = self.get_state(url);
@@ -98,9 +144,23 @@ impl LocalImageCache {
                 }
                 ImageNotReady => {
                     if last_round == round_number {
-                        let (chan, port) = channel();
-                        chan.send(ImageNotReady);
-                        return port;
+                        // If the image became available since the remote request, the waiting
+                        // nodes have already been reported; ask the remote cache again instead.
+                        let still_waiting = match state.waiting_nodes {
+                            Some(ref waiting_nodes) => {
+                                let mut waiting_nodes = waiting_nodes.lock();
+                                if !waiting_nodes.notified {
+                                    waiting_nodes.nodes.push(node_address);
+                                }
+                                !waiting_nodes.notified
+                            }
+                            None => true,
+                        };
+                        if still_waiting {
+                            let (chan, port) = channel();
+                            chan.send(ImageNotReady);
+                            return port;
+                        }
                     } else {
                         // We haven't requested the image from the
                         // remote cache this round
@@ -127,12 +187,24 @@ impl LocalImageCache {
                 // on the image to load and triggering layout
                 let image_cache_task = self.image_cache_task.clone();
                 assert!(self.on_image_available.is_some());
-                let on_image_available: proc(ImageResponseMsg):Send = self.on_image_available.as_ref().unwrap().respond();
+                let on_image_available: proc(ImageResponseMsg, Vec<uint>):Send =
+                    self.on_image_available.as_ref().unwrap().respond();
+                let waiting_nodes = Arc::new(Mutex::new(WaitingNodes {
+                    nodes: vec!(node_address),
+                    notified: false,
+                }));
+                self.get_state(url).waiting_nodes = Some(waiting_nodes.clone());
                 let url = (*url).clone();
                 spawn_named("LocalImageCache", proc() {
                     let (response_chan, response_port) = channel();
                     image_cache_task.send(WaitForImage(url.clone(), response_chan));
-                    on_image_available(response_port.recv());
+                    let response = response_port.recv();
+                    let nodes = {
+                        let mut waiting_nodes = waiting_nodes.lock();
+                        waiting_nodes.notified = true;
+                        mem::replace(&mut waiting_nodes.nodes, vec!())
+                    };
+                    on_image_available(response, nodes);
                 });
             }
             _ => ()
@@ -151,13 +223,30 @@ impl LocalImageCache {
         return port;
     }
 
+    /// Returns the frames of an image that `get_image` found ready, or `None` if it is not
+    /// animated. The frames are only requested from the remote cache once.
+    pub fn get_animation(&mut self, url: &Url) -> Option<Arc<Animation>> {
+        match self.get_state(url).animation {
+            Some(ref animation) => return animation.clone(),
+            None => {}
+        }
+
+        let (response_chan, response_port) = channel();
+        self.image_cache_task.send(GetAnimation((*url).clone(), response_chan));
+        let animation = response_port.recv();
+        self.get_state(url).animation = Some(animation.clone());
+        animation
+    }
+
     fn get_state<'a>(&'a mut self, url: &Url) -> &'a mut ImageState {
         let state = self.state_map.find_or_insert_with(url.clone(), |_| {
             let new_state = ImageState {
                 prefetched: false,
-                decoded: false,
+                decode_size: None,
                 last_request_round: 0,
-                last_response: ImageNotReady
+                last_response: ImageNotReady,
+                animation: None,
+                waiting_nodes: None,
             };
             new_state
         });
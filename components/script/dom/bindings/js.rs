@@ -46,6 +46,7 @@
 //! - `RootedReference`: makes obtaining an `Option<JSRef<T>>` from an `Option<Root<T>>` easy
 
 use dom::bindings::utils::{Reflector, Reflectable};
+use dom::htmlimageelement::{HTMLImageElement, TrustedImageAddress};
 use dom::node::Node;
 use dom::xmlhttprequest::{XMLHttpRequest, TrustedXHRAddress};
 use dom::worker::{Worker, TrustedWorkerAddress};
@@ -157,6 +158,15 @@ impl JS<Worker> {
     }
 }
 
+impl JS<HTMLImageElement> {
+    pub unsafe fn from_trusted_image_address(inner: TrustedImageAddress) -> JS<HTMLImageElement> {
+        let TrustedImageAddress(addr) = inner;
+        JS {
+            ptr: addr as *const HTMLImageElement
+        }
+    }
+}
+
 impl<T: Reflectable> JS<T> {
     /// Create a new JS-owned value wrapped from a raw Rust pointer.
     pub unsafe fn from_raw(raw: *const T) -> JS<T> {
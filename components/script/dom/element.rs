@@ -26,12 +26,11 @@ use dom::eventtarget::{EventTarget, NodeTargetTypeId};
 use dom::htmlcollection::HTMLCollection;
 use dom::htmlserializer::serialize;
 use dom::node::{ElementNodeTypeId, Node, NodeHelpers, NodeIterator, document_from_node};
-use dom::node::{window_from_node, LayoutNodeHelpers};
+use dom::node::{window_from_node, LayoutNodeHelpers, NodeStyleDamaged, OtherNodeDamage};
 use dom::nodelist::NodeList;
 use dom::virtualmethods::{VirtualMethods, vtable_for};
-use layout_interface::ContentChangedDocumentDamage;
-use layout_interface::MatchSelectorsDocumentDamage;
 use devtools_traits::AttrInfo;
+use geom::point::Point2D;
 use style::{matches, parse_selector_list_from_str};
 use style;
 use servo_util::namespace;
@@ -415,12 +414,13 @@ impl<'a> AttributeHandlers for JSRef<'a, Element> {
     fn notify_attribute_changed(self, local_name: &Atom) {
         let node: JSRef<Node> = NodeCast::from_ref(self);
         if node.is_in_doc() {
+            // These attributes only feed into selector matching and the cascade.
             let damage = match local_name.as_slice() {
-                "style" | "id" | "class" => MatchSelectorsDocumentDamage,
-                _ => ContentChangedDocumentDamage
+                "class" | "id" | "style" => NodeStyleDamaged,
+                _ => OtherNodeDamage,
             };
             let document = node.owner_doc().root();
-            document.deref().damage_and_reflow(damage);
+            document.deref().content_changed(node, damage);
         }
     }
 
@@ -776,6 +776,32 @@ impl<'a> ElementMethods for JSRef<'a, Element> {
             rect.origin.x + rect.size.width)
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop
+    fn ScrollTop(self) -> f64 {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_scroll_position().y as f64
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop
+    fn SetScrollTop(self, value: f64) {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        let position = node.get_scroll_position();
+        node.scroll_to(Point2D(position.x, value as f32))
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollleft
+    fn ScrollLeft(self) -> f64 {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_scroll_position().x as f64
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollleft
+    fn SetScrollLeft(self, value: f64) {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        let position = node.get_scroll_position();
+        node.scroll_to(Point2D(value as f32, position.y))
+    }
+
     fn GetInnerHTML(self) -> Fallible<DOMString> {
         //XXX TODO: XML case
         Ok(serialize(&mut NodeIterator::new(NodeCast::from_ref(self), false, false)))
@@ -5,31 +5,42 @@
 use dom::attr::AttrValue;
 use dom::bindings::codegen::Bindings::HTMLImageElementBinding;
 use dom::bindings::codegen::Bindings::HTMLImageElementBinding::HTMLImageElementMethods;
-use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, HTMLElementCast, HTMLImageElementDerived};
+use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, EventTargetCast, HTMLElementCast};
+use dom::bindings::codegen::InheritTypes::HTMLImageElementDerived;
+use dom::bindings::global;
 use dom::bindings::js::{JS, JSRef, Temporary};
 use dom::bindings::trace::Untraceable;
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::document::Document;
 use dom::element::{Element, HTMLImageElementTypeId};
 use dom::element::AttributeHandlers;
-use dom::eventtarget::{EventTarget, NodeTargetTypeId};
+use dom::event::Event;
+use dom::eventtarget::{EventTarget, EventTargetHelpers, NodeTargetTypeId};
 use dom::htmlelement::HTMLElement;
 use dom::node::{Node, ElementNodeTypeId, NodeHelpers, window_from_node};
 use dom::virtualmethods::VirtualMethods;
+use geom::size::Size2D;
+use script_task::{ImageLoadMsg, ScriptChan};
 use servo_net::image_cache_task;
+use servo_net::image_cache_task::{ImageFailed, ImageNotReady, ImageReady, ImageResponseMsg};
 use servo_util::geometry::to_px;
 use servo_util::str::DOMString;
 use string_cache::Atom;
 
+use js::jsapi::{JS_AddObjectRoot, JS_RemoveObjectRoot};
+use libc::c_void;
 use url::{Url, UrlParser};
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 #[jstraceable]
 #[must_root]
 pub struct HTMLImageElement {
     pub htmlelement: HTMLElement,
     image: Untraceable<RefCell<Option<Url>>>,
+    /// The number of image loads that will report back to this element, which is kept alive
+    /// until they do.
+    pinned_count: Untraceable<Cell<uint>>,
 }
 
 impl HTMLImageElementDerived for EventTarget {
@@ -38,8 +49,15 @@ impl HTMLImageElementDerived for EventTarget {
     }
 }
 
+/// The address of an image element that is waiting for its image to load.
+pub struct TrustedImageAddress(pub *const c_void);
+
 trait PrivateHTMLImageElementHelpers {
     fn update_image(self, value: Option<(DOMString, &Url)>);
+    fn decode_message(self, url: Url) -> image_cache_task::Msg;
+    unsafe fn to_trusted(self) -> TrustedImageAddress;
+    fn release_once(self);
+    fn fire_simple_event(self, type_: &str);
 }
 
 impl<'a> PrivateHTMLImageElementHelpers for JSRef<'a, HTMLImageElement> {
@@ -65,10 +83,70 @@ impl<'a> PrivateHTMLImageElementHelpers for JSRef<'a, HTMLImageElement> {
                 //
                 // TODO (Issue #84): don't prefetch if we are within a
                 // <noscript> tag.
-                image_cache.send(image_cache_task::Prefetch(img_url));
+                image_cache.send(image_cache_task::Prefetch(img_url.clone()));
+                image_cache.send(self.decode_message(img_url.clone()));
+
+                // Fire a load or error event once the image is decoded or has failed. The
+                // cache calls back on its own task, so the response is forwarded to this one.
+                let address = unsafe { self.to_trusted() };
+                let ScriptChan(script_chan) = window.script_chan.clone();
+                let callback_url = img_url.clone();
+                image_cache.send(image_cache_task::WaitForImageCallback(img_url, proc(response) {
+                    drop(script_chan.send_opt(ImageLoadMsg(address, callback_url, response)))
+                }));
+            }
+        }
+    }
+
+    /// Returns the message that asks for the image to be decoded. If the `width` and `height`
+    /// attributes give the size of the image, it only keeps enough pixels for that size, unless
+    /// layout asks for more before decoding starts.
+    fn decode_message(self, url: Url) -> image_cache_task::Msg {
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        if !element.has_attribute("width") || !element.has_attribute("height") {
+            return image_cache_task::Decode(url)
+        }
+        let window = window_from_node(self).root();
+        let device_pixels_per_px = window.page().window_size.deref().get().device_pixel_ratio.get();
+        let to_device_pixels = |length: u32| {
+            (length as f32 * device_pixels_per_px).ceil() as u32
+        };
+        let size = Size2D(to_device_pixels(element.get_uint_attribute("width")),
+                          to_device_pixels(element.get_uint_attribute("height")));
+        image_cache_task::DecodeToSize(url, size)
+    }
+
+    // Creates a trusted address to the object, and roots it. Always pair this with a
+    // release_once().
+    unsafe fn to_trusted(self) -> TrustedImageAddress {
+        let pinned_count = self.pinned_count.deref().get();
+        if pinned_count == 0 {
+            let window = window_from_node(self).root();
+            JS_AddObjectRoot(window.get_cx(), self.reflector().rootable());
+        }
+        self.pinned_count.deref().set(pinned_count + 1);
+        TrustedImageAddress(self.deref() as *const HTMLImageElement as *const c_void)
+    }
+
+    fn release_once(self) {
+        let pinned_count = self.pinned_count.deref().get();
+        assert!(pinned_count > 0)
+        self.pinned_count.deref().set(pinned_count - 1);
+        if pinned_count == 1 {
+            let window = window_from_node(self).root();
+            unsafe {
+                JS_RemoveObjectRoot(window.get_cx(), self.reflector().rootable());
             }
         }
     }
+
+    /// Fires an event that does not bubble and is not cancelable at the element.
+    fn fire_simple_event(self, type_: &str) {
+        let window = window_from_node(self).root();
+        let event = Event::new(&global::Window(*window), type_.to_string(), false, false).root();
+        let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+        let _ = target.dispatch_event_with_target(None, *event);
+    }
 }
 
 impl HTMLImageElement {
@@ -76,6 +154,7 @@ impl HTMLImageElement {
         HTMLImageElement {
             htmlelement: HTMLElement::new_inherited(HTMLImageElementTypeId, localName, document),
             image: Untraceable::new(RefCell::new(None)),
+            pinned_count: Untraceable::new(Cell::new(0)),
         }
     }
 
@@ -84,6 +163,23 @@ impl HTMLImageElement {
         let element = HTMLImageElement::new_inherited(localName, document);
         Node::reflect_node(box element, document, HTMLImageElementBinding::Wrap)
     }
+
+    /// Fires a load or error event at an image element once its image is decoded or has failed,
+    /// unless its source has changed since, and then stops keeping the element alive.
+    pub fn handle_image_response(address: TrustedImageAddress,
+                                 url: Url,
+                                 response: ImageResponseMsg) {
+        let element = unsafe { JS::from_trusted_image_address(address).root() };
+        let element = element.deref();
+        if *element.image.deref().borrow() == Some(url) {
+            match response {
+                ImageReady(_) => element.fire_simple_event("load"),
+                ImageFailed => element.fire_simple_event("error"),
+                ImageNotReady => {}
+            }
+        }
+        element.release_once();
+    }
 }
 
 pub trait LayoutHTMLImageElementHelpers {
@@ -44,11 +44,14 @@ use dom::processinginstruction::ProcessingInstruction;
 use dom::text::Text;
 use dom::virtualmethods::{VirtualMethods, vtable_for};
 use dom::window::Window;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use html::hubbub_html_parser::build_element_from_tag;
 use layout_interface::{ContentBoxResponse, ContentBoxesResponse, LayoutRPC,
                        LayoutChan, ReapLayoutDataMsg, TrustedNodeAddress, UntrustedNodeAddress};
+use layout_interface::ScrollRangeResponse;
 use devtools_traits::NodeInfo;
+use servo_msg::compositor_msg::LayerId;
 use servo_util::geometry::Au;
 use servo_util::str::{DOMString, null_str_as_empty};
 use style::{parse_selector_list_from_str, matches};
@@ -57,7 +60,7 @@ use js::jsapi::{JSContext, JSObject, JSRuntime};
 use js::jsfriendapi;
 use libc;
 use libc::uintptr_t;
-use std::cell::{RefCell, Ref, RefMut};
+use std::cell::{Cell, RefCell, Ref, RefMut};
 use std::default::Default;
 use std::iter::{Map, Filter};
 use std::mem;
@@ -102,7 +105,7 @@ pub struct Node {
     child_list: MutNullableJS<NodeList>,
 
     /// A bitfield of flags for node items.
-    flags: Traceable<RefCell<NodeFlags>>,
+    flags: Traceable<Cell<NodeFlags>>,
 
     /// Layout information. Only the layout task may touch this data.
     ///
@@ -133,14 +136,34 @@ bitflags! {
         #[doc = "Specifies whether this node is in disabled state."]
         static InDisabledState = 0x04,
         #[doc = "Specifies whether this node is in enabled state."]
-        static InEnabledState = 0x08
+        static InEnabledState = 0x08,
+        #[doc = "Specifies whether this node must be restyled and have its flows rebuilt by the"]
+        #[doc = "next reflow."]
+        static IsDirty = 0x10,
+        #[doc = "Specifies whether any descendant of this node is dirty."]
+        static HasDirtyDescendants = 0x20,
+        #[doc = "Specifies whether this node has changed in some way other than its style since"]
+        #[doc = "the last reflow, so that layout can't keep the flows it built for it."]
+        static HasChanged = 0x40
     }
 }
 
+/// The ways in which a node can have changed since the last reflow.
+#[deriving(PartialEq)]
+pub enum NodeDamage {
+    /// Only the style of the node may have changed, such as after a change to its `class`
+    /// attribute. Layout may be able to keep its flows.
+    NodeStyleDamaged,
+    /// The node may have changed in any other way, such as its children or its text.
+    OtherNodeDamage,
+}
+
 impl NodeFlags {
     pub fn new(type_id: NodeTypeId) -> NodeFlags {
+        // New nodes have never been laid out, so they start out dirty.
+        let dirty = IsDirty | HasChanged;
         match type_id {
-            DocumentNodeTypeId => IsInDoc,
+            DocumentNodeTypeId => IsInDoc | dirty,
             // The following elements are enabled by default.
             ElementNodeTypeId(HTMLButtonElementTypeId) |
             ElementNodeTypeId(HTMLInputElementTypeId) |
@@ -149,8 +172,8 @@ impl NodeFlags {
             ElementNodeTypeId(HTMLOptGroupElementTypeId) |
             ElementNodeTypeId(HTMLOptionElementTypeId) |
             //ElementNodeTypeId(HTMLMenuItemElementTypeId) |
-            ElementNodeTypeId(HTMLFieldSetElementTypeId) => InEnabledState,
-            _ => NodeFlags::empty(),
+            ElementNodeTypeId(HTMLFieldSetElementTypeId) => InEnabledState | dirty,
+            _ => dirty,
         }
     }
 }
@@ -270,7 +293,7 @@ impl<'a> PrivateNodeHelpers for JSRef<'a, Node> {
         let parent = self.parent_node().root();
         parent.map(|parent| vtable_for(&*parent).child_inserted(self));
 
-        document.deref().content_changed();
+        document.deref().content_changed(self, OtherNodeDamage);
     }
 
     // http://dom.spec.whatwg.org/#node-is-removed
@@ -282,7 +305,7 @@ impl<'a> PrivateNodeHelpers for JSRef<'a, Node> {
             vtable_for(&node).unbind_from_tree(parent_in_doc);
         }
 
-        document.deref().content_changed();
+        document.deref().content_changed(self, OtherNodeDamage);
     }
 
     //
@@ -403,6 +426,17 @@ pub trait NodeHelpers<'a> {
     fn get_enabled_state(self) -> bool;
     fn set_enabled_state(self, state: bool);
 
+    fn get_is_dirty(self) -> bool;
+    fn set_is_dirty(self, state: bool);
+
+    fn get_has_dirty_descendants(self) -> bool;
+    fn set_has_dirty_descendants(self, state: bool);
+
+    fn get_has_changed(self) -> bool;
+    fn set_has_changed(self, state: bool);
+
+    fn dirty(self, damage: NodeDamage);
+
     fn dump(self);
     fn dump_indent(self, indent: uint);
     fn debug_str(self) -> String;
@@ -416,6 +450,9 @@ pub trait NodeHelpers<'a> {
     fn get_bounding_content_box(self) -> Rect<Au>;
     fn get_content_boxes(self) -> Vec<Rect<Au>>;
 
+    fn get_scroll_position(self) -> Point2D<f32>;
+    fn scroll_to(self, position: Point2D<f32>);
+
     fn query_selector(self, selectors: DOMString) -> Fallible<Option<Temporary<Element>>>;
     fn query_selector_all(self, selectors: DOMString) -> Fallible<Temporary<NodeList>>;
 
@@ -425,6 +462,12 @@ pub trait NodeHelpers<'a> {
     fn summarize(self) -> NodeInfo;
 }
 
+/// Returns the ID of the layer that layout creates to clip and scroll the overflow of the given
+/// node.
+fn scroll_root_layer_id(node: JSRef<Node>) -> LayerId {
+    LayerId::scroll_root(node.reflector().get_jsobject() as uint)
+}
+
 impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
     /// Dumps the subtree rooted at this node, for debugging.
     fn dump(self) {
@@ -453,7 +496,7 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
     }
 
     fn is_in_doc(self) -> bool {
-        self.deref().flags.deref().borrow().contains(IsInDoc)
+        self.deref().flags.deref().get().contains(IsInDoc)
     }
 
     /// Returns the type ID of this node. Fails if this node is borrowed mutably.
@@ -512,38 +555,106 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
     }
 
     fn get_hover_state(self) -> bool {
-        self.flags.deref().borrow().contains(InHoverState)
+        self.flags.deref().get().contains(InHoverState)
     }
 
     fn set_hover_state(self, state: bool) {
+        if state == self.get_hover_state() {
+            return
+        }
         if state {
-            self.flags.deref().borrow_mut().insert(InHoverState);
+            self.insert_flags(InHoverState);
         } else {
-            self.flags.deref().borrow_mut().remove(InHoverState);
+            self.remove_flags(InHoverState);
         }
+        self.dirty(NodeStyleDamaged);
     }
 
     fn get_disabled_state(self) -> bool {
-        self.flags.deref().borrow().contains(InDisabledState)
+        self.flags.deref().get().contains(InDisabledState)
     }
 
     fn set_disabled_state(self, state: bool) {
+        if state == self.get_disabled_state() {
+            return
+        }
         if state {
-            self.flags.deref().borrow_mut().insert(InDisabledState);
+            self.insert_flags(InDisabledState);
         } else {
-            self.flags.deref().borrow_mut().remove(InDisabledState);
+            self.remove_flags(InDisabledState);
         }
+        self.dirty(NodeStyleDamaged);
     }
 
     fn get_enabled_state(self) -> bool {
-        self.flags.deref().borrow().contains(InEnabledState)
+        self.flags.deref().get().contains(InEnabledState)
     }
 
     fn set_enabled_state(self, state: bool) {
+        if state == self.get_enabled_state() {
+            return
+        }
         if state {
-            self.flags.deref().borrow_mut().insert(InEnabledState);
+            self.insert_flags(InEnabledState);
         } else {
-            self.flags.deref().borrow_mut().remove(InEnabledState);
+            self.remove_flags(InEnabledState);
+        }
+        self.dirty(NodeStyleDamaged);
+    }
+
+    fn get_is_dirty(self) -> bool {
+        self.flags.deref().get().contains(IsDirty)
+    }
+
+    fn set_is_dirty(self, state: bool) {
+        if state {
+            self.insert_flags(IsDirty);
+        } else {
+            self.remove_flags(IsDirty);
+        }
+    }
+
+    fn get_has_dirty_descendants(self) -> bool {
+        self.flags.deref().get().contains(HasDirtyDescendants)
+    }
+
+    fn set_has_dirty_descendants(self, state: bool) {
+        if state {
+            self.insert_flags(HasDirtyDescendants);
+        } else {
+            self.remove_flags(HasDirtyDescendants);
+        }
+    }
+
+    fn get_has_changed(self) -> bool {
+        self.flags.deref().get().contains(HasChanged)
+    }
+
+    fn set_has_changed(self, state: bool) {
+        if state {
+            self.insert_flags(HasChanged);
+        } else {
+            self.remove_flags(HasChanged);
+        }
+    }
+
+    /// Marks this node as needing to be restyled by the next reflow, and lets its ancestors know
+    /// about it. Layout takes care of restyling the descendants if this node's style turns out to
+    /// have changed, and the siblings if the style sheets have selectors that make them depend on
+    /// this node. It rebuilds the flows of the node unless `damage` says only its style may have
+    /// changed and the new style allows the old flows to be kept.
+    fn dirty(self, damage: NodeDamage) {
+        self.set_is_dirty(true);
+        if damage == OtherNodeDamage {
+            self.set_has_changed(true);
+        }
+
+        // An ancestor that already knows about dirty descendants has ancestors that do too.
+        for ancestor in self.ancestors() {
+            if ancestor.get_has_dirty_descendants() {
+                break
+            }
+            ancestor.set_has_dirty_descendants(true);
         }
     }
 
@@ -605,6 +716,41 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
         rects
     }
 
+    /// Returns the scroll position of this node's scroll root layer, or the origin if this node
+    /// does not scroll its overflow.
+    fn get_scroll_position(self) -> Point2D<f32> {
+        let window = window_from_node(self).root();
+        let page = window.deref().page();
+        let ScrollRangeResponse(range) = page.layout().scroll_range(self.to_trusted_node_address());
+        let scroll_positions = page.scroll_positions.deref().borrow();
+        match (range, scroll_positions.find(&scroll_root_layer_id(self))) {
+            // The contents may have shrunk since they were scrolled.
+            (Some(range), Some(position)) => {
+                Point2D(position.x.min(range.width), position.y.min(range.height))
+            }
+            _ => Point2D(0f32, 0f32),
+        }
+    }
+
+    /// Scrolls this node's scroll root layer to the given position, clamped to the scrollable
+    /// area, and asks the compositor to follow. Does nothing if this node does not scroll its
+    /// overflow.
+    fn scroll_to(self, position: Point2D<f32>) {
+        let window = window_from_node(self).root();
+        let page = window.deref().page();
+        let ScrollRangeResponse(range) = page.layout().scroll_range(self.to_trusted_node_address());
+        let range = match range {
+            Some(range) => range,
+            None => return,
+        };
+
+        let position = Point2D(position.x.max(0f32).min(range.width),
+                               position.y.max(0f32).min(range.height));
+        let layer_id = scroll_root_layer_id(self);
+        page.scroll_positions.deref().borrow_mut().insert(layer_id, position);
+        window.compositor.scroll_fragment_point(page.id, layer_id, position);
+    }
+
     // http://dom.spec.whatwg.org/#dom-parentnode-queryselector
     fn query_selector(self, selectors: DOMString) -> Fallible<Option<Temporary<Element>>> {
         // Step 1.
@@ -739,15 +885,26 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
 /// returns it.
 pub fn from_untrusted_node_address(runtime: *mut JSRuntime, candidate: UntrustedNodeAddress)
     -> Temporary<Node> {
+    match from_untrusted_node_address_if_valid(runtime, candidate) {
+        Some(node) => node,
+        None => fail!("Attempted to create a `JS<Node>` from an invalid pointer!"),
+    }
+}
+
+/// Like `from_untrusted_node_address`, but returns `None` instead of failing if the address does
+/// not represent a valid DOM node.
+pub fn from_untrusted_node_address_if_valid(runtime: *mut JSRuntime,
+                                            candidate: UntrustedNodeAddress)
+                                            -> Option<Temporary<Node>> {
     unsafe {
         let candidate: uintptr_t = mem::transmute(candidate);
         let object: *mut JSObject = jsfriendapi::bindgen::JS_GetAddressableObject(runtime,
                                                                                   candidate);
         if object.is_null() {
-            fail!("Attempted to create a `JS<Node>` from an invalid pointer!")
+            return None
         }
         let boxed_node: *const Node = utils::unwrap(object);
-        Temporary::new(JS::from_raw(boxed_node))
+        Some(Temporary::new(JS::from_raw(boxed_node)))
     }
 }
 
@@ -811,18 +968,42 @@ pub trait RawLayoutNodeHelpers {
     unsafe fn get_hover_state_for_layout(&self) -> bool;
     unsafe fn get_disabled_state_for_layout(&self) -> bool;
     unsafe fn get_enabled_state_for_layout(&self) -> bool;
+    unsafe fn get_is_dirty_for_layout(&self) -> bool;
+    unsafe fn get_has_dirty_descendants_for_layout(&self) -> bool;
+    unsafe fn get_has_changed_for_layout(&self) -> bool;
+    unsafe fn set_is_dirty_for_layout(&self, state: bool);
+    unsafe fn clear_dirty_bits_for_layout(&self);
     fn type_id_for_layout(&self) -> NodeTypeId;
 }
 
 impl RawLayoutNodeHelpers for Node {
     unsafe fn get_hover_state_for_layout(&self) -> bool {
-        (*self.unsafe_get_flags()).contains(InHoverState)
+        self.flags.deref().get().contains(InHoverState)
     }
     unsafe fn get_disabled_state_for_layout(&self) -> bool {
-        (*self.unsafe_get_flags()).contains(InDisabledState)
+        self.flags.deref().get().contains(InDisabledState)
     }
     unsafe fn get_enabled_state_for_layout(&self) -> bool {
-        (*self.unsafe_get_flags()).contains(InEnabledState)
+        self.flags.deref().get().contains(InEnabledState)
+    }
+    unsafe fn get_is_dirty_for_layout(&self) -> bool {
+        self.flags.deref().get().contains(IsDirty)
+    }
+    unsafe fn get_has_dirty_descendants_for_layout(&self) -> bool {
+        self.flags.deref().get().contains(HasDirtyDescendants)
+    }
+    unsafe fn get_has_changed_for_layout(&self) -> bool {
+        self.flags.deref().get().contains(HasChanged)
+    }
+    unsafe fn set_is_dirty_for_layout(&self, state: bool) {
+        if state {
+            self.insert_flags(IsDirty)
+        } else {
+            self.remove_flags(IsDirty)
+        }
+    }
+    unsafe fn clear_dirty_bits_for_layout(&self) {
+        self.remove_flags(IsDirty | HasDirtyDescendants | HasChanged)
     }
 
     fn type_id_for_layout(&self) -> NodeTypeId {
@@ -1034,7 +1215,7 @@ impl Node {
             owner_doc: MutNullableJS::new(doc),
             child_list: Default::default(),
 
-            flags: Traceable::new(RefCell::new(NodeFlags::new(type_id))),
+            flags: Traceable::new(Cell::new(NodeFlags::new(type_id))),
 
             layout_data: Untraceable::new(LayoutDataRef::new()),
 
@@ -1236,9 +1417,9 @@ impl Node {
             let is_in_doc = parent.is_in_doc();
             for kid in node.traverse_preorder() {
                 if is_in_doc {
-                    kid.flags.deref().borrow_mut().insert(IsInDoc);
+                    kid.insert_flags(IsInDoc);
                 } else {
-                    kid.flags.deref().borrow_mut().remove(IsInDoc);
+                    kid.remove_flags(IsInDoc);
                 }
             }
         }
@@ -1323,9 +1504,16 @@ impl Node {
         // Step 1-5: ranges.
         // Step 6-7: mutation observers.
         // Step 8.
+        //
+        // Dirty the node while it still has its parent, so that layout knows to revisit its
+        // ancestors. The flows of the parent contain those of the node, so they can't be kept, and
+        // the siblings may have to be restyled; see
+        // `MatchMethods::dirty_kids_affected_by_siblings`.
+        node.dirty(NodeStyleDamaged);
+        parent.set_has_changed(true);
         parent.remove_child(node);
 
-        node.deref().flags.deref().borrow_mut().remove(IsInDoc);
+        node.deref().remove_flags(IsInDoc);
 
         // Step 9.
         match suppress_observers {
@@ -1461,8 +1649,19 @@ impl Node {
         }
     }
 
-    pub unsafe fn unsafe_get_flags(&self) -> *const NodeFlags {
-        mem::transmute(&self.flags)
+    /// Sets the given flags on this node. Layout may only use this to set and clear the dirty
+    /// bits, while the script task is waiting for it to finish.
+    fn insert_flags(&self, flags: NodeFlags) {
+        let mut new_flags = self.flags.deref().get();
+        new_flags.insert(flags);
+        self.flags.deref().set(new_flags);
+    }
+
+    /// Clears the given flags on this node. See `insert_flags`.
+    fn remove_flags(&self, flags: NodeFlags) {
+        let mut new_flags = self.flags.deref().get();
+        new_flags.remove(flags);
+        self.flags.deref().set(new_flags);
     }
 
     pub fn collect_text_contents<'a, T: Iterator<JSRef<'a, Node>>>(mut iterator: T) -> String {
@@ -1664,7 +1863,7 @@ impl<'a> NodeMethods for JSRef<'a, Node> {
 
                 // Notify the document that the content of this node is different
                 let document = self.owner_doc().root();
-                document.deref().content_changed();
+                document.deref().content_changed(self, OtherNodeDamage);
             }
             DoctypeNodeTypeId |
             DocumentNodeTypeId => {}
@@ -12,6 +12,7 @@ use dom::node::{Node, LayoutDataRef};
 
 use geom::point::Point2D;
 use geom::rect::Rect;
+use geom::size::Size2D;
 use js::jsapi::JSTracer;
 use libc::c_void;
 use script_traits::{ScriptControlChan, OpaqueScriptLayoutChannel};
@@ -66,6 +67,11 @@ pub trait LayoutRPC {
     fn content_box(&self, node: TrustedNodeAddress) -> ContentBoxResponse;
     /// Requests the dimensions of all the content boxes, as in the `getClientRects()` call.
     fn content_boxes(&self, node: TrustedNodeAddress) -> ContentBoxesResponse;
+    /// Requests the display lists of the page and its layers, serialized as JSON.
+    fn display_list(&self) -> DisplayListResponse;
+    /// Requests how far the contents of the given node can be scrolled, as in the `scrollTop`
+    /// and `scrollLeft` setters. Returns `None` if the node doesn't scroll its overflow.
+    fn scroll_range(&self, node: TrustedNodeAddress) -> ScrollRangeResponse;
     /// Requests the node containing the point of interest
     fn hit_test(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()>;
     fn mouse_over(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<MouseOverResponse, ()>;
@@ -91,8 +97,10 @@ pub type UntrustedNodeAddress = *const c_void;
 
 pub struct ContentBoxResponse(pub Rect<Au>);
 pub struct ContentBoxesResponse(pub Vec<Rect<Au>>);
+pub struct DisplayListResponse(pub String);
 pub struct HitTestResponse(pub UntrustedNodeAddress);
 pub struct MouseOverResponse(pub Vec<UntrustedNodeAddress>);
+pub struct ScrollRangeResponse(pub Option<Size2D<f32>>);
 
 /// Determines which part of the
 #[deriving(PartialEq, PartialOrd, Eq, Ord)]
@@ -100,9 +108,10 @@ pub struct MouseOverResponse(pub Vec<UntrustedNodeAddress>);
 pub enum DocumentDamageLevel {
     /// Reflow, but do not perform CSS selector matching.
     ReflowDocumentDamage,
-    /// Perform CSS selector matching and reflow.
+    /// Perform CSS selector matching on the dirty nodes, rebuild their flows, and reflow.
     MatchSelectorsDocumentDamage,
-    /// Content changed; set full style damage and do the above.
+    /// Something changed that the dirty bits on nodes don't capture; restyle every node, rebuild
+    /// every flow, and reflow.
     ContentChangedDocumentDamage,
 }
 
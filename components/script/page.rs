@@ -20,13 +20,14 @@ use script_traits::ScriptControlChan;
 
 use geom::point::Point2D;
 use js::rust::Cx;
-use servo_msg::compositor_msg::PerformingLayout;
+use servo_msg::compositor_msg::{LayerId, PerformingLayout};
 use servo_msg::compositor_msg::ScriptListener;
 use servo_msg::constellation_msg::{ConstellationChan, WindowSizeData};
 use servo_msg::constellation_msg::{PipelineId, SubpageId};
 use servo_net::resource_task::ResourceTask;
 use servo_util::str::DOMString;
 use std::cell::{Cell, RefCell, Ref, RefMut};
+use std::collections::hashmap::HashMap;
 use std::comm::{channel, Receiver, Empty, Disconnected};
 use std::mem::replace;
 use std::rc::Rc;
@@ -78,6 +79,10 @@ pub struct Page {
     /// Pending scroll to fragment event, if any
     pub fragment_name: RefCell<Option<String>>,
 
+    /// The most recent scroll positions reported by the compositor for each scroll root layer
+    /// in this page.
+    pub scroll_positions: Untraceable<RefCell<HashMap<LayerId, Point2D<f32>>>>,
+
     /// Associated resource task for use by DOM objects like XMLHttpRequest
     pub resource_task: Untraceable<ResourceTask>,
 
@@ -90,6 +95,9 @@ pub struct Page {
     /// Number of pending reflows that were sent while layout was active.
     pub pending_reflows: Cell<int>,
 
+    /// The opaque addresses of the nodes whose images finished loading since the last reflow.
+    pub pending_image_nodes: Untraceable<RefCell<Vec<uint>>>,
+
     /// Number of unnecessary potential reflows that were skipped since the last reflow
     pub avoided_reflows: Cell<int>,
 }
@@ -151,11 +159,13 @@ impl Page {
             next_subpage_id: Traceable::new(Cell::new(SubpageId(0))),
             resize_event: Untraceable::new(Cell::new(None)),
             fragment_name: RefCell::new(None),
+            scroll_positions: Untraceable::new(RefCell::new(HashMap::new())),
             last_reflow_id: Traceable::new(Cell::new(0)),
             resource_task: Untraceable::new(resource_task),
             constellation_chan: Untraceable::new(constellation_chan),
             children: Traceable::new(RefCell::new(vec!())),
             pending_reflows: Cell::new(0),
+            pending_image_nodes: Untraceable::new(RefCell::new(vec!())),
             avoided_reflows: Cell::new(0),
         }
     }
@@ -22,8 +22,9 @@ use dom::element::{HTMLSelectElementTypeId, HTMLTextAreaElementTypeId, HTMLOptio
 use dom::event::Event;
 use dom::uievent::UIEvent;
 use dom::eventtarget::{EventTarget, EventTargetHelpers};
+use dom::htmlimageelement::{HTMLImageElement, TrustedImageAddress};
 use dom::node;
-use dom::node::{ElementNodeTypeId, Node, NodeHelpers};
+use dom::node::{ElementNodeTypeId, Node, NodeHelpers, OtherNodeDamage};
 use dom::window::{TimerId, Window, WindowHelpers};
 use dom::worker::{Worker, TrustedWorkerAddress};
 use dom::xmlhttprequest::{TrustedXHRAddress, XMLHttpRequest, XHRProgress};
@@ -31,25 +32,26 @@ use html::hubbub_html_parser::{InputString, InputUrl, HtmlParserResult, HtmlDisc
 use html::hubbub_html_parser;
 use layout_interface::{ScriptLayoutChan, LayoutChan, MatchSelectorsDocumentDamage};
 use layout_interface::{ReflowDocumentDamage, ReflowForDisplay};
-use layout_interface::ContentChangedDocumentDamage;
+use layout_interface::{ContentChangedDocumentDamage, DisplayListResponse};
+use layout_interface::UntrustedNodeAddress;
 use layout_interface;
 use page::{Page, IterablePage, Frame};
 
 use devtools_traits;
 use devtools_traits::{DevtoolsControlChan, DevtoolsControlPort, NewGlobal, NodeInfo, GetRootNode};
 use devtools_traits::{DevtoolScriptControlMsg, EvaluateJS, EvaluateJSReply, GetDocumentElement};
-use devtools_traits::{GetChildren, GetLayout};
+use devtools_traits::{GetChildren, GetDisplayList, GetLayout};
 use script_traits::{CompositorEvent, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent};
 use script_traits::{MouseMoveEvent, MouseUpEvent, ConstellationControlMsg, ScriptTaskFactory};
 use script_traits::{ResizeMsg, AttachLayoutMsg, LoadMsg, SendEventMsg, ResizeInactiveMsg};
 use script_traits::{ExitPipelineMsg, NewLayoutInfo, OpaqueScriptLayoutChannel, ScriptControlChan};
-use script_traits::ReflowCompleteMsg;
+use script_traits::{ReflowCompleteMsg, ScrollEvent};
 use servo_msg::compositor_msg::{FinishedLoading, LayerId, Loading};
 use servo_msg::compositor_msg::{ScriptListener};
 use servo_msg::constellation_msg::{ConstellationChan, LoadCompleteMsg, LoadUrlMsg, NavigationDirection};
 use servo_msg::constellation_msg::{PipelineId, Failure, FailureMsg, WindowSizeData};
 use servo_msg::constellation_msg;
-use servo_net::image_cache_task::ImageCacheTask;
+use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::resource_task::ResourceTask;
 use servo_util::geometry::to_frac_px;
 use servo_util::task::spawn_named_with_send_on_failure;
@@ -68,6 +70,7 @@ use std::any::{Any, AnyRefExt};
 use std::cell::RefCell;
 use std::comm::{channel, Sender, Receiver, Select};
 use std::mem::replace;
+use std::mem;
 use std::rc::Rc;
 use std::u32;
 
@@ -99,6 +102,9 @@ pub enum ScriptMsg {
     WorkerPostMessage(TrustedWorkerAddress, *mut u64, size_t),
     /// Releases one reference to the Worker object (dispatched to all tasks).
     WorkerRelease(TrustedWorkerAddress),
+    /// Notifies an image element that its image was decoded or failed to load (only dispatched
+    /// to ScriptTask).
+    ImageLoadMsg(TrustedImageAddress, Url, ImageResponseMsg),
 }
 
 /// Encapsulates internal communication within the script task.
@@ -499,11 +505,14 @@ impl ScriptTask {
                 FromScript(DOMMessage(..)) => fail!("unexpected message"),
                 FromScript(WorkerPostMessage(addr, data, nbytes)) => Worker::handle_message(addr, data, nbytes),
                 FromScript(WorkerRelease(addr)) => Worker::handle_release(addr),
+                FromScript(ImageLoadMsg(addr, url, response)) =>
+                    HTMLImageElement::handle_image_response(addr, url, response),
                 FromDevtools(EvaluateJS(id, s, reply)) => self.handle_evaluate_js(id, s, reply),
                 FromDevtools(GetRootNode(id, reply)) => self.handle_get_root_node(id, reply),
                 FromDevtools(GetDocumentElement(id, reply)) => self.handle_get_document_element(id, reply),
                 FromDevtools(GetChildren(id, node_id, reply)) => self.handle_get_children(id, node_id, reply),
                 FromDevtools(GetLayout(id, node_id, reply)) => self.handle_get_layout(id, node_id, reply),
+                FromDevtools(GetDisplayList(id, reply)) => self.handle_get_display_list(id, reply),
             }
         }
 
@@ -580,6 +589,12 @@ impl ScriptTask {
         reply.send((rect.Width(), rect.Height()));
     }
 
+    fn handle_get_display_list(&self, pipeline: PipelineId, reply: Sender<String>) {
+        let page = get_page(&*self.page.borrow(), pipeline);
+        let DisplayListResponse(display_list) = page.layout().display_list();
+        reply.send(display_list);
+    }
+
     fn handle_new_layout(&self, new_layout_info: NewLayoutInfo) {
         debug!("Script: new layout: {:?}", new_layout_info);
         let NewLayoutInfo {
@@ -632,11 +647,33 @@ impl ScriptTask {
 
         if page.pending_reflows.get() > 0 {
             page.pending_reflows.set(0);
-            page.damage(MatchSelectorsDocumentDamage);
-            page.reflow(ReflowForDisplay, self.control_chan.clone(), &*self.compositor);
+            self.reflow_for_loaded_images(&*page);
         }
     }
 
+    /// Restyles the nodes that display images that finished loading, so that layout only rebuilds
+    /// their flows, and reflows.
+    fn reflow_for_loaded_images(&self, page: &Page) {
+        // The nodes must not be marked dirty while layout may be reading their flags.
+        page.join_layout();
+
+        let node_addresses = replace(&mut *page.pending_image_nodes.deref().borrow_mut(), vec!());
+        for node_address in node_addresses.into_iter() {
+            let node_address: UntrustedNodeAddress = unsafe {
+                mem::transmute(node_address)
+            };
+            // The node may have been removed and collected since layout asked for its image.
+            match node::from_untrusted_node_address_if_valid(self.js_runtime.deref().ptr,
+                                                             node_address) {
+                Some(node) => node.root().dirty(OtherNodeDamage),
+                None => {}
+            }
+        }
+
+        page.damage(MatchSelectorsDocumentDamage);
+        page.reflow(ReflowForDisplay, self.control_chan.clone(), &*self.compositor);
+    }
+
     /// Handles a navigate forward or backward message.
     /// TODO(tkuehn): is it ever possible to navigate only on a subframe?
     fn handle_navigate_msg(&self, direction: NavigationDirection) {
@@ -796,7 +833,7 @@ impl ScriptTask {
 
         // Kick off the initial reflow of the page.
         debug!("kicking off initial reflow of {}", url);
-        document.deref().content_changed();
+        document.deref().damage_and_reflow(ContentChangedDocumentDamage);
         window.flush_layout(ReflowForDisplay);
 
         {
@@ -905,18 +942,17 @@ impl ScriptTask {
                 }
             }
 
-            // FIXME(pcwalton): This reflows the entire document and is not incremental-y.
-            ReflowEvent => {
+            ReflowEvent(node_addresses) => {
                 debug!("script got reflow event");
                 let page = get_page(&*self.page.borrow(), pipeline_id);
                 let frame = page.frame();
                 if frame.is_some() {
+                    page.pending_image_nodes.deref().borrow_mut().push_all_move(node_addresses);
                     let in_layout = page.layout_join_port.deref().borrow().is_some();
                     if in_layout {
                         page.pending_reflows.set(page.pending_reflows.get() + 1);
                     } else {
-                        page.damage(MatchSelectorsDocumentDamage);
-                        page.reflow(ReflowForDisplay, self.control_chan.clone(), &*self.compositor)
+                        self.reflow_for_loaded_images(&*page)
                     }
                 }
             }
@@ -967,6 +1003,10 @@ impl ScriptTask {
             }
             MouseDownEvent(..) => {}
             MouseUpEvent(..) => {}
+            ScrollEvent(layer_id, position) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                page.scroll_positions.deref().borrow_mut().insert(layer_id, position);
+            }
             MouseMoveEvent(point) => {
                 let page = get_page(&*self.page.borrow(), pipeline_id);
                 match page.get_nodes_under_mouse(&point) {
@@ -22,7 +22,7 @@ extern crate serialize;
 use devtools_traits::DevtoolsControlChan;
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, Failure, WindowSizeData};
 use servo_msg::constellation_msg::SubpageId;
-use servo_msg::compositor_msg::ScriptListener;
+use servo_msg::compositor_msg::{LayerId, ScriptListener};
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::resource_task::ResourceTask;
 use std::any::Any;
@@ -60,11 +60,14 @@ pub enum ConstellationControlMsg {
 /// Events from the compositor that the script task needs to know about
 pub enum CompositorEvent {
     ResizeEvent(WindowSizeData),
-    ReflowEvent,
+    /// An image finished loading. Carries the opaque addresses of the nodes that display it.
+    ReflowEvent(Vec<uint>),
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
-    MouseMoveEvent(Point2D<f32>)
+    MouseMoveEvent(Point2D<f32>),
+    /// The scroll root layer with the given ID was scrolled to the given position.
+    ScrollEvent(LayerId, Point2D<f32>),
 }
 
 /// An opaque wrapper around script<->layout channels to avoid leaking message types into
@@ -2,38 +2,44 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use cssparser::ast;
 use cssparser::ast::*;
 use cssparser::parse_declaration_list;
 use errors::{ErrorLoggerIterator, log_css_error};
 use std::ascii::StrAsciiExt;
+use std::cmp;
 use parsing_utils::{BufferedIter, ParserIter, parse_slice_comma_separated};
 use properties::longhands::font_family::parse_one_family;
 use properties::computed_values::font_family::FamilyName;
-use stylesheets::{CSSRule, CSSFontFaceRule, CSSStyleRule, CSSMediaRule};
-use media_queries::{Device, Screen};
+use properties::longhands::{font_stretch, font_style, font_weight};
+use stylesheets::{CSSRule, CSSFontFaceRule, CSSMediaRule};
+use media_queries::Device;
 use url::{Url, UrlParser};
 
 
-static SUPPORTED_FORMATS: &'static [&'static str] = &["truetype", "opentype"];
+static SUPPORTED_FORMATS: &'static [&'static str] = &["truetype", "opentype", "woff", "woff2"];
 
 
-pub fn iter_font_face_rules_inner(rules: &[CSSRule], callback: |family: &str, source: &Url|) {
-    let device = &Device { media_type: Screen };  // TODO, use Print when printing
+/// Calls `callback` with each `@font-face` rule that applies to the device, along with the URLs
+/// of its sources in a format that is supported, in order of preference.
+pub fn iter_font_face_rules_inner(rules: &[CSSRule], device: &Device,
+                                  callback: |rule: &FontFaceRule, sources: Vec<Url>|) {
     for rule in rules.iter() {
         match *rule {
-            CSSStyleRule(_) => {},
             CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
-                iter_font_face_rules_inner(rule.rules.as_slice(), |f, s| callback(f, s))
+                iter_font_face_rules_inner(rule.rules.as_slice(), device, |r, s| callback(r, s))
             },
             CSSFontFaceRule(ref rule) => {
-                for source in rule.sources.iter() {
-                    if source.format_hints.is_empty() || source.format_hints.iter().any(
+                let sources = rule.sources.iter().filter(|source| {
+                    source.format_hints.is_empty() || source.format_hints.iter().any(
                             |f| SUPPORTED_FORMATS.iter().any(
-                                |s| f.as_slice().eq_ignore_ascii_case(*s))) {
-                        callback(rule.family.as_slice(), &source.url)
-                    }
+                                |s| f.as_slice().eq_ignore_ascii_case(*s)))
+                }).map(|source| source.url.clone()).collect::<Vec<Url>>();
+                if !sources.is_empty() {
+                    callback(rule, sources)
                 }
             },
+            _ => {}
         }
     }
 }
@@ -51,6 +57,33 @@ pub struct UrlSource {
 pub struct FontFaceRule {
     pub family: String,
     pub sources: Vec<UrlSource>,  // local() is not supported yet
+    pub weight: font_weight::T,
+    pub stretch: font_stretch::T,
+    pub style: font_style::T,
+    /// The characters that the face is used for. Never empty.
+    pub unicode_range: Vec<UnicodeRange>,
+}
+
+/// A range of code points, from `unicode-range`. Both ends are inclusive.
+#[deriving(Clone, PartialEq, Show)]
+pub struct UnicodeRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl UnicodeRange {
+    /// The range of all code points, which is the initial value of `unicode-range`.
+    pub fn all() -> UnicodeRange {
+        UnicodeRange {
+            start: 0,
+            end: 0x10FFFF,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, character: char) -> bool {
+        self.start <= character as u32 && character as u32 <= self.end
+    }
 }
 
 pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_url: &Url) {
@@ -69,6 +102,10 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
 
     let mut maybe_family = None;
     let mut maybe_sources = None;
+    let mut weight = font_weight::Weight400;
+    let mut stretch = font_stretch::normal;
+    let mut style = font_style::normal;
+    let mut unicode_range = vec![UnicodeRange::all()];
 
     for item in ErrorLoggerIterator(parse_declaration_list(block.into_iter())) {
         match item {
@@ -98,6 +135,32 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
                             Err(()) => log_css_error(location, "Invalid src in @font-face"),
                         };
                     },
+                    "font-weight" => {
+                        match parse_weight(value.as_slice(), base_url) {
+                            Ok(value) => weight = value,
+                            Err(()) => log_css_error(location, "Invalid font-weight in @font-face"),
+                        }
+                    },
+                    "font-stretch" => {
+                        match parse_single_value(value.as_slice(), font_stretch::from_component_value,
+                                                 base_url) {
+                            Ok(value) => stretch = value,
+                            Err(()) => log_css_error(location, "Invalid font-stretch in @font-face"),
+                        }
+                    },
+                    "font-style" => {
+                        match parse_single_value(value.as_slice(), font_style::from_component_value,
+                                                 base_url) {
+                            Ok(value) => style = value,
+                            Err(()) => log_css_error(location, "Invalid font-style in @font-face"),
+                        }
+                    },
+                    "unicode-range" => {
+                        match parse_slice_comma_separated(value.as_slice(), parse_one_unicode_range) {
+                            Ok(ranges) => unicode_range = ranges,
+                            Err(()) => log_css_error(location, "Invalid unicode-range in @font-face"),
+                        }
+                    },
                     _ => {
                         log_css_error(location, format!("Unsupported declaration {:s}", name).as_slice());
                     }
@@ -110,6 +173,10 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
         (Some(family), Some(sources)) => parent_rules.push(CSSFontFaceRule(FontFaceRule {
             family: family,
             sources: sources,
+            weight: weight,
+            stretch: stretch,
+            style: style,
+            unicode_range: unicode_range,
         })),
         (None, _) => log_css_error(rule.location, "@font-face without a font-family descriptor"),
         _ => log_css_error(rule.location, "@font-face without an src descriptor"),
@@ -181,3 +248,89 @@ fn parse_one_format(iter: ParserIter) -> Result<String, ()> {
         _ => Err(())
     }
 }
+
+
+/// normal | bold | 100 | 200 | 300 | 400 | 500 | 600 | 700 | 800 | 900
+fn parse_weight(input: &[ComponentValue], base_url: &Url) -> Result<font_weight::T, ()> {
+    // `bolder` and `lighter` are relative to the parent, so they are meaningless here.
+    match try!(parse_single_value(input, font_weight::from_component_value, base_url)) {
+        font_weight::SpecifiedWeight100 => Ok(font_weight::Weight100),
+        font_weight::SpecifiedWeight200 => Ok(font_weight::Weight200),
+        font_weight::SpecifiedWeight300 => Ok(font_weight::Weight300),
+        font_weight::SpecifiedWeight400 => Ok(font_weight::Weight400),
+        font_weight::SpecifiedWeight500 => Ok(font_weight::Weight500),
+        font_weight::SpecifiedWeight600 => Ok(font_weight::Weight600),
+        font_weight::SpecifiedWeight700 => Ok(font_weight::Weight700),
+        font_weight::SpecifiedWeight800 => Ok(font_weight::Weight800),
+        font_weight::SpecifiedWeight900 => Ok(font_weight::Weight900),
+        font_weight::Bolder | font_weight::Lighter => Err(()),
+    }
+}
+
+
+fn parse_single_value<T>(input: &[ComponentValue],
+                         parse: fn(&ComponentValue, &Url) -> Result<T, ()>,
+                         base_url: &Url)
+                         -> Result<T, ()> {
+    let mut iter = input.skip_whitespace();
+    match (iter.next(), iter.next()) {
+        (Some(value), None) => parse(value, base_url),
+        _ => Err(()),
+    }
+}
+
+
+/// <urange>, clamped to the range of code points. Ranges that end before they start are invalid.
+fn parse_one_unicode_range(iter: ParserIter) -> Result<UnicodeRange, ()> {
+    match (iter.next(), iter.next()) {
+        (Some(&ast::UnicodeRange(start, end)), None) => {
+            let end = cmp::min(end, 0x10FFFF);
+            if start > end {
+                return Err(())
+            }
+            Ok(UnicodeRange {
+                start: start,
+                end: end,
+            })
+        }
+        _ => Err(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cssparser::tokenize;
+    use cssparser::ast::ComponentValue;
+    use parsing_utils::parse_slice_comma_separated;
+    use properties::longhands::font_weight;
+    use url::Url;
+    use super::{UnicodeRange, parse_one_unicode_range, parse_weight};
+
+    fn component_values(css: &str) -> Vec<ComponentValue> {
+        tokenize(css).map(|(c, _)| c).collect()
+    }
+
+    #[test]
+    fn test_parse_unicode_range() {
+        let parse = |css: &str| {
+            parse_slice_comma_separated(component_values(css).as_slice(), parse_one_unicode_range)
+        };
+        assert_eq!(parse("U+0-7F, u+4??"),
+                   Ok(vec![UnicodeRange { start: 0, end: 0x7F },
+                           UnicodeRange { start: 0x400, end: 0x4FF }]));
+        assert_eq!(parse("U+10FFFF-1FFFFF"),
+                   Ok(vec![UnicodeRange { start: 0x10FFFF, end: 0x10FFFF }]));
+        assert_eq!(parse("U+20-10"), Err(()));
+        assert_eq!(parse("U+20 U+30"), Err(()));
+    }
+
+    #[test]
+    fn test_parse_weight() {
+        let base_url = Url::parse("http://example.com/").unwrap();
+        let parse = |css: &str| parse_weight(component_values(css).as_slice(), &base_url);
+        assert!(parse("bold") == Ok(font_weight::Weight700));
+        assert!(parse(" 300 ") == Ok(font_weight::Weight300));
+        assert!(parse("bolder").is_err());
+        assert!(parse("normal bold").is_err());
+    }
+}
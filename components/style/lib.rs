@@ -34,7 +34,7 @@ extern crate "util" as servo_util;
 
 
 // Public API
-pub use stylesheets::{Stylesheet, iter_font_face_rules};
+pub use stylesheets::{Stylesheet, iter_font_face_rules, iter_page_rules};
 pub use selector_matching::{Stylist, StylesheetOrigin, UserAgentOrigin, AuthorOrigin, UserOrigin};
 pub use selector_matching::{DeclarationBlock, matches,matches_simple_selector};
 pub use selector_matching::{RECOMMENDED_SELECTOR_BLOOM_FILTER_SIZE,SELECTOR_WHITESPACE};
@@ -43,7 +43,12 @@ pub use properties::{PropertyDeclaration, ComputedValues, computed_values, style
 pub use properties::{PropertyDeclarationBlock, parse_style_attribute};  // Style attributes
 pub use properties::{CSSFloat, DeclaredValue, PropertyDeclarationParseResult};
 pub use properties::longhands;
+pub use properties::{DamageClass, RepaintDamageClass, RepositionDamageClass};
+pub use properties::{ReflowDamageClass, RebuildFlowDamageClass};
 pub use node::{TElement, TNode};
+pub use media_queries::{Device, MediaType, Screen, Print};
+pub use font_face::{FontFaceRule, UnicodeRange};
+pub use page::{PageRule, PageSize, AutoPageSize, OrientedPageSize, ExplicitPageSize};
 pub use selectors::{PseudoElement, Before, After, SelectorList, parse_selector_list_from_str};
 pub use selectors::{AttrSelector, NamespaceConstraint, SpecificNamespace, AnyNamespace};
 pub use selectors::{SimpleSelector,LocalNameSelector};
@@ -59,3 +64,4 @@ mod node;
 mod media_queries;
 mod parsing_utils;
 mod font_face;
+mod page;
@@ -36,17 +36,26 @@ pub enum MediaQueryType {
     MediaType_(MediaType),
 }
 
-#[deriving(PartialEq)]
+#[deriving(PartialEq, Clone)]
 pub enum MediaType {
     Screen,
     Print,
 }
 
+#[deriving(Clone)]
 pub struct Device {
     pub media_type: MediaType,
     // TODO: Level 3 MQ data: viewport size, etc.
 }
 
+impl Device {
+    pub fn new(media_type: MediaType) -> Device {
+        Device {
+            media_type: media_type,
+        }
+    }
+}
+
 
 pub fn parse_media_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>,
                         namespaces: &NamespaceMap, base_url: &Url) {
@@ -0,0 +1,279 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `@page` rules, which give the size and margins of the page box when paginating.
+//!
+//! http://dev.w3.org/csswg/css-page-3/
+
+use cssparser::ast::*;
+use cssparser::parse_declaration_list;
+use errors::{ErrorLoggerIterator, log_css_error};
+use geom::side_offsets::SideOffsets2D;
+use geom::size::Size2D;
+use media_queries::Device;
+use properties::common_types::{computed, specified};
+use servo_util::geometry::Au;
+use std::ascii::StrAsciiExt;
+use stylesheets::{CSSRule, CSSPageRule, CSSMediaRule};
+
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum PageOrientation {
+    Portrait,
+    Landscape,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum PageSize {
+    /// `auto`: the user agent's page size.
+    AutoPageSize,
+    /// `portrait` or `landscape`: the user agent's page size, turned if necessary.
+    OrientedPageSize(PageOrientation),
+    /// One or two lengths, or a named paper size.
+    ExplicitPageSize(Size2D<Au>),
+}
+
+impl PageSize {
+    /// Returns the size of the page box, given the size of the user agent's default page.
+    pub fn resolve(&self, default_size: Size2D<Au>) -> Size2D<Au> {
+        match *self {
+            AutoPageSize => default_size,
+            OrientedPageSize(orientation) => orient(default_size, orientation),
+            ExplicitPageSize(size) => size,
+        }
+    }
+}
+
+fn orient(size: Size2D<Au>, orientation: PageOrientation) -> Size2D<Au> {
+    let is_landscape = size.width > size.height;
+    if is_landscape == (orientation == Landscape) {
+        size
+    } else {
+        Size2D(size.height, size.width)
+    }
+}
+
+/// The descriptors of a single `@page` rule. Descriptors the rule does not set are `None`, so
+/// that rules can be cascaded in document order.
+#[deriving(Clone)]
+pub struct PageRule {
+    pub size: Option<PageSize>,
+    pub margin: SideOffsets2D<Option<computed::LengthOrPercentage>>,
+}
+
+/// The page context has no element to inherit a font size from, so relative lengths are resolved
+/// against the initial `font-size`.
+fn compute_length(length: specified::Length) -> Au {
+    computed::compute_Au_with_font_size(length, Au::from_px(16))
+}
+
+fn compute_length_or_percentage(value: specified::LengthOrPercentage)
+                                -> computed::LengthOrPercentage {
+    match value {
+        specified::LP_Length(length) => computed::LP_Length(compute_length(length)),
+        specified::LP_Percentage(percentage) => computed::LP_Percentage(percentage),
+    }
+}
+
+pub fn parse_page_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>) {
+    // TODO: page selectors (`:first`, `:left`, `:right`) and named pages.
+    if rule.prelude.as_slice().skip_whitespace().next().is_some() {
+        log_css_error(rule.location, "Unsupported @page selector");
+        return;
+    }
+
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(rule.location, "Invalid @page rule");
+            return
+        }
+    };
+
+    let mut page_rule = PageRule {
+        size: None,
+        margin: SideOffsets2D::new(None, None, None, None),
+    };
+
+    for item in ErrorLoggerIterator(parse_declaration_list(block.into_iter())) {
+        match item {
+            DeclAtRule(rule) => {
+                log_css_error(rule.location,
+                              format!("Unsupported at-rule in @page: @{:s}", rule.name).as_slice())
+            }
+            Declaration_(Declaration{ location, name, value, important: _ }) => {
+                let name_lower = name.as_slice().to_ascii_lower();
+                let result = match name_lower.as_slice() {
+                    "size" => parse_size(value.as_slice()).map(|size| page_rule.size = Some(size)),
+                    "margin" => parse_margin(value.as_slice()).map(|margin| {
+                        page_rule.margin = SideOffsets2D::new(Some(margin.top),
+                                                              Some(margin.right),
+                                                              Some(margin.bottom),
+                                                              Some(margin.left))
+                    }),
+                    "margin-top" => parse_one_margin(value.as_slice()).map(|margin| {
+                        page_rule.margin.top = Some(margin)
+                    }),
+                    "margin-right" => parse_one_margin(value.as_slice()).map(|margin| {
+                        page_rule.margin.right = Some(margin)
+                    }),
+                    "margin-bottom" => parse_one_margin(value.as_slice()).map(|margin| {
+                        page_rule.margin.bottom = Some(margin)
+                    }),
+                    "margin-left" => parse_one_margin(value.as_slice()).map(|margin| {
+                        page_rule.margin.left = Some(margin)
+                    }),
+                    _ => {
+                        let message = format!("Unsupported @page descriptor {:s}", name);
+                        log_css_error(location, message.as_slice());
+                        continue
+                    }
+                };
+                if result.is_err() {
+                    log_css_error(location, format!("Invalid {:s} in @page", name).as_slice());
+                }
+            }
+        }
+    }
+
+    parent_rules.push(CSSPageRule(page_rule))
+}
+
+/// auto | <length>{1,2} | [ <page-size> || [ portrait | landscape ] ]
+fn parse_size(input: &[ComponentValue]) -> Result<PageSize, ()> {
+    let mut lengths = vec!();
+    let mut named_size = None;
+    let mut orientation = None;
+    for component_value in input.skip_whitespace() {
+        match component_value {
+            &Ident(ref value) => {
+                let keyword = value.as_slice().to_ascii_lower();
+                match keyword.as_slice() {
+                    "auto" if input.skip_whitespace().count() == 1 => return Ok(AutoPageSize),
+                    "portrait" if orientation.is_none() => orientation = Some(Portrait),
+                    "landscape" if orientation.is_none() => orientation = Some(Landscape),
+                    _ if named_size.is_none() => {
+                        named_size = Some(try!(named_page_size(keyword.as_slice())))
+                    }
+                    _ => return Err(()),
+                }
+            }
+            _ if named_size.is_none() && orientation.is_none() && lengths.len() < 2 => {
+                lengths.push(compute_length(try!(specified::Length::parse_non_negative(
+                    component_value))))
+            }
+            _ => return Err(()),
+        }
+    }
+
+    match (lengths.as_slice(), named_size, orientation) {
+        ([width], None, None) => Ok(ExplicitPageSize(Size2D(width, width))),
+        ([width, height], None, None) => Ok(ExplicitPageSize(Size2D(width, height))),
+        ([], Some(size), None) => Ok(ExplicitPageSize(size)),
+        ([], Some(size), Some(orientation)) => Ok(ExplicitPageSize(orient(size, orientation))),
+        ([], None, Some(orientation)) => Ok(OrientedPageSize(orientation)),
+        _ => Err(()),
+    }
+}
+
+/// The paper sizes of CSS Paged Media § 5.1, in portrait orientation.
+fn named_page_size(name: &str) -> Result<Size2D<Au>, ()> {
+    let (width, height, unit) = match name {
+        "a5" => (148., 210., "mm"),
+        "a4" => (210., 297., "mm"),
+        "a3" => (297., 420., "mm"),
+        "b5" => (176., 250., "mm"),
+        "b4" => (250., 353., "mm"),
+        "letter" => (8.5, 11., "in"),
+        "legal" => (8.5, 14., "in"),
+        "ledger" => (11., 17., "in"),
+        _ => return Err(()),
+    };
+    let width = compute_length(try!(specified::Length::parse_dimension(width, unit)));
+    let height = compute_length(try!(specified::Length::parse_dimension(height, unit)));
+    Ok(Size2D(width, height))
+}
+
+fn parse_one_margin(input: &[ComponentValue]) -> Result<computed::LengthOrPercentage, ()> {
+    let mut iter = input.skip_whitespace();
+    match (iter.next(), iter.next()) {
+        (Some(value), None) => {
+            specified::LengthOrPercentage::parse(value).map(compute_length_or_percentage)
+        }
+        _ => Err(()),
+    }
+}
+
+/// <length-percentage>{1,4}, expanded like the `margin` shorthand.
+fn parse_margin(input: &[ComponentValue])
+                -> Result<SideOffsets2D<computed::LengthOrPercentage>, ()> {
+    let mut values = vec!();
+    for component_value in input.skip_whitespace() {
+        values.push(compute_length_or_percentage(
+            try!(specified::LengthOrPercentage::parse(component_value))));
+    }
+    match values.as_slice() {
+        [all] => Ok(SideOffsets2D::new(all, all, all, all)),
+        [vertical, horizontal] => {
+            Ok(SideOffsets2D::new(vertical, horizontal, vertical, horizontal))
+        }
+        [top, horizontal, bottom] => Ok(SideOffsets2D::new(top, horizontal, bottom, horizontal)),
+        [top, right, bottom, left] => Ok(SideOffsets2D::new(top, right, bottom, left)),
+        _ => Err(()),
+    }
+}
+
+pub fn iter_page_rules_inner(rules: &[CSSRule], device: &Device, callback: |&PageRule|) {
+    for rule in rules.iter() {
+        match *rule {
+            CSSPageRule(ref rule) => callback(rule),
+            CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
+                iter_page_rules_inner(rule.rules.as_slice(), device, |r| callback(r))
+            },
+            _ => {}
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use cssparser::tokenize;
+    use cssparser::ast::ComponentValue;
+    use geom::size::Size2D;
+    use properties::common_types::computed;
+    use servo_util::geometry::Au;
+    use super::{AutoPageSize, ExplicitPageSize, Landscape, OrientedPageSize};
+    use super::{parse_margin, parse_size};
+
+    fn component_values(css: &str) -> Vec<ComponentValue> {
+        tokenize(css).map(|(c, _)| c).collect()
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size(component_values("auto").as_slice()), Ok(AutoPageSize));
+        assert_eq!(parse_size(component_values("landscape").as_slice()),
+                   Ok(OrientedPageSize(Landscape)));
+        assert_eq!(parse_size(component_values("100px 200px").as_slice()),
+                   Ok(ExplicitPageSize(Size2D(Au::from_px(100), Au::from_px(200)))));
+        assert_eq!(parse_size(component_values("5in").as_slice()),
+                   Ok(ExplicitPageSize(Size2D(Au::from_px(480), Au::from_px(480)))));
+        assert_eq!(parse_size(component_values("letter landscape").as_slice()),
+                   Ok(ExplicitPageSize(Size2D(Au::from_px(1056), Au::from_px(816)))));
+        assert_eq!(parse_size(component_values("auto auto").as_slice()), Err(()));
+        assert_eq!(parse_size(component_values("a4 10px").as_slice()), Err(()));
+        assert_eq!(parse_size(component_values("tabloid").as_slice()), Err(()));
+    }
+
+    #[test]
+    fn test_parse_margin() {
+        let margin = parse_margin(component_values("1in 10%").as_slice()).unwrap();
+        assert!(margin.top == computed::LP_Length(Au::from_px(96)));
+        assert!(margin.right == computed::LP_Percentage(0.1));
+        assert!(margin.bottom == computed::LP_Length(Au::from_px(96)));
+        assert!(margin.left == computed::LP_Percentage(0.1));
+        assert!(parse_margin(component_values("").as_slice()).is_err());
+    }
+}
@@ -215,15 +215,22 @@ pub mod computed {
         pub inherited_text_decorations_in_effect: longhands::_servo_text_decorations_in_effect::T,
         pub inherited_height: longhands::height::T,
         pub color: longhands::color::computed_value::T,
-        pub text_decoration: longhands::text_decoration::computed_value::T,
+        pub text_decoration_line: longhands::text_decoration_line::computed_value::T,
+        pub text_decoration_color: longhands::text_decoration_color::computed_value::T,
+        pub text_decoration_style: longhands::text_decoration_style::computed_value::T,
         pub font_size: longhands::font_size::computed_value::T,
+        pub font_size_keyword: longhands::_servo_font_size_keyword::computed_value::T,
         pub display: longhands::display::computed_value::T,
         pub positioned: bool,
         pub floated: bool,
+        pub overflow_x: longhands::overflow_x::computed_value::T,
+        pub overflow_y: longhands::overflow_y::computed_value::T,
         pub border_top_present: bool,
         pub border_right_present: bool,
         pub border_bottom_present: bool,
         pub border_left_present: bool,
+        pub column_rule_present: bool,
+        pub outline_present: bool,
         pub is_root_element: bool,
         // TODO, as needed: root font size, viewport size, etc.
     }
@@ -35,8 +35,24 @@ def to_rust_ident(name):
         name += "_"
     return name
 
+# The kinds of layout work that a change to the computed value of a longhand can call for, from
+# least to most work. Every longhand must declare one of these as its `damage`.
+#
+# * repaint: Only the display list needs rebuilding, e.g. for colors.
+# * reposition: The box moves without changing size, e.g. for relative position offsets.
+# * reflow: The box has to be laid out again, e.g. for margins and sizes.
+# * rebuild_flow: The flows have to be constructed again, because flow construction depends on
+#   the value, e.g. for `display` or anything that affects how text is shaped.
+DAMAGE_CLASSES = ["repaint", "reposition", "reflow", "rebuild_flow"]
+
+# Internal longhands are set by the style system itself, and style sheets can't declare them.
 class Longhand(object):
-    def __init__(self, name, derived_from=None, experimental=False):
+    def __init__(self, name, derived_from=None, experimental=False, internal=False,
+                 damage=None):
+        if damage is None:
+            raise Exception("no damage class for %s" % name)
+        if damage not in DAMAGE_CLASSES:
+            raise Exception("unknown damage class %s for %s" % (damage, name))
         self.name = name
         self.ident = to_rust_ident(name)
         self.camel_case, _ = re.subn(
@@ -45,6 +61,8 @@ class Longhand(object):
             self.ident.strip("_").capitalize())
         self.style_struct = THIS_STYLE_STRUCT
         self.experimental = experimental
+        self.internal = internal
+        self.damage = damage
         if derived_from is None:
             self.derived_from = None
         else:
@@ -96,12 +114,14 @@ pub mod longhands {
         value
     }
 
-    <%def name="raw_longhand(name, no_super=False, derived_from=None, experimental=False)">
+    <%def name="raw_longhand(name, no_super=False, derived_from=None, experimental=False,
+                             internal=False, damage=None)">
     <%
         if derived_from is not None:
             derived_from = derived_from.split()
 
-        property = Longhand(name, derived_from=derived_from, experimental=experimental)
+        property = Longhand(name, derived_from=derived_from, experimental=experimental,
+                            internal=internal, damage=damage)
         THIS_STYLE_STRUCT.longhands.append(property)
         LONGHANDS.append(property)
         LONGHANDS_BY_NAME[name] = property
@@ -131,9 +151,11 @@ pub mod longhands {
         }
     </%def>
 
-    <%def name="longhand(name, no_super=False, derived_from=None, experimental=False)">
+    <%def name="longhand(name, no_super=False, derived_from=None, experimental=False,
+                         internal=False, damage=None)">
         <%self:raw_longhand name="${name}" derived_from="${derived_from}"
-                            experimental="${experimental}" no_super="${no_super}">
+                            experimental="${experimental}" internal="${internal}"
+                            no_super="${no_super}" damage="${damage}">
             ${caller.body()}
             % if derived_from is None:
                 pub fn parse_specified(_input: &[ComponentValue], _base_url: &Url)
@@ -144,9 +166,10 @@ pub mod longhands {
         </%self:raw_longhand>
     </%def>
 
-    <%def name="single_component_value(name, derived_from=None, experimental=False)">
+    <%def name="single_component_value(name, derived_from=None, experimental=False,
+                                       damage=None)">
         <%self:longhand name="${name}" derived_from="${derived_from}"
-                        experimental="${experimental}">
+                        experimental="${experimental}" damage="${damage}">
             ${caller.body()}
             pub fn parse(input: &[ComponentValue], base_url: &Url) -> Result<SpecifiedValue, ()> {
                 one_component_value(input).and_then(|c| from_component_value(c, base_url))
@@ -154,8 +177,9 @@ pub mod longhands {
         </%self:longhand>
     </%def>
 
-    <%def name="single_keyword_computed(name, values, experimental=False)">
-        <%self:single_component_value name="${name}" experimental="${experimental}">
+    <%def name="single_keyword_computed(name, values, experimental=False, damage=None)">
+        <%self:single_component_value name="${name}" experimental="${experimental}"
+                                      damage="${damage}">
             ${caller.body()}
             pub mod computed_value {
                 #[allow(non_camel_case_types)]
@@ -165,6 +189,16 @@ pub mod longhands {
                         ${to_rust_ident(value)},
                     % endfor
                 }
+                impl T {
+                    /// Returns the keyword as it is written in CSS.
+                    pub fn to_css_keyword(&self) -> &'static str {
+                        match *self {
+                            % for value in values.split():
+                                ${to_rust_ident(value)} => "${value}",
+                            % endfor
+                        }
+                    }
+                }
             }
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T {
@@ -184,17 +218,18 @@ pub mod longhands {
         </%self:single_component_value>
     </%def>
 
-    <%def name="single_keyword(name, values, experimental=False)">
+    <%def name="single_keyword(name, values, experimental=False, damage=None)">
         <%self:single_keyword_computed name="${name}"
                                        values="${values}"
-                                       experimental="${experimental}">
+                                       experimental="${experimental}"
+                                       damage="${damage}">
             // The computed value is the same as the specified value.
             pub use super::computed_as_specified as to_computed_value;
         </%self:single_keyword_computed>
     </%def>
 
-    <%def name="predefined_type(name, type, initial_value, parse_method='parse')">
-        <%self:single_component_value name="${name}">
+    <%def name="predefined_type(name, type, initial_value, parse_method='parse', damage=None)">
+        <%self:single_component_value name="${name}" damage="${damage}">
             pub use super::super::common_types::computed::compute_${type} as to_computed_value;
             pub type SpecifiedValue = specified::${type};
             pub mod computed_value {
@@ -215,7 +250,7 @@ pub mod longhands {
 
     % for side in ["top", "right", "bottom", "left"]:
         ${predefined_type("margin-" + side, "LengthOrPercentageOrAuto",
-                          "computed::LPA_Length(Au(0))")}
+                          "computed::LPA_Length(Au(0))", damage="reflow")}
     % endfor
 
     ${new_style_struct("Padding", is_inherited=False)}
@@ -223,19 +258,24 @@ pub mod longhands {
     % for side in ["top", "right", "bottom", "left"]:
         ${predefined_type("padding-" + side, "LengthOrPercentage",
                           "computed::LP_Length(Au(0))",
-                          "parse_non_negative")}
+                          "parse_non_negative",
+                          damage="reflow")}
     % endfor
 
     ${new_style_struct("Border", is_inherited=False)}
 
     % for side in ["top", "right", "bottom", "left"]:
-        ${predefined_type("border-%s-color" % side, "CSSColor", "CurrentColor")}
+        ${predefined_type("border-%s-color" % side, "CSSColor", "CurrentColor",
+                          damage="repaint")}
     % endfor
 
-    ${single_keyword("border-top-style", values="none solid double dotted dashed hidden groove ridge inset outset")}
+    // A style of `none` or `hidden` makes the computed border width zero, so only the width of a
+    // border affects layout.
+    ${single_keyword("border-top-style", values="none solid double dotted dashed hidden groove ridge inset outset",
+                     damage="repaint")}
 
     % for side in ["right", "bottom", "left"]:
-        <%self:longhand name="border-${side}-style">
+        <%self:longhand name="border-${side}-style" damage="repaint">
             pub use super::border_top_style::{get_initial_value, parse, to_computed_value};
             pub type SpecifiedValue = super::border_top_style::SpecifiedValue;
             pub mod computed_value {
@@ -259,7 +299,7 @@ pub mod longhands {
         }
     }
     % for side in ["top", "right", "bottom", "left"]:
-        <%self:longhand name="border-${side}-width">
+        <%self:longhand name="border-${side}-width" damage="reflow">
             pub type SpecifiedValue = specified::Length;
             pub mod computed_value {
                 use super::super::Au;
@@ -285,9 +325,12 @@ pub mod longhands {
 
     ${new_style_struct("PositionOffsets", is_inherited=False)}
 
+    // Absolutely positioned boxes are always laid out again, so only relative and sticky positioning
+    // need to be handled here.
     % for side in ["top", "right", "bottom", "left"]:
         ${predefined_type(side, "LengthOrPercentageOrAuto",
-                          "computed::LPA_Auto")}
+                          "computed::LPA_Auto",
+                          damage="reposition")}
     % endfor
 
     // CSS 2.1, Section 9 - Visual formatting model
@@ -300,7 +343,8 @@ pub mod longhands {
             table inline-table table-row-group table-header-group table-footer-group
             table-row table-column-group table-column table-cell table-caption
             list-item
-            none">
+            none"
+            damage="rebuild_flow">
         #[inline]
         pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
                               -> computed_value::T {
@@ -323,11 +367,12 @@ pub mod longhands {
         }
     </%self:single_keyword_computed>
 
-    ${single_keyword("position", "static absolute relative fixed")}
-    ${single_keyword("float", "none left right")}
-    ${single_keyword("clear", "none left right both")}
+    ${single_keyword("position", "static absolute relative fixed sticky", damage="rebuild_flow")}
+    ${single_keyword("float", "none left right", damage="rebuild_flow")}
+    ${single_keyword("clear", "none left right both", damage="reflow")}
 
-    <%self:longhand name="-servo-display-for-hypothetical-box" derived_from="display" no_super="True">
+    <%self:longhand name="-servo-display-for-hypothetical-box" derived_from="display" no_super="True"
+                    damage="rebuild_flow">
         pub use super::computed_as_specified as to_computed_value;
         pub use super::display::{SpecifiedValue, get_initial_value};
         pub use super::display::{parse};
@@ -348,7 +393,7 @@ pub mod longhands {
 
     ${new_style_struct("InheritedBox", is_inherited=True)}
 
-    ${single_keyword("direction", "ltr rtl", experimental=True)}
+    ${single_keyword("direction", "ltr rtl", experimental=True, damage="rebuild_flow")}
 
     // CSS 2.1, Section 10 - Visual formatting model details
 
@@ -356,8 +401,9 @@ pub mod longhands {
 
     ${predefined_type("width", "LengthOrPercentageOrAuto",
                       "computed::LPA_Auto",
-                      "parse_non_negative")}
-    <%self:single_component_value name="height">
+                      "parse_non_negative",
+                      damage="reflow")}
+    <%self:single_component_value name="height" damage="reflow">
         pub type SpecifiedValue = specified::LengthOrPercentageOrAuto;
         pub mod computed_value {
             pub type T = super::super::computed::LengthOrPercentageOrAuto;
@@ -383,21 +429,25 @@ pub mod longhands {
 
     ${predefined_type("min-width", "LengthOrPercentage",
                       "computed::LP_Length(Au(0))",
-                      "parse_non_negative")}
+                      "parse_non_negative",
+                      damage="reflow")}
     ${predefined_type("max-width", "LengthOrPercentageOrNone",
                       "computed::LPN_None",
-                      "parse_non_negative")}
+                      "parse_non_negative",
+                      damage="reflow")}
 
     ${predefined_type("min-height", "LengthOrPercentage",
                       "computed::LP_Length(Au(0))",
-                      "parse_non_negative")}
+                      "parse_non_negative",
+                      damage="reflow")}
     ${predefined_type("max-height", "LengthOrPercentageOrNone",
                       "computed::LPN_None",
-                      "parse_non_negative")}
+                      "parse_non_negative",
+                      damage="reflow")}
 
     ${switch_to_style_struct("InheritedBox")}
 
-    <%self:single_component_value name="line-height">
+    <%self:single_component_value name="line-height" damage="reflow">
         #[deriving(Clone)]
         pub enum SpecifiedValue {
             SpecifiedNormal,
@@ -445,7 +495,7 @@ pub mod longhands {
 
     ${switch_to_style_struct("Box")}
 
-    <%self:single_component_value name="vertical-align">
+    <%self:single_component_value name="vertical-align" damage="reflow">
         <% vertical_align_keywords = (
             "baseline sub super top text-top middle bottom text-bottom".split()) %>
         #[allow(non_camel_case_types)]
@@ -505,24 +555,121 @@ pub mod longhands {
 
 
     // CSS 2.1, Section 11 - Visual effects
-    // FIXME: Implement scrolling for `scroll` and `auto` (#2742).
-    ${single_keyword("overflow", "visible hidden scroll auto")}
+
+    // CSS Overflow Module Level 3
+    // http://dev.w3.org/csswg/css-overflow-3/
+    % for axis, other_axis in [("x", "y"), ("y", "x")]:
+        <%self:single_keyword_computed name="overflow-${axis}"
+                                       values="visible hidden scroll auto"
+                                       damage="rebuild_flow">
+            /// If one of `overflow-x` or `overflow-y` is not `visible`, then a `visible` value in
+            /// the other axis computes to `auto`.
+            #[inline]
+            pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                     -> computed_value::T {
+                match (value, context.overflow_${other_axis}) {
+                    (visible, overflow_${other_axis}::visible) => visible,
+                    (visible, _) => auto,
+                    (value, _) => value,
+                }
+            }
+        </%self:single_keyword_computed>
+    % endfor
+
+    // Only absolutely positioned boxes are clipped.
+    <%self:single_component_value name="clip" damage="repaint">
+        pub mod computed_value {
+            use super::super::Au;
+            /// Offsets from the top left corner of the border box. `None` for the right or bottom
+            /// edge means the corresponding edge of the border box.
+            #[deriving(PartialEq, Clone, Show)]
+            pub struct ClipRect {
+                pub top: Au,
+                pub right: Option<Au>,
+                pub bottom: Option<Au>,
+                pub left: Au,
+            }
+            pub type T = Option<ClipRect>;
+        }
+        #[deriving(Clone)]
+        pub struct SpecifiedClipRect {
+            pub top: Option<specified::Length>,
+            pub right: Option<specified::Length>,
+            pub bottom: Option<specified::Length>,
+            pub left: Option<specified::Length>,
+        }
+        pub type SpecifiedValue = Option<SpecifiedClipRect>;
+        #[inline] pub fn get_initial_value() -> computed_value::T { None }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            value.map(|value| {
+                computed_value::ClipRect {
+                    top: value.top.map_or(Au(0), |top| computed::compute_Au(top, context)),
+                    right: value.right.map(|right| computed::compute_Au(right, context)),
+                    bottom: value.bottom.map(|bottom| computed::compute_Au(bottom, context)),
+                    left: value.left.map_or(Au(0), |left| computed::compute_Au(left, context)),
+                }
+            })
+        }
+        /// auto | rect(<top>, <right>, <bottom>, <left>), where each edge is <length> or auto.
+        /// The commas may be left out, as CSS 2.1 allowed.
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match input {
+                &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("auto") => Ok(None),
+                &Function(ref name, ref arguments)
+                        if name.as_slice().eq_ignore_ascii_case("rect") => {
+                    let mut edges = vec!();
+                    let mut nb_commas = 0u;
+                    for argument in arguments.as_slice().skip_whitespace() {
+                        match argument {
+                            &Comma => nb_commas += 1,
+                            &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("auto") => {
+                                edges.push(None)
+                            }
+                            _ => edges.push(Some(try!(specified::Length::parse(argument)))),
+                        }
+                    }
+                    if edges.len() != 4 || (nb_commas != 0 && nb_commas != 3) {
+                        return Err(())
+                    }
+                    Ok(Some(SpecifiedClipRect {
+                        top: edges[0],
+                        right: edges[1],
+                        bottom: edges[2],
+                        left: edges[3],
+                    }))
+                }
+                _ => Err(()),
+            }
+        }
+    </%self:single_component_value>
 
     ${switch_to_style_struct("InheritedBox")}
 
-    // TODO: collapse. Well, do tables first.
-    ${single_keyword("visibility", "visible hidden")}
+    // `collapse` removes table rows and columns from layout, and is the same as `hidden` for other
+    // boxes.
+    ${single_keyword("visibility", "visible hidden collapse", damage="reflow")}
 
     // CSS 2.1, Section 12 - Generated content, automatic numbering, and lists
 
     ${switch_to_style_struct("Box")}
 
-    <%self:longhand name="content">
+    <%self:longhand name="content" damage="rebuild_flow">
             pub use super::computed_as_specified as to_computed_value;
             pub mod computed_value {
+                pub use url::Url;
                 #[deriving(PartialEq, Clone)]
                 pub enum ContentItem {
                     StringContent(String),
+                    /// The value of the named attribute of the element, or nothing if it is not
+                    /// set.
+                    AttrContent(String),
+                    UrlContent(Url),
+                    OpenQuote,
+                    CloseQuote,
+                    NoOpenQuote,
+                    NoCloseQuote,
                 }
                 #[allow(non_camel_case_types)]
                 #[deriving(PartialEq, Clone)]
@@ -535,9 +682,10 @@ pub mod longhands {
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T  { normal }
 
-            // normal | none | [ <string> ]+
-            // TODO: <uri>, <counter>, attr(<identifier>), open-quote, close-quote, no-open-quote, no-close-quote
-            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            // normal | none | [ <string> | <uri> | attr(<identifier>) | open-quote | close-quote |
+            //                   no-open-quote | no-close-quote ]+
+            // TODO: <counter>
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Result<SpecifiedValue, ()> {
                 match one_component_value(input) {
                     Ok(&Ident(ref keyword)) => {
                         match keyword.as_slice().to_ascii_lower().as_slice() {
@@ -553,21 +701,121 @@ pub mod longhands {
                     match component_value {
                         &QuotedString(ref value)
                         => content.push(StringContent(value.clone())),
+                        &ast::URL(ref url)
+                        => content.push(UrlContent(parse_url(url.as_slice(), base_url))),
+                        &Function(ref name, ref arguments)
+                                if name.as_slice().eq_ignore_ascii_case("attr") => {
+                            // HTML attribute names are case-insensitive, and stored in lower case.
+                            match one_component_value(arguments.as_slice()) {
+                                Ok(&Ident(ref name))
+                                => content.push(AttrContent(name.as_slice().to_ascii_lower())),
+                                _ => return Err(())
+                            }
+                        }
+                        &Ident(ref keyword) => {
+                            match keyword.as_slice().to_ascii_lower().as_slice() {
+                                "open-quote" => content.push(OpenQuote),
+                                "close-quote" => content.push(CloseQuote),
+                                "no-open-quote" => content.push(NoOpenQuote),
+                                "no-close-quote" => content.push(NoCloseQuote),
+                                _ => return Err(())
+                            }
+                        }
                         _ => return Err(())  // invalid/unsupported value
                     }
                 }
                 Ok(Content(content))
             }
     </%self:longhand>
+
+    ${switch_to_style_struct("InheritedBox")}
+
+    <%self:longhand name="quotes" damage="rebuild_flow">
+            pub use super::computed_as_specified as to_computed_value;
+            pub mod computed_value {
+                /// Pairs of open and close quotes, outermost first. `none` is the empty list.
+                pub type T = Vec<(String, String)>;
+            }
+            pub type SpecifiedValue = computed_value::T;
+            #[inline] pub fn get_initial_value() -> computed_value::T {
+                vec!(("\u201c".to_string(), "\u201d".to_string()),
+                     ("\u2018".to_string(), "\u2019".to_string()))
+            }
+
+            // none | [ <string> <string> ]+
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+                match one_component_value(input) {
+                    Ok(&Ident(ref keyword)) if keyword.as_slice().eq_ignore_ascii_case("none") => {
+                        return Ok(vec!())
+                    }
+                    _ => ()
+                }
+                let mut quotes = vec!();
+                let mut iter = input.skip_whitespace();
+                loop {
+                    match iter.next() {
+                        None => break,
+                        Some(&QuotedString(ref open)) => {
+                            match iter.next() {
+                                Some(&QuotedString(ref close)) => {
+                                    quotes.push((open.clone(), close.clone()))
+                                }
+                                _ => return Err(())
+                            }
+                        }
+                        _ => return Err(())
+                    }
+                }
+                if quotes.is_empty() {
+                    Err(())
+                } else {
+                    Ok(quotes)
+                }
+            }
+    </%self:longhand>
+
     // CSS 2.1, Section 13 - Paged media
 
+    // Page breaks are chosen after the flow tree has been laid out, so changing any of these only
+    // needs the pages repainted.
+    ${switch_to_style_struct("Box")}
+
+    ${single_keyword("page-break-before", "auto always avoid left right", damage="repaint")}
+    ${single_keyword("page-break-after", "auto always avoid left right", damage="repaint")}
+    ${single_keyword("page-break-inside", "auto avoid", damage="repaint")}
+
+    ${switch_to_style_struct("InheritedBox")}
+
+    % for name in ["orphans", "widows"]:
+        <%self:single_component_value name="${name}" damage="repaint">
+            pub use super::computed_as_specified as to_computed_value;
+            pub type SpecifiedValue = computed_value::T;
+            pub mod computed_value {
+                pub type T = u32;
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T { 2 }
+            /// <integer>, which must be positive
+            pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                        -> Result<SpecifiedValue, ()> {
+                match input {
+                    &Number(ref value) => match value.int_value {
+                        Some(count) if count > 0 => Ok(count as u32),
+                        _ => Err(()),
+                    },
+                    _ => Err(())
+                }
+            }
+        </%self:single_component_value>
+    % endfor
+
     // CSS 2.1, Section 14 - Colors and Backgrounds
 
     ${new_style_struct("Background", is_inherited=False)}
     ${predefined_type("background-color", "CSSColor",
-                      "RGBAColor(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */")}
+                      "RGBAColor(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */",
+                      damage="repaint")}
 
-    <%self:single_component_value name="background-image">
+    <%self:single_component_value name="background-image" damage="repaint">
             // The computed value is the same as the specified value.
             pub use super::computed_as_specified as to_computed_value;
             pub mod computed_value {
@@ -592,7 +840,7 @@ pub mod longhands {
             }
     </%self:single_component_value>
 
-    <%self:longhand name="background-position">
+    <%self:longhand name="background-position" damage="repaint">
             pub mod computed_value {
                 use super::super::super::common_types::computed::LengthOrPercentage;
 
@@ -698,13 +946,13 @@ pub mod longhands {
             }
     </%self:longhand>
 
-    ${single_keyword("background-repeat", "repeat repeat-x repeat-y no-repeat")}
+    ${single_keyword("background-repeat", "repeat repeat-x repeat-y no-repeat", damage="repaint")}
 
-    ${single_keyword("background-attachment", "scroll fixed")}
+    ${single_keyword("background-attachment", "scroll fixed", damage="repaint")}
 
     ${new_style_struct("Color", is_inherited=True)}
 
-    <%self:raw_longhand name="color">
+    <%self:raw_longhand name="color" damage="repaint">
         pub use super::computed_as_specified as to_computed_value;
         pub type SpecifiedValue = RGBA;
         pub mod computed_value {
@@ -727,30 +975,50 @@ pub mod longhands {
 
     ${new_style_struct("Font", is_inherited=True)}
 
-    <%self:longhand name="font-family">
+    // Text is shaped during flow construction, so changing any font property rebuilds flows.
+    <%self:longhand name="font-family" damage="rebuild_flow">
         pub use super::computed_as_specified as to_computed_value;
         pub mod computed_value {
-            #[deriving(PartialEq, Clone)]
+            #[deriving(PartialEq, Clone, Show)]
             pub enum FontFamily {
                 FamilyName(String),
                 // Generic
-//                Serif,
-//                SansSerif,
-//                Cursive,
-//                Fantasy,
-//                Monospace,
+                Serif,
+                SansSerif,
+                Cursive,
+                Fantasy,
+                Monospace,
+            }
+            impl FontFamily {
+                /// Returns the name of this family. Generic families are named by their keyword,
+                /// which the font cache resolves to a font on the system.
+                pub fn name(&self) -> &str {
+                    match *self {
+                        FamilyName(ref name) => name.as_slice(),
+                        Serif => "serif",
+                        SansSerif => "sans-serif",
+                        Cursive => "cursive",
+                        Fantasy => "fantasy",
+                        Monospace => "monospace",
+                    }
+                }
             }
             pub type T = Vec<FontFamily>;
+
+            /// Returns true if the generic `monospace` family is the only one in the list, in
+            /// which case text defaults to a smaller size.
+            pub fn is_only_monospace(families: &T) -> bool {
+                families.as_slice() == [Monospace]
+            }
         }
         pub type SpecifiedValue = computed_value::T;
 
         #[inline]
         pub fn get_initial_value() -> computed_value::T {
-            vec![FamilyName("serif".to_string())]
+            vec![Serif]
         }
         /// <familiy-name>#
-        /// <familiy-name> = <string> | [ <ident>+ ]
-        /// TODO: <generic-familiy>
+        /// <familiy-name> = <string> | [ <ident>+ ] | <generic-family>
         pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
             parse_slice_comma_separated(input, parse_one_family)
         }
@@ -759,25 +1027,36 @@ pub mod longhands {
             let mut idents = match iter.next() {
                 Some(&QuotedString(ref value)) => return Ok(FamilyName(value.clone())),
                 Some(&Ident(ref value)) => {
-//                    match value.as_slice().to_ascii_lower().as_slice() {
-//                        "serif" => return Ok(Serif),
-//                        "sans-serif" => return Ok(SansSerif),
-//                        "cursive" => return Ok(Cursive),
-//                        "fantasy" => return Ok(Fantasy),
-//                        "monospace" => return Ok(Monospace),
-//                        _ => {
-                            vec![value.as_slice()]
-//                        }
-//                    }
+                    let generic = match value.as_slice().to_ascii_lower().as_slice() {
+                        "serif" => Some(Serif),
+                        "sans-serif" => Some(SansSerif),
+                        "cursive" => Some(Cursive),
+                        "fantasy" => Some(Fantasy),
+                        "monospace" => Some(Monospace),
+                        _ => None,
+                    };
+                    // A generic family keyword followed by more identifiers is part of a family
+                    // name, like `Monospace Bold`.
+                    match generic {
+                        Some(generic) => match iter.next() {
+                            Some(component_value) => {
+                                iter.push_back(component_value);
+                                match *component_value {
+                                    Ident(_) => {}
+                                    _ => return Ok(generic),
+                                }
+                            }
+                            None => return Ok(generic),
+                        },
+                        None => {}
+                    }
+                    vec![value.as_slice()]
                 }
                 _ => return Err(())
             };
             loop {
                 match iter.next() {
-                    Some(&Ident(ref value)) => {
-                        idents.push(value.as_slice());
-                        iter.next();
-                    }
+                    Some(&Ident(ref value)) => idents.push(value.as_slice()),
                     Some(component_value) => {
                         iter.push_back(component_value);
                         break
@@ -790,10 +1069,10 @@ pub mod longhands {
     </%self:longhand>
 
 
-    ${single_keyword("font-style", "normal italic oblique")}
-    ${single_keyword("font-variant", "normal small-caps")}
+    ${single_keyword("font-style", "normal italic oblique", damage="rebuild_flow")}
+    ${single_keyword("font-variant", "normal small-caps", damage="rebuild_flow")}
 
-    <%self:single_component_value name="font-weight">
+    <%self:single_component_value name="font-weight" damage="rebuild_flow">
         #[deriving(Clone)]
         pub enum SpecifiedValue {
             Bolder,
@@ -881,16 +1160,32 @@ pub mod longhands {
         }
     </%self:single_component_value>
 
-    <%self:single_component_value name="font-size">
-        pub type SpecifiedValue = specified::Length;  // Percentages are the same as em.
+    <%self:single_component_value name="font-size" damage="rebuild_flow">
+        #[deriving(Clone)]
+        pub enum SpecifiedValue {
+            SpecifiedLength(specified::Length),  // Percentages are the same as em.
+            /// An `<absolute-size>` keyword, as a multiple of `medium`.
+            SpecifiedKeyword(CSSFloat),
+        }
         pub mod computed_value {
             use super::super::Au;
             pub type T = Au;
         }
         static MEDIUM_PX: int = 16;
+        /// Like other browsers, text in the `monospace` family alone defaults to a smaller size.
+        static MONOSPACE_MEDIUM_PX: int = 13;
         #[inline] pub fn get_initial_value() -> computed_value::T {
             Au::from_px(MEDIUM_PX)
         }
+        /// Returns the size of `medium` text, which the `<absolute-size>` keywords are relative to.
+        #[inline]
+        pub fn medium(is_only_monospace: bool) -> Au {
+            if is_only_monospace {
+                Au::from_px(MONOSPACE_MEDIUM_PX)
+            } else {
+                Au::from_px(MEDIUM_PX)
+            }
+        }
         #[inline]
         pub fn to_computed_value(_value: SpecifiedValue, context: &computed::Context)
                                  -> computed_value::T {
@@ -901,38 +1196,303 @@ pub mod longhands {
         pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
                                     -> Result<SpecifiedValue, ()> {
             match specified::LengthOrPercentage::parse_non_negative(input) {
-                Ok(specified::LP_Length(value)) => return Ok(value),
-                Ok(specified::LP_Percentage(value)) => return Ok(specified::Em(value)),
+                Ok(specified::LP_Length(value)) => return Ok(SpecifiedLength(value)),
+                Ok(specified::LP_Percentage(value)) => {
+                    return Ok(SpecifiedLength(specified::Em(value)))
+                }
                 Err(()) => (),
             }
             match try!(get_ident_lower(input)).as_slice() {
-                "xx-small" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 3 / 5)),
-                "x-small" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 3 / 4)),
-                "small" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 8 / 9)),
-                "medium" => Ok(specified::Au_(Au::from_px(MEDIUM_PX))),
-                "large" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 6 / 5)),
-                "x-large" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 3 / 2)),
-                "xx-large" => Ok(specified::Au_(Au::from_px(MEDIUM_PX) * 2)),
+                "xx-small" => Ok(SpecifiedKeyword(3. / 5.)),
+                "x-small" => Ok(SpecifiedKeyword(3. / 4.)),
+                "small" => Ok(SpecifiedKeyword(8. / 9.)),
+                "medium" => Ok(SpecifiedKeyword(1.)),
+                "large" => Ok(SpecifiedKeyword(6. / 5.)),
+                "x-large" => Ok(SpecifiedKeyword(3. / 2.)),
+                "xx-large" => Ok(SpecifiedKeyword(2.)),
 
                 // https://github.com/servo/servo/issues/3423#issuecomment-56321664
-                "smaller" => Ok(specified::Em(0.85)),
-                "larger" => Ok(specified::Em(1.2)),
+                "smaller" => Ok(SpecifiedLength(specified::Em(0.85))),
+                "larger" => Ok(SpecifiedLength(specified::Em(1.2))),
 
                 _ => return Err(())
             }
         }
     </%self:single_component_value>
 
+    <%self:longhand name="-servo-font-size-keyword" derived_from="font-size"
+                    damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            /// The font size as a multiple of `medium`, if it comes from an `<absolute-size>`
+            /// keyword, so that it can be resized when the family changes to or from `monospace`.
+            pub type T = Option<CSSFloat>;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            Some(1.)
+        }
+        #[inline]
+        pub fn derive_from_font_size(_: font_size::computed_value::T, context: &computed::Context)
+                                     -> computed_value::T {
+            context.font_size_keyword
+        }
+    </%self:longhand>
+
+    ${single_keyword("font-stretch",
+                     "normal ultra-condensed extra-condensed condensed semi-condensed "
+                     "semi-expanded expanded extra-expanded ultra-expanded",
+                     damage="rebuild_flow")}
+
+    <%self:single_component_value name="font-size-adjust" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            /// The aspect value (x-height divided by font size) to scale fonts to.
+            pub type T = Option<CSSFloat>;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { None }
+        /// none | <number>
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match input {
+                &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("none") => Ok(None),
+                &Number(ref value) if value.value >= 0. => Ok(Some(value.value)),
+                _ => Err(())
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS Fonts Module Level 3, Section 6 - Font feature properties
+
+    ${single_keyword("font-kerning", "auto normal none", damage="rebuild_flow")}
+
+    <%self:longhand name="font-variant-ligatures" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        /// Whether each group of ligatures is explicitly enabled or disabled. `None` leaves the
+        /// font's default in place.
+        #[deriving(PartialEq, Clone, Show)]
+        pub struct SpecifiedValue {
+            pub common: Option<bool>,
+            pub discretionary: Option<bool>,
+            pub historical: Option<bool>,
+            pub contextual: Option<bool>,
+        }
+        pub mod computed_value {
+            pub type T = super::SpecifiedValue;
+            pub static normal: T = super::SpecifiedValue {
+                common: None, discretionary: None, historical: None, contextual: None,
+            };
+            pub static none: T = super::SpecifiedValue {
+                common: Some(false), discretionary: Some(false), historical: Some(false),
+                contextual: Some(false),
+            };
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            normal
+        }
+        /// normal | none | [ <common-lig-values> || <discretionary-lig-values> ||
+        ///                   <historical-lig-values> || <contextual-alt-values> ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "normal" => return Ok(normal),
+                Ok(ref keyword) if keyword.as_slice() == "none" => return Ok(none),
+                _ => {}
+            }
+            let mut result = normal;
+            let mut empty = true;
+            for component_value in input.skip_whitespace() {
+                let keyword = try!(get_ident_lower(component_value));
+                let (group, enabled) = match keyword.as_slice() {
+                    "common-ligatures" => (&mut result.common, true),
+                    "no-common-ligatures" => (&mut result.common, false),
+                    "discretionary-ligatures" => (&mut result.discretionary, true),
+                    "no-discretionary-ligatures" => (&mut result.discretionary, false),
+                    "historical-ligatures" => (&mut result.historical, true),
+                    "no-historical-ligatures" => (&mut result.historical, false),
+                    "contextual" => (&mut result.contextual, true),
+                    "no-contextual" => (&mut result.contextual, false),
+                    _ => return Err(()),
+                };
+                if group.is_some() {
+                    return Err(())
+                }
+                *group = Some(enabled);
+                empty = false;
+            }
+            if !empty { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-variant-numeric" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        #[deriving(PartialEq, Clone, Show)]
+        pub struct SpecifiedValue {
+            pub lining_nums: bool,
+            pub oldstyle_nums: bool,
+            pub proportional_nums: bool,
+            pub tabular_nums: bool,
+            pub diagonal_fractions: bool,
+            pub stacked_fractions: bool,
+            pub ordinal: bool,
+            pub slashed_zero: bool,
+        }
+        pub mod computed_value {
+            pub type T = super::SpecifiedValue;
+            pub static normal: T = super::SpecifiedValue {
+                lining_nums: false, oldstyle_nums: false,
+                proportional_nums: false, tabular_nums: false,
+                diagonal_fractions: false, stacked_fractions: false,
+                ordinal: false, slashed_zero: false,
+            };
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            normal
+        }
+        /// normal | [ <numeric-figure-values> || <numeric-spacing-values> ||
+        ///            <numeric-fraction-values> || ordinal || slashed-zero ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "normal" => return Ok(normal),
+                _ => {}
+            }
+            let mut result = normal;
+            let mut empty = true;
+            for component_value in input.skip_whitespace() {
+                let keyword = try!(get_ident_lower(component_value));
+                // Each keyword comes with the one it is mutually exclusive with, if any.
+                let (value, other) = match keyword.as_slice() {
+                    "lining-nums" => (&mut result.lining_nums, result.oldstyle_nums),
+                    "oldstyle-nums" => (&mut result.oldstyle_nums, result.lining_nums),
+                    "proportional-nums" => (&mut result.proportional_nums, result.tabular_nums),
+                    "tabular-nums" => (&mut result.tabular_nums, result.proportional_nums),
+                    "diagonal-fractions" => {
+                        (&mut result.diagonal_fractions, result.stacked_fractions)
+                    }
+                    "stacked-fractions" => {
+                        (&mut result.stacked_fractions, result.diagonal_fractions)
+                    }
+                    "ordinal" => (&mut result.ordinal, false),
+                    "slashed-zero" => (&mut result.slashed_zero, false),
+                    _ => return Err(()),
+                };
+                if *value || other {
+                    return Err(())
+                }
+                *value = true;
+                empty = false;
+            }
+            if !empty { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-feature-settings" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            /// A single `<feature-tag-value>`: a four-letter OpenType feature tag and the value
+            /// to set it to, where 0 disables the feature.
+            #[deriving(PartialEq, Clone, Show)]
+            pub struct FeatureTagValue {
+                pub tag: String,
+                pub value: u32,
+            }
+            /// `normal` is the empty list.
+            pub type T = Vec<FeatureTagValue>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            vec!()
+        }
+        /// normal | <feature-tag-value>#
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input) {
+                Ok(&Ident(ref keyword)) if keyword.as_slice().eq_ignore_ascii_case("normal") => {
+                    return Ok(vec!())
+                }
+                _ => {}
+            }
+            parse_slice_comma_separated(input, parse_one_feature)
+        }
+        /// <string> [ <integer> | on | off ]?
+        pub fn parse_one_feature<'a>(iter: ParserIter) -> Result<computed_value::FeatureTagValue,
+                                                                 ()> {
+            let tag = match iter.next() {
+                Some(&QuotedString(ref tag)) => tag.clone(),
+                _ => return Err(())
+            };
+            // Tags are exactly four printable ASCII characters.
+            if tag.len() != 4 || tag.as_slice().chars().any(|c| c < '\x20' || c > '\x7e') {
+                return Err(())
+            }
+            let value = match iter.next() {
+                None => 1,
+                Some(&Number(ref value)) => match value.int_value {
+                    Some(value) if value >= 0 && value <= ::std::u32::MAX as i64 => {
+                        value as u32
+                    }
+                    _ => return Err(()),
+                },
+                Some(&Ident(ref keyword)) => {
+                    match keyword.as_slice().to_ascii_lower().as_slice() {
+                        "on" => 1,
+                        "off" => 0,
+                        _ => return Err(()),
+                    }
+                }
+                Some(component_value) => {
+                    iter.push_back(component_value);
+                    1
+                }
+            };
+            Ok(computed_value::FeatureTagValue {
+                tag: tag,
+                value: value,
+            })
+        }
+    </%self:longhand>
+
+    // The language of an element's text, which selects script-specific glyphs when shaping.
+    // It comes from the `lang` attribute, which the style system maps to this property, and
+    // style sheets can't set it.
+    <%self:longhand name="-servo-lang" internal="${True}" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            /// A BCP 47 language tag, like `zh-Hant`, or `None` if the language is unknown.
+            pub type T = Option<String>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            None
+        }
+        /// Never called, since the property can't be declared.
+        pub fn parse(_input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            Err(())
+        }
+        /// Returns the language that a `lang` attribute with the given value specifies.
+        pub fn from_attribute(value: &str) -> SpecifiedValue {
+            let value = value.trim();
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        }
+    </%self:longhand>
+
     // CSS 2.1, Section 16 - Text
 
     ${new_style_struct("InheritedText", is_inherited=True)}
 
     // TODO: initial value should be 'start' (CSS Text Level 3, direction-dependent.)
-    ${single_keyword("text-align", "left right center justify")}
+    ${single_keyword("text-align", "left right center justify", damage="reflow")}
 
     ${new_style_struct("Text", is_inherited=False)}
 
-    <%self:longhand name="text-decoration">
+    // CSS Text Decoration Module Level 3, Section 2
+
+    <%self:longhand name="text-decoration-line" damage="repaint">
         pub use super::computed_as_specified as to_computed_value;
         #[deriving(PartialEq, Clone)]
         pub struct SpecifiedValue {
@@ -949,11 +1509,26 @@ pub mod longhands {
         #[inline] pub fn get_initial_value() -> computed_value::T {
             none
         }
+        /// Adds the line that `keyword` names to `result`, failing if the keyword is unknown or
+        /// repeated. `blink` records whether `blink` has been seen.
+        pub fn add_keyword(result: &mut SpecifiedValue, blink: &mut bool, keyword: &str)
+                           -> Result<(), ()> {
+            let seen = match keyword {
+                "underline" => &mut result.underline,
+                "overline" => &mut result.overline,
+                "line-through" => &mut result.line_through,
+                "blink" => blink,
+                _ => return Err(()),
+            };
+            if *seen {
+                return Err(())
+            }
+            *seen = true;
+            Ok(())
+        }
         /// none | [ underline || overline || line-through || blink ]
         pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
-            let mut result = SpecifiedValue {
-                underline: false, overline: false, line_through: false,
-            };
+            let mut result = none;
             match one_component_value(input) {
                 Ok(&Ident(ref value))
                 if value.as_slice().eq_ignore_ascii_case("none") => return Ok(result),
@@ -962,125 +1537,400 @@ pub mod longhands {
             let mut blink = false;
             let mut empty = true;
             for component_value in input.skip_whitespace() {
-                match get_ident_lower(component_value) {
-                    Err(()) => return Err(()),
-                    Ok(keyword) => match keyword.as_slice() {
-                        "underline" => if result.underline { return Err(()) }
-                                      else { empty = false; result.underline = true },
-                        "overline" => if result.overline { return Err(()) }
-                                      else { empty = false; result.overline = true },
-                        "line-through" => if result.line_through { return Err(()) }
-                                          else { empty = false; result.line_through = true },
-                        "blink" => if blink { return Err(()) }
-                                   else { empty = false; blink = true },
-                        _ => return Err(()),
-                    }
-                }
+                let keyword = try!(get_ident_lower(component_value));
+                try!(add_keyword(&mut result, &mut blink, keyword.as_slice()));
+                empty = false;
             }
             if !empty { Ok(result) } else { Err(()) }
         }
     </%self:longhand>
 
+    ${predefined_type("text-decoration-color", "CSSColor", "CurrentColor", damage="repaint")}
+
+    ${single_keyword("text-decoration-style", "solid double dotted dashed wavy",
+                     damage="repaint")}
+
     ${switch_to_style_struct("InheritedText")}
 
-    <%self:longhand name="-servo-text-decorations-in-effect"
-                    derived_from="display text-decoration">
-        pub use super::computed_as_specified as to_computed_value;
+    <%self:longhand name="-servo-text-decorations-in-effect"
+                    derived_from="display text-decoration-line text-decoration-color
+                                  text-decoration-style"
+                    damage="repaint">
+        pub use super::computed_as_specified as to_computed_value;
+
+        /// The color and style of one decorating line.
+        #[deriving(Clone, PartialEq)]
+        pub struct Line {
+            pub color: RGBA,
+            pub style: text_decoration_style::computed_value::T,
+        }
+
+        #[deriving(Clone, PartialEq)]
+        pub struct SpecifiedValue {
+            pub underline: Option<Line>,
+            pub overline: Option<Line>,
+            pub line_through: Option<Line>,
+        }
+
+        pub mod computed_value {
+            pub type T = super::SpecifiedValue;
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            SpecifiedValue {
+                underline: None,
+                overline: None,
+                line_through: None,
+            }
+        }
+
+        fn maybe(flag: bool, context: &computed::Context) -> Option<Line> {
+            if flag {
+                let color = match context.text_decoration_color {
+                    RGBAColor(rgba) => rgba,
+                    CurrentColor => context.color,
+                };
+                Some(Line {
+                    color: color,
+                    style: context.text_decoration_style,
+                })
+            } else {
+                None
+            }
+        }
+
+        fn derive(context: &computed::Context) -> computed_value::T {
+            // Start with no declarations if this is a block; otherwise, start with the
+            // declarations in effect and add in the text decorations that this inline specifies.
+            let mut result = match context.display {
+                display::computed_value::inline => context.inherited_text_decorations_in_effect,
+                _ => {
+                    SpecifiedValue {
+                        underline: None,
+                        overline: None,
+                        line_through: None,
+                    }
+                }
+            };
+
+            if result.underline.is_none() {
+                result.underline = maybe(context.text_decoration_line.underline, context)
+            }
+            if result.overline.is_none() {
+                result.overline = maybe(context.text_decoration_line.overline, context)
+            }
+            if result.line_through.is_none() {
+                result.line_through = maybe(context.text_decoration_line.line_through, context)
+            }
+
+            result
+        }
+
+        #[inline]
+        pub fn derive_from_text_decoration_line(_: text_decoration_line::computed_value::T,
+                                                context: &computed::Context)
+                                                -> computed_value::T {
+            derive(context)
+        }
+
+        #[inline]
+        pub fn derive_from_text_decoration_color(_: text_decoration_color::computed_value::T,
+                                                 context: &computed::Context)
+                                                 -> computed_value::T {
+            derive(context)
+        }
+
+        #[inline]
+        pub fn derive_from_text_decoration_style(_: text_decoration_style::computed_value::T,
+                                                 context: &computed::Context)
+                                                 -> computed_value::T {
+            derive(context)
+        }
+
+        #[inline]
+        pub fn derive_from_display(_: display::computed_value::T, context: &computed::Context)
+                                   -> computed_value::T {
+            derive(context)
+        }
+    </%self:longhand>
+
+    // Whitespace is processed during flow construction.
+    ${single_keyword("white-space", "normal pre nowrap", damage="rebuild_flow")}
+
+    // Soft hyphens are inserted or removed during flow construction too.
+    ${single_keyword("hyphens", "manual none auto", damage="rebuild_flow")}
+
+    // CSS 2.1, Section 17 - Tables
+    ${new_style_struct("Table", is_inherited=False)}
+
+    ${single_keyword("table-layout", "auto fixed", damage="rebuild_flow")}
+
+    // CSS 2.1, Section 18 - User interface
+
+    ${new_style_struct("Pointing", is_inherited=True)}
+
+    // Changes to the cursor reach the window the next time the mouse moves.
+    <%self:single_component_value name="cursor" damage="repaint">
+        use servo_util::cursor::Cursor;
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            use servo_util::cursor::Cursor;
+            #[deriving(PartialEq, Clone, Show)]
+            pub enum T {
+                AutoCursor,
+                SpecifiedCursor(Cursor),
+            }
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T { AutoCursor }
+        /// auto | <cursor keyword>
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            get_ident_lower(input).and_then(|keyword| {
+                if keyword.as_slice() == "auto" {
+                    Ok(AutoCursor)
+                } else {
+                    Cursor::from_css_keyword(keyword.as_slice()).map(SpecifiedCursor).ok_or(())
+                }
+            })
+        }
+    </%self:single_component_value>
+
+    // Outlines are painted outside the border box and take no space, so none of their properties
+    // affect layout.
+    ${new_style_struct("Outline", is_inherited=False)}
+
+    // TODO: `invert`, which needs support from the renderer.
+    ${predefined_type("outline-color", "CSSColor", "CurrentColor", damage="repaint")}
+
+    <%self:single_component_value name="outline-style" damage="repaint">
+        pub use super::border_top_style::{get_initial_value, to_computed_value};
+        pub type SpecifiedValue = super::border_top_style::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::border_top_style::computed_value::T;
+        }
+        /// The values of `border-style`, except for `hidden`.
+        pub fn from_component_value(input: &ComponentValue, base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match super::border_top_style::from_component_value(input, base_url) {
+                Ok(super::border_top_style::hidden) => Err(()),
+                result => result,
+            }
+        }
+    </%self:single_component_value>
+
+    <%self:longhand name="outline-width" damage="repaint">
+        pub type SpecifiedValue = specified::Length;
+        pub mod computed_value {
+            use super::super::Au;
+            pub type T = Au;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            Au::from_px(3)  // medium
+        }
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Result<SpecifiedValue, ()> {
+            one_component_value(input).and_then(|c| parse_border_width(c, base_url))
+        }
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            if !context.outline_present {
+                Au(0)
+            } else {
+                computed::compute_Au(value, context)
+            }
+        }
+    </%self:longhand>
+
+    // CSS Basic User Interface Module Level 3
+    // http://dev.w3.org/csswg/css-ui/#outline-offset
+    <%self:single_component_value name="outline-offset" damage="repaint">
+        pub type SpecifiedValue = specified::Length;
+        pub mod computed_value {
+            use super::super::Au;
+            pub type T = Au;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { Au(0) }
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            specified::Length::parse(input)
+        }
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            computed::compute_Au(value, context)
+        }
+    </%self:single_component_value>
+
+
+    // CSS Writing Modes Level 3
+    // http://dev.w3.org/csswg/css-writing-modes/
+    ${switch_to_style_struct("InheritedBox")}
+
+    ${single_keyword("writing-mode", "horizontal-tb vertical-rl vertical-lr", experimental=True,
+                     damage="rebuild_flow")}
+
+    // FIXME(SimonSapin): Add 'mixed' and 'upright' (needs vertical text support)
+    // FIXME(SimonSapin): initial (first) value should be 'mixed', when that's implemented
+    ${single_keyword("text-orientation", "sideways sideways-left sideways-right", experimental=True,
+                     damage="rebuild_flow")}
+
+    // CSS Basic User Interface Module Level 3
+    // http://dev.w3.org/csswg/css-ui/
+    ${switch_to_style_struct("Box")}
+
+    ${single_keyword("box-sizing", "content-box border-box", damage="reflow")}
 
-        #[deriving(Clone, PartialEq)]
-        pub struct SpecifiedValue {
-            pub underline: Option<RGBA>,
-            pub overline: Option<RGBA>,
-            pub line_through: Option<RGBA>,
-        }
+    // CSS Image Values and Replaced Content Module Level 3
+    // http://dev.w3.org/csswg/css-images-3/#the-object-fit
+    ${single_keyword("object-fit", "fill contain cover none scale-down", damage="repaint")}
 
+    // `object-position` takes the same values as `background-position`; only the initial value
+    // differs.
+    <%self:longhand name="object-position" damage="repaint">
         pub mod computed_value {
-            pub type T = super::SpecifiedValue;
+            pub use super::super::background_position::computed_value::T;
         }
 
+        pub use super::background_position::{SpecifiedValue, to_computed_value, parse};
+
         #[inline]
         pub fn get_initial_value() -> computed_value::T {
-            SpecifiedValue {
-                underline: None,
-                overline: None,
-                line_through: None,
+            computed_value::T {
+                horizontal: computed::LP_Percentage(0.5),
+                vertical: computed::LP_Percentage(0.5),
             }
         }
+    </%self:longhand>
 
-        fn maybe(flag: bool, context: &computed::Context) -> Option<RGBA> {
-            if flag {
-                Some(context.color)
-            } else {
-                None
+    // CSS Multi-column Layout Module Level 1
+    // http://dev.w3.org/csswg/css-multicol/
+    ${new_style_struct("Column", is_inherited=False)}
+
+    // Whether an element is a multi-column element is decided during flow construction.
+    <%self:single_component_value name="column-width" damage="rebuild_flow">
+        #[deriving(Clone)]
+        pub enum SpecifiedValue {
+            SpecifiedAuto,
+            SpecifiedLength(specified::Length),
+        }
+        pub mod computed_value {
+            use super::super::Au;
+            pub type T = Option<Au>;
+        }
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T { None }
+        /// auto | <length>, which must be positive
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match input {
+                &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("auto")
+                => Ok(SpecifiedAuto),
+                &Dimension(ref value, ref unit) if value.value > 0.
+                => specified::Length::parse_dimension(value.value, unit.as_slice())
+                    .map(SpecifiedLength),
+                _ => Err(()),
             }
         }
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            match value {
+                SpecifiedAuto => None,
+                SpecifiedLength(value) => Some(computed::compute_Au(value, context)),
+            }
+        }
+    </%self:single_component_value>
 
-        fn derive(context: &computed::Context) -> computed_value::T {
-            // Start with no declarations if this is a block; otherwise, start with the
-            // declarations in effect and add in the text decorations that this inline specifies.
-            let mut result = match context.display {
-                display::computed_value::inline => context.inherited_text_decorations_in_effect,
-                _ => {
-                    SpecifiedValue {
-                        underline: None,
-                        overline: None,
-                        line_through: None,
-                    }
-                }
-            };
-
-            if result.underline.is_none() {
-                result.underline = maybe(context.text_decoration.underline, context)
+    <%self:single_component_value name="column-count" damage="rebuild_flow">
+        pub use super::computed_as_specified as to_computed_value;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            pub type T = Option<u32>;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { None }
+        /// auto | <integer>, which must be positive
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match input {
+                &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("auto") => Ok(None),
+                &Number(ref value) => match value.int_value {
+                    Some(count) if count > 0 => Ok(Some(count as u32)),
+                    _ => Err(()),
+                },
+                _ => Err(())
             }
-            if result.overline.is_none() {
-                result.overline = maybe(context.text_decoration.overline, context)
+        }
+    </%self:single_component_value>
+
+    <%self:single_component_value name="column-gap" damage="reflow">
+        #[deriving(Clone)]
+        pub enum SpecifiedValue {
+            SpecifiedNormal,
+            SpecifiedLength(specified::Length),
+        }
+        pub mod computed_value {
+            use super::super::Au;
+            /// `None` is `normal`, which layout treats as 1em.
+            pub type T = Option<Au>;
+        }
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T { None }
+        /// normal | <length>
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match input {
+                &Ident(ref value) if value.as_slice().eq_ignore_ascii_case("normal")
+                => Ok(SpecifiedNormal),
+                _ => specified::Length::parse_non_negative(input).map(SpecifiedLength),
             }
-            if result.line_through.is_none() {
-                result.line_through = maybe(context.text_decoration.line_through, context)
+        }
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            match value {
+                SpecifiedNormal => None,
+                SpecifiedLength(value) => Some(computed::compute_Au(value, context)),
             }
-
-            result
         }
+    </%self:single_component_value>
 
-        #[inline]
-        pub fn derive_from_text_decoration(_: text_decoration::computed_value::T,
-                                           context: &computed::Context)
-                                           -> computed_value::T {
-            derive(context)
+    // Column rules take no space, so none of their properties affect layout.
+    <%self:longhand name="column-rule-style" damage="repaint">
+        pub use super::border_top_style::{get_initial_value, parse, to_computed_value};
+        pub type SpecifiedValue = super::border_top_style::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::border_top_style::computed_value::T;
         }
+    </%self:longhand>
 
+    <%self:longhand name="column-rule-width" damage="repaint">
+        pub type SpecifiedValue = specified::Length;
+        pub mod computed_value {
+            use super::super::Au;
+            pub type T = Au;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            Au::from_px(3)  // medium
+        }
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Result<SpecifiedValue, ()> {
+            one_component_value(input).and_then(|c| parse_border_width(c, base_url))
+        }
         #[inline]
-        pub fn derive_from_display(_: display::computed_value::T, context: &computed::Context)
-                                   -> computed_value::T {
-            derive(context)
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            if !context.column_rule_present {
+                Au(0)
+            } else {
+                computed::compute_Au(value, context)
+            }
         }
     </%self:longhand>
 
-    ${single_keyword("white-space", "normal pre nowrap")}
-
-    // CSS 2.1, Section 17 - Tables
-    ${new_style_struct("Table", is_inherited=False)}
-
-    ${single_keyword("table-layout", "auto fixed")}
-
-    // CSS 2.1, Section 18 - User interface
-
-
-    // CSS Writing Modes Level 3
-    // http://dev.w3.org/csswg/css-writing-modes/
-    ${switch_to_style_struct("InheritedBox")}
-
-    ${single_keyword("writing-mode", "horizontal-tb vertical-rl vertical-lr", experimental=True)}
-
-    // FIXME(SimonSapin): Add 'mixed' and 'upright' (needs vertical text support)
-    // FIXME(SimonSapin): initial (first) value should be 'mixed', when that's implemented
-    ${single_keyword("text-orientation", "sideways sideways-left sideways-right", experimental=True)}
-
-    // CSS Basic User Interface Module Level 3
-    // http://dev.w3.org/csswg/css-ui/
-    ${switch_to_style_struct("Box")}
+    ${predefined_type("column-rule-color", "CSSColor", "CurrentColor", damage="repaint")}
 
-    ${single_keyword("box-sizing", "content-box border-box")}
+    ${single_keyword("column-span", "none all", damage="reflow")}
 }
 
 
@@ -1252,6 +2102,16 @@ pub mod shorthands {
                 }
     </%self:shorthand>
 
+    <%self:shorthand name="overflow" sub_properties="overflow-x overflow-y">
+        one_component_value(input).and_then(|c| overflow_x::from_component_value(c, base_url))
+                                  .map(|value| {
+            Longhands {
+                overflow_x: Some(value),
+                overflow_y: Some(value),
+            }
+        })
+    </%self:shorthand>
+
     ${four_sides_shorthand("margin", "margin-%s", "margin_top::from_component_value")}
     ${four_sides_shorthand("padding", "padding-%s", "padding_top::from_component_value")}
 
@@ -1327,18 +2187,84 @@ pub mod shorthands {
         })
     </%self:shorthand>
 
+    <%self:shorthand name="column-rule" sub_properties="column-rule-color column-rule-style
+                                                        column-rule-width">
+        parse_border(input, base_url).map(|(color, style, width)| {
+            Longhands {
+                column_rule_color: color,
+                column_rule_style: style,
+                column_rule_width: width,
+            }
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="outline" sub_properties="outline-color outline-style outline-width">
+        parse_border(input, base_url).and_then(|(color, style, width)| {
+            // Unlike borders, outlines cannot be `hidden`.
+            if style == Some(border_top_style::hidden) {
+                return Err(())
+            }
+            Ok(Longhands {
+                outline_color: color,
+                outline_style: style,
+                outline_width: width,
+            })
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="columns" sub_properties="column-width column-count">
+        let mut width = None;
+        let mut count = None;
+        let mut nb_autos = 0u;
+        for component_value in input.skip_whitespace() {
+            // `auto` is valid in both, and leaves whichever is not otherwise set at its initial
+            // value.
+            match get_ident_lower(component_value) {
+                Ok(ref ident) if ident.as_slice().eq_ignore_ascii_case("auto") => {
+                    nb_autos += 1;
+                    continue
+                }
+                _ => {}
+            }
+            if width.is_none() {
+                match column_width::from_component_value(component_value, base_url) {
+                    Ok(w) => { width = Some(w); continue },
+                    Err(()) => ()
+                }
+            }
+            if count.is_none() {
+                match column_count::from_component_value(component_value, base_url) {
+                    Ok(c) => { count = Some(c); continue },
+                    Err(()) => ()
+                }
+            }
+            return Err(())
+        }
+        let nb_values = nb_autos + width.iter().count() + count.iter().count();
+        if nb_values == 0 || nb_values > 2 {
+            return Err(())
+        }
+        Ok(Longhands {
+            column_width: width,
+            column_count: count,
+        })
+    </%self:shorthand>
+
     <%self:shorthand name="font" sub_properties="font-style font-variant font-weight
-                                                 font-size line-height font-family">
+                                                 font-stretch font-size line-height font-family
+                                                 font-size-adjust font-kerning
+                                                 font-variant-ligatures font-variant-numeric">
         let mut iter = input.skip_whitespace();
         let mut nb_normals = 0u;
         let mut style = None;
         let mut variant = None;
         let mut weight = None;
+        let mut stretch = None;
         let mut size = None;
         let mut line_height = None;
         for component_value in iter {
             // Special-case 'normal' because it is valid in each of
-            // font-style, font-weight and font-variant.
+            // font-style, font-weight, font-variant and font-stretch.
             // Leaves the values to None, 'normal' is the initial value for each of them.
             match get_ident_lower(component_value) {
                 Ok(ref ident) if ident.as_slice().eq_ignore_ascii_case("normal") => {
@@ -1365,6 +2291,12 @@ pub mod shorthands {
                     Err(()) => ()
                 }
             }
+            if stretch.is_none() {
+                match font_stretch::from_component_value(component_value, base_url) {
+                    Ok(s) => { stretch = Some(s); continue },
+                    Err(()) => ()
+                }
+            }
             match font_size::from_component_value(component_value, base_url) {
                 Ok(s) => { size = Some(s); break },
                 Err(()) => return Err(())
@@ -1377,7 +2309,9 @@ pub mod shorthands {
                 &None => 0,
             }
         }
-        if size.is_none() || (count(&style) + count(&weight) + count(&variant) + nb_normals) > 3 {
+        if size.is_none() ||
+                (count(&style) + count(&weight) + count(&variant) + count(&stretch) +
+                 nb_normals) > 4 {
             return Err(())
         }
         let mut copied_iter = iter.clone();
@@ -1398,9 +2332,59 @@ pub mod shorthands {
             font_style: style,
             font_variant: variant,
             font_weight: weight,
+            font_stretch: stretch,
             font_size: size,
             line_height: line_height,
-            font_family: Some(family)
+            font_family: Some(family),
+            // Not settable by the shorthand, but reset by it.
+            font_size_adjust: None,
+            font_kerning: None,
+            font_variant_ligatures: None,
+            font_variant_numeric: None,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="text-decoration" sub_properties="text-decoration-line
+                                                            text-decoration-color
+                                                            text-decoration-style">
+        let mut line = None;
+        let mut color = None;
+        let mut style = None;
+        let mut none = false;
+        let mut blink = false;
+        for component_value in input.skip_whitespace() {
+            if color.is_none() {
+                match specified::CSSColor::parse(component_value) {
+                    Ok(c) => { color = Some(c); continue },
+                    Err(()) => ()
+                }
+            }
+            if style.is_none() {
+                match text_decoration_style::from_component_value(component_value, base_url) {
+                    Ok(s) => { style = Some(s); continue },
+                    Err(()) => ()
+                }
+            }
+            let keyword = try!(get_ident_lower(component_value));
+            let mut result = line.unwrap_or(text_decoration_line::computed_value::none);
+            if keyword.as_slice() == "none" {
+                // `none` is only valid on its own, as the whole of the line value.
+                if line.is_some() { return Err(()) }
+                none = true;
+            } else {
+                if none { return Err(()) }
+                try!(text_decoration_line::add_keyword(&mut result, &mut blink,
+                                                       keyword.as_slice()));
+            }
+            line = Some(result);
+        }
+        if line.is_none() && color.is_none() && style.is_none() {
+            return Err(())
+        }
+        Ok(Longhands {
+            text_decoration_line: line,
+            text_decoration_color: color,
+            text_decoration_style: style,
         })
     </%self:shorthand>
 
@@ -1569,7 +2553,7 @@ impl PropertyDeclaration {
         let name_lower = name.as_slice().to_ascii_lower();
         match name_lower.as_slice() {
             % for property in LONGHANDS:
-                % if property.derived_from is None:
+                % if property.derived_from is None and not property.internal:
                     "${property.name}" => {
                         % if property.experimental:
                             if !::servo_util::opts::experimental_enabled() {
@@ -1678,6 +2662,46 @@ pub struct ComputedValues {
     pub writing_mode: WritingMode,
 }
 
+/// Two styles are equal if all of their computed values are; whether they can be shared doesn't
+/// matter.
+impl PartialEq for ComputedValues {
+    fn eq(&self, other: &ComputedValues) -> bool {
+        % for style_struct in STYLE_STRUCTS:
+            *self.${style_struct.ident} == *other.${style_struct.ident} &&
+        % endfor
+        self.writing_mode == other.writing_mode
+    }
+}
+
+/// The kinds of layout work that a change to the computed value of a property can call for. Every
+/// longhand has one of these, given by the `damage` argument of its definition.
+#[deriving(PartialEq, Eq, Show)]
+pub enum DamageClass {
+    % for damage in DAMAGE_CLASSES:
+        ${"".join(word.capitalize() for word in damage.split("_"))}DamageClass,
+    % endfor
+}
+
+impl ComputedValues {
+    /// Returns true if the computed value of any longhand of the given damage class differs
+    /// between this style and `other`.
+    pub fn differs_in(&self, other: &ComputedValues, damage_class: DamageClass) -> bool {
+        match damage_class {
+            % for damage in DAMAGE_CLASSES:
+                ${"".join(word.capitalize() for word in damage.split("_"))}DamageClass => {
+                    % for property in LONGHANDS:
+                        % if property.damage == damage:
+                            self.${property.style_struct.ident}.${property.ident} !=
+                                other.${property.style_struct.ident}.${property.ident} ||
+                        % endif
+                    % endfor
+                    false
+                }
+            % endfor
+        }
+    }
+}
+
 impl ComputedValues {
     /// Resolves the currentColor keyword.
     /// Any color value form computed values (except for the 'color' property itself)
@@ -1840,6 +2864,131 @@ fn initial_writing_mode_is_empty() {
     assert_eq!(get_writing_mode(INITIAL_VALUES.get_inheritedbox()), WritingMode::empty())
 }
 
+#[test]
+fn test_generic_keyword_in_font_family_name() {
+    use self::longhands::font_family::computed_value::{FamilyName, Monospace, Serif};
+
+    let base_url = Url::parse("http://example.com/").unwrap();
+    let component_values = |css: &str| -> Vec<ComponentValue> {
+        tokenize(css).map(|(c, _)| c).collect()
+    };
+    let parse_family = |css: &str| {
+        longhands::font_family::parse(component_values(css).as_slice(), &base_url)
+    };
+    assert_eq!(parse_family("monospace"), Ok(vec![Monospace]));
+    assert_eq!(parse_family("Monospace, serif"), Ok(vec![Monospace, Serif]));
+    assert_eq!(parse_family("Monospace Bold, serif"),
+               Ok(vec![FamilyName("Monospace Bold".to_string()), Serif]));
+    assert_eq!(parse_family("'monospace'"), Ok(vec![FamilyName("monospace".to_string())]));
+
+    // `bold` before the size is the weight, and after it part of the family name.
+    let font = shorthands::font::parse(component_values("bold 13px Monospace").as_slice(),
+                                       &base_url).unwrap();
+    assert!(match font.font_weight {
+        Some(longhands::font_weight::SpecifiedWeight700) => true,
+        _ => false,
+    });
+    assert_eq!(font.font_family, Some(vec![Monospace]));
+    let font = shorthands::font::parse(component_values("13px Monospace Bold").as_slice(),
+                                       &base_url).unwrap();
+    assert!(font.font_weight.is_none());
+    assert_eq!(font.font_family, Some(vec![FamilyName("Monospace Bold".to_string())]));
+}
+
+#[test]
+fn test_parse_font_variant_ligatures() {
+    use self::longhands::font_variant_ligatures::{SpecifiedValue, parse};
+    use self::longhands::font_variant_ligatures::computed_value::{none, normal};
+
+    let base_url = Url::parse("http://example.com/").unwrap();
+    let parse_css = |css: &str| {
+        let component_values: Vec<ComponentValue> = tokenize(css).map(|(c, _)| c).collect();
+        parse(component_values.as_slice(), &base_url)
+    };
+    assert_eq!(parse_css("normal"), Ok(normal));
+    assert_eq!(parse_css("NONE"), Ok(none));
+    assert_eq!(parse_css("no-common-ligatures discretionary-ligatures"),
+               Ok(SpecifiedValue {
+                   common: Some(false),
+                   discretionary: Some(true),
+                   historical: None,
+                   contextual: None,
+               }));
+    assert_eq!(parse_css("contextual historical-ligatures"),
+               Ok(SpecifiedValue {
+                   common: None,
+                   discretionary: None,
+                   historical: Some(true),
+                   contextual: Some(true),
+               }));
+    // Each group can only be given once, and the keywords don't combine with others.
+    assert_eq!(parse_css("common-ligatures no-common-ligatures"), Err(()));
+    assert_eq!(parse_css("none contextual"), Err(()));
+    assert_eq!(parse_css(""), Err(()));
+}
+
+#[test]
+fn test_parse_font_variant_numeric() {
+    use self::longhands::font_variant_numeric::{SpecifiedValue, parse};
+    use self::longhands::font_variant_numeric::computed_value::normal;
+
+    let base_url = Url::parse("http://example.com/").unwrap();
+    let parse_css = |css: &str| {
+        let component_values: Vec<ComponentValue> = tokenize(css).map(|(c, _)| c).collect();
+        parse(component_values.as_slice(), &base_url)
+    };
+    assert_eq!(parse_css("normal"), Ok(normal));
+    assert_eq!(parse_css("oldstyle-nums tabular-nums slashed-zero"),
+               Ok(SpecifiedValue {
+                   oldstyle_nums: true,
+                   tabular_nums: true,
+                   slashed_zero: true,
+                   ..normal
+               }));
+    assert_eq!(parse_css("ordinal diagonal-fractions"),
+               Ok(SpecifiedValue { ordinal: true, diagonal_fractions: true, ..normal }));
+    // Figures, spacings and fractions are each mutually exclusive.
+    assert_eq!(parse_css("lining-nums oldstyle-nums"), Err(()));
+    assert_eq!(parse_css("tabular-nums proportional-nums"), Err(()));
+    assert_eq!(parse_css("stacked-fractions diagonal-fractions"), Err(()));
+    assert_eq!(parse_css("ordinal ordinal"), Err(()));
+    assert_eq!(parse_css("normal ordinal"), Err(()));
+}
+
+#[test]
+fn test_parse_font_feature_settings() {
+    use self::longhands::font_feature_settings::parse;
+    use self::longhands::font_feature_settings::computed_value::FeatureTagValue;
+
+    let base_url = Url::parse("http://example.com/").unwrap();
+    let parse_css = |css: &str| {
+        let component_values: Vec<ComponentValue> = tokenize(css).map(|(c, _)| c).collect();
+        parse(component_values.as_slice(), &base_url)
+    };
+    let feature = |tag: &str, value: u32| FeatureTagValue { tag: tag.to_string(), value: value };
+    assert_eq!(parse_css("normal"), Ok(vec!()));
+    assert_eq!(parse_css("\"liga\" 0, \"kern\", \"ss01\" 2, \"smcp\" on, \"tnum\" OFF"),
+               Ok(vec!(feature("liga", 0), feature("kern", 1), feature("ss01", 2),
+                       feature("smcp", 1), feature("tnum", 0))));
+    // Tags are four printable ASCII characters, and values are non-negative integers.
+    assert_eq!(parse_css("\"lig\""), Err(()));
+    assert_eq!(parse_css("\"ligat\""), Err(()));
+    assert_eq!(parse_css("liga"), Err(()));
+    assert_eq!(parse_css("\"liga\" -1"), Err(()));
+    assert_eq!(parse_css("\"liga\" 1.5"), Err(()));
+    assert_eq!(parse_css("\"liga\" maybe"), Err(()));
+    assert_eq!(parse_css("\"liga\" 1 2"), Err(()));
+}
+
+#[test]
+fn test_internal_properties_cannot_be_declared() {
+    let base_url = Url::parse("http://example.com/").unwrap();
+    let declarations = parse_style_attribute("-servo-lang: \"en\"; color: red", &base_url);
+    assert_eq!(declarations.normal.len(), 1);
+    let declarations = parse_style_attribute("-servo-lang: \"en\" !important", &base_url);
+    assert!(declarations.important.is_empty());
+}
+
 
 /// This only exists to limit the scope of #[allow(experimental)]
 /// FIXME: remove this when Arc::make_unique() is not experimental anymore.
@@ -1935,6 +3084,11 @@ fn cascade_with_cached_declarations(applicable_declarations: &[DeclarationBlock]
         }
     }
 
+    // The font size may change with the font family even if it is not declared.
+    if style_font.font_size != context.font_size {
+        style_font.make_unique_experimental().font_size = context.font_size;
+    }
+
     ComputedValues {
         writing_mode: get_writing_mode(&*style_inheritedbox),
         % for style_struct in STYLE_STRUCTS:
@@ -1982,15 +3136,22 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
                 inherited_style.get_inheritedtext()._servo_text_decorations_in_effect,
             // To be overridden by applicable declarations:
             font_size: inherited_font_style.font_size,
+            font_size_keyword: inherited_font_style._servo_font_size_keyword,
             display: longhands::display::get_initial_value(),
             color: inherited_style.get_color().color,
-            text_decoration: longhands::text_decoration::get_initial_value(),
+            text_decoration_line: longhands::text_decoration_line::get_initial_value(),
+            text_decoration_color: longhands::text_decoration_color::get_initial_value(),
+            text_decoration_style: longhands::text_decoration_style::get_initial_value(),
             positioned: false,
             floated: false,
+            overflow_x: longhands::overflow_x::get_initial_value(),
+            overflow_y: longhands::overflow_y::get_initial_value(),
             border_top_present: false,
             border_right_present: false,
             border_bottom_present: false,
             border_left_present: false,
+            column_rule_present: false,
+            outline_present: false,
         }
     };
 
@@ -2005,6 +3166,11 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         };
     )
 
+    let inherited_is_only_monospace =
+        longhands::font_family::is_only_monospace(&inherited_style.get_font().font_family);
+    let mut is_only_monospace = inherited_is_only_monospace;
+    let mut font_size_declaration = None;
+
     // Initialize `context`
     // Declarations blocks are already stored in increasing precedence order.
     for sub_list in applicable_declarations.iter() {
@@ -2012,11 +3178,16 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         for declaration in sub_list.declarations.iter().rev() {
             match *declaration {
                 FontSizeDeclaration(ref value) => {
-                    context.font_size = match *value {
-                        SpecifiedValue(specified_value) => computed::compute_Au_with_font_size(
-                            specified_value, context.inherited_font_size),
-                        Initial => longhands::font_size::get_initial_value(),
-                        Inherit => context.inherited_font_size,
+                    // Computed below, once the font family is known.
+                    font_size_declaration = Some(value);
+                }
+                FontFamilyDeclaration(ref value) => {
+                    is_only_monospace = match *value {
+                        SpecifiedValue(ref families) => {
+                            longhands::font_family::is_only_monospace(families)
+                        }
+                        Initial => false,
+                        Inherit => inherited_is_only_monospace,
                     }
                 }
                 ColorDeclaration(ref value) => {
@@ -2035,8 +3206,23 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
                     context.floated = get_specified!(get_box, float, value)
                                       != longhands::float::none;
                 }
-                TextDecorationDeclaration(ref value) => {
-                    context.text_decoration = get_specified!(get_text, text_decoration, value);
+                TextDecorationLineDeclaration(ref value) => {
+                    context.text_decoration_line =
+                        get_specified!(get_text, text_decoration_line, value);
+                }
+                TextDecorationColorDeclaration(ref value) => {
+                    context.text_decoration_color =
+                        get_specified!(get_text, text_decoration_color, value);
+                }
+                TextDecorationStyleDeclaration(ref value) => {
+                    context.text_decoration_style =
+                        get_specified!(get_text, text_decoration_style, value);
+                }
+                OverflowXDeclaration(ref value) => {
+                    context.overflow_x = get_specified!(get_box, overflow_x, value);
+                }
+                OverflowYDeclaration(ref value) => {
+                    context.overflow_y = get_specified!(get_box, overflow_y, value);
                 }
                 % for side in ["top", "right", "bottom", "left"]:
                     Border${side.capitalize()}StyleDeclaration(ref value) => {
@@ -2048,11 +3234,58 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
                         };
                     }
                 % endfor
+                ColumnRuleStyleDeclaration(ref value) => {
+                    context.column_rule_present =
+                    match get_specified!(get_column, column_rule_style, value) {
+                        longhands::border_top_style::none |
+                        longhands::border_top_style::hidden => false,
+                        _ => true,
+                    };
+                }
+                OutlineStyleDeclaration(ref value) => {
+                    context.outline_present =
+                    match get_specified!(get_outline, outline_style, value) {
+                        longhands::border_top_style::none => false,
+                        _ => true,
+                    };
+                }
                 _ => {}
             }
         }
     }
 
+    // Sizes given as `<absolute-size>` keywords, or inherited from one, are relative to `medium`,
+    // which is smaller when `monospace` is the only family.
+    {
+        let medium = longhands::font_size::medium(is_only_monospace);
+        let (font_size, font_size_keyword) = match font_size_declaration {
+            Some(&SpecifiedValue(longhands::font_size::SpecifiedKeyword(ratio))) => {
+                (medium.scale_by(ratio), Some(ratio))
+            }
+            Some(&SpecifiedValue(longhands::font_size::SpecifiedLength(length))) => {
+                match (length, context.font_size_keyword) {
+                    (specified::Em(ems), Some(ratio)) => {
+                        (medium.scale_by(ratio * ems), Some(ratio * ems))
+                    }
+                    _ => {
+                        (computed::compute_Au_with_font_size(length, context.inherited_font_size),
+                         None)
+                    }
+                }
+            }
+            Some(&Initial) => (medium, longhands::_servo_font_size_keyword::get_initial_value()),
+            Some(&Inherit) => (context.inherited_font_size, context.font_size_keyword),
+            None => {
+                match context.font_size_keyword {
+                    Some(ratio) => (medium.scale_by(ratio), Some(ratio)),
+                    None => (context.inherited_font_size, None),
+                }
+            }
+        };
+        context.font_size = font_size;
+        context.font_size_keyword = font_size_keyword;
+    }
+
     match (cached_style, parent_style) {
         (Some(cached_style), Some(parent_style)) => {
             return (cascade_with_cached_declarations(applicable_declarations,
@@ -2135,7 +3368,8 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         }
     }
 
-    // The initial value of border-*-width may be changed at computed value time.
+    // The initial values of border-*-width, column-rule-width and outline-width may be changed at
+    // computed value time.
     {
         let border = style_border.make_unique_experimental();
         % for side in ["top", "right", "bottom", "left"]:
@@ -2145,6 +3379,17 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
             }
         % endfor
     }
+    if !context.column_rule_present {
+        style_column.make_unique_experimental().column_rule_width = Au(0);
+    }
+    if !context.outline_present {
+        style_outline.make_unique_experimental().outline_width = Au(0);
+    }
+
+    // The font size may change with the font family even if it is not declared.
+    if style_font.font_size != context.font_size {
+        style_font.make_unique_experimental().font_size = context.font_size;
+    }
 
     // The initial value of display may be changed at computed value time.
     if !seen.get_display() {
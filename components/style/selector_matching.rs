@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use geom::side_offsets::SideOffsets2D;
 use std::collections::hashmap::HashMap;
 use std::hash::Hash;
 use std::num::div_rem;
@@ -14,11 +15,13 @@ use servo_util::smallvec::VecLike;
 use servo_util::sort;
 use string_cache::Atom;
 
-use media_queries::{Device, Screen};
+use media_queries::Device;
 use node::{TElement, TNode};
-use properties::{PropertyDeclaration, PropertyDeclarationBlock};
+use properties::{PropertyDeclaration, PropertyDeclarationBlock, ServoLangDeclaration};
+use properties::{SpecifiedValue, longhands};
 use selectors::*;
-use stylesheets::{Stylesheet, iter_stylesheet_style_rules};
+use page::PageRule;
+use stylesheets::{Stylesheet, iter_page_rules, iter_stylesheet_style_rules};
 
 pub enum StylesheetOrigin {
     UserAgentOrigin,
@@ -267,16 +270,34 @@ pub struct Stylist {
     before_map: PerPseudoElementSelectorMap,
     after_map: PerPseudoElementSelectorMap,
     rules_source_order: uint,
+
+    /// The device that media queries are evaluated against.
+    device: Device,
+
+    /// The `@page` rules of every stylesheet, in document order.
+    page_rules: Vec<PageRule>,
+
+    /// Whether any selector has a `+` or `~` combinator, which makes the style of an element
+    /// depend on the siblings before it.
+    has_sibling_combinators: bool,
+
+    /// Whether any selector has a structural pseudo-class such as `:first-child`, which makes the
+    /// style of an element depend on where it is among its siblings.
+    has_structural_pseudo_classes: bool,
 }
 
 impl Stylist {
     #[inline]
-    pub fn new() -> Stylist {
+    pub fn new(device: Device) -> Stylist {
         let mut stylist = Stylist {
             element_map: PerPseudoElementSelectorMap::new(),
             before_map: PerPseudoElementSelectorMap::new(),
             after_map: PerPseudoElementSelectorMap::new(),
             rules_source_order: 0u,
+            device: device,
+            page_rules: vec!(),
+            has_sibling_combinators: false,
+            has_structural_pseudo_classes: false,
         };
         let ua_stylesheet = Stylesheet::from_bytes(
             include_bin!("user-agent.css"),
@@ -306,6 +327,8 @@ impl Stylist {
             ),
         };
         let mut rules_source_order = self.rules_source_order;
+        let mut has_sibling_combinators = self.has_sibling_combinators;
+        let mut has_structural_pseudo_classes = self.has_structural_pseudo_classes;
 
         // Take apart the StyleRule into individual Rules and insert
         // them into the SelectorMap of that priority.
@@ -313,6 +336,9 @@ impl Stylist {
             ($style_rule: ident, $priority: ident) => {
                 if $style_rule.declarations.$priority.len() > 0 {
                     for selector in $style_rule.selectors.iter() {
+                        note_sibling_dependencies(&*selector.compound_selectors,
+                                                  &mut has_sibling_combinators,
+                                                  &mut has_structural_pseudo_classes);
                         let map = match selector.pseudo_element {
                             None => &mut element_map,
                             Some(Before) => &mut before_map,
@@ -331,13 +357,63 @@ impl Stylist {
             };
         );
 
-        let device = &Device { media_type: Screen };  // TODO, use Print when printing
-        iter_stylesheet_style_rules(&stylesheet, device, |style_rule| {
+        iter_stylesheet_style_rules(&stylesheet, &self.device, |style_rule| {
             append!(style_rule, normal);
             append!(style_rule, important);
             rules_source_order += 1;
         });
         self.rules_source_order = rules_source_order;
+        self.has_sibling_combinators = has_sibling_combinators;
+        self.has_structural_pseudo_classes = has_structural_pseudo_classes;
+
+        let page_rules = &mut self.page_rules;
+        iter_page_rules(&stylesheet, &self.device, |page_rule| page_rules.push(page_rule.clone()));
+    }
+
+    /// Returns true if some selector makes the style of an element depend on the siblings before
+    /// it, so that restyling an element may mean restyling the siblings after it.
+    #[inline]
+    pub fn has_sibling_combinators(&self) -> bool {
+        self.has_sibling_combinators
+    }
+
+    /// Returns true if some selector makes the style of an element depend on its position among
+    /// its siblings, so that adding or removing an element may mean restyling its siblings.
+    #[inline]
+    pub fn has_structural_pseudo_classes(&self) -> bool {
+        self.has_structural_pseudo_classes
+    }
+
+    #[inline]
+    pub fn device<'a>(&'a self) -> &'a Device {
+        &self.device
+    }
+
+    /// Returns the descriptors that apply to the page box, cascading all `@page` rules so that
+    /// later ones win.
+    pub fn page_rule(&self) -> PageRule {
+        let mut result = PageRule {
+            size: None,
+            margin: SideOffsets2D::new(None, None, None, None),
+        };
+        for page_rule in self.page_rules.iter() {
+            if page_rule.size.is_some() {
+                result.size = page_rule.size.clone()
+            }
+            if page_rule.margin.top.is_some() {
+                result.margin.top = page_rule.margin.top
+            }
+            if page_rule.margin.right.is_some() {
+                result.margin.right = page_rule.margin.right
+            }
+            if page_rule.margin.bottom.is_some() {
+                result.margin.bottom = page_rule.margin.bottom
+            }
+            if page_rule.margin.left.is_some() {
+                result.margin.left = page_rule.margin.left
+            }
+        }
+        result
     }
 
     /// Returns the applicable CSS declarations for the given element. This corresponds to
@@ -375,6 +451,22 @@ impl Stylist {
                                                      applicable_declarations,
                                                      &mut shareable);
         map.user.normal.get_all_matching_rules(element, parent_bf, applicable_declarations, &mut shareable);
+
+        // Presentational hints from attributes come before author rules, as if they started the
+        // author style sheet with zero specificity.
+        if pseudo_element.is_none() {
+            match element.as_element().get_attr(&ns!(""), "lang") {
+                Some(lang) => {
+                    shareable = false;
+                    let language = longhands::_servo_lang::from_attribute(lang);
+                    let hint = vec!(ServoLangDeclaration(SpecifiedValue(language)));
+                    applicable_declarations.vec_push(DeclarationBlock::from_declarations(
+                            Arc::new(hint)))
+                }
+                None => {}
+            }
+        }
+
         map.author.normal.get_all_matching_rules(element, parent_bf, applicable_declarations, &mut shareable);
 
         // Step 2: Normal style attributes.
@@ -470,6 +562,37 @@ impl DeclarationBlock {
     }
 }
 
+/// Records whether the given selector has a sibling combinator and whether it has a structural
+/// pseudo-class. See `Stylist::has_sibling_combinators()`.
+fn note_sibling_dependencies(selector: &CompoundSelector,
+                             has_sibling_combinators: &mut bool,
+                             has_structural_pseudo_classes: &mut bool) {
+    fn is_structural(simple_selector: &SimpleSelector) -> bool {
+        match *simple_selector {
+            FirstChild | LastChild | OnlyChild | NthChild(..) | NthLastChild(..) |
+            NthOfType(..) | NthLastOfType(..) | FirstOfType | LastOfType | OnlyOfType => true,
+            Negation(ref negated) => negated.iter().any(is_structural),
+            _ => false,
+        }
+    }
+
+    if selector.simple_selectors.iter().any(is_structural) {
+        *has_structural_pseudo_classes = true
+    }
+    match selector.next {
+        None => {}
+        Some((ref next, combinator)) => {
+            match combinator {
+                NextSibling | LaterSibling => *has_sibling_combinators = true,
+                Child | Descendant => {}
+            }
+            note_sibling_dependencies(&**next,
+                                      has_sibling_combinators,
+                                      has_structural_pseudo_classes)
+        }
+    }
+}
+
 pub fn matches<'a, E:TElement<'a>, N:TNode<'a, E>>(selector_list: &SelectorList, element: &N, parent_bf: &Option<BloomFilter>) -> bool {
     get_selector_list_selectors(selector_list).iter().any(|selector|
         selector.pseudo_element.is_none() &&
@@ -558,6 +681,12 @@ fn can_fast_reject<'a, E: TElement<'a>, N: TNode<'a, E>>(
         return Some(NotMatchedAndRestartFromClosestLaterSibling);
     }
 
+    // Whether the rest of the selector matches depends on other elements now. This has to be
+    // noted before the bloom filter gets a chance to reject it.
+    if depends_on_ids_or_states_of_other_elements(selector) {
+        *shareable = false;
+    }
+
     let bf: &BloomFilter =
         match *parent_bf {
             None => return None,
@@ -609,6 +738,29 @@ fn can_fast_reject<'a, E: TElement<'a>, N: TNode<'a, E>>(
     return None;
 }
 
+/// Returns true if any compound selector after the first one has an ID selector or a state
+/// pseudo-class. Style sharing candidates are keyed on their own ID and states, but not on those of
+/// their ancestors and siblings.
+fn depends_on_ids_or_states_of_other_elements(mut selector: &CompoundSelector) -> bool {
+    fn is_id_or_state(simple_selector: &SimpleSelector) -> bool {
+        match *simple_selector {
+            IDSelector(..) | Hover | Disabled | Enabled => true,
+            Negation(ref negated) => negated.iter().any(is_id_or_state),
+            _ => false,
+        }
+    }
+
+    loop {
+        match selector.next {
+            None => return false,
+            Some((ref next_selector, _)) => selector = &**next_selector,
+        }
+        if selector.simple_selectors.iter().any(is_id_or_state) {
+            return true
+        }
+    }
+}
+
 fn matches_compound_selector_internal<'a,
                                       E:TElement<'a>,
                                       N:TNode<'a, E>>(
@@ -625,6 +777,10 @@ fn matches_compound_selector_internal<'a,
     match selector.next {
         None => Matched,
         Some((ref next_selector, combinator)) => {
+            // Style sharing candidates aren't keyed on their siblings.
+            if combinator == NextSibling || combinator == LaterSibling {
+                *shareable = false;
+            }
             let (siblings, candidate_not_found) = match combinator {
                 Child => (false, NotMatchedGlobally),
                 Descendant => (false, NotMatchedGlobally),
@@ -705,8 +861,10 @@ pub fn matches_simple_selector<'a, E:TElement<'a>,
         }
         // TODO: case-sensitivity depends on the document type and quirks mode
         // TODO: cache and intern IDs on elements.
+        //
+        // Style sharing candidates are keyed on their ID, so this doesn't make the style
+        // unshareable. If this is an ancestor or a sibling, `can_fast_reject` has done that.
         IDSelector(ref id) => {
-            *shareable = false;
             let element = element.as_element();
             element.get_id().map_or(false, |attr| {
                 attr == *id
@@ -786,20 +944,19 @@ pub fn matches_simple_selector<'a, E:TElement<'a>,
             }
         }
 
+        // Style sharing candidates are keyed on their states, so these don't make the style
+        // unshareable. If this is an ancestor or a sibling, `can_fast_reject` has done that.
         Hover => {
-            *shareable = false;
             let elem = element.as_element();
             elem.get_hover_state()
         },
         // http://www.whatwg.org/html/#selector-disabled
         Disabled => {
-            *shareable = false;
             let elem = element.as_element();
             elem.get_disabled_state()
         },
         // http://www.whatwg.org/html/#selector-enabled
         Enabled => {
-            *shareable = false;
             let elem = element.as_element();
             elem.get_enabled_state()
         },
@@ -997,7 +1154,8 @@ impl<K: Eq + Hash, V> FindPush<K, V> for HashMap<K, Vec<V>> {
 #[cfg(test)]
 mod tests {
     use sync::Arc;
-    use super::{DeclarationBlock, Rule, SelectorMap};
+    use super::{DeclarationBlock, Rule, SelectorMap, depends_on_ids_or_states_of_other_elements};
+    use super::note_sibling_dependencies;
     use selectors::LocalName;
     use string_cache::Atom;
 
@@ -1072,4 +1230,36 @@ mod tests {
         assert_eq!(0, selector_map.class_hash.find(&Atom::from_slice("intro")).unwrap()[0].declarations.source_order);
         assert!(selector_map.class_hash.find(&Atom::from_slice("foo")).is_none());
     }
+
+    #[test]
+    fn test_note_sibling_dependencies() {
+        let check = |selector: &str| {
+            let rules_list = get_mock_rules([selector]);
+            let (mut sibling_combinators, mut structural_pseudo_classes) = (false, false);
+            note_sibling_dependencies(&*rules_list[0][0].selector,
+                                      &mut sibling_combinators,
+                                      &mut structural_pseudo_classes);
+            (sibling_combinators, structural_pseudo_classes)
+        };
+        assert_eq!(check("div > p .a:hover"), (false, false));
+        assert_eq!(check("h1 + p"), (true, false));
+        assert_eq!(check("h1 ~ p span"), (true, false));
+        assert_eq!(check("li:first-child"), (false, true));
+        assert_eq!(check("li:not(:nth-child(2n)) a"), (false, true));
+        assert_eq!(check("ul:last-child > li + li"), (true, true));
+    }
+
+    #[test]
+    fn test_depends_on_ids_or_states_of_other_elements() {
+        let check = |selector: &str| {
+            let rules_list = get_mock_rules([selector]);
+            depends_on_ids_or_states_of_other_elements(&*rules_list[0][0].selector)
+        };
+        assert!(!check("#a"));
+        assert!(!check("div p:hover"));
+        assert!(check("#a p"));
+        assert!(check("a:hover > span"));
+        assert!(check("input:not(:disabled) + label"));
+        assert!(!check("div.a ~ p"));
+    }
 }
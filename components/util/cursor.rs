@@ -0,0 +1,80 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The mouse cursors defined by CSS 3 UI § 8.1.1, shared by style, layout and the windowing
+//! layer.
+
+use std::ascii::StrAsciiExt;
+
+macro_rules! define_cursor {
+    ($( $css: tt => $variant: ident, )+) => {
+        #[deriving(Clone, PartialEq, Eq, Show)]
+        pub enum Cursor {
+            $( $variant, )+
+        }
+
+        impl Cursor {
+            /// Returns the cursor named by the given CSS keyword, ignoring case. `auto` is not a
+            /// cursor and yields `None`.
+            pub fn from_css_keyword(keyword: &str) -> Option<Cursor> {
+                match keyword.to_ascii_lower().as_slice() {
+                    $( $css => Some($variant), )+
+                    _ => None,
+                }
+            }
+
+            /// Returns the CSS keyword that names this cursor.
+            pub fn to_css_keyword(&self) -> &'static str {
+                match *self {
+                    $( $variant => $css, )+
+                }
+            }
+        }
+    }
+}
+
+define_cursor! {
+    "none" => NoCursor,
+    "default" => DefaultCursor,
+    "pointer" => PointerCursor,
+    "context-menu" => ContextMenuCursor,
+    "help" => HelpCursor,
+    "progress" => ProgressCursor,
+    "wait" => WaitCursor,
+    "cell" => CellCursor,
+    "crosshair" => CrosshairCursor,
+    "text" => TextCursor,
+    "vertical-text" => VerticalTextCursor,
+    "alias" => AliasCursor,
+    "copy" => CopyCursor,
+    "move" => MoveCursor,
+    "no-drop" => NoDropCursor,
+    "not-allowed" => NotAllowedCursor,
+    "grab" => GrabCursor,
+    "grabbing" => GrabbingCursor,
+    "e-resize" => EResizeCursor,
+    "n-resize" => NResizeCursor,
+    "ne-resize" => NeResizeCursor,
+    "nw-resize" => NwResizeCursor,
+    "s-resize" => SResizeCursor,
+    "se-resize" => SeResizeCursor,
+    "sw-resize" => SwResizeCursor,
+    "w-resize" => WResizeCursor,
+    "ew-resize" => EwResizeCursor,
+    "ns-resize" => NsResizeCursor,
+    "nesw-resize" => NeswResizeCursor,
+    "nwse-resize" => NwseResizeCursor,
+    "col-resize" => ColResizeCursor,
+    "row-resize" => RowResizeCursor,
+    "all-scroll" => AllScrollCursor,
+    "zoom-in" => ZoomInCursor,
+    "zoom-out" => ZoomOutCursor,
+}
+
+#[test]
+fn css_keywords_round_trip() {
+    assert_eq!(Cursor::from_css_keyword("Pointer"), Some(PointerCursor));
+    assert_eq!(Cursor::from_css_keyword("auto"), None);
+    assert_eq!(NwseResizeCursor.to_css_keyword(), "nwse-resize");
+}
@@ -34,6 +34,7 @@ extern crate string_cache_macros;
 
 pub mod bloom;
 pub mod cache;
+pub mod cursor;
 pub mod debug_utils;
 pub mod fnv;
 pub mod geometry;
@@ -42,6 +43,7 @@ pub mod memory;
 pub mod namespace;
 pub mod opts;
 pub mod range;
+pub mod resource_files;
 pub mod smallvec;
 pub mod sort;
 pub mod str;
@@ -12,11 +12,30 @@ use std::mem::size_of;
 #[cfg(target_os="linux")]
 use std::os::page_size;
 use std::ptr::null_mut;
+use std::sync::atomics::{AtomicUint, INIT_ATOMIC_UINT, SeqCst};
 use std::time::duration::Duration;
 use task::spawn_named;
 #[cfg(target_os="macos")]
 use task_info::task_basic_info::{virtual_size,resident_size};
 
+/// The number of bytes that the pixels of decoded images take up.
+static mut DECODED_IMAGE_BYTES: AtomicUint = INIT_ATOMIC_UINT;
+
+/// Records that the pixels of a decoded image were allocated, so that the memory profiler can
+/// report them.
+pub fn record_decoded_image_allocation(nbytes: uint) {
+    unsafe {
+        DECODED_IMAGE_BYTES.fetch_add(nbytes, SeqCst);
+    }
+}
+
+/// Records that the pixels of a decoded image were freed.
+pub fn record_decoded_image_deallocation(nbytes: uint) {
+    unsafe {
+        DECODED_IMAGE_BYTES.fetch_sub(nbytes, SeqCst);
+    }
+}
+
 pub struct MemoryProfilerChan(pub Sender<MemoryProfilerMsg>);
 
 impl MemoryProfilerChan {
@@ -139,6 +158,9 @@ impl MemoryProfiler {
         // |stats.active|. This does not include inactive chunks.
         MemoryProfiler::print_measurement("heap-mapped",    get_jemalloc_stat("stats.mapped"));
 
+        // Bytes taken up by the pixels of decoded images, which are part of |heap-allocated|.
+        MemoryProfiler::print_measurement("decoded-images", get_decoded_image_bytes());
+
         println!("");
     }
 }
@@ -160,6 +182,13 @@ fn get_jemalloc_stat(name: &'static str) -> Option<u64> {
     if rv == 0 { Some(old as u64) } else { None }
 }
 
+fn get_decoded_image_bytes() -> Option<u64> {
+    let nbytes = unsafe {
+        DECODED_IMAGE_BYTES.load(SeqCst)
+    };
+    Some(nbytes as u64)
+}
+
 // Like std::macros::try!, but for Option<>.
 macro_rules! option_try(
     ($e:expr) => (match $e { Some(e) => e, None => return None })
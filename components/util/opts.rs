@@ -13,6 +13,7 @@ use geom::scale_factor::ScaleFactor;
 use geom::size::TypedSize2D;
 use layers::geometry::DevicePixel;
 use getopts;
+use std::ascii::StrAsciiExt;
 use std::cmp;
 use std::io;
 use std::os;
@@ -27,9 +28,8 @@ pub struct Opts {
     /// The rendering backend to use (`-r`).
     pub render_backend: BackendType,
 
-    /// How many threads to use for CPU rendering (`-t`).
-    ///
-    /// FIXME(pcwalton): This is not currently used. All rendering is sequential.
+    /// How many threads to use for CPU rendering (`-t`). Tiles are rasterized in parallel when
+    /// this is more than one; GPU rendering is always sequential.
     pub n_render_threads: uint,
 
     /// True to use CPU painting, false to use GPU painting via Skia-GL (`-c`). Note that
@@ -62,6 +62,11 @@ pub struct Opts {
     pub exit_after_load: bool,
 
     pub output_file: Option<String>,
+
+    /// True if the document is laid out on pages for print media and written to the output file
+    /// as a PDF, rather than laid out on one continuous canvas (`--paginate`).
+    pub paginate: bool,
+
     pub headless: bool,
     pub hard_fail: bool,
 
@@ -85,11 +90,27 @@ pub struct Opts {
     /// and render.
     pub trace_layout: bool,
 
+    /// True if every incremental reflow should be checked against a layout of the whole document
+    /// from scratch, failing if they differ (`--verify-incremental-layout`).
+    pub verify_incremental_layout: bool,
+
+    /// True if the display list should be printed as JSON after each reflow
+    /// (`--dump-display-list`).
+    pub dump_display_list: bool,
+
+    /// The frame to paint every animated image at instead of playing them, so that painting is
+    /// deterministic (`--animation-frame`).
+    pub animation_frame: Option<uint>,
+
     /// True if we should start a server to listen to remote Firefox devtools connections.
     pub devtools_server: bool,
 
     /// The initial requested size of the window.
     pub initial_window_size: TypedSize2D<ScreenPx, uint>,
+
+    /// Font families to use for generic families instead of the ones fontconfig picks, as pairs
+    /// of generic family and family name (`--font-family`).
+    pub generic_font_families: Vec<(String, String)>,
 }
 
 fn print_usage(app: &str, opts: &[getopts::OptGroup]) {
@@ -121,11 +142,18 @@ pub fn from_cmdline_args(args: &[String]) -> Option<Opts> {
         getopts::optflag("z", "headless", "Headless mode"),
         getopts::optflag("f", "hard-fail", "Exit on task failure instead of displaying about:failure"),
         getopts::optflag("b", "bubble-widths", "Bubble intrinsic widths separately like other engines"),
+        getopts::optflag("", "paginate",
+                         "Lay the document out on pages and write them to the output file as PDF."),
         getopts::optflag("", "show-debug-borders", "Show debugging borders on layers and tiles."),
         getopts::optflag("", "disable-text-aa", "Disable antialiasing for text rendering."),
         getopts::optflag("", "trace-layout", "Write layout trace to external file for debugging."),
+        getopts::optflag("", "verify-incremental-layout", "Check each incremental reflow against a full one."),
+        getopts::optflag("", "dump-display-list", "Print the display list as JSON after each reflow."),
+        getopts::optopt("", "animation-frame", "Show this frame of animated images instead of playing them.", "0"),
         getopts::optflag("", "devtools", "Start remote devtools server"),
         getopts::optopt("", "resolution", "Set window resolution.", "800x600"),
+        getopts::optmulti("", "font-family", "Font family to use for a generic family",
+                          "monospace=DejaVu Sans Mono"),
         getopts::optflag("h", "help", "Print this message")
     );
 
@@ -207,6 +235,13 @@ pub fn from_cmdline_args(args: &[String]) -> Option<Opts> {
         bubble_inline_sizes_separately = true;
     }
 
+    let output_file = opt_match.opt_str("o");
+    let paginate = opt_match.opt_present("paginate");
+    if paginate && output_file.is_none() {
+        args_fail("--paginate needs an output file to write the PDF to (-o)");
+        return None;
+    }
+
     let initial_window_size = match opt_match.opt_str("resolution") {
         Some(res_string) => {
             let res: Vec<uint> = res_string.as_slice().split('x').map(|r| from_str(r).unwrap()).collect();
@@ -217,6 +252,17 @@ pub fn from_cmdline_args(args: &[String]) -> Option<Opts> {
         }
     };
 
+    let mut generic_font_families = vec!();
+    for font_family_str in opt_match.opt_strs("font-family").iter() {
+        let parts: Vec<&str> = font_family_str.as_slice().splitn(1, '=').collect();
+        if parts.len() != 2 {
+            args_fail(format!("font families must be given as generic=family, not {}",
+                              font_family_str).as_slice());
+            return None;
+        }
+        generic_font_families.push((parts[0].trim().to_ascii_lower(), parts[1].trim().to_string()));
+    }
+
     Some(Opts {
         urls: urls,
         render_backend: render_backend,
@@ -229,15 +275,22 @@ pub fn from_cmdline_args(args: &[String]) -> Option<Opts> {
         enable_experimental: opt_match.opt_present("e"),
         layout_threads: layout_threads,
         exit_after_load: opt_match.opt_present("x"),
-        output_file: opt_match.opt_str("o"),
+        output_file: output_file,
+        paginate: paginate,
         headless: opt_match.opt_present("z"),
         hard_fail: opt_match.opt_present("f"),
         bubble_inline_sizes_separately: bubble_inline_sizes_separately,
         show_debug_borders: opt_match.opt_present("show-debug-borders"),
         enable_text_antialiasing: !opt_match.opt_present("disable-text-aa"),
         trace_layout: trace_layout,
+        verify_incremental_layout: opt_match.opt_present("verify-incremental-layout"),
+        dump_display_list: opt_match.opt_present("dump-display-list"),
+        animation_frame: opt_match.opt_str("animation-frame").map(|frame| {
+            from_str(frame.as_slice()).unwrap()
+        }),
         devtools_server: opt_match.opt_present("devtools"),
         initial_window_size: initial_window_size,
+        generic_font_families: generic_font_families,
     })
 }
 
@@ -254,3 +307,19 @@ pub fn experimental_enabled() -> bool {
         EXPERIMENTAL_ENABLED
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::from_cmdline_args;
+
+    #[test]
+    fn test_font_family_overrides() {
+        let args: Vec<String> = ["servo", "--font-family", "Monospace = DejaVu Sans Mono",
+                                 "--font-family", "serif=Fira Sans", "about:blank"]
+            .iter().map(|arg| arg.to_string()).collect();
+        let opts = from_cmdline_args(args.as_slice()).unwrap();
+        assert_eq!(opts.generic_font_families,
+                   vec!(("monospace".to_string(), "DejaVu Sans Mono".to_string()),
+                        ("serif".to_string(), "Fira Sans".to_string())));
+    }
+}
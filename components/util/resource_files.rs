@@ -0,0 +1,44 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Access to the data files that Servo reads at run time, which live in the `resources` directory
+//! at the top of the source tree.
+
+use std::io::{File, IoError, IoResult, FileNotFound};
+use std::io::fs::PathExtensions;
+use std::os;
+
+/// Returns the `resources` directory. Servo and its tests are run from executables at various
+/// depths of the source tree, so this looks in the directory of the executable and then in each
+/// of its ancestors.
+pub fn resources_dir_path() -> Option<Path> {
+    let mut directory = match os::self_exe_path() {
+        Some(directory) => directory,
+        None => return None,
+    };
+    loop {
+        let path = directory.join("resources");
+        if path.is_dir() {
+            return Some(path)
+        }
+        if !directory.pop() {
+            return None
+        }
+    }
+}
+
+/// Reads the file at the given path, relative to the `resources` directory.
+pub fn read_resource_file(relative_path: &str) -> IoResult<Vec<u8>> {
+    let path = match resources_dir_path() {
+        Some(directory) => directory.join(relative_path),
+        None => {
+            return Err(IoError {
+                kind: FileNotFound,
+                desc: "couldn't find the resources directory",
+                detail: None,
+            })
+        }
+    };
+    File::open(&path).read_to_end()
+}
@@ -56,6 +56,9 @@ impl Formatable for Option<TimerMetadata> {
 pub enum TimeProfilerMsg {
     /// Normal message used for reporting time
     TimeMsg((TimeProfilerCategory, Option<TimerMetadata>), f64),
+    /// Message used for reporting how often a cache was hit: the number of hits and the number of
+    /// lookups
+    CacheMsg((TimeProfilerCacheCategory, Option<TimerMetadata>), uint, uint),
     /// Message used to force print the profiling metrics
     PrintMsg,
     /// Tells the profiler to shut down.
@@ -114,12 +117,35 @@ impl Formatable for TimeProfilerCategory {
     }
 }
 
+/// The caches whose hit rates are reported to the profiler.
+#[repr(u32)]
+#[deriving(PartialEq, Clone, PartialOrd, Eq, Ord)]
+pub enum TimeProfilerCacheCategory {
+    StyleSharingCacheCategory,
+    ComputedValuesCacheCategory,
+}
+
+impl Formatable for TimeProfilerCacheCategory {
+    fn format(&self) -> String {
+        let name = match *self {
+            StyleSharingCacheCategory => "Style Sharing",
+            ComputedValuesCacheCategory => "Computed Values Deduplication",
+        };
+        name.to_string()
+    }
+}
+
 type TimeProfilerBuckets = TreeMap<(TimeProfilerCategory, Option<TimerMetadata>), Vec<f64>>;
 
+/// The total number of hits and lookups of each cache.
+type TimeProfilerCacheBuckets = TreeMap<(TimeProfilerCacheCategory, Option<TimerMetadata>),
+                                        (uint, uint)>;
+
 // back end of the profiler that handles data aggregation and performance metrics
 pub struct TimeProfiler {
     pub port: Receiver<TimeProfilerMsg>,
     buckets: TimeProfilerBuckets,
+    cache_buckets: TimeProfilerCacheBuckets,
     pub last_msg: Option<TimeProfilerMsg>,
 }
 
@@ -164,6 +190,7 @@ impl TimeProfiler {
         TimeProfiler {
             port: port,
             buckets: TreeMap::new(),
+            cache_buckets: TreeMap::new(),
             last_msg: None,
         }
     }
@@ -191,12 +218,29 @@ impl TimeProfiler {
         self.buckets.insert(k, vec!(t));
     }
 
+    fn add_cache_hits(&mut self,
+                      k: (TimeProfilerCacheCategory, Option<TimerMetadata>),
+                      hits: uint,
+                      lookups: uint) {
+        match self.cache_buckets.find_mut(&k) {
+            None => {},
+            Some(&(ref mut total_hits, ref mut total_lookups)) => {
+                *total_hits += hits;
+                *total_lookups += lookups;
+                return;
+            }
+        }
+
+        self.cache_buckets.insert(k, (hits, lookups));
+    }
+
     fn handle_msg(&mut self, msg: TimeProfilerMsg) -> bool {
         match msg.clone() {
             TimeMsg(k, t) => self.find_or_insert(k, t),
+            CacheMsg(k, hits, lookups) => self.add_cache_hits(k, hits, lookups),
             PrintMsg => match self.last_msg {
                 // only print if more data has arrived since the last printout
-                Some(TimeMsg(..)) => self.print_buckets(),
+                Some(TimeMsg(..)) | Some(CacheMsg(..)) => self.print_buckets(),
                 _ => ()
             },
             ExitMsg => return false,
@@ -230,9 +274,36 @@ impl TimeProfiler {
             }
         }
         println!("");
+
+        if self.cache_buckets.is_empty() {
+            return
+        }
+        println!("{:35s} {:14} {:9} {:30} {:-15s} {:-15s} {:-15s}",
+                 "_cache_", "_incremental?_", "_iframe?_",
+                 "            _url_", "         _hits_", "      _lookups_",
+                 " _hit rate (%)_");
+        for (&(ref category, ref meta), &(hits, lookups)) in self.cache_buckets.iter() {
+            let hit_rate = if lookups == 0 {
+                0f64
+            } else {
+                (hits as f64) * 100f64 / (lookups as f64)
+            };
+            println!("{:-35s}{} {:15u} {:15u} {:15.2f}",
+                     category.format(), meta.format(), hits, lookups, hit_rate);
+        }
+        println!("");
     }
 }
 
+fn timer_metadata(meta: Option<(&Url, bool, bool)>) -> Option<TimerMetadata> {
+    meta.map(|(url, iframe, first_reflow)|
+        TimerMetadata {
+            url: url.serialize(),
+            iframe: iframe,
+            first_reflow: first_reflow,
+        })
+}
+
 
 pub fn profile<T>(category: TimeProfilerCategory,
                   // url, iframe?, first reflow?
@@ -244,16 +315,21 @@ pub fn profile<T>(category: TimeProfilerCategory,
     let val = callback();
     let end_time = precise_time_ns();
     let ms = (end_time - start_time) as f64 / 1000000f64;
-    let meta = meta.map(|(url, iframe, first_reflow)|
-        TimerMetadata {
-            url: url.serialize(),
-            iframe: iframe,
-            first_reflow: first_reflow,
-        });
+    let meta = timer_metadata(meta);
     time_profiler_chan.send(TimeMsg((category, meta), ms));
     return val;
 }
 
+/// Reports that a cache was hit `hits` times out of `lookups`.
+pub fn profile_cache(category: TimeProfilerCacheCategory,
+                     // url, iframe?, first reflow?
+                     meta: Option<(&Url, bool, bool)>,
+                     time_profiler_chan: TimeProfilerChan,
+                     hits: uint,
+                     lookups: uint) {
+    time_profiler_chan.send(CacheMsg((category, timer_metadata(meta)), hits, lookups));
+}
+
 pub fn time<T>(msg: &str, callback: || -> T) -> T{
     let start_time = precise_time_ns();
     let val = callback();
@@ -62,14 +62,19 @@ pub extern "C" fn cef_run_message_loop() {
         //layout_threads: cmp::max(rt::default_sched_threads() * 3 / 4, 1),
         exit_after_load: false,
         output_file: None,
+        paginate: false,
         headless: false,
         hard_fail: false,
         bubble_inline_sizes_separately: false,
         show_debug_borders: false,
         enable_text_antialiasing: true,
         trace_layout: false,
+        verify_incremental_layout: false,
+        dump_display_list: false,
+        animation_frame: None,
         devtools_server: false,
         initial_window_size: TypedSize2D(800, 600),
+        generic_font_families: vec!(),
     };
     native::start(0, 0 as *const *const u8, proc() {
        servo::run(opts);
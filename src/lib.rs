@@ -119,7 +119,8 @@ pub fn run(opts: opts::Opts) {
             } else {
                 ImageCacheTask::new(resource_task.clone())
             };
-        let font_cache_task = FontCacheTask::new(resource_task.clone());
+        let font_cache_task = FontCacheTask::new(resource_task.clone(),
+                                                 opts.generic_font_families.clone());
         let constellation_chan = Constellation::<layout::layout_task::LayoutTask,
                                                  script::script_task::ScriptTask>::start(
                                                       compositor_chan,
@@ -0,0 +1,64 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bindings to the decoder of the Brotli reference implementation, built from the sources in
+//! `brotli/`.
+
+#![crate_name = "brotli"]
+#![crate_type = "rlib"]
+
+#![comment = "The Servo Parallel Browser Project"]
+#![license = "MPL"]
+
+extern crate libc;
+
+use libc::{c_int, size_t};
+
+#[link(name = "brotlidec", kind = "static")]
+extern {
+    fn BrotliDecoderDecompress(encoded_size: size_t,
+                               encoded_buffer: *const u8,
+                               decoded_size: *mut size_t,
+                               decoded_buffer: *mut u8)
+                               -> c_int;
+}
+
+static BROTLI_DECODER_RESULT_SUCCESS: c_int = 1;
+
+/// Decompresses the given Brotli stream, which must decompress to exactly `decompressed_length`
+/// bytes. The caller is responsible for keeping `decompressed_length` reasonable, since that much
+/// is allocated up front.
+pub fn decompress(compressed: &[u8], decompressed_length: uint) -> Option<Vec<u8>> {
+    let mut data = Vec::from_elem(decompressed_length, 0u8);
+    let mut length = decompressed_length as size_t;
+    let result = unsafe {
+        BrotliDecoderDecompress(compressed.len() as size_t,
+                                compressed.as_ptr(),
+                                &mut length,
+                                data.as_mut_ptr())
+    };
+    if result != BROTLI_DECODER_RESULT_SUCCESS || length as uint != decompressed_length {
+        return None
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decompress;
+
+    #[test]
+    fn test_decompress() {
+        // "hello" as a single uncompressed meta-block.
+        let compressed = [0x0b, 0x02, 0x80, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x03];
+        assert_eq!(decompress(compressed, 5), Some(b"hello".to_vec()));
+        assert_eq!(decompress(compressed, 4), None);
+        assert_eq!(decompress(compressed.slice_to(4), 5), None);
+    }
+}
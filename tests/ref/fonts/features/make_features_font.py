@@ -0,0 +1,207 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+"""Writes features.ttf, a font of rectangles for testing OpenType features.
+
+Run with no arguments from this directory. See README for the glyphs.
+"""
+
+import struct
+
+UNITS_PER_EM = 1000
+ASCENT = 800
+DESCENT = 200
+
+# Name, advance width, rectangle height. Rectangles sit on the baseline and fill the advance.
+GLYPHS = [
+    (".notdef", 500, 0),
+    ("space", 500, 0),
+    ("f", 400, 800),
+    ("i", 400, 400),
+    ("f_i", 800, 200),
+    ("one", 300, 800),
+    ("one.tnum", 600, 800),
+    ("a", 600, 600),
+    ("a.sc", 600, 300),
+]
+
+GLYPH_IDS = dict((name, index) for index, (name, _, _) in enumerate(GLYPHS))
+
+CHARACTERS = [
+    (0x20, "space"),
+    (0x31, "one"),
+    (0x61, "a"),
+    (0x66, "f"),
+    (0x69, "i"),
+    # The substituted glyphs also have characters of their own, for the reference pages.
+    (0xE000, "f_i"),
+    (0xE001, "one.tnum"),
+    (0xE002, "a.sc"),
+]
+
+
+def glyf_and_loca():
+    glyf = b""
+    offsets = []
+    for _, width, height in GLYPHS:
+        offsets.append(len(glyf))
+        if height == 0:
+            continue
+        glyf += struct.pack(">hhhhh", 1, 0, 0, width, height)
+        glyf += struct.pack(">HH", 3, 0)
+        glyf += struct.pack(">BBBB", 1, 1, 1, 1)
+        glyf += struct.pack(">hhhh", 0, 0, width, 0)
+        glyf += struct.pack(">hhhh", 0, height, 0, -height)
+        glyf += b"\0" * (-len(glyf) % 4)
+    offsets.append(len(glyf))
+    return glyf, struct.pack(">%dI" % len(offsets), *offsets)
+
+
+def cmap():
+    segments = [(code, code, GLYPH_IDS[name]) for code, name in CHARACTERS]
+    segments.append((0xFFFF, 0xFFFF, 0))
+    seg_count = len(segments)
+    search_range = 2
+    entry_selector = 0
+    while search_range * 2 <= seg_count * 2:
+        search_range *= 2
+        entry_selector += 1
+    subtable = struct.pack(">%dH" % seg_count, *[end for _, end, _ in segments])
+    subtable += struct.pack(">H", 0)
+    subtable += struct.pack(">%dH" % seg_count, *[start for start, _, _ in segments])
+    subtable += struct.pack(">%dH" % seg_count,
+                            *[(glyph - start) % 0x10000 if glyph else 1
+                              for start, _, glyph in segments])
+    subtable += struct.pack(">%dH" % seg_count, *([0] * seg_count))
+    header = struct.pack(">HHHHHHH", 4, 14 + len(subtable), 0, seg_count * 2, search_range,
+                         entry_selector, seg_count * 2 - search_range)
+    return struct.pack(">HHHHI", 0, 1, 3, 1, 12) + header + subtable
+
+
+def coverage(glyph):
+    return struct.pack(">HHH", 1, 1, glyph)
+
+
+def single_substitution(glyph, substitute):
+    return struct.pack(">HHHH", 2, 8, 1, GLYPH_IDS[substitute]) + coverage(GLYPH_IDS[glyph])
+
+
+def ligature_substitution(first, second, ligature):
+    ligature_table = struct.pack(">HHH", GLYPH_IDS[ligature], 2, GLYPH_IDS[second])
+    ligature_set = struct.pack(">HH", 1, 4) + ligature_table
+    return (struct.pack(">HHHH", 1, 8, 1, 8 + 6) + coverage(GLYPH_IDS[first]) + ligature_set)
+
+
+def gsub():
+    # Features, sorted by tag, each with the lookup that implements it.
+    features = [
+        (b"liga", 4, ligature_substitution("f", "i", "f_i")),
+        (b"smcp", 1, single_substitution("a", "a.sc")),
+        (b"tnum", 1, single_substitution("one", "one.tnum")),
+    ]
+
+    lang_sys = struct.pack(">HHH", 0, 0xFFFF, len(features))
+    lang_sys += struct.pack(">%dH" % len(features), *range(len(features)))
+    script = struct.pack(">HH", 4, 0) + lang_sys
+    script_list = struct.pack(">H", 2)
+    script_list += struct.pack(">4sH", b"DFLT", 2 + 2 * 6)
+    script_list += struct.pack(">4sH", b"latn", 2 + 2 * 6 + len(script))
+    script_list += script + script
+
+    feature_list = struct.pack(">H", len(features))
+    for index, (tag, _, _) in enumerate(features):
+        feature_list += struct.pack(">4sH", tag, 2 + 6 * len(features) + 6 * index)
+    for index in range(len(features)):
+        feature_list += struct.pack(">HHH", 0, 1, index)
+
+    lookup_list = struct.pack(">H", len(features))
+    lookups = b""
+    for lookup_type, subtable in [(lookup_type, subtable)
+                                  for _, lookup_type, subtable in features]:
+        lookup_list += struct.pack(">H", 2 + 2 * len(features) + len(lookups))
+        lookups += struct.pack(">HHHH", lookup_type, 0, 1, 8) + subtable
+    lookup_list += lookups
+
+    header_size = 10
+    return (struct.pack(">IHHH", 0x00010000, header_size, header_size + len(script_list),
+                        header_size + len(script_list) + len(feature_list)) +
+            script_list + feature_list + lookup_list)
+
+
+def name():
+    names = [
+        (1, "Features"),
+        (2, "Regular"),
+        (4, "Features Regular"),
+        (6, "Features-Regular"),
+    ]
+    records = b""
+    strings = b""
+    for name_id, string in names:
+        encoded = string.encode("utf-16-be")
+        records += struct.pack(">HHHHHH", 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+    return struct.pack(">HHH", 0, len(names), 6 + len(records)) + records + strings
+
+
+def os2():
+    return struct.pack(">HhHHH", 3, 500, 400, 5, 0) + \
+        struct.pack(">10h", 650, 600, 0, 75, 650, 600, 0, 350, 50, 300) + \
+        struct.pack(">h10s4I4sHHH", 0, b"\0" * 10, 1, 0, 0, 0, b"NONE", 0x40, 0x20, 0xE002) + \
+        struct.pack(">hhhHH2I", ASCENT, -DESCENT, 0, ASCENT, DESCENT, 1, 0) + \
+        struct.pack(">hhHHH", 400, 800, 0, 0x20, 2)
+
+
+def checksum(data):
+    data += b"\0" * (-len(data) % 4)
+    return sum(struct.unpack(">%dI" % (len(data) // 4), data)) & 0xFFFFFFFF
+
+
+def font():
+    glyf, loca = glyf_and_loca()
+    max_width = max(width for _, width, _ in GLYPHS)
+    tables = {
+        b"GSUB": gsub(),
+        b"OS/2": os2(),
+        b"cmap": cmap(),
+        b"glyf": glyf,
+        b"head": struct.pack(">IIIIHH8s8shhhhHHhhh", 0x00010000, 0x00010000, 0, 0x5F0F3CF5,
+                             0x000B, UNITS_PER_EM, b"\0" * 8, b"\0" * 8, 0, 0, max_width,
+                             ASCENT, 0, 8, 2, 1, 0),
+        b"hhea": struct.pack(">IhhhHhhhhhh4hhH", 0x00010000, ASCENT, -DESCENT, 0, max_width,
+                             0, 0, max_width, 1, 0, 0, 0, 0, 0, 0, 0, len(GLYPHS)),
+        b"hmtx": b"".join(struct.pack(">Hh", width, 0) for _, width, _ in GLYPHS),
+        b"loca": loca,
+        b"maxp": struct.pack(">IHHHHHHHHHHHHHH", 0x00010000, len(GLYPHS), 4, 1, 0, 0, 2,
+                             0, 0, 0, 0, 0, 0, 0, 0),
+        b"name": name(),
+        b"post": struct.pack(">IIhhIIIII", 0x00030000, 0, -100, 50, 0, 0, 0, 0, 0),
+    }
+
+    tags = sorted(tables.keys())
+    search_range = 1
+    entry_selector = 0
+    while search_range * 2 <= len(tags):
+        search_range *= 2
+        entry_selector += 1
+    header = struct.pack(">IHHHH", 0x00010000, len(tags), search_range * 16, entry_selector,
+                         len(tags) * 16 - search_range * 16)
+    offset = len(header) + 16 * len(tags)
+    records = b""
+    data = b""
+    for tag in tags:
+        table = tables[tag]
+        records += struct.pack(">4sIII", tag, checksum(table), offset + len(data), len(table))
+        data += table + b"\0" * (-len(table) % 4)
+    result = header + records + data
+
+    # The head table's checksum adjustment makes the whole font sum to a magic number.
+    head_offset = offset + data.index(tables[b"head"])
+    adjustment = (0xB1B0AFBA - checksum(result)) & 0xFFFFFFFF
+    return result[:head_offset + 8] + struct.pack(">I", adjustment) + result[head_offset + 12:]
+
+
+if __name__ == "__main__":
+    with open("features.ttf", "wb") as output:
+        output.write(font())
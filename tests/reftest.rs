@@ -120,6 +120,8 @@ struct Reftest {
     is_flaky: bool,
     experimental: bool,
     fragment_identifier: Option<String>,
+    /// The frame that animated images show, so that screenshots do not depend on timing.
+    animation_frame: uint,
 }
 
 struct TestLine<'a> {
@@ -172,6 +174,7 @@ fn parse_lists(file: &Path, servo_args: &[String], render_mode: RenderMode, id_o
         let mut flakiness = RenderMode::empty();
         let mut experimental = false;
         let mut fragment_identifier = None;
+        let mut animation_frame = 0;
         for condition in conditions_list {
             match condition {
                 "flaky_cpu" => flakiness.insert(CpuRendering),
@@ -184,6 +187,10 @@ fn parse_lists(file: &Path, servo_args: &[String], render_mode: RenderMode, id_o
             if condition.starts_with("fragment=") {
                 fragment_identifier = Some(condition.slice_from("fragment=".len()).to_string());
             }
+            if condition.starts_with("animation_frame=") {
+                animation_frame = from_str(condition.slice_from("animation_frame=".len()))
+                    .expect("reftest line: animation_frame must be a frame number");
+            }
         }
 
         let reftest = Reftest {
@@ -196,6 +203,7 @@ fn parse_lists(file: &Path, servo_args: &[String], render_mode: RenderMode, id_o
             is_flaky: render_mode.intersects(flakiness),
             experimental: experimental,
             fragment_identifier: fragment_identifier,
+            animation_frame: animation_frame,
         };
 
         tests.push(make_test(reftest));
@@ -224,6 +232,8 @@ fn capture(reftest: &Reftest, side: uint) -> (u32, u32, Vec<u8>) {
         .args(reftest.servo_args.as_slice())
         // Allows pixel perfect rendering of Ahem font for reftests.
         .arg("--disable-text-aa")
+        .arg("--animation-frame")
+        .arg(reftest.animation_frame.to_string())
         .args(["-f", "-o"])
         .arg(png_filename.as_slice())
         .arg({